@@ -2,18 +2,23 @@
 extern crate criterion;
 extern crate redis;
 
+extern crate bytes;
 extern crate futures;
 extern crate tokio;
 
 #[path = "../tests/support/mod.rs"]
 mod support;
 
+use bytes::BytesMut;
+
 use futures::{future, stream, Future, Stream};
 
+use tokio::codec::Decoder;
 use tokio::runtime::current_thread::Runtime;
 
 use criterion::{Bencher, Benchmark, Criterion, Throughput};
 
+use redis::resp::{ReplyCodec, ValueCodec};
 use redis::{PipelineCommands, Value};
 
 fn get_client() -> redis::Client {
@@ -135,11 +140,11 @@ fn bench_async_long_pipeline(b: &mut Bencher) {
     });
 }
 
-fn bench_shared_async_long_pipeline(b: &mut Bencher) {
+fn bench_multiplexed_async_long_pipeline(b: &mut Bencher) {
     let client = get_client();
     let mut runtime = Runtime::new().unwrap();
     let con = runtime
-        .block_on(client.get_shared_async_connection())
+        .block_on(client.get_multiplexed_async_connection())
         .unwrap();
 
     let pipe = long_pipeline();
@@ -153,11 +158,32 @@ fn bench_shared_async_long_pipeline(b: &mut Bencher) {
     });
 }
 
-fn bench_shared_async_implicit_pipeline(b: &mut Bencher) {
+#[cfg(feature = "with-io-uring")]
+fn bench_uring_async_long_pipeline(b: &mut Bencher) {
+    use redis::IntoConnectionInfo;
+
+    let info = "redis://127.0.0.1:6379".into_connection_info().unwrap();
+    let mut runtime = Runtime::new().unwrap();
+    let mut con = Some(runtime.block_on(redis::uring::connect(info)).unwrap());
+
+    let pipe = long_pipeline();
+
+    b.iter(|| {
+        con = runtime
+            .block_on(future::lazy(|| {
+                pipe.clone()
+                    .query_async(con.take().expect("Connection"))
+                    .map(|(con, ())| Some(con))
+            }))
+            .unwrap();
+    });
+}
+
+fn bench_multiplexed_async_implicit_pipeline(b: &mut Bencher) {
     let client = get_client();
     let mut runtime = Runtime::new().unwrap();
     let con = runtime
-        .block_on(client.get_shared_async_connection())
+        .block_on(client.get_multiplexed_async_connection())
         .unwrap();
 
     let cmds: Vec<_> = (0..PIPELINE_QUERIES)
@@ -190,17 +216,23 @@ fn bench_query(c: &mut Criterion) {
     c.bench(
         "query_pipeline",
         Benchmark::new(
-            "shared_async_implicit_pipeline",
-            bench_shared_async_implicit_pipeline,
+            "multiplexed_async_implicit_pipeline",
+            bench_multiplexed_async_implicit_pipeline,
         )
         .with_function(
-            "shared_async_long_pipeline",
-            bench_shared_async_long_pipeline,
+            "multiplexed_async_long_pipeline",
+            bench_multiplexed_async_long_pipeline,
         )
         .with_function("async_long_pipeline", bench_async_long_pipeline)
         .with_function("long_pipeline", bench_long_pipeline)
         .throughput(Throughput::Elements(PIPELINE_QUERIES as u32)),
     );
+    #[cfg(feature = "with-io-uring")]
+    c.bench(
+        "query_pipeline",
+        Benchmark::new("uring_async_long_pipeline", bench_uring_async_long_pipeline)
+            .throughput(Throughput::Elements(PIPELINE_QUERIES as u32)),
+    );
 }
 
 fn bench_encode_small(b: &mut Bencher) {
@@ -276,8 +308,72 @@ fn bench_decode_simple(b: &mut Bencher) {
     support::encode_value(&value, &mut input).unwrap();
     b.iter(|| redis::parse_redis_value(&input).unwrap());
 }
+
+// An `XREAD` reply of 1000 entries with a few fields each.
+fn stream_batch() -> Vec<u8> {
+    let entries = (0..1000)
+        .map(|i| {
+            Value::Bulk(vec![
+                Value::Data(format!("1518951480106-{}", i).into_bytes()),
+                Value::Bulk(vec![
+                    Value::Data(b"sensor".to_vec()),
+                    Value::Data(format!("sensor-{}", i % 16).into_bytes()),
+                    Value::Data(b"payload".to_vec()),
+                    Value::Data(vec![b'x'; 256]),
+                ]),
+            ])
+        })
+        .collect();
+    let value = Value::Bulk(vec![Value::Bulk(vec![
+        Value::Data(b"mystream".to_vec()),
+        Value::Bulk(entries),
+    ])]);
+    let mut input = Vec::new();
+    support::encode_value(&value, &mut input).unwrap();
+    input
+}
+
+fn bench_decode_stream_batch(b: &mut Bencher) {
+    let input = stream_batch();
+    b.iter(|| redis::parse_redis_value(&input).unwrap());
+}
+
+fn bench_decode_stream_batch_ref(b: &mut Bencher) {
+    let input = stream_batch();
+    b.iter(|| redis::parse_redis_value_ref(&input).unwrap());
+}
+
+// The codecs decode the batch as it is read by async connections.
+fn bench_decode_stream_batch_value_codec(b: &mut Bencher) {
+    let input = stream_batch();
+    let mut codec = ValueCodec::default();
+    b.iter(|| {
+        let mut buffer = BytesMut::from(&input[..]);
+        codec.decode(&mut buffer).unwrap().unwrap()
+    });
+}
+
+fn bench_decode_stream_batch_reply_codec(b: &mut Bencher) {
+    let input = stream_batch();
+    let mut codec = ReplyCodec::default();
+    b.iter(|| {
+        let mut buffer = BytesMut::from(&input[..]);
+        let reply = codec.decode(&mut buffer).unwrap().unwrap();
+        reply.value().unwrap();
+    });
+}
+
 fn bench_decode(c: &mut Criterion) {
     c.bench("decode", Benchmark::new("decode", bench_decode_simple));
+    let input_len = stream_batch().len() as u32;
+    c.bench(
+        "decode_stream_batch",
+        Benchmark::new("owned", bench_decode_stream_batch)
+            .with_function("borrowed", bench_decode_stream_batch_ref)
+            .with_function("value_codec", bench_decode_stream_batch_value_codec)
+            .with_function("reply_codec", bench_decode_stream_batch_reply_codec)
+            .throughput(Throughput::Bytes(input_len)),
+    );
 }
 
 criterion_group!(bench, bench_query, bench_encode, bench_decode);
@@ -1,32 +1,173 @@
-use std::collections::VecDeque;
+use std::cmp;
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::fmt::Arguments;
 use std::io::{self, BufReader, Read, Write};
 use std::mem;
-use std::net::ToSocketAddrs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-#[cfg(feature = "with-unix-sockets")]
-use tokio_uds::UnixStream;
+#[cfg(feature = "tls-native")]
+use tokio_tls;
 
 use tokio_codec::{Decoder, Framed};
-use tokio_executor;
-use tokio_io::{self, AsyncWrite};
-use tokio_tcp::TcpStream;
+use tokio_io::{self, AsyncRead, AsyncWrite};
 
-use futures::future::Either;
+use futures::future::{Either, Loop, Shared};
+use futures::sync::mpsc::UnboundedSender;
+use futures::task::{self, Task};
 use futures::{future, Async, AsyncSink, Future, Poll, Sink, StartSend, Stream};
 use tokio_sync::{mpsc, oneshot};
 
-use cmd::cmd;
-use types::{ErrorKind, RedisError, RedisFuture, Value};
+use client::Client;
+use cmd::{cmd, pipe, Cmd, Pipeline as CmdPipeline};
+use types::{
+    clone_error, ArgBuffer, ErrorKind, PushInfo, PushKind, RedisError, RedisFuture, ToRedisArgs,
+    Value,
+};
+
+#[cfg(feature = "tls-native")]
+use connection::tls_connector;
+use connection::{
+    resolve, transaction_aborted, ConnectionAddr, ConnectionInfo, Msg, ProtocolVersion, ReplyLimits,
+};
+
+use parser::{Reply, ValueCodec};
+use runtime::{connect_tcp, spawn, try_spawn, Delay, TcpStream};
+#[cfg(feature = "with-unix-sockets")]
+use runtime::{connect_unix, UnixStream};
+use sentinel;
+
+/// A byte stream that an async `Connection` can run over instead of a TCP
+/// or unix socket.  This is implemented for every `AsyncRead + AsyncWrite`
+/// type.  Create a connection over it with `Connection::from_transport`.
+///
+/// The transport only has to carry the RESP byte stream, so a WebSocket
+/// connected to a WebSocket-to-Redis gateway works as well, see
+/// `WebSocketTransport`.
+pub trait Transport: AsyncRead + AsyncWrite + Send + 'static {}
+
+impl<T> Transport for T where T: AsyncRead + AsyncWrite + Send + 'static {}
+
+/// A `Transport` over a WebSocket, or any other connection that carries
+/// messages instead of a byte stream.
+///
+/// `S` receives and sends the payloads of binary messages.  Every write
+/// of the connection is sent as one message and the messages that arrive
+/// are joined into one byte stream, so a reply may be split across them.
+/// With the `async-std-comp` feature this also works on wasm32, where the
+/// browser's `WebSocket` is wrapped into such a stream and sink.
+///
+/// ```rust,no_run
+/// # extern crate futures;
+/// # extern crate redis;
+/// use std::io;
+///
+/// use futures::{Future, Sink, Stream};
+/// use redis::aio::{Connection, WebSocketTransport};
+/// use redis::{IntoConnectionInfo, RedisError};
+///
+/// fn connect<S>(socket: S) -> impl Future<Item = Connection, Error = RedisError>
+/// where
+///     S: Stream<Item = Vec<u8>, Error = io::Error>
+///         + Sink<SinkItem = Vec<u8>, SinkError = io::Error>
+///         + Send
+///         + 'static,
+/// {
+///     // the address is not used, the socket is connected already
+///     let info = "redis://gateway/".into_connection_info().unwrap();
+///     Connection::from_transport(WebSocketTransport::new(socket), info)
+/// }
+/// # fn main() {}
+/// ```
+pub struct WebSocketTransport<S> {
+    socket: S,
+    // the last message that arrived and the offset of its unread part
+    message: Vec<u8>,
+    pos: usize,
+}
+
+impl<S> WebSocketTransport<S>
+where
+    S: Stream<Item = Vec<u8>, Error = io::Error> + Sink<SinkItem = Vec<u8>, SinkError = io::Error>,
+{
+    /// Creates a transport over the messages of `socket`.
+    pub fn new(socket: S) -> WebSocketTransport<S> {
+        WebSocketTransport {
+            socket,
+            message: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Returns the socket.  The unread part of the last message is lost.
+    pub fn into_inner(self) -> S {
+        self.socket
+    }
+}
+
+impl<S> Read for WebSocketTransport<S>
+where
+    S: Stream<Item = Vec<u8>, Error = io::Error> + Sink<SinkItem = Vec<u8>, SinkError = io::Error>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // connections do not flush after writing a command, so the sent
+        // messages are pushed out while waiting for the reply
+        self.socket.poll_complete()?;
+        while self.pos == self.message.len() {
+            match self.socket.poll()? {
+                Async::Ready(Some(message)) => {
+                    self.message = message;
+                    self.pos = 0;
+                }
+                Async::Ready(None) => return Ok(0),
+                Async::NotReady => return Err(io::ErrorKind::WouldBlock.into()),
+            }
+        }
+        let len = cmp::min(buf.len(), self.message.len() - self.pos);
+        buf[..len].copy_from_slice(&self.message[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+impl<S> Write for WebSocketTransport<S>
+where
+    S: Stream<Item = Vec<u8>, Error = io::Error> + Sink<SinkItem = Vec<u8>, SinkError = io::Error>,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.socket.start_send(buf.to_vec())? {
+            AsyncSink::Ready => Ok(buf.len()),
+            AsyncSink::NotReady(_) => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.socket.poll_complete()? {
+            Async::Ready(()) => Ok(()),
+            Async::NotReady => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+}
 
-use connection::{ConnectionAddr, ConnectionInfo};
+impl<S> AsyncRead for WebSocketTransport<S> where
+    S: Stream<Item = Vec<u8>, Error = io::Error> + Sink<SinkItem = Vec<u8>, SinkError = io::Error>
+{
+}
 
-use parser::ValueCodec;
+impl<S> AsyncWrite for WebSocketTransport<S>
+where
+    S: Stream<Item = Vec<u8>, Error = io::Error> + Sink<SinkItem = Vec<u8>, SinkError = io::Error>,
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.socket.close()
+    }
+}
 
 enum ActualConnection {
     Tcp(BufReader<TcpStream>),
     #[cfg(feature = "with-unix-sockets")]
     Unix(BufReader<UnixStream>),
+    Custom(BufReader<Box<Transport>>),
 }
 
 struct WriteWrapper<T>(BufReader<T>);
@@ -63,23 +204,34 @@ where
 pub struct Connection {
     con: ActualConnection,
     db: i64,
+    protocol: ProtocolVersion,
+    push_sender: Option<PushSender>,
+    reply_limits: ReplyLimits,
+    response_timeout: Option<Duration>,
 }
 
+/// Receives the RESP3 push messages of async connections.
+pub type PushSender = UnboundedSender<PushInfo>;
+
 macro_rules! with_connection {
     ($con:expr, $f:expr) => {
         match $con {
             #[cfg(not(feature = "with-unix-sockets"))]
             ActualConnection::Tcp(con) => {
-                $f(con).map(|(con, value)| (ActualConnection::Tcp(con), value))
+                Either::A($f(con).map(|(con, value)| (ActualConnection::Tcp(con), value)))
             }
 
             #[cfg(feature = "with-unix-sockets")]
-            ActualConnection::Tcp(con) => {
-                Either::A($f(con).map(|(con, value)| (ActualConnection::Tcp(con), value)))
-            }
+            ActualConnection::Tcp(con) => Either::A(Either::A(
+                $f(con).map(|(con, value)| (ActualConnection::Tcp(con), value)),
+            )),
             #[cfg(feature = "with-unix-sockets")]
-            ActualConnection::Unix(con) => {
-                Either::B($f(con).map(|(con, value)| (ActualConnection::Unix(con), value)))
+            ActualConnection::Unix(con) => Either::A(Either::B(
+                $f(con).map(|(con, value)| (ActualConnection::Unix(con), value)),
+            )),
+
+            ActualConnection::Custom(con) => {
+                Either::B($f(con).map(|(con, value)| (ActualConnection::Custom(con), value)))
             }
         }
     };
@@ -89,117 +241,436 @@ macro_rules! with_write_connection {
     ($con:expr, $f:expr) => {
         match $con {
             #[cfg(not(feature = "with-unix-sockets"))]
-            ActualConnection::Tcp(con) => {
-                $f(WriteWrapper(con)).map(|(con, value)| (ActualConnection::Tcp(con.0), value))
-            }
-
-            #[cfg(feature = "with-unix-sockets")]
             ActualConnection::Tcp(con) => Either::A(
                 $f(WriteWrapper(con)).map(|(con, value)| (ActualConnection::Tcp(con.0), value)),
             ),
+
+            #[cfg(feature = "with-unix-sockets")]
+            ActualConnection::Tcp(con) => Either::A(Either::A(
+                $f(WriteWrapper(con)).map(|(con, value)| (ActualConnection::Tcp(con.0), value)),
+            )),
             #[cfg(feature = "with-unix-sockets")]
-            ActualConnection::Unix(con) => Either::B(
+            ActualConnection::Unix(con) => Either::A(Either::B(
                 $f(WriteWrapper(con)).map(|(con, value)| (ActualConnection::Unix(con.0), value)),
+            )),
+
+            ActualConnection::Custom(con) => Either::B(
+                $f(WriteWrapper(con)).map(|(con, value)| (ActualConnection::Custom(con.0), value)),
             ),
         }
     };
 }
 
 impl Connection {
+    /// Creates a connection over a custom transport.  The transport has to
+    /// be connected already; authentication and database selection are
+    /// done according to `connection_info` while its address and socket
+    /// options are ignored.
+    pub fn from_transport<T: Transport>(
+        transport: T,
+        connection_info: ConnectionInfo,
+    ) -> impl Future<Item = Connection, Error = RedisError> {
+        let transport: Box<Transport> = Box::new(transport);
+        setup_connection(
+            ActualConnection::Custom(BufReader::new(transport)),
+            connection_info,
+        )
+    }
+
+    /// Returns the protocol the connection speaks.  This is RESP2 if RESP3
+    /// was requested but the server does not support it.
+    pub fn protocol(&self) -> ProtocolVersion {
+        self.protocol
+    }
+
+    /// Sets the channel that receives the push messages of a RESP3
+    /// connection.  Push messages that arrive while a command waits for
+    /// its reply are sent there; without a sender they are dropped.  A
+    /// `MultiplexedConnection` created from the connection keeps the
+    /// sender.
+    pub fn set_push_sender(&mut self, sender: PushSender) {
+        self.push_sender = Some(sender);
+    }
+
+    /// Reads the next reply.  Push messages in between are handed to the
+    /// push sender.
     pub fn read_response(self) -> impl Future<Item = (Self, Value), Error = RedisError> {
-        let db = self.db;
-        with_connection!(self.con, ::parser::parse_async).then(move |result| {
-            match result {
-                Ok((con, value)) => Ok((Connection { con: con, db }, value)),
-                Err(err) => {
-                    // TODO Do we need to shutdown here as we do in the sync version?
-                    Err(err)
+        future::loop_fn(self, |rv| {
+            let Connection {
+                con,
+                db,
+                protocol,
+                push_sender,
+                reply_limits,
+                response_timeout,
+            } = rv;
+            // TODO Do we need to shutdown on errors as we do in the sync version?
+            with_connection!(con, |con| ::parser::parse_async_with_limits(
+                con,
+                reply_limits
+            ))
+            .map(move |(con, value)| {
+                let rv = Connection {
+                    con,
+                    db,
+                    protocol,
+                    push_sender,
+                    reply_limits,
+                    response_timeout,
+                };
+                match value {
+                    Value::Push { kind, data } => {
+                        send_push(&rv.push_sender, kind, data);
+                        Loop::Continue(rv)
+                    }
+                    value => Loop::Break((rv, value)),
                 }
-            }
+            })
         })
     }
-}
 
-pub fn connect(
-    connection_info: ConnectionInfo,
-) -> impl Future<Item = Connection, Error = RedisError> {
-    let connection = match *connection_info.addr {
-        ConnectionAddr::Tcp(ref host, port) => {
-            let socket_addr = match (&host[..], port).to_socket_addrs() {
-                Ok(mut socket_addrs) => match socket_addrs.next() {
-                    Some(socket_addr) => socket_addr,
-                    None => {
-                        return Either::A(future::err(RedisError::from((
-                            ErrorKind::InvalidClientConfig,
-                            "No address found for host",
-                        ))));
+    /// Reads the next reply like `read_response`, but keeps it in one
+    /// buffer instead of copying every string of it into a `Value`.  This
+    /// saves most allocations for large replies like stream batches.
+    pub fn read_reply(self) -> impl Future<Item = (Self, Reply), Error = RedisError> {
+        future::loop_fn(self, |rv| {
+            let Connection {
+                con,
+                db,
+                protocol,
+                push_sender,
+                reply_limits,
+                response_timeout,
+            } = rv;
+            with_connection!(con, |con| ::parser::read_async_reply(con, reply_limits)).and_then(
+                move |(con, reply)| {
+                    let rv = Connection {
+                        con,
+                        db,
+                        protocol,
+                        push_sender,
+                        reply_limits,
+                        response_timeout,
+                    };
+                    if reply.as_bytes().first() != Some(&b'>') {
+                        return Ok(Loop::Break((rv, reply)));
                     }
+                    if let Value::Push { kind, data } = reply.value()?.to_value() {
+                        send_push(&rv.push_sender, kind, data);
+                    }
+                    Ok(Loop::Continue(rv))
                 },
-                Err(err) => return Either::A(future::err(err.into())),
-            };
+            )
+        })
+    }
+
+    /// Sends the command and reads its reply with `read_reply`.
+    pub fn query_reply(self, cmd: &Cmd) -> RedisFuture<(Self, Reply)> {
+        let Connection {
+            con,
+            db,
+            protocol,
+            push_sender,
+            reply_limits,
+            response_timeout,
+        } = self;
+        let packed = cmd.get_packed_command();
+        let future = Box::new(
+            with_write_connection!(con, |con| tokio_io::io::write_all(con, packed))
+                .from_err()
+                .and_then(move |(con, _)| {
+                    Connection {
+                        con,
+                        db,
+                        protocol,
+                        push_sender,
+                        reply_limits,
+                        response_timeout,
+                    }
+                    .read_reply()
+                }),
+        );
+        with_timeout(future, response_timeout)
+    }
+}
+
+// Fails the future with a `TimedOut` IO error if it does not complete
+// within the timeout, like the socket timeouts of blocking connections.
+fn with_timeout<T: Send + 'static>(
+    future: RedisFuture<T>,
+    timeout: Option<Duration>,
+) -> RedisFuture<T> {
+    match timeout {
+        Some(timeout) => Box::new(future.select2(Delay::new(timeout)).then(
+            |result| match result {
+                Ok(Either::A((value, _))) => Ok(value),
+                Err(Either::A((err, _))) => Err(err),
+                Ok(Either::B(_)) => Err(io::Error::from(io::ErrorKind::TimedOut).into()),
+                Err(Either::B((err, _))) => Err(err),
+            },
+        )),
+        None => future,
+    }
+}
+
+// Connects to the first address the host name resolves to that accepts
+// the connection.
+fn connect_host(host: &str, port: u16, connection_info: &ConnectionInfo) -> RedisFuture<TcpStream> {
+    let socket_addrs = match resolve(host, port, connection_info.shuffle_resolved_addrs) {
+        Ok(socket_addrs) => socket_addrs,
+        Err(err) => return Box::new(future::err(err.into())),
+    };
+
+    let mut connection: RedisFuture<TcpStream> = Box::new(future::err(RedisError::from((
+        ErrorKind::InvalidClientConfig,
+        "No address found for host",
+    ))));
+    for socket_addr in socket_addrs {
+        let options = connection_info.tcp.clone();
+        let timeout = connection_info.connect_timeout;
+        connection = Box::new(
+            connection.or_else(move |_| with_timeout(connect_tcp(&socket_addr, options), timeout)),
+        );
+    }
+    connection
+}
 
-            Either::A(
-                TcpStream::connect(&socket_addr)
-                    .from_err()
-                    .map(|con| ActualConnection::Tcp(BufReader::new(con))),
+fn connect_addr(
+    addr: &ConnectionAddr,
+    connection_info: &ConnectionInfo,
+) -> RedisFuture<ActualConnection> {
+    match *addr {
+        ConnectionAddr::Tcp(ref host, port) => Box::new(
+            connect_host(host, port, connection_info)
+                .map(|con| ActualConnection::Tcp(BufReader::new(con))),
+        ),
+        #[cfg(feature = "tls-native")]
+        ConnectionAddr::TcpTls { ref host, port } => {
+            let connector = match tls_connector(&connection_info.tls) {
+                Ok(connector) => tokio_tls::TlsConnector::from(connector),
+                Err(err) => return Box::new(future::err(err)),
+            };
+            let host = host.clone();
+            Box::new(
+                connect_host(&host, port, connection_info).and_then(move |con| {
+                    connector.connect(&host, con).from_err().map(|stream| {
+                        let transport: Box<Transport> = Box::new(stream);
+                        ActualConnection::Custom(BufReader::new(transport))
+                    })
+                }),
             )
         }
+        #[cfg(not(feature = "tls-native"))]
+        ConnectionAddr::TcpTls { .. } => Box::new(future::err(RedisError::from((
+            ErrorKind::InvalidClientConfig,
+            "Cannot connect to TLS servers \
+             without the tls-native feature",
+        )))),
         #[cfg(feature = "with-unix-sockets")]
-        ConnectionAddr::Unix(ref path) => Either::B(
-            UnixStream::connect(path).map(|stream| ActualConnection::Unix(BufReader::new(stream))),
+        ConnectionAddr::Unix(ref path) => Box::new(
+            connect_unix(path).map(|stream| ActualConnection::Unix(BufReader::new(stream))),
         ),
         #[cfg(not(feature = "with-unix-sockets"))]
-        ConnectionAddr::Unix(_) => Either::B(future::err(RedisError::from((
+        ConnectionAddr::Unix(_) => Box::new(future::err(RedisError::from((
             ErrorKind::InvalidClientConfig,
             "Cannot connect to unix sockets \
              on this platform",
         )))),
+        ConnectionAddr::Sentinel(_) => Box::new(future::err(RedisError::from((
+            ErrorKind::InvalidClientConfig,
+            "Sentinel addresses have to be resolved first",
+        )))),
+    }
+}
+
+fn connect_node(
+    addr: &ConnectionAddr,
+    connection_info: &ConnectionInfo,
+) -> RedisFuture<Connection> {
+    match *addr {
+        ConnectionAddr::Sentinel(ref config) => sentinel::connect_async(config, connection_info),
+        _ => {
+            let info = connection_info.clone();
+            Box::new(
+                connect_addr(addr, connection_info)
+                    .and_then(move |con| setup_connection(con, info)),
+            )
+        }
+    }
+}
+
+pub fn connect(
+    connection_info: ConnectionInfo,
+) -> impl Future<Item = Connection, Error = RedisError> {
+    // try the addresses one after another until a connection succeeds
+    let mut connection: RedisFuture<Connection> = Box::new(future::err(RedisError::from((
+        ErrorKind::InvalidClientConfig,
+        "No address to connect to",
+    ))));
+    for addr in connection_info.addrs() {
+        let addr = addr.clone();
+        let info = connection_info.clone();
+        connection = Box::new(connection.or_else(move |_| connect_node(&addr, &info)));
+    }
+    connection
+}
+
+// Returns whether the push message confirms a subscribe or unsubscribe.
+fn is_subscription_reply(kind: &PushKind) -> bool {
+    match *kind {
+        PushKind::Subscribe
+        | PushKind::PSubscribe
+        | PushKind::SSubscribe
+        | PushKind::Unsubscribe
+        | PushKind::PUnsubscribe
+        | PushKind::SUnsubscribe => true,
+        _ => false,
+    }
+}
+
+// Hands a push message to the sender, if there is one.
+fn send_push(sender: &Option<PushSender>, kind: PushKind, data: Vec<Value>) {
+    if let Some(ref sender) = *sender {
+        let _ = sender.unbounded_send(PushInfo { kind, data });
+    }
+}
+
+// Authenticates and selects the database.
+fn setup_connection(
+    con: ActualConnection,
+    connection_info: ConnectionInfo,
+) -> impl Future<Item = Connection, Error = RedisError> {
+    let rv = Connection {
+        con,
+        db: connection_info.db,
+        protocol: ProtocolVersion::RESP2,
+        push_sender: None,
+        reply_limits: connection_info.reply_limits,
+        response_timeout: connection_info.response_timeout,
     };
 
-    Either::B(connection.from_err().and_then(move |con| {
-        let rv = Connection {
-            con,
-            db: connection_info.db,
-        };
+    let login = match connection_info.passwd {
+        Some(ref passwd) => Either::A(cmd("AUTH").arg(&**passwd).query_async::<_, Value>(rv).then(
+            |x| match x {
+                Ok((rv, Value::Okay)) => Ok(rv),
+                _ => {
+                    fail!((
+                        ErrorKind::AuthenticationFailed,
+                        "Password authentication failed"
+                    ));
+                }
+            },
+        )),
+        None => Either::B(future::ok(rv)),
+    };
 
-        let login = match connection_info.passwd {
-            Some(ref passwd) => {
-                Either::A(cmd("AUTH").arg(&**passwd).query_async::<_, Value>(rv).then(
-                    |x| match x {
-                        Ok((rv, Value::Okay)) => Ok(rv),
-                        _ => {
-                            fail!((
-                                ErrorKind::AuthenticationFailed,
-                                "Password authentication failed"
-                            ));
-                        }
-                    },
-                ))
-            }
-            None => Either::B(future::ok(rv)),
-        };
+    let db = connection_info.db;
+    let select = login.and_then(move |rv| {
+        if db != 0 {
+            Either::A(cmd("SELECT").arg(db).query_async::<_, Value>(rv).then(
+                |result| match result {
+                    Ok((rv, Value::Okay)) => Ok(rv),
+                    _ => fail!((
+                        ErrorKind::ResponseError,
+                        "Redis server refused to switch database"
+                    )),
+                },
+            ))
+        } else {
+            Either::B(future::ok(rv))
+        }
+    });
 
-        login.and_then(move |rv| {
-            if connection_info.db != 0 {
-                Either::A(
-                    cmd("SELECT")
-                        .arg(connection_info.db)
-                        .query_async::<_, Value>(rv)
-                        .then(|result| match result {
-                            Ok((rv, Value::Okay)) => Ok(rv),
-                            _ => fail!((
-                                ErrorKind::ResponseError,
-                                "Redis server refused to switch database"
-                            )),
-                        }),
-                )
-            } else {
-                Either::B(future::ok(rv))
-            }
+    let protocol = connection_info.protocol;
+    let set_lib_info = connection_info.set_lib_info;
+    let name = select.and_then(move |rv| match connection_info.client_name {
+        Some(ref name) => Either::A(
+            cmd("CLIENT")
+                .arg("SETNAME")
+                .arg(&**name)
+                .query_async::<_, ()>(rv)
+                .map(|(rv, ())| rv),
+        ),
+        None => Either::B(future::ok(rv)),
+    });
+
+    let lib_info = name.and_then(move |rv| {
+        if set_lib_info {
+            Either::A(send_lib_info(rv))
+        } else {
+            Either::B(future::ok(rv))
+        }
+    });
+
+    lib_info.and_then(move |rv| match protocol {
+        ProtocolVersion::RESP3 => Either::A(negotiate_resp3(rv)),
+        ProtocolVersion::RESP2 => Either::B(future::ok(rv)),
+    })
+}
+
+// Reports the library with `CLIENT SETINFO`.  Servers before redis 7.2
+// reply with errors, which are ignored.
+fn send_lib_info(rv: Connection) -> impl Future<Item = Connection, Error = RedisError> {
+    let Connection {
+        con,
+        db,
+        protocol,
+        push_sender,
+        reply_limits,
+        response_timeout,
+    } = rv;
+    let packed = ::connection::lib_info_pipeline().get_packed_pipeline(false);
+    with_write_connection!(con, |con| tokio_io::io::write_all(con, packed))
+        .from_err()
+        .and_then(move |(con, _)| {
+            with_connection!(con, |con| ::parser::parse_async_reply(con, reply_limits))
+        })
+        .and_then(move |(con, _)| {
+            with_connection!(con, |con| ::parser::parse_async_reply(con, reply_limits))
+        })
+        .map(move |(con, _)| Connection {
+            con,
+            db,
+            protocol,
+            push_sender,
+            reply_limits,
+            response_timeout,
+        })
+}
+
+// Switches to RESP3 with `HELLO 3`.  Servers before redis 6 reply with an
+// error and keep speaking RESP2.
+fn negotiate_resp3(rv: Connection) -> impl Future<Item = Connection, Error = RedisError> {
+    let db = rv.db;
+    let reply_limits = rv.reply_limits;
+    let response_timeout = rv.response_timeout;
+    let hello = cmd("HELLO").arg(3).get_packed_command();
+    with_write_connection!(rv.con, |con| tokio_io::io::write_all(con, hello))
+        .from_err()
+        .and_then(move |(con, _)| {
+            with_connection!(con, |con| ::parser::parse_async_reply(con, reply_limits))
+        })
+        .map(move |(con, reply)| Connection {
+            con,
+            db,
+            protocol: match reply {
+                Ok(_) => ProtocolVersion::RESP3,
+                Err(_) => ProtocolVersion::RESP2,
+            },
+            push_sender: None,
+            reply_limits,
+            response_timeout,
         })
-    }))
 }
 
+/// Implemented by the async connection types.
+///
+/// The methods consume the connection and hand it back with the response.
+/// Dropping a returned future before it resolves never leaves a connection
+/// in a state where later responses are mismatched to their requests: a
+/// `Connection` is dropped together with the future and so closed, while
+/// `MultiplexedConnection` and `ConnectionManager` keep reading the
+/// response of the cancelled request in the background and discard it.
 pub trait ConnectionLike: Sized {
     /// Sends an already encoded (packed) command into the TCP socket and
     /// reads the single response from it.
@@ -224,12 +695,30 @@ pub trait ConnectionLike: Sized {
 
 impl ConnectionLike for Connection {
     fn req_packed_command(self, cmd: Vec<u8>) -> RedisFuture<(Self, Value)> {
-        let db = self.db;
-        Box::new(
-            with_write_connection!(self.con, |con| tokio_io::io::write_all(con, cmd))
+        let Connection {
+            con,
+            db,
+            protocol,
+            push_sender,
+            reply_limits,
+            response_timeout,
+        } = self;
+        let future = Box::new(
+            with_write_connection!(con, |con| tokio_io::io::write_all(con, cmd))
                 .from_err()
-                .and_then(move |(con, _)| Connection { con, db }.read_response()),
-        )
+                .and_then(move |(con, _)| {
+                    Connection {
+                        con,
+                        db,
+                        protocol,
+                        push_sender,
+                        reply_limits,
+                        response_timeout,
+                    }
+                    .read_response()
+                }),
+        );
+        with_timeout(future, response_timeout)
     }
 
     fn req_packed_commands(
@@ -238,12 +727,26 @@ impl ConnectionLike for Connection {
         offset: usize,
         count: usize,
     ) -> RedisFuture<(Self, Vec<Value>)> {
-        let db = self.db;
-        Box::new(
-            with_write_connection!(self.con, |con| tokio_io::io::write_all(con, cmd))
+        let Connection {
+            con,
+            db,
+            protocol,
+            push_sender,
+            reply_limits,
+            response_timeout,
+        } = self;
+        let future = Box::new(
+            with_write_connection!(con, |con| tokio_io::io::write_all(con, cmd))
                 .from_err()
                 .and_then(move |(con, _)| {
-                    let mut con = Some(Connection { con, db });
+                    let mut con = Some(Connection {
+                        con,
+                        db,
+                        protocol,
+                        push_sender,
+                        reply_limits,
+                        response_timeout,
+                    });
                     let mut rv = vec![];
                     let mut future = None;
                     let mut idx = 0;
@@ -267,7 +770,8 @@ impl ConnectionLike for Connection {
                         )))
                     })
                 }),
-        )
+        );
+        with_timeout(future, response_timeout)
     }
 
     fn get_db(&self) -> i64 {
@@ -275,13 +779,130 @@ impl ConnectionLike for Connection {
     }
 }
 
+/// Runs a transaction on an async connection like `redis::transaction`.
+///
+/// The keys are watched and the closure is called with the connection and
+/// a fresh pipeline in atomic mode.  It has to return the result of
+/// querying the pipeline as `Option`, which is `None` if the transaction
+/// was aborted because a watched key was modified.  In that case the keys
+/// are watched again and the closure is called once more.
+///
+/// `WATCH` applies to the whole connection, so the connection should not be
+/// shared with other tasks while the transaction runs.
+///
+/// ```rust,no_run
+/// # extern crate futures;
+/// # extern crate redis;
+/// use futures::Future;
+/// use redis::AsyncCommands;
+/// use redis::PipelineCommands;
+/// # fn main() {
+/// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+/// let future = client.get_async_connection().and_then(|con| {
+///     redis::aio::transaction(con, &["the_key"], |con, mut pipe| {
+///         con.get("the_key").and_then(move |(con, old_val): (_, isize)| {
+///             pipe.set("the_key", old_val + 1).ignore().get("the_key");
+///             pipe.query_async(con)
+///         })
+///     })
+/// });
+/// let (_con, (new_val,)): (_, (isize,)) = future.wait().unwrap();
+/// # }
+/// ```
+pub fn transaction<C, K, T, F, R>(con: C, keys: &[K], func: F) -> RedisFuture<(C, T)>
+where
+    C: ConnectionLike + Send + 'static,
+    K: ToRedisArgs,
+    T: Send + 'static,
+    F: FnMut(C, CmdPipeline) -> R + Send + 'static,
+    R: Future<Item = (C, Option<T>), Error = RedisError> + Send + 'static,
+{
+    run_transaction(con, keys, None, func)
+}
+
+/// Like `transaction` but gives up after the transaction was aborted
+/// `max_retries + 1` times.  The error returned then is of kind
+/// `ErrorKind::ExecAbortError`.
+pub fn transaction_with_retries<C, K, T, F, R>(
+    con: C,
+    keys: &[K],
+    max_retries: usize,
+    func: F,
+) -> RedisFuture<(C, T)>
+where
+    C: ConnectionLike + Send + 'static,
+    K: ToRedisArgs,
+    T: Send + 'static,
+    F: FnMut(C, CmdPipeline) -> R + Send + 'static,
+    R: Future<Item = (C, Option<T>), Error = RedisError> + Send + 'static,
+{
+    run_transaction(con, keys, Some(max_retries), func)
+}
+
+fn run_transaction<C, K, T, F, R>(
+    con: C,
+    keys: &[K],
+    max_retries: Option<usize>,
+    func: F,
+) -> RedisFuture<(C, T)>
+where
+    C: ConnectionLike + Send + 'static,
+    K: ToRedisArgs,
+    T: Send + 'static,
+    F: FnMut(C, CmdPipeline) -> R + Send + 'static,
+    R: Future<Item = (C, Option<T>), Error = RedisError> + Send + 'static,
+{
+    let mut watch = cmd("WATCH");
+    watch.arg(keys);
+    Box::new(future::loop_fn(
+        (con, func, 0),
+        move |(con, mut func, retries): (C, F, usize)| {
+            watch
+                .query_async(con)
+                .and_then(move |(con, ()): (C, ())| {
+                    let mut pipe = pipe();
+                    pipe.atomic();
+                    let response = func(con, pipe);
+                    response.map(move |(con, response)| (con, func, response))
+                })
+                .and_then(move |(con, func, response)| {
+                    let unwatch = cmd("UNWATCH").query_async(con);
+                    let next: RedisFuture<Loop<(C, T), (C, F, usize)>> = match response {
+                        Some(response) => {
+                            // make sure no watch is left in the connection,
+                            // even if the pipeline was not used.
+                            Box::new(unwatch.map(|(con, ()): (C, ())| Loop::Break((con, response))))
+                        }
+                        None => {
+                            if max_retries.map_or(false, |max_retries| retries >= max_retries) {
+                                Box::new(unwatch.and_then(move |(_con, ()): (C, ())| {
+                                    Err(transaction_aborted(retries + 1))
+                                }))
+                            } else {
+                                Box::new(unwatch.map(move |(con, ()): (C, ())| {
+                                    Loop::Continue((con, func, retries + 1))
+                                }))
+                            }
+                        }
+                    };
+                    next
+                })
+        },
+    ))
+}
+
 // Senders which the result of a single request are sent through
 type PipelineOutput<O, E> = oneshot::Sender<Result<Vec<O>, E>>;
 
 struct InFlight<O, E> {
     output: PipelineOutput<O, E>,
     response_count: usize,
+    received: usize,
     buffer: Vec<O>,
+    // the first error reply, which is returned once all responses arrived
+    error: Option<E>,
+    // whether the responses are subscription push messages
+    push_replies: bool,
 }
 
 // A single message sent through the pipeline
@@ -289,6 +910,7 @@ struct PipelineMessage<S, I, E> {
     input: S,
     output: PipelineOutput<I, E>,
     response_count: usize,
+    push_replies: bool,
 }
 
 /// Wrapper around a `Stream + Sink` where each item sent through the `Sink` results in one or more
@@ -308,26 +930,213 @@ where
     }
 }
 
+/// Controls how a `MultiplexedConnection` batches the requests of its
+/// users into writes.
+///
+/// Requests that are queued at the same time are always written together.
+/// With a `flush_interval` the connection additionally waits for more
+/// requests before it writes, which saves system calls under high
+/// concurrency at the price of latency.  See `Client::set_auto_pipeline`.
+#[derive(Clone, Debug)]
+pub struct AutoPipelineConfig {
+    /// How long the first request of a batch waits for further requests.
+    /// The default of zero writes as soon as no more requests are queued.
+    pub flush_interval: Duration,
+    /// A batch is written right away once it has this many requests.
+    pub max_batch_size: usize,
+}
+
+impl Default for AutoPipelineConfig {
+    fn default() -> AutoPipelineConfig {
+        AutoPipelineConfig {
+            flush_interval: Duration::from_millis(0),
+            max_batch_size: 1000,
+        }
+    }
+}
+
 struct PipelineSink<T>
 where
-    T: Sink<SinkError = <T as Stream>::Error> + Stream + 'static,
+    T: Sink<SinkError = RedisError> + Stream<Item = Value, Error = RedisError> + 'static,
 {
     sink_stream: T,
     in_flight: VecDeque<InFlight<T::Item, T::Error>>,
+    config: AutoPipelineConfig,
+    // the number of requests written to the sink since the last flush
+    unflushed: usize,
+    flush_delay: Option<Delay>,
+    push_sender: Arc<Mutex<Option<PushSender>>>,
+    // receives the messages of a `PubSub`
+    messages: Option<MessageSender>,
+}
+
+/// What an async `PubSub` does with a message that arrives while its
+/// buffer is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Drops the oldest buffered message to make room for the new one.
+    DropOldest,
+    /// Drops the message that arrived.
+    DropNewest,
+    /// Drops the message that arrived and fails the next poll of the
+    /// stream with `ErrorKind::BufferFull`.  The stream can be polled
+    /// again afterwards.
+    Error,
+}
+
+/// Configures the message buffer of an async `PubSub`, which holds the
+/// messages that arrived but were not consumed yet.
+#[derive(Clone, Debug)]
+pub struct PubSubConfig {
+    /// The most messages that are buffered.  `None`, the default, buffers
+    /// without limit, so a slow consumer makes the buffer grow.
+    pub buffer_size: Option<usize>,
+    /// What happens to messages that arrive while the buffer is full.
+    /// Defaults to `OverflowPolicy::DropOldest`.
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for PubSubConfig {
+    fn default() -> PubSubConfig {
+        PubSubConfig {
+            buffer_size: None,
+            overflow: OverflowPolicy::DropOldest,
+        }
+    }
+}
+
+// The messages of a `PubSub` that were not consumed yet.
+struct MessageQueue {
+    config: PubSubConfig,
+    messages: VecDeque<Result<Msg, RedisError>>,
+    dropped: u64,
+    // whether the next poll fails because messages were dropped
+    overflowed: bool,
+    // whether the connection is gone
+    closed: bool,
+    task: Option<Task>,
+}
+
+impl MessageQueue {
+    fn push(&mut self, item: Result<Msg, RedisError>) {
+        self.messages.push_back(item);
+        if let Some(task) = self.task.take() {
+            task.notify();
+        }
+    }
+}
+
+// Hands the messages of a `PubSub` from the task that reads them to the
+// stream.
+struct MessageSender(Arc<Mutex<MessageQueue>>);
+
+impl MessageSender {
+    fn send(&self, msg: Msg) {
+        let mut queue = self.0.lock().unwrap();
+        let full = match queue.config.buffer_size {
+            Some(size) => queue.messages.len() >= size,
+            None => false,
+        };
+        if full {
+            queue.dropped += 1;
+            match queue.config.overflow {
+                OverflowPolicy::DropOldest => {
+                    queue.messages.pop_front();
+                }
+                OverflowPolicy::DropNewest => return,
+                OverflowPolicy::Error => {
+                    queue.overflowed = true;
+                    if let Some(task) = queue.task.take() {
+                        task.notify();
+                    }
+                    return;
+                }
+            }
+        }
+        queue.push(Ok(msg));
+    }
+
+    // Passes on the error that closed the connection, regardless of the
+    // size of the buffer.
+    fn fail(&self, err: RedisError) {
+        self.0.lock().unwrap().push(Err(err));
+    }
+}
+
+impl Drop for MessageSender {
+    fn drop(&mut self) {
+        let mut queue = self.0.lock().unwrap();
+        queue.closed = true;
+        if let Some(task) = queue.task.take() {
+            task.notify();
+        }
+    }
 }
 
 impl<T> PipelineSink<T>
 where
-    T: Sink<SinkError = <T as Stream>::Error> + Stream + 'static,
+    T: Sink<SinkError = RedisError> + Stream<Item = Value, Error = RedisError> + 'static,
 {
+    // Returns true if the current batch should wait for more requests.
+    fn should_delay_flush(&mut self) -> bool {
+        let interval = self.config.flush_interval;
+        if self.unflushed == 0
+            || self.unflushed >= self.config.max_batch_size
+            || interval == Duration::from_millis(0)
+        {
+            return false;
+        }
+        let delay = self.flush_delay.get_or_insert_with(|| Delay::new(interval));
+        match delay.poll() {
+            Ok(Async::NotReady) => true,
+            // flush once the interval passed or the timer failed
+            _ => false,
+        }
+    }
+
     // Read messages from the stream and send them back to the caller
     fn poll_read(&mut self) -> Poll<(), ()> {
         loop {
             let item = match self.sink_stream.poll() {
-                Ok(Async::Ready(Some(item))) => Ok(item),
+                Ok(Async::Ready(Some(item))) => {
+                    // the messages of a `PubSub` go to its stream
+                    if let Some(ref messages) = self.messages {
+                        if let Some(msg) = Msg::from_value(&item) {
+                            messages.send(msg);
+                            continue;
+                        }
+                    }
+                    match item {
+                        // push messages are not responses to a request, except for the
+                        // confirmations of subscriptions
+                        Value::Push { kind, data } => {
+                            let push_replies = match self.in_flight.front() {
+                                Some(entry) => entry.push_replies,
+                                None => false,
+                            };
+                            if push_replies && is_subscription_reply(&kind) {
+                                Ok(Value::Push { kind, data })
+                            } else {
+                                send_push(&self.push_sender.lock().unwrap(), kind, data);
+                                continue;
+                            }
+                        }
+                        item => Ok(item),
+                    }
+                }
                 // The redis response stream is not going to produce any more items so we `Err`
                 // to break out of the `forward` combinator and stop handling requests
                 Ok(Async::Ready(None)) => return Err(()),
+                // After an IO or parse error or a reply beyond the limits the position in the
+                // response stream is unknown, so the connection is given up.  Dropping the sink
+                // fails all other requests.
+                Err(ref err) if err.is_io_error() || err.kind() == ErrorKind::ReplyTooLarge => {
+                    self.fail_front(clone_error(err));
+                    if let Some(ref messages) = self.messages {
+                        messages.fail(clone_error(err));
+                    }
+                    return Err(());
+                }
                 Err(err) => Err(err),
                 Ok(Async::NotReady) => return Ok(Async::NotReady),
             };
@@ -341,17 +1150,28 @@ where
                 Some(entry) => entry,
                 None => return,
             };
+            entry.received += 1;
             match result {
-                Ok(item) => {
-                    entry.buffer.push(item);
-                    if entry.response_count > entry.buffer.len() {
-                        // Need to gather more response values
-                        return;
+                Ok(item) => entry.buffer.push(item),
+                // An error reply answers a single command, the responses of the other
+                // commands in the pipeline still have to be read
+                Err(err) => {
+                    if entry.error.is_none() {
+                        entry.error = Some(err);
+                    }
+                    // a failed subscription is not confirmed per channel
+                    if entry.push_replies {
+                        entry.received = entry.response_count;
                     }
-                    Ok(mem::replace(&mut entry.buffer, Vec::new()))
                 }
-                // If we fail we must respond immediately
-                Err(err) => Err(err),
+            }
+            if entry.response_count > entry.received {
+                // Need to gather more response values
+                return;
+            }
+            match entry.error.take() {
+                Some(err) => Err(err),
+                None => Ok(mem::replace(&mut entry.buffer, Vec::new())),
             }
         };
 
@@ -365,11 +1185,18 @@ where
             }
         }
     }
+
+    // Fails the oldest request after the connection broke.
+    fn fail_front(&mut self, err: RedisError) {
+        if let Some(entry) = self.in_flight.pop_front() {
+            let _ = entry.output.send(Err(err));
+        }
+    }
 }
 
 impl<T> Sink for PipelineSink<T>
 where
-    T: Sink<SinkError = <T as Stream>::Error> + Stream + 'static,
+    T: Sink<SinkError = RedisError> + Stream<Item = Value, Error = RedisError> + 'static,
 {
     type SinkItem = PipelineMessage<T::SinkItem, T::Item, T::Error>;
     type SinkError = ();
@@ -381,20 +1208,37 @@ where
             input,
             output,
             response_count,
+            push_replies,
         }: Self::SinkItem,
     ) -> StartSend<Self::SinkItem, Self::SinkError> {
+        if output.is_closed() {
+            // the request was dropped before it was written, so it can be skipped
+            return Ok(AsyncSink::Ready);
+        }
         match self.sink_stream.start_send(input) {
             Ok(AsyncSink::NotReady(input)) => Ok(AsyncSink::NotReady(PipelineMessage {
                 input,
                 output,
                 response_count,
+                push_replies,
             })),
             Ok(AsyncSink::Ready) => {
                 self.in_flight.push_back(InFlight {
                     output,
                     response_count,
+                    received: 0,
                     buffer: Vec::new(),
+                    error: None,
+                    push_replies,
                 });
+                self.unflushed += 1;
+                if self.unflushed >= self.config.max_batch_size {
+                    // start writing the full batch, `poll_complete` finishes it
+                    if let Ok(Async::Ready(())) = self.sink_stream.poll_complete() {
+                        self.unflushed = 0;
+                        self.flush_delay = None;
+                    }
+                }
                 Ok(AsyncSink::Ready)
             }
             Err(err) => {
@@ -405,37 +1249,52 @@ where
     }
 
     fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
-        try_ready!(self.sink_stream.poll_complete().map_err(|err| {
-            self.send_result(Err(err));
-        }));
+        if self.should_delay_flush() {
+            // the timer wakes the task up again
+            return self.poll_read();
+        }
+        try_ready!(self
+            .sink_stream
+            .poll_complete()
+            .map_err(|err| self.fail_front(err)));
+        self.unflushed = 0;
+        self.flush_delay = None;
         self.poll_read()
     }
 
     fn close(&mut self) -> Poll<(), Self::SinkError> {
-        try_ready!(self.sink_stream.close().map_err(|err| {
-            self.send_result(Err(err));
-        }));
+        try_ready!(self.sink_stream.close().map_err(|err| self.fail_front(err)));
         self.poll_read()
     }
 }
 
 impl<T> Pipeline<T>
 where
-    T: Sink<SinkError = <T as Stream>::Error> + Stream + Send + 'static,
+    T: Sink<SinkError = RedisError> + Stream<Item = Value, Error = RedisError> + Send + 'static,
     T::SinkItem: Send,
     T::Item: Send,
-    T::Error: Send,
-    T::Error: ::std::fmt::Debug,
 {
-    fn new(sink_stream: T) -> Self {
+    fn new(
+        sink_stream: T,
+        config: AutoPipelineConfig,
+        push_sender: Arc<Mutex<Option<PushSender>>>,
+        messages: Option<MessageSender>,
+    ) -> Self {
+        // the channel does not limit the batches, the sink takes requests
+        // from it until the batch is full or the channel is empty
         const BUFFER_SIZE: usize = 50;
         let (sender, receiver) = mpsc::channel(BUFFER_SIZE);
-        tokio_executor::spawn(
+        spawn(
             receiver
                 .map_err(|_| ())
                 .forward(PipelineSink {
                     sink_stream,
                     in_flight: VecDeque::new(),
+                    config,
+                    unflushed: 0,
+                    flush_delay: None,
+                    push_sender,
+                    messages,
                 })
                 .map(|_| ()),
         );
@@ -455,6 +1314,24 @@ where
         &self,
         input: T::SinkItem,
         count: usize,
+    ) -> impl Future<Item = Vec<T::Item>, Error = Option<T::Error>> + Send {
+        self.send_message(input, count, false)
+    }
+
+    // Like `send_recv_multiple` but the responses are subscription push messages.
+    fn send_subscription(
+        &self,
+        input: T::SinkItem,
+        count: usize,
+    ) -> impl Future<Item = Vec<T::Item>, Error = Option<T::Error>> + Send {
+        self.send_message(input, count, true)
+    }
+
+    fn send_message(
+        &self,
+        input: T::SinkItem,
+        count: usize,
+        push_replies: bool,
     ) -> impl Future<Item = Vec<T::Item>, Error = Option<T::Error>> + Send {
         let self_ = self.0.clone();
 
@@ -465,6 +1342,7 @@ where
                 input,
                 response_count: count,
                 output: sender,
+                push_replies,
             })
             .map_err(|_| None)
             .and_then(|_| {
@@ -485,52 +1363,256 @@ enum ActualPipeline {
     Tcp(Pipeline<Framed<TcpStream, ValueCodec>>),
     #[cfg(feature = "with-unix-sockets")]
     Unix(Pipeline<Framed<UnixStream, ValueCodec>>),
+    Custom(Pipeline<Framed<Box<Transport>, ValueCodec>>),
 }
 
-#[derive(Clone)]
-pub struct SharedConnection {
-    pipeline: ActualPipeline,
-    db: i64,
-}
-
-impl SharedConnection {
-    pub fn new(con: Connection) -> impl Future<Item = Self, Error = RedisError> {
-        future::lazy(|| {
-            let pipeline = match con.con {
-                ActualConnection::Tcp(tcp) => {
-                    let codec = ValueCodec::default().framed(tcp.into_inner());
-                    ActualPipeline::Tcp(Pipeline::new(codec))
-                }
-                #[cfg(feature = "with-unix-sockets")]
-                ActualConnection::Unix(unix) => {
-                    let codec = ValueCodec::default().framed(unix.into_inner());
-                    ActualPipeline::Unix(Pipeline::new(codec))
-                }
-            };
-            Ok(SharedConnection {
+impl ActualPipeline {
+    // Spawns the task that drives the connection.
+    fn new(
+        con: Connection,
+        config: AutoPipelineConfig,
+        push_sender: Arc<Mutex<Option<PushSender>>>,
+        messages: Option<MessageSender>,
+    ) -> ActualPipeline {
+        let codec = ValueCodec::new(con.reply_limits);
+        match con.con {
+            ActualConnection::Tcp(tcp) => ActualPipeline::Tcp(Pipeline::new(
+                codec.framed(tcp.into_inner()),
+                config,
+                push_sender,
+                messages,
+            )),
+            #[cfg(feature = "with-unix-sockets")]
+            ActualConnection::Unix(unix) => ActualPipeline::Unix(Pipeline::new(
+                codec.framed(unix.into_inner()),
+                config,
+                push_sender,
+                messages,
+            )),
+            ActualConnection::Custom(custom) => ActualPipeline::Custom(Pipeline::new(
+                codec.framed(custom.into_inner()),
+                config,
+                push_sender,
+                messages,
+            )),
+        }
+    }
+
+    // Sends a (un)subscription and waits until the server confirmed all
+    // `count` channels.
+    fn send_subscription(&self, packed: Vec<u8>, count: usize) -> RedisFuture<()> {
+        #[cfg(not(feature = "with-unix-sockets"))]
+        let future = match *self {
+            ActualPipeline::Tcp(ref pipeline) => {
+                Either::A(pipeline.send_subscription(packed, count))
+            }
+            ActualPipeline::Custom(ref pipeline) => {
+                Either::B(pipeline.send_subscription(packed, count))
+            }
+        };
+
+        #[cfg(feature = "with-unix-sockets")]
+        let future = match *self {
+            ActualPipeline::Tcp(ref pipeline) => {
+                Either::A(Either::A(pipeline.send_subscription(packed, count)))
+            }
+            ActualPipeline::Unix(ref pipeline) => {
+                Either::A(Either::B(pipeline.send_subscription(packed, count)))
+            }
+            ActualPipeline::Custom(ref pipeline) => {
+                Either::B(pipeline.send_subscription(packed, count))
+            }
+        };
+
+        Box::new(future.map(|_| ()).map_err(|err| {
+            err.unwrap_or_else(|| RedisError::from(io::Error::from(io::ErrorKind::BrokenPipe)))
+        }))
+    }
+}
+
+// Packs a (un)subscription and returns it with the number of channels,
+// which the server confirms one by one.
+fn pack_subscription<T: ToRedisArgs>(
+    name: &str,
+    channels: T,
+) -> Result<(Vec<u8>, usize), RedisError> {
+    let mut args = ArgBuffer::new();
+    channels.write_redis_args(&mut args);
+    let count = args.len();
+    if count == 0 {
+        return Err(RedisError::from((
+            ErrorKind::TypeError,
+            "No channels to subscribe or unsubscribe",
+        )));
+    }
+    Ok((cmd(name).arg(&args).get_packed_command(), count))
+}
+
+/// A connection that can be cloned and shared between many tasks.
+///
+/// All clones send their requests over the same underlying socket.  The
+/// requests are written in the order they are issued and a background task
+/// routes every response back to the request that produced it, so commands
+/// from different tasks interleave without the need for a connection per
+/// task.  The background task is spawned on the current tokio executor when
+/// the connection is created.
+///
+/// Requests that are issued while others are queued are written to the
+/// socket with a single write, see `AutoPipelineConfig`.
+///
+/// Dropping the future of a request is safe at any time.  A request that
+/// was not written yet is skipped, otherwise its response is read and
+/// discarded by the background task.  If the response stream can no
+/// longer be trusted, after an IO error or a response that cannot be
+/// parsed, all pending requests fail with an IO error and the connection
+/// is closed.
+#[derive(Clone)]
+pub struct MultiplexedConnection {
+    pipeline: ActualPipeline,
+    db: i64,
+    protocol: ProtocolVersion,
+    push_sender: Arc<Mutex<Option<PushSender>>>,
+    response_timeout: Option<Duration>,
+}
+
+/// The previous name of `MultiplexedConnection`.
+#[deprecated(note = "renamed to `MultiplexedConnection`")]
+pub type SharedConnection = MultiplexedConnection;
+
+impl MultiplexedConnection {
+    /// Turns a regular async connection into a multiplexed one.  This has
+    /// to be called from within a tokio executor.
+    pub fn new(con: Connection) -> impl Future<Item = Self, Error = RedisError> {
+        MultiplexedConnection::with_config(con, AutoPipelineConfig::default())
+    }
+
+    /// Turns a regular async connection into a multiplexed one that
+    /// batches requests according to `config`.
+    pub fn with_config(
+        mut con: Connection,
+        config: AutoPipelineConfig,
+    ) -> impl Future<Item = Self, Error = RedisError> {
+        future::lazy(move || {
+            let db = con.db;
+            let protocol = con.protocol;
+            let response_timeout = con.response_timeout;
+            let push_sender = Arc::new(Mutex::new(con.push_sender.take()));
+            let pipeline = ActualPipeline::new(con, config, push_sender.clone(), None);
+            Ok(MultiplexedConnection {
                 pipeline,
-                db: con.db,
+                db,
+                protocol,
+                push_sender,
+                response_timeout,
             })
         })
     }
+
+    /// Sets the channel that receives the push messages of a RESP3
+    /// connection, for instance the invalidation messages of client side
+    /// caching.  The sender is shared by all clones of the connection.
+    pub fn set_push_sender(&self, sender: PushSender) {
+        *self.push_sender.lock().unwrap() = Some(sender);
+    }
+
+    /// Returns the protocol the connection speaks.
+    pub fn protocol(&self) -> ProtocolVersion {
+        self.protocol
+    }
+
+    /// Subscribes to channels.  Their messages are handed to the push
+    /// sender while the connection can still be used for other commands.
+    ///
+    /// This requires a RESP3 connection, where messages are push messages
+    /// and cannot be mistaken for replies.  Over RESP2 a dedicated
+    /// `PubSub` connection has to be used instead.
+    ///
+    /// ```rust,no_run
+    /// # extern crate futures;
+    /// # extern crate redis;
+    /// use futures::sync::mpsc;
+    /// use futures::{Future, Stream};
+    /// use redis::aio::MultiplexedConnection;
+    ///
+    /// # fn main() {
+    /// let client = redis::Client::open("redis://127.0.0.1/?protocol=resp3").unwrap();
+    /// let (sender, receiver) = mpsc::unbounded();
+    /// let messages = client
+    ///     .get_multiplexed_async_connection()
+    ///     .and_then(move |con| {
+    ///         con.set_push_sender(sender);
+    ///         con.subscribe("channel")
+    ///     })
+    ///     .and_then(|_con| {
+    ///         receiver
+    ///             .for_each(|push| {
+    ///                 println!("{:?}: {:?}", push.kind, push.data);
+    ///                 Ok(())
+    ///             })
+    ///             .map_err(|_| unreachable!())
+    ///     });
+    /// # }
+    /// ```
+    pub fn subscribe<T: ToRedisArgs>(self, channels: T) -> RedisFuture<(Self, ())> {
+        self.send_subscription("SUBSCRIBE", channels)
+    }
+
+    /// Subscribes to channels matching patterns, see `subscribe`.
+    pub fn psubscribe<T: ToRedisArgs>(self, patterns: T) -> RedisFuture<(Self, ())> {
+        self.send_subscription("PSUBSCRIBE", patterns)
+    }
+
+    /// Unsubscribes from channels.  Unlike the `UNSUBSCRIBE` command the
+    /// channels have to be given.
+    pub fn unsubscribe<T: ToRedisArgs>(self, channels: T) -> RedisFuture<(Self, ())> {
+        self.send_subscription("UNSUBSCRIBE", channels)
+    }
+
+    /// Unsubscribes from patterns, see `unsubscribe`.
+    pub fn punsubscribe<T: ToRedisArgs>(self, patterns: T) -> RedisFuture<(Self, ())> {
+        self.send_subscription("PUNSUBSCRIBE", patterns)
+    }
+
+    fn send_subscription<T: ToRedisArgs>(self, name: &str, channels: T) -> RedisFuture<(Self, ())> {
+        if self.protocol != ProtocolVersion::RESP3 {
+            return Box::new(future::err(RedisError::from((
+                ErrorKind::InvalidClientConfig,
+                "Subscriptions on a multiplexed connection require RESP3",
+            ))));
+        }
+        // the server confirms every channel with a push message
+        let (packed, count) = match pack_subscription(name, channels) {
+            Ok(rv) => rv,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        Box::new(
+            self.pipeline
+                .send_subscription(packed, count)
+                .map(|()| (self, ())),
+        )
+    }
 }
 
-impl ConnectionLike for SharedConnection {
+impl ConnectionLike for MultiplexedConnection {
     fn req_packed_command(self, cmd: Vec<u8>) -> RedisFuture<(Self, Value)> {
         #[cfg(not(feature = "with-unix-sockets"))]
         let future = match self.pipeline {
-            ActualPipeline::Tcp(ref pipeline) => pipeline.send(cmd),
+            ActualPipeline::Tcp(ref pipeline) => Either::A(pipeline.send(cmd)),
+            ActualPipeline::Custom(ref pipeline) => Either::B(pipeline.send(cmd)),
         };
 
         #[cfg(feature = "with-unix-sockets")]
         let future = match self.pipeline {
-            ActualPipeline::Tcp(ref pipeline) => Either::A(pipeline.send(cmd)),
-            ActualPipeline::Unix(ref pipeline) => Either::B(pipeline.send(cmd)),
+            ActualPipeline::Tcp(ref pipeline) => Either::A(Either::A(pipeline.send(cmd))),
+            ActualPipeline::Unix(ref pipeline) => Either::A(Either::B(pipeline.send(cmd))),
+            ActualPipeline::Custom(ref pipeline) => Either::B(pipeline.send(cmd)),
         };
 
-        Box::new(future.map(|value| (self, value)).map_err(|err| {
+        let timeout = self.response_timeout;
+        let future = future.map(|value| (self, value)).map_err(|err| {
             err.unwrap_or_else(|| RedisError::from(io::Error::from(io::ErrorKind::BrokenPipe)))
-        }))
+        });
+        with_timeout(Box::new(future), timeout)
     }
 
     fn req_packed_commands(
@@ -541,34 +1623,808 @@ impl ConnectionLike for SharedConnection {
     ) -> RedisFuture<(Self, Vec<Value>)> {
         #[cfg(not(feature = "with-unix-sockets"))]
         let future = match self.pipeline {
-            ActualPipeline::Tcp(ref pipeline) => pipeline.send_recv_multiple(cmd, offset + count),
+            ActualPipeline::Tcp(ref pipeline) => {
+                Either::A(pipeline.send_recv_multiple(cmd, offset + count))
+            }
+            ActualPipeline::Custom(ref pipeline) => {
+                Either::B(pipeline.send_recv_multiple(cmd, offset + count))
+            }
         };
 
         #[cfg(feature = "with-unix-sockets")]
         let future = match self.pipeline {
             ActualPipeline::Tcp(ref pipeline) => {
-                Either::A(pipeline.send_recv_multiple(cmd, offset + count))
+                Either::A(Either::A(pipeline.send_recv_multiple(cmd, offset + count)))
             }
             ActualPipeline::Unix(ref pipeline) => {
+                Either::A(Either::B(pipeline.send_recv_multiple(cmd, offset + count)))
+            }
+            ActualPipeline::Custom(ref pipeline) => {
                 Either::B(pipeline.send_recv_multiple(cmd, offset + count))
             }
         };
 
-        Box::new(
-            future
-                .map(move |mut value| {
-                    value.drain(..offset);
-                    (self, value)
+        let timeout = self.response_timeout;
+        let future = future
+            .map(move |mut value| {
+                value.drain(..offset);
+                (self, value)
+            })
+            .map_err(|err| {
+                err.unwrap_or_else(|| RedisError::from(io::Error::from(io::ErrorKind::BrokenPipe)))
+            });
+        with_timeout(Box::new(future), timeout)
+    }
+
+    fn get_db(&self) -> i64 {
+        self.db
+    }
+}
+
+/// An async connection in subscribed state whose messages are consumed as
+/// a `Stream`.
+///
+/// The connection is driven by a background task that is spawned on the
+/// current tokio executor, so channels can be subscribed and unsubscribed
+/// while the stream is consumed, through a `PubSubSink` from `sink`.
+/// Unlike the subscriptions of `MultiplexedConnection` this works with
+/// RESP2 as well.  The stream ends when the connection is closed; after an
+/// IO error it yields the error first.
+///
+/// ```rust,no_run
+/// # extern crate futures;
+/// # extern crate redis;
+/// use futures::{Future, Stream};
+///
+/// # fn main() {
+/// let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+/// let messages = client.get_async_pubsub().and_then(|pubsub| {
+///     let sink = pubsub.sink();
+///     sink.subscribe("news")
+///         .and_then(move |()| sink.psubscribe("news.*"))
+///         .and_then(|()| {
+///             pubsub.for_each(|msg| {
+///                 let payload: String = msg.get_payload()?;
+///                 println!("{}: {}", msg.get_channel_name(), payload);
+///                 Ok(())
+///             })
+///         })
+/// });
+/// # }
+/// ```
+pub struct PubSub {
+    sink: PubSubSink,
+    messages: Arc<Mutex<MessageQueue>>,
+}
+
+/// Subscribes and unsubscribes the channels of a `PubSub`.  It can be
+/// cloned and used while the messages are consumed.
+#[derive(Clone)]
+pub struct PubSubSink {
+    pipeline: ActualPipeline,
+    // the number of `Subscription` handles of every channel, keyed by the
+    // command that unsubscribes it
+    handles: Arc<Mutex<HashMap<(&'static str, Vec<u8>), usize>>>,
+}
+
+impl PubSub {
+    /// Puts an async connection into subscribed state.  This has to be
+    /// called from within a tokio executor.  The connection keeps its push
+    /// sender for other push messages, like invalidations.
+    pub fn new(con: Connection) -> impl Future<Item = Self, Error = RedisError> {
+        PubSub::with_config(con, PubSubConfig::default())
+    }
+
+    /// Puts an async connection into subscribed state with a message
+    /// buffer configured by `config`.
+    pub fn with_config(
+        mut con: Connection,
+        config: PubSubConfig,
+    ) -> impl Future<Item = Self, Error = RedisError> {
+        future::lazy(move || {
+            let messages = Arc::new(Mutex::new(MessageQueue {
+                config,
+                messages: VecDeque::new(),
+                dropped: 0,
+                overflowed: false,
+                closed: false,
+                task: None,
+            }));
+            let push_sender = Arc::new(Mutex::new(con.push_sender.take()));
+            let pipeline = ActualPipeline::new(
+                con,
+                AutoPipelineConfig::default(),
+                push_sender,
+                Some(MessageSender(messages.clone())),
+            );
+            Ok(PubSub {
+                sink: PubSubSink {
+                    pipeline,
+                    handles: Arc::new(Mutex::new(HashMap::new())),
+                },
+                messages,
+            })
+        })
+    }
+
+    /// Returns the number of messages that were dropped because the
+    /// buffer was full, which tells how far a slow consumer lags behind.
+    pub fn dropped_messages(&self) -> u64 {
+        self.messages.lock().unwrap().dropped
+    }
+
+    /// Returns the number of messages in the buffer.
+    pub fn buffered_messages(&self) -> usize {
+        self.messages.lock().unwrap().messages.len()
+    }
+
+    /// Returns a handle to subscribe and unsubscribe while the messages
+    /// are consumed.
+    pub fn sink(&self) -> PubSubSink {
+        self.sink.clone()
+    }
+
+    /// Subscribes to channels, see `PubSubSink::subscribe`.
+    pub fn subscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.sink.subscribe(channels)
+    }
+
+    /// Subscribes to channels matching patterns, see
+    /// `PubSubSink::psubscribe`.
+    pub fn psubscribe<T: ToRedisArgs>(&self, patterns: T) -> RedisFuture<()> {
+        self.sink.psubscribe(patterns)
+    }
+
+    /// Unsubscribes from channels, see `PubSubSink::unsubscribe`.
+    pub fn unsubscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.sink.unsubscribe(channels)
+    }
+
+    /// Unsubscribes from patterns, see `PubSubSink::punsubscribe`.
+    pub fn punsubscribe<T: ToRedisArgs>(&self, patterns: T) -> RedisFuture<()> {
+        self.sink.punsubscribe(patterns)
+    }
+
+    /// Subscribes to shard channels, see `PubSubSink::ssubscribe`.
+    pub fn ssubscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.sink.ssubscribe(channels)
+    }
+
+    /// Unsubscribes from shard channels, see `PubSubSink::sunsubscribe`.
+    pub fn sunsubscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.sink.sunsubscribe(channels)
+    }
+
+    /// Subscribes to channels until the returned handle is dropped, see
+    /// `PubSubSink::subscribe_handle`.
+    pub fn subscribe_handle<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<Subscription> {
+        self.sink.subscribe_handle(channels)
+    }
+
+    /// Subscribes to patterns until the returned handle is dropped, see
+    /// `PubSubSink::subscribe_handle`.
+    pub fn psubscribe_handle<T: ToRedisArgs>(&self, patterns: T) -> RedisFuture<Subscription> {
+        self.sink.psubscribe_handle(patterns)
+    }
+
+    /// Subscribes to shard channels until the returned handle is dropped,
+    /// see `PubSubSink::subscribe_handle`.
+    pub fn ssubscribe_handle<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<Subscription> {
+        self.sink.ssubscribe_handle(channels)
+    }
+}
+
+impl Stream for PubSub {
+    type Item = Msg;
+    type Error = RedisError;
+
+    fn poll(&mut self) -> Poll<Option<Msg>, RedisError> {
+        let mut queue = self.messages.lock().unwrap();
+        if queue.overflowed {
+            queue.overflowed = false;
+            fail!((
+                ErrorKind::BufferFull,
+                "Messages were dropped because the buffer is full"
+            ));
+        }
+        match queue.messages.pop_front() {
+            Some(Ok(msg)) => Ok(Async::Ready(Some(msg))),
+            Some(Err(err)) => Err(err),
+            None if queue.closed => Ok(Async::Ready(None)),
+            None => {
+                queue.task = Some(task::current());
+                Ok(Async::NotReady)
+            }
+        }
+    }
+}
+
+impl PubSubSink {
+    /// Subscribes to channels.  The future resolves once the server
+    /// confirmed all of them, later messages of the channels are yielded
+    /// by the `PubSub` stream.
+    pub fn subscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.send_subscription("SUBSCRIBE", channels)
+    }
+
+    /// Subscribes to channels matching patterns.
+    pub fn psubscribe<T: ToRedisArgs>(&self, patterns: T) -> RedisFuture<()> {
+        self.send_subscription("PSUBSCRIBE", patterns)
+    }
+
+    /// Unsubscribes from channels.  Unlike the `UNSUBSCRIBE` command the
+    /// channels have to be given.
+    pub fn unsubscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.send_subscription("UNSUBSCRIBE", channels)
+    }
+
+    /// Unsubscribes from patterns, see `unsubscribe`.
+    pub fn punsubscribe<T: ToRedisArgs>(&self, patterns: T) -> RedisFuture<()> {
+        self.send_subscription("PUNSUBSCRIBE", patterns)
+    }
+
+    /// Subscribes to shard channels (redis 7).  Their messages are
+    /// distinguished by `Msg::from_shard_channel`.
+    pub fn ssubscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.send_subscription("SSUBSCRIBE", channels)
+    }
+
+    /// Unsubscribes from shard channels, see `unsubscribe`.
+    pub fn sunsubscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.send_subscription("SUNSUBSCRIBE", channels)
+    }
+
+    /// Subscribes to channels and returns a handle that unsubscribes from
+    /// them when it is dropped.  Several handles of the same channel can
+    /// exist at a time, the channel is unsubscribed once the last of them
+    /// is dropped.  Handles can be created and dropped from any thread
+    /// while the messages are consumed.
+    ///
+    /// Dropping a handle sends `UNSUBSCRIBE` from a task spawned on the
+    /// current tokio executor; outside of an executor use
+    /// `Subscription::unsubscribe` instead.  The channels of handles are
+    /// counted separately from `subscribe` and `unsubscribe`, which should
+    /// not be used for the same channels.
+    ///
+    /// ```rust,no_run
+    /// # extern crate futures;
+    /// # extern crate redis;
+    /// use futures::{Future, Stream};
+    ///
+    /// # fn main() {
+    /// let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    /// let messages = client.get_async_pubsub().and_then(|pubsub| {
+    ///     pubsub.subscribe_handle("news").and_then(|handle| {
+    ///         // unsubscribes after the first message
+    ///         pubsub.take(1).for_each(move |_msg| {
+    ///             let _ = &handle;
+    ///             Ok(())
+    ///         })
+    ///     })
+    /// });
+    /// # }
+    /// ```
+    pub fn subscribe_handle<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<Subscription> {
+        self.acquire("SUBSCRIBE", "UNSUBSCRIBE", channels)
+    }
+
+    /// Subscribes to patterns until the returned handle is dropped, see
+    /// `subscribe_handle`.
+    pub fn psubscribe_handle<T: ToRedisArgs>(&self, patterns: T) -> RedisFuture<Subscription> {
+        self.acquire("PSUBSCRIBE", "PUNSUBSCRIBE", patterns)
+    }
+
+    /// Subscribes to shard channels until the returned handle is dropped,
+    /// see `subscribe_handle`.
+    pub fn ssubscribe_handle<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<Subscription> {
+        self.acquire("SSUBSCRIBE", "SUNSUBSCRIBE", channels)
+    }
+
+    // Counts a handle for the channels and subscribes to those that had
+    // none.
+    fn acquire<T: ToRedisArgs>(
+        &self,
+        subscribe: &str,
+        unsubscribe: &'static str,
+        channels: T,
+    ) -> RedisFuture<Subscription> {
+        let names = channels.to_redis_args();
+        let new: Vec<Vec<u8>> = {
+            let mut handles = self.handles.lock().unwrap();
+            names
+                .iter()
+                .filter(|name| {
+                    let count = handles.entry((unsubscribe, name.to_vec())).or_insert(0);
+                    *count += 1;
+                    *count == 1
                 })
-                .map_err(|err| {
-                    err.unwrap_or_else(|| {
-                        RedisError::from(io::Error::from(io::ErrorKind::BrokenPipe))
-                    })
-                }),
+                .cloned()
+                .collect()
+        };
+        let subscription = Subscription {
+            sink: self.clone(),
+            unsubscribe,
+            names,
+        };
+        if new.is_empty() {
+            return Box::new(future::ok(subscription));
+        }
+        // after a failure the handle is dropped, which releases the channels
+        Box::new(
+            self.send_subscription(subscribe, new)
+                .map(move |()| subscription),
         )
     }
 
+    // Forgets a handle of the channels and returns those without handles.
+    fn release(&self, unsubscribe: &'static str, names: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+        let mut handles = self.handles.lock().unwrap();
+        names
+            .into_iter()
+            .filter(|name| {
+                let key = (unsubscribe, name.clone());
+                let unused = match handles.get_mut(&key) {
+                    Some(count) => {
+                        *count -= 1;
+                        *count == 0
+                    }
+                    None => false,
+                };
+                if unused {
+                    handles.remove(&key);
+                }
+                unused
+            })
+            .collect()
+    }
+
+    fn send_subscription<T: ToRedisArgs>(&self, name: &str, channels: T) -> RedisFuture<()> {
+        match pack_subscription(name, channels) {
+            Ok((packed, count)) => self.pipeline.send_subscription(packed, count),
+            Err(err) => Box::new(future::err(err)),
+        }
+    }
+}
+
+/// A handle of subscribed channels, see `PubSubSink::subscribe_handle`.
+/// The channels are unsubscribed when the last handle of them is dropped.
+pub struct Subscription {
+    sink: PubSubSink,
+    unsubscribe: &'static str,
+    names: Vec<Vec<u8>>,
+}
+
+impl Subscription {
+    /// Returns the channels or patterns of the handle.
+    pub fn channels(&self) -> &[Vec<u8>] {
+        &self.names
+    }
+
+    /// Drops the handle and resolves once the channels without other
+    /// handles are unsubscribed.
+    pub fn unsubscribe(mut self) -> RedisFuture<()> {
+        match self.release() {
+            Some(future) => future,
+            None => Box::new(future::ok(())),
+        }
+    }
+
+    fn release(&mut self) -> Option<RedisFuture<()>> {
+        let names = mem::replace(&mut self.names, vec![]);
+        let unused = self.sink.release(self.unsubscribe, names);
+        if unused.is_empty() {
+            None
+        } else {
+            Some(self.sink.send_subscription(self.unsubscribe, unused))
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(future) = self.release() {
+            try_spawn(future.map_err(|_| ()));
+        }
+    }
+}
+
+/// An event of a `PubSubManager`.
+pub enum PubSubEvent {
+    /// A message of a subscribed channel or pattern.
+    Message(Msg),
+    /// The connection broke and a new one subscribed to all channels and
+    /// patterns again.  Messages published in between were lost.
+    Resubscribed,
+}
+
+// The channels and patterns a `PubSubManager` is subscribed to.
+struct Subscriptions {
+    sink: PubSubSink,
+    channels: BTreeSet<Vec<u8>>,
+    patterns: BTreeSet<Vec<u8>>,
+    shard_channels: BTreeSet<Vec<u8>>,
+}
+
+/// A `PubSub` that reconnects on its own.
+///
+/// The manager keeps track of the subscribed channels and patterns.  Once
+/// the connection breaks, it connects again (including authentication and
+/// database selection), subscribes to all of them and yields
+/// `PubSubEvent::Resubscribed`, so consumers know that messages may have
+/// been missed.  If the client has a retry policy (see
+/// `Client::set_retry_policy`) failed attempts to reconnect are retried
+/// according to it, otherwise the stream yields the error and tries again
+/// when it is polled the next time.
+///
+/// ```rust,no_run
+/// # extern crate futures;
+/// # extern crate redis;
+/// use futures::{Future, Stream};
+/// use redis::aio::{PubSubEvent, PubSubManager};
+///
+/// # fn main() {
+/// let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+/// let events = PubSubManager::new(client).and_then(|manager| {
+///     manager.subscribe("news").and_then(|()| {
+///         manager.for_each(|event| {
+///             match event {
+///                 PubSubEvent::Message(msg) => println!("{}", msg.get_channel_name()),
+///                 PubSubEvent::Resubscribed => println!("reconnected, messages may be lost"),
+///             }
+///             Ok(())
+///         })
+///     })
+/// });
+/// # }
+/// ```
+pub struct PubSubManager {
+    sink: PubSubManagerSink,
+    pubsub: PubSub,
+    reconnecting: Option<RedisFuture<PubSub>>,
+    attempt: u32,
+}
+
+/// Subscribes and unsubscribes the channels of a `PubSubManager`.  It can
+/// be cloned and used while the events are consumed.
+#[derive(Clone)]
+pub struct PubSubManagerSink {
+    client: Client,
+    subscriptions: Arc<Mutex<Subscriptions>>,
+}
+
+impl PubSubManager {
+    /// Connects to the server described by the client.  This has to be
+    /// called from within a tokio executor.
+    pub fn new(client: Client) -> impl Future<Item = Self, Error = RedisError> {
+        client.get_async_pubsub().map(move |pubsub| PubSubManager {
+            sink: PubSubManagerSink {
+                client,
+                subscriptions: Arc::new(Mutex::new(Subscriptions {
+                    sink: pubsub.sink(),
+                    channels: BTreeSet::new(),
+                    patterns: BTreeSet::new(),
+                    shard_channels: BTreeSet::new(),
+                })),
+            },
+            pubsub,
+            reconnecting: None,
+            attempt: 0,
+        })
+    }
+
+    /// Returns a handle to subscribe and unsubscribe while the events are
+    /// consumed.
+    pub fn sink(&self) -> PubSubManagerSink {
+        self.sink.clone()
+    }
+
+    /// Subscribes to channels, see `PubSubManagerSink::subscribe`.
+    pub fn subscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.sink.subscribe(channels)
+    }
+
+    /// Subscribes to channels matching patterns, see
+    /// `PubSubManagerSink::psubscribe`.
+    pub fn psubscribe<T: ToRedisArgs>(&self, patterns: T) -> RedisFuture<()> {
+        self.sink.psubscribe(patterns)
+    }
+
+    /// Unsubscribes from channels, see `PubSubManagerSink::unsubscribe`.
+    pub fn unsubscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.sink.unsubscribe(channels)
+    }
+
+    /// Unsubscribes from patterns, see `PubSubManagerSink::punsubscribe`.
+    pub fn punsubscribe<T: ToRedisArgs>(&self, patterns: T) -> RedisFuture<()> {
+        self.sink.punsubscribe(patterns)
+    }
+
+    /// Subscribes to shard channels, see `PubSubManagerSink::ssubscribe`.
+    pub fn ssubscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.sink.ssubscribe(channels)
+    }
+
+    /// Unsubscribes from shard channels, see
+    /// `PubSubManagerSink::sunsubscribe`.
+    pub fn sunsubscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.sink.sunsubscribe(channels)
+    }
+}
+
+impl Stream for PubSubManager {
+    type Item = PubSubEvent;
+    type Error = RedisError;
+
+    fn poll(&mut self) -> Poll<Option<PubSubEvent>, RedisError> {
+        loop {
+            if let Some(mut reconnecting) = self.reconnecting.take() {
+                match reconnecting.poll() {
+                    Ok(Async::Ready(pubsub)) => {
+                        self.sink.subscriptions.lock().unwrap().sink = pubsub.sink();
+                        self.pubsub = pubsub;
+                        self.attempt = 0;
+                        return Ok(Async::Ready(Some(PubSubEvent::Resubscribed)));
+                    }
+                    Ok(Async::NotReady) => {
+                        self.reconnecting = Some(reconnecting);
+                        return Ok(Async::NotReady);
+                    }
+                    // the next poll tries again
+                    Err(err) => return Err(err),
+                }
+            }
+            match self.pubsub.poll() {
+                Ok(Async::Ready(Some(msg))) => {
+                    return Ok(Async::Ready(Some(PubSubEvent::Message(msg))))
+                }
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                // the connection broke or was closed
+                Ok(Async::Ready(None)) | Err(_) => {
+                    self.attempt += 1;
+                    self.reconnecting = Some(self.sink.resubscribe(self.attempt));
+                }
+            }
+        }
+    }
+}
+
+impl PubSubManagerSink {
+    /// Subscribes to channels.  The channels are subscribed again after
+    /// the connection broke, even if this request failed because of it.
+    pub fn subscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.send_subscription("SUBSCRIBE", channels, |subs| &mut subs.channels, true)
+    }
+
+    /// Subscribes to channels matching patterns, see `subscribe`.
+    pub fn psubscribe<T: ToRedisArgs>(&self, patterns: T) -> RedisFuture<()> {
+        self.send_subscription("PSUBSCRIBE", patterns, |subs| &mut subs.patterns, true)
+    }
+
+    /// Unsubscribes from channels.  Unlike the `UNSUBSCRIBE` command the
+    /// channels have to be given.
+    pub fn unsubscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.send_subscription("UNSUBSCRIBE", channels, |subs| &mut subs.channels, false)
+    }
+
+    /// Unsubscribes from patterns, see `unsubscribe`.
+    pub fn punsubscribe<T: ToRedisArgs>(&self, patterns: T) -> RedisFuture<()> {
+        self.send_subscription("PUNSUBSCRIBE", patterns, |subs| &mut subs.patterns, false)
+    }
+
+    /// Subscribes to shard channels (redis 7), see `subscribe`.
+    pub fn ssubscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.send_subscription(
+            "SSUBSCRIBE",
+            channels,
+            |subs| &mut subs.shard_channels,
+            true,
+        )
+    }
+
+    /// Unsubscribes from shard channels, see `unsubscribe`.
+    pub fn sunsubscribe<T: ToRedisArgs>(&self, channels: T) -> RedisFuture<()> {
+        self.send_subscription(
+            "SUNSUBSCRIBE",
+            channels,
+            |subs| &mut subs.shard_channels,
+            false,
+        )
+    }
+
+    fn send_subscription<T, F>(&self, name: &str, channels: T, set: F, add: bool) -> RedisFuture<()>
+    where
+        T: ToRedisArgs,
+        F: FnOnce(&mut Subscriptions) -> &mut BTreeSet<Vec<u8>>,
+    {
+        let channels = channels.to_redis_args();
+        let sink = {
+            let mut guard = self.subscriptions.lock().unwrap();
+            {
+                let set = set(&mut guard);
+                for channel in &channels {
+                    if add {
+                        set.insert(channel.clone());
+                    } else {
+                        set.remove(channel);
+                    }
+                }
+            }
+            guard.sink.clone()
+        };
+        sink.send_subscription(name, channels)
+    }
+
+    // Connects a new `PubSub` and subscribes to all channels and patterns.
+    fn resubscribe(&self, attempt: u32) -> RedisFuture<PubSub> {
+        let this = self.clone();
+        let connect = self.client.get_async_pubsub().and_then(move |pubsub| {
+            let subscriptions = {
+                let guard = this.subscriptions.lock().unwrap();
+                vec![
+                    (
+                        "SUBSCRIBE",
+                        guard.channels.iter().cloned().collect::<Vec<_>>(),
+                    ),
+                    ("PSUBSCRIBE", guard.patterns.iter().cloned().collect()),
+                    ("SSUBSCRIBE", guard.shard_channels.iter().cloned().collect()),
+                ]
+            };
+            let requests: Vec<_> = subscriptions
+                .into_iter()
+                .filter(|&(_, ref names)| !names.is_empty())
+                .map(|(name, names)| pubsub.sink.send_subscription(name, names))
+                .collect();
+            future::join_all(requests).map(move |_| pubsub)
+        });
+        let this = self.clone();
+        Box::new(connect.or_else(move |err| {
+            let delay = match this.client.retry_policy() {
+                Some(policy) if policy.should_retry(&err, attempt) => policy.delay(attempt),
+                _ => return Either::A(future::err(err)),
+            };
+            Either::B(Delay::new(delay).then(move |_| this.resubscribe(attempt + 1)))
+        }))
+    }
+}
+
+type SharedMultiplexedConnection = Shared<RedisFuture<MultiplexedConnection>>;
+
+/// A multiplexed connection that reconnects on its own.
+///
+/// Requests are sent over a `MultiplexedConnection`.  Once a request fails
+/// with an IO error, the connection is considered broken and a new one is
+/// established (including authentication and database selection) in the
+/// background, so later requests go over the new connection.  Requests that
+/// were in flight when the connection broke fail with the IO error and can
+/// simply be retried.  If the client has a retry policy (see
+/// `Client::set_retry_policy`) the manager does that automatically.
+///
+/// The manager can be cloned cheaply and all clones share the same
+/// underlying connection.
+///
+/// ```rust,no_run
+/// # extern crate futures;
+/// # extern crate redis;
+/// use futures::Future;
+///
+/// # fn main() {
+/// let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+/// let future = client.get_connection_manager().and_then(|manager| {
+///     redis::cmd("PING").query_async(manager).map(|(_, pong): (_, String)| pong)
+/// });
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ConnectionManager {
+    client: Client,
+    // The generation is bumped on every reconnect so that concurrent
+    // failures of the same connection only trigger a single reconnect.
+    connection: Arc<Mutex<(usize, SharedMultiplexedConnection)>>,
+}
+
+impl ConnectionManager {
+    /// Connects to the server described by the client and returns a
+    /// manager for the connection.  This has to be called from within a
+    /// tokio executor.
+    pub fn new(client: Client) -> impl Future<Item = Self, Error = RedisError> {
+        client
+            .get_multiplexed_async_connection()
+            .map(move |connection| {
+                let connection: RedisFuture<MultiplexedConnection> =
+                    Box::new(future::ok(connection));
+                ConnectionManager {
+                    client,
+                    connection: Arc::new(Mutex::new((0, connection.shared()))),
+                }
+            })
+    }
+
+    fn current(&self) -> (usize, SharedMultiplexedConnection) {
+        let guard = self.connection.lock().unwrap();
+        (guard.0, guard.1.clone())
+    }
+
+    // Replaces the connection of the given generation with a new one.  If
+    // another request already replaced it nothing happens.
+    fn reconnect(&self, generation: usize) {
+        let mut guard = self.connection.lock().unwrap();
+        if guard.0 != generation {
+            return;
+        }
+        let observer = self.client.metrics_observer().cloned();
+        let addr = self.client.get_connection_info().addr.clone();
+        let connection: RedisFuture<MultiplexedConnection> = Box::new(
+            self.client
+                .get_multiplexed_async_connection()
+                .map(move |con| {
+                    if let Some(observer) = observer {
+                        observer.reconnected(&addr);
+                    }
+                    con
+                }),
+        );
+        *guard = (generation.wrapping_add(1), connection.shared());
+    }
+
+    fn with_connection<T, F, R>(self, f: F) -> RedisFuture<(Self, T)>
+    where
+        T: Send + 'static,
+        F: Fn(MultiplexedConnection) -> R + Send + Sync + 'static,
+        R: Future<Item = (MultiplexedConnection, T), Error = RedisError> + Send + 'static,
+    {
+        self.attempt(Arc::new(f), 1)
+    }
+
+    // Runs `f` on the current connection and, if the client has a retry
+    // policy that allows it, runs it again after failures.
+    fn attempt<T, F, R>(self, f: Arc<F>, attempt: u32) -> RedisFuture<(Self, T)>
+    where
+        T: Send + 'static,
+        F: Fn(MultiplexedConnection) -> R + Send + Sync + 'static,
+        R: Future<Item = (MultiplexedConnection, T), Error = RedisError> + Send + 'static,
+    {
+        let (generation, connection) = self.current();
+        let request = f.clone();
+        // A failed connection future stays failed, so it has to be replaced
+        // whatever the error was.  Failed requests only call for a new
+        // connection if the connection itself broke.
+        let result = connection.then(move |result| match result {
+            Ok(connection) => {
+                Either::B((*request)((*connection).clone()).map_err(|err| (false, err)))
+            }
+            Err(err) => Either::A(future::err((true, clone_error(&*err)))),
+        });
+        Box::new(result.then(move |result| match result {
+            Ok((_, value)) => Either::A(future::ok((self, value))),
+            Err((connect_failed, err)) => {
+                if connect_failed || err.is_io_error() {
+                    self.reconnect(generation);
+                }
+                let delay = match self.client.retry_policy() {
+                    Some(policy) if policy.should_retry(&err, attempt) => policy.delay(attempt),
+                    _ => return Either::A(future::err(err)),
+                };
+                Either::B(Delay::new(delay).then(move |_| self.attempt(f, attempt + 1)))
+            }
+        }))
+    }
+}
+
+impl ConnectionLike for ConnectionManager {
+    fn req_packed_command(self, cmd: Vec<u8>) -> RedisFuture<(Self, Value)> {
+        self.with_connection(move |con| con.req_packed_command(cmd.clone()))
+    }
+
+    fn req_packed_commands(
+        self,
+        cmd: Vec<u8>,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<(Self, Vec<Value>)> {
+        self.with_connection(move |con| con.req_packed_commands(cmd.clone(), offset, count))
+    }
+
     fn get_db(&self) -> i64 {
-        self.db
+        ::connection::ConnectionLike::get_db(&self.client)
     }
 }
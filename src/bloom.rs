@@ -0,0 +1,200 @@
+//! Replies of the probabilistic data structures of the RedisBloom module
+//! (`with-bloom` feature).
+//!
+//! The commands are part of `Commands`: `bf_*` for Bloom filters, `cf_*`
+//! for Cuckoo filters, `cms_*` for Count-Min sketches, `topk_*` for Top-K
+//! lists and `tdigest_*` for t-digests.  The `*_info` commands return the
+//! types of this module:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::bloom::BloomInfo;
+//! use redis::Commands;
+//!
+//! # let client = redis::Client::open("redis://127.0.0.1/")?;
+//! # let mut con = client.get_connection()?;
+//! let _: () = con.bf_reserve("seen", 0.001, 100_000)?;
+//! let added: Vec<bool> = con.bf_madd("seen", &["a", "b", "a"])?;
+//! assert_eq!(added, vec![true, true, false]);
+//! let info: BloomInfo = con.bf_info("seen")?;
+//! println!("{} items in {} bytes", info.items, info.size);
+//! # Ok(()) }
+//! ```
+use std::collections::HashMap;
+
+use types::{from_redis_value, ErrorKind, FromRedisValue, RedisError, RedisResult, Value};
+
+/// The reply of `BF.INFO`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BloomInfo {
+    /// The number of items the filter can hold before it scales or
+    /// fills up.
+    pub capacity: u64,
+    /// The memory used in bytes.
+    pub size: u64,
+    /// The number of sub-filters, which grows as the filter scales.
+    pub filters: u64,
+    /// The number of items added.
+    pub items: u64,
+    /// The factor by which each new sub-filter grows, none for filters
+    /// that do not scale.
+    pub expansion_rate: Option<u64>,
+}
+
+impl FromRedisValue for BloomInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<BloomInfo> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(BloomInfo {
+            capacity: field(&map, "Capacity")?,
+            size: field(&map, "Size")?,
+            filters: field(&map, "Number of filters")?,
+            items: field(&map, "Number of items inserted")?,
+            expansion_rate: optional_field(&map, "Expansion rate")?,
+        })
+    }
+}
+
+/// The reply of `CF.INFO`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CuckooInfo {
+    /// The memory used in bytes.
+    pub size: u64,
+    /// The number of buckets.
+    pub buckets: u64,
+    /// The number of sub-filters.
+    pub filters: u64,
+    /// The number of items added.
+    pub items_inserted: u64,
+    /// The number of items deleted.
+    pub items_deleted: u64,
+    /// The number of items in each bucket.
+    pub bucket_size: u64,
+    /// The factor by which each new sub-filter grows.
+    pub expansion_rate: u64,
+    /// How often items are swapped before the filter grows.
+    pub max_iterations: u64,
+}
+
+impl FromRedisValue for CuckooInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<CuckooInfo> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(CuckooInfo {
+            size: field(&map, "Size")?,
+            buckets: field(&map, "Number of buckets")?,
+            filters: field(&map, "Number of filters")?,
+            items_inserted: field(&map, "Number of items inserted")?,
+            items_deleted: field(&map, "Number of items deleted")?,
+            bucket_size: field(&map, "Bucket size")?,
+            expansion_rate: field(&map, "Expansion rate")?,
+            max_iterations: field(&map, "Max iterations")?,
+        })
+    }
+}
+
+/// The reply of `CMS.INFO`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CmsInfo {
+    /// The number of counters in each row.
+    pub width: u64,
+    /// The number of rows.
+    pub depth: u64,
+    /// The sum of all increments.
+    pub count: u64,
+}
+
+impl FromRedisValue for CmsInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<CmsInfo> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(CmsInfo {
+            width: field(&map, "width")?,
+            depth: field(&map, "depth")?,
+            count: field(&map, "count")?,
+        })
+    }
+}
+
+/// The reply of `TOPK.INFO`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TopKInfo {
+    /// The number of items kept.
+    pub k: u64,
+    /// The number of counters in each row of the sketch.
+    pub width: u64,
+    /// The number of rows of the sketch.
+    pub depth: u64,
+    /// The probability by which counters of other items decay.
+    pub decay: f64,
+}
+
+impl FromRedisValue for TopKInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<TopKInfo> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(TopKInfo {
+            k: field(&map, "k")?,
+            width: field(&map, "width")?,
+            depth: field(&map, "depth")?,
+            decay: field(&map, "decay")?,
+        })
+    }
+}
+
+/// The reply of `TDIGEST.INFO`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TDigestInfo {
+    /// The trade-off between accuracy and memory.
+    pub compression: u64,
+    /// The number of centroids that can be held.
+    pub capacity: u64,
+    /// The number of merged centroids.
+    pub merged_nodes: u64,
+    /// The number of values not merged yet.
+    pub unmerged_nodes: u64,
+    /// The weight of the merged centroids.
+    pub merged_weight: f64,
+    /// The weight of the values not merged yet.
+    pub unmerged_weight: f64,
+    /// The number of values added.
+    pub observations: u64,
+    /// The number of times the values were merged.
+    pub total_compressions: u64,
+    /// The memory used in bytes.
+    pub memory_usage: u64,
+}
+
+impl FromRedisValue for TDigestInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<TDigestInfo> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(TDigestInfo {
+            compression: field(&map, "Compression")?,
+            capacity: field(&map, "Capacity")?,
+            merged_nodes: field(&map, "Merged nodes")?,
+            unmerged_nodes: field(&map, "Unmerged nodes")?,
+            merged_weight: field(&map, "Merged weight")?,
+            unmerged_weight: field(&map, "Unmerged weight")?,
+            observations: field(&map, "Observations")?,
+            total_compressions: field(&map, "Total compressions")?,
+            memory_usage: field(&map, "Memory usage")?,
+        })
+    }
+}
+
+fn field<T: FromRedisValue>(map: &HashMap<String, Value>, name: &str) -> RedisResult<T> {
+    match map.get(name) {
+        Some(value) => from_redis_value(value),
+        None => Err(RedisError::from((
+            ErrorKind::TypeError,
+            "Info reply without field",
+            name.to_string(),
+        ))),
+    }
+}
+
+fn optional_field<T: FromRedisValue>(
+    map: &HashMap<String, Value>,
+    name: &str,
+) -> RedisResult<Option<T>> {
+    match map.get(name) {
+        Some(&Value::Nil) | None => Ok(None),
+        Some(value) => from_redis_value(value).map(Some),
+    }
+}
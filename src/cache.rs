@@ -0,0 +1,370 @@
+//! Cache-aside with protection against stampedes (`with-serde` feature).
+//!
+//! `Cache::get_or_set` returns the cached value of a key or computes,
+//! stores and returns it if the key is missing.  Only one client computes
+//! a missing value at a time: it holds a lock key next to the cached key
+//! while the others wait for the value to appear.  With `early_refresh`
+//! a value is recomputed by a single client shortly before it expires,
+//! while the others keep using the cached value, so popular keys never
+//! miss at all.
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use std::time::Duration;
+//! use redis::cache::Cache;
+//! use redis::codec::JsonCodec;
+//!
+//! # fn load_user_names() -> redis::RedisResult<Vec<String>> { Ok(vec![]) }
+//! # let client = redis::Client::open("redis://127.0.0.1/")?;
+//! # let mut con = client.get_connection()?;
+//! let cache: Cache<JsonCodec> = Cache::new().early_refresh(1.0);
+//! let names: Vec<String> =
+//!     cache.get_or_set(&mut con, "users:names", Duration::from_secs(300), load_user_names)?;
+//! # Ok(()) }
+//! ```
+//!
+//! The cached key is a hash with the encoded value and the time the
+//! computation took, which decides how early the value is refreshed.
+use std::fmt;
+use std::marker::PhantomData;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use cmd::{cmd, pipe, Cmd, Pipeline};
+use codec::Codec;
+use connection::ConnectionLike;
+use script::Script;
+use types::{random_u64, RedisError, RedisResult};
+
+#[cfg(feature = "aio")]
+use futures::future::{self, Either, Loop};
+#[cfg(feature = "aio")]
+use futures::Future;
+#[cfg(feature = "aio")]
+use runtime::Delay;
+#[cfg(feature = "aio")]
+use std::sync::Arc;
+#[cfg(feature = "aio")]
+use types::RedisFuture;
+
+// Deletes the lock only if it is still held by this client and did not
+// expire and get taken by another one in the meantime.
+const UNLOCK: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+";
+
+// The value, the time the computation took in milliseconds and the
+// milliseconds until the key expires.
+type Lookup = ((Option<Vec<u8>>, Option<u64>), i64);
+
+/// Caches values that are encoded with the codec `C`.
+///
+/// The cache holds only its settings, so one can be shared by all
+/// connections and keys.
+pub struct Cache<C> {
+    lock_timeout: Duration,
+    poll_interval: Duration,
+    early_refresh: Option<f64>,
+    unlock: Script,
+    codec: PhantomData<fn() -> C>,
+}
+
+impl<C> Clone for Cache<C> {
+    fn clone(&self) -> Cache<C> {
+        Cache {
+            lock_timeout: self.lock_timeout,
+            poll_interval: self.poll_interval,
+            early_refresh: self.early_refresh,
+            unlock: self.unlock.clone(),
+            codec: PhantomData,
+        }
+    }
+}
+
+impl<C> fmt::Debug for Cache<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("lock_timeout", &self.lock_timeout)
+            .field("poll_interval", &self.poll_interval)
+            .field("early_refresh", &self.early_refresh)
+            .finish()
+    }
+}
+
+impl<C: Codec> Cache<C> {
+    /// Creates a cache that holds locks for up to 10 seconds, polls for
+    /// values computed by other clients every 50 milliseconds and does not
+    /// refresh values early.
+    pub fn new() -> Cache<C> {
+        Cache {
+            lock_timeout: Duration::from_secs(10),
+            poll_interval: Duration::from_millis(50),
+            early_refresh: None,
+            unlock: Script::new(UNLOCK),
+            codec: PhantomData,
+        }
+    }
+
+    /// Sets how long a lock is held at most.  This should be longer than
+    /// any computation takes.  Clients that waited that long for another
+    /// one compute the value themselves.
+    pub fn lock_timeout(mut self, timeout: Duration) -> Cache<C> {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// Sets how often waiting clients check whether the value arrived.
+    pub fn poll_interval(mut self, interval: Duration) -> Cache<C> {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Refreshes values before they expire, with a probability that grows
+    /// as the expiry comes closer and with the time the computation took.
+    /// A `beta` of 1.0 is a good default, larger values refresh earlier.
+    pub fn early_refresh(mut self, beta: f64) -> Cache<C> {
+        self.early_refresh = Some(beta);
+        self
+    }
+
+    /// Returns the cached value of `key`, or computes it with `compute`
+    /// and caches it for `ttl`.  Errors of `compute` are returned and not
+    /// cached.
+    pub fn get_or_set<T, E, F>(
+        &self,
+        con: &mut ConnectionLike,
+        key: &str,
+        ttl: Duration,
+        compute: F,
+    ) -> Result<T, E>
+    where
+        T: Serialize + DeserializeOwned,
+        E: From<RedisError>,
+        F: FnOnce() -> Result<T, E>,
+    {
+        let deadline = Instant::now() + self.lock_timeout;
+        loop {
+            let lookup: Lookup = lookup_pipe(key).query(con)?;
+            let stale = match self.decide(lookup)? {
+                Some((value, false)) => return Ok(value),
+                found => found.map(|(value, _)| value),
+            };
+
+            let token = lock_token();
+            let locked: Option<String> = self.lock_cmd(key, &token).query(con)?;
+            if locked.is_some() {
+                let start = Instant::now();
+                let result = compute().and_then(|value| {
+                    self.store_pipe(key, &value, ttl, start.elapsed())?
+                        .query::<()>(con)?;
+                    Ok(value)
+                });
+                // a lock that is not released expires after `lock_timeout`
+                let _: RedisResult<i64> = self.unlock.key(lock_key(key)).arg(token).invoke(con);
+                return result;
+            }
+
+            // another client is computing the value
+            if let Some(value) = stale {
+                return Ok(value);
+            }
+            if Instant::now() >= deadline {
+                return compute();
+            }
+            sleep(self.poll_interval);
+        }
+    }
+
+    // Decodes the cached value, if any, and decides whether it is
+    // refreshed early.
+    fn decide<T: DeserializeOwned>(&self, lookup: Lookup) -> RedisResult<Option<(T, bool)>> {
+        let ((value, delta), ttl) = lookup;
+        let value = match value {
+            Some(bytes) => C::decode(&bytes)?,
+            None => return Ok(None),
+        };
+        let refresh = match self.early_refresh {
+            // XFetch: refresh when `delta * beta * -ln(rand)` reaches the
+            // time left, with `rand` uniform in (0, 1]
+            Some(beta) if ttl > 0 => {
+                let rand = ((random_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64;
+                delta.unwrap_or(0) as f64 * beta * -rand.ln() >= ttl as f64
+            }
+            _ => false,
+        };
+        Ok(Some((value, refresh)))
+    }
+
+    fn lock_cmd(&self, key: &str, token: &str) -> Cmd {
+        let mut cmd = cmd("SET");
+        cmd.arg(lock_key(key))
+            .arg(token)
+            .arg("NX")
+            .arg("PX")
+            .arg(millis(self.lock_timeout));
+        cmd
+    }
+
+    fn store_pipe<T: Serialize>(
+        &self,
+        key: &str,
+        value: &T,
+        ttl: Duration,
+        delta: Duration,
+    ) -> RedisResult<Pipeline> {
+        let mut pipe = pipe();
+        pipe.atomic()
+            .cmd("HMSET")
+            .arg(key)
+            .arg("value")
+            .arg(C::encode(value)?)
+            .arg("delta")
+            .arg(millis(delta))
+            .ignore()
+            .cmd("PEXPIRE")
+            .arg(key)
+            .arg(millis(ttl).max(1))
+            .ignore();
+        Ok(pipe)
+    }
+}
+
+#[cfg(feature = "aio")]
+impl<C: Codec + 'static> Cache<C> {
+    /// Like `get_or_set` for async connections, with a computation that
+    /// returns a future.
+    pub fn get_or_set_async<Con, T, F, R>(
+        &self,
+        con: Con,
+        key: &str,
+        ttl: Duration,
+        compute: F,
+    ) -> RedisFuture<(Con, T)>
+    where
+        Con: ::aio::ConnectionLike + Clone + Send + 'static,
+        T: Serialize + DeserializeOwned + Send + 'static,
+        F: FnOnce() -> R + Send + 'static,
+        R: Future<Item = T, Error = RedisError> + Send + 'static,
+    {
+        let cache = Arc::new(self.clone());
+        let key = key.to_string();
+        let deadline = Instant::now() + self.lock_timeout;
+        Box::new(future::loop_fn((con, compute), move |(con, compute)| {
+            let cache = cache.clone();
+            let key = key.clone();
+            lookup_pipe(&key)
+                .query_async(con)
+                .and_then(move |(con, lookup): (Con, Lookup)| {
+                    let stale = match cache.decide(lookup) {
+                        Ok(Some((value, false))) => {
+                            return Either::A(future::ok::<_, RedisError>(Loop::Break((
+                                con, value,
+                            ))));
+                        }
+                        Ok(found) => found.map(|(value, _)| value),
+                        Err(err) => return Either::A(future::err(err)),
+                    };
+
+                    let token = lock_token();
+                    let lock = cache.lock_cmd(&key, &token).query_async(con);
+                    Either::B(lock.and_then(
+                        move |(con, locked): (Con, Option<String>)| -> RedisFuture<_> {
+                            if locked.is_some() {
+                                let computed =
+                                    compute_and_store(cache, con, key, ttl, token, compute);
+                                return Box::new(computed.map(Loop::Break));
+                            }
+
+                            // another client is computing the value
+                            if let Some(value) = stale {
+                                return Box::new(future::ok::<_, RedisError>(Loop::Break((
+                                    con, value,
+                                ))));
+                            }
+                            if Instant::now() >= deadline {
+                                return Box::new(
+                                    compute().map(move |value| Loop::Break((con, value))),
+                                );
+                            }
+                            Box::new(Delay::new(cache.poll_interval).then(move |_| {
+                                future::ok::<_, RedisError>(Loop::Continue((con, compute)))
+                            }))
+                        },
+                    ))
+                })
+        }))
+    }
+}
+
+// Runs the computation of `get_or_set_async` while holding the lock.
+#[cfg(feature = "aio")]
+fn compute_and_store<C, Con, T, F, R>(
+    cache: Arc<Cache<C>>,
+    con: Con,
+    key: String,
+    ttl: Duration,
+    token: String,
+    compute: F,
+) -> RedisFuture<(Con, T)>
+where
+    C: Codec + 'static,
+    Con: ::aio::ConnectionLike + Clone + Send + 'static,
+    T: Serialize + DeserializeOwned + Send + 'static,
+    F: FnOnce() -> R + Send + 'static,
+    R: Future<Item = T, Error = RedisError> + Send + 'static,
+{
+    let start = Instant::now();
+    Box::new(compute().then(move |result| -> RedisFuture<(Con, T)> {
+        let stored = result.and_then(|value| {
+            let store = cache.store_pipe(&key, &value, ttl, start.elapsed())?;
+            Ok((value, store))
+        });
+        // the lock is released after the value is stored, so that waiting
+        // clients do not take the lock and compute the value again
+        let unlock = move |con: Con| {
+            cache
+                .unlock
+                .key(lock_key(&key))
+                .arg(token)
+                .invoke_async::<Con, i64>(con)
+        };
+        match stored {
+            Ok((value, store)) => Box::new(
+                store
+                    .query_async(con)
+                    .and_then(move |(con, ()): (Con, ())| unlock(con))
+                    .map(move |(con, _)| (con, value)),
+            ),
+            Err(err) => Box::new(unlock(con).then(move |_| Err::<(Con, T), _>(err))),
+        }
+    }))
+}
+
+fn lookup_pipe(key: &str) -> Pipeline {
+    let mut pipe = pipe();
+    pipe.cmd("HMGET")
+        .arg(key)
+        .arg("value")
+        .arg("delta")
+        .cmd("PTTL")
+        .arg(key);
+    pipe
+}
+
+fn lock_key(key: &str) -> String {
+    format!("{}:lock", key)
+}
+
+fn lock_token() -> String {
+    format!("{:016x}", random_u64())
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_nanos() / 1_000_000)
+}
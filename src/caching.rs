@@ -0,0 +1,429 @@
+//! Client side caching with invalidations from the server.
+//!
+//! A `CachingConnection` wraps a RESP3 connection and enables
+//! `CLIENT TRACKING` on it.  The replies of read commands like `GET` or
+//! `HGETALL` are kept in memory and returned without asking the server
+//! again.  The server remembers which keys the connection has read and
+//! sends an invalidation push message when one of them changes, which
+//! removes the cached replies of that key.
+//!
+//! Invalidations arrive on the connection itself, so they are read before
+//! a cached reply is returned.  This works for TCP and unix connections;
+//! over custom transports only invalidations that were read already take
+//! effect and cached replies can be stale.
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::caching::{CacheConfig, CachingConnection};
+//! use redis::Commands;
+//!
+//! let client = redis::Client::open("redis://127.0.0.1/?protocol=resp3")?;
+//! let mut con = CachingConnection::new(client.get_connection()?, CacheConfig::default())?;
+//! let first: Option<String> = con.get("my_key")?;
+//! // answered from the cache unless `my_key` changed in between
+//! let second: Option<String> = con.get("my_key")?;
+//! # Ok(()) }
+//! ```
+//!
+//! Custom caching setups can use the `CLIENT TRACKING` commands of
+//! `Commands` directly, together with `TrackingOptions` and the
+//! `TrackingInfo` reply of `CLIENT TRACKINGINFO`.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use cluster_routing::parse_packed_commands;
+use cmd::cmd;
+use connection::{Connection, ConnectionLike, ProtocolVersion};
+use types::{
+    ErrorKind, FromRedisValue, PushInfo, PushKind, RedisResult, RedisWrite, ToRedisArgs, Value,
+};
+
+/// Controls how many replies a `CachingConnection` keeps and for how long.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CacheConfig {
+    /// The maximum number of cached replies.  The oldest reply is removed
+    /// when a new one does not fit.
+    pub max_entries: usize,
+    /// How long a reply is used at most, `None` keeps it until it is
+    /// invalidated.
+    pub ttl: Option<Duration>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> CacheConfig {
+        CacheConfig {
+            max_entries: 10_000,
+            ttl: None,
+        }
+    }
+}
+
+/// Which keys the server tracks in the default mode of `CLIENT TRACKING`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrackingMode {
+    /// All keys read by the connection are tracked.
+    Always,
+    /// Only keys read by the command after `CLIENT CACHING YES` are
+    /// tracked (`OPTIN`).
+    OptIn,
+    /// All keys are tracked except those read by the command after
+    /// `CLIENT CACHING NO` (`OPTOUT`).
+    OptOut,
+}
+
+impl Default for TrackingMode {
+    fn default() -> TrackingMode {
+        TrackingMode::Always
+    }
+}
+
+/// The options of `CLIENT TRACKING ON`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackingOptions {
+    /// Sends the invalidations to the connection with this id instead,
+    /// which is needed for RESP2 connections (`REDIRECT`).
+    pub redirect: Option<i64>,
+    /// Tracks all keys starting with one of the prefixes instead of the
+    /// keys read by the connection (`BCAST`).
+    pub bcast: bool,
+    /// The prefixes tracked in broadcasting mode, none tracks all keys
+    /// (`PREFIX`).
+    pub prefixes: Vec<Vec<u8>>,
+    /// Which keys are tracked if `bcast` is not set.
+    pub mode: TrackingMode,
+    /// Does not send invalidations of keys modified by the connection
+    /// itself (`NOLOOP`).
+    pub noloop: bool,
+}
+
+impl ToRedisArgs for TrackingOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(redirect) = self.redirect {
+            out.write_arg(b"REDIRECT");
+            redirect.write_redis_args(out);
+        }
+        if self.bcast {
+            out.write_arg(b"BCAST");
+        }
+        for prefix in &self.prefixes {
+            out.write_arg(b"PREFIX");
+            out.write_arg(prefix);
+        }
+        match self.mode {
+            TrackingMode::Always => {}
+            TrackingMode::OptIn => out.write_arg(b"OPTIN"),
+            TrackingMode::OptOut => out.write_arg(b"OPTOUT"),
+        }
+        if self.noloop {
+            out.write_arg(b"NOLOOP");
+        }
+    }
+}
+
+/// The reply of `CLIENT TRACKINGINFO` (redis 6.2).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackingInfo {
+    /// The flags of the tracking, like `on`, `bcast`, `optin`,
+    /// `caching-yes` or `broken_redirect`.
+    pub flags: Vec<String>,
+    /// The id of the connection the invalidations are redirected to, `0`
+    /// if they are not redirected and `-1` if tracking is off.
+    pub redirect: i64,
+    /// The tracked prefixes in broadcasting mode.
+    pub prefixes: Vec<Vec<u8>>,
+}
+
+impl TrackingInfo {
+    /// Returns whether tracking is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.has_flag("on")
+    }
+
+    /// Returns whether the flag is set.
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
+}
+
+impl FromRedisValue for TrackingInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<TrackingInfo> {
+        let mut fields: HashMap<String, Value> = FromRedisValue::from_redis_value(v)?;
+        let mut field = |name: &str| fields.remove(name).unwrap_or(Value::Nil);
+        Ok(TrackingInfo {
+            flags: FromRedisValue::from_redis_value(&field("flags"))?,
+            redirect: match field("redirect") {
+                Value::Nil => -1,
+                redirect => FromRedisValue::from_redis_value(&redirect)?,
+            },
+            prefixes: FromRedisValue::from_redis_value(&field("prefixes"))?,
+        })
+    }
+}
+
+// Read commands whose reply only depends on the key at index 1.
+const CACHEABLE_COMMANDS: &[&[u8]] = &[
+    b"GET",
+    b"STRLEN",
+    b"GETRANGE",
+    b"GETBIT",
+    b"BITCOUNT",
+    b"BITPOS",
+    b"EXISTS",
+    b"TYPE",
+    b"HGET",
+    b"HMGET",
+    b"HGETALL",
+    b"HKEYS",
+    b"HVALS",
+    b"HLEN",
+    b"HEXISTS",
+    b"HSTRLEN",
+    b"LRANGE",
+    b"LLEN",
+    b"LINDEX",
+    b"LPOS",
+    b"SMEMBERS",
+    b"SISMEMBER",
+    b"SCARD",
+    b"ZRANGE",
+    b"ZREVRANGE",
+    b"ZRANGEBYSCORE",
+    b"ZREVRANGEBYSCORE",
+    b"ZRANGEBYLEX",
+    b"ZREVRANGEBYLEX",
+    b"ZSCORE",
+    b"ZCARD",
+    b"ZCOUNT",
+    b"ZLEXCOUNT",
+    b"ZRANK",
+    b"ZREVRANK",
+    b"XRANGE",
+    b"XREVRANGE",
+    b"XLEN",
+    b"GEOPOS",
+    b"GEODIST",
+    b"GEOHASH",
+];
+
+// Returns the key of the packed command if its reply can be cached.
+fn cacheable_key(packed: &[u8]) -> Option<Vec<u8>> {
+    let commands = parse_packed_commands(packed).ok()?;
+    if commands.len() != 1 {
+        return None;
+    }
+    let args = &commands[0].0;
+    let name = match args.get(0) {
+        Some(&Value::Data(ref name)) => name.to_ascii_uppercase(),
+        _ => return None,
+    };
+    // an `EXISTS` of several keys would have to be invalidated by each
+    if !CACHEABLE_COMMANDS.contains(&&name[..]) || (&name[..] == b"EXISTS" && args.len() != 2) {
+        return None;
+    }
+    match args.get(1) {
+        Some(&Value::Data(ref key)) => Some(key.clone()),
+        _ => None,
+    }
+}
+
+struct Entry {
+    key: Vec<u8>,
+    value: Value,
+    inserted: Instant,
+    // the position in the eviction order
+    seq: u64,
+}
+
+/// A connection that caches the replies of read commands, see the module
+/// documentation.
+///
+/// Only single commands are cached.  Pipelines and transactions are sent
+/// to the server, they invalidate the cache like any other write.
+pub struct CachingConnection {
+    con: Connection,
+    receiver: mpsc::Receiver<PushInfo>,
+    config: CacheConfig,
+    // cached replies by packed command
+    entries: HashMap<Vec<u8>, Entry>,
+    // the packed commands cached for every key
+    keys: HashMap<Vec<u8>, HashSet<Vec<u8>>>,
+    // the packed commands in insertion order
+    order: BTreeMap<u64, Vec<u8>>,
+    next_seq: u64,
+}
+
+impl CachingConnection {
+    /// Enables tracking on the connection and caches its replies from then
+    /// on.  The connection has to speak RESP3 since invalidations are push
+    /// messages.  Its push sender is replaced, other push messages are
+    /// dropped.
+    pub fn new(mut con: Connection, config: CacheConfig) -> RedisResult<CachingConnection> {
+        if con.protocol() != ProtocolVersion::RESP3 {
+            fail!((
+                ErrorKind::InvalidClientConfig,
+                "Client side caching requires a RESP3 connection"
+            ));
+        }
+        let (sender, receiver) = mpsc::channel();
+        con.set_push_sender(sender);
+        cmd("CLIENT")
+            .arg("TRACKING")
+            .arg("ON")
+            .query::<()>(&mut con)?;
+        Ok(CachingConnection {
+            con,
+            receiver,
+            config,
+            entries: HashMap::new(),
+            keys: HashMap::new(),
+            order: BTreeMap::new(),
+            next_seq: 0,
+        })
+    }
+
+    /// Returns the number of cached replies.
+    pub fn cached_entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Removes all cached replies.
+    pub fn clear_cache(&mut self) {
+        self.entries.clear();
+        self.keys.clear();
+        self.order.clear();
+    }
+
+    /// Returns the underlying connection.  Tracking stays enabled on it.
+    pub fn into_inner(self) -> Connection {
+        self.con
+    }
+
+    // Removes the cached replies of the invalidated keys.
+    fn apply_invalidations(&mut self) {
+        while let Ok(push) = self.receiver.try_recv() {
+            if push.kind != PushKind::Invalidate {
+                continue;
+            }
+            match push.data.into_iter().next() {
+                Some(Value::Bulk(keys)) | Some(Value::Set(keys)) => {
+                    for key in keys {
+                        if let Value::Data(key) = key {
+                            self.invalidate_key(&key);
+                        }
+                    }
+                }
+                // the server flushed its tracking table or the database
+                _ => self.clear_cache(),
+            }
+        }
+    }
+
+    fn invalidate_key(&mut self, key: &[u8]) {
+        for packed in self.keys.remove(key).unwrap_or_default() {
+            if let Some(entry) = self.entries.remove(&packed) {
+                self.order.remove(&entry.seq);
+            }
+        }
+    }
+
+    fn remove_entry(&mut self, packed: &[u8]) {
+        let entry = unwrap_or!(self.entries.remove(packed), return);
+        self.order.remove(&entry.seq);
+        let empty = match self.keys.get_mut(&entry.key) {
+            Some(commands) => {
+                commands.remove(packed);
+                commands.is_empty()
+            }
+            None => false,
+        };
+        if empty {
+            self.keys.remove(&entry.key);
+        }
+    }
+
+    // Returns the cached reply to the packed command if it did not expire.
+    fn lookup(&mut self, packed: &[u8]) -> Option<Value> {
+        let expired = {
+            let entry = self.entries.get(packed)?;
+            match self.config.ttl {
+                Some(ttl) => entry.inserted.elapsed() >= ttl,
+                None => false,
+            }
+        };
+        if expired {
+            self.remove_entry(packed);
+            return None;
+        }
+        self.entries.get(packed).map(|entry| entry.value.clone())
+    }
+
+    fn insert(&mut self, packed: &[u8], key: Vec<u8>, value: Value) {
+        if self.config.max_entries == 0 {
+            return;
+        }
+        self.remove_entry(packed);
+        while self.entries.len() >= self.config.max_entries {
+            let oldest = match self.order.iter().next() {
+                Some((_, packed)) => packed.clone(),
+                None => break,
+            };
+            self.remove_entry(&oldest);
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.order.insert(seq, packed.to_vec());
+        self.keys
+            .entry(key.clone())
+            .or_insert_with(HashSet::new)
+            .insert(packed.to_vec());
+        self.entries.insert(
+            packed.to_vec(),
+            Entry {
+                key,
+                value,
+                inserted: Instant::now(),
+                seq,
+            },
+        );
+    }
+}
+
+impl ConnectionLike for CachingConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        // invalidations that arrived in the meantime have to be applied first
+        self.con.read_push_messages()?;
+        self.apply_invalidations();
+        let key = cacheable_key(cmd);
+        if key.is_some() {
+            if let Some(value) = self.lookup(cmd) {
+                return Ok(value);
+            }
+        }
+        let value = self.con.req_packed_command(cmd)?;
+        // an invalidation read together with the reply is older than it
+        self.apply_invalidations();
+        if let Some(key) = key {
+            self.insert(cmd, key, value.clone());
+        }
+        Ok(value)
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        let result = self.con.req_packed_commands(cmd, offset, count);
+        self.apply_invalidations();
+        result
+    }
+
+    fn get_db(&self) -> i64 {
+        self.con.get_db()
+    }
+}
@@ -1,12 +1,36 @@
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+
+#[cfg(feature = "aio")]
 use futures::Future;
 
 use connection::{connect, Connection, ConnectionInfo, ConnectionLike, IntoConnectionInfo};
+use metrics::MetricsObserver;
+use retry::RetryPolicy;
 use types::{RedisError, RedisResult, Value};
 
 /// The client type.
 #[derive(Debug, Clone)]
 pub struct Client {
     connection_info: ConnectionInfo,
+    retry_policy: Option<RetryPolicy>,
+    metrics: Metrics,
+    #[cfg(feature = "aio")]
+    auto_pipeline: ::aio::AutoPipelineConfig,
+}
+
+// The metrics observer of a client, which is not `Debug`.
+#[derive(Clone, Default)]
+struct Metrics(Option<Arc<MetricsObserver>>);
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "Some(MetricsObserver)"),
+            None => write!(f, "None"),
+        }
+    }
 }
 
 /// The client acts as connector to the redis server.  By itself it does not
@@ -32,35 +56,215 @@ impl Client {
     pub fn open<T: IntoConnectionInfo>(params: T) -> RedisResult<Client> {
         Ok(Client {
             connection_info: params.into_connection_info()?,
+            retry_policy: None,
+            metrics: Default::default(),
+            #[cfg(feature = "aio")]
+            auto_pipeline: Default::default(),
         })
     }
 
+    /// Sets the policy used to retry requests that failed with a transient
+    /// error.  It applies when the client itself is used as a connection and
+    /// to connection managers created from the client afterwards; plain
+    /// connections never retry.  By default nothing is retried.
+    pub fn set_retry_policy(&mut self, policy: Option<RetryPolicy>) {
+        self.retry_policy = policy;
+    }
+
+    /// Sets how multiplexed connections and connection managers created
+    /// from the client afterwards batch concurrent requests.
+    #[cfg(feature = "aio")]
+    pub fn set_auto_pipeline(&mut self, config: ::aio::AutoPipelineConfig) {
+        self.auto_pipeline = config;
+    }
+
+    /// Returns the retry policy of the client.
+    pub fn retry_policy(&self) -> Option<&RetryPolicy> {
+        self.retry_policy.as_ref()
+    }
+
+    /// Sets the observer that is told about connects, about the commands
+    /// of synchronous connections and about reconnects and pool checkouts,
+    /// see the `metrics` module.  It applies to connections, pools and
+    /// connection managers created from the client afterwards.
+    pub fn set_metrics_observer(&mut self, observer: Option<Arc<MetricsObserver>>) {
+        self.metrics = Metrics(observer);
+    }
+
+    /// Returns the metrics observer of the client.
+    pub fn metrics_observer(&self) -> Option<&Arc<MetricsObserver>> {
+        self.metrics.0.as_ref()
+    }
+
+    /// Returns the connection info the client connects with.
+    pub fn get_connection_info(&self) -> &ConnectionInfo {
+        &self.connection_info
+    }
+
+    // Runs `func` until it succeeds or the retry policy gives up.
+    fn with_retries<T, F>(&self, mut func: F) -> RedisResult<T>
+    where
+        F: FnMut() -> RedisResult<T>,
+    {
+        let mut attempt = 1;
+        loop {
+            match func() {
+                Err(ref err) if self.should_retry(err, attempt) => {
+                    thread::sleep(self.retry_policy.as_ref().unwrap().delay(attempt));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn should_retry(&self, err: &RedisError, attempt: u32) -> bool {
+        match self.retry_policy {
+            Some(ref policy) => policy.should_retry(err, attempt),
+            None => false,
+        }
+    }
+
     /// Instructs the client to actually connect to redis and returns a
     /// connection object.  The connection object can be used to send
     /// commands to the server.  This can fail with a variety of errors
     /// (like unreachable host) so it's important that you handle those
     /// errors.
     pub fn get_connection(&self) -> RedisResult<Connection> {
-        Ok(connect(&self.connection_info)?)
+        let observer = match self.metrics.0 {
+            Some(ref observer) => observer,
+            None => return connect(&self.connection_info),
+        };
+        match connect(&self.connection_info) {
+            Ok(mut con) => {
+                observer.connected(&self.connection_info.addr);
+                con.set_metrics_observer(Some(observer.clone()));
+                Ok(con)
+            }
+            Err(err) => {
+                observer.connection_failed(&self.connection_info.addr, &err);
+                Err(err)
+            }
+        }
     }
 
+    /// Opens a connection and issues `MONITOR` on it.  The returned
+    /// `Monitor` is an iterator of the commands the server processes.
+    pub fn get_monitor(&self) -> RedisResult<::monitor::Monitor> {
+        ::monitor::Monitor::new(self.get_connection()?)
+    }
+
+    #[cfg(feature = "aio")]
     pub fn get_async_connection(
         &self,
     ) -> impl Future<Item = ::aio::Connection, Error = RedisError> {
-        ::aio::connect(self.connection_info.clone())
+        let observer = self.metrics.0.clone();
+        let addr = self.connection_info.addr.clone();
+        ::aio::connect(self.connection_info.clone()).then(move |result| {
+            if let Some(observer) = observer {
+                match result {
+                    Ok(_) => observer.connected(&addr),
+                    Err(ref err) => observer.connection_failed(&addr, err),
+                }
+            }
+            result
+        })
     }
 
-    pub fn get_shared_async_connection(
+    /// Creates a pool of connections to the server which keeps at most
+    /// `max_size` connections open and opens `min_idle` of them right away.
+    /// See `Pool::new` for more control over the pool.
+    #[cfg(feature = "with-pool")]
+    pub fn get_pool(&self, max_size: usize, min_idle: usize) -> RedisResult<::pool::Pool> {
+        ::pool::Pool::new(
+            self.clone(),
+            ::pool::PoolConfig {
+                max_size: max_size,
+                min_idle: min_idle,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Creates a pool of async connections to the server which keeps at
+    /// most `max_size` connections open and opens `min_idle` of them right
+    /// away.  See `AsyncPool::new` for more control over the pool.
+    #[cfg(all(feature = "with-pool", feature = "aio"))]
+    pub fn get_async_pool(
+        &self,
+        max_size: usize,
+        min_idle: usize,
+    ) -> impl Future<Item = ::pool::AsyncPool, Error = RedisError> {
+        ::pool::AsyncPool::new(
+            self.clone(),
+            ::pool::PoolConfig {
+                max_size: max_size,
+                min_idle: min_idle,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Returns a multiplexed async connection which can be cloned and
+    /// shared between tasks.  With `tokio-comp` the connection has to be
+    /// established from within a tokio executor.
+    #[cfg(feature = "aio")]
+    pub fn get_multiplexed_async_connection(
+        &self,
+    ) -> impl Future<Item = ::aio::MultiplexedConnection, Error = RedisError> {
+        let config = self.auto_pipeline.clone();
+        self.get_async_connection()
+            .and_then(move |con| ::aio::MultiplexedConnection::with_config(con, config))
+    }
+
+    /// Returns an async `PubSub` connection whose messages are consumed as
+    /// a stream.
+    #[cfg(feature = "aio")]
+    pub fn get_async_pubsub(&self) -> impl Future<Item = ::aio::PubSub, Error = RedisError> {
+        self.get_async_connection().and_then(::aio::PubSub::new)
+    }
+
+    /// Returns an async connection in monitor mode, whose stream yields
+    /// the commands the server processes.
+    #[cfg(feature = "aio")]
+    pub fn get_async_monitor(
         &self,
-    ) -> impl Future<Item = ::aio::SharedConnection, Error = RedisError> {
+    ) -> impl Future<Item = ::monitor::MonitorStream, Error = RedisError> {
         self.get_async_connection()
-            .and_then(move |con| ::aio::SharedConnection::new(con))
+            .and_then(::monitor::monitor_async)
+    }
+
+    /// Returns a `PubSubManager` for the server, which subscribes to its
+    /// channels again after the connection broke.
+    #[cfg(feature = "aio")]
+    pub fn get_pubsub_manager(
+        &self,
+    ) -> impl Future<Item = ::aio::PubSubManager, Error = RedisError> {
+        ::aio::PubSubManager::new(self.clone())
+    }
+
+    /// Returns a `ConnectionManager` for the server.  The manager is a
+    /// multiplexed async connection which transparently reconnects after
+    /// the connection broke.
+    #[cfg(feature = "aio")]
+    pub fn get_connection_manager(
+        &self,
+    ) -> impl Future<Item = ::aio::ConnectionManager, Error = RedisError> {
+        ::aio::ConnectionManager::new(self.clone())
+    }
+
+    #[cfg(feature = "aio")]
+    #[deprecated(note = "renamed to `get_multiplexed_async_connection`")]
+    pub fn get_shared_async_connection(
+        &self,
+    ) -> impl Future<Item = ::aio::MultiplexedConnection, Error = RedisError> {
+        self.get_multiplexed_async_connection()
     }
 }
 
 impl ConnectionLike for Client {
     fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
-        self.get_connection()?.req_packed_command(cmd)
+        self.with_retries(|| self.get_connection()?.req_packed_command(cmd))
     }
 
     fn req_packed_commands(
@@ -69,8 +273,10 @@ impl ConnectionLike for Client {
         offset: usize,
         count: usize,
     ) -> RedisResult<Vec<Value>> {
-        self.get_connection()?
-            .req_packed_commands(cmd, offset, count)
+        self.with_retries(|| {
+            self.get_connection()?
+                .req_packed_commands(cmd, offset, count)
+        })
     }
 
     fn get_db(&self) -> i64 {
@@ -0,0 +1,318 @@
+//! Types for the `CLIENT` commands that inspect and manage the
+//! connections of a server.
+use std::collections::HashMap;
+use std::str::{from_utf8, FromStr};
+use std::time::Duration;
+
+use types::{
+    from_redis_value, ErrorKind, FromRedisValue, RedisError, RedisResult, RedisWrite, ToRedisArgs,
+    Value,
+};
+
+/// The kinds of connections the `CLIENT` commands distinguish.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ClientType {
+    /// A regular connection.
+    Normal,
+    /// The connection of a replica to its master.
+    Replica,
+    /// The connection of a master to its replica.
+    Master,
+    /// A connection in pub/sub mode.
+    PubSub,
+    /// Any other type.
+    Other(String),
+}
+
+impl ClientType {
+    /// Returns the type for a name used by redis.  Unknown names become
+    /// `Other`.
+    pub fn from_name(name: &str) -> ClientType {
+        match name {
+            "normal" => ClientType::Normal,
+            "replica" | "slave" => ClientType::Replica,
+            "master" => ClientType::Master,
+            "pubsub" => ClientType::PubSub,
+            other => ClientType::Other(other.to_string()),
+        }
+    }
+
+    /// Returns the name redis uses for the type.
+    pub fn name(&self) -> &str {
+        match *self {
+            ClientType::Normal => "normal",
+            ClientType::Replica => "replica",
+            ClientType::Master => "master",
+            ClientType::PubSub => "pubsub",
+            ClientType::Other(ref name) => name,
+        }
+    }
+}
+
+impl ToRedisArgs for ClientType {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(self.name().as_bytes())
+    }
+}
+
+/// Which commands `CLIENT PAUSE` holds back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseMode {
+    /// All commands of normal and pub/sub connections.
+    All,
+    /// Only commands that may write, reads go on (redis 6.2).
+    Write,
+}
+
+impl ToRedisArgs for PauseMode {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            PauseMode::All => &b"ALL"[..],
+            PauseMode::Write => &b"WRITE"[..],
+        })
+    }
+}
+
+/// The filters of `CLIENT KILL`.  A connection is closed if it matches
+/// all of them.
+///
+/// ```rust,no_run
+/// # fn do_something() -> redis::RedisResult<()> {
+/// use redis::clients::{ClientKillOptions, ClientType};
+/// use redis::Commands;
+///
+/// # let client = redis::Client::open("redis://127.0.0.1/")?;
+/// # let mut con = client.get_connection()?;
+/// let options = ClientKillOptions::new()
+///     .client_type(ClientType::PubSub)
+///     .user("worker");
+/// let killed: usize = con.client_kill(&options)?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientKillOptions {
+    args: Vec<Vec<u8>>,
+}
+
+impl ClientKillOptions {
+    /// Creates options without filters.  At least one filter has to be
+    /// added before they are sent.
+    pub fn new() -> ClientKillOptions {
+        ClientKillOptions::default()
+    }
+
+    fn filter<T: ToRedisArgs>(mut self, name: &str, value: T) -> ClientKillOptions {
+        self.args.push(name.as_bytes().to_vec());
+        self.args.extend(value.to_redis_args());
+        self
+    }
+
+    /// Closes the connection with the id (`ID`).
+    pub fn id(self, id: i64) -> ClientKillOptions {
+        self.filter("ID", id)
+    }
+
+    /// Closes the connection from the address `ip:port` (`ADDR`).
+    pub fn addr(self, addr: &str) -> ClientKillOptions {
+        self.filter("ADDR", addr)
+    }
+
+    /// Closes the connections to the local address `ip:port` of the
+    /// server (`LADDR`, redis 6.2).
+    pub fn laddr(self, addr: &str) -> ClientKillOptions {
+        self.filter("LADDR", addr)
+    }
+
+    /// Closes the connections of the type (`TYPE`).
+    pub fn client_type(self, client_type: ClientType) -> ClientKillOptions {
+        self.filter("TYPE", client_type)
+    }
+
+    /// Closes the connections authenticated as the user (`USER`, redis 6).
+    pub fn user(self, user: &str) -> ClientKillOptions {
+        self.filter("USER", user)
+    }
+
+    /// Whether the connection sending the command is closed too if it
+    /// matches (`SKIPME`).  Redis skips it by default.
+    pub fn skipme(self, skip: bool) -> ClientKillOptions {
+        self.filter("SKIPME", if skip { "yes" } else { "no" })
+    }
+
+    /// Closes the connections older than the duration (`MAXAGE`,
+    /// redis 7.4).  Redis counts in whole seconds.
+    pub fn maxage(self, age: Duration) -> ClientKillOptions {
+        self.filter("MAXAGE", age.as_secs())
+    }
+}
+
+impl ToRedisArgs for ClientKillOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        for arg in &self.args {
+            out.write_arg(arg);
+        }
+    }
+}
+
+/// A connection as described by `CLIENT INFO` and `CLIENT LIST`.
+///
+/// `CLIENT LIST` is read as `Vec<ClientInfo>`.  Fields that older servers
+/// do not report are `None`, all fields are kept in `fields`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientInfo {
+    /// The unique id of the connection.
+    pub id: i64,
+    /// The address of the client, `ip:port` or the path of a unix socket.
+    pub addr: String,
+    /// The address of the server the client connected to (redis 6.2).
+    pub laddr: Option<String>,
+    /// The name set with `CLIENT SETNAME`, if any.
+    pub name: Option<String>,
+    /// How long the connection has been open.
+    pub age: Duration,
+    /// How long the connection has been idle.
+    pub idle: Duration,
+    /// The flags of the connection, like `N` for a normal connection or
+    /// `P` for pub/sub.
+    pub flags: String,
+    /// The selected database.
+    pub db: i64,
+    /// The number of channel subscriptions.
+    pub sub: usize,
+    /// The number of pattern subscriptions.
+    pub psub: usize,
+    /// The number of shard channel subscriptions (redis 7).
+    pub ssub: Option<usize>,
+    /// The number of commands queued in a transaction, `-1` outside of
+    /// one.
+    pub multi: i64,
+    /// The last command the connection ran.
+    pub cmd: String,
+    /// The authenticated user (redis 6).
+    pub user: Option<String>,
+    /// The protocol version of the connection (redis 7).
+    pub resp: Option<u8>,
+    /// All fields as reported by the server.
+    pub fields: HashMap<String, String>,
+}
+
+impl ClientInfo {
+    /// Parses a line of `CLIENT LIST` or the reply of `CLIENT INFO`.
+    pub fn parse(line: &str) -> RedisResult<ClientInfo> {
+        let fields: HashMap<String, String> = line
+            .split_whitespace()
+            .map(|field| {
+                let mut parts = field.splitn(2, '=');
+                let key = parts.next().unwrap_or("");
+                (key.to_string(), parts.next().unwrap_or("").to_string())
+            })
+            .collect();
+        let mut info = ClientInfo {
+            id: parse_field(&fields, "id")?,
+            addr: required(&fields, "addr")?.to_string(),
+            laddr: optional(&fields, "laddr"),
+            name: optional(&fields, "name"),
+            age: Duration::from_secs(parse_field(&fields, "age")?),
+            idle: Duration::from_secs(parse_field(&fields, "idle")?),
+            flags: required(&fields, "flags")?.to_string(),
+            db: parse_field(&fields, "db")?,
+            sub: parse_field(&fields, "sub")?,
+            psub: parse_field(&fields, "psub")?,
+            ssub: match fields.get("ssub") {
+                Some(_) => Some(parse_field(&fields, "ssub")?),
+                None => None,
+            },
+            multi: parse_field(&fields, "multi")?,
+            cmd: required(&fields, "cmd")?.to_string(),
+            user: optional(&fields, "user"),
+            resp: match fields.get("resp") {
+                Some(_) => Some(parse_field(&fields, "resp")?),
+                None => None,
+            },
+            fields: HashMap::new(),
+        };
+        info.fields = fields;
+        Ok(info)
+    }
+
+    /// Returns whether the flag is set.
+    pub fn has_flag(&self, flag: char) -> bool {
+        self.flags.contains(flag)
+    }
+
+    /// Returns the type of the connection according to its flags.
+    pub fn client_type(&self) -> ClientType {
+        if self.has_flag('M') {
+            ClientType::Master
+        } else if self.has_flag('S') {
+            ClientType::Replica
+        } else if self.has_flag('P') {
+            ClientType::PubSub
+        } else {
+            ClientType::Normal
+        }
+    }
+
+    /// Returns a field by name, like `qbuf` or `lib-name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(|value| &value[..])
+    }
+}
+
+fn required<'a>(fields: &'a HashMap<String, String>, name: &str) -> RedisResult<&'a str> {
+    match fields.get(name) {
+        Some(value) => Ok(value),
+        None => Err(RedisError::from((
+            ErrorKind::TypeError,
+            "Client info without field",
+            name.to_string(),
+        ))),
+    }
+}
+
+// Empty fields like `name=` are `None`.
+fn optional(fields: &HashMap<String, String>, name: &str) -> Option<String> {
+    match fields.get(name) {
+        Some(value) if !value.is_empty() => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn parse_field<T: FromStr>(fields: &HashMap<String, String>, name: &str) -> RedisResult<T> {
+    let value = required(fields, name)?;
+    match value.parse() {
+        Ok(value) => Ok(value),
+        Err(_) => Err(RedisError::from((
+            ErrorKind::TypeError,
+            "Invalid client info field",
+            format!("{}={}", name, value),
+        ))),
+    }
+}
+
+impl FromRedisValue for ClientInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<ClientInfo> {
+        let line: String = from_redis_value(v)?;
+        ClientInfo::parse(line.trim())
+    }
+
+    // the lines of `CLIENT LIST`
+    fn from_byte_vec(vec: &[u8]) -> Option<Vec<ClientInfo>> {
+        from_utf8(vec)
+            .ok()?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(ClientInfo::parse)
+            .collect::<RedisResult<_>>()
+            .ok()
+    }
+}
@@ -0,0 +1,1575 @@
+//! Support for redis cluster.
+//!
+//! A `ClusterConnection` keeps a connection to every master of the cluster
+//! it needs to talk to and sends each command to the node that serves the
+//! hash slot of its key.  The slot map is fetched with `CLUSTER SLOTS` from
+//! the first reachable initial node.  Commands without keys go to an
+//! arbitrary node, `FLUSHALL`, `FLUSHDB` and `SCRIPT` go to every master.
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::Commands;
+//! use redis::cluster::ClusterClient;
+//!
+//! let nodes = vec!["redis://127.0.0.1:7000/", "redis://127.0.0.1:7001/"];
+//! let client = ClusterClient::open(nodes)?;
+//! let mut con = client.get_connection()?;
+//! let _: () = con.set("key", 42)?;
+//! let value: i32 = con.get("key")?;
+//! # Ok(()) }
+//! ```
+//!
+//! All keys of a command have to be in the same slot, commands with keys in
+//! different slots fail with `ErrorKind::CrossSlot` before they are sent.
+//! Hash tags like `{user1}.name` and `{user1}.email` put keys into the same
+//! slot.  `key_slot`, `tagged_key` and `command_slot` help to design key
+//! schemas that keep related keys together:
+//!
+//! ```rust
+//! use redis::cluster::{command_slot, key_slot, tagged_key};
+//!
+//! let name = tagged_key("user1", ".name");
+//! assert_eq!(name, "{user1}.name");
+//! assert_eq!(key_slot(&name), key_slot("user1"));
+//!
+//! let cmd = redis::cmd("MGET").arg(&name).arg(tagged_key("user1", ".email")).clone();
+//! assert_eq!(command_slot(&cmd).unwrap(), Some(key_slot("user1")));
+//! assert!(command_slot(redis::cmd("MGET").arg("a").arg("b")).is_err());
+//! ```
+//!
+//! `MOVED` and `ASK` redirects are followed transparently and `MOVED`
+//! triggers a refresh of the slot map, see `ClusterConfig`.
+//!
+//! Read-only commands like `GET` or `ZRANGE` can be served by replicas to
+//! take load off the masters, see `ReadFrom`.  Replicas may lag behind
+//! their master, so a read can miss a write that was just made.
+//!
+//! Pipelines are split by node and the parts are sent to all nodes before
+//! any reply is read; the replies are returned in the original order.
+//! Atomic pipelines are sent to a single node, so all their keys have to be
+//! in the same slot as well.
+//!
+//! `ClusterClient::get_pubsub` subscribes to channels, patterns and shard
+//! channels, see `ClusterPubSub`.
+//!
+//! `SCAN` only sees the keys of a single node, `ClusterConnection::scan_keys`
+//! iterates the keys of the whole cluster instead.
+//!
+//! The replies of `CLUSTER INFO`, `CLUSTER SLOTS`, `CLUSTER SHARDS` and
+//! `CLUSTER NODES` can be converted into `ClusterInfo`, `ClusterSlotsReply`,
+//! `ClusterShardsReply` and `ClusterNodesReply`.
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::mem;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cluster_routing::{self, is_readonly_command, parse_packed_commands, RoutingInfo};
+use cmd::{cmd, Cmd};
+use connection::{
+    connect, tcp_addr, Connection, ConnectionAddr, ConnectionInfo, ConnectionLike,
+    IntoConnectionInfo, Msg,
+};
+use types::{
+    clone_error, from_redis_value, random_u64, ErrorKind, FromRedisValue, PushKind, RedisError,
+    RedisResult, ToRedisArgs, Value,
+};
+
+pub use cluster_routing::SLOT_SIZE;
+
+/// Returns the hash slot of a key, like `CLUSTER KEYSLOT`.  Only the hash
+/// tag is hashed if the key has one.
+pub fn key_slot<K: AsRef<[u8]>>(key: K) -> u16 {
+    cluster_routing::key_slot(key.as_ref())
+}
+
+/// Returns the hash tag of a key, the content of its first `{...}`
+/// section, if there is one and it is not empty.
+pub fn hash_tag(key: &[u8]) -> Option<&[u8]> {
+    cluster_routing::get_hashtag(key)
+}
+
+/// Creates a key that hashes like `tag`, by prefixing `key` with `{tag}`.
+/// The tag must not contain `}`.
+pub fn tagged_key(tag: &str, key: &str) -> String {
+    format!("{{{}}}{}", tag, key)
+}
+
+/// Returns the hash slot of the keys of a command, or `None` if it has no
+/// keys.  Fails with `ErrorKind::CrossSlot` if the keys hash to different
+/// slots, which a cluster would reject.
+pub fn command_slot(cmd: &Cmd) -> RedisResult<Option<u16>> {
+    let packed = cmd.get_packed_command();
+    let commands = parse_packed_commands(&packed)?;
+    let args = commands
+        .first()
+        .map(|command| &command.0[..])
+        .unwrap_or(&[]);
+    match RoutingInfo::for_args(args)? {
+        RoutingInfo::Slot(slot) => Ok(Some(slot)),
+        _ => Ok(None),
+    }
+}
+
+/// Which nodes serve read-only commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadFrom {
+    /// Every command goes to the master of the slot.
+    Master,
+    /// Read-only commands go to the replicas of the slot in turn, or to the
+    /// master if the slot has no replica.
+    ReplicaPreferred,
+    /// Read-only commands go to the master and the replicas of the slot in
+    /// turn.
+    RoundRobin,
+}
+
+/// Configures how a `ClusterConnection` deals with changes of the cluster
+/// topology and where it sends reads.
+#[derive(Clone, Debug)]
+pub struct ClusterConfig {
+    /// How often a command is redirected with `MOVED` or `ASK` or retried
+    /// after `TRYAGAIN` before the error is returned.
+    pub max_redirects: u32,
+    /// The slot map is fetched again after a `MOVED` redirect or when a
+    /// node cannot be reached, but at most once per this interval.
+    /// Redirects are followed in the meantime.
+    pub min_refresh_interval: Duration,
+    /// Fetches the slot map periodically.  This is checked before each
+    /// request, there is no background thread.
+    pub refresh_interval: Option<Duration>,
+    /// Where read-only commands are sent.  Unless this is `Master`, every
+    /// connection is put into `READONLY` mode.
+    pub read_from: ReadFrom,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> ClusterConfig {
+        ClusterConfig {
+            max_redirects: 16,
+            min_refresh_interval: Duration::from_millis(100),
+            refresh_interval: None,
+            read_from: ReadFrom::Master,
+        }
+    }
+}
+
+/// A client for redis clusters.
+#[derive(Debug, Clone)]
+pub struct ClusterClient {
+    initial_nodes: Vec<ConnectionInfo>,
+    config: ClusterConfig,
+}
+
+impl ClusterClient {
+    /// Creates a client from the addresses of some nodes of the cluster,
+    /// which are used to discover the rest of it.  The password, timeouts
+    /// and TLS settings of the first node apply to all nodes.
+    pub fn open<T: IntoConnectionInfo>(initial_nodes: Vec<T>) -> RedisResult<ClusterClient> {
+        ClusterClient::with_config(initial_nodes, ClusterConfig::default())
+    }
+
+    /// Creates a client with the given configuration, see `open`.
+    pub fn with_config<T: IntoConnectionInfo>(
+        initial_nodes: Vec<T>,
+        config: ClusterConfig,
+    ) -> RedisResult<ClusterClient> {
+        let initial_nodes = initial_nodes
+            .into_iter()
+            .map(|node| node.into_connection_info())
+            .collect::<RedisResult<Vec<_>>>()?;
+        if initial_nodes.is_empty() {
+            fail!((ErrorKind::InvalidClientConfig, "No initial nodes given"));
+        }
+        for info in &initial_nodes {
+            if info.db != 0 {
+                fail!((
+                    ErrorKind::InvalidClientConfig,
+                    "Redis cluster only supports database 0"
+                ));
+            }
+        }
+        Ok(ClusterClient {
+            initial_nodes: initial_nodes,
+            config: config,
+        })
+    }
+
+    /// Connects to the cluster and fetches its slot map.
+    pub fn get_connection(&self) -> RedisResult<ClusterConnection> {
+        ClusterConnection::new(self.initial_nodes.clone(), self.config.clone())
+    }
+
+    /// Creates a subscriber for the channels of the cluster.  It connects
+    /// to the subscriber nodes once the first subscriptions are made.
+    pub fn get_pubsub(&self) -> RedisResult<ClusterPubSub> {
+        Ok(ClusterPubSub::new(self.get_connection()?))
+    }
+}
+
+// The nodes serving a range of slots.
+#[derive(Debug, Clone)]
+struct SlotRange {
+    start: u16,
+    master: String,
+    replicas: Vec<String>,
+}
+
+/// A connection to a redis cluster.  It implements `ConnectionLike`, so
+/// commands are sent as usual.
+pub struct ClusterConnection {
+    initial_nodes: Vec<ConnectionInfo>,
+    config: ClusterConfig,
+    last_refresh: Instant,
+    connections: HashMap<String, Connection>,
+    // keyed by the last slot of each range
+    slots: BTreeMap<u16, SlotRange>,
+    // picks the node of the next read
+    read_counter: usize,
+    // incremented whenever the slot map is fetched
+    slots_version: u64,
+    // the node of the last request, see `ConnectionLike::last_node`
+    last_node: Option<String>,
+}
+
+fn node_name(addr: &ConnectionAddr) -> Option<String> {
+    match *addr {
+        ConnectionAddr::Tcp(ref host, port) | ConnectionAddr::TcpTls { ref host, port } => {
+            Some(format!("{}:{}", host, port))
+        }
+        _ => None,
+    }
+}
+
+// Parses the node of a `CLUSTER SLOTS` entry, `[host, port, id, ...]`.
+fn parse_slot_node(value: &Value, default_host: &str) -> RedisResult<String> {
+    let node: Vec<Value> = from_redis_value(value)?;
+    if node.len() < 2 {
+        fail!((ErrorKind::TypeError, "Invalid node in CLUSTER SLOTS reply"));
+    }
+    let host: String = from_redis_value(&node[0])?;
+    let port: u16 = from_redis_value(&node[1])?;
+    // an empty host means the node we asked
+    let host = if host.is_empty() {
+        default_host.to_string()
+    } else {
+        host
+    };
+    Ok(format!("{}:{}", host, port))
+}
+
+impl ClusterConnection {
+    fn new(
+        initial_nodes: Vec<ConnectionInfo>,
+        config: ClusterConfig,
+    ) -> RedisResult<ClusterConnection> {
+        let mut con = ClusterConnection {
+            initial_nodes: initial_nodes,
+            config: config,
+            last_refresh: Instant::now(),
+            connections: HashMap::new(),
+            slots: BTreeMap::new(),
+            read_counter: 0,
+            slots_version: 0,
+            last_node: None,
+        };
+        con.refresh_slots()?;
+        Ok(con)
+    }
+
+    // The connection info of the node `host:port`.
+    fn node_info(&self, node: &str) -> RedisResult<ConnectionInfo> {
+        let template = &self.initial_nodes[0];
+        let idx = unwrap_or!(
+            node.rfind(':'),
+            fail!((ErrorKind::InvalidClientConfig, "Invalid node address"))
+        );
+        let port = unwrap_or!(
+            node[idx + 1..].parse().ok(),
+            fail!((ErrorKind::InvalidClientConfig, "Invalid node address"))
+        );
+        let tls = match *template.addr {
+            ConnectionAddr::TcpTls { .. } => true,
+            _ => false,
+        };
+        Ok(ConnectionInfo {
+            addr: Box::new(tcp_addr(node[..idx].to_string(), port, tls)),
+            fallback_addrs: vec![],
+            ..template.clone()
+        })
+    }
+
+    fn get_connection(&mut self, node: &str) -> RedisResult<&mut Connection> {
+        if !self.connections.contains_key(node) {
+            let mut con = connect(&self.node_info(node)?)?;
+            if self.config.read_from != ReadFrom::Master {
+                cmd("READONLY").query::<()>(&mut con)?;
+            }
+            self.connections.insert(node.to_string(), con);
+        }
+        Ok(self.connections.get_mut(node).unwrap())
+    }
+
+    // Asks the connected nodes and then the initial nodes for the slot map
+    // until one of them answers.
+    fn fetch_slots(&mut self) -> RedisResult<BTreeMap<u16, SlotRange>> {
+        let mut nodes: Vec<String> = self.connections.keys().cloned().collect();
+        for info in &self.initial_nodes {
+            if let Some(name) = node_name(&info.addr) {
+                if !nodes.contains(&name) {
+                    nodes.push(name);
+                }
+            }
+        }
+
+        let mut result = Err(RedisError::from((
+            ErrorKind::InvalidClientConfig,
+            "No cluster node to connect to",
+        )));
+        for node in nodes {
+            let host = node[..node.rfind(':').unwrap_or(0)].to_string();
+            let slots = self.get_connection(&node).and_then(|con| {
+                let reply: Vec<Vec<Value>> = cmd("CLUSTER").arg("SLOTS").query(con)?;
+                let mut slots = BTreeMap::new();
+                for entry in reply {
+                    if entry.len() < 3 {
+                        fail!((ErrorKind::TypeError, "Invalid CLUSTER SLOTS reply"));
+                    }
+                    let start: u16 = from_redis_value(&entry[0])?;
+                    let end: u16 = from_redis_value(&entry[1])?;
+                    let replicas = entry[3..]
+                        .iter()
+                        .map(|node| parse_slot_node(node, &host))
+                        .collect::<RedisResult<_>>()?;
+                    slots.insert(
+                        end,
+                        SlotRange {
+                            start: start,
+                            master: parse_slot_node(&entry[2], &host)?,
+                            replicas: replicas,
+                        },
+                    );
+                }
+                Ok(slots)
+            });
+            match slots {
+                Ok(ref slots) if slots.is_empty() => {
+                    result = Err(RedisError::from((
+                        ErrorKind::ClusterDown,
+                        "The cluster has no slots assigned",
+                    )));
+                }
+                Ok(slots) => return Ok(slots),
+                Err(err) => {
+                    self.connections.remove(&node);
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+
+    /// Fetches the slot map again and closes the connections to nodes
+    /// that no longer serve any slot.
+    pub fn refresh_slots(&mut self) -> RedisResult<()> {
+        self.last_refresh = Instant::now();
+        let slots = self.fetch_slots()?;
+        self.connections.retain(|node, _| {
+            slots
+                .values()
+                .any(|range| range.master == *node || range.replicas.contains(node))
+        });
+        self.slots = slots;
+        self.slots_version += 1;
+        Ok(())
+    }
+
+    /// Returns the shard channels with subscribers on all masters,
+    /// optionally only those matching a pattern (redis 7).
+    pub fn shard_channels<T: FromRedisValue>(&mut self, pattern: Option<&str>) -> RedisResult<T> {
+        let mut channels = vec![];
+        for node in self.masters() {
+            let reply: Vec<Value> = self.request_node(&node, &mut |con| {
+                cmd("PUBSUB").arg("SHARDCHANNELS").arg(pattern).query(con)
+            })?;
+            channels.extend(reply);
+        }
+        from_redis_value(&Value::Bulk(channels))
+    }
+
+    /// Iterates the keys of all masters with `SCAN`.  Each master is
+    /// scanned with its own cursor and only keys of the slots it serves
+    /// are returned.  If slots move to another node during the scan and
+    /// the slot map is refreshed, that node is scanned for them from the
+    /// start, so keys may be returned more than once.
+    ///
+    /// The iteration ends after the first error, including a key that
+    /// cannot be converted into `T`.  Scan as `Vec<u8>` if keys may not be
+    /// valid UTF-8.
+    pub fn scan_keys<T: FromRedisValue>(&mut self, options: ScanOptions) -> ClusterScan<T> {
+        let pending = self.split_by_owner(&[(0, SLOT_SIZE - 1)]);
+        let slots_version = self.slots_version;
+        ClusterScan {
+            con: self,
+            options: options,
+            pending: pending,
+            batch: vec![],
+            slots_version: slots_version,
+            failures: 0,
+            done: false,
+        }
+    }
+
+    // Splits the inclusive slot ranges by the masters serving them.  Slots
+    // without a master are left out.
+    fn split_by_owner(&self, ranges: &[(u16, u16)]) -> Vec<ScanState> {
+        let mut states: Vec<ScanState> = vec![];
+        for &(start, end) in ranges {
+            for (&range_end, range) in self.slots.range(start..) {
+                if range.start > end {
+                    break;
+                }
+                let slots = (range.start.max(start), range_end.min(end));
+                match states.iter().position(|state| state.node == range.master) {
+                    Some(pos) => states[pos].slots.push(slots),
+                    None => states.push(ScanState {
+                        node: range.master.clone(),
+                        cursor: 0,
+                        slots: vec![slots],
+                    }),
+                }
+            }
+        }
+        states
+    }
+
+    // Refreshes the slot map if it is older than `interval`.  A failed
+    // refresh keeps the old map, the request that follows reports errors.
+    fn refresh_if_older(&mut self, interval: Duration) {
+        if self.last_refresh.elapsed() >= interval {
+            let _ = self.refresh_slots();
+        }
+    }
+
+    fn slot_range(&self, slot: u16) -> RedisResult<&SlotRange> {
+        match self.slots.range(slot..).next() {
+            Some((_, range)) if range.start <= slot => Ok(range),
+            _ => Err(RedisError::from((
+                ErrorKind::ClusterDown,
+                "Slot is not served by any node",
+                slot.to_string(),
+            ))),
+        }
+    }
+
+    fn node_for_slot(&self, slot: u16) -> RedisResult<String> {
+        Ok(self.slot_range(slot)?.master.clone())
+    }
+
+    // The node that serves a read of the slot according to `read_from`.
+    fn read_node_for_slot(&mut self, slot: u16) -> RedisResult<String> {
+        let counter = self.read_counter;
+        let node = {
+            let range = self.slot_range(slot)?;
+            match self.config.read_from {
+                ReadFrom::Master => range.master.clone(),
+                ReadFrom::ReplicaPreferred if range.replicas.is_empty() => range.master.clone(),
+                ReadFrom::ReplicaPreferred => {
+                    range.replicas[counter % range.replicas.len()].clone()
+                }
+                ReadFrom::RoundRobin => match counter % (range.replicas.len() + 1) {
+                    0 => range.master.clone(),
+                    idx => range.replicas[idx - 1].clone(),
+                },
+            }
+        };
+        self.read_counter = counter.wrapping_add(1);
+        Ok(node)
+    }
+
+    fn masters(&self) -> Vec<String> {
+        let mut masters: Vec<String> = self.slots.values().map(|r| r.master.clone()).collect();
+        masters.sort();
+        masters.dedup();
+        masters
+    }
+
+    fn random_master(&self) -> RedisResult<String> {
+        let masters = self.masters();
+        if masters.is_empty() {
+            fail!((ErrorKind::ClusterDown, "The cluster has no slots assigned"));
+        }
+        // prefer a node we are connected to already
+        for master in &masters {
+            if self.connections.contains_key(master) {
+                return Ok(master.clone());
+            }
+        }
+        Ok(masters[(random_u64() % masters.len() as u64) as usize].clone())
+    }
+
+    // Drops the broken connection to `node`, so that the next request
+    // reconnects, and refreshes the slot map since the node may have
+    // failed over or left the cluster.
+    fn drop_broken(&mut self, node: &str) {
+        self.connections.remove(node);
+        let interval = self.config.min_refresh_interval;
+        self.refresh_if_older(interval);
+    }
+
+    // Runs `func` on the connection to `node`.
+    fn request_node<T, F>(&mut self, node: &str, func: &mut F) -> RedisResult<T>
+    where
+        F: FnMut(&mut Connection) -> RedisResult<T>,
+    {
+        if self.last_node.as_ref().map(|last| &last[..]) != Some(node) {
+            self.last_node = Some(node.to_string());
+        }
+        let (result, broken) = match self.get_connection(node) {
+            Ok(con) => {
+                let result = func(con);
+                let broken = match result {
+                    Err(ref err) => err.is_io_error(),
+                    Ok(_) => false,
+                };
+                (result, broken)
+            }
+            Err(err) => (Err(err), true),
+        };
+        if broken {
+            self.drop_broken(node);
+        }
+        result
+    }
+
+    fn request<T, F>(&mut self, routing: RoutingInfo, readonly: bool, mut func: F) -> RedisResult<T>
+    where
+        F: FnMut(&mut Connection) -> RedisResult<T>,
+    {
+        match routing {
+            RoutingInfo::Slot(slot) => {
+                let node = if readonly {
+                    self.read_node_for_slot(slot)?
+                } else {
+                    self.node_for_slot(slot)?
+                };
+                self.request_node(&node, &mut func)
+            }
+            RoutingInfo::AllMasters => {
+                let mut result = Err(RedisError::from((
+                    ErrorKind::ClusterDown,
+                    "The cluster has no slots assigned",
+                )));
+                for node in self.masters() {
+                    result = Ok(self.request_node(&node, &mut func)?);
+                }
+                result
+            }
+            RoutingInfo::Random => {
+                let node = self.random_master()?;
+                self.request_node(&node, &mut func)
+            }
+        }
+    }
+
+    // Like `request` but follows redirects and retries after `TRYAGAIN`.
+    fn request_with_redirects<T, F>(
+        &mut self,
+        routing: RoutingInfo,
+        readonly: bool,
+        mut func: F,
+    ) -> RedisResult<T>
+    where
+        F: FnMut(&mut Connection) -> RedisResult<T>,
+    {
+        if let Some(interval) = self.config.refresh_interval {
+            self.refresh_if_older(interval);
+        }
+        let result = self.request(routing, readonly, &mut func);
+        self.follow_redirects(routing, readonly, result, func)
+    }
+
+    // Handles the redirect or `TRYAGAIN` error in `result`, if any.
+    fn follow_redirects<T, F>(
+        &mut self,
+        routing: RoutingInfo,
+        readonly: bool,
+        mut result: RedisResult<T>,
+        mut func: F,
+    ) -> RedisResult<T>
+    where
+        F: FnMut(&mut Connection) -> RedisResult<T>,
+    {
+        let mut redirects = 0;
+        while redirects < self.config.max_redirects {
+            let (kind, node) = match result {
+                Err(ref err) => match err.kind() {
+                    ErrorKind::Moved | ErrorKind::Ask => match err.redirect_node() {
+                        Some((node, _)) => (err.kind(), node.to_string()),
+                        None => break,
+                    },
+                    ErrorKind::TryAgain => (ErrorKind::TryAgain, String::new()),
+                    _ => break,
+                },
+                Ok(_) => break,
+            };
+            redirects += 1;
+            result = match kind {
+                ErrorKind::Moved => {
+                    let interval = self.config.min_refresh_interval;
+                    self.refresh_if_older(interval);
+                    self.request_node(&node, &mut func)
+                }
+                ErrorKind::Ask => self.request_node(&node, &mut |con: &mut Connection| {
+                    cmd("ASKING").query::<()>(con)?;
+                    func(con)
+                }),
+                _ => {
+                    // the slot is being migrated, give it a moment
+                    thread::sleep(Duration::from_millis(10 * u64::from(redirects)));
+                    self.request(routing, readonly, &mut func)
+                }
+            };
+        }
+        result
+    }
+
+    // Sends the commands of a pipeline to their nodes and reads the
+    // replies afterwards, so the nodes work on them at the same time.
+    fn execute_pipeline(&mut self, commands: Vec<(Vec<Value>, &[u8])>) -> RedisResult<Vec<Value>> {
+        let mut batches: Vec<(String, Vec<u8>, Vec<usize>)> = vec![];
+        let mut random_node: Option<String> = None;
+        for (idx, &(ref args, packed)) in commands.iter().enumerate() {
+            let node = match RoutingInfo::for_args(args)? {
+                RoutingInfo::Slot(slot) if is_readonly_command(args) => {
+                    self.read_node_for_slot(slot)?
+                }
+                RoutingInfo::Slot(slot) => self.node_for_slot(slot)?,
+                RoutingInfo::Random => match random_node {
+                    Some(ref node) => node.clone(),
+                    None => {
+                        let node = self.random_master()?;
+                        random_node = Some(node.clone());
+                        node
+                    }
+                },
+                RoutingInfo::AllMasters => fail!((
+                    ErrorKind::InvalidClientConfig,
+                    "Commands for all nodes cannot be pipelined in a cluster"
+                )),
+            };
+            match batches.iter().position(|batch| batch.0 == node) {
+                Some(pos) => {
+                    batches[pos].1.extend_from_slice(packed);
+                    batches[pos].2.push(idx);
+                }
+                None => batches.push((node, packed.to_vec(), vec![idx])),
+            }
+        }
+
+        let mut sent = vec![];
+        let mut error = None;
+        // the slot map is refreshed once all replies were read, as that
+        // may close connections that still have replies pending
+        let mut broken = false;
+        for &(ref node, ref packed, _) in &batches {
+            let result = self
+                .get_connection(node)
+                .and_then(|con| con.send_packed_command(packed));
+            match result {
+                Ok(()) => sent.push(true),
+                Err(err) => {
+                    self.connections.remove(node);
+                    broken = true;
+                    sent.push(false);
+                    error = error.or(Some(err));
+                }
+            }
+        }
+
+        let mut results: Vec<Option<Value>> = commands.iter().map(|_| None).collect();
+        let mut redirected = vec![];
+        for (&(ref node, _, ref indices), sent) in batches.iter().zip(sent) {
+            if !sent {
+                continue;
+            }
+            // read every reply, even after errors, to keep the connection
+            // in sync
+            for &idx in indices {
+                match self
+                    .get_connection(node)
+                    .and_then(|con| con.recv_response())
+                {
+                    Ok(value) => results[idx] = Some(value),
+                    Err(ref err)
+                        if err.is_cluster_redirect() || err.kind() == ErrorKind::TryAgain =>
+                    {
+                        redirected.push((idx, clone_error(err)));
+                    }
+                    Err(err) => {
+                        let is_io_error = err.is_io_error();
+                        error = error.or(Some(err));
+                        if is_io_error {
+                            self.connections.remove(node);
+                            broken = true;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        if broken {
+            let interval = self.config.min_refresh_interval;
+            self.refresh_if_older(interval);
+        }
+
+        // the redirected commands were not executed, send them again
+        for (idx, err) in redirected {
+            let (ref args, packed) = commands[idx];
+            let routing = RoutingInfo::for_args(args)?;
+            let readonly = is_readonly_command(args);
+            let result = self.follow_redirects(routing, readonly, Err(err), |con| {
+                con.req_packed_command(packed)
+            });
+            match result {
+                Ok(value) => results[idx] = Some(value),
+                Err(err) => error = error.or(Some(err)),
+            }
+        }
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(results.into_iter().map(|value| value.unwrap()).collect()),
+        }
+    }
+}
+
+impl ConnectionLike for ClusterConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        let commands = parse_packed_commands(cmd)?;
+        if commands.len() != 1 {
+            fail!((ErrorKind::TypeError, "Expected a single packed command"));
+        }
+        let args = &commands[0].0;
+        let routing = RoutingInfo::for_args(args)?;
+        let readonly = is_readonly_command(args);
+        self.request_with_redirects(routing, readonly, |con| con.req_packed_command(cmd))
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        let commands = parse_packed_commands(cmd)?;
+        if offset > 0 {
+            // a transaction, which has to go to a single node as a whole
+            let routing =
+                RoutingInfo::for_commands(commands.iter().map(|&(ref args, _)| &args[..]))?;
+            return self.request_with_redirects(routing, false, |con| {
+                con.req_packed_commands(cmd, offset, count)
+            });
+        }
+        self.execute_pipeline(commands)
+    }
+
+    fn last_node(&self) -> Option<&str> {
+        self.last_node.as_ref().map(|node| &node[..])
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+}
+
+/// Filters of a cluster wide scan, see `ClusterConnection::scan_keys`.
+#[derive(Clone, Debug, Default)]
+pub struct ScanOptions {
+    /// Only keys matching this glob-style pattern are returned (`MATCH`).
+    /// Like keys, patterns are binary safe.
+    pub pattern: Option<Vec<u8>>,
+    /// How many keys a node looks at per call (`COUNT`).
+    pub count: Option<usize>,
+    /// Only keys of this type, like `hash`, are returned (`TYPE`).  This
+    /// requires redis 6.
+    pub key_type: Option<String>,
+}
+
+// The part of a cluster wide scan done by one node.
+struct ScanState {
+    node: String,
+    cursor: u64,
+    // inclusive ranges of the slots the node is scanned for
+    slots: Vec<(u16, u16)>,
+}
+
+impl ScanState {
+    fn contains(&self, key: &Value) -> bool {
+        match *key {
+            Value::Data(ref key) => {
+                let slot = cluster_routing::key_slot(key);
+                self.slots
+                    .iter()
+                    .any(|&(start, end)| start <= slot && slot <= end)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// An iterator over the keys of a cluster, see
+/// `ClusterConnection::scan_keys`.
+pub struct ClusterScan<'a, T> {
+    con: &'a mut ClusterConnection,
+    options: ScanOptions,
+    pending: Vec<ScanState>,
+    batch: Vec<T>,
+    slots_version: u64,
+    failures: u32,
+    done: bool,
+}
+
+impl<'a, T: FromRedisValue> ClusterScan<'a, T> {
+    // Reassigns the slots of the pending scans after the slot map changed.
+    // A node keeps its cursor for the slots it still serves.
+    fn rebalance(&mut self) {
+        let pending = mem::replace(&mut self.pending, vec![]);
+        for state in pending {
+            for mut owner in self.con.split_by_owner(&state.slots) {
+                if owner.node == state.node {
+                    owner.cursor = state.cursor;
+                }
+                self.pending.push(owner);
+            }
+        }
+        self.slots_version = self.con.slots_version;
+    }
+
+    // Fetches the next batch of keys from one of the nodes.
+    fn fetch(&mut self) -> RedisResult<()> {
+        if let Some(interval) = self.con.config.refresh_interval {
+            self.con.refresh_if_older(interval);
+        }
+        if self.slots_version != self.con.slots_version {
+            self.rebalance();
+        }
+        let mut state = unwrap_or!(self.pending.pop(), {
+            self.done = true;
+            return Ok(());
+        });
+
+        let mut scan = cmd("SCAN");
+        scan.arg(state.cursor);
+        if let Some(ref pattern) = self.options.pattern {
+            scan.arg("MATCH").arg(&pattern[..]);
+        }
+        if let Some(count) = self.options.count {
+            scan.arg("COUNT").arg(count);
+        }
+        if let Some(ref key_type) = self.options.key_type {
+            scan.arg("TYPE").arg(key_type);
+        }
+        let result = self
+            .con
+            .request_node(&state.node, &mut |con: &mut Connection| {
+                scan.query::<(u64, Vec<Value>)>(con)
+            });
+        match result {
+            Ok((cursor, keys)) => {
+                self.failures = 0;
+                for key in keys.iter().rev() {
+                    if state.contains(key) {
+                        self.batch.push(from_redis_value(key)?);
+                    }
+                }
+                if cursor != 0 {
+                    state.cursor = cursor;
+                    self.pending.push(state);
+                }
+                Ok(())
+            }
+            Err(ref err)
+                if (err.is_io_error() || err.kind() == ErrorKind::ClusterDown)
+                    && self.failures < self.con.config.max_redirects =>
+            {
+                // the node may have failed over, ask the cluster who serves
+                // its slots now
+                self.failures += 1;
+                self.pending.push(state);
+                thread::sleep(Duration::from_millis(10 * u64::from(self.failures)));
+                let _ = self.con.refresh_slots();
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<'a, T: FromRedisValue> Iterator for ClusterScan<'a, T> {
+    type Item = RedisResult<T>;
+
+    fn next(&mut self) -> Option<RedisResult<T>> {
+        loop {
+            if let Some(key) = self.batch.pop() {
+                return Some(Ok(key));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(err) = self.fetch() {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+// How long a subscriber connection is waited on before the next one is
+// polled when there are several.
+const POLL_INTERVAL_MS: u64 = 20;
+
+/// Receives the messages of channels, patterns and shard channels of a
+/// cluster.
+///
+/// Regular messages are broadcast to every node, so channels and patterns
+/// are subscribed at a single node.  Shard channels (`SSUBSCRIBE`, redis 7)
+/// are subscribed at the master serving the slot of the channel.  When a
+/// subscriber node fails or a slot moves, the slot map is refreshed and the
+/// affected subscriptions are made again at the new node, so messages sent
+/// in the meantime are lost.
+///
+/// With several subscriber connections they are polled in turn, each with
+/// a short read timeout.
+///
+/// ```rust,no_run
+/// # fn do_something() -> redis::RedisResult<()> {
+/// use redis::cluster::ClusterClient;
+///
+/// let client = ClusterClient::open(vec!["redis://127.0.0.1:7000/"])?;
+/// let mut pubsub = client.get_pubsub()?;
+/// pubsub.subscribe("news")?;
+/// pubsub.ssubscribe("{user1}:events")?;
+/// loop {
+///     let msg = pubsub.get_message()?;
+///     let payload: String = msg.get_payload()?;
+///     println!("channel '{}': {}", msg.get_channel_name(), payload);
+/// }
+/// # }
+/// ```
+pub struct ClusterPubSub {
+    con: ClusterConnection,
+    subscribers: HashMap<String, Connection>,
+    // the node with the channel and pattern subscriptions
+    global_node: Option<String>,
+    channels: Vec<Vec<u8>>,
+    patterns: Vec<Vec<u8>>,
+    // the node each shard channel is subscribed at, `None` if the
+    // subscription has to be made (again)
+    shard_channels: HashMap<Vec<u8>, Option<String>>,
+    next_node: usize,
+    read_timeout: Option<Duration>,
+}
+
+impl ClusterPubSub {
+    fn new(con: ClusterConnection) -> ClusterPubSub {
+        ClusterPubSub {
+            con: con,
+            subscribers: HashMap::new(),
+            global_node: None,
+            channels: vec![],
+            patterns: vec![],
+            shard_channels: HashMap::new(),
+            next_node: 0,
+            read_timeout: None,
+        }
+    }
+
+    /// Subscribes to new channels.
+    pub fn subscribe<T: ToRedisArgs>(&mut self, channel: T) -> RedisResult<()> {
+        let names = channel.to_redis_args();
+        self.send_global("SUBSCRIBE", &names)?;
+        self.channels.extend(names);
+        Ok(())
+    }
+
+    /// Subscribes to new channel patterns.
+    pub fn psubscribe<T: ToRedisArgs>(&mut self, pchannel: T) -> RedisResult<()> {
+        let names = pchannel.to_redis_args();
+        self.send_global("PSUBSCRIBE", &names)?;
+        self.patterns.extend(names);
+        Ok(())
+    }
+
+    /// Unsubscribes from channels.
+    pub fn unsubscribe<T: ToRedisArgs>(&mut self, channel: T) -> RedisResult<()> {
+        let names = channel.to_redis_args();
+        self.channels.retain(|name| !names.contains(name));
+        self.send_global("UNSUBSCRIBE", &names)
+    }
+
+    /// Unsubscribes from channel patterns.
+    pub fn punsubscribe<T: ToRedisArgs>(&mut self, pchannel: T) -> RedisResult<()> {
+        let names = pchannel.to_redis_args();
+        self.patterns.retain(|name| !names.contains(name));
+        self.send_global("PUNSUBSCRIBE", &names)
+    }
+
+    /// Subscribes to new shard channels.  Each channel is subscribed at the
+    /// master serving its slot.  If that fails the subscription is made
+    /// again by `get_message`.
+    pub fn ssubscribe<T: ToRedisArgs>(&mut self, channel: T) -> RedisResult<()> {
+        for name in channel.to_redis_args() {
+            self.shard_channels.insert(name, None);
+        }
+        self.resubscribe()
+    }
+
+    /// Unsubscribes from shard channels.
+    pub fn sunsubscribe<T: ToRedisArgs>(&mut self, channel: T) -> RedisResult<()> {
+        for name in channel.to_redis_args() {
+            if let Some(Some(node)) = self.shard_channels.remove(&name) {
+                self.send(&node, cmd("SUNSUBSCRIBE").arg(name))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the read timeout of `get_message`.  `None`, the default, blocks
+    /// until a message arrives.
+    pub fn set_read_timeout(&mut self, dur: Option<Duration>) {
+        self.read_timeout = dur;
+    }
+
+    fn subscriber(&mut self, node: &str) -> RedisResult<&mut Connection> {
+        if !self.subscribers.contains_key(node) {
+            let con = connect(&self.con.node_info(node)?)?;
+            self.subscribers.insert(node.to_string(), con);
+        }
+        Ok(self.subscribers.get_mut(node).unwrap())
+    }
+
+    // Sends a command without waiting for the reply, the confirmations of
+    // subscriptions are skipped by `get_message`.
+    fn send(&mut self, node: &str, cmd: &Cmd) -> RedisResult<()> {
+        let result = self
+            .subscriber(node)
+            .and_then(|con| con.send_packed_command(&cmd.get_packed_command()));
+        if result.is_err() {
+            self.drop_subscriber(node);
+        }
+        result
+    }
+
+    fn send_global(&mut self, command: &str, names: &[Vec<u8>]) -> RedisResult<()> {
+        if names.is_empty() {
+            return Ok(());
+        }
+        let node = match self.global_node {
+            Some(ref node) => node.clone(),
+            None => self.con.random_master()?,
+        };
+        self.send(&node, cmd(command).arg(names))?;
+        self.global_node = Some(node);
+        Ok(())
+    }
+
+    // Forgets a broken subscriber connection, so that its subscriptions
+    // are made again at the node serving them now.
+    fn drop_subscriber(&mut self, node: &str) {
+        self.subscribers.remove(node);
+        if self.global_node.as_ref().map(|x| &x[..]) == Some(node) {
+            self.global_node = None;
+        }
+        for subscribed_at in self.shard_channels.values_mut() {
+            if subscribed_at.as_ref().map(|x| &x[..]) == Some(node) {
+                *subscribed_at = None;
+            }
+        }
+        let _ = self.con.refresh_slots();
+    }
+
+    // Makes the subscriptions that were lost.
+    fn resubscribe(&mut self) -> RedisResult<()> {
+        if self.global_node.is_none() {
+            let channels = self.channels.clone();
+            let patterns = self.patterns.clone();
+            self.send_global("SUBSCRIBE", &channels)?;
+            self.send_global("PSUBSCRIBE", &patterns)?;
+        }
+        let lost: Vec<Vec<u8>> = self
+            .shard_channels
+            .iter()
+            .filter(|&(_, node)| node.is_none())
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in lost {
+            let node = self.con.node_for_slot(cluster_routing::key_slot(&name))?;
+            // a shard channel has a slot of its own, so it is subscribed
+            // on its own
+            self.send(&node, cmd("SSUBSCRIBE").arg(&name[..]))?;
+            self.shard_channels.insert(name, Some(node));
+        }
+        Ok(())
+    }
+
+    // Handles a reply that is not a message.
+    fn handle_reply(&mut self, node: &str, value: &Value) {
+        // the server unsubscribes shard channels whose slot moved away
+        let name = match *value {
+            Value::Bulk(ref items) if items.len() == 3 => {
+                let kind: String = unwrap_or!(from_redis_value(&items[0]).ok(), return);
+                if kind != "sunsubscribe" {
+                    return;
+                }
+                &items[1]
+            }
+            Value::Push {
+                kind: PushKind::SUnsubscribe,
+                ref data,
+            } if data.len() == 2 => &data[0],
+            _ => return,
+        };
+        let name: Vec<u8> = unwrap_or!(from_redis_value(name).ok(), return);
+        if let Some(subscribed_at) = self.shard_channels.get_mut(&name) {
+            if subscribed_at.as_ref().map(|x| &x[..]) == Some(node) {
+                *subscribed_at = None;
+            }
+        }
+    }
+
+    /// Fetches the next message of any subscription.  Blocks until a
+    /// message arrives or the read timeout passes.
+    pub fn get_message(&mut self) -> RedisResult<Msg> {
+        let deadline = self.read_timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if self.shard_channels.values().any(|node| node.is_none()) {
+                let _ = self.con.refresh_slots();
+            }
+            self.resubscribe()?;
+
+            let mut nodes: Vec<String> = self.subscribers.keys().cloned().collect();
+            if nodes.is_empty() {
+                fail!((ErrorKind::InvalidClientConfig, "Not subscribed to anything"));
+            }
+            nodes.sort();
+            let node = nodes[self.next_node % nodes.len()].clone();
+            self.next_node = self.next_node.wrapping_add(1);
+
+            let remaining = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(RedisError::from(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "no message received",
+                        )));
+                    }
+                    Some(deadline - now)
+                }
+                None => None,
+            };
+            let timeout = if nodes.len() > 1 {
+                let poll = Duration::from_millis(POLL_INTERVAL_MS);
+                Some(remaining.map_or(poll, |remaining| remaining.min(poll)))
+            } else {
+                remaining
+            };
+
+            let result = self.subscriber(&node).and_then(|con| {
+                con.set_read_timeout(timeout)?;
+                con.recv_response()
+            });
+            match result {
+                Ok(value) => match Msg::from_value(&value) {
+                    Some(msg) => return Ok(msg),
+                    None => self.handle_reply(&node, &value),
+                },
+                Err(ref err) if err.is_timeout() => {}
+                Err(ref err) if err.is_cluster_redirect() => {
+                    // a shard channel was subscribed at the wrong node
+                    let slot = err.redirect_node().map(|(_, slot)| slot);
+                    for (name, subscribed_at) in &mut self.shard_channels {
+                        if Some(cluster_routing::key_slot(name)) == slot {
+                            *subscribed_at = None;
+                        }
+                    }
+                }
+                Err(ref err) if err.is_io_error() => self.drop_subscriber(&node),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// The reply of `CLUSTER INFO`.
+///
+/// ```rust,no_run
+/// # fn do_something() -> redis::RedisResult<()> {
+/// # let client = redis::Client::open("redis://127.0.0.1:7000/").unwrap();
+/// # let mut con = client.get_connection().unwrap();
+/// use redis::Commands;
+/// use redis::cluster::ClusterInfo;
+///
+/// let info: ClusterInfo = con.cluster_info()?;
+/// if !info.state_ok {
+///     println!("{} of {} slots failed", info.slots_fail, info.slots_assigned);
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClusterInfo {
+    /// Whether `cluster_state` is `ok`, so that the node serves queries.
+    pub state_ok: bool,
+    /// The number of slots that are assigned to a node.
+    pub slots_assigned: usize,
+    /// The number of assigned slots whose node is reachable.
+    pub slots_ok: usize,
+    /// The number of assigned slots whose node may have failed.
+    pub slots_pfail: usize,
+    /// The number of assigned slots whose node has failed.
+    pub slots_fail: usize,
+    /// The number of nodes in the cluster, including handshaking ones.
+    pub known_nodes: usize,
+    /// The number of masters that serve at least one slot.
+    pub size: usize,
+    /// The highest epoch known to the node.
+    pub current_epoch: u64,
+    /// The configuration epoch of the node.
+    pub my_epoch: u64,
+    /// All fields of the reply, including the ones above.
+    pub fields: HashMap<String, String>,
+}
+
+// Looks up a numeric field of the `CLUSTER INFO` reply.
+fn info_number(fields: &HashMap<String, String>, key: &str) -> u64 {
+    fields
+        .get(key)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0)
+}
+
+impl FromRedisValue for ClusterInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<ClusterInfo> {
+        let text: String = from_redis_value(v)?;
+        let mut fields = HashMap::new();
+        for line in text.lines() {
+            let mut pieces = line.splitn(2, ':');
+            let key = unwrap_or!(pieces.next(), continue);
+            let value = unwrap_or!(pieces.next(), continue);
+            fields.insert(key.to_string(), value.trim().to_string());
+        }
+        Ok(ClusterInfo {
+            state_ok: fields.get("cluster_state").map(|x| &x[..]) == Some("ok"),
+            slots_assigned: info_number(&fields, "cluster_slots_assigned") as usize,
+            slots_ok: info_number(&fields, "cluster_slots_ok") as usize,
+            slots_pfail: info_number(&fields, "cluster_slots_pfail") as usize,
+            slots_fail: info_number(&fields, "cluster_slots_fail") as usize,
+            known_nodes: info_number(&fields, "cluster_known_nodes") as usize,
+            size: info_number(&fields, "cluster_size") as usize,
+            current_epoch: info_number(&fields, "cluster_current_epoch"),
+            my_epoch: info_number(&fields, "cluster_my_epoch"),
+            fields: fields,
+        })
+    }
+}
+
+/// A node of a `CLUSTER SLOTS` entry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterSlotNode {
+    /// The host of the node.  It is empty if the node does not know its
+    /// own address.
+    pub host: String,
+    /// The port of the node.
+    pub port: u16,
+    /// The id of the node, which redis 4 and later report.
+    pub id: Option<String>,
+}
+
+impl FromRedisValue for ClusterSlotNode {
+    fn from_redis_value(v: &Value) -> RedisResult<ClusterSlotNode> {
+        match *v {
+            Value::Bulk(ref items) if items.len() >= 2 => Ok(ClusterSlotNode {
+                host: from_redis_value(&items[0])?,
+                port: from_redis_value(&items[1])?,
+                id: match items.get(2) {
+                    Some(id) => Some(from_redis_value(id)?),
+                    None => None,
+                },
+            }),
+            _ => invalid_type_error!(v, "Response type not cluster slot node compatible"),
+        }
+    }
+}
+
+/// A range of slots and the nodes serving it, an entry of `CLUSTER SLOTS`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterSlotRange {
+    /// The first slot of the range.
+    pub start: u16,
+    /// The last slot of the range, inclusive.
+    pub end: u16,
+    /// The master serving the range.
+    pub master: ClusterSlotNode,
+    /// The replicas of the master.
+    pub replicas: Vec<ClusterSlotNode>,
+}
+
+impl FromRedisValue for ClusterSlotRange {
+    fn from_redis_value(v: &Value) -> RedisResult<ClusterSlotRange> {
+        match *v {
+            Value::Bulk(ref items) if items.len() >= 3 => Ok(ClusterSlotRange {
+                start: from_redis_value(&items[0])?,
+                end: from_redis_value(&items[1])?,
+                master: from_redis_value(&items[2])?,
+                replicas: items[3..]
+                    .iter()
+                    .map(from_redis_value)
+                    .collect::<RedisResult<_>>()?,
+            }),
+            _ => invalid_type_error!(v, "Response type not cluster slot range compatible"),
+        }
+    }
+}
+
+/// The reply of `CLUSTER SLOTS`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClusterSlotsReply {
+    /// The slot ranges in the order returned by the server.
+    pub ranges: Vec<ClusterSlotRange>,
+}
+
+impl FromRedisValue for ClusterSlotsReply {
+    fn from_redis_value(v: &Value) -> RedisResult<ClusterSlotsReply> {
+        Ok(ClusterSlotsReply {
+            ranges: from_redis_value(v)?,
+        })
+    }
+}
+
+/// A node of a shard as reported by `CLUSTER SHARDS`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterShardNode {
+    /// The id of the node.
+    pub id: String,
+    /// The plain text port, if the node has one.
+    pub port: Option<u16>,
+    /// The TLS port, if the node has one.
+    pub tls_port: Option<u16>,
+    /// The IP address of the node.
+    pub ip: String,
+    /// The preferred endpoint to connect to the node.
+    pub endpoint: String,
+    /// The announced hostname of the node.
+    pub hostname: Option<String>,
+    /// `master` or `replica`.
+    pub role: String,
+    /// The replication offset of the node.
+    pub replication_offset: i64,
+    /// `online`, `failed` or `loading`.
+    pub health: String,
+}
+
+// Looks up a field of a `CLUSTER SHARDS` map.
+fn shard_field<T: FromRedisValue>(map: &HashMap<String, Value>, key: &str) -> RedisResult<T> {
+    match map.get(key) {
+        Some(value) => from_redis_value(value),
+        None => fail!((
+            ErrorKind::TypeError,
+            "Missing field in CLUSTER SHARDS reply",
+            key.to_string()
+        )),
+    }
+}
+
+fn optional_shard_field<T: FromRedisValue>(
+    map: &HashMap<String, Value>,
+    key: &str,
+) -> RedisResult<Option<T>> {
+    match map.get(key) {
+        Some(value) => Ok(Some(from_redis_value(value)?)),
+        None => Ok(None),
+    }
+}
+
+impl FromRedisValue for ClusterShardNode {
+    fn from_redis_value(v: &Value) -> RedisResult<ClusterShardNode> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(ClusterShardNode {
+            id: shard_field(&map, "id")?,
+            port: optional_shard_field(&map, "port")?,
+            tls_port: optional_shard_field(&map, "tls-port")?,
+            ip: shard_field(&map, "ip")?,
+            endpoint: shard_field(&map, "endpoint")?,
+            hostname: optional_shard_field(&map, "hostname")?,
+            role: shard_field(&map, "role")?,
+            replication_offset: shard_field(&map, "replication-offset")?,
+            health: shard_field(&map, "health")?,
+        })
+    }
+}
+
+/// A master and its replicas as reported by `CLUSTER SHARDS`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterShard {
+    /// The inclusive slot ranges served by the shard.
+    pub slots: Vec<(u16, u16)>,
+    /// The nodes of the shard.
+    pub nodes: Vec<ClusterShardNode>,
+}
+
+impl FromRedisValue for ClusterShard {
+    fn from_redis_value(v: &Value) -> RedisResult<ClusterShard> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        // the ranges are sent as a flat list of bounds
+        let bounds: Vec<u16> = shard_field(&map, "slots")?;
+        if bounds.len() % 2 != 0 {
+            invalid_type_error!(v, "Odd number of slot bounds in CLUSTER SHARDS reply");
+        }
+        Ok(ClusterShard {
+            slots: bounds.chunks(2).map(|pair| (pair[0], pair[1])).collect(),
+            nodes: shard_field(&map, "nodes")?,
+        })
+    }
+}
+
+/// The reply of `CLUSTER SHARDS`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClusterShardsReply {
+    /// The shards of the cluster.
+    pub shards: Vec<ClusterShard>,
+}
+
+impl FromRedisValue for ClusterShardsReply {
+    fn from_redis_value(v: &Value) -> RedisResult<ClusterShardsReply> {
+        Ok(ClusterShardsReply {
+            shards: from_redis_value(v)?,
+        })
+    }
+}
+
+/// A line of the `CLUSTER NODES` reply.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClusterNodeInfo {
+    /// The id of the node.
+    pub id: String,
+    /// The host of the node.  It is empty if the address is not known yet.
+    pub host: String,
+    /// The port clients connect to.
+    pub port: u16,
+    /// The port of the cluster bus, which redis 4 and later report.
+    pub cluster_port: Option<u16>,
+    /// The announced hostname of the node.
+    pub hostname: Option<String>,
+    /// The flags, like `myself`, `master`, `slave` or `fail?`.
+    pub flags: Vec<String>,
+    /// The id of the master if the node is a replica.
+    pub master_id: Option<String>,
+    /// When the last pending ping was sent, in milliseconds since the epoch.
+    pub ping_sent: u64,
+    /// When the last pong was received, in milliseconds since the epoch.
+    pub pong_received: u64,
+    /// The configuration epoch of the node.
+    pub config_epoch: u64,
+    /// Whether the cluster bus link to the node is up.
+    pub connected: bool,
+    /// The inclusive slot ranges served by the node.  Slots that are being
+    /// imported or migrated are not included.
+    pub slots: Vec<(u16, u16)>,
+}
+
+impl ClusterNodeInfo {
+    /// Returns whether the node has the given flag.
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|x| x == flag)
+    }
+
+    /// Returns whether the node is a master.
+    pub fn is_master(&self) -> bool {
+        self.has_flag("master")
+    }
+
+    /// Returns whether this is the node that was asked.
+    pub fn is_myself(&self) -> bool {
+        self.has_flag("myself")
+    }
+
+    /// Parses a line of the `CLUSTER NODES` reply.
+    pub fn parse(line: &str) -> Option<ClusterNodeInfo> {
+        let parts: Vec<&str> = line.split(' ').collect();
+        if parts.len() < 8 {
+            return None;
+        }
+        // `host:port@cport,hostname` with the last two parts being optional
+        let mut addr = parts[1].splitn(2, ',');
+        let endpoint = addr.next()?;
+        let hostname = addr.next().map(|x| x.to_string());
+        let mut endpoint = endpoint.splitn(2, '@');
+        let host_port = endpoint.next()?;
+        let cluster_port = match endpoint.next() {
+            Some(port) => Some(port.parse().ok()?),
+            None => None,
+        };
+        let idx = host_port.rfind(':')?;
+
+        let mut slots = vec![];
+        for slot in &parts[8..] {
+            if slot.starts_with('[') {
+                continue;
+            }
+            let mut bounds = slot.splitn(2, '-');
+            let start = bounds.next()?.parse().ok()?;
+            let end = match bounds.next() {
+                Some(end) => end.parse().ok()?,
+                None => start,
+            };
+            slots.push((start, end));
+        }
+
+        Some(ClusterNodeInfo {
+            id: parts[0].to_string(),
+            host: host_port[..idx].to_string(),
+            port: host_port[idx + 1..].parse().ok()?,
+            cluster_port: cluster_port,
+            hostname: hostname,
+            flags: parts[2].split(',').map(|x| x.to_string()).collect(),
+            master_id: match parts[3] {
+                "-" => None,
+                id => Some(id.to_string()),
+            },
+            ping_sent: parts[4].parse().ok()?,
+            pong_received: parts[5].parse().ok()?,
+            config_epoch: parts[6].parse().ok()?,
+            connected: parts[7] == "connected",
+            slots: slots,
+        })
+    }
+}
+
+/// The reply of `CLUSTER NODES`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClusterNodesReply {
+    /// The nodes in the order returned by the server.
+    pub nodes: Vec<ClusterNodeInfo>,
+}
+
+impl ClusterNodesReply {
+    /// Returns the node that was asked.
+    pub fn myself(&self) -> Option<&ClusterNodeInfo> {
+        self.nodes.iter().find(|node| node.is_myself())
+    }
+}
+
+impl FromRedisValue for ClusterNodesReply {
+    fn from_redis_value(v: &Value) -> RedisResult<ClusterNodesReply> {
+        let text: String = from_redis_value(v)?;
+        let mut nodes = vec![];
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            match ClusterNodeInfo::parse(line) {
+                Some(node) => nodes.push(node),
+                None => invalid_type_error!(v, "Invalid line in CLUSTER NODES reply"),
+            }
+        }
+        Ok(ClusterNodesReply { nodes: nodes })
+    }
+}
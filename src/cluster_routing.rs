@@ -0,0 +1,326 @@
+use types::{ErrorKind, RedisError, RedisResult, Value};
+
+/// The number of hash slots of a redis cluster.
+pub const SLOT_SIZE: u16 = 16384;
+
+// CRC16 as specified for redis cluster (XMODEM).
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Returns the content of the first `{...}` section of the key if it is
+/// not empty.
+pub fn get_hashtag(key: &[u8]) -> Option<&[u8]> {
+    let open = key.iter().position(|&b| b == b'{')?;
+    let close = key[open + 1..].iter().position(|&b| b == b'}')?;
+    if close == 0 {
+        return None;
+    }
+    Some(&key[open + 1..open + 1 + close])
+}
+
+/// Returns the hash slot of the key: the hash of its hash tag or of the
+/// whole key if it has none.
+pub fn key_slot(key: &[u8]) -> u16 {
+    crc16(get_hashtag(key).unwrap_or(key)) % SLOT_SIZE
+}
+
+/// Where a command has to be sent in a cluster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoutingInfo {
+    /// To every master, for instance `FLUSHALL`.
+    AllMasters,
+    /// To any node, for commands without keys.
+    Random,
+    /// To the node serving the slot.
+    Slot(u16),
+}
+
+// Returns the uppercased name of the command in `args`.
+fn command_name(args: &[Value]) -> Option<Vec<u8>> {
+    match args.first() {
+        Some(&Value::Data(ref name)) => Some(name.to_ascii_uppercase()),
+        _ => None,
+    }
+}
+
+fn get_arg(args: &[Value], idx: usize) -> Option<&[u8]> {
+    match args.get(idx) {
+        Some(&Value::Data(ref data)) => Some(&data[..]),
+        _ => None,
+    }
+}
+
+// Returns the indices of the keys that follow the number of keys at `idx`.
+fn counted_key_indices(args: &[Value], idx: usize) -> Vec<usize> {
+    let numkeys = get_arg(args, idx)
+        .and_then(|n| ::std::str::from_utf8(n).ok())
+        .and_then(|n| n.parse::<usize>().ok());
+    match numkeys {
+        Some(numkeys) => (idx + 1..(idx + 1 + numkeys).min(args.len())).collect(),
+        None => vec![],
+    }
+}
+
+// Returns the indices of the keys of the command.
+fn key_indices(name: &[u8], args: &[Value]) -> Vec<usize> {
+    let len = args.len();
+    match name {
+        b"EVAL" | b"EVALSHA" | b"EVAL_RO" | b"EVALSHA_RO" | b"FCALL" | b"FCALL_RO" | b"BLMPOP"
+        | b"BZMPOP" => counted_key_indices(args, 2),
+        b"LMPOP" | b"ZMPOP" | b"ZDIFF" | b"ZINTER" | b"ZUNION" => counted_key_indices(args, 1),
+        // several keys follow `KEYS` in place of an empty key
+        b"MIGRATE" => match get_arg(args, 3) {
+            Some(key) if key.is_empty() => {
+                let keys = args.iter().position(|arg| match *arg {
+                    Value::Data(ref data) => data.eq_ignore_ascii_case(b"KEYS"),
+                    _ => false,
+                });
+                match keys {
+                    Some(idx) => (idx + 1..len).collect(),
+                    None => vec![],
+                }
+            }
+            _ => vec![3],
+        },
+        // the result is stored in the key that follows `STORE`
+        b"SORT" => {
+            let store = args.iter().skip(2).position(|arg| match *arg {
+                Value::Data(ref data) => data.eq_ignore_ascii_case(b"STORE"),
+                _ => false,
+            });
+            match store {
+                Some(idx) => vec![1, idx + 3],
+                None => vec![1],
+            }
+        }
+        b"XREAD" | b"XREADGROUP" => {
+            let streams = args.iter().position(|arg| match *arg {
+                Value::Data(ref data) => data.eq_ignore_ascii_case(b"STREAMS"),
+                _ => false,
+            });
+            match streams {
+                // the keys are followed by as many ids
+                Some(idx) => (idx + 1..idx + 1 + (len - idx - 1) / 2).collect(),
+                None => vec![],
+            }
+        }
+        b"XGROUP" | b"XINFO" | b"OBJECT" | b"MEMORY" => vec![2],
+        // the other subcommands are answered by any node
+        b"PUBSUB" => match get_arg(args, 1) {
+            Some(sub) if sub.eq_ignore_ascii_case(b"SHARDNUMSUB") => (2..len).collect(),
+            _ => vec![],
+        },
+        b"BITOP" => (2..len).collect(),
+        b"MGET" | b"DEL" | b"EXISTS" | b"UNLINK" | b"TOUCH" | b"WATCH" | b"SINTER" | b"SUNION"
+        | b"SDIFF" | b"SINTERSTORE" | b"SUNIONSTORE" | b"SDIFFSTORE" | b"PFCOUNT" | b"PFMERGE" => {
+            (1..len).collect()
+        }
+        b"RENAME" | b"RENAMENX" | b"SMOVE" | b"RPOPLPUSH" | b"LMOVE" | b"BLMOVE" | b"COPY"
+        | b"ZRANGESTORE" | b"GEOSEARCHSTORE" | b"LCS" => vec![1, 2],
+        b"MSET" | b"MSETNX" => (1..len).step_by(2).collect(),
+        b"BLPOP" | b"BRPOP" | b"BZPOPMIN" | b"BZPOPMAX" | b"BRPOPLPUSH" => {
+            (1..len.saturating_sub(1)).collect()
+        }
+        b"PING" | b"ECHO" | b"INFO" | b"TIME" | b"DBSIZE" | b"RANDOMKEY" | b"LASTSAVE"
+        | b"CLIENT" | b"CONFIG" | b"CLUSTER" | b"COMMAND" | b"FUNCTION" | b"PUBLISH"
+        | b"SLOWLOG" | b"ROLE" | b"READONLY" | b"READWRITE" | b"MULTI" | b"EXEC" | b"DISCARD"
+        | b"UNWATCH" | b"WAIT" | b"AUTH" | b"SELECT" | b"HELLO" | b"QUIT" | b"SAVE" | b"BGSAVE"
+        | b"SCAN" | b"KEYS" | b"REPLICAOF" | b"SLAVEOF" | b"BGREWRITEAOF" | b"LATENCY"
+        | b"MODULE" | b"ACL" | b"DEBUG" | b"SHUTDOWN" | b"SCRIPT" | b"FLUSHDB" | b"FLUSHALL"
+        | b"SWAPDB" => vec![],
+        _ if len > 1 => vec![1],
+        _ => vec![],
+    }
+}
+
+/// Returns the indices of the keys in the arguments of a command.
+pub fn command_key_indices(args: &[Value]) -> Vec<usize> {
+    let name = unwrap_or!(command_name(args), return vec![]);
+    key_indices(&name, args)
+}
+
+// Commands that only read data.  `XREAD` without `GROUP` does not change the
+// stream.
+const READONLY_COMMANDS: &[&[u8]] = &[
+    b"GET",
+    b"MGET",
+    b"STRLEN",
+    b"GETRANGE",
+    b"SUBSTR",
+    b"GETBIT",
+    b"BITCOUNT",
+    b"BITPOS",
+    b"BITFIELD_RO",
+    b"LCS",
+    b"EXISTS",
+    b"TYPE",
+    b"TTL",
+    b"PTTL",
+    b"DUMP",
+    b"HGET",
+    b"HMGET",
+    b"HGETALL",
+    b"HKEYS",
+    b"HVALS",
+    b"HLEN",
+    b"HEXISTS",
+    b"HSTRLEN",
+    b"HSCAN",
+    b"HTTL",
+    b"HPTTL",
+    b"HEXPIRETIME",
+    b"HPEXPIRETIME",
+    b"SORT_RO",
+    b"LRANGE",
+    b"LLEN",
+    b"LINDEX",
+    b"LPOS",
+    b"SMEMBERS",
+    b"SISMEMBER",
+    b"SCARD",
+    b"SRANDMEMBER",
+    b"SINTER",
+    b"SUNION",
+    b"SDIFF",
+    b"SSCAN",
+    b"ZRANGE",
+    b"ZREVRANGE",
+    b"ZRANGEBYSCORE",
+    b"ZREVRANGEBYSCORE",
+    b"ZRANGEBYLEX",
+    b"ZREVRANGEBYLEX",
+    b"ZSCORE",
+    b"ZCARD",
+    b"ZCOUNT",
+    b"ZLEXCOUNT",
+    b"ZDIFF",
+    b"ZINTER",
+    b"ZUNION",
+    b"ZRANK",
+    b"ZREVRANK",
+    b"ZSCAN",
+    b"XRANGE",
+    b"XREVRANGE",
+    b"XLEN",
+    b"GEOPOS",
+    b"GEODIST",
+    b"GEOHASH",
+    b"GEOSEARCH",
+    b"GEORADIUS_RO",
+    b"GEORADIUSBYMEMBER_RO",
+    b"EVAL_RO",
+    b"EVALSHA_RO",
+    b"FCALL_RO",
+    b"XREAD",
+];
+
+/// Returns whether the command only reads data, so that it can be sent to
+/// a replica.
+pub fn is_readonly_command(args: &[Value]) -> bool {
+    let name = unwrap_or!(command_name(args), return false);
+    READONLY_COMMANDS.contains(&&name[..])
+}
+
+fn cross_slot_error(name: &[u8]) -> RedisError {
+    RedisError::from((
+        ErrorKind::CrossSlot,
+        "Keys of the command hash to different slots",
+        String::from_utf8_lossy(name).into_owned(),
+    ))
+}
+
+impl RoutingInfo {
+    /// Returns the routing of the command with the given arguments.  Fails
+    /// if the keys of the command are not all in the same slot.
+    pub fn for_args(args: &[Value]) -> RedisResult<RoutingInfo> {
+        let name = unwrap_or!(command_name(args), return Ok(RoutingInfo::Random));
+        match &name[..] {
+            b"FLUSHALL" | b"FLUSHDB" | b"SCRIPT" => return Ok(RoutingInfo::AllMasters),
+            _ => {}
+        }
+        let mut routing = RoutingInfo::Random;
+        for idx in key_indices(&name, args) {
+            let slot = key_slot(unwrap_or!(get_arg(args, idx), continue));
+            match routing {
+                RoutingInfo::Slot(other) if other != slot => return Err(cross_slot_error(&name)),
+                _ => routing = RoutingInfo::Slot(slot),
+            }
+        }
+        Ok(routing)
+    }
+
+    /// Returns the common routing of several commands, for instance of a
+    /// transaction.  Keyless commands can be sent to any node.
+    pub fn for_commands<'a, I>(commands: I) -> RedisResult<RoutingInfo>
+    where
+        I: IntoIterator<Item = &'a [Value]>,
+    {
+        let mut routing = RoutingInfo::Random;
+        for args in commands {
+            match (routing, RoutingInfo::for_args(args)?) {
+                (_, RoutingInfo::Random) => {}
+                (RoutingInfo::Slot(a), RoutingInfo::Slot(b)) if a != b => {
+                    return Err(cross_slot_error(b"MULTI"));
+                }
+                (RoutingInfo::AllMasters, _) | (_, RoutingInfo::AllMasters) => {
+                    return Err(cross_slot_error(b"MULTI"));
+                }
+                (_, other) => routing = other,
+            }
+        }
+        Ok(routing)
+    }
+}
+
+// Splits `line\r\nrest` and parses the line after the `prefix` byte as number.
+fn read_number(buf: &[u8], prefix: u8) -> Option<(usize, &[u8])> {
+    let end = buf.windows(2).position(|w| w == b"\r\n")?;
+    if end == 0 || buf[0] != prefix {
+        return None;
+    }
+    let number = ::std::str::from_utf8(&buf[1..end]).ok()?.parse().ok()?;
+    Some((number, &buf[end + 2..]))
+}
+
+// Parses one packed command, returns its arguments, its bytes and the
+// remaining bytes.
+fn parse_packed_command(buf: &[u8]) -> Option<(Vec<Value>, &[u8], &[u8])> {
+    let (count, mut rest) = read_number(buf, b'*')?;
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        let (len, data) = read_number(rest, b'$')?;
+        if data.len() < len + 2 {
+            return None;
+        }
+        args.push(Value::Data(data[..len].to_vec()));
+        rest = &data[len + 2..];
+    }
+    let consumed = buf.len() - rest.len();
+    Some((args, &buf[..consumed], rest))
+}
+
+/// Splits packed commands into the arguments and the bytes of each command.
+pub fn parse_packed_commands(mut buf: &[u8]) -> RedisResult<Vec<(Vec<Value>, &[u8])>> {
+    let mut commands = vec![];
+    while !buf.is_empty() {
+        let (args, packed, rest) = unwrap_or!(
+            parse_packed_command(buf),
+            fail!((ErrorKind::TypeError, "Invalid packed command"))
+        );
+        commands.push((args, packed));
+        buf = rest;
+    }
+    Ok(commands)
+}
@@ -1,10 +1,16 @@
-use connection::ConnectionLike;
+use std::cmp;
+use std::time::Duration;
+
+use connection::{Connection, ConnectionLike, ReplyIter};
+use script::{self, Script, ScriptInvocation};
 use types::{
-    from_redis_value, ErrorKind, FromRedisValue, RedisFuture, RedisResult, RedisWrite, ToRedisArgs,
-    Value,
+    from_redis_value, ErrorKind, FromRedisValue, RedisResult, RedisWrite, ToRedisArgs, Value,
 };
+#[cfg(feature = "aio")]
+use types::{RedisError, RedisFuture};
 
-use futures::Future;
+#[cfg(feature = "aio")]
+use futures::{Async, Future, Poll, Stream};
 
 #[derive(Clone)]
 enum Arg<D> {
@@ -27,6 +33,7 @@ pub struct Cmd {
 pub struct Pipeline {
     commands: Vec<Cmd>,
     transaction_mode: bool,
+    scripts: Vec<Script>,
 }
 
 /// Represents a redis iterator.
@@ -72,6 +79,98 @@ impl<'a, T: FromRedisValue> Iterator for Iter<'a, T> {
     }
 }
 
+#[cfg(feature = "aio")]
+enum AsyncIterState<C> {
+    Idle(C),
+    Fetching(RedisFuture<(C, Value)>),
+    Done,
+}
+
+/// Represents a redis iterator on an async connection, see
+/// `Cmd::iter_async`.
+///
+/// The stream owns the connection while it runs and sends the next
+/// request only when its items are used up.  Dropping the stream drops
+/// the connection with it, so a cancelled scan never leaves a reply
+/// unread on a connection that is used again.  `into_connection` takes
+/// the connection back between requests.
+#[cfg(feature = "aio")]
+pub struct AsyncIter<C, T> {
+    batch: Vec<T>,
+    cursor: Option<u64>,
+    cmd: Cmd,
+    state: AsyncIterState<C>,
+}
+
+#[cfg(feature = "aio")]
+impl<C, T> AsyncIter<C, T> {
+    /// Returns the connection, unless a request is in flight or one
+    /// failed.  Items not yet taken from the stream are discarded.
+    pub fn into_connection(self) -> Option<C> {
+        match self.state {
+            AsyncIterState::Idle(con) => Some(con),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "aio")]
+impl<C, T> Stream for AsyncIter<C, T>
+where
+    C: ::aio::ConnectionLike + Send + 'static,
+    T: FromRedisValue,
+{
+    type Item = T;
+    type Error = RedisError;
+
+    fn poll(&mut self) -> Poll<Option<T>, RedisError> {
+        loop {
+            if let Some(v) = self.batch.pop() {
+                return Ok(Async::Ready(Some(v)));
+            }
+            match ::std::mem::replace(&mut self.state, AsyncIterState::Done) {
+                AsyncIterState::Idle(con) => {
+                    let pcmd = match self.cursor {
+                        // the cursor is `0` again when the iteration is over
+                        Some(0) => {
+                            self.state = AsyncIterState::Idle(con);
+                            return Ok(Async::Ready(None));
+                        }
+                        Some(cursor) => unwrap_or!(
+                            self.cmd.get_packed_command_with_cursor(cursor),
+                            return Ok(Async::Ready(None))
+                        ),
+                        None => self.cmd.get_packed_command(),
+                    };
+                    self.state = AsyncIterState::Fetching(con.req_packed_command(pcmd));
+                }
+                AsyncIterState::Fetching(mut future) => {
+                    let (con, rv) = match future.poll()? {
+                        Async::Ready(reply) => reply,
+                        Async::NotReady => {
+                            self.state = AsyncIterState::Fetching(future);
+                            return Ok(Async::NotReady);
+                        }
+                    };
+                    let mut batch: Vec<T>;
+                    if rv.looks_like_cursor() {
+                        let (next, b): (u64, Vec<T>) = from_redis_value(&rv)?;
+                        batch = b;
+                        self.cursor = Some(next);
+                    } else {
+                        batch = from_redis_value(&rv)?;
+                        self.cursor = Some(0);
+                    }
+                    batch.reverse();
+                    self.batch = batch;
+                    self.state = AsyncIterState::Idle(con);
+                }
+                AsyncIterState::Done => return Ok(Async::Ready(None)),
+            }
+        }
+    }
+}
+
 fn countdigits(mut v: usize) -> usize {
     let mut result = 1;
     loop {
@@ -189,6 +288,71 @@ fn encode_pipeline(cmds: &[Cmd], atomic: bool) -> Vec<u8> {
     rv
 }
 
+/// Packs commands into a single buffer.
+pub fn pack_commands(cmds: &[&Cmd]) -> Vec<u8> {
+    let mut rv = Vec::with_capacity(cmds.iter().map(|cmd| cmd_len(cmd)).sum());
+    for cmd in cmds {
+        cmd.write_packed_command_preallocated(&mut rv);
+    }
+    rv
+}
+
+// Arguments of this size or larger are written from the command itself
+// instead of being copied by `pack_commands_vectored`.
+#[cfg(feature = "with-vectored-writes")]
+const VECTORED_ARG_THRESHOLD: usize = 1024;
+
+/// A part of the commands packed by `pack_commands_vectored`.
+#[cfg(feature = "with-vectored-writes")]
+pub enum PackedSegment<'a> {
+    /// A range of the buffer.
+    Buffered(usize, usize),
+    /// An argument of a command.
+    Borrowed(&'a [u8]),
+}
+
+/// Packs commands for a vectored write.  The framing and small arguments
+/// are written into `buf` while large arguments are referenced, so that
+/// big pipelines are not copied into one giant buffer.
+#[cfg(feature = "with-vectored-writes")]
+pub fn pack_commands_vectored<'a>(cmds: &[&'a Cmd], buf: &mut Vec<u8>) -> Vec<PackedSegment<'a>> {
+    fn write_header(buf: &mut Vec<u8>, prefix: u8, len: usize) {
+        buf.push(prefix);
+        ::itoa::write(&mut *buf, len).unwrap();
+        buf.extend_from_slice(b"\r\n");
+    }
+
+    let mut segments = vec![];
+    let mut start = buf.len();
+    for cmd in cmds {
+        let args = cmd.args_iter();
+        write_header(buf, b'*', args.len());
+        for item in args {
+            match item {
+                Arg::Cursor => {
+                    let mut cursor_bytes = [0; 20];
+                    let n = ::itoa::write(&mut cursor_bytes[..], cmd.cursor.unwrap_or(0)).unwrap();
+                    write_header(buf, b'$', n);
+                    buf.extend_from_slice(&cursor_bytes[..n]);
+                }
+                Arg::Simple(val) => {
+                    write_header(buf, b'$', val.len());
+                    if val.len() >= VECTORED_ARG_THRESHOLD {
+                        segments.push(PackedSegment::Buffered(start, buf.len()));
+                        segments.push(PackedSegment::Borrowed(val));
+                        start = buf.len();
+                    } else {
+                        buf.extend_from_slice(val);
+                    }
+                }
+            }
+            buf.extend_from_slice(b"\r\n");
+        }
+    }
+    segments.push(PackedSegment::Buffered(start, buf.len()));
+    segments
+}
+
 impl RedisWrite for Cmd {
     fn write_arg(&mut self, arg: &[u8]) {
         let prev = self.data.len();
@@ -227,9 +391,15 @@ impl RedisWrite for Cmd {
 impl Cmd {
     /// Creates a new empty command.
     pub fn new() -> Cmd {
+        Cmd::with_capacity(0, 0)
+    }
+
+    /// Creates a new empty command with room for `arg_count` arguments
+    /// with a total size of `size_of_data` bytes.
+    pub fn with_capacity(arg_count: usize, size_of_data: usize) -> Cmd {
         Cmd {
-            data: vec![],
-            args: vec![],
+            data: Vec::with_capacity(size_of_data),
+            args: Vec::with_capacity(arg_count),
             cursor: None,
             is_ignored: false,
         }
@@ -285,7 +455,10 @@ impl Cmd {
         cmd
     }
 
-    fn write_packed_command(&self, cmd: &mut Vec<u8>) {
+    /// Appends the packed command to `cmd`.  This allows to reuse a buffer
+    /// instead of allocating a new one for every command.
+    #[inline]
+    pub fn write_packed_command(&self, cmd: &mut Vec<u8>) {
         write_command(cmd, self.args_iter(), self.cursor.unwrap_or(0))
     }
 
@@ -316,13 +489,26 @@ impl Cmd {
     /// you can retrieve data.
     #[inline]
     pub fn query<T: FromRedisValue>(&self, con: &mut ConnectionLike) -> RedisResult<T> {
-        let pcmd = self.get_packed_command();
-        match con.req_packed_command(&pcmd) {
+        match con.req_command(self) {
             Ok(val) => from_redis_value(&val),
             Err(e) => Err(e),
         }
     }
 
+    /// Like `query()` but fails with an error for which `is_timeout` is
+    /// true if sending the command and reading the reply took longer than
+    /// `timeout`.  The connection is closed when that happens; see
+    /// `Connection::with_timeout` for details.
+    #[inline]
+    pub fn query_timeout<T: FromRedisValue>(
+        &self,
+        con: &mut Connection,
+        timeout: Duration,
+    ) -> RedisResult<T> {
+        con.with_timeout(timeout, |con| self.query(con))
+    }
+
+    #[cfg(feature = "aio")]
     #[inline]
     pub fn query_async<C, T: FromRedisValue>(&self, con: C) -> RedisFuture<(C, T)>
     where
@@ -377,6 +563,46 @@ impl Cmd {
         })
     }
 
+    /// Like `iter` but for async connections: returns a stream of the
+    /// items that sends the first request when it is polled.  Unlike
+    /// `iter` errors are passed on, after an error the stream ends.
+    #[cfg(feature = "aio")]
+    #[inline]
+    pub fn iter_async<C, T: FromRedisValue>(self, con: C) -> AsyncIter<C, T>
+    where
+        C: ::aio::ConnectionLike + Send + 'static,
+    {
+        AsyncIter {
+            batch: vec![],
+            cursor: None,
+            cmd: self,
+            state: AsyncIterState::Idle(con),
+        }
+    }
+
+    /// Sends the command and returns an iterator over the elements of its
+    /// array reply, which are parsed as they are consumed.  Unlike `query`
+    /// into a `Vec` this never holds the whole reply in memory, which
+    /// matters for commands like `XRANGE` or `LRANGE` over huge keys.
+    ///
+    /// ```rust,no_run
+    /// # fn do_something() -> redis::RedisResult<()> {
+    /// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    /// # let mut con = client.get_connection().unwrap();
+    /// let mut total = 0;
+    /// for item in redis::cmd("LRANGE").arg("my_list").arg(0).arg(-1).query_iter::<i64>(&mut con)? {
+    ///     total += item?;
+    /// }
+    /// # Ok(()) }
+    /// ```
+    #[inline]
+    pub fn query_iter<'a, T: FromRedisValue>(
+        &self,
+        con: &'a mut Connection,
+    ) -> RedisResult<ReplyIter<'a, T>> {
+        con.req_command_iter(self)
+    }
+
     /// This is a shortcut to `query()` that does not return a value and
     /// will fail the task if the query fails because of an error.  This is
     /// mainly useful in examples and for simple commands like setting
@@ -394,6 +620,18 @@ impl Cmd {
         let _: () = self.query(con).unwrap();
     }
 
+    /// Sends the command without waiting for its reply, which the server
+    /// does not send because of `CLIENT REPLY SKIP` (redis 3.2).  Errors
+    /// of the command are not reported either.
+    ///
+    /// This saves the round trip for writes whose outcome does not
+    /// matter, like metrics.
+    pub fn send_without_reply(&self, con: &mut Connection) -> RedisResult<()> {
+        let mut packed = cmd("CLIENT").arg("REPLY").arg("SKIP").get_packed_command();
+        self.write_packed_command(&mut packed);
+        con.req_packed_commands(&packed, 0, 0).map(|_| ())
+    }
+
     fn args_iter(&self) -> impl Iterator<Item = Arg<&[u8]>> + Clone + ExactSizeIterator {
         let mut prev = 0;
         self.args.iter().map(move |arg| match *arg {
@@ -441,6 +679,7 @@ impl Pipeline {
         Pipeline {
             commands: Vec::with_capacity(capacity),
             transaction_mode: false,
+            scripts: vec![],
         }
     }
 
@@ -459,6 +698,36 @@ impl Pipeline {
         self
     }
 
+    /// Adds the invocation of a script to the pipeline.  The script is
+    /// called with `EVALSHA`, and before the pipeline is sent the scripts
+    /// the server does not know yet are loaded with `SCRIPT LOAD`.  This
+    /// makes scripts usable in atomic pipelines, which cannot retry a
+    /// single command.
+    ///
+    /// ```rust,no_run
+    /// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    /// # let mut con = client.get_connection().unwrap();
+    /// let script = redis::Script::new("return redis.call('INCRBY', KEYS[1], ARGV[1])");
+    /// let (value,): (i32,) = redis::pipe()
+    ///     .atomic()
+    ///     .cmd("SET").arg("key").arg(1).ignore()
+    ///     .invoke_script(script.key("key").arg(2))
+    ///     .query(&mut con).unwrap();
+    /// ```
+    ///
+    /// On a cluster the scripts are loaded on all masters.
+    pub fn invoke_script(&mut self, invocation: &ScriptInvocation) -> &mut Pipeline {
+        let (cmd, script) = script::pipeline_command(invocation);
+        if !self
+            .scripts
+            .iter()
+            .any(|known| known.get_hash() == script.get_hash())
+        {
+            self.scripts.push(script);
+        }
+        self.add_command(cmd)
+    }
+
     #[inline]
     fn get_last_command(&mut self) -> &mut Cmd {
         let idx = match self.commands.len() {
@@ -531,19 +800,18 @@ impl Pipeline {
     }
 
     fn execute_pipelined(&self, con: &mut ConnectionLike) -> RedisResult<Value> {
-        Ok(self.make_pipeline_results(con.req_packed_commands(
-            &encode_pipeline(&self.commands, false),
-            0,
-            self.commands.len(),
-        )?))
+        let cmds: Vec<&Cmd> = self.commands.iter().collect();
+        Ok(self.make_pipeline_results(con.req_commands(&cmds, 0, self.commands.len())?))
     }
 
     fn execute_transaction(&self, con: &mut ConnectionLike) -> RedisResult<Value> {
-        let mut resp = con.req_packed_commands(
-            &encode_pipeline(&self.commands, true),
-            self.commands.len() + 1,
-            1,
-        )?;
+        let multi = cmd("MULTI");
+        let exec = cmd("EXEC");
+        let mut cmds = Vec::with_capacity(self.commands.len() + 2);
+        cmds.push(&multi);
+        cmds.extend(self.commands.iter());
+        cmds.push(&exec);
+        let mut resp = con.req_commands(&cmds, self.commands.len() + 1, 1)?;
         match resp.pop() {
             Some(Value::Nil) => Ok(Value::Nil),
             Some(Value::Bulk(items)) => Ok(self.make_pipeline_results(items)),
@@ -568,11 +836,17 @@ impl Pipeline {
     ///     .cmd("GET").arg("key_2").query(&mut con).unwrap();
     /// ```
     ///
+    /// Use `Option` for values that may be nil and for the whole result of
+    /// an atomic pipeline whose transaction was aborted.  If a value cannot
+    /// be converted, the type error names its position, like `element 1 of
+    /// 2`, which is the index among the commands that are not ignored.
+    ///
     /// NOTE: A Pipeline object may be reused after `query()` with all the commands as were inserted
     ///       to them. In order to clear a Pipeline object with minimal memory released/allocated,
     ///       it is necessary to call the `clear()` before inserting new commands.
     #[inline]
     pub fn query<T: FromRedisValue>(&self, con: &mut ConnectionLike) -> RedisResult<T> {
+        script::load_scripts(&self.scripts, con)?;
         from_redis_value(
             &(if self.commands.len() == 0 {
                 Value::Bulk(vec![])
@@ -584,6 +858,115 @@ impl Pipeline {
         )
     }
 
+    /// Executes a large pipeline in chunks of `chunk_size` commands.  Each
+    /// chunk is sent and its replies are read before the next one is
+    /// packed, so the buffers stay bounded no matter how many commands the
+    /// pipeline holds.  The values of the commands that are not ignored are
+    /// collected and converted like with `query()`.
+    ///
+    /// ```rust,no_run
+    /// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    /// # let mut con = client.get_connection().unwrap();
+    /// let mut pipe = redis::pipe();
+    /// for i in 0..1_000_000 {
+    ///     pipe.cmd("SET").arg(format!("key:{}", i)).arg(i).ignore();
+    /// }
+    /// pipe.cmd("DBSIZE");
+    /// let (size,): (usize,) = pipe.query_chunked(&mut con, 10_000).unwrap();
+    /// ```
+    ///
+    /// If a chunk fails, the chunks before it have been executed already.
+    /// Atomic pipelines cannot be split and fail with
+    /// `ErrorKind::InvalidClientConfig`.
+    pub fn query_chunked<T: FromRedisValue>(
+        &self,
+        con: &mut ConnectionLike,
+        chunk_size: usize,
+    ) -> RedisResult<T> {
+        if self.transaction_mode {
+            fail!((
+                ErrorKind::InvalidClientConfig,
+                "Atomic pipelines cannot be executed in chunks"
+            ));
+        }
+        script::load_scripts(&self.scripts, con)?;
+        let mut rv = vec![];
+        for chunk in self.commands.chunks(cmp::max(chunk_size, 1)) {
+            let cmds: Vec<&Cmd> = chunk.iter().collect();
+            let values = con.req_commands(&cmds, 0, cmds.len())?;
+            for (cmd, value) in chunk.iter().zip(values) {
+                if !cmd.is_ignored {
+                    rv.push(value);
+                }
+            }
+        }
+        from_redis_value(&Value::Bulk(rv))
+    }
+
+    /// Like `query_chunked()` for pipelines whose values are not needed.
+    /// Errors are still reported.
+    pub fn execute_chunked(&self, con: &mut ConnectionLike, chunk_size: usize) -> RedisResult<()> {
+        let _: Value = self.query_chunked(con, chunk_size)?;
+        Ok(())
+    }
+
+    /// Executes the pipeline and returns the result of each command, so
+    /// that a failing command does not fail the whole pipeline.  Batch jobs
+    /// can tell which commands failed and send only those again.
+    ///
+    /// There is one result for every command, including the ignored ones,
+    /// so the index of a result is the index of its command.  Error replies
+    /// of the server and values that cannot be converted to `T` are
+    /// returned in place, other errors like I/O errors fail the whole call.
+    ///
+    /// ```rust,no_run
+    /// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    /// # let mut con = client.get_connection().unwrap();
+    /// let results: Vec<redis::RedisResult<redis::Value>> = redis::pipe()
+    ///     .cmd("SET").arg("key").arg("value")
+    ///     .cmd("INCR").arg("key")
+    ///     .query_each(&mut con).unwrap();
+    /// assert!(results[0].is_ok());
+    /// assert!(results[1].is_err());
+    /// ```
+    ///
+    /// Atomic pipelines are not supported, since a transaction succeeds or
+    /// fails as a whole.
+    pub fn query_each<T: FromRedisValue>(
+        &self,
+        con: &mut ConnectionLike,
+    ) -> RedisResult<Vec<RedisResult<T>>> {
+        if self.transaction_mode {
+            fail!((
+                ErrorKind::InvalidClientConfig,
+                "Per command results are not available for atomic pipelines"
+            ));
+        }
+        if self.commands.is_empty() {
+            return Ok(vec![]);
+        }
+        script::load_scripts(&self.scripts, con)?;
+        let cmds: Vec<&Cmd> = self.commands.iter().collect();
+        Ok(con
+            .req_commands_each(&cmds)?
+            .into_iter()
+            .map(|result| result.and_then(|value| from_redis_value(&value)))
+            .collect())
+    }
+
+    /// Like `query()` but fails with an error for which `is_timeout` is
+    /// true if the whole pipeline took longer than `timeout`.  The
+    /// connection is closed when that happens; see
+    /// `Connection::with_timeout` for details.
+    #[inline]
+    pub fn query_timeout<T: FromRedisValue>(
+        &self,
+        con: &mut Connection,
+        timeout: Duration,
+    ) -> RedisResult<T> {
+        con.with_timeout(timeout, |con| self.query(con))
+    }
+
     /// Clear a Pipeline object internal data structure.
     ///
     /// This allows reusing a Pipeline object as a clear object while performing a minimal amount of
@@ -591,8 +974,10 @@ impl Pipeline {
     #[inline]
     pub fn clear(&mut self) {
         self.commands.clear();
+        self.scripts.clear();
     }
 
+    #[cfg(feature = "aio")]
     fn execute_pipelined_async<C>(self, con: C) -> RedisFuture<(C, Value)>
     where
         C: ::aio::ConnectionLike + Send + 'static,
@@ -607,6 +992,7 @@ impl Pipeline {
         )
     }
 
+    #[cfg(feature = "aio")]
     fn execute_transaction_async<C>(self, con: C) -> RedisFuture<(C, Value)>
     where
         C: ::aio::ConnectionLike + Send + 'static,
@@ -628,14 +1014,22 @@ impl Pipeline {
         )
     }
 
+    #[cfg(feature = "aio")]
     #[inline]
-    pub fn query_async<C, T: FromRedisValue>(self, con: C) -> RedisFuture<(C, T)>
+    pub fn query_async<C, T: FromRedisValue>(mut self, con: C) -> RedisFuture<(C, T)>
     where
         C: ::aio::ConnectionLike + Send + 'static,
         T: Send + 'static,
     {
         use futures::future;
+        use std::mem;
 
+        if !self.scripts.is_empty() {
+            let scripts = mem::replace(&mut self.scripts, vec![]);
+            return Box::new(
+                script::load_scripts_async(scripts, con).and_then(move |con| self.query_async(con)),
+            );
+        }
         let future = if self.commands.len() == 0 {
             return Box::new(future::result(
                 from_redis_value(&Value::Bulk(vec![])).map(|v| (con, v)),
@@ -666,6 +1060,28 @@ impl Pipeline {
     pub fn execute(&self, con: &mut ConnectionLike) {
         let _: () = self.query(con).unwrap();
     }
+
+    /// Sends the commands without their replies, which the server does
+    /// not send between `CLIENT REPLY OFF` and `CLIENT REPLY ON`
+    /// (redis 3.2).  Errors of the commands are not reported either.
+    ///
+    /// Only the reply to `CLIENT REPLY ON` is read, so the connection
+    /// sends replies again afterwards.
+    pub fn send_without_replies(&self, con: &mut Connection) -> RedisResult<()> {
+        let mut packed = cmd("CLIENT").arg("REPLY").arg("OFF").get_packed_command();
+        packed.extend(self.get_packed_pipeline(self.transaction_mode));
+        cmd("CLIENT")
+            .arg("REPLY")
+            .arg("ON")
+            .write_packed_command(&mut packed);
+        match con.req_packed_command(&packed)? {
+            Value::Okay => Ok(()),
+            _ => fail!((
+                ErrorKind::ResponseError,
+                "Redis server did not resume replies"
+            )),
+        }
+    }
 }
 
 /// Shortcut function to creating a command with a single argument.
@@ -678,7 +1094,8 @@ impl Pipeline {
 /// redis::cmd("PING");
 /// ```
 pub fn cmd<'a>(name: &'a str) -> Cmd {
-    let mut rv = Cmd::new();
+    // enough for most commands to never grow
+    let mut rv = Cmd::with_capacity(4, 64);
     rv.arg(name);
     rv
 }
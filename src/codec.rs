@@ -0,0 +1,291 @@
+//! Typed values serialized with serde.
+//!
+//! The wrappers `Json`, `MsgPack` and `Bincode` implement `ToRedisArgs` and
+//! `FromRedisValue` for any type that implements `Serialize` and
+//! `Deserialize` by encoding it in the respective format, so they can be
+//! used with all commands that take or return values:
+//!
+//! ```rust,no_run
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate redis;
+//! use redis::codec::Json;
+//! use redis::Commands;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Job {
+//!     id: u64,
+//!     command: String,
+//! }
+//!
+//! # fn do_something() -> redis::RedisResult<()> {
+//! # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+//! # let mut con = client.get_connection().unwrap();
+//! let job = Job { id: 1, command: "resize".to_string() };
+//! let _: () = con.rpush("jobs", Json(&job))?;
+//! let Json(job): Json<Job> = con.lpop("jobs")?;
+//! let jobs: Vec<Json<Job>> = con.lrange("jobs", 0, -1)?;
+//! # Ok(()) }
+//! # fn main() {}
+//! ```
+//!
+//! `Json` needs the `json` feature, `MsgPack` the `with-msgpack` feature
+//! and `Bincode` the `with-bincode` feature.  Other formats are added by
+//! implementing `Codec` and using `Encoded<C, T>`.
+//!
+//! Serializing a value that the format cannot represent, like a map with
+//! keys that are not strings in JSON, panics when the value is written.
+//! Values that may fail to serialize are encoded up front with `encode`,
+//! which returns the error instead, and the bytes are passed to the
+//! command:
+//!
+//! ```rust,no_run
+//! # extern crate redis;
+//! # use std::collections::HashMap;
+//! use redis::codec::Json;
+//! use redis::Commands;
+//!
+//! # fn do_something() -> redis::RedisResult<()> {
+//! # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+//! # let mut con = client.get_connection().unwrap();
+//! let mut scores = HashMap::new();
+//! scores.insert((1, 2), 3);
+//! let bytes = Json(&scores).encode()?;
+//! let _: () = con.set("scores", bytes)?;
+//! # Ok(()) }
+//! # fn main() {}
+//! ```
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use types::{ErrorKind, FromRedisValue, RedisError, RedisResult, RedisWrite, ToRedisArgs, Value};
+
+/// A format that values are serialized into.
+pub trait Codec {
+    /// The name of the format that is used in error messages.
+    const NAME: &'static str;
+
+    /// Serializes the value.
+    fn encode<T: Serialize + ?Sized>(value: &T) -> RedisResult<Vec<u8>>;
+
+    /// Deserializes a value.
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> RedisResult<T>;
+}
+
+fn codec_error<C: Codec, E: fmt::Display>(desc: &'static str, err: E) -> RedisError {
+    RedisError::from((ErrorKind::TypeError, desc, format!("{}: {}", C::NAME, err)))
+}
+
+/// Serializes values as JSON.
+#[cfg(feature = "json")]
+#[derive(Clone, Copy, Debug)]
+pub enum JsonCodec {}
+
+#[cfg(feature = "json")]
+impl Codec for JsonCodec {
+    const NAME: &'static str = "JSON";
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> RedisResult<Vec<u8>> {
+        ::serde_json::to_vec(value).map_err(|e| codec_error::<Self, _>("Failed to encode", e))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> RedisResult<T> {
+        ::serde_json::from_slice(bytes).map_err(|e| codec_error::<Self, _>("Failed to decode", e))
+    }
+}
+
+/// Serializes values as MessagePack.  Structs are written as maps, so
+/// fields can be added and reordered.
+#[cfg(feature = "with-msgpack")]
+#[derive(Clone, Copy, Debug)]
+pub enum MsgPackCodec {}
+
+#[cfg(feature = "with-msgpack")]
+impl Codec for MsgPackCodec {
+    const NAME: &'static str = "MessagePack";
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> RedisResult<Vec<u8>> {
+        let mut bytes = Vec::new();
+        ::rmp_serde::encode::write_named(&mut bytes, value)
+            .map_err(|e| codec_error::<Self, _>("Failed to encode", e))?;
+        Ok(bytes)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> RedisResult<T> {
+        ::rmp_serde::from_read_ref(bytes).map_err(|e| codec_error::<Self, _>("Failed to decode", e))
+    }
+}
+
+/// Serializes values with bincode.  The encoding is compact but does not
+/// describe itself, so changing a type makes stored values unreadable.
+#[cfg(feature = "with-bincode")]
+#[derive(Clone, Copy, Debug)]
+pub enum BincodeCodec {}
+
+#[cfg(feature = "with-bincode")]
+impl Codec for BincodeCodec {
+    const NAME: &'static str = "bincode";
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> RedisResult<Vec<u8>> {
+        ::bincode::serialize(value).map_err(|e| codec_error::<Self, _>("Failed to encode", e))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> RedisResult<T> {
+        ::bincode::deserialize(bytes).map_err(|e| codec_error::<Self, _>("Failed to decode", e))
+    }
+}
+
+/// A value that is written and read in the format of the codec `C`.
+pub struct Encoded<C, T> {
+    /// The wrapped value.
+    pub value: T,
+    codec: PhantomData<C>,
+}
+
+impl<C: Codec, T> Encoded<C, T> {
+    /// Wraps a value.
+    pub fn new(value: T) -> Encoded<C, T> {
+        Encoded {
+            value,
+            codec: PhantomData,
+        }
+    }
+
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<C: Codec, T: Serialize> Encoded<C, T> {
+    /// Serializes the wrapped value.  Unlike writing the value as an
+    /// argument, which panics, this returns an error if the format cannot
+    /// represent the value.
+    pub fn encode(&self) -> RedisResult<Vec<u8>> {
+        C::encode(&self.value)
+    }
+}
+
+impl<C, T: Clone> Clone for Encoded<C, T> {
+    fn clone(&self) -> Encoded<C, T> {
+        Encoded {
+            value: self.value.clone(),
+            codec: PhantomData,
+        }
+    }
+}
+
+impl<C, T: fmt::Debug> fmt::Debug for Encoded<C, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Encoded").field(&self.value).finish()
+    }
+}
+
+impl<C, T: PartialEq> PartialEq for Encoded<C, T> {
+    fn eq(&self, other: &Encoded<C, T>) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<C, T> Deref for Encoded<C, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<C, T> DerefMut for Encoded<C, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+fn write_encoded<C, T, W>(value: &T, out: &mut W)
+where
+    C: Codec,
+    T: Serialize + ?Sized,
+    W: ?Sized + RedisWrite,
+{
+    match C::encode(value) {
+        Ok(bytes) => out.write_arg(&bytes),
+        Err(err) => panic!("{}", err),
+    }
+}
+
+fn read_encoded<C: Codec, T: DeserializeOwned>(v: &Value) -> RedisResult<T> {
+    match *v {
+        Value::Data(ref bytes) => C::decode(bytes),
+        Value::Status(ref s) => C::decode(s.as_bytes()),
+        _ => invalid_type_error!(v, "Response type not decodable"),
+    }
+}
+
+impl<C: Codec, T: Serialize> ToRedisArgs for Encoded<C, T> {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        write_encoded::<C, _, _>(&self.value, out)
+    }
+}
+
+impl<C: Codec, T: DeserializeOwned> FromRedisValue for Encoded<C, T> {
+    fn from_redis_value(v: &Value) -> RedisResult<Encoded<C, T>> {
+        read_encoded::<C, _>(v).map(Encoded::new)
+    }
+}
+
+macro_rules! codec_wrapper {
+    ($(#[$attr:meta])* $name:ident, $codec:ty) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name<T>(pub T);
+
+        impl<T: Serialize> $name<T> {
+            /// Serializes the wrapped value.  Unlike writing the value as
+            /// an argument, which panics, this returns an error if the
+            /// format cannot represent the value.
+            pub fn encode(&self) -> RedisResult<Vec<u8>> {
+                <$codec as Codec>::encode(&self.0)
+            }
+        }
+
+        impl<T: Serialize> ToRedisArgs for $name<T> {
+            fn write_redis_args<W>(&self, out: &mut W)
+            where
+                W: ?Sized + RedisWrite,
+            {
+                write_encoded::<$codec, _, _>(&self.0, out)
+            }
+        }
+
+        impl<T: DeserializeOwned> FromRedisValue for $name<T> {
+            fn from_redis_value(v: &Value) -> RedisResult<$name<T>> {
+                read_encoded::<$codec, _>(v).map($name)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "json")]
+codec_wrapper!(
+    /// A value that is written and read as JSON.
+    Json,
+    JsonCodec
+);
+#[cfg(feature = "with-msgpack")]
+codec_wrapper!(
+    /// A value that is written and read as MessagePack.
+    MsgPack,
+    MsgPackCodec
+);
+#[cfg(feature = "with-bincode")]
+codec_wrapper!(
+    /// A value that is written and read with bincode.
+    Bincode,
+    BincodeCodec
+);
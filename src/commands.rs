@@ -1,8 +1,30 @@
 // can't use rustfmt here because it screws up the file.
 #![cfg_attr(rustfmt, rustfmt_skip)]
 use types::{FromRedisValue, ToRedisArgs, RedisResult, NumericBehavior};
+use keys::Ttl;
+use info::Info;
+#[cfg(feature = "aio")]
+use types::RedisFuture;
+#[cfg(feature = "aio")]
+use futures::Future;
+#[cfg(feature = "aio")]
+use cmd::AsyncIter;
 use connection::{ConnectionLike, Msg, Connection};
 use cmd::{cmd, Cmd, Pipeline, Iter};
+use streams::ExclusiveId;
+use caching::TrackingOptions;
+use clients::{ClientKillOptions, ClientType, PauseMode};
+use geo::GeoSearchOptions;
+use functions::RestorePolicy;
+#[cfg(feature = "with-search")]
+use search::{AggregateOptions, CreateOptions, Schema, SearchOptions};
+#[cfg(feature = "with-timeseries")]
+use timeseries::{MRangeOptions, RangeBound, RangeOptions, SeriesOptions, Timestamp};
+use options::{
+    BitFieldOptions, BitRange, CopyOptions, ExpireOption, FlushMode, LcsOptions, ListDirection,
+    LposOptions, MigrateOptions, RestoreOptions, ScanOptions, ScoreEnd, SetOptions,
+    ShutdownOptions, SortOptions, ZAggregateOptions, ZRangeOptions,
+};
 
 
 macro_rules! implement_commands {
@@ -50,7 +72,27 @@ macro_rules! implement_commands {
                     { ($body).query(self) }
             )*
 
-            /// Incrementally iterate the keys space.
+            /// Returns the time to live of a key as `Ttl`, which tells
+            /// missing keys and keys without expiration apart.
+            #[inline]
+            fn ttl_typed<K: ToRedisArgs>(&mut self, key: K) -> RedisResult<Ttl> {
+                cmd("TTL").arg(key).query(self).map(Ttl::from_secs)
+            }
+
+            /// Returns the time to live of a key in milliseconds as `Ttl`.
+            #[inline]
+            fn pttl_typed<K: ToRedisArgs>(&mut self, key: K) -> RedisResult<Ttl> {
+                cmd("PTTL").arg(key).query(self).map(Ttl::from_millis)
+            }
+
+            /// Returns the default sections of `INFO` parsed into `Info`.
+            #[inline]
+            fn info_typed(&mut self) -> RedisResult<Info> {
+                cmd("INFO").query(self)
+            }
+
+            /// Incrementally iterate the keys space.  Iterate as `Vec<u8>`
+            /// for keys that are not valid UTF-8, they are skipped as `String`.
             #[inline]
             fn scan<RV: FromRedisValue>(&mut self) -> RedisResult<Iter<RV>> {
                 let mut c = cmd("SCAN");
@@ -66,6 +108,15 @@ macro_rules! implement_commands {
                 c.iter(self)
             }
 
+            /// Incrementally iterate the keys space with the options, like
+            /// only the keys of a type.
+            #[inline]
+            fn scan_options<RV: FromRedisValue>(&mut self, options: &ScanOptions) -> RedisResult<Iter<RV>> {
+                let mut c = cmd("SCAN");
+                c.cursor_arg(0).arg(options);
+                c.iter(self)
+            }
+
             /// Incrementally iterate hash fields and associated values.
             #[inline]
             fn hscan<K: ToRedisArgs, RV: FromRedisValue>(&mut self, key: K) -> RedisResult<Iter<RV>> {
@@ -84,6 +135,16 @@ macro_rules! implement_commands {
                 c.iter(self)
             }
 
+            /// Incrementally iterate hash fields and associated values with
+            /// the options.
+            #[inline]
+            fn hscan_options<K: ToRedisArgs, RV: FromRedisValue>
+                    (&mut self, key: K, options: &ScanOptions) -> RedisResult<Iter<RV>> {
+                let mut c = cmd("HSCAN");
+                c.arg(key).cursor_arg(0).arg(options);
+                c.iter(self)
+            }
+
             /// Incrementally iterate set elements.
             #[inline]
             fn sscan<K: ToRedisArgs, RV: FromRedisValue>(&mut self, key: K) -> RedisResult<Iter<RV>> {
@@ -101,6 +162,15 @@ macro_rules! implement_commands {
                 c.iter(self)
             }
 
+            /// Incrementally iterate set elements with the options.
+            #[inline]
+            fn sscan_options<K: ToRedisArgs, RV: FromRedisValue>
+                    (&mut self, key: K, options: &ScanOptions) -> RedisResult<Iter<RV>> {
+                let mut c = cmd("SSCAN");
+                c.arg(key).cursor_arg(0).arg(options);
+                c.iter(self)
+            }
+
             /// Incrementally iterate sorted set elements.
             #[inline]
             fn zscan<K: ToRedisArgs, RV: FromRedisValue>(&mut self, key: K) -> RedisResult<Iter<RV>> {
@@ -117,6 +187,179 @@ macro_rules! implement_commands {
                 c.arg(key).cursor_arg(0).arg("MATCH").arg(pattern);
                 c.iter(self)
             }
+
+            /// Incrementally iterate sorted set elements with the options.
+            #[inline]
+            fn zscan_options<K: ToRedisArgs, RV: FromRedisValue>
+                    (&mut self, key: K, options: &ScanOptions) -> RedisResult<Iter<RV>> {
+                let mut c = cmd("ZSCAN");
+                c.arg(key).cursor_arg(0).arg(options);
+                c.iter(self)
+            }
+        }
+
+        /// Implements common redis commands for asynchronous connections.
+        /// This mirrors the `Commands` trait but as the asynchronous
+        /// connections are passed by value, every method consumes the
+        /// connection and returns a future that resolves to the connection
+        /// and the converted result.
+        ///
+        /// ```rust,no_run
+        /// # extern crate futures;
+        /// # extern crate redis;
+        /// use futures::Future;
+        /// use redis::AsyncCommands;
+        ///
+        /// # fn main() {
+        /// let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        /// let future = client.get_async_connection()
+        ///     .and_then(|con| con.set("my_key", 42))
+        ///     .and_then(|(con, ()): (_, ())| con.get("my_key"))
+        ///     .map(|(_con, value): (_, i32)| assert_eq!(value, 42));
+        /// # }
+        /// ```
+        #[cfg(feature = "aio")]
+        pub trait AsyncCommands : ::aio::ConnectionLike + Send + Sized + 'static {
+            $(
+                $(#[$attr])*
+                #[inline]
+                fn $name<$($tyargs: $ty,)* RV: FromRedisValue + Send + 'static>(
+                    self $(, $argname: $argty)*) -> RedisFuture<(Self, RV)>
+                    { ($body).query_async(self) }
+            )*
+
+            /// Returns the time to live of a key as `Ttl`, which tells
+            /// missing keys and keys without expiration apart.
+            #[inline]
+            fn ttl_typed<K: ToRedisArgs>(self, key: K) -> RedisFuture<(Self, Ttl)> {
+                Box::new(cmd("TTL").arg(key).query_async(self)
+                    .map(|(con, ttl)| (con, Ttl::from_secs(ttl))))
+            }
+
+            /// Returns the time to live of a key in milliseconds as `Ttl`.
+            #[inline]
+            fn pttl_typed<K: ToRedisArgs>(self, key: K) -> RedisFuture<(Self, Ttl)> {
+                Box::new(cmd("PTTL").arg(key).query_async(self)
+                    .map(|(con, ttl)| (con, Ttl::from_millis(ttl))))
+            }
+
+            /// Returns the default sections of `INFO` parsed into `Info`.
+            #[inline]
+            fn info_typed(self) -> RedisFuture<(Self, Info)> {
+                cmd("INFO").query_async(self)
+            }
+
+            /// Incrementally iterate the keys space as a stream.  The stream
+            /// owns the connection, see `AsyncIter`.
+            #[inline]
+            fn scan<RV: FromRedisValue>(self) -> AsyncIter<Self, RV> {
+                let mut c = cmd("SCAN");
+                c.cursor_arg(0);
+                c.iter_async(self)
+            }
+
+            /// Incrementally iterate the keys space for keys matching a pattern
+            /// as a stream.
+            #[inline]
+            fn scan_match<P: ToRedisArgs, RV: FromRedisValue>(self, pattern: P) -> AsyncIter<Self, RV> {
+                let mut c = cmd("SCAN");
+                c.cursor_arg(0).arg("MATCH").arg(pattern);
+                c.iter_async(self)
+            }
+
+            /// Incrementally iterate the keys space with the options as a
+            /// stream.
+            #[inline]
+            fn scan_options<RV: FromRedisValue>(self, options: &ScanOptions) -> AsyncIter<Self, RV> {
+                let mut c = cmd("SCAN");
+                c.cursor_arg(0).arg(options);
+                c.iter_async(self)
+            }
+
+            /// Incrementally iterate hash fields and associated values as a
+            /// stream.
+            #[inline]
+            fn hscan<K: ToRedisArgs, RV: FromRedisValue>(self, key: K) -> AsyncIter<Self, RV> {
+                let mut c = cmd("HSCAN");
+                c.arg(key).cursor_arg(0);
+                c.iter_async(self)
+            }
+
+            /// Incrementally iterate hash fields and associated values for
+            /// field names matching a pattern as a stream.
+            #[inline]
+            fn hscan_match<K: ToRedisArgs, P: ToRedisArgs, RV: FromRedisValue>
+                    (self, key: K, pattern: P) -> AsyncIter<Self, RV> {
+                let mut c = cmd("HSCAN");
+                c.arg(key).cursor_arg(0).arg("MATCH").arg(pattern);
+                c.iter_async(self)
+            }
+
+            /// Incrementally iterate hash fields and associated values with
+            /// the options as a stream.
+            #[inline]
+            fn hscan_options<K: ToRedisArgs, RV: FromRedisValue>
+                    (self, key: K, options: &ScanOptions) -> AsyncIter<Self, RV> {
+                let mut c = cmd("HSCAN");
+                c.arg(key).cursor_arg(0).arg(options);
+                c.iter_async(self)
+            }
+
+            /// Incrementally iterate set elements as a stream.
+            #[inline]
+            fn sscan<K: ToRedisArgs, RV: FromRedisValue>(self, key: K) -> AsyncIter<Self, RV> {
+                let mut c = cmd("SSCAN");
+                c.arg(key).cursor_arg(0);
+                c.iter_async(self)
+            }
+
+            /// Incrementally iterate set elements for elements matching a
+            /// pattern as a stream.
+            #[inline]
+            fn sscan_match<K: ToRedisArgs, P: ToRedisArgs, RV: FromRedisValue>
+                    (self, key: K, pattern: P) -> AsyncIter<Self, RV> {
+                let mut c = cmd("SSCAN");
+                c.arg(key).cursor_arg(0).arg("MATCH").arg(pattern);
+                c.iter_async(self)
+            }
+
+            /// Incrementally iterate set elements with the options as a
+            /// stream.
+            #[inline]
+            fn sscan_options<K: ToRedisArgs, RV: FromRedisValue>
+                    (self, key: K, options: &ScanOptions) -> AsyncIter<Self, RV> {
+                let mut c = cmd("SSCAN");
+                c.arg(key).cursor_arg(0).arg(options);
+                c.iter_async(self)
+            }
+
+            /// Incrementally iterate sorted set elements as a stream.
+            #[inline]
+            fn zscan<K: ToRedisArgs, RV: FromRedisValue>(self, key: K) -> AsyncIter<Self, RV> {
+                let mut c = cmd("ZSCAN");
+                c.arg(key).cursor_arg(0);
+                c.iter_async(self)
+            }
+
+            /// Incrementally iterate sorted set elements for elements matching
+            /// a pattern as a stream.
+            #[inline]
+            fn zscan_match<K: ToRedisArgs, P: ToRedisArgs, RV: FromRedisValue>
+                    (self, key: K, pattern: P) -> AsyncIter<Self, RV> {
+                let mut c = cmd("ZSCAN");
+                c.arg(key).cursor_arg(0).arg("MATCH").arg(pattern);
+                c.iter_async(self)
+            }
+
+            /// Incrementally iterate sorted set elements with the options as
+            /// a stream.
+            #[inline]
+            fn zscan_options<K: ToRedisArgs, RV: FromRedisValue>
+                    (self, key: K, options: &ScanOptions) -> AsyncIter<Self, RV> {
+                let mut c = cmd("ZSCAN");
+                c.arg(key).cursor_arg(0).arg(options);
+                c.iter_async(self)
+            }
         }
 
         /// Implements common redis commands for pipelines.  Unlike the regular
@@ -156,6 +399,13 @@ implement_commands! {
         cmd("SET").arg(key).arg(value)
     }
 
+    /// Sets the value of a key with the options of `SET`, like a condition
+    /// and an expiration.  With `SetOptions::get` the previous value is
+    /// returned.
+    fn set_options<K: ToRedisArgs, V: ToRedisArgs>(key: K, value: V, options: &SetOptions) {
+        cmd("SET").arg(key).arg(value).arg(options)
+    }
+
     /// Sets multiple keys to their values.
     fn set_multiple<K: ToRedisArgs, V: ToRedisArgs>(items: &[(K, V)]) {
         cmd("MSET").arg(items)
@@ -211,16 +461,119 @@ implement_commands! {
         cmd("PEXPIREAT").arg(key).arg(ts)
     }
 
+    /// Sets a key's time to live in seconds if the condition holds and
+    /// returns whether it was set (redis 7).
+    fn expire_options<K: ToRedisArgs>(key: K, seconds: usize, option: ExpireOption) {
+        cmd("EXPIRE").arg(key).arg(seconds).arg(option)
+    }
+
+    /// Sets a key's time to live in milliseconds if the condition holds
+    /// and returns whether it was set (redis 7).
+    fn pexpire_options<K: ToRedisArgs>(key: K, ms: usize, option: ExpireOption) {
+        cmd("PEXPIRE").arg(key).arg(ms).arg(option)
+    }
+
+    /// Returns the UNIX timestamp at which a key expires, `-1` if it has
+    /// no expiration and `-2` if it does not exist (redis 7).
+    fn expire_time<K: ToRedisArgs>(key: K) {
+        cmd("EXPIRETIME").arg(key)
+    }
+
+    /// Returns the UNIX timestamp in milliseconds at which a key expires,
+    /// `-1` if it has no expiration and `-2` if it does not exist
+    /// (redis 7).
+    fn pexpire_time<K: ToRedisArgs>(key: K) {
+        cmd("PEXPIRETIME").arg(key)
+    }
+
     /// Remove the expiration from a key.
     fn persist<K: ToRedisArgs>(key: K) {
         cmd("PERSIST").arg(key)
     }
 
+    /// Sort the elements of a list, set or sorted set, or look up values
+    /// by them, as set in the options.
+    fn sort<K: ToRedisArgs>(key: K, options: &SortOptions) {
+        cmd("SORT").arg(key).arg(options)
+    }
+
+    /// Sort like `sort`, store the result as a list in another key and
+    /// return its length.
+    fn sort_store<K: ToRedisArgs>(key: K, dstkey: K, options: &SortOptions) {
+        cmd("SORT").arg(key).arg(options).arg("STORE").arg(dstkey)
+    }
+
+    /// Sort like `sort`, also on replicas (redis 7).
+    fn sort_ro<K: ToRedisArgs>(key: K, options: &SortOptions) {
+        cmd("SORT_RO").arg(key).arg(options)
+    }
+
+    /// Get the type of the value of a key, as `keys::KeyType`.
+    fn key_type<K: ToRedisArgs>(key: K) {
+        cmd("TYPE").arg(key)
+    }
+
+    /// Serialize the value of a key, as `Option<Vec<u8>>`.  The value can
+    /// be recreated with `restore`.
+    fn dump<K: ToRedisArgs>(key: K) {
+        cmd("DUMP").arg(key)
+    }
+
+    /// Create a key from a value serialized by `dump`, expiring after `ttl`
+    /// milliseconds or never with `0`.
+    fn restore<K: ToRedisArgs, V: ToRedisArgs>(key: K, ttl: usize, value: V) {
+        cmd("RESTORE").arg(key).arg(ttl).arg(value)
+    }
+
+    /// Create a key from a value serialized by `dump` with the options.
+    fn restore_options<K: ToRedisArgs, V: ToRedisArgs>(
+            key: K, ttl: usize, value: V, options: &RestoreOptions) {
+        cmd("RESTORE").arg(key).arg(ttl).arg(value).arg(options)
+    }
+
+    /// Move a key to a database of another server, waiting at most
+    /// `timeout` milliseconds for it.
+    fn migrate<H: ToRedisArgs, K: ToRedisArgs>(
+            host: H, port: u16, key: K, db: i64, timeout: usize, options: &MigrateOptions) {
+        cmd("MIGRATE").arg(host).arg(port).arg(key).arg(db).arg(timeout).arg(options)
+    }
+
+    /// Move several keys to a database of another server at once
+    /// (redis 3.0.6).
+    fn migrate_keys<H: ToRedisArgs, K: ToRedisArgs>(
+            host: H, port: u16, keys: &[K], db: i64, timeout: usize, options: &MigrateOptions) {
+        cmd("MIGRATE").arg(host).arg(port).arg("").arg(db).arg(timeout).arg(options)
+            .arg("KEYS").arg(keys)
+    }
+
     /// Check the expiration time of a key.
     fn ttl<K: ToRedisArgs>(key: K) {
         cmd("TTL").arg(key)
     }
 
+    /// Returns the internal representation of the value of a key, read it
+    /// as `ObjectEncoding`.  Nil if the key does not exist.
+    fn object_encoding<K: ToRedisArgs>(key: K) {
+        cmd("OBJECT").arg("ENCODING").arg(key)
+    }
+
+    /// Returns the logarithmic access frequency counter of a key.  Only
+    /// available with an LFU `maxmemory-policy`.
+    fn object_freq<K: ToRedisArgs>(key: K) {
+        cmd("OBJECT").arg("FREQ").arg(key)
+    }
+
+    /// Returns the seconds since a key was last accessed.  Not available
+    /// with an LFU `maxmemory-policy`.
+    fn object_idletime<K: ToRedisArgs>(key: K) {
+        cmd("OBJECT").arg("IDLETIME").arg(key)
+    }
+
+    /// Returns the number of references to the value of a key.
+    fn object_refcount<K: ToRedisArgs>(key: K) {
+        cmd("OBJECT").arg("REFCOUNT").arg(key)
+    }
+
     /// Rename a key.
     fn rename<K: ToRedisArgs>(key: K, new_key: K) {
         cmd("RENAME").arg(key).arg(new_key)
@@ -231,6 +584,18 @@ implement_commands! {
         cmd("RENAMENX").arg(key).arg(new_key)
     }
 
+    /// Copies the value of a key to another key unless that exists and
+    /// returns whether it was copied (redis 6.2).
+    fn copy<K: ToRedisArgs>(source: K, destination: K) {
+        cmd("COPY").arg(source).arg(destination)
+    }
+
+    /// Copies the value of a key with the options of `COPY`, like another
+    /// database, and returns whether it was copied (redis 6.2).
+    fn copy_options<K: ToRedisArgs>(source: K, destination: K, options: &CopyOptions) {
+        cmd("COPY").arg(source).arg(destination).arg(options)
+    }
+
     // common string operations
 
     /// Append a value to a key.
@@ -258,6 +623,35 @@ implement_commands! {
         cmd("GETBIT").arg(key).arg(offset)
     }
 
+    /// Reads and writes integers of arbitrary width at arbitrary offsets
+    /// of a string, as `Vec<Option<i64>>`.
+    fn bitfield<K: ToRedisArgs>(key: K, options: &BitFieldOptions) {
+        cmd("BITFIELD").arg(key).arg(options)
+    }
+
+    /// Reads integers of arbitrary width at arbitrary offsets of a string,
+    /// also on replicas (redis 6).
+    fn bitfield_ro<K: ToRedisArgs>(key: K, options: &BitFieldOptions) {
+        cmd("BITFIELD_RO").arg(key).arg(options)
+    }
+
+    /// Return the longest common subsequence of two strings (redis 7).
+    fn lcs<K: ToRedisArgs>(key1: K, key2: K) {
+        cmd("LCS").arg(key1).arg(key2)
+    }
+
+    /// Return the length of the longest common subsequence of two strings
+    /// (redis 7).
+    fn lcs_len<K: ToRedisArgs>(key1: K, key2: K) {
+        cmd("LCS").arg(key1).arg(key2).arg("LEN")
+    }
+
+    /// Return the positions of the longest common subsequence of two
+    /// strings, as `options::LcsReply` (redis 7).
+    fn lcs_idx<K: ToRedisArgs>(key1: K, key2: K, options: &LcsOptions) {
+        cmd("LCS").arg(key1).arg(key2).arg("IDX").arg(options)
+    }
+
     /// Count set bits in a string.
     fn bitcount<K: ToRedisArgs>(key: K) {
         cmd("BITCOUNT").arg(key)
@@ -268,6 +662,22 @@ implement_commands! {
         cmd("BITCOUNT").arg(key).arg(start).arg(end)
     }
 
+    /// Count set bits in a string in a range of bytes or bits.
+    fn bitcount_in_range<K: ToRedisArgs>(key: K, range: BitRange) {
+        cmd("BITCOUNT").arg(key).arg(range)
+    }
+
+    /// Return the position of the first bit set to 1 or 0 in a string.
+    fn bitpos<K: ToRedisArgs>(key: K, bit: bool) {
+        cmd("BITPOS").arg(key).arg(if bit {1} else {0})
+    }
+
+    /// Return the position of the first bit set to 1 or 0 in a range of
+    /// bytes or bits of a string, `-1` if the range has none.
+    fn bitpos_in_range<K: ToRedisArgs>(key: K, bit: bool, range: BitRange) {
+        cmd("BITPOS").arg(key).arg(if bit {1} else {0}).arg(range)
+    }
+
     /// Perform a bitwise AND between multiple keys (containing string values)
     /// and store the result in the destination key.
     fn bit_and<K: ToRedisArgs>(dstkey: K, srckeys: K) {
@@ -358,6 +768,73 @@ implement_commands! {
         cmd("HLEN").arg(key)
     }
 
+    /// Set the expiration of hash fields in seconds, as
+    /// `Vec<options::FieldExpireResult>` (redis 7.4).
+    fn hexpire<K: ToRedisArgs, F: ToRedisArgs>(key: K, seconds: usize, fields: &[F]) {
+        cmd("HEXPIRE").arg(key).arg(seconds).arg("FIELDS").arg(fields.len()).arg(fields)
+    }
+
+    /// Set the expiration of hash fields in seconds if the condition is met
+    /// (redis 7.4).
+    fn hexpire_options<K: ToRedisArgs, F: ToRedisArgs>(
+            key: K, seconds: usize, option: ExpireOption, fields: &[F]) {
+        cmd("HEXPIRE").arg(key).arg(seconds).arg(option).arg("FIELDS").arg(fields.len()).arg(fields)
+    }
+
+    /// Set the expiration of hash fields in milliseconds (redis 7.4).
+    fn hpexpire<K: ToRedisArgs, F: ToRedisArgs>(key: K, ms: usize, fields: &[F]) {
+        cmd("HPEXPIRE").arg(key).arg(ms).arg("FIELDS").arg(fields.len()).arg(fields)
+    }
+
+    /// Set the expiration of hash fields in milliseconds if the condition
+    /// is met (redis 7.4).
+    fn hpexpire_options<K: ToRedisArgs, F: ToRedisArgs>(
+            key: K, ms: usize, option: ExpireOption, fields: &[F]) {
+        cmd("HPEXPIRE").arg(key).arg(ms).arg(option).arg("FIELDS").arg(fields.len()).arg(fields)
+    }
+
+    /// Set hash fields to expire at a unix time in seconds (redis 7.4).
+    fn hexpire_at<K: ToRedisArgs, F: ToRedisArgs>(key: K, ts: usize, fields: &[F]) {
+        cmd("HEXPIREAT").arg(key).arg(ts).arg("FIELDS").arg(fields.len()).arg(fields)
+    }
+
+    /// Set hash fields to expire at a unix time in milliseconds
+    /// (redis 7.4).
+    fn hpexpire_at<K: ToRedisArgs, F: ToRedisArgs>(key: K, ts: usize, fields: &[F]) {
+        cmd("HPEXPIREAT").arg(key).arg(ts).arg("FIELDS").arg(fields.len()).arg(fields)
+    }
+
+    /// Get the remaining time to live of hash fields in seconds, `-2` for
+    /// missing fields and `-1` for fields without expiration (redis 7.4).
+    /// The values can be read with `keys::Ttl::from_secs`.
+    fn httl<K: ToRedisArgs, F: ToRedisArgs>(key: K, fields: &[F]) {
+        cmd("HTTL").arg(key).arg("FIELDS").arg(fields.len()).arg(fields)
+    }
+
+    /// Get the remaining time to live of hash fields in milliseconds
+    /// (redis 7.4).
+    fn hpttl<K: ToRedisArgs, F: ToRedisArgs>(key: K, fields: &[F]) {
+        cmd("HPTTL").arg(key).arg("FIELDS").arg(fields.len()).arg(fields)
+    }
+
+    /// Get the unix times in seconds at which hash fields expire
+    /// (redis 7.4).
+    fn hexpire_time<K: ToRedisArgs, F: ToRedisArgs>(key: K, fields: &[F]) {
+        cmd("HEXPIRETIME").arg(key).arg("FIELDS").arg(fields.len()).arg(fields)
+    }
+
+    /// Get the unix times in milliseconds at which hash fields expire
+    /// (redis 7.4).
+    fn hpexpire_time<K: ToRedisArgs, F: ToRedisArgs>(key: K, fields: &[F]) {
+        cmd("HPEXPIRETIME").arg(key).arg("FIELDS").arg(fields.len()).arg(fields)
+    }
+
+    /// Remove the expiration of hash fields, as
+    /// `Vec<options::FieldPersistResult>` (redis 7.4).
+    fn hpersist<K: ToRedisArgs, F: ToRedisArgs>(key: K, fields: &[F]) {
+        cmd("HPERSIST").arg(key).arg("FIELDS").arg(fields.len()).arg(fields)
+    }
+
     // list operations
 
     /// Remove and get the first element in a list, or block until one is available.
@@ -376,6 +853,20 @@ implement_commands! {
         cmd("BRPOPLPUSH").arg(srckey).arg(dstkey).arg(timeout)
     }
 
+    /// Pops up to `count` elements from the first non-empty list of the
+    /// keys, read it as `Option<MPopReply<T>>` (redis 7).
+    fn lmpop<K: ToRedisArgs>(keys: &[K], direction: ListDirection, count: usize) {
+        cmd("LMPOP").arg(keys.len()).arg(keys).arg(direction).arg("COUNT").arg(count)
+    }
+
+    /// Pops up to `count` elements from the first non-empty list of the
+    /// keys or blocks for up to `timeout` seconds until one is available,
+    /// `0` blocks indefinitely (redis 7).
+    fn blmpop<K: ToRedisArgs>(timeout: usize, keys: &[K], direction: ListDirection, count: usize) {
+        cmd("BLMPOP").arg(timeout).arg(keys.len()).arg(keys).arg(direction)
+            .arg("COUNT").arg(count)
+    }
+
     /// Get an element from a list by its index.
     fn lindex<K: ToRedisArgs>(key: K, index: isize) {
         cmd("LINDEX").arg(key).arg(index)
@@ -403,6 +894,19 @@ implement_commands! {
         cmd("LPOP").arg(key)
     }
 
+    /// Returns the index of the first element equal to value in the list
+    /// stored at key, nil if there is none (redis 6.0.6).
+    fn lpos<K: ToRedisArgs, V: ToRedisArgs>(key: K, value: V) {
+        cmd("LPOS").arg(key).arg(value)
+    }
+
+    /// Returns the index of an element equal to value with the options of
+    /// `LPOS`.  With `LposOptions::count` the reply is a list of indexes,
+    /// read it as `Vec<usize>`, and `Option<usize>` otherwise.
+    fn lpos_options<K: ToRedisArgs, V: ToRedisArgs>(key: K, value: V, options: &LposOptions) {
+        cmd("LPOS").arg(key).arg(value).arg(options)
+    }
+
     /// Insert all the specified values at the head of the list stored at key.
     fn lpush<K: ToRedisArgs, V: ToRedisArgs>(key: K, value: V) {
         cmd("LPUSH").arg(key).arg(value)
@@ -562,6 +1066,35 @@ implement_commands! {
         cmd("ZINCRBY").arg(key).arg(delta).arg(member)
     }
 
+    /// Return the members of the first sorted set that are in none of the
+    /// others (redis 6.2).
+    fn zdiff<K: ToRedisArgs>(keys: &[K]) {
+        cmd("ZDIFF").arg(keys.len()).arg(keys)
+    }
+
+    /// Return the members of the first sorted set that are in none of the
+    /// others, with their scores (redis 6.2).
+    fn zdiff_withscores<K: ToRedisArgs>(keys: &[K]) {
+        cmd("ZDIFF").arg(keys.len()).arg(keys).arg("WITHSCORES")
+    }
+
+    /// Intersect multiple sorted sets and return the members (redis 6.2).
+    fn zinter<K: ToRedisArgs>(keys: &[K]) {
+        cmd("ZINTER").arg(keys.len()).arg(keys)
+    }
+
+    /// Intersect multiple sorted sets and return the members with the sum
+    /// of their scores (redis 6.2).
+    fn zinter_withscores<K: ToRedisArgs>(keys: &[K]) {
+        cmd("ZINTER").arg(keys.len()).arg(keys).arg("WITHSCORES")
+    }
+
+    /// Intersect multiple sorted sets and return the members, combining
+    /// the scores as set in the options (redis 6.2).
+    fn zinter_options<K: ToRedisArgs>(keys: &[K], options: &ZAggregateOptions) {
+        cmd("ZINTER").arg(keys.len()).arg(keys).arg(options)
+    }
+
     /// Intersect multiple sorted sets and store the resulting sorted set in
     /// a new key using SUM as aggregation function.
     fn zinterstore<K: ToRedisArgs>(dstkey: K, keys: &[K]) {
@@ -585,6 +1118,20 @@ implement_commands! {
         cmd("ZLEXCOUNT").arg(key).arg(min).arg(max)
     }
 
+    /// Pops up to `count` members with their scores from the first
+    /// non-empty sorted set of the keys, read it as
+    /// `Option<MPopReply<(T, f64)>>` (redis 7).
+    fn zmpop<K: ToRedisArgs>(keys: &[K], end: ScoreEnd, count: usize) {
+        cmd("ZMPOP").arg(keys.len()).arg(keys).arg(end).arg("COUNT").arg(count)
+    }
+
+    /// Pops up to `count` members with their scores from the first
+    /// non-empty sorted set of the keys or blocks for up to `timeout`
+    /// seconds until one is available, `0` blocks indefinitely (redis 7).
+    fn bzmpop<K: ToRedisArgs>(timeout: usize, keys: &[K], end: ScoreEnd, count: usize) {
+        cmd("BZMPOP").arg(timeout).arg(keys.len()).arg(keys).arg(end).arg("COUNT").arg(count)
+    }
+
     /// Return a range of members in a sorted set, by index
     fn zrange<K: ToRedisArgs>(key: K, start: isize, stop: isize) {
         cmd("ZRANGE").arg(key).arg(start).arg(stop)
@@ -595,6 +1142,27 @@ implement_commands! {
         cmd("ZRANGE").arg(key).arg(start).arg(stop).arg("WITHSCORES")
     }
 
+    /// Return a range of members in a sorted set, by index, score or
+    /// member as set in the options (redis 6.2).
+    fn zrange_options<K: ToRedisArgs, S: ToRedisArgs, E: ToRedisArgs>(
+            key: K, start: S, stop: E, options: &ZRangeOptions) {
+        cmd("ZRANGE").arg(key).arg(start).arg(stop).arg(options)
+    }
+
+    /// Store a range of members of a sorted set, by index, in another
+    /// sorted set and return its size (redis 6.2).
+    fn zrangestore<K: ToRedisArgs>(dstkey: K, srckey: K, start: isize, stop: isize) {
+        cmd("ZRANGESTORE").arg(dstkey).arg(srckey).arg(start).arg(stop)
+    }
+
+    /// Store a range of members of a sorted set, by index, score or
+    /// member as set in the options, in another sorted set and return
+    /// its size (redis 6.2).
+    fn zrangestore_options<K: ToRedisArgs, S: ToRedisArgs, E: ToRedisArgs>(
+            dstkey: K, srckey: K, start: S, stop: E, options: &ZRangeOptions) {
+        cmd("ZRANGESTORE").arg(dstkey).arg(srckey).arg(start).arg(stop).arg(options)
+    }
+
     /// Return a range of members in a sorted set, by lexicographical range.
     fn zrangebylex<K: ToRedisArgs, M: ToRedisArgs, MM: ToRedisArgs>(key: K, min: M, max: MM) {
         cmd("ZRANGEBYLEX").arg(key).arg(min).arg(max)
@@ -712,6 +1280,23 @@ implement_commands! {
         cmd("ZSCORE").arg(key).arg(member)
     }
 
+    /// Unions multiple sorted sets and return the members (redis 6.2).
+    fn zunion<K: ToRedisArgs>(keys: &[K]) {
+        cmd("ZUNION").arg(keys.len()).arg(keys)
+    }
+
+    /// Unions multiple sorted sets and return the members with the sum of
+    /// their scores (redis 6.2).
+    fn zunion_withscores<K: ToRedisArgs>(keys: &[K]) {
+        cmd("ZUNION").arg(keys.len()).arg(keys).arg("WITHSCORES")
+    }
+
+    /// Unions multiple sorted sets and return the members, combining the
+    /// scores as set in the options (redis 6.2).
+    fn zunion_options<K: ToRedisArgs>(keys: &[K], options: &ZAggregateOptions) {
+        cmd("ZUNION").arg(keys.len()).arg(keys).arg(options)
+    }
+
     /// Unions multiple sorted sets and store the resulting sorted set in
     /// a new key using SUM as aggregation function.
     fn zunionstore<K: ToRedisArgs>(dstkey: K, keys: &[K]) {
@@ -748,10 +1333,803 @@ implement_commands! {
         cmd("PFMERGE").arg(dstkey).arg(srckeys)
     }
 
+    // geospatial commands
+
+    /// Return the members of a geospatial index within an area, as
+    /// `Vec<geo::GeoSearchResult>` (redis 6.2).
+    fn geosearch<K: ToRedisArgs>(key: K, options: &GeoSearchOptions) {
+        cmd("GEOSEARCH").arg(key).arg(options)
+    }
+
+    /// Store the members of a geospatial index within an area in another
+    /// index and return their number (redis 6.2).
+    fn geosearch_store<K: ToRedisArgs>(dstkey: K, srckey: K, options: &GeoSearchOptions) {
+        cmd("GEOSEARCHSTORE").arg(dstkey).arg(srckey).arg(options)
+    }
+
+    /// Store the members of a geospatial index within an area in a sorted
+    /// set with their distances as scores and return their number
+    /// (redis 6.2).
+    fn geosearch_store_dist<K: ToRedisArgs>(dstkey: K, srckey: K, options: &GeoSearchOptions) {
+        cmd("GEOSEARCHSTORE").arg(dstkey).arg(srckey).arg(options).arg("STOREDIST")
+    }
+
     /// Posts a message to the given channel.
     fn publish<K: ToRedisArgs, E: ToRedisArgs>(channel: K, message: E) {
         cmd("PUBLISH").arg(channel).arg(message)
     }
+
+    /// Posts a message to the given shard channel (redis 7).  In a cluster
+    /// it is sent to the node serving the slot of the channel.
+    fn spublish<K: ToRedisArgs, E: ToRedisArgs>(channel: K, message: E) {
+        cmd("SPUBLISH").arg(channel).arg(message)
+    }
+
+    /// Returns the shard channels with subscribers, optionally only those
+    /// matching a pattern (redis 7).  In a cluster only the channels of a
+    /// single node are returned, see `ClusterConnection::shard_channels`.
+    fn pubsub_shardchannels<P: ToRedisArgs>(pattern: Option<P>) {
+        cmd("PUBSUB").arg("SHARDCHANNELS").arg(pattern)
+    }
+
+    /// Returns the number of subscribers of each shard channel as pairs
+    /// of channel and count (redis 7).  In a cluster all channels have to
+    /// be in the same slot.
+    fn pubsub_shardnumsub<K: ToRedisArgs>(channels: K) {
+        cmd("PUBSUB").arg("SHARDNUMSUB").arg(channels)
+    }
+
+    // client commands
+
+    /// Closes the connections matching all filters and returns their
+    /// number.
+    fn client_kill<>(options: &ClientKillOptions) {
+        cmd("CLIENT").arg("KILL").arg(options)
+    }
+
+    /// Returns the id of the connection.
+    fn client_id<>() {
+        cmd("CLIENT").arg("ID")
+    }
+
+    /// Describes the connection as `ClientInfo` (redis 6.2).
+    fn client_info<>() {
+        cmd("CLIENT").arg("INFO")
+    }
+
+    /// Describes all connections, read it as `Vec<ClientInfo>`.
+    fn client_list<>() {
+        cmd("CLIENT").arg("LIST")
+    }
+
+    /// Describes the connections of the type, read it as
+    /// `Vec<ClientInfo>`.
+    fn client_list_type<>(client_type: ClientType) {
+        cmd("CLIENT").arg("LIST").arg("TYPE").arg(client_type)
+    }
+
+    /// Describes the connections with the ids, read it as
+    /// `Vec<ClientInfo>` (redis 6.2).
+    fn client_list_ids<>(ids: &[i64]) {
+        cmd("CLIENT").arg("LIST").arg("ID").arg(ids)
+    }
+
+    /// Holds back the commands of all clients for `ms` milliseconds, for
+    /// example while a replica is promoted.
+    fn client_pause<>(ms: usize, mode: PauseMode) {
+        cmd("CLIENT").arg("PAUSE").arg(ms).arg(mode)
+    }
+
+    /// Resumes the clients paused by `CLIENT PAUSE` (redis 6.2).
+    fn client_unpause<>() {
+        cmd("CLIENT").arg("UNPAUSE")
+    }
+
+    /// Exempts the connection from the eviction of clients when the
+    /// server runs out of memory (redis 7).
+    fn client_no_evict<>(on: bool) {
+        cmd("CLIENT").arg("NO-EVICT").arg(if on { "ON" } else { "OFF" })
+    }
+
+    /// Keeps the commands of the connection from changing the access time
+    /// of keys, which LRU and LFU eviction are based on (redis 7.2).
+    fn client_no_touch<>(on: bool) {
+        cmd("CLIENT").arg("NO-TOUCH").arg(if on { "ON" } else { "OFF" })
+    }
+
+    // client side caching commands
+
+    /// Enables tracking of the keys read by the connection, so the server
+    /// sends invalidations when they change (redis 6).
+    fn client_tracking<>(options: &TrackingOptions) {
+        cmd("CLIENT").arg("TRACKING").arg("ON").arg(options)
+    }
+
+    /// Disables tracking of keys.
+    fn client_tracking_off<>() {
+        cmd("CLIENT").arg("TRACKING").arg("OFF")
+    }
+
+    /// Tracks the keys of the next command (`YES`) in `OPTIN` mode or
+    /// does not track them (`NO`) in `OPTOUT` mode.
+    fn client_caching<>(yes: bool) {
+        cmd("CLIENT").arg("CACHING").arg(if yes { "YES" } else { "NO" })
+    }
+
+    /// Returns the tracking state of the connection as `TrackingInfo`
+    /// (redis 6.2).
+    fn client_trackinginfo<>() {
+        cmd("CLIENT").arg("TRACKINGINFO")
+    }
+
+    // stream commands
+
+    /// Returns the stream entries with ids between `start` and `end`.
+    /// Use `-` and `+` for the lowest and highest possible ids.
+    fn xrange<K: ToRedisArgs, S: ToRedisArgs, E: ToRedisArgs>(key: K, start: S, end: E) {
+        cmd("XRANGE").arg(key).arg(start).arg(end)
+    }
+
+    /// Returns at most `count` stream entries with ids between `start`
+    /// and `end`.
+    fn xrange_count<K: ToRedisArgs, S: ToRedisArgs, E: ToRedisArgs>(
+            key: K, start: S, end: E, count: usize) {
+        cmd("XRANGE").arg(key).arg(start).arg(end).arg("COUNT").arg(count)
+    }
+
+    /// Returns at most `count` stream entries with ids strictly greater
+    /// than `id`.  This is the building block for paginating forward
+    /// through a stream: pass the id of the last entry of the previous
+    /// page.
+    fn xrange_after<K: ToRedisArgs, I: ToRedisArgs>(key: K, id: I, count: usize) {
+        cmd("XRANGE").arg(key).arg(ExclusiveId(id)).arg("+").arg("COUNT").arg(count)
+    }
+
+    /// Returns the stream entries with ids between `end` and `start` in
+    /// reverse order.
+    fn xrevrange<K: ToRedisArgs, E: ToRedisArgs, S: ToRedisArgs>(key: K, end: E, start: S) {
+        cmd("XREVRANGE").arg(key).arg(end).arg(start)
+    }
+
+    /// Returns at most `count` stream entries with ids between `end` and
+    /// `start` in reverse order.
+    fn xrevrange_count<K: ToRedisArgs, E: ToRedisArgs, S: ToRedisArgs>(
+            key: K, end: E, start: S, count: usize) {
+        cmd("XREVRANGE").arg(key).arg(end).arg(start).arg("COUNT").arg(count)
+    }
+
+    /// Returns at most `count` stream entries with ids strictly lower than
+    /// `id` in reverse order.  This paginates backwards through a stream.
+    fn xrevrange_before<K: ToRedisArgs, I: ToRedisArgs>(key: K, id: I, count: usize) {
+        cmd("XREVRANGE").arg(key).arg(ExclusiveId(id)).arg("-").arg("COUNT").arg(count)
+    }
+
+    // cluster commands
+
+    /// Returns the hash slot of the key as computed by the server.  See
+    /// `cluster::key_slot` to compute it locally.
+    fn cluster_keyslot<K: ToRedisArgs>(key: K) {
+        cmd("CLUSTER").arg("KEYSLOT").arg(key)
+    }
+
+    /// Returns the state of the cluster as seen by the node, see
+    /// `cluster::ClusterInfo`.
+    fn cluster_info<>() {
+        cmd("CLUSTER").arg("INFO")
+    }
+
+    /// Returns the slot ranges and the nodes serving them, see
+    /// `cluster::ClusterSlotsReply`.
+    fn cluster_slots<>() {
+        cmd("CLUSTER").arg("SLOTS")
+    }
+
+    /// Returns the shards of the cluster, see `cluster::ClusterShardsReply`.
+    /// This requires redis 7.
+    fn cluster_shards<>() {
+        cmd("CLUSTER").arg("SHARDS")
+    }
+
+    /// Returns the nodes known to the node, see `cluster::ClusterNodesReply`.
+    fn cluster_nodes<>() {
+        cmd("CLUSTER").arg("NODES")
+    }
+
+    /// Returns the number of keys in the slot.  Only the node serving the
+    /// slot knows about its keys.
+    fn cluster_countkeysinslot<>(slot: u16) {
+        cmd("CLUSTER").arg("COUNTKEYSINSLOT").arg(slot)
+    }
+
+    /// Makes the replica this is sent to take over from its master in a
+    /// coordinated failover.  This has to be sent to the replica itself.
+    fn cluster_failover<>() {
+        cmd("CLUSTER").arg("FAILOVER")
+    }
+
+    /// Makes the replica take over from its master without the master
+    /// agreeing, for instance because the master is down.
+    fn cluster_failover_force<>() {
+        cmd("CLUSTER").arg("FAILOVER").arg("FORCE")
+    }
+
+    /// Makes the replica take over from its master without the agreement
+    /// of the other masters.  This can violate the guarantees of the
+    /// cluster and is meant for manual interventions.
+    fn cluster_failover_takeover<>() {
+        cmd("CLUSTER").arg("FAILOVER").arg("TAKEOVER")
+    }
+
+    /// Makes the node a replica of the master with the given id.
+    fn cluster_replicate<I: ToRedisArgs>(node_id: I) {
+        cmd("CLUSTER").arg("REPLICATE").arg(node_id)
+    }
+
+    /// Sets the configuration epoch of a new node.  This only works on
+    /// nodes that do not know other nodes yet.
+    fn cluster_set_config_epoch<>(epoch: u64) {
+        cmd("CLUSTER").arg("SET-CONFIG-EPOCH").arg(epoch)
+    }
+
+    // replication commands
+
+    /// Makes the server a replica of the given master.  This requires
+    /// redis 5, use `slaveof` with older servers.
+    fn replicaof<H: ToRedisArgs>(host: H, port: u16) {
+        cmd("REPLICAOF").arg(host).arg(port)
+    }
+
+    /// Stops the replication and turns the server into a master.  This
+    /// requires redis 5, use `slaveof_no_one` with older servers.
+    fn replicaof_no_one<>() {
+        cmd("REPLICAOF").arg("NO").arg("ONE")
+    }
+
+    /// Makes the server a replica of the given master.
+    fn slaveof<H: ToRedisArgs>(host: H, port: u16) {
+        cmd("SLAVEOF").arg(host).arg(port)
+    }
+
+    /// Stops the replication and turns the server into a master.
+    fn slaveof_no_one<>() {
+        cmd("SLAVEOF").arg("NO").arg("ONE")
+    }
+
+    // server commands
+
+    /// Returns the configuration parameters matching one or more glob
+    /// patterns, as `HashMap<String, String>`.  Several patterns at once
+    /// require redis 7.
+    fn config_get<P: ToRedisArgs>(patterns: P) {
+        cmd("CONFIG").arg("GET").arg(patterns)
+    }
+
+    /// Sets a configuration parameter.
+    fn config_set<P: ToRedisArgs, V: ToRedisArgs>(parameter: P, value: V) {
+        cmd("CONFIG").arg("SET").arg(parameter).arg(value)
+    }
+
+    /// Sets several configuration parameters at once, either all or none
+    /// of them (redis 7).
+    fn config_set_multiple<P: ToRedisArgs, V: ToRedisArgs>(items: &[(P, V)]) {
+        cmd("CONFIG").arg("SET").arg(items)
+    }
+
+    /// Resets the statistics of `INFO` and the latency of commands.
+    fn config_resetstat<>() {
+        cmd("CONFIG").arg("RESETSTAT")
+    }
+
+    /// Writes the configuration of the running server to its
+    /// configuration file.
+    fn config_rewrite<>() {
+        cmd("CONFIG").arg("REWRITE")
+    }
+
+    /// Returns the bytes a key and its value use, nil if the key does not
+    /// exist.  Nested values are estimated from `samples` of their
+    /// elements, `5` like the server default or `0` for all elements.
+    fn memory_usage<K: ToRedisArgs>(key: K, samples: usize) {
+        cmd("MEMORY").arg("USAGE").arg(key).arg("SAMPLES").arg(samples)
+    }
+
+    /// Returns the memory statistics of the server, as
+    /// `server::MemoryStats`.
+    fn memory_stats<>() {
+        cmd("MEMORY").arg("STATS")
+    }
+
+    /// Returns advice on memory problems of the server as text.
+    fn memory_doctor<>() {
+        cmd("MEMORY").arg("DOCTOR")
+    }
+
+    /// Returns the latest entries of the slow log, newest first, as
+    /// `Vec<server::SlowlogEntry>`.
+    fn slowlog_get<>(count: usize) {
+        cmd("SLOWLOG").arg("GET").arg(count)
+    }
+
+    /// Returns the number of entries in the slow log.
+    fn slowlog_len<>() {
+        cmd("SLOWLOG").arg("LEN")
+    }
+
+    /// Removes all entries from the slow log.
+    fn slowlog_reset<>() {
+        cmd("SLOWLOG").arg("RESET")
+    }
+
+    /// Returns the number of commands the server knows.
+    fn command_count<>() {
+        cmd("COMMAND").arg("COUNT")
+    }
+
+    /// Returns the details of one or more commands as
+    /// `Vec<Option<server::CommandInfo>>`, with none for commands the
+    /// server does not know.
+    fn command_info<N: ToRedisArgs>(names: N) {
+        cmd("COMMAND").arg("INFO").arg(names)
+    }
+
+    /// Returns the documentation of one or more commands as
+    /// `HashMap<String, server::CommandDocs>`, leaving out commands the
+    /// server does not know (redis 7).
+    fn command_docs<N: ToRedisArgs>(names: N) {
+        cmd("COMMAND").arg("DOCS").arg(names)
+    }
+
+    /// Deletes all keys of the selected database.
+    fn flushdb<>() {
+        &mut cmd("FLUSHDB")
+    }
+
+    /// Deletes all keys of the selected database, freeing their memory
+    /// in the background or before replying.
+    fn flushdb_options<>(mode: FlushMode) {
+        cmd("FLUSHDB").arg(mode)
+    }
+
+    /// Deletes all keys of all databases.
+    fn flushall<>() {
+        &mut cmd("FLUSHALL")
+    }
+
+    /// Deletes all keys of all databases, freeing their memory in the
+    /// background or before replying.
+    fn flushall_options<>(mode: FlushMode) {
+        cmd("FLUSHALL").arg(mode)
+    }
+
+    /// Stops the server.  The server closes the connection when it shuts
+    /// down, so this only returns an error reply if it refused to, for
+    /// instance because it could not save the dataset.  Check
+    /// `RedisError::is_connection_dropped` for a successful shutdown.
+    fn shutdown<>(options: &ShutdownOptions) {
+        cmd("SHUTDOWN").arg(options)
+    }
+
+    // debug commands (test-support feature)
+
+    /// Blocks the server for `seconds`, to simulate a slow or hanging
+    /// server.  Needs `enable-debug-command` on redis 7.
+    #[cfg(feature = "test-support")]
+    fn debug_sleep<>(seconds: f64) {
+        cmd("DEBUG").arg("SLEEP").arg(seconds)
+    }
+
+    /// Returns how a key is stored, as `test_support::DebugObject`.
+    #[cfg(feature = "test-support")]
+    fn debug_object<K: ToRedisArgs>(key: K) {
+        cmd("DEBUG").arg("OBJECT").arg(key)
+    }
+
+    /// Enables or disables the active expiry of keys, so that expired
+    /// keys are only removed when they are accessed.
+    #[cfg(feature = "test-support")]
+    fn debug_set_active_expire<>(enabled: bool) {
+        cmd("DEBUG").arg("SET-ACTIVE-EXPIRE").arg(if enabled { 1 } else { 0 })
+    }
+
+    /// Saves the dataset to disk, empties the database and loads it again
+    /// from disk, like a restart of the server.
+    #[cfg(feature = "test-support")]
+    fn debug_reload<>() {
+        cmd("DEBUG").arg("RELOAD")
+    }
+
+    /// Enables or disables `TCP_QUICKACK` on the connection, which
+    /// changes when the server acknowledges packets (redis 7).
+    #[cfg(feature = "test-support")]
+    fn debug_quickack<>(enabled: bool) {
+        cmd("DEBUG").arg("QUICKACK").arg(if enabled { 1 } else { 0 })
+    }
+
+    // function commands
+
+    /// Loads a function library (redis 7).  Returns the name of the
+    /// library.  See `functions::Library` for calling its functions.
+    fn function_load<C: ToRedisArgs>(code: C) {
+        cmd("FUNCTION").arg("LOAD").arg(code)
+    }
+
+    /// Loads a function library and replaces a library of the same name
+    /// (redis 7).
+    fn function_load_replace<C: ToRedisArgs>(code: C) {
+        cmd("FUNCTION").arg("LOAD").arg("REPLACE").arg(code)
+    }
+
+    /// Deletes a function library (redis 7).
+    fn function_delete<L: ToRedisArgs>(library: L) {
+        cmd("FUNCTION").arg("DELETE").arg(library)
+    }
+
+    /// Deletes all function libraries (redis 7).
+    fn function_flush<>() {
+        cmd("FUNCTION").arg("FLUSH")
+    }
+
+    /// Lists all function libraries as `Vec<functions::LibraryInfo>`,
+    /// optionally with their code (redis 7).
+    fn function_list<>(with_code: bool) {
+        cmd("FUNCTION").arg("LIST").arg(if with_code { Some("WITHCODE") } else { None })
+    }
+
+    /// Lists the function libraries whose name matches a glob pattern as
+    /// `Vec<functions::LibraryInfo>` (redis 7).
+    fn function_list_matching<P: ToRedisArgs>(pattern: P, with_code: bool) {
+        cmd("FUNCTION").arg("LIST").arg("LIBRARYNAME").arg(pattern)
+            .arg(if with_code { Some("WITHCODE") } else { None })
+    }
+
+    /// Returns the running function and the loaded libraries as
+    /// `functions::FunctionStats` (redis 7).
+    fn function_stats<>() {
+        cmd("FUNCTION").arg("STATS")
+    }
+
+    /// Returns a serialized payload of all function libraries (redis 7).
+    fn function_dump<>() {
+        cmd("FUNCTION").arg("DUMP")
+    }
+
+    /// Restores the function libraries from a payload of `function_dump`
+    /// (redis 7).
+    fn function_restore<P: ToRedisArgs>(payload: P, policy: RestorePolicy) {
+        cmd("FUNCTION").arg("RESTORE").arg(payload).arg(policy)
+    }
+
+    /// Calls a function of a loaded library (redis 7).
+    fn fcall<F: ToRedisArgs, K: ToRedisArgs, A: ToRedisArgs>(function: F, keys: &[K], args: A) {
+        cmd("FCALL").arg(function).arg(keys.len()).arg(keys).arg(args)
+    }
+
+    /// Calls a function flagged `no-writes` with `FCALL_RO`, which may be
+    /// served by a replica (redis 7).
+    fn fcall_ro<F: ToRedisArgs, K: ToRedisArgs, A: ToRedisArgs>(function: F, keys: &[K], args: A) {
+        cmd("FCALL_RO").arg(function).arg(keys.len()).arg(keys).arg(args)
+    }
+
+    // search commands (RediSearch module)
+
+    /// Creates an index with the fields of the schema.
+    #[cfg(feature = "with-search")]
+    fn ft_create<I: ToRedisArgs>(index: I, options: &CreateOptions, schema: &Schema) {
+        cmd("FT.CREATE").arg(index).arg(options).arg(schema)
+    }
+
+    /// Searches an index, as `search::SearchResult`.
+    #[cfg(feature = "with-search")]
+    fn ft_search<I: ToRedisArgs, Q: ToRedisArgs>(index: I, query: Q, options: &SearchOptions) {
+        cmd("FT.SEARCH").arg(index).arg(query).arg(options)
+    }
+
+    /// Runs an aggregation over the documents matching the query, as
+    /// `search::AggregateResult`.
+    #[cfg(feature = "with-search")]
+    fn ft_aggregate<I: ToRedisArgs, Q: ToRedisArgs>(index: I, query: Q, options: &AggregateOptions) {
+        cmd("FT.AGGREGATE").arg(index).arg(query).arg(options)
+    }
+
+    /// Deletes an index, and with `delete_documents` the documents it
+    /// indexed.
+    #[cfg(feature = "with-search")]
+    fn ft_dropindex<I: ToRedisArgs>(index: I, delete_documents: bool) {
+        cmd("FT.DROPINDEX").arg(index).arg(if delete_documents { Some("DD") } else { None })
+    }
+
+    /// Returns information about an index, as `search::IndexInfo`.
+    #[cfg(feature = "with-search")]
+    fn ft_info<I: ToRedisArgs>(index: I) {
+        cmd("FT.INFO").arg(index)
+    }
+
+    // probabilistic commands (RedisBloom module)
+
+    /// Creates a Bloom filter for `capacity` items with a false positive
+    /// rate of `error_rate`.
+    #[cfg(feature = "with-bloom")]
+    fn bf_reserve<K: ToRedisArgs>(key: K, error_rate: f64, capacity: usize) {
+        cmd("BF.RESERVE").arg(key).arg(error_rate).arg(capacity)
+    }
+
+    /// Adds an item to a Bloom filter, creating it with the default
+    /// parameters.  Returns false if the item may have been added before.
+    #[cfg(feature = "with-bloom")]
+    fn bf_add<K: ToRedisArgs, I: ToRedisArgs>(key: K, item: I) {
+        cmd("BF.ADD").arg(key).arg(item)
+    }
+
+    /// Adds items to a Bloom filter, as `Vec<bool>` like `bf_add`.
+    #[cfg(feature = "with-bloom")]
+    fn bf_madd<K: ToRedisArgs, I: ToRedisArgs>(key: K, items: I) {
+        cmd("BF.MADD").arg(key).arg(items)
+    }
+
+    /// Returns false if an item was certainly not added to a Bloom filter.
+    #[cfg(feature = "with-bloom")]
+    fn bf_exists<K: ToRedisArgs, I: ToRedisArgs>(key: K, item: I) {
+        cmd("BF.EXISTS").arg(key).arg(item)
+    }
+
+    /// Checks items like `bf_exists`, as `Vec<bool>`.
+    #[cfg(feature = "with-bloom")]
+    fn bf_mexists<K: ToRedisArgs, I: ToRedisArgs>(key: K, items: I) {
+        cmd("BF.MEXISTS").arg(key).arg(items)
+    }
+
+    /// Returns the number of items added to a Bloom filter.
+    #[cfg(feature = "with-bloom")]
+    fn bf_card<K: ToRedisArgs>(key: K) {
+        cmd("BF.CARD").arg(key)
+    }
+
+    /// Returns information about a Bloom filter, as `bloom::BloomInfo`.
+    #[cfg(feature = "with-bloom")]
+    fn bf_info<K: ToRedisArgs>(key: K) {
+        cmd("BF.INFO").arg(key)
+    }
+
+    /// Creates a Cuckoo filter for `capacity` items.
+    #[cfg(feature = "with-bloom")]
+    fn cf_reserve<K: ToRedisArgs>(key: K, capacity: usize) {
+        cmd("CF.RESERVE").arg(key).arg(capacity)
+    }
+
+    /// Adds an item to a Cuckoo filter, creating it with the default
+    /// parameters.  Items can be added more than once.
+    #[cfg(feature = "with-bloom")]
+    fn cf_add<K: ToRedisArgs, I: ToRedisArgs>(key: K, item: I) {
+        cmd("CF.ADD").arg(key).arg(item)
+    }
+
+    /// Adds an item to a Cuckoo filter unless it may exist.  Returns
+    /// whether it was added.
+    #[cfg(feature = "with-bloom")]
+    fn cf_addnx<K: ToRedisArgs, I: ToRedisArgs>(key: K, item: I) {
+        cmd("CF.ADDNX").arg(key).arg(item)
+    }
+
+    /// Returns false if an item is certainly not in a Cuckoo filter.
+    #[cfg(feature = "with-bloom")]
+    fn cf_exists<K: ToRedisArgs, I: ToRedisArgs>(key: K, item: I) {
+        cmd("CF.EXISTS").arg(key).arg(item)
+    }
+
+    /// Checks items like `cf_exists`, as `Vec<bool>`.
+    #[cfg(feature = "with-bloom")]
+    fn cf_mexists<K: ToRedisArgs, I: ToRedisArgs>(key: K, items: I) {
+        cmd("CF.MEXISTS").arg(key).arg(items)
+    }
+
+    /// Deletes one occurrence of an item from a Cuckoo filter.  Returns
+    /// whether it was found.
+    #[cfg(feature = "with-bloom")]
+    fn cf_del<K: ToRedisArgs, I: ToRedisArgs>(key: K, item: I) {
+        cmd("CF.DEL").arg(key).arg(item)
+    }
+
+    /// Returns how often an item may have been added to a Cuckoo filter.
+    #[cfg(feature = "with-bloom")]
+    fn cf_count<K: ToRedisArgs, I: ToRedisArgs>(key: K, item: I) {
+        cmd("CF.COUNT").arg(key).arg(item)
+    }
+
+    /// Returns information about a Cuckoo filter, as `bloom::CuckooInfo`.
+    #[cfg(feature = "with-bloom")]
+    fn cf_info<K: ToRedisArgs>(key: K) {
+        cmd("CF.INFO").arg(key)
+    }
+
+    /// Creates a Count-Min sketch of `depth` rows of `width` counters.
+    #[cfg(feature = "with-bloom")]
+    fn cms_initbydim<K: ToRedisArgs>(key: K, width: usize, depth: usize) {
+        cmd("CMS.INITBYDIM").arg(key).arg(width).arg(depth)
+    }
+
+    /// Creates a Count-Min sketch whose counts are off by at most `error`
+    /// times the total count with the probability `1 - probability`.
+    #[cfg(feature = "with-bloom")]
+    fn cms_initbyprob<K: ToRedisArgs>(key: K, error: f64, probability: f64) {
+        cmd("CMS.INITBYPROB").arg(key).arg(error).arg(probability)
+    }
+
+    /// Increments the counts of items in a Count-Min sketch.  Returns the
+    /// new counts.
+    #[cfg(feature = "with-bloom")]
+    fn cms_incrby<K: ToRedisArgs, I: ToRedisArgs>(key: K, items: &[(I, i64)]) {
+        cmd("CMS.INCRBY").arg(key).arg(items)
+    }
+
+    /// Returns the counts of items in a Count-Min sketch.
+    #[cfg(feature = "with-bloom")]
+    fn cms_query<K: ToRedisArgs, I: ToRedisArgs>(key: K, items: I) {
+        cmd("CMS.QUERY").arg(key).arg(items)
+    }
+
+    /// Returns information about a Count-Min sketch, as `bloom::CmsInfo`.
+    #[cfg(feature = "with-bloom")]
+    fn cms_info<K: ToRedisArgs>(key: K) {
+        cmd("CMS.INFO").arg(key)
+    }
+
+    /// Creates a Top-K list that keeps the `k` most frequent items.
+    #[cfg(feature = "with-bloom")]
+    fn topk_reserve<K: ToRedisArgs>(key: K, k: usize) {
+        cmd("TOPK.RESERVE").arg(key).arg(k)
+    }
+
+    /// Adds items to a Top-K list.  Returns the item each one pushed out
+    /// of the list, as `Vec<Option<String>>`.
+    #[cfg(feature = "with-bloom")]
+    fn topk_add<K: ToRedisArgs, I: ToRedisArgs>(key: K, items: I) {
+        cmd("TOPK.ADD").arg(key).arg(items)
+    }
+
+    /// Increments the counts of items in a Top-K list, like `topk_add`.
+    #[cfg(feature = "with-bloom")]
+    fn topk_incrby<K: ToRedisArgs, I: ToRedisArgs>(key: K, items: &[(I, i64)]) {
+        cmd("TOPK.INCRBY").arg(key).arg(items)
+    }
+
+    /// Returns whether items are in a Top-K list, as `Vec<bool>`.
+    #[cfg(feature = "with-bloom")]
+    fn topk_query<K: ToRedisArgs, I: ToRedisArgs>(key: K, items: I) {
+        cmd("TOPK.QUERY").arg(key).arg(items)
+    }
+
+    /// Returns the items of a Top-K list, the most frequent first.
+    #[cfg(feature = "with-bloom")]
+    fn topk_list<K: ToRedisArgs>(key: K) {
+        cmd("TOPK.LIST").arg(key)
+    }
+
+    /// Returns the items of a Top-K list with their counts, as
+    /// `Vec<(String, u64)>`.
+    #[cfg(feature = "with-bloom")]
+    fn topk_list_with_count<K: ToRedisArgs>(key: K) {
+        cmd("TOPK.LIST").arg(key).arg("WITHCOUNT")
+    }
+
+    /// Returns information about a Top-K list, as `bloom::TopKInfo`.
+    #[cfg(feature = "with-bloom")]
+    fn topk_info<K: ToRedisArgs>(key: K) {
+        cmd("TOPK.INFO").arg(key)
+    }
+
+    /// Creates a t-digest, with the default compression of 100 if none is
+    /// given.
+    #[cfg(feature = "with-bloom")]
+    fn tdigest_create<K: ToRedisArgs>(key: K, compression: Option<u32>) {
+        cmd("TDIGEST.CREATE").arg(key).arg(compression.map(|c| ("COMPRESSION", c)))
+    }
+
+    /// Adds values to a t-digest.
+    #[cfg(feature = "with-bloom")]
+    fn tdigest_add<K: ToRedisArgs>(key: K, values: &[f64]) {
+        cmd("TDIGEST.ADD").arg(key).arg(values)
+    }
+
+    /// Removes all values from a t-digest.
+    #[cfg(feature = "with-bloom")]
+    fn tdigest_reset<K: ToRedisArgs>(key: K) {
+        cmd("TDIGEST.RESET").arg(key)
+    }
+
+    /// Returns estimates of the values at the quantiles, like 0.5 for the
+    /// median, as `Vec<f64>`.
+    #[cfg(feature = "with-bloom")]
+    fn tdigest_quantile<K: ToRedisArgs>(key: K, quantiles: &[f64]) {
+        cmd("TDIGEST.QUANTILE").arg(key).arg(quantiles)
+    }
+
+    /// Returns estimates of the share of values up to each of the values,
+    /// as `Vec<f64>`.
+    #[cfg(feature = "with-bloom")]
+    fn tdigest_cdf<K: ToRedisArgs>(key: K, values: &[f64]) {
+        cmd("TDIGEST.CDF").arg(key).arg(values)
+    }
+
+    /// Returns the smallest value of a t-digest, NaN if it is empty.
+    #[cfg(feature = "with-bloom")]
+    fn tdigest_min<K: ToRedisArgs>(key: K) {
+        cmd("TDIGEST.MIN").arg(key)
+    }
+
+    /// Returns the largest value of a t-digest, NaN if it is empty.
+    #[cfg(feature = "with-bloom")]
+    fn tdigest_max<K: ToRedisArgs>(key: K) {
+        cmd("TDIGEST.MAX").arg(key)
+    }
+
+    /// Returns information about a t-digest, as `bloom::TDigestInfo`.
+    #[cfg(feature = "with-bloom")]
+    fn tdigest_info<K: ToRedisArgs>(key: K) {
+        cmd("TDIGEST.INFO").arg(key)
+    }
+
+    // time series commands (RedisTimeSeries module)
+
+    /// Creates a time series.
+    #[cfg(feature = "with-timeseries")]
+    fn ts_create<K: ToRedisArgs>(key: K, options: &SeriesOptions) {
+        cmd("TS.CREATE").arg(key).arg(options)
+    }
+
+    /// Changes the retention, chunk size, duplicate policy or labels of
+    /// a time series.
+    #[cfg(feature = "with-timeseries")]
+    fn ts_alter<K: ToRedisArgs>(key: K, options: &SeriesOptions) {
+        cmd("TS.ALTER").arg(key).arg(options)
+    }
+
+    /// Adds a sample to a time series, creating it with the default
+    /// options if it does not exist.  Returns the time of the sample.
+    #[cfg(feature = "with-timeseries")]
+    fn ts_add<K: ToRedisArgs>(key: K, timestamp: Timestamp, value: f64) {
+        cmd("TS.ADD").arg(key).arg(timestamp).arg(value)
+    }
+
+    /// Adds a sample to a time series, creating it with `options` if it
+    /// does not exist.  Returns the time of the sample.
+    #[cfg(feature = "with-timeseries")]
+    fn ts_add_options<K: ToRedisArgs>(key: K, timestamp: Timestamp, value: f64, options: &SeriesOptions) {
+        cmd("TS.ADD").arg(key).arg(timestamp).arg(value).arg(options)
+    }
+
+    /// Adds samples to existing time series.  Returns the time of each
+    /// sample, or an error for each sample that was not added.
+    #[cfg(feature = "with-timeseries")]
+    fn ts_madd<K: ToRedisArgs>(samples: &[(K, Timestamp, f64)]) {
+        cmd("TS.MADD").arg(samples)
+    }
+
+    /// Returns the samples of a time series between two times, as
+    /// `Vec<timeseries::Sample>`.
+    #[cfg(feature = "with-timeseries")]
+    fn ts_range<K: ToRedisArgs>(key: K, from: RangeBound, to: RangeBound, options: &RangeOptions) {
+        cmd("TS.RANGE").arg(key).arg(from).arg(to).arg(options)
+    }
+
+    /// Returns the samples of a time series between two times, latest
+    /// first.
+    #[cfg(feature = "with-timeseries")]
+    fn ts_revrange<K: ToRedisArgs>(key: K, from: RangeBound, to: RangeBound, options: &RangeOptions) {
+        cmd("TS.REVRANGE").arg(key).arg(from).arg(to).arg(options)
+    }
+
+    /// Returns the samples between two times of all time series whose
+    /// labels match the filters, as `Vec<timeseries::Series>`.
+    #[cfg(feature = "with-timeseries")]
+    fn ts_mrange<>(from: RangeBound, to: RangeBound, options: &MRangeOptions) {
+        cmd("TS.MRANGE").arg(from).arg(to).arg(options)
+    }
+
+    /// Like `ts_mrange`, latest samples first.
+    #[cfg(feature = "with-timeseries")]
+    fn ts_mrevrange<>(from: RangeBound, to: RangeBound, options: &MRangeOptions) {
+        cmd("TS.MREVRANGE").arg(from).arg(to).arg(options)
+    }
 }
 
 /// Allows pubsub callbacks to stop receiving messages.
@@ -820,6 +2198,9 @@ pub trait PubSubCommands: Sized {
 
 impl<T> Commands for T where T: ConnectionLike {}
 
+#[cfg(feature = "aio")]
+impl<T> AsyncCommands for T where T: ::aio::ConnectionLike + Send + 'static {}
+
 impl PubSubCommands for Connection {
     fn subscribe<'a, C, F, U>(&mut self, channels: C, mut func: F) -> RedisResult<U>
         where F: FnMut(Msg) -> ControlFlow<U>,
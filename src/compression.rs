@@ -0,0 +1,221 @@
+//! Transparent compression of large values.
+//!
+//! Wrapping a value in `Compressed` compresses every argument it writes
+//! that is larger than `Compression::default().threshold` and decompresses
+//! replies again, so large blobs take less memory in redis and less
+//! bandwidth:
+//!
+//! ```rust,no_run
+//! # extern crate redis;
+//! use redis::compression::Compressed;
+//! use redis::Commands;
+//!
+//! # fn do_something() -> redis::RedisResult<()> {
+//! # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+//! # let mut con = client.get_connection().unwrap();
+//! let page = "<html>...</html>".repeat(1000);
+//! let _: () = con.set("page:/", Compressed(&page))?;
+//! let Compressed(page): Compressed<String> = con.get("page:/")?;
+//! # Ok(()) }
+//! # fn main() {}
+//! ```
+//!
+//! It can be combined with the wrappers of the `codec` module, like
+//! `Compressed<Json<T>>`.
+//!
+//! LZ4 needs the `with-lz4` feature and zstd the `with-zstd` feature.
+//! Compressed values start with a header of eight bytes: the magic bytes
+//! `\xffRZ`, the algorithm and the length of the uncompressed value.
+//! Values without the header are returned unchanged, so keys written
+//! before compression was enabled stay readable, and values compressed
+//! with either algorithm are read regardless of the one used for writing.
+use std::io;
+
+use types::{ErrorKind, FromRedisValue, RedisError, RedisResult, RedisWrite, ToRedisArgs, Value};
+
+const MAGIC: &[u8] = b"\xffRZ";
+const HEADER_LEN: usize = 8;
+
+/// The compression algorithms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// LZ4, which is very fast but compresses less.
+    #[cfg(feature = "with-lz4")]
+    Lz4,
+    /// Zstandard with the given level from 1 to 22.
+    #[cfg(feature = "with-zstd")]
+    Zstd(i32),
+}
+
+impl Algorithm {
+    fn id(self) -> u8 {
+        match self {
+            #[cfg(feature = "with-lz4")]
+            Algorithm::Lz4 => 1,
+            #[cfg(feature = "with-zstd")]
+            Algorithm::Zstd(_) => 2,
+        }
+    }
+}
+
+/// The settings of the compression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Compression {
+    /// The algorithm used to compress values.  Defaults to LZ4 if the
+    /// `with-lz4` feature is enabled and to zstd at level 3 otherwise.
+    pub algorithm: Algorithm,
+    /// Values of up to this many bytes are stored uncompressed.  Defaults
+    /// to 1024.
+    pub threshold: usize,
+}
+
+impl Default for Compression {
+    #[cfg(feature = "with-lz4")]
+    fn default() -> Compression {
+        Compression {
+            algorithm: Algorithm::Lz4,
+            threshold: 1024,
+        }
+    }
+
+    #[cfg(not(feature = "with-lz4"))]
+    fn default() -> Compression {
+        Compression {
+            algorithm: Algorithm::Zstd(3),
+            threshold: 1024,
+        }
+    }
+}
+
+fn decompress_error(detail: String) -> RedisError {
+    RedisError::from((ErrorKind::TypeError, "Failed to decompress value", detail))
+}
+
+impl Compression {
+    /// Compresses the value if it is larger than the threshold and
+    /// compression makes it smaller, otherwise the value is returned
+    /// unchanged.
+    pub fn compress(&self, data: &[u8]) -> Vec<u8> {
+        if data.len() <= self.threshold || data.len() > i32::max_value() as usize {
+            return data.to_vec();
+        }
+        let compressed = match self.compress_block(data) {
+            Ok(compressed) => compressed,
+            Err(_) => return data.to_vec(),
+        };
+        if compressed.len() + HEADER_LEN >= data.len() {
+            return data.to_vec();
+        }
+        let len = data.len() as u32;
+        let mut rv = Vec::with_capacity(HEADER_LEN + compressed.len());
+        rv.extend_from_slice(MAGIC);
+        rv.push(self.algorithm.id());
+        rv.extend_from_slice(&[
+            len as u8,
+            (len >> 8) as u8,
+            (len >> 16) as u8,
+            (len >> 24) as u8,
+        ]);
+        rv.extend_from_slice(&compressed);
+        rv
+    }
+
+    fn compress_block(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self.algorithm {
+            #[cfg(feature = "with-lz4")]
+            Algorithm::Lz4 => ::lz4::block::compress(data, None, false),
+            #[cfg(feature = "with-zstd")]
+            Algorithm::Zstd(level) => ::zstd::block::compress(data, level),
+        }
+    }
+}
+
+/// Returns whether the value starts with the header of a compressed value.
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.len() >= HEADER_LEN && data.starts_with(MAGIC)
+}
+
+/// Decompresses a value written by `Compression::compress`.  Values
+/// without the header are returned unchanged.
+pub fn decompress(data: &[u8]) -> RedisResult<Vec<u8>> {
+    if !is_compressed(data) {
+        return Ok(data.to_vec());
+    }
+    let len = data[4..HEADER_LEN]
+        .iter()
+        .rev()
+        .fold(0usize, |len, &b| len << 8 | b as usize);
+    if len > i32::max_value() as usize {
+        return Err(decompress_error(format!("invalid length {}", len)));
+    }
+    let block = &data[HEADER_LEN..];
+    let rv = match data[3] {
+        #[cfg(feature = "with-lz4")]
+        1 => ::lz4::block::decompress(block, Some(len as i32)),
+        #[cfg(feature = "with-zstd")]
+        2 => ::zstd::block::decompress(block, len),
+        id => {
+            return Err(decompress_error(format!(
+                "unsupported compression algorithm {}",
+                id
+            )))
+        }
+    };
+    match rv {
+        Ok(ref rv) if rv.len() != len => Err(decompress_error(format!(
+            "expected {} bytes, got {}",
+            len,
+            rv.len()
+        ))),
+        Ok(rv) => Ok(rv),
+        Err(err) => Err(decompress_error(err.to_string())),
+    }
+}
+
+// Decompresses all data in the value, including nested values.
+fn decompress_value(v: &Value) -> RedisResult<Value> {
+    Ok(match *v {
+        Value::Data(ref data) => Value::Data(decompress(data)?),
+        Value::Bulk(ref items) => Value::Bulk(decompress_values(items)?),
+        Value::Set(ref items) => Value::Set(decompress_values(items)?),
+        Value::Map(ref items) => {
+            let mut rv = Vec::with_capacity(items.len());
+            for &(ref k, ref v) in items {
+                rv.push((decompress_value(k)?, decompress_value(v)?));
+            }
+            Value::Map(rv)
+        }
+        ref other => other.clone(),
+    })
+}
+
+fn decompress_values(items: &[Value]) -> RedisResult<Vec<Value>> {
+    items.iter().map(decompress_value).collect()
+}
+
+/// A value whose arguments are compressed with the default `Compression`
+/// and whose replies are decompressed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Compressed<T>(pub T);
+
+impl<T: ToRedisArgs> ToRedisArgs for Compressed<T> {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        let compression = Compression::default();
+        for arg in self.0.to_redis_args() {
+            out.write_arg(&compression.compress(&arg));
+        }
+    }
+
+    fn is_single_arg(&self) -> bool {
+        self.0.is_single_arg()
+    }
+}
+
+impl<T: FromRedisValue> FromRedisValue for Compressed<T> {
+    fn from_redis_value(v: &Value) -> RedisResult<Compressed<T>> {
+        T::from_redis_value(&decompress_value(v)?).map(Compressed)
+    }
+}
@@ -1,15 +1,29 @@
-use std::io::{BufRead, BufReader, Write};
-use std::net::{self, TcpStream};
-use std::path::PathBuf;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::marker::PhantomData;
+use std::mem;
+use std::net::{self, SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
 use std::str::from_utf8;
-use std::time::Duration;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
 
+#[cfg(feature = "tls-native")]
+use native_tls::{Certificate, HandshakeError, Identity, TlsConnector, TlsStream};
+#[cfg(not(target_arch = "wasm32"))]
+use net2::TcpStreamExt;
 use url;
 
-use cmd::{cmd, pipe, Pipeline};
-use parser::Parser;
+use cmd::{cmd, pack_commands, pipe, Cmd, Pipeline};
+#[cfg(feature = "with-vectored-writes")]
+use cmd::{pack_commands_vectored, PackedSegment};
+use metrics::{command_name, CommandEvent, MetricsObserver};
+use parser::parse_value_with_buffer;
+use sentinel::{self, SentinelConfig, SentinelRole};
 use types::{
-    from_redis_value, ErrorKind, FromRedisValue, RedisError, RedisResult, ToRedisArgs, Value,
+    from_redis_value, random_u64, ErrorKind, FromRedisValue, PushInfo, PushKind, RedisError,
+    RedisResult, ToRedisArgs, Value,
 };
 
 #[cfg(all(
@@ -21,6 +35,7 @@ use std::os::unix::net::UnixStream;
 use unix_socket::UnixStream;
 
 static DEFAULT_PORT: u16 = 6379;
+static DEFAULT_SENTINEL_PORT: u16 = 26379;
 
 /// This function takes a redis URL string and parses it into a URL
 /// as used by rust-url.  This is necessary as the default parser does
@@ -28,7 +43,7 @@ static DEFAULT_PORT: u16 = 6379;
 pub fn parse_redis_url(input: &str) -> Result<url::Url, ()> {
     match url::Url::parse(input) {
         Ok(result) => match result.scheme() {
-            "redis" | "redis+unix" | "unix" => Ok(result),
+            "redis" | "rediss" | "redis+unix" | "unix" => Ok(result),
             _ => Err(()),
         },
         Err(_) => Err(()),
@@ -44,8 +59,21 @@ pub fn parse_redis_url(input: &str) -> Result<url::Url, ()> {
 pub enum ConnectionAddr {
     /// Format for this is `(host, port)`.
     Tcp(String, u16),
+    /// A TCP connection secured with TLS.  The certificate of the server is
+    /// verified against `host` and the trust store of the platform.  This
+    /// requires the `tls-native` feature.
+    TcpTls {
+        /// The host name to connect to and to verify the certificate for.
+        host: String,
+        /// The port to connect to.
+        port: u16,
+    },
     /// Format for this is the path to the unix socket.
     Unix(PathBuf),
+    /// A service managed by redis sentinel.  The sentinels are asked for
+    /// the node to connect to on every connection attempt, see the
+    /// `sentinel` module.
+    Sentinel(SentinelConfig),
 }
 
 impl ConnectionAddr {
@@ -57,11 +85,207 @@ impl ConnectionAddr {
     pub fn is_supported(&self) -> bool {
         match *self {
             ConnectionAddr::Tcp(_, _) => true,
+            ConnectionAddr::TcpTls { .. } => cfg!(feature = "tls-native"),
             #[cfg(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets"))]
             ConnectionAddr::Unix(_) => true,
             #[cfg(not(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets")))]
             ConnectionAddr::Unix(_) => false,
+            ConnectionAddr::Sentinel(ref config) => {
+                config.sentinels.iter().all(|addr| addr.is_supported())
+            }
+        }
+    }
+}
+
+/// Options that are applied to TCP sockets right after connecting, both
+/// for blocking and async connections.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use redis::IntoConnectionInfo;
+///
+/// let mut info = "redis://127.0.0.1/".into_connection_info().unwrap();
+/// info.tcp.nodelay = true;
+/// info.tcp.keepalive = Some(Duration::from_secs(60));
+/// let client = redis::Client::open(info).unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TcpOptions {
+    /// Disables Nagle's algorithm (`TCP_NODELAY`) so that small commands
+    /// are sent right away.
+    pub nodelay: bool,
+    /// Enables keepalive probes after the connection was idle for the given
+    /// duration.  This keeps long lived idle connections from silently
+    /// being dropped by NAT gateways and firewalls.
+    pub keepalive: Option<Duration>,
+    /// The size of the socket's send buffer (`SO_SNDBUF`).
+    pub send_buffer_size: Option<usize>,
+    /// The size of the socket's receive buffer (`SO_RCVBUF`).
+    pub recv_buffer_size: Option<usize>,
+}
+
+impl TcpOptions {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn apply(&self, tcp: &TcpStream) -> io::Result<()> {
+        if self.nodelay {
+            tcp.set_nodelay(true)?;
+        }
+        if self.keepalive.is_some() {
+            TcpStreamExt::set_keepalive(tcp, self.keepalive)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            tcp.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            tcp.set_recv_buffer_size(size)?;
+        }
+        Ok(())
+    }
+    // std has no sockets on wasm32, so no connection gets this far
+    #[cfg(target_arch = "wasm32")]
+    fn apply(&self, _: &TcpStream) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Limits on the size of the replies read from the server.  A reply beyond
+/// them fails with `ErrorKind::ReplyTooLarge` before its data is read, so a
+/// broken or malicious server cannot make the client allocate arbitrary
+/// amounts of memory by announcing an enormous length.  The connection is
+/// closed afterwards.
+///
+/// The defaults only limit strings to the 512 MiB that the server allows.
+///
+/// ```rust
+/// use redis::IntoConnectionInfo;
+///
+/// let mut info = "redis://127.0.0.1/".into_connection_info().unwrap();
+/// info.reply_limits.max_bulk_size = 16 * 1024 * 1024;
+/// info.reply_limits.max_reply_size = 64 * 1024 * 1024;
+/// let client = redis::Client::open(info).unwrap();
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReplyLimits {
+    /// The maximum length of a string in bytes.
+    pub max_bulk_size: usize,
+    /// The maximum number of elements of an array, set, map or push
+    /// message.  Maps count their pairs.
+    pub max_array_len: usize,
+    /// The maximum size of a whole reply in bytes, including nested
+    /// values.
+    pub max_reply_size: usize,
+}
+
+impl Default for ReplyLimits {
+    fn default() -> ReplyLimits {
+        ReplyLimits {
+            max_bulk_size: 512 * 1024 * 1024,
+            max_array_len: usize::max_value(),
+            max_reply_size: usize::max_value(),
+        }
+    }
+}
+
+/// Configures TLS connections made to `ConnectionAddr::TcpTls` addresses.
+///
+/// Server certificates are verified against the trust store of the
+/// platform and `root_certs`.  native-tls does not support verification
+/// callbacks, so trusting a private certificate authority through
+/// `root_certs` is the way to customize verification.
+#[derive(Clone, Default, PartialEq)]
+pub struct TlsOptions {
+    /// A PEM encoded client certificate, optionally followed by the
+    /// intermediate certificates, for servers that require mutual TLS.
+    pub client_cert: Option<Vec<u8>>,
+    /// The PEM encoded PKCS #8 private key of `client_cert`.
+    pub client_key: Option<Vec<u8>>,
+    /// Additional PEM encoded certificates that are trusted as roots.
+    pub root_certs: Vec<Vec<u8>>,
+    /// Accepts any server certificate, including expired and self-signed
+    /// ones, and does not check the host name.  This makes the connection
+    /// vulnerable to man-in-the-middle attacks and should only be used
+    /// for development.  Set by the `insecure=true` URL parameter.
+    pub danger_accept_invalid_certs: bool,
+    /// Accepts certificates that are not valid for the host name that was
+    /// connected to, which is useful when connecting by IP address.
+    pub danger_accept_invalid_hostnames: bool,
+}
+
+impl fmt::Debug for TlsOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsOptions")
+            .field("client_cert", &self.client_cert.as_ref().map(|_| "<pem>"))
+            .field(
+                "client_key",
+                &self.client_key.as_ref().map(|_| "<redacted>"),
+            )
+            .field("root_certs", &self.root_certs.len())
+            .field(
+                "danger_accept_invalid_certs",
+                &self.danger_accept_invalid_certs,
+            )
+            .field(
+                "danger_accept_invalid_hostnames",
+                &self.danger_accept_invalid_hostnames,
+            )
+            .finish()
+    }
+}
+
+impl TlsOptions {
+    /// Sets the client certificate and its private key from PEM encoded
+    /// files.
+    pub fn with_client_cert_files<P: AsRef<Path>, Q: AsRef<Path>>(
+        mut self,
+        cert: P,
+        key: Q,
+    ) -> RedisResult<TlsOptions> {
+        self.client_cert = Some(fs::read(cert)?);
+        self.client_key = Some(fs::read(key)?);
+        Ok(self)
+    }
+
+    /// Trusts the PEM encoded certificate in the given file as a root.
+    pub fn with_root_cert_file<P: AsRef<Path>>(mut self, cert: P) -> RedisResult<TlsOptions> {
+        self.root_certs.push(fs::read(cert)?);
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "tls-native")]
+pub fn tls_connector(options: &TlsOptions) -> RedisResult<TlsConnector> {
+    let mut builder = TlsConnector::builder();
+    match (&options.client_cert, &options.client_key) {
+        (&Some(ref cert), &Some(ref key)) => {
+            builder.identity(Identity::from_pkcs8(cert, key)?);
         }
+        (&None, &None) => {}
+        _ => fail!((
+            ErrorKind::InvalidClientConfig,
+            "A client certificate requires a private key and vice versa"
+        )),
+    }
+    for cert in &options.root_certs {
+        builder.add_root_certificate(Certificate::from_pem(cert)?);
+    }
+    builder.danger_accept_invalid_certs(options.danger_accept_invalid_certs);
+    builder.danger_accept_invalid_hostnames(options.danger_accept_invalid_hostnames);
+    Ok(builder.build()?)
+}
+
+/// The version of the redis protocol a connection speaks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProtocolVersion {
+    /// The protocol of all redis versions.
+    RESP2,
+    /// The protocol introduced with redis 6.  It is negotiated with `HELLO 3`
+    /// after connecting and adds, among others, out-of-band push messages.
+    RESP3,
+}
+
+impl Default for ProtocolVersion {
+    fn default() -> ProtocolVersion {
+        ProtocolVersion::RESP2
     }
 }
 
@@ -74,6 +298,81 @@ pub struct ConnectionInfo {
     pub db: i64,
     /// Optionally a password that should be used for connection.
     pub passwd: Option<String>,
+    /// Optionally a name that is set with `CLIENT SETNAME` after
+    /// connecting, which makes the connection easy to identify in the
+    /// output of `CLIENT LIST`.
+    pub client_name: Option<String>,
+    /// Reports the name and version of this library with `CLIENT SETINFO`
+    /// after connecting, which shows them in `CLIENT LIST` (redis 7.2).
+    /// Older servers ignore it.
+    pub set_lib_info: bool,
+    /// How long to wait for a TCP connection to be established.  `None`
+    /// waits for as long as the operating system allows.  Async
+    /// connections fail the connect with a `TimedOut` IO error once it
+    /// passed.
+    pub connect_timeout: Option<Duration>,
+    /// The read and write timeout set on blocking connections right after
+    /// connecting.  Async connections fail a request with a `TimedOut` IO
+    /// error if its reply does not arrive in time.  `None` blocks
+    /// indefinitely.
+    pub response_timeout: Option<Duration>,
+    /// Options for TCP sockets.  They are ignored for unix sockets.
+    pub tcp: TcpOptions,
+    /// Options for TLS connections.
+    pub tls: TlsOptions,
+    /// Further addresses that are tried if no connection to `addr` could
+    /// be established, for instance the replicas of a simple high
+    /// availability setup.  Host names are resolved again on every
+    /// connection attempt.
+    pub fallback_addrs: Vec<ConnectionAddr>,
+    /// Tries `addr` and `fallback_addrs` starting at a random one instead
+    /// of in order, which spreads connections over all servers.
+    pub shuffle_addrs: bool,
+    /// Tries the IP addresses a host name resolves to starting at a random
+    /// one instead of in the order returned by the resolver.
+    pub shuffle_resolved_addrs: bool,
+    /// The protocol to request from the server.  Servers that do not
+    /// support RESP3 are spoken to with RESP2, see `Connection::protocol`.
+    pub protocol: ProtocolVersion,
+    /// Limits on the size of replies, see `ReplyLimits`.
+    pub reply_limits: ReplyLimits,
+}
+
+impl ConnectionInfo {
+    /// Returns the addresses to connect to in the order they should be
+    /// tried.
+    pub fn addrs(&self) -> Vec<&ConnectionAddr> {
+        let mut addrs = vec![&*self.addr];
+        addrs.extend(self.fallback_addrs.iter());
+        if self.shuffle_addrs {
+            let len = addrs.len();
+            addrs.rotate_left((random_u64() % len as u64) as usize);
+        }
+        addrs
+    }
+}
+
+impl Default for ConnectionInfo {
+    /// Connects to the default port on localhost without a password and
+    /// uses database `0`.
+    fn default() -> ConnectionInfo {
+        ConnectionInfo {
+            addr: Box::new(ConnectionAddr::Tcp("127.0.0.1".to_string(), DEFAULT_PORT)),
+            db: 0,
+            passwd: None,
+            connect_timeout: None,
+            response_timeout: None,
+            tcp: TcpOptions::default(),
+            fallback_addrs: vec![],
+            shuffle_addrs: false,
+            shuffle_resolved_addrs: false,
+            tls: TlsOptions::default(),
+            client_name: None,
+            protocol: ProtocolVersion::default(),
+            reply_limits: ReplyLimits::default(),
+            set_lib_info: true,
+        }
+    }
 }
 
 /// Converts an object into a connection info struct.  This allows the
@@ -91,6 +390,9 @@ impl IntoConnectionInfo for ConnectionInfo {
 
 impl<'a> IntoConnectionInfo for &'a str {
     fn into_connection_info(self) -> RedisResult<ConnectionInfo> {
+        if self.starts_with("redis+sentinel://") || self.starts_with("rediss+sentinel://") {
+            return url_to_sentinel_connection_info(self);
+        }
         match parse_redis_url(self) {
             Ok(u) => u.into_connection_info(),
             Err(_) => fail!((ErrorKind::InvalidClientConfig, "Redis URL did not parse")),
@@ -98,32 +400,165 @@ impl<'a> IntoConnectionInfo for &'a str {
     }
 }
 
+impl IntoConnectionInfo for String {
+    fn into_connection_info(self) -> RedisResult<ConnectionInfo> {
+        (&self[..]).into_connection_info()
+    }
+}
+
+fn decode_password(pw: &str) -> RedisResult<String> {
+    match url::percent_encoding::percent_decode(pw.as_bytes()).decode_utf8() {
+        Ok(decoded) => Ok(decoded.into_owned()),
+        Err(_) => fail!((
+            ErrorKind::InvalidClientConfig,
+            "Password is not valid UTF-8 string"
+        )),
+    }
+}
+
+fn get_query_param(url: &url::Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .filter(|&(ref key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+        .next()
+}
+
+// Parses a timeout in seconds, optionally suffixed with `s` or `ms`.
+fn get_timeout_param(url: &url::Url, name: &str) -> RedisResult<Option<Duration>> {
+    let value = unwrap_or!(get_query_param(url, name), return Ok(None));
+    let secs = if value.ends_with("ms") {
+        value[..value.len() - 2]
+            .parse::<f64>()
+            .map(|ms| ms / 1000.0)
+    } else if value.ends_with('s') {
+        value[..value.len() - 1].parse::<f64>()
+    } else {
+        value.parse::<f64>()
+    };
+    match secs {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(Some(Duration::new(
+            secs.trunc() as u64,
+            (secs.fract() * 1e9) as u32,
+        ))),
+        _ => fail!((
+            ErrorKind::InvalidClientConfig,
+            "Invalid timeout",
+            format!("{}={}", name, value)
+        )),
+    }
+}
+
+fn parse_db(value: &str) -> RedisResult<i64> {
+    match value.parse::<i64>() {
+        Ok(db) => Ok(db),
+        Err(_) => fail!((ErrorKind::InvalidClientConfig, "Invalid database number")),
+    }
+}
+
+fn get_protocol_param(url: &url::Url) -> RedisResult<ProtocolVersion> {
+    match get_query_param(url, "protocol") {
+        None => Ok(ProtocolVersion::RESP2),
+        Some(ref value) if value == "resp2" || value == "2" => Ok(ProtocolVersion::RESP2),
+        Some(ref value) if value == "resp3" || value == "3" => Ok(ProtocolVersion::RESP3),
+        Some(value) => fail!((
+            ErrorKind::InvalidClientConfig,
+            "Unsupported protocol",
+            value
+        )),
+    }
+}
+
+// Parses `host:port` or `[ipv6]:port`; the port is optional.
+fn parse_host_port(input: &str, default_port: u16, tls: bool) -> RedisResult<ConnectionAddr> {
+    let (host, port) = match input.rfind(':') {
+        Some(idx) if !input[idx..].contains(']') => (
+            &input[..idx],
+            unwrap_or!(
+                input[idx + 1..].parse::<u16>().ok(),
+                fail!((
+                    ErrorKind::InvalidClientConfig,
+                    "Invalid port",
+                    input.to_string()
+                ))
+            ),
+        ),
+        _ => (input, default_port),
+    };
+    let host = host.trim_left_matches('[').trim_right_matches(']');
+    if host.is_empty() {
+        fail!((ErrorKind::InvalidClientConfig, "Missing hostname"));
+    }
+    Ok(tcp_addr(host.to_string(), port, tls))
+}
+
+pub fn tcp_addr(host: String, port: u16, tls: bool) -> ConnectionAddr {
+    if tls {
+        ConnectionAddr::TcpTls {
+            host: host,
+            port: port,
+        }
+    } else {
+        ConnectionAddr::Tcp(host, port)
+    }
+}
+
+fn get_bool_param(url: &url::Url, name: &str) -> RedisResult<bool> {
+    get_bool_param_or(url, name, false)
+}
+
+fn get_bool_param_or(url: &url::Url, name: &str, default: bool) -> RedisResult<bool> {
+    match get_query_param(url, name) {
+        None => Ok(default),
+        Some(ref value) if value == "true" || value == "1" => Ok(true),
+        Some(ref value) if value == "false" || value == "0" => Ok(false),
+        Some(value) => fail!((
+            ErrorKind::InvalidClientConfig,
+            "Invalid boolean",
+            format!("{}={}", name, value)
+        )),
+    }
+}
+
 fn url_to_tcp_connection_info(url: url::Url) -> RedisResult<ConnectionInfo> {
+    let tls = url.scheme() == "rediss";
     Ok(ConnectionInfo {
-        addr: Box::new(ConnectionAddr::Tcp(
+        addr: Box::new(tcp_addr(
             match url.host() {
                 Some(host) => host.to_string(),
                 None => fail!((ErrorKind::InvalidClientConfig, "Missing hostname")),
             },
             url.port().unwrap_or(DEFAULT_PORT),
+            tls,
         )),
         db: match url.path().trim_matches('/') {
-            "" => 0,
-            path => unwrap_or!(
-                path.parse::<i64>().ok(),
-                fail!((ErrorKind::InvalidClientConfig, "Invalid database number"))
-            ),
+            "" => match get_query_param(&url, "db") {
+                Some(db) => parse_db(&db)?,
+                None => 0,
+            },
+            path => parse_db(path)?,
         },
         passwd: match url.password() {
-            Some(pw) => match url::percent_encoding::percent_decode(pw.as_bytes()).decode_utf8() {
-                Ok(decoded) => Some(decoded.into_owned()),
-                Err(_) => fail!((
-                    ErrorKind::InvalidClientConfig,
-                    "Password is not valid UTF-8 string"
-                )),
-            },
+            Some(pw) => Some(decode_password(pw)?),
             None => None,
         },
+        client_name: get_query_param(&url, "client_name"),
+        set_lib_info: get_bool_param_or(&url, "set_lib_info", true)?,
+        connect_timeout: get_timeout_param(&url, "connect_timeout")?,
+        response_timeout: get_timeout_param(&url, "response_timeout")?,
+        tcp: TcpOptions::default(),
+        tls: TlsOptions {
+            danger_accept_invalid_certs: get_bool_param(&url, "insecure")?,
+            ..Default::default()
+        },
+        fallback_addrs: url
+            .query_pairs()
+            .filter(|&(ref key, _)| key == "fallback")
+            .map(|(_, value)| parse_host_port(&value, DEFAULT_PORT, tls))
+            .collect::<RedisResult<_>>()?,
+        shuffle_addrs: get_bool_param(&url, "shuffle_addrs")?,
+        shuffle_resolved_addrs: get_bool_param(&url, "shuffle_resolved_addrs")?,
+        protocol: get_protocol_param(&url)?,
+        reply_limits: ReplyLimits::default(),
     })
 }
 
@@ -134,22 +569,77 @@ fn url_to_unix_connection_info(url: url::Url) -> RedisResult<ConnectionInfo> {
             url.to_file_path().ok(),
             fail!((ErrorKind::InvalidClientConfig, "Missing path"))
         ))),
-        db: match url
-            .query_pairs()
-            .into_iter()
-            .filter(|&(ref key, _)| key == "db")
-            .next()
-        {
-            Some((_, db)) => unwrap_or!(
-                db.parse::<i64>().ok(),
-                fail!((ErrorKind::InvalidClientConfig, "Invalid database number"))
-            ),
+        db: match get_query_param(&url, "db") {
+            Some(db) => parse_db(&db)?,
             None => 0,
         },
-        passwd: url.password().and_then(|pw| Some(pw.to_string())),
+        passwd: match url.password() {
+            Some(pw) => Some(decode_password(pw)?),
+            None => get_query_param(&url, "password"),
+        },
+        client_name: get_query_param(&url, "client_name"),
+        set_lib_info: get_bool_param_or(&url, "set_lib_info", true)?,
+        connect_timeout: None,
+        response_timeout: get_timeout_param(&url, "response_timeout")?,
+        tcp: TcpOptions::default(),
+        tls: TlsOptions::default(),
+        fallback_addrs: vec![],
+        shuffle_addrs: false,
+        shuffle_resolved_addrs: false,
+        protocol: get_protocol_param(&url)?,
+        reply_limits: ReplyLimits::default(),
     })
 }
 
+// Parses `redis+sentinel://[:<passwd>@]<host>[:port][,<host>[:port]...]/<service>[/<db>]`.
+// The list of hosts is not a valid URL authority, so it is split off before
+// the rest is parsed like a regular redis URL.
+fn url_to_sentinel_connection_info(input: &str) -> RedisResult<ConnectionInfo> {
+    let tls = input.starts_with("rediss+");
+    let rest = &input[input.find("://").unwrap() + 3..];
+    let (authority, rest) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let (path, query) = match rest.find('?') {
+        Some(idx) => (&rest[..idx], &rest[idx..]),
+        None => (rest, ""),
+    };
+    let (userinfo, hosts) = match authority.rfind('@') {
+        Some(idx) => (&authority[..idx + 1], &authority[idx + 1..]),
+        None => ("", authority),
+    };
+    let url = unwrap_or!(
+        url::Url::parse(&format!("redis://{}sentinel/{}", userinfo, query)).ok(),
+        fail!((ErrorKind::InvalidClientConfig, "Redis URL did not parse"))
+    );
+    let mut info = url_to_tcp_connection_info(url.clone())?;
+
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+    let service_name = unwrap_or!(
+        segments.next(),
+        fail!((ErrorKind::InvalidClientConfig, "Missing service name"))
+    );
+    if let Some(db) = segments.next() {
+        info.db = parse_db(db)?;
+    }
+    let role = match get_query_param(&url, "role") {
+        None => SentinelRole::Master,
+        Some(ref role) if role == "master" => SentinelRole::Master,
+        Some(ref role) if role == "replica" || role == "slave" => SentinelRole::Replica,
+        Some(ref role) if role == "any" => SentinelRole::Any,
+        Some(role) => fail!((ErrorKind::InvalidClientConfig, "Invalid role", role)),
+    };
+    info.addr = Box::new(ConnectionAddr::Sentinel(SentinelConfig {
+        sentinels: hosts
+            .split(',')
+            .map(|host| parse_host_port(host, DEFAULT_SENTINEL_PORT, tls))
+            .collect::<RedisResult<_>>()?,
+        service_name: service_name.to_string(),
+        role: role,
+        passwd: get_query_param(&url, "sentinel_password"),
+    }));
+    info.fallback_addrs = vec![];
+    Ok(info)
+}
+
 #[cfg(not(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets")))]
 fn url_to_unix_connection_info(_: url::Url) -> RedisResult<ConnectionInfo> {
     fail!((
@@ -160,7 +650,7 @@ fn url_to_unix_connection_info(_: url::Url) -> RedisResult<ConnectionInfo> {
 
 impl IntoConnectionInfo for url::Url {
     fn into_connection_info(self) -> RedisResult<ConnectionInfo> {
-        if self.scheme() == "redis" {
+        if self.scheme() == "redis" || self.scheme() == "rediss" {
             url_to_tcp_connection_info(self)
         } else if self.scheme() == "unix" || self.scheme() == "redis+unix" {
             url_to_unix_connection_info(self)
@@ -173,11 +663,104 @@ impl IntoConnectionInfo for url::Url {
     }
 }
 
+/// A byte stream that a `Connection` can run over instead of a TCP or unix
+/// socket, for instance an in-memory pipe in tests, a tunnel or a TLS
+/// stream of a library this crate does not support directly.  Create a
+/// connection over it with `Connection::from_transport`.
+///
+/// Only reading and writing are required.  Transports that do not support
+/// timeouts fail when one is set, which makes `response_timeout` and
+/// `Connection::with_timeout` unusable with them.
+pub trait Transport: Read + Write + Send {
+    /// Sets the read timeout of the transport.
+    fn set_read_timeout(&self, _dur: Option<Duration>) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "timeouts are not supported by this transport",
+        ))
+    }
+
+    /// Sets the write timeout of the transport.
+    fn set_write_timeout(&self, _dur: Option<Duration>) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "timeouts are not supported by this transport",
+        ))
+    }
+
+    /// Returns the read timeout of the transport.
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    /// Returns the write timeout of the transport.
+    fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        Ok(None)
+    }
+
+    /// Closes the transport.  This is called after protocol errors and
+    /// timeouts which leave the connection in an unknown state.
+    fn shutdown_transport(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Transport for TcpStream {
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_write_timeout(self, dur)
+    }
+
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        TcpStream::read_timeout(self)
+    }
+
+    fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        TcpStream::write_timeout(self)
+    }
+
+    fn shutdown_transport(&mut self) -> io::Result<()> {
+        TcpStream::shutdown(self, net::Shutdown::Both)
+    }
+}
+
+#[cfg(feature = "tls-native")]
+impl Transport for TlsStream<TcpStream> {
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.get_ref().set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.get_ref().set_write_timeout(dur)
+    }
+
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.get_ref().read_timeout()
+    }
+
+    fn write_timeout(&self) -> io::Result<Option<Duration>> {
+        self.get_ref().write_timeout()
+    }
+
+    fn shutdown_transport(&mut self) -> io::Result<()> {
+        let _ = TlsStream::shutdown(self);
+        self.get_ref().shutdown(net::Shutdown::Both)
+    }
+}
+
 struct TcpConnection {
     reader: BufReader<TcpStream>,
     open: bool,
 }
 
+struct CustomConnection {
+    reader: BufReader<Box<Transport>>,
+    open: bool,
+}
+
 #[cfg(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets"))]
 struct UnixConnection {
     sock: BufReader<UnixStream>,
@@ -188,6 +771,7 @@ enum ActualConnection {
     Tcp(TcpConnection),
     #[cfg(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets"))]
     Unix(UnixConnection),
+    Custom(CustomConnection),
 }
 
 /// Represents a stateful redis TCP connection.
@@ -195,11 +779,61 @@ pub struct Connection {
     con: ActualConnection,
     db: i64,
 
+    /// The password to authenticate with again after `recover`.
+    passwd: Option<String>,
+
     /// Flag indicating whether the connection was left in the PubSub state after dropping `PubSub`.
     ///
     /// This flag is checked when attempting to send a command, and if it's raised, we attempt to
     /// exit the pubsub state before executing the new request.
     pubsub: bool,
+
+    /// The point in time at which the current `with_timeout` call expires.
+    deadline: Option<Instant>,
+
+    /// The protocol negotiated with the server.
+    protocol: ProtocolVersion,
+
+    /// Where RESP3 push messages are sent, they are dropped without one.
+    push_sender: Option<mpsc::Sender<PushInfo>>,
+
+    /// Reused to encode commands and to assemble replies that do not fit
+    /// into the buffer of the reader, see `reuse_buffer`.
+    write_buffer: Vec<u8>,
+    read_buffer: Vec<u8>,
+
+    reply_limits: ReplyLimits,
+
+    /// Told about the commands of the connection, see
+    /// `set_metrics_observer`.
+    metrics: Option<Arc<MetricsObserver>>,
+
+    /// The number of bytes read from the server so far.
+    bytes_received: usize,
+}
+
+// Buffers that grew beyond this are freed after use so that a single large
+// command or reply does not pin the memory for the lifetime of the
+// connection.
+const BUFFER_SHRINK_THRESHOLD: usize = 64 * 1024;
+
+// Empties a buffer for the next use and frees it if it grew too large.
+fn reuse_buffer(buffer: &mut Vec<u8>) {
+    buffer.clear();
+    if buffer.capacity() > BUFFER_SHRINK_THRESHOLD {
+        buffer.shrink_to_fit();
+    }
+}
+
+/// An iterator over the elements of an array reply that parses them as
+/// they are read, see `Cmd::query_iter`.
+///
+/// The elements that were not consumed are read and discarded when the
+/// iterator is dropped, so that the connection can be used again.
+pub struct ReplyIter<'a, T> {
+    con: &'a mut Connection,
+    remaining: usize,
+    phantom: PhantomData<T>,
 }
 
 /// Represents a pubsub connection.
@@ -212,14 +846,69 @@ pub struct Msg {
     payload: Value,
     channel: Value,
     pattern: Option<Value>,
+    kind: PushKind,
+}
+
+// Counts the bytes read through a reader.
+struct CountingReader<'a> {
+    inner: &'a mut BufRead,
+    count: &'a mut usize,
+}
+
+impl<'a> Read for CountingReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        *self.count = self.count.wrapping_add(read);
+        Ok(read)
+    }
+}
+
+impl<'a> BufRead for CountingReader<'a> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        *self.count = self.count.wrapping_add(amt);
+        self.inner.consume(amt)
+    }
 }
 
 impl ActualConnection {
-    pub fn new(addr: &ConnectionAddr) -> RedisResult<ActualConnection> {
+    pub fn new(
+        addr: &ConnectionAddr,
+        connection_info: &ConnectionInfo,
+    ) -> RedisResult<ActualConnection> {
         Ok(match *addr {
+            #[cfg(feature = "tls-native")]
+            ConnectionAddr::TcpTls { ref host, port } => {
+                let tcp = connect_tcp(host, port, connection_info)?;
+                connection_info.tcp.apply(&tcp)?;
+                let tls = match tls_connector(&connection_info.tls)?.connect(host, tcp) {
+                    Ok(tls) => tls,
+                    Err(HandshakeError::Failure(err)) => return Err(err.into()),
+                    Err(HandshakeError::WouldBlock(_)) => {
+                        return Err(io::Error::from(io::ErrorKind::WouldBlock).into())
+                    }
+                };
+                let transport: Box<Transport> = Box::new(tls);
+                ActualConnection::Custom(CustomConnection {
+                    reader: BufReader::new(transport),
+                    open: true,
+                })
+            }
+            #[cfg(not(feature = "tls-native"))]
+            ConnectionAddr::TcpTls { .. } => {
+                fail!((
+                    ErrorKind::InvalidClientConfig,
+                    "Cannot connect to TLS servers \
+                     without the tls-native feature"
+                ));
+            }
             ConnectionAddr::Tcp(ref host, ref port) => {
                 let host: &str = &*host;
-                let tcp = TcpStream::connect((host, *port))?;
+                let tcp = connect_tcp(host, *port, connection_info)?;
+                connection_info.tcp.apply(&tcp)?;
                 let buffered = BufReader::new(tcp);
                 ActualConnection::Tcp(TcpConnection {
                     reader: buffered,
@@ -239,6 +928,12 @@ impl ActualConnection {
                      on this platform"
                 ));
             }
+            ConnectionAddr::Sentinel(_) => {
+                fail!((
+                    ErrorKind::InvalidClientConfig,
+                    "Sentinel addresses have to be resolved first"
+                ));
+            }
         })
     }
 
@@ -277,34 +972,139 @@ impl ActualConnection {
                     Ok(_) => Ok(Value::Okay),
                 }
             }
+            ActualConnection::Custom(ref mut connection) => {
+                let result = connection
+                    .reader
+                    .get_mut()
+                    .write_all(bytes)
+                    .map_err(|e| RedisError::from(e));
+                match result {
+                    Err(e) => {
+                        if e.is_connection_dropped() {
+                            connection.open = false;
+                        }
+                        Err(e)
+                    }
+                    Ok(_) => Ok(Value::Okay),
+                }
+            }
         }
     }
 
-    pub fn read_response(&mut self) -> RedisResult<Value> {
-        let result = Parser::new(match *self {
-            ActualConnection::Tcp(TcpConnection { ref mut reader, .. }) => reader as &mut BufRead,
+    #[cfg(feature = "with-vectored-writes")]
+    pub fn send_vectored(&mut self, slices: &[&[u8]]) -> RedisResult<()> {
+        let (result, open) = match *self {
+            ActualConnection::Tcp(ref mut connection) => (
+                write_all_vectored(connection.reader.get_mut(), slices),
+                &mut connection.open,
+            ),
             #[cfg(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets"))]
-            ActualConnection::Unix(UnixConnection { ref mut sock, .. }) => sock as &mut BufRead,
-        })
-        .parse_value();
-        // shutdown connection on protocol error
-        match result {
-            Err(ref e) if e.kind() == ErrorKind::ResponseError => match *self {
-                ActualConnection::Tcp(ref mut connection) => {
-                    let _ = connection.reader.get_mut().shutdown(net::Shutdown::Both);
-                    connection.open = false;
-                }
-                #[cfg(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets"))]
-                ActualConnection::Unix(ref mut connection) => {
-                    let _ = connection.sock.get_mut().shutdown(net::Shutdown::Both);
-                    connection.open = false;
+            ActualConnection::Unix(ref mut connection) => (
+                write_all_vectored(connection.sock.get_mut(), slices),
+                &mut connection.open,
+            ),
+            ActualConnection::Custom(ref mut connection) => {
+                let transport = connection.reader.get_mut();
+                (
+                    write_all_vectored(transport, slices).and_then(|()| transport.flush()),
+                    &mut connection.open,
+                )
+            }
+        };
+        match result.map_err(RedisError::from) {
+            Err(e) => {
+                if e.is_connection_dropped() {
+                    *open = false;
                 }
+                Err(e)
+            }
+            Ok(()) => Ok(()),
+        }
+    }
+
+    pub fn reader(&mut self) -> &mut BufRead {
+        match *self {
+            ActualConnection::Tcp(TcpConnection { ref mut reader, .. }) => reader,
+            #[cfg(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets"))]
+            ActualConnection::Unix(UnixConnection { ref mut sock, .. }) => sock,
+            ActualConnection::Custom(CustomConnection { ref mut reader, .. }) => reader,
+        }
+    }
+
+    pub fn read_response(
+        &mut self,
+        buffer: &mut Vec<u8>,
+        limits: ReplyLimits,
+        received: &mut usize,
+    ) -> RedisResult<Value> {
+        let result = parse_value_with_buffer(
+            CountingReader {
+                inner: self.reader(),
+                count: received,
             },
+            buffer,
+            limits,
+        );
+        // shutdown connection on protocol error
+        match result {
+            Err(ref e)
+                if e.kind() == ErrorKind::ResponseError || e.kind() == ErrorKind::ReplyTooLarge =>
+            {
+                self.close()
+            }
             _ => (),
         }
         result
     }
 
+    pub fn close(&mut self) {
+        match *self {
+            ActualConnection::Tcp(ref mut connection) => {
+                let _ = connection.reader.get_mut().shutdown(net::Shutdown::Both);
+                connection.open = false;
+            }
+            #[cfg(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets"))]
+            ActualConnection::Unix(ref mut connection) => {
+                let _ = connection.sock.get_mut().shutdown(net::Shutdown::Both);
+                connection.open = false;
+            }
+            ActualConnection::Custom(ref mut connection) => {
+                let _ = connection.reader.get_mut().shutdown_transport();
+                connection.open = false;
+            }
+        }
+    }
+
+    pub fn write_timeout(&self) -> RedisResult<Option<Duration>> {
+        Ok(match *self {
+            ActualConnection::Tcp(TcpConnection { ref reader, .. }) => {
+                reader.get_ref().write_timeout()?
+            }
+            #[cfg(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets"))]
+            ActualConnection::Unix(UnixConnection { ref sock, .. }) => {
+                sock.get_ref().write_timeout()?
+            }
+            ActualConnection::Custom(CustomConnection { ref reader, .. }) => {
+                reader.get_ref().write_timeout()?
+            }
+        })
+    }
+
+    pub fn read_timeout(&self) -> RedisResult<Option<Duration>> {
+        Ok(match *self {
+            ActualConnection::Tcp(TcpConnection { ref reader, .. }) => {
+                reader.get_ref().read_timeout()?
+            }
+            #[cfg(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets"))]
+            ActualConnection::Unix(UnixConnection { ref sock, .. }) => {
+                sock.get_ref().read_timeout()?
+            }
+            ActualConnection::Custom(CustomConnection { ref reader, .. }) => {
+                reader.get_ref().read_timeout()?
+            }
+        })
+    }
+
     pub fn set_write_timeout(&self, dur: Option<Duration>) -> RedisResult<()> {
         match *self {
             ActualConnection::Tcp(TcpConnection { ref reader, .. }) => {
@@ -314,6 +1114,9 @@ impl ActualConnection {
             ActualConnection::Unix(UnixConnection { ref sock, .. }) => {
                 sock.get_ref().set_write_timeout(dur)?;
             }
+            ActualConnection::Custom(CustomConnection { ref reader, .. }) => {
+                reader.get_ref().set_write_timeout(dur)?;
+            }
         }
         Ok(())
     }
@@ -327,25 +1130,220 @@ impl ActualConnection {
             ActualConnection::Unix(UnixConnection { ref sock, .. }) => {
                 sock.get_ref().set_read_timeout(dur)?;
             }
+            ActualConnection::Custom(CustomConnection { ref reader, .. }) => {
+                reader.get_ref().set_read_timeout(dur)?;
+            }
         }
         Ok(())
     }
 
+    // Returns true if data can be read without blocking.  End of file
+    // counts as data so that reading reports the closed connection.
+    // Custom transports cannot be polled, only their buffer is checked.
+    pub fn has_pending_data(&mut self) -> RedisResult<bool> {
+        fn poll<R: Read>(
+            reader: &mut BufReader<R>,
+            set_nonblocking: &Fn(&R, bool) -> io::Result<()>,
+        ) -> io::Result<bool> {
+            if !reader.buffer().is_empty() {
+                return Ok(true);
+            }
+            set_nonblocking(reader.get_ref(), true)?;
+            let result = match reader.fill_buf() {
+                Ok(_) => Ok(true),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Ok(false),
+                Err(err) => Err(err),
+            };
+            set_nonblocking(reader.get_ref(), false)?;
+            result
+        }
+
+        Ok(match *self {
+            ActualConnection::Tcp(TcpConnection { ref mut reader, .. }) => {
+                poll(reader, &TcpStream::set_nonblocking)?
+            }
+            #[cfg(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets"))]
+            ActualConnection::Unix(UnixConnection { ref mut sock, .. }) => {
+                poll(sock, &UnixStream::set_nonblocking)?
+            }
+            ActualConnection::Custom(CustomConnection { ref reader, .. }) => {
+                !reader.buffer().is_empty()
+            }
+        })
+    }
+
     pub fn is_open(&self) -> bool {
         match *self {
             ActualConnection::Tcp(TcpConnection { open, .. }) => open,
             #[cfg(any(feature = "with-unix-sockets", feature = "with-system-unix-sockets"))]
             ActualConnection::Unix(UnixConnection { open, .. }) => open,
+            ActualConnection::Custom(CustomConnection { open, .. }) => open,
+        }
+    }
+}
+
+// Writes all slices, with as few system calls as the writer allows.
+#[cfg(feature = "with-vectored-writes")]
+fn write_all_vectored<W: Write + ?Sized>(writer: &mut W, slices: &[&[u8]]) -> io::Result<()> {
+    // the number of slices most systems accept in one call
+    const MAX_SLICES: usize = 1024;
+
+    let mut slices = slices;
+    // the part of the first slice that was written already
+    let mut written = 0;
+    while !slices.is_empty() {
+        let io_slices: Vec<io::IoSlice> = slices
+            .iter()
+            .take(MAX_SLICES)
+            .enumerate()
+            .map(|(idx, slice)| io::IoSlice::new(if idx == 0 { &slice[written..] } else { slice }))
+            .collect();
+        let mut n = match writer.write_vectored(&io_slices) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        while n > 0 {
+            let left = slices[0].len() - written;
+            if n >= left {
+                n -= left;
+                written = 0;
+                slices = &slices[1..];
+            } else {
+                written += n;
+                n = 0;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolves a host name into the IP addresses to try.  This is done anew
+/// for every connection so that reconnects pick up DNS changes.
+pub fn resolve(host: &str, port: u16, shuffle: bool) -> io::Result<Vec<SocketAddr>> {
+    let mut addrs: Vec<SocketAddr> = (host, port).to_socket_addrs()?.collect();
+    if shuffle && !addrs.is_empty() {
+        let len = addrs.len();
+        addrs.rotate_left((random_u64() % len as u64) as usize);
+    }
+    Ok(addrs)
+}
+
+// Like `TcpStream::connect` but honors the connect timeout and the address
+// order of the connection info.
+fn connect_tcp(host: &str, port: u16, info: &ConnectionInfo) -> RedisResult<TcpStream> {
+    let mut last_err = None;
+    for addr in resolve(host, port, info.shuffle_resolved_addrs)? {
+        let result = match info.connect_timeout {
+            Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+            None => TcpStream::connect(&addr),
+        };
+        match result {
+            Ok(tcp) => return Ok(tcp),
+            Err(err) => last_err = Some(err),
         }
     }
+    Err(RedisError::from(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any addresses",
+        )
+    })))
+}
+
+// Connects a blocking socket with the TCP options set, for the async
+// connections of runtimes that cannot set them on their own sockets.
+#[cfg(all(
+    feature = "async-std-comp",
+    not(feature = "tokio-comp"),
+    not(target_arch = "wasm32")
+))]
+pub fn connect_socket(addr: &SocketAddr, options: &TcpOptions) -> io::Result<TcpStream> {
+    let tcp = TcpStream::connect(addr)?;
+    options.apply(&tcp)?;
+    Ok(tcp)
+}
+
+pub fn connect(connection_info: &ConnectionInfo) -> RedisResult<Connection> {
+    let mut result = Err(RedisError::from((
+        ErrorKind::InvalidClientConfig,
+        "No address to connect to",
+    )));
+    for addr in connection_info.addrs() {
+        result = connect_addr(addr, connection_info);
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+pub fn connect_addr(
+    addr: &ConnectionAddr,
+    connection_info: &ConnectionInfo,
+) -> RedisResult<Connection> {
+    match *addr {
+        ConnectionAddr::Sentinel(ref config) => sentinel::connect(config, connection_info),
+        _ => setup_connection(
+            ActualConnection::new(addr, connection_info)?,
+            connection_info,
+        ),
+    }
+}
+
+// Returns whether the value is the reply to `PING <marker>`, in subscribed
+// state it is `["pong", <marker>]`.
+fn is_ping_reply(value: &Value, marker: &str) -> bool {
+    match *value {
+        Value::Data(ref data) => data == marker.as_bytes(),
+        Value::Bulk(ref items) => match items.get(1) {
+            Some(&Value::Data(ref data)) => items.len() == 2 && data == marker.as_bytes(),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+// Reports the name and version of the library, see
+// `ConnectionInfo::set_lib_info`.
+pub fn lib_info_pipeline() -> Pipeline {
+    let mut pipe = pipe();
+    pipe.cmd("CLIENT")
+        .arg("SETINFO")
+        .arg("LIB-NAME")
+        .arg("redis-rs")
+        .ignore()
+        .cmd("CLIENT")
+        .arg("SETINFO")
+        .arg("LIB-VER")
+        .arg(env!("CARGO_PKG_VERSION"))
+        .ignore();
+    pipe
 }
 
-pub fn connect(connection_info: &ConnectionInfo) -> RedisResult<Connection> {
-    let con = ActualConnection::new(&connection_info.addr)?;
+// Applies the timeouts and authenticates and selects the database.
+fn setup_connection(
+    con: ActualConnection,
+    connection_info: &ConnectionInfo,
+) -> RedisResult<Connection> {
+    if connection_info.response_timeout.is_some() {
+        con.set_read_timeout(connection_info.response_timeout)?;
+        con.set_write_timeout(connection_info.response_timeout)?;
+    }
     let mut rv = Connection {
         con: con,
         db: connection_info.db,
+        passwd: connection_info.passwd.clone(),
         pubsub: false,
+        deadline: None,
+        protocol: ProtocolVersion::RESP2,
+        push_sender: None,
+        write_buffer: Vec::new(),
+        read_buffer: Vec::new(),
+        reply_limits: connection_info.reply_limits,
+        metrics: None,
+        bytes_received: 0,
     };
 
     match connection_info.passwd {
@@ -374,6 +1372,38 @@ pub fn connect(connection_info: &ConnectionInfo) -> RedisResult<Connection> {
         }
     }
 
+    if let Some(ref name) = connection_info.client_name {
+        cmd("CLIENT")
+            .arg("SETNAME")
+            .arg(&**name)
+            .query::<()>(&mut rv)?;
+    }
+
+    if connection_info.set_lib_info {
+        rv.send_bytes(&lib_info_pipeline().get_packed_pipeline(false))?;
+        // servers before redis 7.2 do not know `CLIENT SETINFO`, both
+        // errors have to be read
+        for _ in 0..2 {
+            if let Err(err) = rv.read_response() {
+                if err.is_io_error() {
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    if connection_info.protocol == ProtocolVersion::RESP3 {
+        // servers before redis 6 do not know `HELLO` and keep speaking RESP2
+        match cmd("HELLO").arg(3).query::<Value>(&mut rv) {
+            Ok(_) => rv.protocol = ProtocolVersion::RESP3,
+            Err(err) => {
+                if err.is_io_error() {
+                    return Err(err);
+                }
+            }
+        }
+    }
+
     Ok(rv)
 }
 
@@ -392,6 +1422,13 @@ pub trait ConnectionLike {
     /// reads the single response from it.
     fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value>;
 
+    /// Sends a command and reads the single response.  Connections override
+    /// this to encode the command into a buffer they reuse, the default
+    /// packs it into a new one.
+    fn req_command(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        self.req_packed_command(&cmd.get_packed_command())
+    }
+
     /// Sends multiple already encoded (packed) command into the TCP socket
     /// and reads `count` responses from it.  This is used to implement
     /// pipelining.
@@ -402,6 +1439,49 @@ pub trait ConnectionLike {
         count: usize,
     ) -> RedisResult<Vec<Value>>;
 
+    /// Sends multiple commands and reads `offset + count` responses, of
+    /// which the last `count` are returned.  This is used by pipelines,
+    /// connections override it to avoid copying the commands into one
+    /// buffer.
+    fn req_commands(
+        &mut self,
+        cmds: &[&Cmd],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        self.req_packed_commands(&pack_commands(cmds), offset, count)
+    }
+
+    /// Sends multiple commands and returns the reply of each of them, where
+    /// error replies of the server are returned in place of their values
+    /// instead of failing the whole request.  Other errors, like I/O
+    /// errors, still fail it.  This is used by `Pipeline::query_each`.
+    ///
+    /// The default sends the commands one after the other, connections
+    /// override it to pipeline them.
+    fn req_commands_each(&mut self, cmds: &[&Cmd]) -> RedisResult<Vec<RedisResult<Value>>> {
+        let mut rv = Vec::with_capacity(cmds.len());
+        for cmd in cmds {
+            match self.req_command(cmd) {
+                Ok(value) => rv.push(Ok(value)),
+                Err(err) => {
+                    if !err.is_server_error() {
+                        return Err(err);
+                    }
+                    rv.push(Err(err));
+                }
+            }
+        }
+        Ok(rv)
+    }
+
+    /// Returns the address of the node that served the last request, as
+    /// `host:port`, for connections that talk to several nodes like cluster
+    /// connections.  The default returns `None`.
+    fn last_node(&self) -> Option<&str> {
+        None
+    }
+
     /// Returns the database this connection is bound to.  Note that this
     /// information might be unreliable because it's initially cached and
     /// also might be incorrect if the connection like object is not
@@ -422,14 +1502,145 @@ impl Connection {
     /// `MONITOR` which yield multiple items.  This needs to be used with
     /// care because it changes the state of the connection.
     pub fn send_packed_command(&mut self, cmd: &[u8]) -> RedisResult<()> {
-        self.con.send_bytes(cmd)?;
+        self.send_bytes(cmd)
+    }
+
+    /// Creates a connection over a custom transport.  The transport has to
+    /// be connected already; authentication and database selection are
+    /// done according to `connection_info` while its address and socket
+    /// options are ignored.
+    pub fn from_transport<T: Transport + 'static>(
+        transport: T,
+        connection_info: &ConnectionInfo,
+    ) -> RedisResult<Connection> {
+        let transport: Box<Transport> = Box::new(transport);
+        setup_connection(
+            ActualConnection::Custom(CustomConnection {
+                reader: BufReader::new(transport),
+                open: true,
+            }),
+            connection_info,
+        )
+    }
+
+    /// Returns the protocol the connection speaks.  This is RESP2 if RESP3
+    /// was requested but the server does not support it.
+    pub fn protocol(&self) -> ProtocolVersion {
+        self.protocol
+    }
+
+    /// Resets the state of the connection with `RESET` (redis 6.2), so a
+    /// connection that was left in a transaction, in subscribed state or
+    /// with tracking enabled after an error can be used again.
+    ///
+    /// Replies that are still outstanding are discarded.  Afterwards the
+    /// connection authenticates, selects its database and switches to
+    /// RESP3 again like after connecting.  Other state, like `WATCH`ed
+    /// keys or `CLIENT TRACKING`, is gone.  Servers before redis 6.2 fail
+    /// with a response error.
+    pub fn recover(&mut self) -> RedisResult<()> {
+        // the number of outstanding replies is unknown, so the reply of
+        // the `PING` marks the end
+        let marker = format!("recover-{}", random_u64());
+        let mut packed = cmd("RESET").get_packed_command();
+        packed.extend(cmd("PING").arg(&*marker).get_packed_command());
+        self.con.send_bytes(&packed)?;
+        let mut reset = false;
+        loop {
+            match self.read_value() {
+                Ok(Value::Status(ref status)) if status == "RESET" => reset = true,
+                Ok(ref value) if is_ping_reply(value, &marker) => break,
+                Ok(_) => {}
+                Err(err) => {
+                    if err.is_io_error() {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+        if !reset {
+            fail!((
+                ErrorKind::ResponseError,
+                "Redis server does not support RESET"
+            ));
+        }
+
+        self.pubsub = false;
+        let protocol = self.protocol;
+        self.protocol = ProtocolVersion::RESP2;
+        if let Some(passwd) = self.passwd.clone() {
+            cmd("AUTH").arg(passwd).query::<()>(self)?;
+        }
+        if self.db != 0 {
+            let db = self.db;
+            cmd("SELECT").arg(db).query::<()>(self)?;
+        }
+        if protocol == ProtocolVersion::RESP3 {
+            cmd("HELLO").arg(3).query::<Value>(self)?;
+            self.protocol = ProtocolVersion::RESP3;
+        }
+        Ok(())
+    }
+
+    /// Sets the channel that receives the push messages of a RESP3
+    /// connection, like the invalidations of client side caching.  Push
+    /// messages that arrive while a command waits for its reply are sent
+    /// there; without a sender they are dropped.
+    ///
+    /// ```rust,no_run
+    /// # fn do_something() -> redis::RedisResult<()> {
+    /// use std::sync::mpsc;
+    ///
+    /// let client = redis::Client::open("redis://127.0.0.1/?protocol=resp3")?;
+    /// let mut con = client.get_connection()?;
+    /// let (sender, receiver) = mpsc::channel();
+    /// con.set_push_sender(sender);
+    /// redis::cmd("CLIENT").arg("TRACKING").arg("ON").query::<()>(&mut con)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn set_push_sender(&mut self, sender: mpsc::Sender<PushInfo>) {
+        self.push_sender = Some(sender);
+    }
+
+    /// Reads the push messages that already arrived without waiting for
+    /// more and hands them to the push sender.  This must not be called
+    /// while replies are outstanding, they would be read as well.
+    ///
+    /// Push messages are otherwise only read while a command waits for
+    /// its reply.  Over custom transports only the messages that were
+    /// buffered already are read.
+    pub fn read_push_messages(&mut self) -> RedisResult<()> {
+        while self.con.has_pending_data()? {
+            match self.read_value()? {
+                Value::Push { kind, data } => self.send_push(kind, data),
+                _ => {
+                    self.con.close();
+                    fail!((
+                        ErrorKind::ResponseError,
+                        "Received a reply without a request"
+                    ));
+                }
+            }
+        }
         Ok(())
     }
 
+    // Hands a push message to the push sender.
+    fn send_push(&mut self, kind: PushKind, data: Vec<Value>) {
+        let disconnected = match self.push_sender {
+            Some(ref sender) => sender.send(PushInfo { kind, data }).is_err(),
+            None => false,
+        };
+        if disconnected {
+            self.push_sender = None;
+        }
+    }
+
     /// Fetches a single response from the connection.  This is useful
-    /// if used in combination with `send_packed_command`.
+    /// if used in combination with `send_packed_command`.  Push messages
+    /// are returned like responses.
     pub fn recv_response(&mut self) -> RedisResult<Value> {
-        self.con.read_response()
+        self.read_value()
     }
 
     /// Sets the write timeout for the connection.
@@ -450,6 +1661,192 @@ impl Connection {
         self.con.set_read_timeout(dur)
     }
 
+    /// Runs `func` with a deadline of `timeout` from now that covers
+    /// writing all commands and reading all responses.  This is what
+    /// `Cmd::query_timeout` and `Pipeline::query_timeout` use internally.
+    ///
+    /// If the deadline passes an error is returned for which `is_timeout`
+    /// is true and the connection is closed, since a partially written
+    /// command or a reply that arrives late would otherwise be mistaken
+    /// for the reply of the next command.  The read and write timeouts
+    /// that were configured before are restored afterwards.
+    ///
+    /// ```rust,no_run
+    /// # fn do_something() -> redis::RedisResult<()> {
+    /// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    /// # let mut con = client.get_connection().unwrap();
+    /// use std::time::Duration;
+    /// use redis::Commands;
+    ///
+    /// let value: Option<String> = con.with_timeout(Duration::from_millis(50), |con| {
+    ///     con.get("my_key")
+    /// })?;
+    /// # Ok(()) }
+    /// ```
+    pub fn with_timeout<T, F>(&mut self, timeout: Duration, func: F) -> RedisResult<T>
+    where
+        F: FnOnce(&mut Connection) -> RedisResult<T>,
+    {
+        let read_timeout = self.con.read_timeout()?;
+        let write_timeout = self.con.write_timeout()?;
+        let outer_deadline = self.deadline;
+        let deadline = Instant::now() + timeout;
+        self.deadline = Some(match outer_deadline {
+            Some(outer) if outer < deadline => outer,
+            _ => deadline,
+        });
+
+        let result = func(self);
+
+        self.deadline = outer_deadline;
+        match result {
+            Err(ref err) if err.is_timeout() => self.con.close(),
+            _ => {
+                self.con.set_read_timeout(read_timeout)?;
+                self.con.set_write_timeout(write_timeout)?;
+            }
+        }
+        result
+    }
+
+    // Returns the time left until the deadline, failing if it passed.
+    fn remaining(&self) -> RedisResult<Option<Duration>> {
+        match self.deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(RedisError::from(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "command timed out",
+                    )));
+                }
+                Ok(Some(deadline - now))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn send_bytes(&mut self, bytes: &[u8]) -> RedisResult<()> {
+        if let Some(remaining) = self.remaining()? {
+            self.con.set_write_timeout(Some(remaining))?;
+        }
+        self.con.send_bytes(bytes)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "with-vectored-writes")]
+    fn send_vectored(&mut self, slices: &[&[u8]]) -> RedisResult<()> {
+        if let Some(remaining) = self.remaining()? {
+            self.con.set_write_timeout(Some(remaining))?;
+        }
+        self.con.send_vectored(slices)
+    }
+
+    /// Sends a command whose reply is an array and returns an iterator
+    /// that parses its elements one by one, so that huge replies do not
+    /// have to be held in memory at once.  `Cmd::query_iter` is the
+    /// convenient way to use this.
+    ///
+    /// A nil reply yields no elements; maps yield their keys and values
+    /// alternately.  Replies of other types fail with a type error.
+    pub fn req_command_iter<T: FromRedisValue>(&mut self, cmd: &Cmd) -> RedisResult<ReplyIter<T>> {
+        if self.pubsub {
+            self.exit_pubsub()?;
+        }
+        let mut buffer = mem::replace(&mut self.write_buffer, Vec::new());
+        cmd.write_packed_command(&mut buffer);
+        let result = self.send_bytes(&buffer);
+        reuse_buffer(&mut buffer);
+        self.write_buffer = buffer;
+        result?;
+        let remaining = self.read_aggregate_len()?;
+        Ok(ReplyIter {
+            con: self,
+            remaining,
+            phantom: PhantomData,
+        })
+    }
+
+    // Reads the header of an array, set or map reply and returns the
+    // number of values that follow.
+    fn read_aggregate_len(&mut self) -> RedisResult<usize> {
+        loop {
+            if let Some(remaining) = self.remaining()? {
+                self.con.set_read_timeout(Some(remaining))?;
+            }
+            let prefix = {
+                let buf = self.con.reader().fill_buf()?;
+                if buf.is_empty() {
+                    fail!((ErrorKind::ResponseError, "Could not read enough bytes"));
+                }
+                buf[0]
+            };
+            match prefix {
+                b'*' | b'~' | b'%' => {
+                    let mut line = Vec::new();
+                    self.con.reader().read_until(b'\n', &mut line)?;
+                    let len = from_utf8(&line[1..])
+                        .ok()
+                        .and_then(|line| line.trim().parse::<i64>().ok());
+                    return match len {
+                        Some(len) if prefix == b'%' => Ok(len.max(0) as usize * 2),
+                        Some(len) => Ok(len.max(0) as usize),
+                        None => {
+                            self.con.close();
+                            fail!((ErrorKind::ResponseError, "Expected integer, got garbage"))
+                        }
+                    };
+                }
+                // errors are returned by `read_value`
+                _ => match self.read_value()? {
+                    Value::Push { kind, data } => self.send_push(kind, data),
+                    Value::Nil => return Ok(0),
+                    _ => fail!((
+                        ErrorKind::TypeError,
+                        "Response was of incompatible type",
+                        "Response type not iterable".to_string()
+                    )),
+                },
+            }
+        }
+    }
+
+    // Reads `offset + count` replies and returns the last `count`.
+    fn read_responses(&mut self, offset: usize, count: usize) -> RedisResult<Vec<Value>> {
+        let mut rv = Vec::with_capacity(count);
+        for idx in 0..(offset + count) {
+            let item = self.read_response()?;
+            if idx >= offset {
+                rv.push(item);
+            }
+        }
+        Ok(rv)
+    }
+
+    fn read_value(&mut self) -> RedisResult<Value> {
+        if let Some(remaining) = self.remaining()? {
+            self.con.set_read_timeout(Some(remaining))?;
+        }
+        let result = self.con.read_response(
+            &mut self.read_buffer,
+            self.reply_limits,
+            &mut self.bytes_received,
+        );
+        reuse_buffer(&mut self.read_buffer);
+        result
+    }
+
+    // Reads the reply to a command, push messages in between are handed
+    // to the push sender.
+    fn read_response(&mut self) -> RedisResult<Value> {
+        loop {
+            match self.read_value()? {
+                Value::Push { kind, data } => self.send_push(kind, data),
+                value => return Ok(value),
+            }
+        }
+    }
+
     pub fn as_pubsub<'a>(&'a mut self) -> PubSub<'a> {
         // NOTE: The pubsub flag is intentionally not raised at this time since running commands
         // within the pubsub state should not try and exit from the pubsub state.
@@ -500,15 +1897,30 @@ impl Connection {
         let mut received_unsub = false;
         let mut received_punsub = false;
         loop {
-            let res: (Vec<u8>, (), isize) = from_redis_value(&self.recv_response()?)?;
+            let (kind, count): (Vec<u8>, isize) = match self.recv_response()? {
+                Value::Push { kind, data } => match kind {
+                    PushKind::Unsubscribe | PushKind::PUnsubscribe => (
+                        kind.name().as_bytes().to_vec(),
+                        from_redis_value(data.last().unwrap_or(&Value::Nil))?,
+                    ),
+                    _ => {
+                        self.send_push(kind, data);
+                        continue;
+                    }
+                },
+                value => {
+                    let res: (Vec<u8>, (), isize) = from_redis_value(&value)?;
+                    (res.0, res.2)
+                }
+            };
 
-            match res.0.first().map(|v| *v) {
+            match kind.first().map(|v| *v) {
                 Some(b'u') => received_unsub = true,
                 Some(b'p') => received_punsub = true,
                 _ => (),
             }
 
-            if received_unsub && received_punsub && res.2 == 0 {
+            if received_unsub && received_punsub && count == 0 {
                 break;
             }
         }
@@ -528,6 +1940,46 @@ impl Connection {
     pub fn is_open(&self) -> bool {
         self.con.is_open()
     }
+
+    /// Sets the observer that is told about the commands and pipelines
+    /// sent over the connection.  Connections of a client with an observer
+    /// get it from the client.
+    pub fn set_metrics_observer(&mut self, observer: Option<Arc<MetricsObserver>>) {
+        self.metrics = observer;
+    }
+
+    // Runs a request and tells the metrics observer about it.  `name` is
+    // only called if there is an observer.
+    fn observe<N, F, T>(
+        &mut self,
+        name: N,
+        commands: usize,
+        bytes_sent: usize,
+        func: F,
+    ) -> RedisResult<T>
+    where
+        N: FnOnce() -> String,
+        F: FnOnce(&mut Connection) -> RedisResult<T>,
+    {
+        let observer = match self.metrics {
+            Some(ref observer) => observer.clone(),
+            None => return func(self),
+        };
+        let name = name();
+        observer.command_issued(&name);
+        let received = self.bytes_received;
+        let start = Instant::now();
+        let result = func(self);
+        observer.command_completed(&CommandEvent {
+            name: &name,
+            commands,
+            latency: start.elapsed(),
+            bytes_sent,
+            bytes_received: self.bytes_received.wrapping_sub(received),
+            error: result.as_ref().err(),
+        });
+        result
+    }
 }
 
 impl ConnectionLike for Connection {
@@ -536,9 +1988,36 @@ impl ConnectionLike for Connection {
             self.exit_pubsub()?;
         }
 
-        let con = &mut self.con;
-        con.send_bytes(cmd)?;
-        con.read_response()
+        self.observe(
+            || command_name(cmd),
+            1,
+            cmd.len(),
+            |con| {
+                con.send_bytes(cmd)?;
+                con.read_response()
+            },
+        )
+    }
+
+    fn req_command(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        if self.pubsub {
+            self.exit_pubsub()?;
+        }
+
+        let mut buffer = mem::replace(&mut self.write_buffer, Vec::new());
+        cmd.write_packed_command(&mut buffer);
+        let result = self.observe(
+            || command_name(&buffer),
+            1,
+            buffer.len(),
+            |con| {
+                con.send_bytes(&buffer)?;
+                con.read_response()
+            },
+        );
+        reuse_buffer(&mut buffer);
+        self.write_buffer = buffer;
+        result
     }
 
     fn req_packed_commands(
@@ -550,16 +2029,83 @@ impl ConnectionLike for Connection {
         if self.pubsub {
             self.exit_pubsub()?;
         }
-        let con = &mut self.con;
-        con.send_bytes(cmd)?;
-        let mut rv = vec![];
-        for idx in 0..(offset + count) {
-            let item = con.read_response()?;
-            if idx >= offset {
-                rv.push(item);
-            }
+        self.observe(
+            || "PIPELINE".to_string(),
+            offset + count,
+            cmd.len(),
+            |con| {
+                con.send_bytes(cmd)?;
+                con.read_responses(offset, count)
+            },
+        )
+    }
+
+    fn req_commands_each(&mut self, cmds: &[&Cmd]) -> RedisResult<Vec<RedisResult<Value>>> {
+        if self.pubsub {
+            self.exit_pubsub()?;
         }
-        Ok(rv)
+        let packed = pack_commands(cmds);
+        self.observe(
+            || "PIPELINE".to_string(),
+            cmds.len(),
+            packed.len(),
+            |con| {
+                con.send_bytes(&packed)?;
+                // read every reply, even after errors, to keep the
+                // connection in sync
+                let mut rv = Vec::with_capacity(cmds.len());
+                for _ in cmds {
+                    match con.read_response() {
+                        Ok(value) => rv.push(Ok(value)),
+                        Err(err) => {
+                            if !err.is_server_error() {
+                                return Err(err);
+                            }
+                            rv.push(Err(err));
+                        }
+                    }
+                }
+                Ok(rv)
+            },
+        )
+    }
+
+    #[cfg(feature = "with-vectored-writes")]
+    fn req_commands(
+        &mut self,
+        cmds: &[&Cmd],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        if self.pubsub {
+            self.exit_pubsub()?;
+        }
+
+        let mut buffer = mem::replace(&mut self.write_buffer, Vec::new());
+        let result = {
+            let segments = pack_commands_vectored(cmds, &mut buffer);
+            let slices: Vec<&[u8]> = segments
+                .iter()
+                .map(|segment| match *segment {
+                    PackedSegment::Buffered(start, end) => &buffer[start..end],
+                    PackedSegment::Borrowed(data) => data,
+                })
+                .filter(|slice| !slice.is_empty())
+                .collect();
+            let bytes_sent = slices.iter().map(|slice| slice.len()).sum();
+            self.observe(
+                || "PIPELINE".to_string(),
+                offset + count,
+                bytes_sent,
+                |con| {
+                    con.send_vectored(&slices)?;
+                    con.read_responses(offset, count)
+                },
+            )
+        };
+        reuse_buffer(&mut buffer);
+        self.write_buffer = buffer;
+        result
     }
 
     fn get_db(&self) -> i64 {
@@ -567,6 +2113,44 @@ impl ConnectionLike for Connection {
     }
 }
 
+impl<'a, T: FromRedisValue> Iterator for ReplyIter<'a, T> {
+    type Item = RedisResult<T>;
+
+    fn next(&mut self) -> Option<RedisResult<T>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        match self.con.read_value() {
+            Ok(value) => Some(from_redis_value(&value)),
+            Err(err) => {
+                // error replies of single elements are fine, after others the rest
+                // of the reply is lost
+                if err.kind() == ErrorKind::IoError || !self.con.is_open() {
+                    self.remaining = 0;
+                }
+                Some(Err(err))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> Drop for ReplyIter<'a, T> {
+    fn drop(&mut self) {
+        while self.remaining > 0 {
+            self.remaining -= 1;
+            match self.con.read_value() {
+                Err(ref err) if err.kind() == ErrorKind::IoError || !self.con.is_open() => break,
+                _ => {}
+            }
+        }
+    }
+}
+
 /// The pubsub object provides convenient access to the redis pubsub
 /// system.  Once created you can subscribe and unsubscribe from channels
 /// and listen in on messages.
@@ -593,28 +2177,56 @@ impl<'a> PubSub<'a> {
         Self { con }
     }
 
+    // Sends a (un)subscribe command and reads its first confirmation.
+    // With RESP3 the confirmations are push messages, other push messages
+    // that arrive first go to the push sender.
+    fn send_subscription(&mut self, cmd: &Cmd) -> RedisResult<()> {
+        self.con.send_packed_command(&cmd.get_packed_command())?;
+        loop {
+            match self.con.recv_response()? {
+                Value::Push { kind, data } => match kind {
+                    PushKind::Subscribe
+                    | PushKind::PSubscribe
+                    | PushKind::SSubscribe
+                    | PushKind::Unsubscribe
+                    | PushKind::PUnsubscribe
+                    | PushKind::SUnsubscribe => return Ok(()),
+                    _ => self.con.send_push(kind, data),
+                },
+                _ => return Ok(()),
+            }
+        }
+    }
+
     /// Subscribes to a new channel.
     pub fn subscribe<T: ToRedisArgs>(&mut self, channel: T) -> RedisResult<()> {
-        let _: () = cmd("SUBSCRIBE").arg(channel).query(self.con)?;
-        Ok(())
+        self.send_subscription(cmd("SUBSCRIBE").arg(channel))
     }
 
     /// Subscribes to a new channel with a pattern.
     pub fn psubscribe<T: ToRedisArgs>(&mut self, pchannel: T) -> RedisResult<()> {
-        let _: () = cmd("PSUBSCRIBE").arg(pchannel).query(self.con)?;
-        Ok(())
+        self.send_subscription(cmd("PSUBSCRIBE").arg(pchannel))
     }
 
     /// Unsubscribes from a channel.
     pub fn unsubscribe<T: ToRedisArgs>(&mut self, channel: T) -> RedisResult<()> {
-        let _: () = cmd("UNSUBSCRIBE").arg(channel).query(self.con)?;
-        Ok(())
+        self.send_subscription(cmd("UNSUBSCRIBE").arg(channel))
     }
 
     /// Unsubscribes from a channel with a pattern.
     pub fn punsubscribe<T: ToRedisArgs>(&mut self, pchannel: T) -> RedisResult<()> {
-        let _: () = cmd("PUNSUBSCRIBE").arg(pchannel).query(self.con)?;
-        Ok(())
+        self.send_subscription(cmd("PUNSUBSCRIBE").arg(pchannel))
+    }
+
+    /// Subscribes to a new shard channel (redis 7).  Use `ClusterPubSub`
+    /// in a cluster, where shard channels are served by different nodes.
+    pub fn ssubscribe<T: ToRedisArgs>(&mut self, channel: T) -> RedisResult<()> {
+        self.send_subscription(cmd("SSUBSCRIBE").arg(channel))
+    }
+
+    /// Unsubscribes from a shard channel.
+    pub fn sunsubscribe<T: ToRedisArgs>(&mut self, channel: T) -> RedisResult<()> {
+        self.send_subscription(cmd("SUNSUBSCRIBE").arg(channel))
     }
 
     /// Fetches the next message from the pubsub connection.  Blocks until
@@ -625,29 +2237,13 @@ impl<'a> PubSub<'a> {
     /// appropriate type through the helper methods on it.
     pub fn get_message(&mut self) -> RedisResult<Msg> {
         loop {
-            let raw_msg: Vec<Value> = from_redis_value(&self.con.recv_response()?)?;
-            let mut iter = raw_msg.into_iter();
-            let msg_type: String = from_redis_value(&unwrap_or!(iter.next(), continue))?;
-            let mut pattern = None;
-            let payload;
-            let channel;
-
-            if msg_type == "message" {
-                channel = unwrap_or!(iter.next(), continue);
-                payload = unwrap_or!(iter.next(), continue);
-            } else if msg_type == "pmessage" {
-                pattern = Some(unwrap_or!(iter.next(), continue));
-                channel = unwrap_or!(iter.next(), continue);
-                payload = unwrap_or!(iter.next(), continue);
-            } else {
-                continue;
+            let value = self.con.recv_response()?;
+            if let Some(msg) = Msg::from_value(&value) {
+                return Ok(msg);
+            }
+            if let Value::Push { kind, data } = value {
+                self.con.send_push(kind, data);
             }
-
-            return Ok(Msg {
-                payload: payload,
-                channel: channel,
-                pattern: pattern,
-            });
         }
     }
 
@@ -670,11 +2266,61 @@ impl<'a> Drop for PubSub<'a> {
 /// This holds the data that comes from listening to a pubsub
 /// connection.  It only contains actual message data.
 impl Msg {
-    /// Returns the channel this message came on.
+    /// Creates a message from a reply received in subscribed state.
+    /// Returns `None` for replies that are not messages, like the
+    /// confirmations of subscriptions.  Messages of shard channels
+    /// (`smessage`) are supported as well.
+    pub fn from_value(value: &Value) -> Option<Msg> {
+        let items = match *value {
+            Value::Bulk(ref items) => items,
+            Value::Push { ref kind, ref data } => {
+                return match (kind, data.len()) {
+                    (&PushKind::Message, 2) | (&PushKind::SMessage, 2) => Some(Msg {
+                        payload: data[1].clone(),
+                        channel: data[0].clone(),
+                        pattern: None,
+                        kind: kind.clone(),
+                    }),
+                    (&PushKind::PMessage, 3) => Some(Msg {
+                        payload: data[2].clone(),
+                        channel: data[1].clone(),
+                        pattern: Some(data[0].clone()),
+                        kind: PushKind::PMessage,
+                    }),
+                    _ => None,
+                };
+            }
+            _ => return None,
+        };
+        let msg_type: String = from_redis_value(items.first()?).ok()?;
+        match (&msg_type[..], items.len()) {
+            ("message", 3) | ("smessage", 3) => Some(Msg {
+                payload: items[2].clone(),
+                channel: items[1].clone(),
+                pattern: None,
+                kind: PushKind::from_name(&msg_type),
+            }),
+            ("pmessage", 4) => Some(Msg {
+                payload: items[3].clone(),
+                channel: items[2].clone(),
+                pattern: Some(items[1].clone()),
+                kind: PushKind::PMessage,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the channel this message came on, for instance as
+    /// `String` or as `Vec<u8>` for binary channel names.
     pub fn get_channel<T: FromRedisValue>(&self) -> RedisResult<T> {
         from_redis_value(&self.channel)
     }
 
+    /// Returns the bytes of the channel without copying them.
+    pub fn channel_bytes(&self) -> &[u8] {
+        value_bytes(&self.channel)
+    }
+
     /// Convenience method to get a string version of the channel.  Unless
     /// your channel contains non utf-8 bytes you can always use this
     /// method.  If the channel is not a valid string (which really should
@@ -686,19 +2332,23 @@ impl Msg {
         }
     }
 
-    /// Returns the message's payload in a specific format.
+    /// Returns the message's payload in a specific format, like a number,
+    /// `String` or `Vec<u8>`, or any type with a `FromRedisValue`
+    /// implementation such as the wrappers of the `codec` module.
     pub fn get_payload<T: FromRedisValue>(&self) -> RedisResult<T> {
         from_redis_value(&self.payload)
     }
 
+    /// Returns the bytes of the payload without copying them.
+    pub fn payload_bytes(&self) -> &[u8] {
+        value_bytes(&self.payload)
+    }
+
     /// Returns the bytes that are the message's payload.  This can be used
     /// as an alternative to the `get_payload` function if you are interested
-    /// in the raw bytes in it.
+    /// in the raw bytes in it.  Same as `payload_bytes`.
     pub fn get_payload_bytes(&self) -> &[u8] {
-        match self.payload {
-            Value::Data(ref bytes) => bytes,
-            _ => b"",
-        }
+        self.payload_bytes()
     }
 
     /// Returns true if the message was constructed from a pattern
@@ -707,6 +2357,18 @@ impl Msg {
         self.pattern.is_some()
     }
 
+    /// Returns true if the message was published to a shard channel with
+    /// `SPUBLISH`.
+    pub fn from_shard_channel(&self) -> bool {
+        self.kind == PushKind::SMessage
+    }
+
+    /// Returns the kind of the message: `PushKind::Message`,
+    /// `PushKind::PMessage` or `PushKind::SMessage`.
+    pub fn kind(&self) -> &PushKind {
+        &self.kind
+    }
+
     /// If the message was constructed from a message pattern this can be
     /// used to find out which one.  It's recommended to match against
     /// an `Option<String>` so that you do not need to use `from_pattern`
@@ -717,6 +2379,22 @@ impl Msg {
             Some(ref x) => from_redis_value(x),
         }
     }
+
+    /// Returns the bytes of the pattern without copying them, `None` if
+    /// the message was not received through a pattern subscription.
+    pub fn pattern_bytes(&self) -> Option<&[u8]> {
+        self.pattern.as_ref().map(value_bytes)
+    }
+}
+
+// The bytes of a channel, pattern or payload.  They are bulk strings, or
+// simple strings for some proxies.
+fn value_bytes(value: &Value) -> &[u8] {
+    match *value {
+        Value::Data(ref bytes) => bytes,
+        Value::Status(ref s) => s.as_bytes(),
+        _ => b"",
+    }
 }
 
 /// This function simplifies transaction management slightly.  What it
@@ -760,15 +2438,64 @@ pub fn transaction<
     con: &mut C,
     keys: &[K],
     func: F,
+) -> RedisResult<T> {
+    run_transaction(con, keys, None, func)
+}
+
+/// Like `transaction` but gives up after the transaction was aborted
+/// `max_retries + 1` times because a watched key was modified.  The error
+/// returned then is of kind `ErrorKind::ExecAbortError`.
+///
+/// ```rust,no_run
+/// use redis::{Commands, PipelineCommands};
+/// # fn do_something() -> redis::RedisResult<()> {
+/// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+/// # let mut con = client.get_connection().unwrap();
+/// let key = "the_key";
+/// let (new_val,) : (isize,) = redis::transaction_with_retries(&mut con, &[key], 5, |con, pipe| {
+///     let old_val : isize = con.get(key)?;
+///     pipe.set(key, old_val + 1).ignore().get(key).query(con)
+/// })?;
+/// # Ok(()) }
+/// ```
+pub fn transaction_with_retries<
+    C: ConnectionLike,
+    K: ToRedisArgs,
+    T: FromRedisValue,
+    F: FnMut(&mut C, &mut Pipeline) -> RedisResult<Option<T>>,
+>(
+    con: &mut C,
+    keys: &[K],
+    max_retries: usize,
+    func: F,
+) -> RedisResult<T> {
+    run_transaction(con, keys, Some(max_retries), func)
+}
+
+fn run_transaction<
+    C: ConnectionLike,
+    K: ToRedisArgs,
+    T: FromRedisValue,
+    F: FnMut(&mut C, &mut Pipeline) -> RedisResult<Option<T>>,
+>(
+    con: &mut C,
+    keys: &[K],
+    max_retries: Option<usize>,
+    func: F,
 ) -> RedisResult<T> {
     let mut func = func;
+    let mut retries = 0;
     loop {
         let _: () = cmd("WATCH").arg(keys).query(con)?;
         let mut p = pipe();
         let response: Option<T> = func(con, p.atomic())?;
         match response {
             None => {
-                continue;
+                if max_retries.map_or(false, |max_retries| retries >= max_retries) {
+                    let _: () = cmd("UNWATCH").query(con)?;
+                    return Err(transaction_aborted(retries + 1));
+                }
+                retries += 1;
             }
             Some(response) => {
                 // make sure no watch is left in the connection, even if
@@ -779,3 +2506,13 @@ pub fn transaction<
         }
     }
 }
+
+// The error of a transaction that was aborted too often by modifications
+// of the watched keys.
+pub fn transaction_aborted(attempts: usize) -> RedisError {
+    RedisError::from((
+        ErrorKind::ExecAbortError,
+        "Watched keys were modified during every attempt of the transaction",
+        format!("{} attempts", attempts),
+    ))
+}
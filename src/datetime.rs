@@ -0,0 +1,265 @@
+//! Conversions of timestamps of the `chrono` and `time` crates.
+//!
+//! With the `with-chrono` feature `chrono::DateTime<Utc>` and
+//! `chrono::NaiveDateTime` (taken as UTC) can be used as arguments and
+//! converted from replies, with the `with-time` feature the same works for
+//! `time::OffsetDateTime`.
+//!
+//! As arguments the types are written as RFC 3339 strings, like
+//! `2019-05-04T12:30:00.250Z`.  Wrap them in `UnixMillis` to write the
+//! milliseconds since the epoch instead, which sort correctly as scores of
+//! sorted sets.  Replies are read from either format; `UnixMillis` and
+//! `Rfc3339` only accept theirs.
+//!
+//! The expiration of keys is converted with `from_ttl`, `from_pttl`,
+//! `from_expire_time` and `from_pexpire_time`.
+//!
+//! ```rust,no_run
+//! # extern crate chrono;
+//! # extern crate redis;
+//! # fn do_something() -> redis::RedisResult<()> {
+//! # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+//! # let mut con = client.get_connection().unwrap();
+//! use chrono::{DateTime, Utc};
+//! use redis::datetime::{from_pttl, UnixMillis};
+//! use redis::Commands;
+//!
+//! let _: () = con.set("last_login", Utc::now())?;
+//! let last_login: DateTime<Utc> = con.get("last_login")?;
+//!
+//! let _: () = con.zadd("logins", "alice", UnixMillis(last_login))?;
+//! let UnixMillis(at): UnixMillis<DateTime<Utc>> = con.zscore("logins", "alice")?;
+//!
+//! let expires: Option<DateTime<Utc>> = from_pttl(con.pttl("session")?);
+//! # Ok(()) }
+//! # fn main() {}
+//! ```
+
+#[cfg(feature = "with-chrono")]
+use chrono::{self, DateTime, NaiveDateTime, TimeZone, Utc};
+#[cfg(feature = "with-time")]
+use time::{self, OffsetDateTime};
+
+use types::{from_redis_value, FromRedisValue, RedisResult, RedisWrite, ToRedisArgs, Value};
+
+/// A point in time that can be converted to and from the representations
+/// used with redis.
+pub trait Timestamp: Sized {
+    /// Returns the current time.
+    fn now() -> Self;
+
+    /// Returns the milliseconds since the unix epoch, negative before it.
+    fn unix_millis(&self) -> i64;
+
+    /// Creates the timestamp from milliseconds since the unix epoch.
+    /// Returns `None` if it is out of range.
+    fn from_unix_millis(millis: i64) -> Option<Self>;
+
+    /// Formats the timestamp as RFC 3339 in UTC.
+    fn format_rfc3339(&self) -> String;
+
+    /// Parses an RFC 3339 timestamp with any offset.
+    fn parse_rfc3339(s: &str) -> Option<Self>;
+}
+
+// Splits milliseconds into seconds and non-negative nanoseconds.
+#[cfg(feature = "with-chrono")]
+fn split_millis(millis: i64) -> (i64, u32) {
+    let (secs, rem) = (millis / 1000, millis % 1000);
+    if rem < 0 {
+        (secs - 1, (rem + 1000) as u32 * 1_000_000)
+    } else {
+        (secs, rem as u32 * 1_000_000)
+    }
+}
+
+#[cfg(feature = "with-chrono")]
+impl Timestamp for DateTime<Utc> {
+    fn now() -> Self {
+        Utc::now()
+    }
+
+    fn unix_millis(&self) -> i64 {
+        self.timestamp() * 1000 + i64::from(self.timestamp_subsec_millis())
+    }
+
+    fn from_unix_millis(millis: i64) -> Option<Self> {
+        let (secs, nanos) = split_millis(millis);
+        Utc.timestamp_opt(secs, nanos).single()
+    }
+
+    fn format_rfc3339(&self) -> String {
+        self.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+    }
+
+    fn parse_rfc3339(s: &str) -> Option<Self> {
+        DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|dt| dt.with_timezone(&Utc))
+    }
+}
+
+#[cfg(feature = "with-chrono")]
+impl Timestamp for NaiveDateTime {
+    fn now() -> Self {
+        Utc::now().naive_utc()
+    }
+
+    fn unix_millis(&self) -> i64 {
+        self.timestamp() * 1000 + i64::from(self.timestamp_subsec_millis())
+    }
+
+    fn from_unix_millis(millis: i64) -> Option<Self> {
+        let (secs, nanos) = split_millis(millis);
+        NaiveDateTime::from_timestamp_opt(secs, nanos)
+    }
+
+    fn format_rfc3339(&self) -> String {
+        Utc.from_utc_datetime(self).format_rfc3339()
+    }
+
+    fn parse_rfc3339(s: &str) -> Option<Self> {
+        DateTime::<Utc>::parse_rfc3339(s).map(|dt| dt.naive_utc())
+    }
+}
+
+#[cfg(feature = "with-time")]
+impl Timestamp for OffsetDateTime {
+    fn now() -> Self {
+        OffsetDateTime::now_utc()
+    }
+
+    fn unix_millis(&self) -> i64 {
+        (self.unix_timestamp_nanos() / 1_000_000) as i64
+    }
+
+    fn from_unix_millis(millis: i64) -> Option<Self> {
+        OffsetDateTime::from_unix_timestamp_nanos(i128::from(millis) * 1_000_000).ok()
+    }
+
+    fn format_rfc3339(&self) -> String {
+        // only years beyond 9999 cannot be formatted
+        self.to_offset(time::UtcOffset::UTC)
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap_or_else(|_| self.to_string())
+    }
+
+    fn parse_rfc3339(s: &str) -> Option<Self> {
+        OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339).ok()
+    }
+}
+
+// Reads a timestamp from milliseconds or an RFC 3339 string.
+fn timestamp_from_redis_value<T: Timestamp>(v: &Value) -> RedisResult<T> {
+    match from_redis_value(v) {
+        Ok(UnixMillis(ts)) => Ok(ts),
+        Err(_) => from_redis_value(v).map(|Rfc3339(ts)| ts),
+    }
+}
+
+macro_rules! timestamp_impls {
+    ($t:ty) => {
+        impl ToRedisArgs for $t {
+            fn write_redis_args<W>(&self, out: &mut W)
+            where
+                W: ?Sized + RedisWrite,
+            {
+                out.write_arg(self.format_rfc3339().as_bytes())
+            }
+        }
+
+        impl FromRedisValue for $t {
+            fn from_redis_value(v: &Value) -> RedisResult<$t> {
+                timestamp_from_redis_value(v)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "with-chrono")]
+timestamp_impls!(DateTime<Utc>);
+#[cfg(feature = "with-chrono")]
+timestamp_impls!(NaiveDateTime);
+#[cfg(feature = "with-time")]
+timestamp_impls!(OffsetDateTime);
+
+/// A timestamp that is written and read as milliseconds since the unix
+/// epoch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnixMillis<T>(pub T);
+
+impl<T: Timestamp> ToRedisArgs for UnixMillis<T> {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        self.0.unix_millis().write_redis_args(out)
+    }
+}
+
+impl<T: Timestamp> FromRedisValue for UnixMillis<T> {
+    fn from_redis_value(v: &Value) -> RedisResult<UnixMillis<T>> {
+        // scores of sorted sets are doubles
+        let millis = match from_redis_value::<i64>(v) {
+            Ok(millis) => millis,
+            Err(_) => from_redis_value::<f64>(v)? as i64,
+        };
+        match T::from_unix_millis(millis) {
+            Some(ts) => Ok(UnixMillis(ts)),
+            None => invalid_type_error!(v, "Timestamp out of range"),
+        }
+    }
+}
+
+/// A timestamp that is written and read as an RFC 3339 string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rfc3339<T>(pub T);
+
+impl<T: Timestamp> ToRedisArgs for Rfc3339<T> {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(self.0.format_rfc3339().as_bytes())
+    }
+}
+
+impl<T: Timestamp> FromRedisValue for Rfc3339<T> {
+    fn from_redis_value(v: &Value) -> RedisResult<Rfc3339<T>> {
+        let s: String = from_redis_value(v)?;
+        match T::parse_rfc3339(&s) {
+            Some(ts) => Ok(Rfc3339(ts)),
+            None => invalid_type_error!(v, "Not an RFC 3339 timestamp"),
+        }
+    }
+}
+
+/// Converts the reply of `TTL` into the time the key expires.  Returns
+/// `None` if the key does not exist or does not expire.
+pub fn from_ttl<T: Timestamp>(seconds: i64) -> Option<T> {
+    from_pttl(seconds.checked_mul(1000)?)
+}
+
+/// Converts the reply of `PTTL` into the time the key expires.  Returns
+/// `None` if the key does not exist or does not expire.
+pub fn from_pttl<T: Timestamp>(millis: i64) -> Option<T> {
+    if millis < 0 {
+        return None;
+    }
+    T::from_unix_millis(T::now().unix_millis().checked_add(millis)?)
+}
+
+/// Converts the reply of `EXPIRETIME` into the time the key expires.
+/// Returns `None` if the key does not exist or does not expire.
+pub fn from_expire_time<T: Timestamp>(seconds: i64) -> Option<T> {
+    from_pexpire_time(seconds.checked_mul(1000)?)
+}
+
+/// Converts the reply of `PEXPIRETIME` into the time the key expires.
+/// Returns `None` if the key does not exist or does not expire.
+pub fn from_pexpire_time<T: Timestamp>(millis: i64) -> Option<T> {
+    if millis < 0 {
+        return None;
+    }
+    T::from_unix_millis(millis)
+}
@@ -0,0 +1,311 @@
+//! Calling the functions of a library loaded with `FUNCTION LOAD`
+//! (redis 7).
+//!
+//! Functions replace `EVAL` scripts on redis 7: a library of functions is
+//! loaded once and persisted and replicated by the server, and its functions
+//! are called by name.  `Library` mirrors `Script`, it loads the library
+//! again when the server does not know a function it calls:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::functions::Library;
+//!
+//! # let client = redis::Client::open("redis://127.0.0.1/")?;
+//! # let mut con = client.get_connection()?;
+//! let library = Library::new(r"#!lua name=counters
+//!     redis.register_function('bump', function(keys, args)
+//!         return redis.call('INCRBY', keys[1], args[1])
+//!     end)
+//! ");
+//! let value: i64 = library.function("bump").key("hits").arg(5).invoke(&mut con)?;
+//! # Ok(()) }
+//! ```
+use std::collections::HashMap;
+
+use cmd::{cmd, Cmd};
+use connection::ConnectionLike;
+use types::{
+    from_redis_value, ArgBuffer, ErrorKind, FromRedisValue, RedisError, RedisResult, RedisWrite,
+    ToRedisArgs, Value,
+};
+
+/// The source of a function library.
+///
+/// The code has to start with a shebang naming the engine and the library,
+/// like `#!lua name=mylib`.
+#[derive(Clone, Debug)]
+pub struct Library {
+    code: String,
+    name: String,
+}
+
+impl Library {
+    /// Creates a library from its code.  The name is read from the
+    /// shebang and is empty if there is none.
+    pub fn new(code: &str) -> Library {
+        let code = code.trim_left();
+        let shebang = code.lines().next().unwrap_or("");
+        let name = if shebang.starts_with("#!") {
+            shebang
+                .split_whitespace()
+                .filter(|part| part.starts_with("name="))
+                .map(|part| part["name=".len()..].to_string())
+                .next()
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+        Library {
+            code: code.to_string(),
+            name,
+        }
+    }
+
+    /// Returns the name of the library.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the code of the library.
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// Loads the library, replacing a library of the same name.  Returns
+    /// the name of the library.
+    pub fn load(&self, con: &mut ConnectionLike) -> RedisResult<String> {
+        cmd("FUNCTION")
+            .arg("LOAD")
+            .arg("REPLACE")
+            .arg(&self.code)
+            .query(con)
+    }
+
+    /// Creates a call of a function of the library without keys and
+    /// arguments.
+    pub fn function(&self, name: &str) -> FunctionCall {
+        FunctionCall {
+            library: self,
+            name: name.to_string(),
+            keys: ArgBuffer::new(),
+            args: ArgBuffer::new(),
+            read_only: false,
+        }
+    }
+}
+
+/// A prepared call of a function of a `Library`.
+pub struct FunctionCall<'a> {
+    library: &'a Library,
+    name: String,
+    keys: ArgBuffer,
+    args: ArgBuffer,
+    read_only: bool,
+}
+
+impl<'a> FunctionCall<'a> {
+    /// Adds a key argument to the call.  This ends up in `keys` of the
+    /// function.
+    pub fn key<T: ToRedisArgs>(mut self, key: T) -> FunctionCall<'a> {
+        key.write_redis_args(&mut self.keys);
+        self
+    }
+
+    /// Adds a regular argument to the call.  This ends up in `args` of the
+    /// function.
+    pub fn arg<T: ToRedisArgs>(mut self, arg: T) -> FunctionCall<'a> {
+        arg.write_redis_args(&mut self.args);
+        self
+    }
+
+    /// Calls the function with `FCALL_RO`.  The function has to be
+    /// registered with the `no-writes` flag, and the call may be served by
+    /// a replica of a cluster.
+    pub fn read_only(mut self) -> FunctionCall<'a> {
+        self.read_only = true;
+        self
+    }
+
+    fn fcall_cmd(&self) -> Cmd {
+        let mut cmd = cmd(if self.read_only { "FCALL_RO" } else { "FCALL" });
+        cmd.arg(&self.name)
+            .arg(self.keys.len())
+            .arg(&self.keys)
+            .arg(&self.args);
+        cmd
+    }
+
+    /// Calls the function and returns the result.
+    ///
+    /// If the server does not know the function, the library is loaded
+    /// with `FUNCTION LOAD REPLACE` and the function is called again.  On
+    /// a cluster the library is loaded on a random node only, so it should
+    /// be loaded on all masters up front.
+    pub fn invoke<T: FromRedisValue>(&self, con: &mut ConnectionLike) -> RedisResult<T> {
+        match self.fcall_cmd().query(con) {
+            Err(ref err) if is_function_not_found(err) => {}
+            result => return result,
+        }
+        self.library.load(con)?;
+        self.fcall_cmd().query(con)
+    }
+}
+
+fn is_function_not_found(err: &RedisError) -> bool {
+    err.kind() == ErrorKind::ResponseError && err.to_string().contains("Function not found")
+}
+
+/// A function of a library, as listed by `FUNCTION LIST`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionInfo {
+    /// The name of the function.
+    pub name: String,
+    /// The description given when registering the function, if any.
+    pub description: Option<String>,
+    /// The flags of the function, like `no-writes`.
+    pub flags: Vec<String>,
+}
+
+impl FromRedisValue for FunctionInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<FunctionInfo> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(FunctionInfo {
+            name: field(&map, "name")?,
+            description: optional_field(&map, "description")?,
+            flags: optional_field(&map, "flags")?.unwrap_or_default(),
+        })
+    }
+}
+
+/// A library as listed by `FUNCTION LIST`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LibraryInfo {
+    /// The name of the library.
+    pub name: String,
+    /// The engine running the library, like `LUA`.
+    pub engine: String,
+    /// The functions of the library.
+    pub functions: Vec<FunctionInfo>,
+    /// The code of the library, if it was requested with `WITHCODE`.
+    pub code: Option<String>,
+}
+
+impl FromRedisValue for LibraryInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<LibraryInfo> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(LibraryInfo {
+            name: field(&map, "library_name")?,
+            engine: field(&map, "engine")?,
+            functions: field(&map, "functions")?,
+            code: optional_field(&map, "library_code")?,
+        })
+    }
+}
+
+/// The function that is running while `FUNCTION STATS` was called.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunningFunction {
+    /// The name of the function.
+    pub name: String,
+    /// The command that called the function with its arguments.
+    pub command: Vec<String>,
+    /// How long the function has been running in milliseconds.
+    pub duration_ms: u64,
+}
+
+impl FromRedisValue for RunningFunction {
+    fn from_redis_value(v: &Value) -> RedisResult<RunningFunction> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(RunningFunction {
+            name: field(&map, "name")?,
+            command: field(&map, "command")?,
+            duration_ms: field(&map, "duration_ms")?,
+        })
+    }
+}
+
+/// The libraries and functions loaded into an engine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EngineStats {
+    /// The number of libraries.
+    pub libraries_count: u64,
+    /// The number of functions.
+    pub functions_count: u64,
+}
+
+impl FromRedisValue for EngineStats {
+    fn from_redis_value(v: &Value) -> RedisResult<EngineStats> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(EngineStats {
+            libraries_count: field(&map, "libraries_count")?,
+            functions_count: field(&map, "functions_count")?,
+        })
+    }
+}
+
+/// The reply of `FUNCTION STATS`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionStats {
+    /// The function that is running right now, if any.
+    pub running_script: Option<RunningFunction>,
+    /// The statistics of each engine by name, like `LUA`.
+    pub engines: HashMap<String, EngineStats>,
+}
+
+impl FromRedisValue for FunctionStats {
+    fn from_redis_value(v: &Value) -> RedisResult<FunctionStats> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(FunctionStats {
+            running_script: optional_field(&map, "running_script")?,
+            engines: field(&map, "engines")?,
+        })
+    }
+}
+
+/// What `FUNCTION RESTORE` does with the libraries that are already
+/// loaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RestorePolicy {
+    /// Deletes all libraries before restoring the dump (`FLUSH`).
+    Flush,
+    /// Adds the libraries of the dump and fails if one of them exists
+    /// already (`APPEND`), the default of the server.
+    Append,
+    /// Adds the libraries of the dump and replaces existing libraries of
+    /// the same name (`REPLACE`).
+    Replace,
+}
+
+impl ToRedisArgs for RestorePolicy {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            RestorePolicy::Flush => &b"FLUSH"[..],
+            RestorePolicy::Append => &b"APPEND"[..],
+            RestorePolicy::Replace => &b"REPLACE"[..],
+        })
+    }
+}
+
+fn field<T: FromRedisValue>(map: &HashMap<String, Value>, name: &str) -> RedisResult<T> {
+    match map.get(name) {
+        Some(value) => from_redis_value(value),
+        None => Err(RedisError::from((
+            ErrorKind::TypeError,
+            "Function reply without field",
+            name.to_string(),
+        ))),
+    }
+}
+
+fn optional_field<T: FromRedisValue>(
+    map: &HashMap<String, Value>,
+    name: &str,
+) -> RedisResult<Option<T>> {
+    match map.get(name) {
+        Some(&Value::Nil) | None => Ok(None),
+        Some(value) => from_redis_value(value).map(Some),
+    }
+}
@@ -0,0 +1,267 @@
+//! Searching the members of a geospatial index with `GEOSEARCH` and
+//! `GEOSEARCHSTORE` (redis 6.2).
+//!
+//! A search starts at a member or at a position and covers a circle or a
+//! rectangle around it:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::geo::{GeoSearchOptions, GeoSearchResult, Unit};
+//! use redis::Commands;
+//!
+//! # let client = redis::Client::open("redis://127.0.0.1/")?;
+//! # let mut con = client.get_connection()?;
+//! let options = GeoSearchOptions::from_lonlat(13.361389, 38.115556)
+//!     .by_radius(200.0, Unit::Kilometers)
+//!     .asc()
+//!     .with_dist();
+//! let nearby: Vec<GeoSearchResult> = con.geosearch("cities", &options)?;
+//! for city in nearby {
+//!     println!("{} is {:?} km away", city.name, city.dist);
+//! }
+//! # Ok(()) }
+//! ```
+use types::{from_redis_value, FromRedisValue, RedisResult, RedisWrite, ToRedisArgs, Value};
+
+/// The unit of distances.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    /// Meters (`m`).
+    Meters,
+    /// Kilometers (`km`).
+    Kilometers,
+    /// Miles (`mi`).
+    Miles,
+    /// Feet (`ft`).
+    Feet,
+}
+
+impl ToRedisArgs for Unit {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            Unit::Meters => &b"m"[..],
+            Unit::Kilometers => &b"km"[..],
+            Unit::Miles => &b"mi"[..],
+            Unit::Feet => &b"ft"[..],
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Origin {
+    Member(Vec<Vec<u8>>),
+    LonLat(f64, f64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Shape {
+    Radius(f64, Unit),
+    Box(f64, f64, Unit),
+}
+
+/// The options of `GEOSEARCH` and `GEOSEARCHSTORE`, see
+/// `Commands::geosearch` and `Commands::geosearch_store`.
+///
+/// The options are created with the center of the search, and the area
+/// has to be set with `by_radius` or `by_box` before they are sent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoSearchOptions {
+    origin: Origin,
+    shape: Option<Shape>,
+    ascending: Option<bool>,
+    count: Option<(usize, bool)>,
+    with_coord: bool,
+    with_dist: bool,
+    with_hash: bool,
+}
+
+impl GeoSearchOptions {
+    fn new(origin: Origin) -> GeoSearchOptions {
+        GeoSearchOptions {
+            origin,
+            shape: None,
+            ascending: None,
+            count: None,
+            with_coord: false,
+            with_dist: false,
+            with_hash: false,
+        }
+    }
+
+    /// Searches around the position of a member of the index
+    /// (`FROMMEMBER`).
+    pub fn from_member<M: ToRedisArgs>(member: M) -> GeoSearchOptions {
+        GeoSearchOptions::new(Origin::Member(member.to_redis_args()))
+    }
+
+    /// Searches around a longitude and latitude (`FROMLONLAT`).
+    pub fn from_lonlat(longitude: f64, latitude: f64) -> GeoSearchOptions {
+        GeoSearchOptions::new(Origin::LonLat(longitude, latitude))
+    }
+
+    /// Searches within a circle of the radius (`BYRADIUS`).
+    pub fn by_radius(mut self, radius: f64, unit: Unit) -> GeoSearchOptions {
+        self.shape = Some(Shape::Radius(radius, unit));
+        self
+    }
+
+    /// Searches within a rectangle of the width and height (`BYBOX`).
+    pub fn by_box(mut self, width: f64, height: f64, unit: Unit) -> GeoSearchOptions {
+        self.shape = Some(Shape::Box(width, height, unit));
+        self
+    }
+
+    /// Returns the nearest members first (`ASC`).
+    pub fn asc(mut self) -> GeoSearchOptions {
+        self.ascending = Some(true);
+        self
+    }
+
+    /// Returns the farthest members first (`DESC`).
+    pub fn desc(mut self) -> GeoSearchOptions {
+        self.ascending = Some(false);
+        self
+    }
+
+    /// Returns at most `count` members (`COUNT`).  Combined with `asc` or
+    /// `desc` these are the nearest or farthest.
+    pub fn count(mut self, count: usize) -> GeoSearchOptions {
+        self.count = Some((count, false));
+        self
+    }
+
+    /// Returns the first `count` members found, which need not be the
+    /// nearest, but is faster on large areas (`COUNT ... ANY`).
+    pub fn count_any(mut self, count: usize) -> GeoSearchOptions {
+        self.count = Some((count, true));
+        self
+    }
+
+    /// Returns the positions of the members (`WITHCOORD`).  Only for
+    /// `GEOSEARCH`.
+    pub fn with_coord(mut self) -> GeoSearchOptions {
+        self.with_coord = true;
+        self
+    }
+
+    /// Returns the distances of the members from the center in the unit
+    /// of the area (`WITHDIST`).  Only for `GEOSEARCH`.
+    pub fn with_dist(mut self) -> GeoSearchOptions {
+        self.with_dist = true;
+        self
+    }
+
+    /// Returns the geohashes of the members (`WITHHASH`).  Only for
+    /// `GEOSEARCH`.
+    pub fn with_hash(mut self) -> GeoSearchOptions {
+        self.with_hash = true;
+        self
+    }
+}
+
+impl ToRedisArgs for GeoSearchOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        match self.origin {
+            Origin::Member(ref member) => {
+                out.write_arg(b"FROMMEMBER");
+                for arg in member {
+                    out.write_arg(arg);
+                }
+            }
+            Origin::LonLat(longitude, latitude) => {
+                out.write_arg(b"FROMLONLAT");
+                longitude.write_redis_args(out);
+                latitude.write_redis_args(out);
+            }
+        }
+        match self.shape {
+            Some(Shape::Radius(radius, unit)) => {
+                out.write_arg(b"BYRADIUS");
+                radius.write_redis_args(out);
+                unit.write_redis_args(out);
+            }
+            Some(Shape::Box(width, height, unit)) => {
+                out.write_arg(b"BYBOX");
+                width.write_redis_args(out);
+                height.write_redis_args(out);
+                unit.write_redis_args(out);
+            }
+            None => {}
+        }
+        match self.ascending {
+            Some(true) => out.write_arg(b"ASC"),
+            Some(false) => out.write_arg(b"DESC"),
+            None => {}
+        }
+        if let Some((count, any)) = self.count {
+            out.write_arg(b"COUNT");
+            count.write_redis_args(out);
+            if any {
+                out.write_arg(b"ANY");
+            }
+        }
+        if self.with_coord {
+            out.write_arg(b"WITHCOORD");
+        }
+        if self.with_dist {
+            out.write_arg(b"WITHDIST");
+        }
+        if self.with_hash {
+            out.write_arg(b"WITHHASH");
+        }
+    }
+}
+
+/// A member found by `GEOSEARCH`.  The other fields are set if the
+/// options asked for them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoSearchResult {
+    /// The name of the member.
+    pub name: String,
+    /// The distance from the center of the search.
+    pub dist: Option<f64>,
+    /// The geohash of the position, as stored as score in the index.
+    pub hash: Option<u64>,
+    /// The longitude and latitude of the member.
+    pub coord: Option<(f64, f64)>,
+}
+
+impl FromRedisValue for GeoSearchResult {
+    fn from_redis_value(v: &Value) -> RedisResult<GeoSearchResult> {
+        let items = match *v {
+            Value::Bulk(ref items) if !items.is_empty() => items,
+            Value::Bulk(_) => invalid_type_error!(v, "Empty geo search result"),
+            _ => {
+                return Ok(GeoSearchResult {
+                    name: from_redis_value(v)?,
+                    dist: None,
+                    hash: None,
+                    coord: None,
+                })
+            }
+        };
+        let mut result = GeoSearchResult {
+            name: from_redis_value(&items[0])?,
+            dist: None,
+            hash: None,
+            coord: None,
+        };
+        // the distance is a string (or a double in RESP3), the hash an
+        // integer and the position a pair
+        for item in &items[1..] {
+            match *item {
+                Value::Data(_) | Value::Double(_) => result.dist = Some(from_redis_value(item)?),
+                Value::Int(hash) => result.hash = Some(hash as u64),
+                Value::Bulk(_) => result.coord = Some(from_redis_value(item)?),
+                _ => invalid_type_error!(v, "Response type not geo search result compatible"),
+            }
+        }
+        Ok(result)
+    }
+}
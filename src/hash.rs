@@ -0,0 +1,544 @@
+//! Stores serde structs as hashes.
+//!
+//! With the `with-serde` feature a struct that implements `Serialize` is
+//! written to the fields of a hash with `HashCommands::hset_struct` and
+//! read back with `HashCommands::hgetall_as`:
+//!
+//! ```rust,no_run
+//! # #[macro_use] extern crate serde_derive;
+//! # extern crate redis;
+//! use redis::hash::HashCommands;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Address {
+//!     city: String,
+//!     zip: u32,
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct User {
+//!     name: String,
+//!     age: u8,
+//!     email: Option<String>,
+//!     address: Address,
+//! }
+//!
+//! # fn do_something() -> redis::RedisResult<()> {
+//! # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+//! # let mut con = client.get_connection().unwrap();
+//! let user = User {
+//!     name: "Alice".to_string(),
+//!     age: 42,
+//!     email: None,
+//!     address: Address { city: "Vienna".to_string(), zip: 1010 },
+//! };
+//! // sets the fields `name`, `age`, `address.city` and `address.zip`
+//! con.hset_struct("user:1", &user)?;
+//! let user: Option<User> = con.hgetall_as("user:1")?;
+//! # Ok(()) }
+//! # fn main() {}
+//! ```
+//!
+//! Numbers, booleans and strings are stored as text and parsed again when
+//! read.  A field that is `None` is removed from the hash and missing
+//! fields are read as `None`.  Nested structs and maps are flattened into
+//! fields joined with a dot, like `address.city`; sequences cannot be
+//! stored.  Fields added with `#[serde(flatten)]` are stored like all
+//! others, but serde reads them back as strings, so only flatten structs
+//! whose fields are strings.
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str;
+
+use serde::de::value::MapDeserializer;
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+
+use cmd::{cmd, pipe};
+use connection::ConnectionLike;
+use types::{
+    from_redis_value, ErrorKind, FromRedisValue, RedisError, RedisResult, ToRedisArgs, Value,
+};
+
+impl ser::Error for RedisError {
+    fn custom<T: Display>(msg: T) -> RedisError {
+        RedisError::from((
+            ErrorKind::TypeError,
+            "Could not convert the value into hash fields",
+            msg.to_string(),
+        ))
+    }
+}
+
+impl de::Error for RedisError {
+    fn custom<T: Display>(msg: T) -> RedisError {
+        RedisError::from((
+            ErrorKind::TypeError,
+            "Could not convert the hash fields",
+            msg.to_string(),
+        ))
+    }
+}
+
+/// The fields a value is stored as.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HashFields {
+    /// The fields to set and their values.
+    pub set: Vec<(String, Vec<u8>)>,
+    /// The fields whose value is `None` and which are removed.
+    pub unset: Vec<String>,
+}
+
+/// Converts a struct or map into hash fields.
+pub fn to_hash_fields<T: Serialize + ?Sized>(value: &T) -> RedisResult<HashFields> {
+    let mut fields = HashFields::default();
+    value.serialize(FieldSerializer {
+        fields: &mut fields,
+        name: String::new(),
+    })?;
+    Ok(fields)
+}
+
+/// Converts the fields of a hash, like the reply of `HGETALL`, into a
+/// struct or map.
+pub fn from_hash_fields<T: DeserializeOwned>(fields: Vec<(String, Vec<u8>)>) -> RedisResult<T> {
+    let mut root = BTreeMap::new();
+    for (name, value) in fields {
+        insert_field(&mut root, &name, value);
+    }
+    T::deserialize(Node::Branch(root))
+}
+
+/// A value that is read from the fields of a hash, see `from_hash_fields`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HashStruct<T>(pub T);
+
+impl<T: DeserializeOwned> FromRedisValue for HashStruct<T> {
+    fn from_redis_value(v: &Value) -> RedisResult<HashStruct<T>> {
+        from_hash_fields(from_redis_value(v)?).map(HashStruct)
+    }
+}
+
+/// Stores structs as hashes, see the module documentation.
+pub trait HashCommands: ConnectionLike + Sized {
+    /// Sets the fields of the hash to the ones of `value` and removes the
+    /// fields that are `None`.  Other fields of the hash are kept.  Both
+    /// happen in a transaction.
+    fn hset_struct<K: ToRedisArgs, T: Serialize + ?Sized>(
+        &mut self,
+        key: K,
+        value: &T,
+    ) -> RedisResult<()> {
+        let fields = to_hash_fields(value)?;
+        let key = key.to_redis_args();
+        let mut pipe = pipe();
+        pipe.atomic();
+        if !fields.unset.is_empty() {
+            pipe.cmd("HDEL")
+                .arg(&key[..])
+                .arg(&fields.unset[..])
+                .ignore();
+        }
+        if !fields.set.is_empty() {
+            pipe.cmd("HMSET")
+                .arg(&key[..])
+                .arg(&fields.set[..])
+                .ignore();
+        }
+        if fields.unset.is_empty() && fields.set.is_empty() {
+            return Ok(());
+        }
+        pipe.query(self)
+    }
+
+    /// Reads the hash into a `T`.  Returns `None` if the key does not
+    /// exist.
+    fn hgetall_as<K: ToRedisArgs, T: DeserializeOwned>(
+        &mut self,
+        key: K,
+    ) -> RedisResult<Option<T>> {
+        let fields: Vec<(String, Vec<u8>)> = cmd("HGETALL").arg(key).query(self)?;
+        if fields.is_empty() {
+            return Ok(None);
+        }
+        from_hash_fields(fields).map(Some)
+    }
+}
+
+impl<T> HashCommands for T where T: ConnectionLike {}
+
+fn unsupported(what: &str) -> RedisError {
+    ser::Error::custom(format!("{} cannot be stored in a hash field", what))
+}
+
+// Serializes a value into the field `name`, or a struct or map into the
+// fields below it.  The name is empty for the value itself.
+struct FieldSerializer<'a> {
+    fields: &'a mut HashFields,
+    name: String,
+}
+
+impl<'a> FieldSerializer<'a> {
+    fn set(self, value: Vec<u8>) -> RedisResult<()> {
+        if self.name.is_empty() {
+            return Err(ser::Error::custom(
+                "only structs and maps can be stored as a hash",
+            ));
+        }
+        self.fields.set.push((self.name, value));
+        Ok(())
+    }
+
+    fn set_display<T: Display>(self, value: T) -> RedisResult<()> {
+        self.set(value.to_string().into_bytes())
+    }
+
+    fn nested(self) -> StructSerializer<'a> {
+        let prefix = if self.name.is_empty() {
+            self.name
+        } else {
+            self.name + "."
+        };
+        StructSerializer {
+            fields: self.fields,
+            prefix,
+            key: None,
+        }
+    }
+}
+
+impl<'a> ser::Serializer for FieldSerializer<'a> {
+    type Ok = ();
+    type Error = RedisError;
+    type SerializeSeq = Impossible<(), RedisError>;
+    type SerializeTuple = Impossible<(), RedisError>;
+    type SerializeTupleStruct = Impossible<(), RedisError>;
+    type SerializeTupleVariant = Impossible<(), RedisError>;
+    type SerializeMap = StructSerializer<'a>;
+    type SerializeStruct = StructSerializer<'a>;
+    type SerializeStructVariant = Impossible<(), RedisError>;
+
+    fn serialize_bool(self, v: bool) -> RedisResult<()> {
+        self.set_display(v)
+    }
+    fn serialize_i8(self, v: i8) -> RedisResult<()> {
+        self.set_display(v)
+    }
+    fn serialize_i16(self, v: i16) -> RedisResult<()> {
+        self.set_display(v)
+    }
+    fn serialize_i32(self, v: i32) -> RedisResult<()> {
+        self.set_display(v)
+    }
+    fn serialize_i64(self, v: i64) -> RedisResult<()> {
+        self.set_display(v)
+    }
+    fn serialize_u8(self, v: u8) -> RedisResult<()> {
+        self.set_display(v)
+    }
+    fn serialize_u16(self, v: u16) -> RedisResult<()> {
+        self.set_display(v)
+    }
+    fn serialize_u32(self, v: u32) -> RedisResult<()> {
+        self.set_display(v)
+    }
+    fn serialize_u64(self, v: u64) -> RedisResult<()> {
+        self.set_display(v)
+    }
+    fn serialize_f32(self, v: f32) -> RedisResult<()> {
+        self.set_display(v)
+    }
+    fn serialize_f64(self, v: f64) -> RedisResult<()> {
+        self.set_display(v)
+    }
+    fn serialize_char(self, v: char) -> RedisResult<()> {
+        self.set_display(v)
+    }
+    fn serialize_str(self, v: &str) -> RedisResult<()> {
+        self.set(v.as_bytes().to_vec())
+    }
+    fn serialize_bytes(self, v: &[u8]) -> RedisResult<()> {
+        self.set(v.to_vec())
+    }
+
+    fn serialize_none(self) -> RedisResult<()> {
+        if !self.name.is_empty() {
+            self.fields.unset.push(self.name);
+        }
+        Ok(())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> RedisResult<()> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> RedisResult<()> {
+        self.serialize_none()
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> RedisResult<()> {
+        self.serialize_none()
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> RedisResult<()> {
+        self.serialize_str(variant)
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> RedisResult<()> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> RedisResult<()> {
+        Err(unsupported("An enum variant with data"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> RedisResult<Self::SerializeSeq> {
+        Err(unsupported("A sequence"))
+    }
+    fn serialize_tuple(self, _len: usize) -> RedisResult<Self::SerializeTuple> {
+        Err(unsupported("A tuple"))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> RedisResult<Self::SerializeTupleStruct> {
+        Err(unsupported("A tuple struct"))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> RedisResult<Self::SerializeTupleVariant> {
+        Err(unsupported("An enum variant with data"))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> RedisResult<Self::SerializeMap> {
+        Ok(self.nested())
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> RedisResult<Self::SerializeStruct> {
+        Ok(self.nested())
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> RedisResult<Self::SerializeStructVariant> {
+        Err(unsupported("An enum variant with data"))
+    }
+}
+
+// Serializes the entries of a struct or map into fields below `prefix`.
+struct StructSerializer<'a> {
+    fields: &'a mut HashFields,
+    prefix: String,
+    // the key of a map entry whose value comes next
+    key: Option<String>,
+}
+
+impl<'a> StructSerializer<'a> {
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> RedisResult<()> {
+        value.serialize(FieldSerializer {
+            fields: &mut *self.fields,
+            name: format!("{}{}", self.prefix, key),
+        })
+    }
+}
+
+impl<'a> ser::SerializeStruct for StructSerializer<'a> {
+    type Ok = ();
+    type Error = RedisError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> RedisResult<()> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> RedisResult<()> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeMap for StructSerializer<'a> {
+    type Ok = ();
+    type Error = RedisError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> RedisResult<()> {
+        // keys are serialized like a field value and taken from there
+        let mut key_fields = HashFields::default();
+        key.serialize(FieldSerializer {
+            fields: &mut key_fields,
+            name: "key".to_string(),
+        })?;
+        match key_fields.set.pop() {
+            Some((_, key)) if key_fields.set.is_empty() => match String::from_utf8(key) {
+                Ok(key) => {
+                    self.key = Some(key);
+                    Ok(())
+                }
+                Err(_) => Err(ser::Error::custom("map keys must be valid UTF-8")),
+            },
+            _ => Err(ser::Error::custom("map keys must be strings or numbers")),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> RedisResult<()> {
+        let key = self.key.take().unwrap_or_default();
+        self.field(&key, value)
+    }
+
+    fn end(self) -> RedisResult<()> {
+        Ok(())
+    }
+}
+
+// The fields of a hash, with the ones containing dots nested into branches.
+enum Node {
+    Leaf(Vec<u8>),
+    Branch(BTreeMap<String, Node>),
+}
+
+// Inserts a field.  A field named like a prefix of others, like `a` for
+// `a.b`, hides them.
+fn insert_field(map: &mut BTreeMap<String, Node>, name: &str, value: Vec<u8>) {
+    match name.find('.') {
+        Some(idx) => {
+            let node = map
+                .entry(name[..idx].to_string())
+                .or_insert_with(|| Node::Branch(BTreeMap::new()));
+            if let Node::Branch(ref mut children) = *node {
+                insert_field(children, &name[idx + 1..], value);
+            }
+        }
+        None => {
+            map.insert(name.to_string(), Node::Leaf(value));
+        }
+    }
+}
+
+impl Node {
+    fn text(&self) -> RedisResult<&str> {
+        match *self {
+            Node::Leaf(ref value) => match str::from_utf8(value) {
+                Ok(text) => Ok(text),
+                Err(_) => Err(de::Error::custom("field is not valid UTF-8")),
+            },
+            Node::Branch(_) => Err(de::Error::custom("expected a field, got nested fields")),
+        }
+    }
+
+    fn parse<T: str::FromStr>(&self, what: &str) -> RedisResult<T> {
+        let text = self.text()?;
+        text.parse()
+            .map_err(|_| de::Error::custom(format!("expected {}, got {:?}", what, text)))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, RedisError> for Node {
+    type Deserializer = Node;
+
+    fn into_deserializer(self) -> Node {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident, $what:expr;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> RedisResult<V::Value> {
+                visitor.$visit(self.parse($what)?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Node {
+    type Error = RedisError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> RedisResult<V::Value> {
+        match self {
+            Node::Leaf(value) => match String::from_utf8(value) {
+                Ok(text) => visitor.visit_string(text),
+                Err(err) => visitor.visit_byte_buf(err.into_bytes()),
+            },
+            Node::Branch(children) => {
+                let mut map = MapDeserializer::new(children.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> RedisResult<V::Value> {
+        match self.text()? {
+            "1" | "true" => visitor.visit_bool(true),
+            "0" | "false" => visitor.visit_bool(false),
+            text => Err(de::Error::custom(format!(
+                "expected a boolean, got {:?}",
+                text
+            ))),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8, "an integer";
+        deserialize_i16 => visit_i16, "an integer";
+        deserialize_i32 => visit_i32, "an integer";
+        deserialize_i64 => visit_i64, "an integer";
+        deserialize_u8 => visit_u8, "an integer";
+        deserialize_u16 => visit_u16, "an integer";
+        deserialize_u32 => visit_u32, "an integer";
+        deserialize_u64 => visit_u64, "an integer";
+        deserialize_f32 => visit_f32, "a number";
+        deserialize_f64 => visit_f64, "a number";
+        deserialize_char => visit_char, "a character";
+    }
+
+    // a field that exists is never `None`
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> RedisResult<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> RedisResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> RedisResult<V::Value> {
+        let variant = self.text()?.to_string();
+        visitor.visit_enum(variant.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple tuple_struct map
+        struct identifier ignored_any
+    }
+}
@@ -0,0 +1,379 @@
+//! A structured reply of `INFO`, see `Commands::info_typed`.
+//!
+//! `InfoDict` gives untyped access to every field of the reply.  `Info`
+//! parses the sections that are commonly monitored into typed structs and
+//! keeps all fields, grouped by section, for the rest:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::Commands;
+//!
+//! # let client = redis::Client::open("redis://127.0.0.1/")?;
+//! # let mut con = client.get_connection()?;
+//! let info = con.info_typed()?;
+//! if let Some(memory) = info.memory {
+//!     println!("{} bytes used", memory.used_memory);
+//! }
+//! for (db, keyspace) in &info.keyspace {
+//!     println!("db{}: {} keys", db, keyspace.keys);
+//! }
+//! # Ok(()) }
+//! ```
+//!
+//! Fields that older servers do not report are `None`.
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::time::Duration;
+
+use types::{from_redis_value, ErrorKind, FromRedisValue, RedisError, RedisResult, Value};
+
+// The fields of a section by name.
+type Fields = HashMap<String, String>;
+
+/// The `Server` section.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerInfo {
+    /// The version of the server, like `7.2.4`.
+    pub redis_version: String,
+    /// `standalone`, `sentinel` or `cluster`.
+    pub redis_mode: Option<String>,
+    /// The operating system of the server.
+    pub os: String,
+    /// The id of the server process.
+    pub process_id: u32,
+    /// A random value that identifies the server process.
+    pub run_id: String,
+    /// The TCP port the server listens on.
+    pub tcp_port: u16,
+    /// How long the server has been running.
+    pub uptime: Duration,
+}
+
+impl ServerInfo {
+    fn parse(fields: &Fields) -> RedisResult<ServerInfo> {
+        Ok(ServerInfo {
+            redis_version: parse_field(fields, "redis_version")?,
+            redis_mode: optional(fields, "redis_mode")?,
+            os: parse_field(fields, "os")?,
+            process_id: parse_field(fields, "process_id")?,
+            run_id: parse_field(fields, "run_id")?,
+            tcp_port: parse_field(fields, "tcp_port")?,
+            uptime: Duration::from_secs(parse_field(fields, "uptime_in_seconds")?),
+        })
+    }
+}
+
+/// The `Clients` section.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientsInfo {
+    /// The number of client connections, without replicas.
+    pub connected_clients: usize,
+    /// The number of clients waiting in a blocking command.
+    pub blocked_clients: usize,
+    /// The maximum number of clients (redis 7).
+    pub maxclients: Option<usize>,
+    /// The number of clients with client side caching (redis 6).
+    pub tracking_clients: Option<usize>,
+}
+
+impl ClientsInfo {
+    fn parse(fields: &Fields) -> RedisResult<ClientsInfo> {
+        Ok(ClientsInfo {
+            connected_clients: parse_field(fields, "connected_clients")?,
+            blocked_clients: parse_field(fields, "blocked_clients")?,
+            maxclients: optional(fields, "maxclients")?,
+            tracking_clients: optional(fields, "tracking_clients")?,
+        })
+    }
+}
+
+/// The `Memory` section.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryInfo {
+    /// The bytes allocated by the server.
+    pub used_memory: u64,
+    /// The bytes of memory the operating system reports for the process.
+    pub used_memory_rss: u64,
+    /// The highest `used_memory` so far.
+    pub used_memory_peak: u64,
+    /// The configured memory limit, `0` for none.
+    pub maxmemory: Option<u64>,
+    /// What the server evicts at the limit, like `allkeys-lru`.
+    pub maxmemory_policy: Option<String>,
+    /// `used_memory_rss` in relation to `used_memory`.
+    pub mem_fragmentation_ratio: Option<f64>,
+}
+
+impl MemoryInfo {
+    fn parse(fields: &Fields) -> RedisResult<MemoryInfo> {
+        Ok(MemoryInfo {
+            used_memory: parse_field(fields, "used_memory")?,
+            used_memory_rss: parse_field(fields, "used_memory_rss")?,
+            used_memory_peak: parse_field(fields, "used_memory_peak")?,
+            maxmemory: optional(fields, "maxmemory")?,
+            maxmemory_policy: optional(fields, "maxmemory_policy")?,
+            mem_fragmentation_ratio: optional(fields, "mem_fragmentation_ratio")?,
+        })
+    }
+}
+
+/// The `Persistence` section.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PersistenceInfo {
+    /// Whether a dump is being loaded.
+    pub loading: bool,
+    /// The number of changes since the last dump.
+    pub rdb_changes_since_last_save: u64,
+    /// Whether a dump is being saved.
+    pub rdb_bgsave_in_progress: bool,
+    /// The unix time of the last successful dump.
+    pub rdb_last_save_time: u64,
+    /// Whether the last dump succeeded, `ok` or `err`.
+    pub rdb_last_bgsave_status: String,
+    /// Whether the append only file is enabled.
+    pub aof_enabled: bool,
+    /// Whether the append only file is being rewritten.
+    pub aof_rewrite_in_progress: bool,
+}
+
+impl PersistenceInfo {
+    fn parse(fields: &Fields) -> RedisResult<PersistenceInfo> {
+        Ok(PersistenceInfo {
+            loading: parse_flag(fields, "loading")?,
+            rdb_changes_since_last_save: parse_field(fields, "rdb_changes_since_last_save")?,
+            rdb_bgsave_in_progress: parse_flag(fields, "rdb_bgsave_in_progress")?,
+            rdb_last_save_time: parse_field(fields, "rdb_last_save_time")?,
+            rdb_last_bgsave_status: parse_field(fields, "rdb_last_bgsave_status")?,
+            aof_enabled: parse_flag(fields, "aof_enabled")?,
+            aof_rewrite_in_progress: parse_flag(fields, "aof_rewrite_in_progress")?,
+        })
+    }
+}
+
+/// A replica of the server as listed in the `Replication` section, like
+/// `slave0:ip=127.0.0.1,port=6380,state=online,offset=1234,lag=0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplicaInfo {
+    /// The address of the replica.
+    pub ip: String,
+    /// The port of the replica.
+    pub port: u16,
+    /// The state of the replication, like `online` or
+    /// `wait_bgsave`.
+    pub state: String,
+    /// The replication offset the replica acknowledged.
+    pub offset: i64,
+    /// The seconds since the replica last acknowledged.
+    pub lag: Option<u64>,
+}
+
+impl ReplicaInfo {
+    fn parse(value: &str) -> RedisResult<ReplicaInfo> {
+        let fields: Fields = value
+            .split(',')
+            .map(|field| {
+                let mut parts = field.splitn(2, '=');
+                let key = parts.next().unwrap_or("");
+                (key.to_string(), parts.next().unwrap_or("").to_string())
+            })
+            .collect();
+        Ok(ReplicaInfo {
+            ip: parse_field(&fields, "ip")?,
+            port: parse_field(&fields, "port")?,
+            state: parse_field(&fields, "state")?,
+            offset: parse_field(&fields, "offset")?,
+            lag: optional(&fields, "lag")?,
+        })
+    }
+}
+
+/// The `Replication` section.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplicationInfo {
+    /// `master` or `slave`.
+    pub role: String,
+    /// The connected replicas of a master.
+    pub replicas: Vec<ReplicaInfo>,
+    /// The host of the master of a replica.
+    pub master_host: Option<String>,
+    /// The port of the master of a replica.
+    pub master_port: Option<u16>,
+    /// Whether a replica is connected to its master, `up` or `down`.
+    pub master_link_status: Option<String>,
+    /// The replication offset of the server.
+    pub master_repl_offset: Option<i64>,
+}
+
+impl ReplicationInfo {
+    fn parse(fields: &Fields) -> RedisResult<ReplicationInfo> {
+        let count: usize = optional(fields, "connected_slaves")?.unwrap_or(0);
+        let mut replicas = Vec::with_capacity(count);
+        for idx in 0..count {
+            if let Some(value) = fields.get(&format!("slave{}", idx)) {
+                replicas.push(ReplicaInfo::parse(value)?);
+            }
+        }
+        Ok(ReplicationInfo {
+            role: parse_field(fields, "role")?,
+            replicas,
+            master_host: optional(fields, "master_host")?,
+            master_port: optional(fields, "master_port")?,
+            master_link_status: optional(fields, "master_link_status")?,
+            master_repl_offset: optional(fields, "master_repl_offset")?,
+        })
+    }
+}
+
+/// The keys of a database as listed in the `Keyspace` section, like
+/// `db0:keys=1,expires=0,avg_ttl=0`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyspaceInfo {
+    /// The number of keys.
+    pub keys: u64,
+    /// The number of keys with an expiration.
+    pub expires: u64,
+    /// The estimated average time to live of the keys with an expiration.
+    pub avg_ttl: Duration,
+}
+
+impl KeyspaceInfo {
+    fn parse(value: &str) -> RedisResult<KeyspaceInfo> {
+        let fields: Fields = value
+            .split(',')
+            .map(|field| {
+                let mut parts = field.splitn(2, '=');
+                let key = parts.next().unwrap_or("");
+                (key.to_string(), parts.next().unwrap_or("").to_string())
+            })
+            .collect();
+        Ok(KeyspaceInfo {
+            keys: parse_field(&fields, "keys")?,
+            expires: parse_field(&fields, "expires")?,
+            avg_ttl: Duration::from_millis(optional(&fields, "avg_ttl")?.unwrap_or(0)),
+        })
+    }
+}
+
+/// The reply of `INFO`.  A section is `None` if it was not requested.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Info {
+    /// The `Server` section.
+    pub server: Option<ServerInfo>,
+    /// The `Clients` section.
+    pub clients: Option<ClientsInfo>,
+    /// The `Memory` section.
+    pub memory: Option<MemoryInfo>,
+    /// The `Persistence` section.
+    pub persistence: Option<PersistenceInfo>,
+    /// The `Replication` section.
+    pub replication: Option<ReplicationInfo>,
+    /// The databases with keys by index.
+    pub keyspace: BTreeMap<i64, KeyspaceInfo>,
+    /// All fields by lowercase section name, like `stats`.
+    pub sections: HashMap<String, HashMap<String, String>>,
+}
+
+impl Info {
+    /// Parses the text of an `INFO` reply.
+    pub fn parse(text: &str) -> RedisResult<Info> {
+        let mut sections: HashMap<String, Fields> = HashMap::new();
+        let mut section = String::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                section = line[1..].trim().to_lowercase();
+                sections.entry(section.clone()).or_insert_with(HashMap::new);
+                continue;
+            }
+            let mut parts = line.splitn(2, ':');
+            let key = parts.next().unwrap_or("");
+            let value = unwrap_or!(parts.next(), continue);
+            sections
+                .entry(section.clone())
+                .or_insert_with(HashMap::new)
+                .insert(key.to_string(), value.to_string());
+        }
+
+        let mut keyspace = BTreeMap::new();
+        if let Some(fields) = sections.get("keyspace") {
+            for (name, value) in fields {
+                if !name.starts_with("db") {
+                    continue;
+                }
+                let db = parse_value(name, &name[2..])?;
+                keyspace.insert(db, KeyspaceInfo::parse(value)?);
+            }
+        }
+        Ok(Info {
+            server: parse_section(&sections, "server", ServerInfo::parse)?,
+            clients: parse_section(&sections, "clients", ClientsInfo::parse)?,
+            memory: parse_section(&sections, "memory", MemoryInfo::parse)?,
+            persistence: parse_section(&sections, "persistence", PersistenceInfo::parse)?,
+            replication: parse_section(&sections, "replication", ReplicationInfo::parse)?,
+            keyspace,
+            sections,
+        })
+    }
+
+    /// Returns a field of any section by name, like `total_commands_processed`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.sections
+            .values()
+            .filter_map(|fields| fields.get(name))
+            .map(|value| &value[..])
+            .next()
+    }
+}
+
+impl FromRedisValue for Info {
+    fn from_redis_value(v: &Value) -> RedisResult<Info> {
+        let text: String = from_redis_value(v)?;
+        Info::parse(&text)
+    }
+}
+
+fn parse_section<T>(
+    sections: &HashMap<String, Fields>,
+    name: &str,
+    parse: fn(&Fields) -> RedisResult<T>,
+) -> RedisResult<Option<T>> {
+    match sections.get(name) {
+        Some(fields) => Ok(Some(parse(fields)?)),
+        None => Ok(None),
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> RedisResult<T> {
+    match value.parse() {
+        Ok(value) => Ok(value),
+        Err(_) => Err(RedisError::from((
+            ErrorKind::TypeError,
+            "Invalid info field",
+            format!("{}:{}", name, value),
+        ))),
+    }
+}
+
+fn parse_field<T: FromStr>(fields: &Fields, name: &str) -> RedisResult<T> {
+    match fields.get(name) {
+        Some(value) => parse_value(name, value),
+        None => Err(RedisError::from((
+            ErrorKind::TypeError,
+            "Info without field",
+            name.to_string(),
+        ))),
+    }
+}
+
+fn optional<T: FromStr>(fields: &Fields, name: &str) -> RedisResult<Option<T>> {
+    match fields.get(name) {
+        Some(value) => Ok(Some(parse_value(name, value)?)),
+        None => Ok(None),
+    }
+}
+
+// `0` or `1`
+fn parse_flag(fields: &Fields, name: &str) -> RedisResult<bool> {
+    let flag: u8 = parse_field(fields, name)?;
+    Ok(flag != 0)
+}
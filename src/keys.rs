@@ -0,0 +1,204 @@
+//! Types for the replies of the commands that inspect keys, like
+//! `OBJECT ENCODING`, `TTL` and `TYPE`.
+use std::time::Duration;
+
+use types::{FromRedisValue, RedisResult, RedisWrite, ToRedisArgs, Value};
+
+/// The remaining time to live of a key, see `Commands::ttl_typed`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ttl {
+    /// The key does not exist.
+    NoKey,
+    /// The key exists but does not expire.
+    NoExpiry,
+    /// The key expires after the duration.
+    Expires(Duration),
+}
+
+impl Ttl {
+    /// Interprets the reply of `TTL`: `-2` if the key does not exist, `-1`
+    /// if it does not expire and the seconds until it expires otherwise.
+    pub fn from_secs(ttl: i64) -> Ttl {
+        match ttl {
+            -1 => Ttl::NoExpiry,
+            ttl if ttl < 0 => Ttl::NoKey,
+            ttl => Ttl::Expires(Duration::from_secs(ttl as u64)),
+        }
+    }
+
+    /// Interprets the reply of `PTTL`, like `from_secs`.
+    pub fn from_millis(ttl: i64) -> Ttl {
+        match ttl {
+            -1 => Ttl::NoExpiry,
+            ttl if ttl < 0 => Ttl::NoKey,
+            ttl => Ttl::Expires(Duration::from_millis(ttl as u64)),
+        }
+    }
+
+    /// Returns the time until the key expires, if it does.
+    pub fn duration(&self) -> Option<Duration> {
+        match *self {
+            Ttl::Expires(duration) => Some(duration),
+            _ => None,
+        }
+    }
+}
+
+/// The internal representation of a value as reported by
+/// `OBJECT ENCODING`.
+///
+/// Redis picks a compact encoding for small values and switches to a
+/// general one when they grow, which makes a big difference in memory
+/// use.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ObjectEncoding {
+    /// A string that holds an integer.
+    Int,
+    /// A short string stored together with its object.
+    EmbStr,
+    /// A general string.
+    Raw,
+    /// A small list, hash, set or sorted set in a single allocation
+    /// (redis 7, formerly `ziplist`).
+    ListPack,
+    /// A small list, hash or sorted set before redis 7.
+    ZipList,
+    /// A list of list packs or zip lists.
+    QuickList,
+    /// A hash or set in a hash table.
+    HashTable,
+    /// A small set of integers.
+    IntSet,
+    /// A sorted set in a skip list.
+    SkipList,
+    /// A stream.
+    Stream,
+    /// Any other encoding, like `listpackex` of hashes with field
+    /// expirations.
+    Other(String),
+}
+
+impl ObjectEncoding {
+    /// Returns the encoding for a name used by redis.  Unknown names
+    /// become `Other`.
+    pub fn from_name(name: &str) -> ObjectEncoding {
+        match name {
+            "int" => ObjectEncoding::Int,
+            "embstr" => ObjectEncoding::EmbStr,
+            "raw" => ObjectEncoding::Raw,
+            "listpack" => ObjectEncoding::ListPack,
+            "ziplist" => ObjectEncoding::ZipList,
+            "quicklist" => ObjectEncoding::QuickList,
+            "hashtable" => ObjectEncoding::HashTable,
+            "intset" => ObjectEncoding::IntSet,
+            "skiplist" => ObjectEncoding::SkipList,
+            "stream" => ObjectEncoding::Stream,
+            other => ObjectEncoding::Other(other.to_string()),
+        }
+    }
+
+    /// Returns the name redis uses for the encoding.
+    pub fn name(&self) -> &str {
+        match *self {
+            ObjectEncoding::Int => "int",
+            ObjectEncoding::EmbStr => "embstr",
+            ObjectEncoding::Raw => "raw",
+            ObjectEncoding::ListPack => "listpack",
+            ObjectEncoding::ZipList => "ziplist",
+            ObjectEncoding::QuickList => "quicklist",
+            ObjectEncoding::HashTable => "hashtable",
+            ObjectEncoding::IntSet => "intset",
+            ObjectEncoding::SkipList => "skiplist",
+            ObjectEncoding::Stream => "stream",
+            ObjectEncoding::Other(ref name) => name,
+        }
+    }
+
+    /// Returns whether the encoding is one of the compact ones used for
+    /// small values.
+    pub fn is_compact(&self) -> bool {
+        match *self {
+            ObjectEncoding::Int
+            | ObjectEncoding::EmbStr
+            | ObjectEncoding::ListPack
+            | ObjectEncoding::ZipList
+            | ObjectEncoding::IntSet => true,
+            _ => false,
+        }
+    }
+}
+
+impl FromRedisValue for ObjectEncoding {
+    fn from_redis_value(v: &Value) -> RedisResult<ObjectEncoding> {
+        let name: String = FromRedisValue::from_redis_value(v)?;
+        Ok(ObjectEncoding::from_name(&name))
+    }
+}
+
+/// The type of the value of a key as reported by `TYPE`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum KeyType {
+    /// The key does not exist (`none`).
+    NoKey,
+    /// A string.
+    String,
+    /// A list.
+    List,
+    /// A set.
+    Set,
+    /// A sorted set.
+    ZSet,
+    /// A hash.
+    Hash,
+    /// A stream.
+    Stream,
+    /// Any other type, like the types of modules.
+    Other(String),
+}
+
+impl KeyType {
+    /// Returns the type for a name used by redis.  Unknown names become
+    /// `Other`.
+    pub fn from_name(name: &str) -> KeyType {
+        match name {
+            "none" => KeyType::NoKey,
+            "string" => KeyType::String,
+            "list" => KeyType::List,
+            "set" => KeyType::Set,
+            "zset" => KeyType::ZSet,
+            "hash" => KeyType::Hash,
+            "stream" => KeyType::Stream,
+            other => KeyType::Other(other.to_string()),
+        }
+    }
+
+    /// Returns the name redis uses for the type.
+    pub fn name(&self) -> &str {
+        match *self {
+            KeyType::NoKey => "none",
+            KeyType::String => "string",
+            KeyType::List => "list",
+            KeyType::Set => "set",
+            KeyType::ZSet => "zset",
+            KeyType::Hash => "hash",
+            KeyType::Stream => "stream",
+            KeyType::Other(ref name) => name,
+        }
+    }
+}
+
+impl ToRedisArgs for KeyType {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(self.name().as_bytes())
+    }
+}
+
+impl FromRedisValue for KeyType {
+    fn from_redis_value(v: &Value) -> RedisResult<KeyType> {
+        let name: String = FromRedisValue::from_redis_value(v)?;
+        Ok(KeyType::from_name(&name))
+    }
+}
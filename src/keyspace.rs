@@ -0,0 +1,321 @@
+//! Copies keys between servers.
+//!
+//! `KeyspaceCopy` scans the keys of a source server and copies each of
+//! them with `DUMP` and `RESTORE` to a target, which can be another server
+//! or a cluster.  The remaining time to live of every key is preserved.
+//! Keys are read and written in pipelined batches, and `copy_parallel`
+//! restores the batches over several target connections at once:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::keyspace::KeyspaceCopy;
+//!
+//! let source = redis::Client::open("redis://old-host/")?;
+//! let target = redis::Client::open("redis://new-host/")?;
+//! let progress = KeyspaceCopy::new()
+//!     .with_pattern("session:*")
+//!     .batch_size(500)
+//!     .copy(
+//!         &mut source.get_connection()?,
+//!         &mut target.get_connection()?,
+//!         |progress| println!("{} of {} keys copied", progress.copied, progress.scanned),
+//!     )?;
+//! # Ok(()) }
+//! ```
+//!
+//! The source is scanned with `SCAN`, so keys that are added or removed
+//! during the copy may or may not be copied.  On a cluster every master has
+//! to be copied as a source of its own.  Both servers need compatible
+//! versions, since `RESTORE` rejects dumps of newer versions.
+use std::sync::mpsc::{channel, sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use cmd::{cmd, pipe};
+use connection::ConnectionLike;
+use keys::KeyType;
+use options::{RestoreOptions, ScanOptions};
+use types::{ErrorKind, RedisResult, ToRedisArgs};
+
+/// How far a copy got.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CopyProgress {
+    /// The number of keys returned by `SCAN`.
+    pub scanned: u64,
+    /// The number of keys restored on the target.
+    pub copied: u64,
+    /// The number of keys that expired or were deleted before they were
+    /// dumped, or that exist on the target without `replace`.
+    pub skipped: u64,
+}
+
+/// Copies the keys that match a pattern or type, see the module
+/// documentation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyspaceCopy {
+    pattern: Option<Vec<Vec<u8>>>,
+    key_type: Option<KeyType>,
+    batch_size: usize,
+    replace: bool,
+}
+
+// A key dumped from the source with its time to live in milliseconds, 0
+// for keys that do not expire.
+struct Dumped {
+    key: Vec<u8>,
+    ttl: i64,
+    payload: Vec<u8>,
+}
+
+impl KeyspaceCopy {
+    /// Creates a copy of all keys in batches of 100 keys that fails on
+    /// keys that exist on the target.
+    pub fn new() -> KeyspaceCopy {
+        KeyspaceCopy {
+            pattern: None,
+            key_type: None,
+            batch_size: 100,
+            replace: false,
+        }
+    }
+
+    /// Only copies the keys matching the glob-style pattern (`MATCH`).
+    pub fn with_pattern<P: ToRedisArgs>(mut self, pattern: P) -> KeyspaceCopy {
+        self.pattern = Some(pattern.to_redis_args());
+        self
+    }
+
+    /// Only copies the keys of the type (`TYPE`, redis 6).
+    pub fn with_type(mut self, key_type: KeyType) -> KeyspaceCopy {
+        self.key_type = Some(key_type);
+        self
+    }
+
+    /// Sets how many keys are scanned, dumped and restored per round trip.
+    pub fn batch_size(mut self, batch_size: usize) -> KeyspaceCopy {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Overwrites keys that exist on the target (`REPLACE`) instead of
+    /// skipping them.
+    pub fn replace(mut self) -> KeyspaceCopy {
+        self.replace = true;
+        self
+    }
+
+    /// Copies the keys from `source` to `target`.  `progress` is called
+    /// after every batch.  Stops at the first error other than a key that
+    /// exists on the target.
+    pub fn copy<F>(
+        &self,
+        source: &mut ConnectionLike,
+        target: &mut ConnectionLike,
+        mut progress: F,
+    ) -> RedisResult<CopyProgress>
+    where
+        F: FnMut(&CopyProgress),
+    {
+        let mut state = CopyProgress::default();
+        let replace = self.replace;
+        self.scan(source, &mut state, |state, batch| {
+            let (copied, skipped) = restore(target, &batch, replace)?;
+            state.copied += copied;
+            state.skipped += skipped;
+            progress(state);
+            Ok(())
+        })?;
+        Ok(state)
+    }
+
+    /// Like `copy`, but restores batches over all `targets` concurrently,
+    /// each of them on a thread of its own.
+    pub fn copy_parallel<C, F>(
+        &self,
+        source: &mut ConnectionLike,
+        targets: Vec<C>,
+        mut progress: F,
+    ) -> RedisResult<CopyProgress>
+    where
+        C: ConnectionLike + Send + 'static,
+        F: FnMut(&CopyProgress),
+    {
+        if targets.is_empty() {
+            fail!((
+                ErrorKind::InvalidClientConfig,
+                "A parallel copy needs at least one target"
+            ));
+        }
+
+        // at most one batch per worker waits to be restored
+        let (batches, queue) = sync_channel::<Vec<Dumped>>(targets.len());
+        let queue = Arc::new(Mutex::new(queue));
+        let (done, results) = channel();
+        let workers: Vec<_> = targets
+            .into_iter()
+            .map(|mut target| {
+                let queue = queue.clone();
+                let done = done.clone();
+                let replace = self.replace;
+                thread::spawn(move || loop {
+                    let batch = match queue.lock().unwrap().recv() {
+                        Ok(batch) => batch,
+                        Err(_) => return,
+                    };
+                    if done.send(restore(&mut target, &batch, replace)).is_err() {
+                        return;
+                    }
+                })
+            })
+            .collect();
+        drop(done);
+
+        let mut state = CopyProgress::default();
+        let scanned = self.scan(source, &mut state, |state, batch| {
+            while let Ok(result) = results.try_recv() {
+                apply(state, result, &mut progress)?;
+            }
+            batches
+                .send(batch)
+                .map_err(|_| (ErrorKind::IoError, "The workers of the copy stopped").into())
+        });
+        drop(batches);
+        let finished = finish(&mut state, &results, &mut progress);
+        for worker in workers {
+            let _ = worker.join();
+        }
+        scanned.and(finished).map(|_| state)
+    }
+
+    // Scans the keys, dumps them in batches and hands every batch to
+    // `restore`.
+    fn scan<F>(
+        &self,
+        source: &mut ConnectionLike,
+        state: &mut CopyProgress,
+        mut restore: F,
+    ) -> RedisResult<()>
+    where
+        F: FnMut(&mut CopyProgress, Vec<Dumped>) -> RedisResult<()>,
+    {
+        let mut options = ScanOptions::new().with_count(self.batch_size);
+        if let Some(ref pattern) = self.pattern {
+            options = options.with_pattern(pattern);
+        }
+        if let Some(ref key_type) = self.key_type {
+            options = options.with_type(key_type.clone());
+        }
+
+        let mut cursor = 0u64;
+        loop {
+            let (next, keys): (u64, Vec<Vec<u8>>) =
+                cmd("SCAN").arg(cursor).arg(options.clone()).query(source)?;
+            state.scanned += keys.len() as u64;
+            for keys in keys.chunks(self.batch_size) {
+                let (batch, skipped) = dump(source, keys)?;
+                state.skipped += skipped;
+                restore(state, batch)?;
+            }
+            if next == 0 {
+                return Ok(());
+            }
+            cursor = next;
+        }
+    }
+}
+
+impl Default for KeyspaceCopy {
+    fn default() -> KeyspaceCopy {
+        KeyspaceCopy::new()
+    }
+}
+
+// Dumps the keys with their time to live.  Returns the dumps and the
+// number of keys that no longer exist.
+fn dump(source: &mut ConnectionLike, keys: &[Vec<u8>]) -> RedisResult<(Vec<Dumped>, u64)> {
+    let mut pipe = pipe();
+    for key in keys {
+        pipe.cmd("PTTL").arg(&key[..]).cmd("DUMP").arg(&key[..]);
+    }
+    let replies: Vec<(i64, Option<Vec<u8>>)> = pipe.query(source)?;
+    let mut batch = Vec::with_capacity(keys.len());
+    let mut skipped = 0;
+    for (key, (ttl, payload)) in keys.iter().zip(replies) {
+        match payload {
+            // -2 if the key expired between the two commands
+            Some(payload) if ttl != -2 => batch.push(Dumped {
+                key: key.clone(),
+                ttl: ttl.max(0),
+                payload,
+            }),
+            _ => skipped += 1,
+        }
+    }
+    Ok((batch, skipped))
+}
+
+// Restores the keys.  Returns the number of keys copied and skipped.
+fn restore(
+    target: &mut ConnectionLike,
+    batch: &[Dumped],
+    replace: bool,
+) -> RedisResult<(u64, u64)> {
+    if batch.is_empty() {
+        return Ok((0, 0));
+    }
+    let options = if replace {
+        RestoreOptions::new().replace()
+    } else {
+        RestoreOptions::new()
+    };
+    let mut pipe = pipe();
+    for dumped in batch {
+        pipe.cmd("RESTORE")
+            .arg(&dumped.key[..])
+            .arg(dumped.ttl)
+            .arg(&dumped.payload[..])
+            .arg(options);
+    }
+    let (mut copied, mut skipped) = (0, 0);
+    for result in pipe.query_each::<()>(target)? {
+        match result {
+            Ok(()) => copied += 1,
+            Err(ref err) if err.code() == Some("BUSYKEY") => skipped += 1,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok((copied, skipped))
+}
+
+fn apply<F>(
+    state: &mut CopyProgress,
+    result: RedisResult<(u64, u64)>,
+    progress: &mut F,
+) -> RedisResult<()>
+where
+    F: FnMut(&CopyProgress),
+{
+    let (copied, skipped) = result?;
+    state.copied += copied;
+    state.skipped += skipped;
+    progress(state);
+    Ok(())
+}
+
+// Waits for the workers to restore the remaining batches.
+fn finish<F>(
+    state: &mut CopyProgress,
+    results: &Receiver<RedisResult<(u64, u64)>>,
+    progress: &mut F,
+) -> RedisResult<()>
+where
+    F: FnMut(&CopyProgress),
+{
+    let mut rv = Ok(());
+    for result in results.iter() {
+        if rv.is_ok() {
+            rv = apply(state, result, progress);
+        }
+    }
+    rv
+}
@@ -0,0 +1,313 @@
+//! Leaderboards over sorted sets.
+//!
+//! A `Leaderboard` wraps the sorted set commands with the options that a
+//! ranking needs: the order of the ranks, how a new score of a member
+//! combines with its old one, and ranges around a member.  Members are
+//! written with `ToRedisArgs` and read with `FromRedisValue`:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::leaderboard::{Entry, Leaderboard, ScorePolicy};
+//!
+//! # let client = redis::Client::open("redis://127.0.0.1/")?;
+//! # let mut con = client.get_connection()?;
+//! let board = Leaderboard::new("highscores").policy(ScorePolicy::KeepBest);
+//! board.submit_score(&mut con, "alice", 1200.0)?;
+//! board.submit_score(&mut con, "bob", 900.0)?;
+//!
+//! let top: Vec<Entry<String>> = board.top(&mut con, 10)?;
+//! let neighbours: Vec<Entry<String>> = board.around(&mut con, "bob", 2)?;
+//! # Ok(()) }
+//! ```
+//!
+//! Ranks start at 0 like the ranks of `ZRANK`.  Members with the same score
+//! are ordered lexicographically.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use cmd::{cmd, pipe};
+use connection::ConnectionLike;
+use script::Script;
+use types::{FromRedisValue, RedisResult, ToRedisArgs};
+
+// Returns the rank of the first entry and the entries with their scores
+// around a member, or nil if the member has no score.
+const AROUND: &str = r"
+local rank = redis.call(ARGV[1], KEYS[1], ARGV[2])
+if not rank then
+    return false
+end
+local radius = tonumber(ARGV[3])
+local start = math.max(0, rank - radius)
+return {start, redis.call(ARGV[4], KEYS[1], start, rank + radius, 'WITHSCORES')}
+";
+
+// Renames the leaderboard to the archive if it exists.
+const ROTATE: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return 0
+end
+redis.call('RENAME', KEYS[1], KEYS[2])
+if tonumber(ARGV[1]) > 0 then
+    redis.call('PEXPIRE', KEYS[2], ARGV[1])
+end
+return 1
+";
+
+/// Which scores rank first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// The highest score has rank 0, the default.
+    HighestFirst,
+    /// The lowest score has rank 0, like for times of a race.
+    LowestFirst,
+}
+
+/// How a submitted score combines with the score a member has already.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScorePolicy {
+    /// The submitted score replaces the old one, the default.
+    Replace,
+    /// The better of both scores is kept, according to the `Order`
+    /// (`ZADD GT` or `ZADD LT`, redis 6.2).
+    KeepBest,
+    /// The submitted score is added to the old one (`ZINCRBY`).
+    Increment,
+}
+
+/// A member of a leaderboard with its score and rank.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry<M> {
+    /// The member.
+    pub member: M,
+    /// The score of the member.
+    pub score: f64,
+    /// The rank of the member, starting at 0.
+    pub rank: u64,
+}
+
+/// A ranking of members by score, stored in a sorted set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Leaderboard {
+    key: String,
+    order: Order,
+    policy: ScorePolicy,
+    expire_after: Option<Duration>,
+}
+
+impl Leaderboard {
+    /// Creates a leaderboard stored at `key` that ranks the highest scores
+    /// first and replaces old scores.
+    pub fn new(key: &str) -> Leaderboard {
+        Leaderboard {
+            key: key.to_string(),
+            order: Order::HighestFirst,
+            policy: ScorePolicy::Replace,
+            expire_after: None,
+        }
+    }
+
+    /// Creates a leaderboard that is reset every `period`, like a daily or
+    /// weekly ranking.  The leaderboard of the period that contains `at`
+    /// is stored at `prefix:N`, where `N` counts the periods since the
+    /// epoch, so the leaderboard of the previous period is the one at
+    /// `at - period`.  Use `expire_after` to drop old periods.
+    pub fn for_period(prefix: &str, period: Duration, at: SystemTime) -> Leaderboard {
+        let elapsed = at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::from_secs(0));
+        let index = elapsed.as_secs() / period.as_secs().max(1);
+        Leaderboard::new(&format!("{}:{}", prefix, index))
+    }
+
+    /// Sets which scores rank first.
+    pub fn order(mut self, order: Order) -> Leaderboard {
+        self.order = order;
+        self
+    }
+
+    /// Sets how submitted scores combine with old ones.
+    pub fn policy(mut self, policy: ScorePolicy) -> Leaderboard {
+        self.policy = policy;
+        self
+    }
+
+    /// Lets the leaderboard expire `ttl` after the last submitted score.
+    pub fn expire_after(mut self, ttl: Duration) -> Leaderboard {
+        self.expire_after = Some(ttl);
+        self
+    }
+
+    /// Returns the key of the sorted set.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Submits a score of a member and returns the score the member has
+    /// now, which differs from the submitted one unless the policy is
+    /// `Replace`.
+    pub fn submit_score<M: ToRedisArgs>(
+        &self,
+        con: &mut ConnectionLike,
+        member: M,
+        score: f64,
+    ) -> RedisResult<f64> {
+        let mut pipe = pipe();
+        pipe.atomic();
+        match self.policy {
+            ScorePolicy::Replace => {
+                pipe.cmd("ZADD").arg(&self.key).arg(score).arg(&member);
+            }
+            ScorePolicy::KeepBest => {
+                let comparison = match self.order {
+                    Order::HighestFirst => "GT",
+                    Order::LowestFirst => "LT",
+                };
+                pipe.cmd("ZADD")
+                    .arg(&self.key)
+                    .arg(comparison)
+                    .arg(score)
+                    .arg(&member);
+            }
+            ScorePolicy::Increment => {
+                pipe.cmd("ZINCRBY").arg(&self.key).arg(score).arg(&member);
+            }
+        }
+        pipe.ignore();
+        if let Some(ttl) = self.expire_after {
+            pipe.cmd("PEXPIRE").arg(&self.key).arg(millis(ttl)).ignore();
+        }
+        pipe.cmd("ZSCORE").arg(&self.key).arg(&member);
+        let (score,): (f64,) = pipe.query(con)?;
+        Ok(score)
+    }
+
+    /// Removes members from the leaderboard and returns how many had a
+    /// score.
+    pub fn remove<M: ToRedisArgs>(&self, con: &mut ConnectionLike, members: M) -> RedisResult<u64> {
+        cmd("ZREM").arg(&self.key).arg(members).query(con)
+    }
+
+    /// Returns the rank of a member, none if it has no score.
+    pub fn rank_of<M: ToRedisArgs>(
+        &self,
+        con: &mut ConnectionLike,
+        member: M,
+    ) -> RedisResult<Option<u64>> {
+        cmd(self.rank_cmd()).arg(&self.key).arg(member).query(con)
+    }
+
+    /// Returns the score of a member, none if it has no score.
+    pub fn score_of<M: ToRedisArgs>(
+        &self,
+        con: &mut ConnectionLike,
+        member: M,
+    ) -> RedisResult<Option<f64>> {
+        cmd("ZSCORE").arg(&self.key).arg(member).query(con)
+    }
+
+    /// Returns the number of members.
+    pub fn len(&self, con: &mut ConnectionLike) -> RedisResult<u64> {
+        cmd("ZCARD").arg(&self.key).query(con)
+    }
+
+    /// Returns the `n` best ranked members.
+    pub fn top<M: FromRedisValue>(
+        &self,
+        con: &mut ConnectionLike,
+        n: usize,
+    ) -> RedisResult<Vec<Entry<M>>> {
+        if n == 0 {
+            return Ok(vec![]);
+        }
+        self.range(con, 0, n - 1)
+    }
+
+    /// Returns the members ranked from `start` to `stop`, inclusive.
+    pub fn range<M: FromRedisValue>(
+        &self,
+        con: &mut ConnectionLike,
+        start: usize,
+        stop: usize,
+    ) -> RedisResult<Vec<Entry<M>>> {
+        let entries: Vec<(M, f64)> = cmd(self.range_cmd())
+            .arg(&self.key)
+            .arg(start)
+            .arg(stop)
+            .arg("WITHSCORES")
+            .query(con)?;
+        Ok(ranked(start as u64, entries))
+    }
+
+    /// Returns the member with up to `radius` members ranked right before
+    /// and after it, none if the member has no score.  The rank and the
+    /// range are read atomically.
+    pub fn around<M: ToRedisArgs, R: FromRedisValue>(
+        &self,
+        con: &mut ConnectionLike,
+        member: M,
+        radius: usize,
+    ) -> RedisResult<Vec<Entry<R>>> {
+        let around: Option<(u64, Vec<(R, f64)>)> = Script::new(AROUND)
+            .key(&self.key)
+            .arg(self.rank_cmd())
+            .arg(member)
+            .arg(radius)
+            .arg(self.range_cmd())
+            .invoke(con)?;
+        Ok(match around {
+            Some((start, entries)) => ranked(start, entries),
+            None => vec![],
+        })
+    }
+
+    /// Deletes the leaderboard.  Returns whether it had members.
+    pub fn reset(&self, con: &mut ConnectionLike) -> RedisResult<bool> {
+        cmd("DEL").arg(&self.key).query(con)
+    }
+
+    /// Moves the leaderboard to `archive_key`, replacing what was stored
+    /// there, and starts an empty one.  The archive expires after `keep`
+    /// if given.  Returns whether the leaderboard had members.
+    pub fn rotate(
+        &self,
+        con: &mut ConnectionLike,
+        archive_key: &str,
+        keep: Option<Duration>,
+    ) -> RedisResult<bool> {
+        Script::new(ROTATE)
+            .key(&self.key)
+            .key(archive_key)
+            .arg(keep.map_or(0, millis))
+            .invoke(con)
+    }
+
+    fn rank_cmd(&self) -> &'static str {
+        match self.order {
+            Order::HighestFirst => "ZREVRANK",
+            Order::LowestFirst => "ZRANK",
+        }
+    }
+
+    fn range_cmd(&self) -> &'static str {
+        match self.order {
+            Order::HighestFirst => "ZREVRANGE",
+            Order::LowestFirst => "ZRANGE",
+        }
+    }
+}
+
+fn ranked<M>(start: u64, entries: Vec<(M, f64)>) -> Vec<Entry<M>> {
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(i, (member, score))| Entry {
+            member,
+            score,
+            rank: start + i as u64,
+        })
+        .collect()
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_nanos() / 1_000_000)
+}
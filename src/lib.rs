@@ -50,18 +50,78 @@
 //!
 //! ## Optional Features
 //!
-//! There are currently two features defined that can enable additional
+//! There are currently a few features defined that can enable additional
 //! functionality if so desired.
 //!
+//! `tokio-comp` (enabled by default):
+//!   Provides the asynchronous API in the `aio` module on top of the tokio
+//!   runtime.  Disable the default features if you only need the blocking
+//!   API and do not want to pull in tokio.
+//!
+//! `async-std-comp`:
+//!   Provides the same asynchronous API on top of async-std instead, which
+//!   runs the sockets, timers and the tasks of multiplexed connections.
+//!   Enable it with the default features disabled to leave out the tokio
+//!   runtime; only tokio's runtime independent io and sync crates are
+//!   still used.  The API is built on futures 0.1, use the compat layer of
+//!   futures 0.3 to await its futures.  If both runtime features are
+//!   enabled, tokio is used.  This is also the feature for wasm32 targets,
+//!   which have no sockets: connect with `aio::Connection::from_transport`
+//!   over an `aio::WebSocketTransport` there.
+//!
 //! `with-unix-sockets`:
 //!   By default this library does not support unix sockets on older versions
 //!   of Rust but you can optionally compile it with unix sockets enabled by
 //!   enabling the `with-unix-sockets` feature flag.  On rust 1.10 or later
 //!   this is not needed.
 //!
+//! `with-pool`:
+//!   Enables the `pool` module with a simple pool of blocking connections
+//!   which is available through `Client::get_pool`, and of async
+//!   connections through `Client::get_async_pool`.
+//!
+//! `with-io-uring`:
+//!   Enables the `uring` module on Linux, which runs async connections
+//!   over io_uring instead of epoll.  Needs Linux 5.6 or later.
+//!
+//! `with-vectored-writes`:
+//!   Sends pipelines of blocking connections with vectored writes, so
+//!   that large arguments are written straight from the commands instead
+//!   of being copied into one buffer first.  Needs Rust 1.36 or later.
+//!
+//! `with-nonzero`:
+//!   Implements `ToRedisArgs` and `FromRedisValue` for the non-zero
+//!   integer types of `std::num`.  Needs Rust 1.28 or later.
+//!
+//! `tls-native`:
+//!   Enables connections secured with TLS through the `rediss://` URL
+//!   scheme.  This uses native-tls, that is the TLS implementation and
+//!   trust store of the platform.
+//!
 //! `with-rustc-json`:
 //!   This feature flag enables the `rustc_serialize` JSON support.
 //!
+//! `with-bloom`:
+//!   Enables the commands of the RedisBloom module, like `bf_add`, and the
+//!   `bloom` module with their replies.
+//!
+//! `with-search`:
+//!   Enables the `search` module and the `ft_*` commands of the RediSearch
+//!   module.
+//!
+//! `with-timeseries`:
+//!   Enables the `timeseries` module and the `ts_*` commands of the
+//!   RedisTimeSeries module.
+//!
+//! `test-support`:
+//!   Enables the `test_support` module with a `TestContext` that starts a
+//!   throwaway `redis-server` for integration tests, and the `debug_*`
+//!   commands.
+//!
+//! `opentelemetry`:
+//!   Enables the `telemetry` module with an interceptor that records an
+//!   OpenTelemetry span for every command.
+//!
 //! ## Connection Parameters
 //!
 //! redis-rs knows different ways to define where a connection should
@@ -74,15 +134,72 @@
 //!
 //! The URL format is `redis://[:<passwd>@]<hostname>[:port][/<db>]`
 //!
+//! With the `tls-native` feature the `rediss://` scheme connects over TLS
+//! instead, using the same format.  Servers that require mutual TLS need a
+//! client certificate, which is configured through the `tls` field of
+//! `ConnectionInfo`:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::IntoConnectionInfo;
+//!
+//! let mut info = "rediss://redis.example.com/".into_connection_info()?;
+//! info.tls = info.tls.with_client_cert_files("client.crt", "client.key")?;
+//! let client = redis::Client::open(info)?;
+//! # Ok(()) }
+//! ```
+//!
+//! Certificates signed by a private authority are accepted after adding it
+//! with `TlsOptions::with_root_cert_file`.  For development servers with
+//! self-signed certificates `insecure=true` disables verification
+//! altogether.
+//!
+//! The `connect_timeout` and `response_timeout` query parameters set the
+//! corresponding fields of `ConnectionInfo`.  Both are given in seconds and
+//! may be fractional or carry an `s` or `ms` suffix, for instance
+//! `redis://127.0.0.1/?connect_timeout=500ms&response_timeout=2s`.
+//!
+//! The database can also be given as `db=<db>` parameter and
+//! `client_name=<name>` names the connection with `CLIENT SETNAME` right
+//! after connecting.  The name and version of this library are reported
+//! with `CLIENT SETINFO` unless `set_lib_info=false` is given.
+//! `protocol=resp3` switches the connection to the RESP3
+//! protocol of redis 6 with `HELLO 3`; older servers keep speaking RESP2,
+//! which is also the default.  The new reply types of RESP3 convert to the
+//! same Rust types as their RESP2 counterparts.
+//!
+//! Additional servers that are tried when the first one cannot be reached
+//! can be given with one `fallback=<hostname>[:port]` parameter each.  With
+//! `shuffle_addrs=true` the servers are tried starting at a random one.
+//!
+//! Host names are resolved on every connection attempt, so reconnects pick
+//! up changed DNS records.  All addresses a name resolves to are tried in
+//! turn; `shuffle_resolved_addrs=true` starts at a random one.
+//!
+//! Redis clusters are supported by the `cluster` module.
+//!
+//! Deployments managed by redis sentinel are reached through a
+//! `ConnectionAddr::Sentinel` address, see the `sentinel` module, or a URL
+//! in this format:
+//!
+//! `redis+sentinel://[:<passwd>@]<host>[:port][,<host>[:port]...]/<service>[/<db>]`
+//!
+//! The hosts are the sentinels, which listen on port 26379 by default.
+//! The password is the one of the redis nodes; `sentinel_password=<passwd>`
+//! sets the one of the sentinels.  `role=replica` connects to a replica
+//! instead of the master and `role=any` to the master or a replica.  All
+//! other parameters work like for `redis://` URLs and `rediss+sentinel://`
+//! uses TLS.
+//!
 //! In case the crate is compiled with Unix socket support you can also
 //! use a unix URL in this format:
 //!
-//! `redis+unix:///[:<passwd>@]<path>[?db=<db>]`
+//! `redis+unix:///[:<passwd>@]<path>[?db=<db>][&password=<passwd>]`
 //!
 //! For compatibility with some other redis libraries, the "unix" scheme
 //! is also supported:
 //!
-//! `unix:///[:<passwd>@]<path>[?db=<db>]`
+//! `unix:///[:<passwd>@]<path>[?db=<db>][&password=<passwd>]`
 //!
 //! ## Executing Low-Level Commands
 //!
@@ -177,6 +294,26 @@
 //! instead of `arg` so that the library knows which argument needs updating
 //! as the query is run for more items.
 //!
+//! Items that cannot be converted into the item type are skipped.  Keys are
+//! binary safe in redis, so iterating over them as `String` leaves out keys
+//! that are not valid UTF-8.  Iterate as `Vec<u8>` to see every key:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+//! # let mut con = client.get_connection().unwrap();
+//! use redis::Commands;
+//!
+//! for key in con.scan::<Vec<u8>>()? {
+//!     // do something with the key
+//! }
+//! // fields and values of hashes work the same way
+//! for (field, value) in con.hscan::<_, (Vec<u8>, Vec<u8>)>("my_hash")? {
+//!     // do something with the pair
+//! }
+//! # Ok(()) }
+//! ```
+//!
 //! # Pipelining
 //!
 //! In addition to simple queries you can also send command pipelines.  This
@@ -254,7 +391,9 @@
 //! # Ok(()) }
 //! ```
 //!
-//! For more information see the `transaction` function.
+//! For more information see the `transaction` function.  To give up after
+//! a number of conflicting modifications use `transaction_with_retries`,
+//! async connections have the same helpers in the `aio` module.
 //!
 //! # PubSub
 //!
@@ -301,10 +440,30 @@
 //! # Ok(()) }
 //! ```
 //!
+//! # Streams
+//!
+//! The reply types of the stream commands as well as helpers for
+//! consuming streams live in the `streams` module.  For instance
+//! `StreamFanIn` reads several streams at once and yields their entries
+//! merged by id:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+//! # let mut con = client.get_connection().unwrap();
+//! let reader = redis::streams::StreamFanIn::new(&mut con, &["shard:0", "shard:1"], "0")?;
+//! for entry in reader {
+//!     let (key, entry) = entry?;
+//!     println!("{}: {}", key, entry.id);
+//! }
+//! # Ok(()) }
+//! ```
+//!
 //! # Async
 //!
 //! In addition to the synchronous interface that's been explained above there also exists an
-//! asynchronous interface based on [`futures`][] and [`tokio`][].
+//! asynchronous interface based on [`futures`][] and [`tokio`][].  It is only available with the
+//! `tokio-comp` feature, which is enabled by default, or with the `async-std-comp` feature.
 //!
 //! This interface exists under the `async` module and largely mirrors the synchronous with a few
 //! concessions to make it fit the constraints of `futures`.
@@ -345,6 +504,29 @@
 //! # }
 //! ```
 //!
+//! The high-level commands are available for asynchronous connections through the
+//! `AsyncCommands` trait.  Like `query_async` every method takes the connection by value and
+//! hands it back together with the result:
+//!
+//! ```rust,no_run
+//! extern crate redis;
+//! extern crate futures;
+//! extern crate tokio;
+//!
+//! use futures::Future;
+//! use redis::AsyncCommands;
+//!
+//! # fn main() {
+//! let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+//!
+//! tokio::run(client.get_async_connection().and_then(|con| {
+//!     con.set("key1", b"foo")
+//!         .and_then(|(con, ()): (_, ())| con.get("key1"))
+//!         .map(|(_con, value): (_, String)| assert_eq!(value, "foo"))
+//! }).map_err(|err| panic!("{}", err)));
+//! # }
+//! ```
+//!
 //! [`futures`]:https://crates.io/crates/futures
 //! [`tokio`]:https://tokio.rs
 //!
@@ -359,26 +541,73 @@
 
 #[macro_use]
 extern crate combine;
-extern crate bytes;
 extern crate dtoa;
 extern crate itoa;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate net2;
 extern crate sha1;
 extern crate url;
 #[macro_use]
 extern crate futures;
+
+#[cfg(feature = "aio")]
+extern crate bytes;
+#[cfg(feature = "aio")]
+extern crate tokio_codec;
+#[cfg(feature = "tokio-comp")]
 extern crate tokio_executor;
-#[macro_use]
+#[cfg(feature = "aio")]
 extern crate tokio_io;
-extern crate tokio_codec;
+#[cfg(feature = "aio")]
 extern crate tokio_sync;
+#[cfg(feature = "tokio-comp")]
 extern crate tokio_tcp;
+#[cfg(feature = "tokio-comp")]
+extern crate tokio_timer;
 
+#[cfg(feature = "async-std-comp")]
+extern crate async_std;
+#[cfg(feature = "with-bincode")]
+extern crate bincode;
+#[cfg(feature = "with-chrono")]
+extern crate chrono;
+#[cfg(feature = "async-std-comp")]
+extern crate futures_util;
+#[cfg(all(feature = "with-io-uring", target_os = "linux"))]
+extern crate io_uring;
+#[cfg(all(feature = "with-io-uring", target_os = "linux"))]
+extern crate libc;
+#[cfg(feature = "with-lz4")]
+extern crate lz4;
+#[cfg(all(feature = "with-io-uring", target_os = "linux"))]
+extern crate mio;
+#[cfg(feature = "tls-native")]
+extern crate native_tls;
+#[cfg(feature = "opentelemetry")]
+extern crate opentelemetry;
+#[cfg(feature = "with-msgpack")]
+extern crate rmp_serde;
 #[cfg(feature = "with-rustc-json")]
 pub extern crate rustc_serialize as serialize;
+#[cfg(feature = "json")]
+pub extern crate serde_json;
+#[cfg(feature = "with-time")]
+extern crate time;
+#[cfg(all(feature = "with-io-uring", target_os = "linux"))]
+extern crate tokio_reactor;
+#[cfg(all(feature = "tls-native", feature = "aio"))]
+extern crate tokio_tls;
 #[cfg(feature = "with-unix-sockets")]
 extern crate tokio_uds;
 #[cfg(feature = "with-unix-sockets")]
 extern crate unix_socket;
+#[cfg(feature = "with-uuid")]
+extern crate uuid;
+#[cfg(feature = "with-zstd")]
+extern crate zstd;
+#[cfg(feature = "with-serde")]
+#[macro_use]
+extern crate serde;
 
 #[doc(hidden)]
 #[cfg(feature = "with-rustc-json")]
@@ -386,19 +615,29 @@ pub use serialize::json::Json;
 
 // public api
 pub use client::Client;
+#[cfg(feature = "aio")]
+pub use cmd::AsyncIter;
 pub use cmd::{cmd, pack_command, pipe, Cmd, Iter, Pipeline};
+#[cfg(feature = "aio")]
+pub use commands::AsyncCommands;
 pub use commands::{Commands, ControlFlow, PipelineCommands, PubSubCommands};
 pub use connection::{
-    parse_redis_url, transaction, Connection, ConnectionAddr, ConnectionInfo, ConnectionLike,
-    IntoConnectionInfo, Msg, PubSub,
+    parse_redis_url, transaction, transaction_with_retries, Connection, ConnectionAddr,
+    ConnectionInfo, ConnectionLike, IntoConnectionInfo, Msg, ProtocolVersion, PubSub, ReplyIter,
+    ReplyLimits, TcpOptions, TlsOptions, Transport,
 };
-pub use parser::{parse_async, parse_redis_value, Parser};
+#[cfg(feature = "aio")]
+pub use parser::parse_async;
+pub use parser::{parse_redis_value, parse_redis_value_ref, Parser};
 pub use script::{Script, ScriptInvocation};
 
 pub use types::{
     // utility functions
     from_redis_value,
 
+    // argument buffers
+    ArgBuffer,
+
     // error kinds
     ErrorKind,
 
@@ -408,6 +647,8 @@ pub use types::{
     // utility types
     InfoDict,
     NumericBehavior,
+    PushInfo,
+    PushKind,
 
     // error and result types
     RedisError,
@@ -417,16 +658,67 @@ pub use types::{
 
     // low level values
     Value,
+    ValueRef,
 };
 
 mod macros;
 
+#[cfg(feature = "aio")]
 pub mod aio;
+#[cfg(feature = "with-bloom")]
+pub mod bloom;
+#[cfg(feature = "with-serde")]
+pub mod cache;
+pub mod caching;
+pub mod clients;
+pub mod cluster;
+#[cfg(feature = "with-serde")]
+pub mod codec;
+#[cfg(any(feature = "with-lz4", feature = "with-zstd"))]
+pub mod compression;
+#[cfg(any(feature = "with-chrono", feature = "with-time"))]
+pub mod datetime;
+pub mod functions;
+pub mod geo;
+#[cfg(feature = "with-serde")]
+pub mod hash;
+pub mod info;
+pub mod keys;
+pub mod keyspace;
+pub mod leaderboard;
+pub mod metrics;
+pub mod middleware;
+pub mod mock;
+pub mod monitor;
+pub mod namespace;
+pub mod notifications;
+pub mod options;
+#[cfg(feature = "with-pool")]
+pub mod pool;
+pub mod ratelimit;
+pub mod resp;
+pub mod retry;
+#[cfg(feature = "with-search")]
+pub mod search;
+pub mod sentinel;
+pub mod server;
+pub mod streams;
+#[cfg(feature = "opentelemetry")]
+pub mod telemetry;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+#[cfg(feature = "with-timeseries")]
+pub mod timeseries;
+#[cfg(all(feature = "with-io-uring", target_os = "linux"))]
+pub mod uring;
 
 mod client;
+mod cluster_routing;
 mod cmd;
 mod commands;
 mod connection;
 mod parser;
+#[cfg(feature = "aio")]
+mod runtime;
 mod script;
 mod types;
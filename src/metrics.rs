@@ -0,0 +1,98 @@
+//! Hooks for exporting metrics of commands and connections.
+//!
+//! A `MetricsObserver` set with `Client::set_metrics_observer` is told
+//! about the commands of the synchronous connections of the client, about
+//! connects and failed connects, about reconnects of connection managers
+//! and about checkouts from pools created from the client.  Commands sent
+//! over async connections are not reported.
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//! use redis::metrics::{CommandEvent, MetricsObserver};
+//!
+//! #[derive(Default)]
+//! struct Counters {
+//!     commands: AtomicUsize,
+//!     errors: AtomicUsize,
+//! }
+//!
+//! impl MetricsObserver for Counters {
+//!     fn command_completed(&self, event: &CommandEvent) {
+//!         self.commands.fetch_add(event.commands, Ordering::Relaxed);
+//!         if event.error.is_some() {
+//!             self.errors.fetch_add(1, Ordering::Relaxed);
+//!         }
+//!     }
+//! }
+//!
+//! let mut client = redis::Client::open("redis://127.0.0.1/")?;
+//! let counters = Arc::new(Counters::default());
+//! client.set_metrics_observer(Some(counters.clone()));
+//! # Ok(()) }
+//! ```
+use std::time::Duration;
+
+use connection::ConnectionAddr;
+use types::RedisError;
+
+/// A command or a pipeline that was answered, or that failed.
+#[derive(Debug)]
+pub struct CommandEvent<'a> {
+    /// The name of the command in upper case, like `GET`, or `PIPELINE`
+    /// for pipelines and transactions.
+    pub name: &'a str,
+    /// The number of commands that were sent.
+    pub commands: usize,
+    /// The time from sending the first command until the last reply was
+    /// read.
+    pub latency: Duration,
+    /// The number of bytes sent.
+    pub bytes_sent: usize,
+    /// The number of bytes received.
+    pub bytes_received: usize,
+    /// The error if the request failed.
+    pub error: Option<&'a RedisError>,
+}
+
+/// Receives metrics events.  All methods do nothing by default.
+///
+/// The methods are called on the thread of the connection while it waits,
+/// so they should be fast.
+pub trait MetricsObserver: Send + Sync {
+    /// A command or a pipeline is about to be sent.
+    fn command_issued(&self, _name: &str) {}
+
+    /// A command or a pipeline was answered or failed.
+    fn command_completed(&self, _event: &CommandEvent) {}
+
+    /// A connection to the server was established.
+    fn connected(&self, _addr: &ConnectionAddr) {}
+
+    /// A connection to the server could not be established.
+    fn connection_failed(&self, _addr: &ConnectionAddr, _error: &RedisError) {}
+
+    /// A connection manager replaced its broken connection.
+    fn reconnected(&self, _addr: &ConnectionAddr) {}
+
+    /// A connection was checked out of a pool after waiting for `wait`.
+    fn pool_checkout(&self, _wait: Duration) {}
+}
+
+// Returns the name of a packed command in upper case, the third line of
+// `*<argc>\r\n$<len>\r\n<name>\r\n`.
+pub fn command_name(packed: &[u8]) -> String {
+    let mut lines = packed.split(|&b| b == b'\n');
+    match (lines.next(), lines.next(), lines.next()) {
+        (Some(_), Some(_), Some(name)) => {
+            let name = if name.ends_with(b"\r") {
+                &name[..name.len() - 1]
+            } else {
+                name
+            };
+            String::from_utf8_lossy(name).to_uppercase()
+        }
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,266 @@
+//! Interceptors that observe, rewrite or answer the commands of a
+//! connection.
+//!
+//! An `InterceptedConnection` wraps any connection and hands every command
+//! to its interceptors before it is sent and together with its reply and
+//! the time it took afterwards.  This allows audit logging, metrics, key
+//! prefixes or injected failures without changing the call sites:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use std::time::Duration;
+//! use redis::middleware::{Command, InterceptedConnection, Interceptor};
+//! use redis::{Commands, RedisResult, Value};
+//!
+//! struct SlowLog;
+//!
+//! impl Interceptor for SlowLog {
+//!     fn after(&mut self, command: &Command, elapsed: Duration, _: &RedisResult<Value>) {
+//!         if elapsed > Duration::from_millis(10) {
+//!             println!("{} took {:?}", command.name(), elapsed);
+//!         }
+//!     }
+//! }
+//!
+//! let client = redis::Client::open("redis://127.0.0.1/")?;
+//! let mut con = InterceptedConnection::new(client.get_connection()?).with(SlowLog);
+//! let _: () = con.set("my_key", 42)?;
+//! # Ok(()) }
+//! ```
+//!
+//! Pipelines pass each of their commands through the interceptors, the
+//! elapsed time is that of the whole pipeline then.  Transactions include
+//! `MULTI` and `EXEC`, their commands see the `QUEUED` reply and `EXEC`
+//! the replies of all of them.
+use std::time::{Duration, Instant};
+
+use cluster_routing::parse_packed_commands;
+use cmd::{pack_commands, Cmd};
+use connection::ConnectionLike;
+use types::{ErrorKind, RedisResult, Value};
+
+/// A command that passes through an `InterceptedConnection`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    args: Vec<Vec<u8>>,
+    node: Option<String>,
+}
+
+impl Command {
+    /// Creates a command from its arguments, the first one being the name.
+    pub fn new(args: Vec<Vec<u8>>) -> Command {
+        Command { args, node: None }
+    }
+
+    /// Returns the name of the command in upper case, like `GET`.
+    pub fn name(&self) -> String {
+        match self.args.first() {
+            Some(name) => String::from_utf8_lossy(name).to_uppercase(),
+            None => String::new(),
+        }
+    }
+
+    /// Returns the arguments of the command including its name.
+    pub fn args(&self) -> &[Vec<u8>] {
+        &self.args
+    }
+
+    /// Returns the arguments for rewriting the command.
+    pub fn args_mut(&mut self) -> &mut Vec<Vec<u8>> {
+        &mut self.args
+    }
+
+    /// Returns the node that served the command, as `host:port`, once it
+    /// was sent over a connection that reports it, see
+    /// `ConnectionLike::last_node`.  For pipelines on a cluster this is the
+    /// node of the last request.
+    pub fn node(&self) -> Option<&str> {
+        self.node.as_ref().map(|node| &node[..])
+    }
+
+    fn to_cmd(&self) -> Cmd {
+        let mut rv =
+            Cmd::with_capacity(self.args.len(), self.args.iter().map(|arg| arg.len()).sum());
+        for arg in &self.args {
+            rv.arg(&arg[..]);
+        }
+        rv
+    }
+}
+
+/// Observes and changes the commands of an `InterceptedConnection`.
+///
+/// Both methods do nothing by default.
+pub trait Interceptor: Send {
+    /// Called before a command is sent.  The command may be rewritten.  If
+    /// a result is returned, the command is not sent and the result is used
+    /// as its reply; the remaining interceptors are not asked then.
+    fn before(&mut self, _command: &mut Command) -> Option<RedisResult<Value>> {
+        None
+    }
+
+    /// Called with the reply to a command and how long it took, also when
+    /// the reply came from `before`.
+    fn after(&mut self, _command: &Command, _elapsed: Duration, _result: &RedisResult<Value>) {}
+}
+
+/// A connection that passes its commands through interceptors, see the
+/// module documentation.
+pub struct InterceptedConnection<C> {
+    con: C,
+    interceptors: Vec<Box<Interceptor>>,
+}
+
+impl<C: ConnectionLike> InterceptedConnection<C> {
+    /// Wraps a connection without interceptors.
+    pub fn new(con: C) -> InterceptedConnection<C> {
+        InterceptedConnection {
+            con,
+            interceptors: vec![],
+        }
+    }
+
+    /// Adds an interceptor.  Interceptors see commands in the order they
+    /// were added and replies in the reverse order.
+    pub fn with<I: Interceptor + 'static>(mut self, interceptor: I) -> InterceptedConnection<C> {
+        self.add_interceptor(interceptor);
+        self
+    }
+
+    /// Adds an interceptor to a connection in use.
+    pub fn add_interceptor<I: Interceptor + 'static>(&mut self, interceptor: I) {
+        self.interceptors.push(Box::new(interceptor));
+    }
+
+    /// Returns the wrapped connection.
+    pub fn get_ref(&self) -> &C {
+        &self.con
+    }
+
+    /// Returns the wrapped connection.  Commands sent through it directly
+    /// are not intercepted.
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.con
+    }
+
+    /// Returns the wrapped connection and drops the interceptors.
+    pub fn into_inner(self) -> C {
+        self.con
+    }
+
+    fn parse(cmd: &[u8]) -> RedisResult<Vec<Command>> {
+        Ok(parse_packed_commands(cmd)?
+            .into_iter()
+            .map(|(args, _)| {
+                Command::new(
+                    args.into_iter()
+                        .map(|arg| match arg {
+                            Value::Data(data) => data,
+                            _ => vec![],
+                        })
+                        .collect(),
+                )
+            })
+            .collect())
+    }
+
+    fn before(&mut self, command: &mut Command) -> Option<RedisResult<Value>> {
+        for interceptor in &mut self.interceptors {
+            if let Some(result) = interceptor.before(command) {
+                return Some(result);
+            }
+        }
+        None
+    }
+
+    fn after(&mut self, command: &Command, elapsed: Duration, result: &RedisResult<Value>) {
+        for interceptor in self.interceptors.iter_mut().rev() {
+            interceptor.after(command, elapsed, result);
+        }
+    }
+
+    // Runs the commands and returns the result of each of them, like
+    // `req_commands_each`.
+    fn run(&mut self, mut commands: Vec<Command>) -> RedisResult<Vec<RedisResult<Value>>> {
+        let answers: Vec<Option<RedisResult<Value>>> = commands
+            .iter_mut()
+            .map(|command| self.before(command))
+            .collect();
+        let sent: Vec<Cmd> = commands
+            .iter()
+            .zip(&answers)
+            .filter(|&(_, answer)| answer.is_none())
+            .map(|(command, _)| command.to_cmd())
+            .collect();
+
+        let start = Instant::now();
+        let mut replies = if sent.is_empty() {
+            vec![]
+        } else if sent.len() == 1 {
+            vec![self.con.req_command(&sent[0])]
+        } else {
+            let cmds: Vec<&Cmd> = sent.iter().collect();
+            self.con.req_commands_each(&cmds)?
+        }
+        .into_iter();
+        let elapsed = start.elapsed();
+        let node = self.con.last_node().map(|node| node.to_string());
+
+        let mut results = Vec::with_capacity(commands.len());
+        for (command, answer) in commands.iter_mut().zip(answers) {
+            let result = match answer {
+                Some(result) => result,
+                None => {
+                    command.node = node.clone();
+                    match replies.next() {
+                        Some(result) => result,
+                        None => fail!((ErrorKind::ResponseError, "Missing reply to a command")),
+                    }
+                }
+            };
+            self.after(command, elapsed, &result);
+            results.push(result);
+        }
+        Ok(results)
+    }
+}
+
+impl<C: ConnectionLike> ConnectionLike for InterceptedConnection<C> {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        let commands = Self::parse(cmd)?;
+        if commands.len() != 1 {
+            fail!((ErrorKind::TypeError, "Expected a single packed command"));
+        }
+        self.run(commands)?.pop().unwrap()
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        let commands = Self::parse(cmd)?;
+        let mut values = Vec::with_capacity(count);
+        for (idx, result) in self.run(commands)?.into_iter().enumerate() {
+            let value = result?;
+            if idx >= offset && values.len() < count {
+                values.push(value);
+            }
+        }
+        Ok(values)
+    }
+
+    fn req_commands_each(&mut self, cmds: &[&Cmd]) -> RedisResult<Vec<RedisResult<Value>>> {
+        let commands = Self::parse(&pack_commands(cmds))?;
+        self.run(commands)
+    }
+
+    fn last_node(&self) -> Option<&str> {
+        self.con.last_node()
+    }
+
+    fn get_db(&self) -> i64 {
+        self.con.get_db()
+    }
+}
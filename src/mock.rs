@@ -0,0 +1,1312 @@
+//! A connection for unit tests that needs no server.
+//!
+//! A `MockConnection` either replies to the commands with replies that the
+//! test scripted in advance, or emulates a small in-memory server.  It
+//! implements the sync and the async `ConnectionLike` traits, so code
+//! that uses `Commands`, pipelines or scripts can be tested against it:
+//!
+//! ```rust
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::mock::MockConnection;
+//! use redis::{cmd, Commands, Value};
+//!
+//! let mut con = MockConnection::new()
+//!     .expect(cmd("GET").arg("greeting"), Ok(Value::Data(b"hello".to_vec())))
+//!     .expect(cmd("INCRBY").arg("visits").arg(1), Ok(Value::Int(1)));
+//! let greeting: String = con.get("greeting")?;
+//! let visits: u64 = con.incr("visits", 1)?;
+//! assert!(con.is_done());
+//!
+//! let mut con = MockConnection::in_memory();
+//! let _: () = con.set("greeting", "hello")?;
+//! let greeting: String = con.get("greeting")?;
+//! # Ok(()) }
+//! ```
+//!
+//! Scripted connections expect the commands in the scripted order and
+//! panic on any other command, so that a test fails where the code under
+//! test deviates.  `MULTI`, `EXEC` and `DISCARD` are handled by the mock
+//! itself, so the commands of a transaction are scripted without them.
+//!
+//! The in-memory server knows the common commands for strings, lists,
+//! hashes, sets, streams and keys, and replies with an error to others.
+//! Scripted replies added to it are returned in place of emulating the
+//! next command that matches, which is how errors can be injected.
+//! Blocking commands do not block and there is no pub/sub.
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::str;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use cluster_routing::parse_packed_commands;
+use cmd::Cmd;
+use connection::ConnectionLike;
+use parser::error_from_line;
+use types::{ErrorKind, RedisError, RedisResult, Value};
+
+#[cfg(feature = "aio")]
+use futures::future;
+#[cfg(feature = "aio")]
+use types::RedisFuture;
+
+/// A connection to a scripted or emulated server, see the module
+/// documentation.
+///
+/// Clones share the same state, so a test can keep a clone to inspect the
+/// commands after handing the connection to the code under test.
+#[derive(Clone)]
+pub struct MockConnection {
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    expected: VecDeque<(Vec<Vec<u8>>, RedisResult<Value>)>,
+    // the databases of the in-memory server, none for scripted connections
+    dbs: Option<HashMap<i64, Db>>,
+    db: i64,
+    queued: Option<Vec<Vec<Vec<u8>>>>,
+    received: Vec<Vec<Vec<u8>>>,
+}
+
+impl MockConnection {
+    /// Creates a connection that only replies with scripted replies.
+    pub fn new() -> MockConnection {
+        MockConnection::with_dbs(None)
+    }
+
+    /// Creates a connection to an empty in-memory server.
+    pub fn in_memory() -> MockConnection {
+        MockConnection::with_dbs(Some(HashMap::new()))
+    }
+
+    fn with_dbs(dbs: Option<HashMap<i64, Db>>) -> MockConnection {
+        MockConnection {
+            state: Arc::new(Mutex::new(State {
+                expected: VecDeque::new(),
+                dbs,
+                db: 0,
+                queued: None,
+                received: vec![],
+            })),
+        }
+    }
+
+    /// Scripts the reply to a command.  Error replies of the server are
+    /// scripted with `server_error`.
+    pub fn expect(self, cmd: &Cmd, reply: RedisResult<Value>) -> MockConnection {
+        let mut args =
+            parse_args(&cmd.get_packed_command()).expect("Cannot script an empty command");
+        self.lock().expected.push_back((args.remove(0), reply));
+        self
+    }
+
+    /// Returns whether all scripted replies were used.
+    pub fn is_done(&self) -> bool {
+        self.lock().expected.is_empty()
+    }
+
+    /// Returns the commands the connection received so far, as their
+    /// arguments.
+    pub fn received(&self) -> Vec<Vec<Vec<u8>>> {
+        self.lock().received.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<State> {
+        // a test that panicked while holding the lock still gets to see
+        // the state
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn execute_packed(&self, packed: &[u8]) -> RedisResult<Vec<RedisResult<Value>>> {
+        let commands = parse_args(packed)?;
+        let mut state = self.lock();
+        Ok(commands
+            .into_iter()
+            .map(|args| state.execute(args))
+            .collect())
+    }
+}
+
+impl Default for MockConnection {
+    fn default() -> MockConnection {
+        MockConnection::new()
+    }
+}
+
+/// Creates the error a server replies with, from the line of the reply
+/// like `WRONGTYPE Operation against a key holding the wrong kind of
+/// value`.
+pub fn server_error(line: &str) -> RedisError {
+    error_from_line(line)
+}
+
+fn parse_args(packed: &[u8]) -> RedisResult<Vec<Vec<Vec<u8>>>> {
+    let mut commands = vec![];
+    for (args, _) in parse_packed_commands(packed)? {
+        if args.is_empty() {
+            fail!((ErrorKind::TypeError, "Invalid packed command"));
+        }
+        commands.push(
+            args.into_iter()
+                .map(|arg| match arg {
+                    Value::Data(data) => data,
+                    _ => vec![],
+                })
+                .collect(),
+        );
+    }
+    Ok(commands)
+}
+
+fn first_error(replies: Vec<RedisResult<Value>>, offset: usize) -> RedisResult<Vec<Value>> {
+    let mut rv = Vec::with_capacity(replies.len());
+    for (idx, reply) in replies.into_iter().enumerate() {
+        let value = reply?;
+        if idx >= offset {
+            rv.push(value);
+        }
+    }
+    Ok(rv)
+}
+
+fn describe(args: &[Vec<u8>]) -> String {
+    args.iter()
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl State {
+    fn execute(&mut self, args: Vec<Vec<u8>>) -> RedisResult<Value> {
+        self.received.push(args.clone());
+        match &upper(&args[0])[..] {
+            "MULTI" => {
+                if self.queued.is_some() {
+                    return Err(server_error("ERR MULTI calls can not be nested"));
+                }
+                self.queued = Some(vec![]);
+                Ok(Value::Okay)
+            }
+            "EXEC" => {
+                let queued = unwrap_or!(
+                    self.queued.take(),
+                    return Err(server_error("ERR EXEC without MULTI"))
+                );
+                let mut replies = Vec::with_capacity(queued.len());
+                for args in queued {
+                    replies.push(self.reply(&args)?);
+                }
+                Ok(Value::Bulk(replies))
+            }
+            "DISCARD" => match self.queued.take() {
+                Some(_) => Ok(Value::Okay),
+                None => Err(server_error("ERR DISCARD without MULTI")),
+            },
+            _ => {
+                if let Some(ref mut queued) = self.queued {
+                    queued.push(args);
+                    return Ok(Value::Status("QUEUED".to_string()));
+                }
+                self.reply(&args)
+            }
+        }
+    }
+
+    fn reply(&mut self, args: &[Vec<u8>]) -> RedisResult<Value> {
+        let scripted = if self.dbs.is_some() {
+            self.expected
+                .iter()
+                .position(|&(ref expected, _)| expected[..] == *args)
+        } else {
+            match self.expected.front() {
+                Some(&(ref expected, _)) if expected[..] == *args => Some(0),
+                Some(&(ref expected, _)) => panic!(
+                    "MockConnection expected `{}` but received `{}`",
+                    describe(expected),
+                    describe(args)
+                ),
+                None => panic!("MockConnection received unexpected `{}`", describe(args)),
+            }
+        };
+        if let Some(idx) = scripted {
+            return self.expected.remove(idx).unwrap().1;
+        }
+
+        let dbs = self.dbs.as_mut().unwrap();
+        match &upper(&args[0])[..] {
+            "SELECT" => {
+                arity(args, 2)?;
+                self.db = int(&args[1])?;
+                Ok(Value::Okay)
+            }
+            "FLUSHALL" => {
+                dbs.clear();
+                Ok(Value::Okay)
+            }
+            _ => dbs.entry(self.db).or_insert_with(Db::default).execute(args),
+        }
+    }
+}
+
+impl ConnectionLike for MockConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        let mut replies = self.execute_packed(cmd)?;
+        if replies.len() != 1 {
+            fail!((ErrorKind::TypeError, "Expected a single packed command"));
+        }
+        replies.pop().unwrap()
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        let replies = self.execute_packed(cmd)?;
+        if replies.len() != offset + count {
+            fail!((
+                ErrorKind::TypeError,
+                "Expected a reply to every packed command"
+            ));
+        }
+        first_error(replies, offset)
+    }
+
+    fn req_commands_each(&mut self, cmds: &[&Cmd]) -> RedisResult<Vec<RedisResult<Value>>> {
+        let mut rv = Vec::with_capacity(cmds.len());
+        for cmd in cmds {
+            rv.extend(self.execute_packed(&cmd.get_packed_command())?);
+        }
+        Ok(rv)
+    }
+
+    fn get_db(&self) -> i64 {
+        self.lock().db
+    }
+}
+
+#[cfg(feature = "aio")]
+impl ::aio::ConnectionLike for MockConnection {
+    fn req_packed_command(mut self, cmd: Vec<u8>) -> RedisFuture<(Self, Value)> {
+        Box::new(future::result(
+            ConnectionLike::req_packed_command(&mut self, &cmd).map(|value| (self, value)),
+        ))
+    }
+
+    fn req_packed_commands(
+        mut self,
+        cmd: Vec<u8>,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<(Self, Vec<Value>)> {
+        Box::new(future::result(
+            ConnectionLike::req_packed_commands(&mut self, &cmd, offset, count)
+                .map(|values| (self, values)),
+        ))
+    }
+
+    fn get_db(&self) -> i64 {
+        self.lock().db
+    }
+}
+
+// The in-memory server.
+
+type StreamId = (u64, u64);
+
+#[derive(Default)]
+struct Db {
+    keys: HashMap<Vec<u8>, Entry>,
+}
+
+struct Entry {
+    data: Data,
+    expires_at: Option<Instant>,
+}
+
+enum Data {
+    String(Vec<u8>),
+    List(VecDeque<Vec<u8>>),
+    // fields in the order they were added, like small hashes on a server
+    Hash(Vec<(Vec<u8>, Vec<u8>)>),
+    Set(BTreeSet<Vec<u8>>),
+    Stream(Stream),
+}
+
+#[derive(Default)]
+struct Stream {
+    entries: Vec<(StreamId, Vec<Vec<u8>>)>,
+    last_id: StreamId,
+}
+
+impl Data {
+    fn string(&mut self) -> Option<&mut Vec<u8>> {
+        match *self {
+            Data::String(ref mut value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn list(&mut self) -> Option<&mut VecDeque<Vec<u8>>> {
+        match *self {
+            Data::List(ref mut list) => Some(list),
+            _ => None,
+        }
+    }
+
+    fn hash(&mut self) -> Option<&mut Vec<(Vec<u8>, Vec<u8>)>> {
+        match *self {
+            Data::Hash(ref mut hash) => Some(hash),
+            _ => None,
+        }
+    }
+
+    fn set(&mut self) -> Option<&mut BTreeSet<Vec<u8>>> {
+        match *self {
+            Data::Set(ref mut set) => Some(set),
+            _ => None,
+        }
+    }
+
+    fn stream(&mut self) -> Option<&mut Stream> {
+        match *self {
+            Data::Stream(ref mut stream) => Some(stream),
+            _ => None,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match *self {
+            Data::String(_) => "string",
+            Data::List(_) => "list",
+            Data::Hash(_) => "hash",
+            Data::Set(_) => "set",
+            Data::Stream(_) => "stream",
+        }
+    }
+
+    // Streams are kept when they are empty, other types are deleted.
+    fn is_empty(&self) -> bool {
+        match *self {
+            Data::List(ref list) => list.is_empty(),
+            Data::Hash(ref hash) => hash.is_empty(),
+            Data::Set(ref set) => set.is_empty(),
+            Data::String(_) | Data::Stream(_) => false,
+        }
+    }
+}
+
+impl Db {
+    fn execute(&mut self, args: &[Vec<u8>]) -> RedisResult<Value> {
+        let name = upper(&args[0]);
+        match &name[..] {
+            "PING" => Ok(match args.get(1) {
+                Some(message) => Value::Data(message.clone()),
+                None => Value::Status("PONG".to_string()),
+            }),
+            "ECHO" => {
+                arity(args, 2)?;
+                Ok(Value::Data(args[1].clone()))
+            }
+            "DBSIZE" => {
+                self.purge_all();
+                Ok(Value::Int(self.keys.len() as i64))
+            }
+            "FLUSHDB" => {
+                self.keys.clear();
+                Ok(Value::Okay)
+            }
+
+            // keys
+            "DEL" | "UNLINK" => {
+                arity(args, 2)?;
+                let mut deleted = 0;
+                for key in &args[1..] {
+                    self.purge(key);
+                    if self.keys.remove(key).is_some() {
+                        deleted += 1;
+                    }
+                }
+                Ok(Value::Int(deleted))
+            }
+            "EXISTS" => {
+                arity(args, 2)?;
+                let mut found = 0;
+                for key in &args[1..] {
+                    if self.exists(key) {
+                        found += 1;
+                    }
+                }
+                Ok(Value::Int(found))
+            }
+            "TYPE" => {
+                arity(args, 2)?;
+                self.purge(&args[1]);
+                let name = self
+                    .keys
+                    .get(&args[1])
+                    .map_or("none", |e| e.data.type_name());
+                Ok(Value::Status(name.to_string()))
+            }
+            "EXPIRE" | "PEXPIRE" => {
+                arity(args, 3)?;
+                let amount = int(&args[2])?;
+                if !self.exists(&args[1]) {
+                    return Ok(Value::Int(0));
+                }
+                if amount <= 0 {
+                    self.keys.remove(&args[1]);
+                } else {
+                    let ttl = if name == "EXPIRE" {
+                        Duration::from_secs(amount as u64)
+                    } else {
+                        Duration::from_millis(amount as u64)
+                    };
+                    self.keys.get_mut(&args[1]).unwrap().expires_at = Some(Instant::now() + ttl);
+                }
+                Ok(Value::Int(1))
+            }
+            "TTL" | "PTTL" => {
+                arity(args, 2)?;
+                self.purge(&args[1]);
+                let expires_at = match self.keys.get(&args[1]) {
+                    Some(entry) => entry.expires_at,
+                    None => return Ok(Value::Int(-2)),
+                };
+                Ok(Value::Int(match expires_at {
+                    Some(at) => {
+                        let now = Instant::now();
+                        let left = if at > now { millis(at - now) } else { 0 };
+                        if name == "TTL" {
+                            (left + 500) / 1000
+                        } else {
+                            left
+                        }
+                    }
+                    None => -1,
+                }))
+            }
+            "PERSIST" => {
+                arity(args, 2)?;
+                self.purge(&args[1]);
+                Ok(Value::Int(match self.keys.get_mut(&args[1]) {
+                    Some(entry) => entry.expires_at.take().is_some() as i64,
+                    None => 0,
+                }))
+            }
+            "KEYS" => {
+                arity(args, 2)?;
+                Ok(Value::Bulk(
+                    self.matching_keys(Some(&args[1]), None)
+                        .into_iter()
+                        .map(Value::Data)
+                        .collect(),
+                ))
+            }
+            "SCAN" => {
+                // the whole keyspace is returned in one round
+                arity(args, 2)?;
+                int(&args[1])?;
+                let (mut pattern, mut type_name) = (None, None);
+                for option in args[2..].chunks(2) {
+                    if option.len() != 2 {
+                        return Err(syntax_error());
+                    }
+                    match &upper(&option[0])[..] {
+                        "MATCH" => pattern = Some(&option[1][..]),
+                        "COUNT" => {
+                            int(&option[1])?;
+                        }
+                        "TYPE" => {
+                            type_name = Some(String::from_utf8_lossy(&option[1]).to_lowercase())
+                        }
+                        _ => return Err(syntax_error()),
+                    }
+                }
+                let keys = self.matching_keys(pattern, type_name.as_ref().map(|s| &s[..]));
+                Ok(Value::Bulk(vec![
+                    Value::Data(b"0".to_vec()),
+                    Value::Bulk(keys.into_iter().map(Value::Data).collect()),
+                ]))
+            }
+
+            // strings
+            "GET" => {
+                arity(args, 2)?;
+                Ok(opt_data(self.lookup(&args[1], Data::string)?.cloned()))
+            }
+            "SET" => {
+                arity(args, 3)?;
+                self.set(args)
+            }
+            "SETNX" => {
+                arity(args, 3)?;
+                if self.exists(&args[1]) {
+                    return Ok(Value::Int(0));
+                }
+                self.insert(&args[1], Data::String(args[2].clone()), None);
+                Ok(Value::Int(1))
+            }
+            "SETEX" | "PSETEX" => {
+                arity(args, 4)?;
+                let amount = int(&args[2])?;
+                if amount <= 0 {
+                    return Err(server_error(&format!(
+                        "ERR invalid expire time in '{}' command",
+                        name.to_lowercase()
+                    )));
+                }
+                let ttl = if name == "SETEX" {
+                    Duration::from_secs(amount as u64)
+                } else {
+                    Duration::from_millis(amount as u64)
+                };
+                self.insert(&args[1], Data::String(args[3].clone()), Some(ttl));
+                Ok(Value::Okay)
+            }
+            "GETSET" => {
+                arity(args, 3)?;
+                let old = self.lookup(&args[1], Data::string)?.cloned();
+                self.insert(&args[1], Data::String(args[2].clone()), None);
+                Ok(opt_data(old))
+            }
+            "MGET" => {
+                arity(args, 2)?;
+                let mut values = Vec::with_capacity(args.len() - 1);
+                for key in &args[1..] {
+                    // keys of other types are nil instead of an error
+                    values.push(opt_data(
+                        self.lookup(key, Data::string).ok().and_then(|v| v.cloned()),
+                    ));
+                }
+                Ok(Value::Bulk(values))
+            }
+            "MSET" => {
+                if args.len() < 3 || args.len() % 2 == 0 {
+                    return Err(wrong_arity(args));
+                }
+                for pair in args[1..].chunks(2) {
+                    self.insert(&pair[0], Data::String(pair[1].clone()), None);
+                }
+                Ok(Value::Okay)
+            }
+            "APPEND" => {
+                arity(args, 3)?;
+                let value = self.lookup_or_create(&args[1], Data::String(vec![]), Data::string)?;
+                value.extend_from_slice(&args[2]);
+                Ok(Value::Int(value.len() as i64))
+            }
+            "STRLEN" => {
+                arity(args, 2)?;
+                Ok(Value::Int(
+                    self.lookup(&args[1], Data::string)?.map_or(0, |v| v.len()) as i64,
+                ))
+            }
+            "INCR" | "DECR" => {
+                arity(args, 2)?;
+                self.incr_by(&args[1], if name == "INCR" { 1 } else { -1 })
+            }
+            "INCRBY" | "DECRBY" => {
+                arity(args, 3)?;
+                let by = int(&args[2])?;
+                self.incr_by(&args[1], if name == "INCRBY" { by } else { -by })
+            }
+
+            // hashes
+            "HSET" | "HMSET" => {
+                if args.len() < 4 || args.len() % 2 != 0 {
+                    return Err(wrong_arity(args));
+                }
+                let hash = self.lookup_or_create(&args[1], Data::Hash(vec![]), Data::hash)?;
+                let mut added = 0;
+                for pair in args[2..].chunks(2) {
+                    match hash.iter().position(|&(ref field, _)| *field == pair[0]) {
+                        Some(idx) => hash[idx].1 = pair[1].clone(),
+                        None => {
+                            hash.push((pair[0].clone(), pair[1].clone()));
+                            added += 1;
+                        }
+                    }
+                }
+                Ok(if name == "HSET" {
+                    Value::Int(added)
+                } else {
+                    Value::Okay
+                })
+            }
+            "HGET" => {
+                arity(args, 3)?;
+                let hash = self.lookup(&args[1], Data::hash)?;
+                Ok(opt_data(hash.and_then(|hash| field(hash, &args[2]))))
+            }
+            "HMGET" => {
+                arity(args, 3)?;
+                let hash = self.lookup(&args[1], Data::hash)?;
+                let hash = hash.map_or(&[][..], |hash| &hash[..]);
+                Ok(Value::Bulk(
+                    args[2..]
+                        .iter()
+                        .map(|name| opt_data(field(hash, name)))
+                        .collect(),
+                ))
+            }
+            "HGETALL" | "HKEYS" | "HVALS" => {
+                arity(args, 2)?;
+                let mut values = vec![];
+                if let Some(hash) = self.lookup(&args[1], Data::hash)? {
+                    for &(ref field, ref value) in hash.iter() {
+                        if name != "HVALS" {
+                            values.push(Value::Data(field.clone()));
+                        }
+                        if name != "HKEYS" {
+                            values.push(Value::Data(value.clone()));
+                        }
+                    }
+                }
+                Ok(Value::Bulk(values))
+            }
+            "HDEL" => {
+                arity(args, 3)?;
+                let mut deleted = 0;
+                if let Some(hash) = self.lookup(&args[1], Data::hash)? {
+                    for name in &args[2..] {
+                        if let Some(idx) = hash.iter().position(|&(ref field, _)| field == name) {
+                            hash.remove(idx);
+                            deleted += 1;
+                        }
+                    }
+                }
+                self.remove_if_empty(&args[1]);
+                Ok(Value::Int(deleted))
+            }
+            "HEXISTS" => {
+                arity(args, 3)?;
+                let hash = self.lookup(&args[1], Data::hash)?;
+                Ok(Value::Int(
+                    hash.and_then(|hash| field(hash, &args[2])).is_some() as i64,
+                ))
+            }
+            "HLEN" => {
+                arity(args, 2)?;
+                Ok(Value::Int(
+                    self.lookup(&args[1], Data::hash)?.map_or(0, |h| h.len()) as i64,
+                ))
+            }
+            "HINCRBY" => {
+                arity(args, 4)?;
+                let by = int(&args[3])?;
+                let hash = self.lookup_or_create(&args[1], Data::Hash(vec![]), Data::hash)?;
+                let idx = match hash.iter().position(|&(ref field, _)| *field == args[2]) {
+                    Some(idx) => idx,
+                    None => {
+                        hash.push((args[2].clone(), b"0".to_vec()));
+                        hash.len() - 1
+                    }
+                };
+                let value = int(&hash[idx].1)?
+                    .checked_add(by)
+                    .ok_or_else(|| server_error("ERR increment or decrement would overflow"))?;
+                hash[idx].1 = value.to_string().into_bytes();
+                Ok(Value::Int(value))
+            }
+
+            // lists
+            "LPUSH" | "RPUSH" => {
+                arity(args, 3)?;
+                let list =
+                    self.lookup_or_create(&args[1], Data::List(VecDeque::new()), Data::list)?;
+                for value in &args[2..] {
+                    if name == "LPUSH" {
+                        list.push_front(value.clone());
+                    } else {
+                        list.push_back(value.clone());
+                    }
+                }
+                Ok(Value::Int(list.len() as i64))
+            }
+            "LPOP" | "RPOP" => {
+                arity(args, 2)?;
+                let count = match args.get(2) {
+                    Some(count) => Some(int(count)?.max(0) as usize),
+                    None => None,
+                };
+                let mut popped = vec![];
+                let found = match self.lookup(&args[1], Data::list)? {
+                    Some(list) => {
+                        for _ in 0..count.unwrap_or(1) {
+                            let value = if name == "LPOP" {
+                                list.pop_front()
+                            } else {
+                                list.pop_back()
+                            };
+                            popped.extend(value.map(Value::Data));
+                        }
+                        true
+                    }
+                    None => false,
+                };
+                self.remove_if_empty(&args[1]);
+                Ok(match count {
+                    _ if !found => Value::Nil,
+                    Some(_) => Value::Bulk(popped),
+                    None => popped.pop().unwrap_or(Value::Nil),
+                })
+            }
+            "LLEN" => {
+                arity(args, 2)?;
+                Ok(Value::Int(
+                    self.lookup(&args[1], Data::list)?.map_or(0, |l| l.len()) as i64,
+                ))
+            }
+            "LRANGE" => {
+                arity(args, 4)?;
+                let (start, stop) = (int(&args[2])?, int(&args[3])?);
+                let mut values = vec![];
+                if let Some(list) = self.lookup(&args[1], Data::list)? {
+                    if let Some((start, stop)) = index_range(list.len(), start, stop) {
+                        for value in list.iter().skip(start).take(stop - start + 1) {
+                            values.push(Value::Data(value.clone()));
+                        }
+                    }
+                }
+                Ok(Value::Bulk(values))
+            }
+            "LINDEX" => {
+                arity(args, 3)?;
+                let index = int(&args[2])?;
+                let list = self.lookup(&args[1], Data::list)?;
+                Ok(opt_data(list.and_then(|list| {
+                    index_range(list.len(), index, index)
+                        .and_then(|(idx, _)| list.get(idx).cloned())
+                })))
+            }
+
+            // sets
+            "SADD" => {
+                arity(args, 3)?;
+                let set = self.lookup_or_create(&args[1], Data::Set(BTreeSet::new()), Data::set)?;
+                let added = args[2..].iter().filter(|m| set.insert(m.to_vec())).count();
+                Ok(Value::Int(added as i64))
+            }
+            "SREM" => {
+                arity(args, 3)?;
+                let removed = match self.lookup(&args[1], Data::set)? {
+                    Some(set) => args[2..].iter().filter(|m| set.remove(&m[..])).count(),
+                    None => 0,
+                };
+                self.remove_if_empty(&args[1]);
+                Ok(Value::Int(removed as i64))
+            }
+            "SMEMBERS" => {
+                arity(args, 2)?;
+                let set = self.lookup(&args[1], Data::set)?;
+                Ok(Value::Bulk(set.map_or(vec![], |set| {
+                    set.iter().cloned().map(Value::Data).collect()
+                })))
+            }
+            "SISMEMBER" => {
+                arity(args, 3)?;
+                let set = self.lookup(&args[1], Data::set)?;
+                Ok(Value::Int(
+                    set.map_or(false, |set| set.contains(&args[2])) as i64
+                ))
+            }
+            "SCARD" => {
+                arity(args, 2)?;
+                Ok(Value::Int(
+                    self.lookup(&args[1], Data::set)?.map_or(0, |s| s.len()) as i64,
+                ))
+            }
+
+            // streams
+            "XADD" => {
+                arity(args, 5)?;
+                self.xadd(args)
+            }
+            "XLEN" => {
+                arity(args, 2)?;
+                Ok(Value::Int(
+                    self.lookup(&args[1], Data::stream)?
+                        .map_or(0, |s| s.entries.len()) as i64,
+                ))
+            }
+            "XRANGE" | "XREVRANGE" => {
+                arity(args, 4)?;
+                let (start, end) = if name == "XRANGE" {
+                    (&args[2], &args[3])
+                } else {
+                    (&args[3], &args[2])
+                };
+                let (start, end) = (range_start(start)?, range_end(end)?);
+                let count = match args.get(4) {
+                    Some(option) if upper(option) == "COUNT" && args.len() == 6 => {
+                        int(&args[5])?.max(0) as usize
+                    }
+                    Some(_) => return Err(syntax_error()),
+                    None => usize::max_value(),
+                };
+                let entries = match self.lookup(&args[1], Data::stream)? {
+                    Some(stream) => {
+                        let entries = stream
+                            .entries
+                            .iter()
+                            .filter(|&&(id, _)| start <= id && id <= end);
+                        if name == "XRANGE" {
+                            entries.take(count).map(entry_value).collect()
+                        } else {
+                            entries.rev().take(count).map(entry_value).collect()
+                        }
+                    }
+                    None => vec![],
+                };
+                Ok(Value::Bulk(entries))
+            }
+            "XDEL" => {
+                arity(args, 3)?;
+                let mut ids = vec![];
+                for id in &args[2..] {
+                    ids.push(parse_id(id, 0)?);
+                }
+                Ok(Value::Int(match self.lookup(&args[1], Data::stream)? {
+                    Some(stream) => {
+                        let len = stream.entries.len();
+                        stream.entries.retain(|&(id, _)| !ids.contains(&id));
+                        (len - stream.entries.len()) as i64
+                    }
+                    None => 0,
+                }))
+            }
+            "XREAD" => self.xread(args),
+
+            _ => Err(server_error(&format!(
+                "ERR unknown command '{}'",
+                String::from_utf8_lossy(&args[0])
+            ))),
+        }
+    }
+
+    fn set(&mut self, args: &[Vec<u8>]) -> RedisResult<Value> {
+        let (mut ttl, mut nx, mut xx, mut get, mut keep_ttl) = (None, false, false, false, false);
+        let mut idx = 3;
+        while idx < args.len() {
+            let option = upper(&args[idx]);
+            match &option[..] {
+                "NX" => nx = true,
+                "XX" => xx = true,
+                "GET" => get = true,
+                "KEEPTTL" => keep_ttl = true,
+                "EX" | "PX" => {
+                    idx += 1;
+                    let amount = int(args.get(idx).ok_or_else(syntax_error)?)?;
+                    if amount <= 0 {
+                        return Err(server_error("ERR invalid expire time in 'set' command"));
+                    }
+                    ttl = Some(if option == "EX" {
+                        Duration::from_secs(amount as u64)
+                    } else {
+                        Duration::from_millis(amount as u64)
+                    });
+                }
+                _ => return Err(syntax_error()),
+            }
+            idx += 1;
+        }
+        if nx && xx {
+            return Err(syntax_error());
+        }
+
+        let old = if get {
+            self.lookup(&args[1], Data::string)?.cloned()
+        } else {
+            None
+        };
+        let exists = self.exists(&args[1]);
+        if (nx && exists) || (xx && !exists) {
+            return Ok(opt_data(old));
+        }
+        let expires_at = match self.keys.get(&args[1]) {
+            Some(entry) if keep_ttl && ttl.is_none() => entry.expires_at,
+            _ => ttl.map(|ttl| Instant::now() + ttl),
+        };
+        self.keys.insert(
+            args[1].clone(),
+            Entry {
+                data: Data::String(args[2].clone()),
+                expires_at,
+            },
+        );
+        Ok(if get { opt_data(old) } else { Value::Okay })
+    }
+
+    fn incr_by(&mut self, key: &[u8], by: i64) -> RedisResult<Value> {
+        let value = self.lookup_or_create(key, Data::String(b"0".to_vec()), Data::string)?;
+        let next = int(value)?
+            .checked_add(by)
+            .ok_or_else(|| server_error("ERR increment or decrement would overflow"))?;
+        *value = next.to_string().into_bytes();
+        Ok(Value::Int(next))
+    }
+
+    fn xadd(&mut self, args: &[Vec<u8>]) -> RedisResult<Value> {
+        let mut idx = 2;
+        let mut max_len = None;
+        if upper(&args[idx]) == "MAXLEN" {
+            idx += 1;
+            if args
+                .get(idx)
+                .map_or(false, |arg| arg[..] == b"~"[..] || arg[..] == b"="[..])
+            {
+                idx += 1;
+            }
+            max_len = Some(int(args.get(idx).ok_or_else(syntax_error)?)?.max(0) as usize);
+            idx += 1;
+        }
+        if idx + 3 > args.len() || (args.len() - idx - 1) % 2 != 0 {
+            return Err(wrong_arity(args));
+        }
+        let fields = &args[idx + 1..];
+
+        let last_id = self
+            .lookup(&args[1], Data::stream)?
+            .map_or((0, 0), |stream| stream.last_id);
+        let id = if args[idx][..] == b"*"[..] {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| millis(elapsed) as u64)
+                .unwrap_or(0);
+            if now > last_id.0 {
+                (now, 0)
+            } else {
+                (last_id.0, last_id.1 + 1)
+            }
+        } else {
+            let id = parse_id(&args[idx], 0)?;
+            if id == (0, 0) {
+                return Err(server_error(
+                    "ERR The ID specified in XADD must be greater than 0-0",
+                ));
+            }
+            if id <= last_id {
+                return Err(server_error(
+                    "ERR The ID specified in XADD is equal or smaller than the target stream top item",
+                ));
+            }
+            id
+        };
+
+        let stream =
+            self.lookup_or_create(&args[1], Data::Stream(Stream::default()), Data::stream)?;
+        stream.entries.push((id, fields.to_vec()));
+        stream.last_id = id;
+        if let Some(max_len) = max_len {
+            let excess = stream.entries.len().saturating_sub(max_len);
+            stream.entries.drain(..excess);
+        }
+        Ok(Value::Data(format_id(id).into_bytes()))
+    }
+
+    // Reads without blocking, `BLOCK` is accepted but ignored.
+    fn xread(&mut self, args: &[Vec<u8>]) -> RedisResult<Value> {
+        let mut count = usize::max_value();
+        let mut idx = 1;
+        loop {
+            let option = upper(args.get(idx).ok_or_else(|| wrong_arity(args))?);
+            match &option[..] {
+                "COUNT" => {
+                    count = int(args.get(idx + 1).ok_or_else(syntax_error)?)?.max(0) as usize
+                }
+                "BLOCK" => {
+                    int(args.get(idx + 1).ok_or_else(syntax_error)?)?;
+                }
+                "STREAMS" => break,
+                _ => return Err(syntax_error()),
+            }
+            idx += 2;
+        }
+        let streams = &args[idx + 1..];
+        if streams.is_empty() || streams.len() % 2 != 0 {
+            return Err(server_error(
+                "ERR Unbalanced 'xread' list of streams: for each stream key an ID or '$' must be specified.",
+            ));
+        }
+        let (keys, ids) = streams.split_at(streams.len() / 2);
+        let mut rv = vec![];
+        for (key, id) in keys.iter().zip(ids) {
+            let stream = unwrap_or!(self.lookup(key, Data::stream)?, continue);
+            let after = if id[..] == b"$"[..] {
+                stream.last_id
+            } else {
+                parse_id(id, 0)?
+            };
+            let entries: Vec<Value> = stream
+                .entries
+                .iter()
+                .filter(|&&(id, _)| id > after)
+                .take(count)
+                .map(entry_value)
+                .collect();
+            if !entries.is_empty() {
+                rv.push(Value::Bulk(vec![
+                    Value::Data(key.clone()),
+                    Value::Bulk(entries),
+                ]));
+            }
+        }
+        Ok(if rv.is_empty() {
+            Value::Nil
+        } else {
+            Value::Bulk(rv)
+        })
+    }
+
+    // Deletes the key if it expired.
+    fn purge(&mut self, key: &[u8]) {
+        let expired = match self.keys.get(key) {
+            Some(entry) => entry.expires_at.map_or(false, |at| at <= Instant::now()),
+            None => false,
+        };
+        if expired {
+            self.keys.remove(key);
+        }
+    }
+
+    fn purge_all(&mut self) {
+        let now = Instant::now();
+        self.keys
+            .retain(|_, entry| entry.expires_at.map_or(true, |at| at > now));
+    }
+
+    fn exists(&mut self, key: &[u8]) -> bool {
+        self.purge(key);
+        self.keys.contains_key(key)
+    }
+
+    fn insert(&mut self, key: &[u8], data: Data, ttl: Option<Duration>) {
+        self.keys.insert(
+            key.to_vec(),
+            Entry {
+                data,
+                expires_at: ttl.map(|ttl| Instant::now() + ttl),
+            },
+        );
+    }
+
+    // Returns the value of a key, an error if it has another type.
+    fn lookup<T>(
+        &mut self,
+        key: &[u8],
+        project: fn(&mut Data) -> Option<&mut T>,
+    ) -> RedisResult<Option<&mut T>> {
+        self.purge(key);
+        match self.keys.get_mut(key) {
+            Some(entry) => project(&mut entry.data).map(Some).ok_or_else(wrong_type),
+            None => Ok(None),
+        }
+    }
+
+    // Like `lookup`, but sets missing keys to `empty` first.
+    fn lookup_or_create<T>(
+        &mut self,
+        key: &[u8],
+        empty: Data,
+        project: fn(&mut Data) -> Option<&mut T>,
+    ) -> RedisResult<&mut T> {
+        self.purge(key);
+        let entry = self.keys.entry(key.to_vec()).or_insert(Entry {
+            data: empty,
+            expires_at: None,
+        });
+        project(&mut entry.data).ok_or_else(wrong_type)
+    }
+
+    fn remove_if_empty(&mut self, key: &[u8]) {
+        let empty = self
+            .keys
+            .get(key)
+            .map_or(false, |entry| entry.data.is_empty());
+        if empty {
+            self.keys.remove(key);
+        }
+    }
+
+    fn matching_keys(&mut self, pattern: Option<&[u8]>, type_name: Option<&str>) -> Vec<Vec<u8>> {
+        self.purge_all();
+        let mut keys: Vec<Vec<u8>> = self
+            .keys
+            .iter()
+            .filter(|&(key, entry)| {
+                pattern.map_or(true, |pattern| glob_match(pattern, key))
+                    && type_name.map_or(true, |name| entry.data.type_name() == name)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        keys.sort();
+        keys
+    }
+}
+
+fn upper(arg: &[u8]) -> String {
+    String::from_utf8_lossy(arg).to_ascii_uppercase()
+}
+
+fn int(arg: &[u8]) -> RedisResult<i64> {
+    str::from_utf8(arg)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .ok_or_else(|| server_error("ERR value is not an integer or out of range"))
+}
+
+fn arity(args: &[Vec<u8>], min: usize) -> RedisResult<()> {
+    if args.len() < min {
+        return Err(wrong_arity(args));
+    }
+    Ok(())
+}
+
+fn wrong_arity(args: &[Vec<u8>]) -> RedisError {
+    server_error(&format!(
+        "ERR wrong number of arguments for '{}' command",
+        String::from_utf8_lossy(&args[0]).to_lowercase()
+    ))
+}
+
+fn wrong_type() -> RedisError {
+    server_error("WRONGTYPE Operation against a key holding the wrong kind of value")
+}
+
+fn syntax_error() -> RedisError {
+    server_error("ERR syntax error")
+}
+
+fn opt_data(value: Option<Vec<u8>>) -> Value {
+    match value {
+        Some(value) => Value::Data(value),
+        None => Value::Nil,
+    }
+}
+
+fn field(hash: &[(Vec<u8>, Vec<u8>)], name: &[u8]) -> Option<Vec<u8>> {
+    hash.iter()
+        .find(|&&(ref field, _)| field[..] == *name)
+        .map(|&(_, ref value)| value.clone())
+}
+
+fn millis(duration: Duration) -> i64 {
+    duration.as_secs() as i64 * 1000 + i64::from(duration.subsec_nanos() / 1_000_000)
+}
+
+// Resolves the inclusive range of `LRANGE`, with negative indexes
+// counting from the end.
+fn index_range(len: usize, start: i64, stop: i64) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    if start > stop || start >= len {
+        return None;
+    }
+    Some((start as usize, stop as usize))
+}
+
+fn parse_id(arg: &[u8], default_seq: u64) -> RedisResult<StreamId> {
+    let invalid = || server_error("ERR Invalid stream ID specified as stream command argument");
+    let text = str::from_utf8(arg).map_err(|_| invalid())?;
+    let mut parts = text.splitn(2, '-');
+    let ms = parts.next().unwrap().parse().map_err(|_| invalid())?;
+    let seq = match parts.next() {
+        Some(seq) => seq.parse().map_err(|_| invalid())?,
+        None => default_seq,
+    };
+    Ok((ms, seq))
+}
+
+fn range_start(arg: &[u8]) -> RedisResult<StreamId> {
+    match arg {
+        b"-" => Ok((0, 0)),
+        _ if arg.starts_with(b"(") => {
+            let (ms, seq) = parse_id(&arg[1..], 0)?;
+            Ok(if seq == u64::max_value() {
+                (ms + 1, 0)
+            } else {
+                (ms, seq + 1)
+            })
+        }
+        _ => parse_id(arg, 0),
+    }
+}
+
+fn range_end(arg: &[u8]) -> RedisResult<StreamId> {
+    match arg {
+        b"+" => Ok((u64::max_value(), u64::max_value())),
+        _ if arg.starts_with(b"(") => {
+            let (ms, seq) = parse_id(&arg[1..], u64::max_value())?;
+            Ok(if seq == 0 {
+                (ms.saturating_sub(1), u64::max_value())
+            } else {
+                (ms, seq - 1)
+            })
+        }
+        _ => parse_id(arg, u64::max_value()),
+    }
+}
+
+fn format_id(id: StreamId) -> String {
+    format!("{}-{}", id.0, id.1)
+}
+
+fn entry_value(entry: &(StreamId, Vec<Vec<u8>>)) -> Value {
+    Value::Bulk(vec![
+        Value::Data(format_id(entry.0).into_bytes()),
+        Value::Bulk(entry.1.iter().cloned().map(Value::Data).collect()),
+    ])
+}
+
+// Matches a key against a glob-style pattern like `KEYS` does.
+fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    let first = match pattern.first() {
+        Some(&first) => first,
+        None => return string.is_empty(),
+    };
+    match first {
+        b'*' => (0..string.len() + 1).any(|idx| glob_match(&pattern[1..], &string[idx..])),
+        b'?' => !string.is_empty() && glob_match(&pattern[1..], &string[1..]),
+        b'[' if !string.is_empty() => {
+            let negate = pattern.get(1) == Some(&b'^');
+            let mut idx = if negate { 2 } else { 1 };
+            let mut matched = false;
+            while idx < pattern.len() && pattern[idx] != b']' {
+                if pattern[idx] == b'\\' && idx + 1 < pattern.len() {
+                    matched |= pattern[idx + 1] == string[0];
+                    idx += 2;
+                } else if idx + 2 < pattern.len()
+                    && pattern[idx + 1] == b'-'
+                    && pattern[idx + 2] != b']'
+                {
+                    let (low, high) = (
+                        pattern[idx].min(pattern[idx + 2]),
+                        pattern[idx].max(pattern[idx + 2]),
+                    );
+                    matched |= low <= string[0] && string[0] <= high;
+                    idx += 3;
+                } else {
+                    matched |= pattern[idx] == string[0];
+                    idx += 1;
+                }
+            }
+            let rest = &pattern[(idx + 1).min(pattern.len())..];
+            matched != negate && glob_match(rest, &string[1..])
+        }
+        b'\\' if pattern.len() > 1 => {
+            string.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &string[1..])
+        }
+        _ => string.first() == Some(&first) && glob_match(&pattern[1..], &string[1..]),
+    }
+}
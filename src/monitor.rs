@@ -0,0 +1,214 @@
+//! Watching the commands a server processes with `MONITOR`.
+//!
+//! After `MONITOR` the server sends a line for every command it processes.
+//! `Monitor` issues it and yields the parsed lines:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! let client = redis::Client::open("redis://127.0.0.1/")?;
+//! for entry in client.get_monitor()? {
+//!     let entry = entry?;
+//!     println!("{} {:?} {:?}", entry.client, entry.command, entry.args);
+//! }
+//! # Ok(()) }
+//! ```
+//!
+//! `MONITOR` slows the server down considerably, so it is meant for
+//! debugging only.
+use std::str::from_utf8;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "aio")]
+use futures::{stream, Future, Stream};
+
+use cmd::cmd;
+use connection::Connection;
+use types::{FromRedisValue, RedisResult, Value};
+#[cfg(feature = "aio")]
+use types::{RedisError, RedisFuture};
+
+/// A command processed by the server.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonitorEntry {
+    /// When the server processed the command.
+    pub time: SystemTime,
+    /// The database the command ran in.
+    pub db: i64,
+    /// The client that sent the command: its address, like
+    /// `127.0.0.1:60866` or `unix:/tmp/redis.sock`, or `lua` for commands
+    /// of scripts.
+    pub client: String,
+    /// The name of the command as sent by the client, like `set`.
+    pub command: Vec<u8>,
+    /// The arguments of the command.
+    pub args: Vec<Vec<u8>>,
+}
+
+impl MonitorEntry {
+    /// Parses a line sent by the server, like
+    /// `1339518083.107412 [0 127.0.0.1:60866] "keys" "*"`.  Returns `None`
+    /// if it is malformed.
+    pub fn parse(line: &str) -> Option<MonitorEntry> {
+        let space = line.find(' ')?;
+        let time = parse_time(&line[..space])?;
+        let rest = line[space + 1..].trim_left();
+        if !rest.starts_with('[') {
+            return None;
+        }
+        let end = rest.find(']')?;
+        let mut source = rest[1..end].splitn(2, ' ');
+        let db = source.next()?.parse().ok()?;
+        let client = source.next()?.to_string();
+        let mut words = parse_quoted(&rest[end + 1..])?.into_iter();
+        let command = words.next()?;
+        Some(MonitorEntry {
+            time,
+            db,
+            client,
+            command,
+            args: words.collect(),
+        })
+    }
+
+    /// Returns the name of the command as string.  If it is not valid
+    /// UTF-8 the return value is `"?"`.
+    pub fn command_name(&self) -> &str {
+        from_utf8(&self.command).unwrap_or("?")
+    }
+}
+
+impl FromRedisValue for MonitorEntry {
+    fn from_redis_value(v: &Value) -> RedisResult<MonitorEntry> {
+        let line = match *v {
+            Value::Status(ref line) => &line[..],
+            Value::Data(ref data) => match from_utf8(data) {
+                Ok(line) => line,
+                Err(_) => invalid_type_error!(v, "Monitor line is not valid UTF-8"),
+            },
+            _ => invalid_type_error!(v, "Response type not a monitor line"),
+        };
+        match MonitorEntry::parse(line) {
+            Some(entry) => Ok(entry),
+            None => invalid_type_error!(v, "Malformed monitor line"),
+        }
+    }
+}
+
+// Parses `<seconds>.<microseconds>`.
+fn parse_time(s: &str) -> Option<SystemTime> {
+    let mut parts = s.splitn(2, '.');
+    let secs: u64 = parts.next()?.parse().ok()?;
+    let micros: u32 = match parts.next() {
+        Some(micros) if micros.len() <= 6 => {
+            // pad `5` to `500000`
+            let digits: u32 = micros.parse().ok()?;
+            digits * 10u32.pow(6 - micros.len() as u32)
+        }
+        Some(_) => return None,
+        None => 0,
+    };
+    Some(UNIX_EPOCH + Duration::new(secs, micros * 1000))
+}
+
+// Parses the quoted and escaped words of a monitor line.
+fn parse_quoted(s: &str) -> Option<Vec<Vec<u8>>> {
+    let bytes = s.as_bytes();
+    let mut words = vec![];
+    let mut idx = 0;
+    loop {
+        while idx < bytes.len() && bytes[idx] == b' ' {
+            idx += 1;
+        }
+        if idx == bytes.len() {
+            return Some(words);
+        }
+        if bytes[idx] != b'"' {
+            return None;
+        }
+        idx += 1;
+        let mut word = vec![];
+        loop {
+            match *bytes.get(idx)? {
+                b'"' => break,
+                b'\\' => {
+                    idx += 1;
+                    match *bytes.get(idx)? {
+                        b'n' => word.push(b'\n'),
+                        b'r' => word.push(b'\r'),
+                        b't' => word.push(b'\t'),
+                        b'a' => word.push(7),
+                        b'b' => word.push(8),
+                        b'x' => {
+                            let hex = from_utf8(bytes.get(idx + 1..idx + 3)?).ok()?;
+                            word.push(u8::from_str_radix(hex, 16).ok()?);
+                            idx += 2;
+                        }
+                        other => word.push(other),
+                    }
+                }
+                other => word.push(other),
+            }
+            idx += 1;
+        }
+        words.push(word);
+        idx += 1;
+    }
+}
+
+/// A connection that issued `MONITOR`.  It is an iterator of the commands
+/// the server processes.
+pub struct Monitor {
+    con: Connection,
+}
+
+impl Monitor {
+    /// Issues `MONITOR` on the connection.  The connection cannot be used
+    /// for anything else afterwards.
+    pub fn new(mut con: Connection) -> RedisResult<Monitor> {
+        cmd("MONITOR").query::<()>(&mut con)?;
+        Ok(Monitor { con })
+    }
+
+    /// Fetches the next command.  Blocks until one arrives or the read
+    /// timeout passes.
+    pub fn next_entry(&mut self) -> RedisResult<MonitorEntry> {
+        let value = self.con.recv_response()?;
+        MonitorEntry::from_redis_value(&value)
+    }
+
+    /// Sets the read timeout of `next_entry`.  `None`, the default, blocks
+    /// until a command arrives.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> RedisResult<()> {
+        self.con.set_read_timeout(dur)
+    }
+}
+
+impl Iterator for Monitor {
+    type Item = RedisResult<MonitorEntry>;
+
+    fn next(&mut self) -> Option<RedisResult<MonitorEntry>> {
+        Some(self.next_entry())
+    }
+}
+
+/// The commands an async connection in monitor mode receives.
+#[cfg(feature = "aio")]
+pub type MonitorStream = Box<Stream<Item = MonitorEntry, Error = RedisError> + Send>;
+
+/// Issues `MONITOR` on an async connection and returns the stream of the
+/// commands the server processes.
+#[cfg(feature = "aio")]
+pub fn monitor_async(con: ::aio::Connection) -> RedisFuture<MonitorStream> {
+    Box::new(
+        cmd("MONITOR")
+            .query_async::<_, ()>(con)
+            .map(|(con, ())| -> MonitorStream {
+                Box::new(
+                    stream::unfold(con, |con| {
+                        Some(con.read_response().map(|(con, value)| (value, con)))
+                    })
+                    .and_then(|value| MonitorEntry::from_redis_value(&value)),
+                )
+            }),
+    )
+}
@@ -0,0 +1,269 @@
+//! Key namespaces that isolate the keys of several applications or
+//! tenants in one database.
+//!
+//! A `NamespacedConnection` prefixes the keys of every command it sends,
+//! including the keys of multi-key commands, scripts and pipelines, so
+//! that the call sites use the keys without the prefix:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::namespace::NamespacedConnection;
+//! use redis::Commands;
+//!
+//! let client = redis::Client::open("redis://127.0.0.1/")?;
+//! let mut con = NamespacedConnection::new(client.get_connection()?, "tenant:42:");
+//! // sets `tenant:42:greeting`
+//! let _: () = con.set("greeting", "hello")?;
+//! // only finds the keys of the namespace, without the prefix
+//! let keys: Vec<String> = con.scan_match("greet*")?.collect();
+//! # Ok(()) }
+//! ```
+//!
+//! `SCAN` and `KEYS` only match keys of the namespace and the prefix is
+//! removed from the keys they return, as from the keys returned by
+//! blocking pops like `BLPOP`.  Commands without keys, like `FLUSHDB` or
+//! `DBSIZE`, are sent unchanged and still act on the whole database, as do
+//! the patterns of `SORT ... BY` and `GET`, and keys that scripts build
+//! themselves.
+use cluster_routing::{command_key_indices, parse_packed_commands};
+use cmd::{pack_commands, Cmd};
+use connection::ConnectionLike;
+use types::{ErrorKind, RedisResult, Value};
+
+/// A connection that prefixes all keys, see the module documentation.
+pub struct NamespacedConnection<C> {
+    con: C,
+    prefix: Vec<u8>,
+}
+
+impl<C: ConnectionLike> NamespacedConnection<C> {
+    /// Wraps a connection so that its keys start with `prefix`.
+    pub fn new<P: AsRef<[u8]>>(con: C, prefix: P) -> NamespacedConnection<C> {
+        NamespacedConnection {
+            con,
+            prefix: prefix.as_ref().to_vec(),
+        }
+    }
+
+    /// Returns the prefix of the keys.
+    pub fn prefix(&self) -> &[u8] {
+        &self.prefix
+    }
+
+    /// Returns the wrapped connection.
+    pub fn get_ref(&self) -> &C {
+        &self.con
+    }
+
+    /// Returns the wrapped connection.  Keys of commands sent through it
+    /// directly are not prefixed.
+    pub fn get_mut(&mut self) -> &mut C {
+        &mut self.con
+    }
+
+    /// Returns the wrapped connection.
+    pub fn into_inner(self) -> C {
+        self.con
+    }
+
+    // Parses the commands and prefixes their keys.
+    fn rewrite(&self, packed: &[u8]) -> RedisResult<Vec<Vec<Vec<u8>>>> {
+        let mut commands = vec![];
+        for (args, _) in parse_packed_commands(packed)? {
+            let keys = command_key_indices(&args);
+            let mut args: Vec<Vec<u8>> = args
+                .into_iter()
+                .map(|arg| match arg {
+                    Value::Data(data) => data,
+                    _ => vec![],
+                })
+                .collect();
+            for idx in keys {
+                if let Some(key) = args.get_mut(idx) {
+                    *key = self.prefixed(key);
+                }
+            }
+            self.rewrite_pattern(&mut args);
+            commands.push(args);
+        }
+        Ok(commands)
+    }
+
+    fn prefixed(&self, key: &[u8]) -> Vec<u8> {
+        let mut rv = Vec::with_capacity(self.prefix.len() + key.len());
+        rv.extend_from_slice(&self.prefix);
+        rv.extend_from_slice(key);
+        rv
+    }
+
+    // Restricts the patterns of `SCAN` and `KEYS` to the namespace.
+    fn rewrite_pattern(&self, args: &mut Vec<Vec<u8>>) {
+        let name = match args.first() {
+            Some(name) => name.to_ascii_uppercase(),
+            None => return,
+        };
+        let mut escaped = Vec::with_capacity(self.prefix.len());
+        for &byte in &self.prefix {
+            if b"*?[]\\".contains(&byte) {
+                escaped.push(b'\\');
+            }
+            escaped.push(byte);
+        }
+        match &name[..] {
+            b"KEYS" if args.len() > 1 => {
+                escaped.extend_from_slice(&args[1]);
+                args[1] = escaped;
+            }
+            b"SCAN" => {
+                let pattern = args
+                    .iter()
+                    .skip(2)
+                    .position(|arg| arg.eq_ignore_ascii_case(b"MATCH"))
+                    .map(|idx| idx + 3);
+                match pattern {
+                    Some(idx) if idx < args.len() => {
+                        escaped.extend_from_slice(&args[idx]);
+                        args[idx] = escaped;
+                    }
+                    _ => {
+                        escaped.push(b'*');
+                        args.push(b"MATCH".to_vec());
+                        args.push(escaped);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Removes the prefix from the keys in the reply to a command.
+    fn strip_reply(&self, args: &[Vec<u8>], value: &mut Value) {
+        let name = match args.first() {
+            Some(name) => name.to_ascii_uppercase(),
+            None => return,
+        };
+        match (&name[..], value) {
+            (b"KEYS", &mut Value::Bulk(ref mut keys)) => {
+                for key in keys {
+                    self.strip_key(key);
+                }
+            }
+            (b"SCAN", &mut Value::Bulk(ref mut reply)) => {
+                if let Some(&mut Value::Bulk(ref mut keys)) = reply.get_mut(1) {
+                    for key in keys {
+                        self.strip_key(key);
+                    }
+                }
+            }
+            // replies that start with the key the value came from
+            (b"BLPOP", &mut Value::Bulk(ref mut reply))
+            | (b"BRPOP", &mut Value::Bulk(ref mut reply))
+            | (b"BZPOPMIN", &mut Value::Bulk(ref mut reply))
+            | (b"BZPOPMAX", &mut Value::Bulk(ref mut reply))
+            | (b"LMPOP", &mut Value::Bulk(ref mut reply))
+            | (b"BLMPOP", &mut Value::Bulk(ref mut reply))
+            | (b"ZMPOP", &mut Value::Bulk(ref mut reply))
+            | (b"BZMPOP", &mut Value::Bulk(ref mut reply)) => {
+                if let Some(key) = reply.first_mut() {
+                    self.strip_key(key);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn strip_key(&self, key: &mut Value) {
+        if let Value::Data(ref mut data) = *key {
+            if data.starts_with(&self.prefix) {
+                data.drain(..self.prefix.len());
+            }
+        }
+    }
+
+    // Removes the prefixes from the replies to the commands from `offset`
+    // on.  The replies of a transaction are in the reply to its `EXEC`.
+    fn strip_replies(&self, commands: &[Vec<Vec<u8>>], offset: usize, values: &mut [Value]) {
+        for (idx, value) in values.iter_mut().enumerate() {
+            let idx = offset + idx;
+            let args = unwrap_or!(commands.get(idx), return);
+            if !args
+                .first()
+                .map_or(false, |name| name.eq_ignore_ascii_case(b"EXEC"))
+            {
+                self.strip_reply(args, value);
+                continue;
+            }
+            let multi = commands[..idx].iter().rposition(|args| {
+                args.first()
+                    .map_or(false, |name| name.eq_ignore_ascii_case(b"MULTI"))
+            });
+            if let (Some(multi), &mut Value::Bulk(ref mut replies)) = (multi, value) {
+                for (args, reply) in commands[multi + 1..idx].iter().zip(replies) {
+                    self.strip_reply(args, reply);
+                }
+            }
+        }
+    }
+}
+
+fn to_cmds(commands: &[Vec<Vec<u8>>]) -> Vec<Cmd> {
+    commands
+        .iter()
+        .map(|args| {
+            let mut cmd = Cmd::new();
+            for arg in args {
+                cmd.arg(&arg[..]);
+            }
+            cmd
+        })
+        .collect()
+}
+
+impl<C: ConnectionLike> ConnectionLike for NamespacedConnection<C> {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        let commands = self.rewrite(cmd)?;
+        if commands.len() != 1 {
+            fail!((ErrorKind::TypeError, "Expected a single packed command"));
+        }
+        let mut value = self.con.req_command(&to_cmds(&commands)[0])?;
+        self.strip_reply(&commands[0], &mut value);
+        Ok(value)
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        let commands = self.rewrite(cmd)?;
+        let cmds = to_cmds(&commands);
+        let cmds: Vec<&Cmd> = cmds.iter().collect();
+        let mut values = self
+            .con
+            .req_packed_commands(&pack_commands(&cmds), offset, count)?;
+        self.strip_replies(&commands, offset, &mut values);
+        Ok(values)
+    }
+
+    fn req_commands_each(&mut self, cmds: &[&Cmd]) -> RedisResult<Vec<RedisResult<Value>>> {
+        let commands = self.rewrite(&pack_commands(cmds))?;
+        let rewritten = to_cmds(&commands);
+        let rewritten: Vec<&Cmd> = rewritten.iter().collect();
+        let mut results = self.con.req_commands_each(&rewritten)?;
+        for (args, result) in commands.iter().zip(&mut results) {
+            if let Ok(ref mut value) = *result {
+                self.strip_reply(args, value);
+            }
+        }
+        Ok(results)
+    }
+
+    fn last_node(&self) -> Option<&str> {
+        self.con.last_node()
+    }
+
+    fn get_db(&self) -> i64 {
+        self.con.get_db()
+    }
+}
@@ -0,0 +1,339 @@
+//! Keyspace notifications.
+//!
+//! Redis publishes a message for every change of a key when keyspace
+//! notifications are enabled with `notify-keyspace-events`.
+//! `KeyspaceNotifications` enables them, subscribes to the keyspace
+//! channels and yields the parsed events:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::notifications::{KeyEventKind, KeyspaceNotifications, NotificationOptions};
+//!
+//! let client = redis::Client::open("redis://127.0.0.1/")?;
+//! let mut con = client.get_connection()?;
+//! let options = NotificationOptions {
+//!     key_pattern: "session:*".to_string(),
+//!     ..Default::default()
+//! };
+//! let mut notifications = KeyspaceNotifications::new(&mut con, options)?;
+//! loop {
+//!     let event = notifications.next_event()?;
+//!     if event.kind == KeyEventKind::Expired {
+//!         println!("{} expired in db {}", event.key_name(), event.db);
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! Async `PubSub` connections yield the same events with
+//! `KeyspaceEvent::from_msg`, after subscribing to the channel returned by
+//! `NotificationOptions::pattern`.
+use std::str::from_utf8;
+use std::time::Duration;
+
+use cmd::cmd;
+use connection::{Connection, ConnectionLike, Msg, PubSub};
+use types::RedisResult;
+
+/// The kind of a keyspace event.  These are the names redis publishes,
+/// like `del` or `expired`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum KeyEventKind {
+    /// The key was deleted.
+    Del,
+    /// The key was renamed, this is the old name.
+    RenameFrom,
+    /// The key was renamed, this is the new name.
+    RenameTo,
+    /// The key was copied, this is the destination.
+    CopyTo,
+    /// The key was created with `RESTORE`.
+    Restore,
+    /// An expiration was set on the key.
+    Expire,
+    /// The expiration of the key was removed.
+    Persist,
+    /// The key expired.
+    Expired,
+    /// The key was evicted because of `maxmemory`.
+    Evicted,
+    /// A new key was added (flag `n`).
+    New,
+    /// The key was set with `SET` or a variant of it.
+    Set,
+    /// The string was changed with `SETRANGE`.
+    SetRange,
+    /// The number was changed with `INCR`, `DECR`, `INCRBY` or `DECRBY`.
+    IncrBy,
+    /// The number was changed with `INCRBYFLOAT`.
+    IncrByFloat,
+    /// The string was appended to.
+    Append,
+    /// Elements were pushed to the head of the list.
+    LPush,
+    /// Elements were pushed to the tail of the list.
+    RPush,
+    /// Elements were popped from the head of the list.
+    LPop,
+    /// Elements were popped from the tail of the list.
+    RPop,
+    /// An element was inserted into the list.
+    LInsert,
+    /// An element of the list was set.
+    LSet,
+    /// Elements were removed from the list.
+    LRem,
+    /// The list was trimmed.
+    LTrim,
+    /// Fields of the hash were set.
+    HSet,
+    /// A field of the hash was incremented.
+    HIncrBy,
+    /// A field of the hash was incremented by a float.
+    HIncrByFloat,
+    /// Fields were removed from the hash.
+    HDel,
+    /// Members were added to the set.
+    SAdd,
+    /// Members were removed from the set.
+    SRem,
+    /// Members were popped from the set.
+    SPop,
+    /// Members were added to the sorted set.
+    ZAdd,
+    /// A member of the sorted set was incremented.
+    ZIncr,
+    /// Members were removed from the sorted set.
+    ZRem,
+    /// An entry was added to the stream.
+    XAdd,
+    /// The stream was trimmed.
+    XTrim,
+    /// Entries were removed from the stream.
+    XDel,
+    /// An event unknown to the library.
+    Other(String),
+}
+
+impl KeyEventKind {
+    /// Returns the kind with the given name, for instance `expired`.
+    pub fn from_name(name: &str) -> KeyEventKind {
+        match name {
+            "del" => KeyEventKind::Del,
+            "rename_from" => KeyEventKind::RenameFrom,
+            "rename_to" => KeyEventKind::RenameTo,
+            "copy_to" => KeyEventKind::CopyTo,
+            "restore" => KeyEventKind::Restore,
+            "expire" => KeyEventKind::Expire,
+            "persist" => KeyEventKind::Persist,
+            "expired" => KeyEventKind::Expired,
+            "evicted" => KeyEventKind::Evicted,
+            "new" => KeyEventKind::New,
+            "set" => KeyEventKind::Set,
+            "setrange" => KeyEventKind::SetRange,
+            "incrby" => KeyEventKind::IncrBy,
+            "incrbyfloat" => KeyEventKind::IncrByFloat,
+            "append" => KeyEventKind::Append,
+            "lpush" => KeyEventKind::LPush,
+            "rpush" => KeyEventKind::RPush,
+            "lpop" => KeyEventKind::LPop,
+            "rpop" => KeyEventKind::RPop,
+            "linsert" => KeyEventKind::LInsert,
+            "lset" => KeyEventKind::LSet,
+            "lrem" => KeyEventKind::LRem,
+            "ltrim" => KeyEventKind::LTrim,
+            "hset" => KeyEventKind::HSet,
+            "hincrby" => KeyEventKind::HIncrBy,
+            "hincrbyfloat" => KeyEventKind::HIncrByFloat,
+            "hdel" => KeyEventKind::HDel,
+            "sadd" => KeyEventKind::SAdd,
+            "srem" => KeyEventKind::SRem,
+            "spop" => KeyEventKind::SPop,
+            "zadd" => KeyEventKind::ZAdd,
+            "zincr" => KeyEventKind::ZIncr,
+            "zrem" => KeyEventKind::ZRem,
+            "xadd" => KeyEventKind::XAdd,
+            "xtrim" => KeyEventKind::XTrim,
+            "xdel" => KeyEventKind::XDel,
+            other => KeyEventKind::Other(other.to_string()),
+        }
+    }
+
+    /// Returns the name redis uses for the event.
+    pub fn name(&self) -> &str {
+        match *self {
+            KeyEventKind::Del => "del",
+            KeyEventKind::RenameFrom => "rename_from",
+            KeyEventKind::RenameTo => "rename_to",
+            KeyEventKind::CopyTo => "copy_to",
+            KeyEventKind::Restore => "restore",
+            KeyEventKind::Expire => "expire",
+            KeyEventKind::Persist => "persist",
+            KeyEventKind::Expired => "expired",
+            KeyEventKind::Evicted => "evicted",
+            KeyEventKind::New => "new",
+            KeyEventKind::Set => "set",
+            KeyEventKind::SetRange => "setrange",
+            KeyEventKind::IncrBy => "incrby",
+            KeyEventKind::IncrByFloat => "incrbyfloat",
+            KeyEventKind::Append => "append",
+            KeyEventKind::LPush => "lpush",
+            KeyEventKind::RPush => "rpush",
+            KeyEventKind::LPop => "lpop",
+            KeyEventKind::RPop => "rpop",
+            KeyEventKind::LInsert => "linsert",
+            KeyEventKind::LSet => "lset",
+            KeyEventKind::LRem => "lrem",
+            KeyEventKind::LTrim => "ltrim",
+            KeyEventKind::HSet => "hset",
+            KeyEventKind::HIncrBy => "hincrby",
+            KeyEventKind::HIncrByFloat => "hincrbyfloat",
+            KeyEventKind::HDel => "hdel",
+            KeyEventKind::SAdd => "sadd",
+            KeyEventKind::SRem => "srem",
+            KeyEventKind::SPop => "spop",
+            KeyEventKind::ZAdd => "zadd",
+            KeyEventKind::ZIncr => "zincr",
+            KeyEventKind::ZRem => "zrem",
+            KeyEventKind::XAdd => "xadd",
+            KeyEventKind::XTrim => "xtrim",
+            KeyEventKind::XDel => "xdel",
+            KeyEventKind::Other(ref name) => name,
+        }
+    }
+}
+
+/// A change of a key.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct KeyspaceEvent {
+    /// The database of the key.
+    pub db: i64,
+    /// The key that changed.
+    pub key: Vec<u8>,
+    /// What happened to the key.
+    pub kind: KeyEventKind,
+}
+
+// Splits `__keyspace@0__:rest` into the kind of channel, the db and rest.
+fn split_channel(channel: &[u8]) -> Option<(&[u8], i64, &[u8])> {
+    if !channel.starts_with(b"__key") {
+        return None;
+    }
+    let at = channel.iter().position(|&b| b == b'@')?;
+    let end = at + channel[at..].windows(3).position(|w| w == b"__:")?;
+    let db = from_utf8(&channel[at + 1..end]).ok()?.parse().ok()?;
+    Some((&channel[..at], db, &channel[end + 3..]))
+}
+
+impl KeyspaceEvent {
+    /// Parses a message of a `__keyspace@<db>__:<key>` channel, whose
+    /// payload is the event, or of a `__keyevent@<db>__:<event>` channel,
+    /// whose payload is the key.  Returns `None` for other messages.
+    pub fn from_msg(msg: &Msg) -> Option<KeyspaceEvent> {
+        let channel: Vec<u8> = msg.get_channel().ok()?;
+        let payload = msg.get_payload_bytes();
+        let (prefix, db, rest) = split_channel(&channel)?;
+        let (key, event) = match prefix {
+            b"__keyspace" => (rest, payload),
+            b"__keyevent" => (payload, rest),
+            _ => return None,
+        };
+        Some(KeyspaceEvent {
+            db,
+            key: key.to_vec(),
+            kind: KeyEventKind::from_name(from_utf8(event).ok()?),
+        })
+    }
+
+    /// Returns the key as string.  If the key is not valid UTF-8 the
+    /// return value is `"?"`.
+    pub fn key_name(&self) -> &str {
+        from_utf8(&self.key).unwrap_or("?")
+    }
+}
+
+/// Which notifications `KeyspaceNotifications` enables and subscribes to.
+#[derive(Clone, Debug)]
+pub struct NotificationOptions {
+    /// The value `notify-keyspace-events` is set to, see the redis
+    /// documentation.  It has to contain `K` for the keyspace channels.
+    /// Defaults to `KEA`, all events.  With `None` the configuration of
+    /// the server is left alone.
+    pub flags: Option<String>,
+    /// The database whose keys are watched, all if `None` (the default).
+    pub db: Option<i64>,
+    /// Only keys matching this glob style pattern are watched.  Defaults
+    /// to `*`.
+    pub key_pattern: String,
+}
+
+impl Default for NotificationOptions {
+    fn default() -> NotificationOptions {
+        NotificationOptions {
+            flags: Some("KEA".to_string()),
+            db: None,
+            key_pattern: "*".to_string(),
+        }
+    }
+}
+
+impl NotificationOptions {
+    /// Returns the pattern of the keyspace channels to subscribe to, like
+    /// `__keyspace@0__:session:*`.
+    pub fn pattern(&self) -> String {
+        match self.db {
+            Some(db) => format!("__keyspace@{}__:{}", db, self.key_pattern),
+            None => format!("__keyspace@*__:{}", self.key_pattern),
+        }
+    }
+}
+
+/// Sets `notify-keyspace-events` on the server, for instance to `KEA` for
+/// all events or to `Ex` for expired keys only.  An empty string disables
+/// the notifications.
+pub fn enable_notifications<C: ConnectionLike>(con: &mut C, flags: &str) -> RedisResult<()> {
+    cmd("CONFIG")
+        .arg("SET")
+        .arg("notify-keyspace-events")
+        .arg(flags)
+        .query(con)
+}
+
+/// A connection subscribed to keyspace notifications.
+pub struct KeyspaceNotifications<'a> {
+    pubsub: PubSub<'a>,
+}
+
+impl<'a> KeyspaceNotifications<'a> {
+    /// Enables the notifications according to `options` and puts the
+    /// connection into subscribed state.  The connection leaves it again
+    /// when this is dropped.
+    pub fn new(
+        con: &'a mut Connection,
+        options: NotificationOptions,
+    ) -> RedisResult<KeyspaceNotifications<'a>> {
+        if let Some(ref flags) = options.flags {
+            enable_notifications(con, flags)?;
+        }
+        let mut pubsub = con.as_pubsub();
+        pubsub.psubscribe(options.pattern())?;
+        Ok(KeyspaceNotifications { pubsub })
+    }
+
+    /// Fetches the next event.  Blocks until one arrives or the read
+    /// timeout passes.
+    pub fn next_event(&mut self) -> RedisResult<KeyspaceEvent> {
+        loop {
+            let msg = self.pubsub.get_message()?;
+            if let Some(event) = KeyspaceEvent::from_msg(&msg) {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Sets the read timeout of `next_event`.  `None`, the default, blocks
+    /// until an event arrives.
+    pub fn set_read_timeout(&self, dur: Option<Duration>) -> RedisResult<()> {
+        self.pubsub.set_read_timeout(dur)
+    }
+}
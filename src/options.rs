@@ -0,0 +1,1263 @@
+//! Options of the commands that take many optional arguments and the
+//! typed replies of some of them.
+//!
+//! The options are built by chaining methods and passed to the
+//! corresponding methods of `Commands`:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::options::{ExistenceCheck, SetExpiry, SetOptions};
+//! use redis::Commands;
+//!
+//! # let client = redis::Client::open("redis://127.0.0.1/")?;
+//! # let mut con = client.get_connection()?;
+//! let options = SetOptions::new()
+//!     .conditional_set(ExistenceCheck::Nx)
+//!     .with_expiration(SetExpiry::Ex(60));
+//! let locked: bool = con.set_options("lock", "owner", &options)?;
+//! # Ok(()) }
+//! ```
+use std::collections::HashMap;
+
+use keys::KeyType;
+use types::{from_redis_value, FromRedisValue, RedisResult, RedisWrite, ToRedisArgs, Value};
+
+/// Whether a command only applies to keys that do not exist yet or to
+/// keys that exist.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExistenceCheck {
+    /// Only if the key does not exist (`NX`).
+    Nx,
+    /// Only if the key exists (`XX`).
+    Xx,
+}
+
+impl ToRedisArgs for ExistenceCheck {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            ExistenceCheck::Nx => b"NX",
+            ExistenceCheck::Xx => b"XX",
+        })
+    }
+}
+
+/// The condition under which the `EXPIRE` commands set the expiration
+/// of a key (redis 7).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpireOption {
+    /// Only if the key has no expiration (`NX`).
+    Nx,
+    /// Only if the key has an expiration (`XX`).
+    Xx,
+    /// Only if the new expiration is later than the current one (`GT`).
+    /// Keys without expiration count as expiring never.
+    Gt,
+    /// Only if the new expiration is earlier than the current one
+    /// (`LT`).
+    Lt,
+}
+
+impl ToRedisArgs for ExpireOption {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            ExpireOption::Nx => b"NX",
+            ExpireOption::Xx => b"XX",
+            ExpireOption::Gt => b"GT",
+            ExpireOption::Lt => b"LT",
+        })
+    }
+}
+
+/// The expiration `SET` gives a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetExpiry {
+    /// Expires after the given number of seconds (`EX`).
+    Ex(usize),
+    /// Expires after the given number of milliseconds (`PX`).
+    Px(usize),
+    /// Expires at the given unix time in seconds (`EXAT`, redis 6.2).
+    ExAt(u64),
+    /// Expires at the given unix time in milliseconds (`PXAT`,
+    /// redis 6.2).
+    PxAt(u64),
+    /// Keeps the expiration the key had before (`KEEPTTL`, redis 6).
+    KeepTtl,
+}
+
+impl ToRedisArgs for SetExpiry {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        match *self {
+            SetExpiry::Ex(secs) => {
+                out.write_arg(b"EX");
+                secs.write_redis_args(out);
+            }
+            SetExpiry::Px(ms) => {
+                out.write_arg(b"PX");
+                ms.write_redis_args(out);
+            }
+            SetExpiry::ExAt(secs) => {
+                out.write_arg(b"EXAT");
+                secs.write_redis_args(out);
+            }
+            SetExpiry::PxAt(ms) => {
+                out.write_arg(b"PXAT");
+                ms.write_redis_args(out);
+            }
+            SetExpiry::KeepTtl => out.write_arg(b"KEEPTTL"),
+        }
+    }
+}
+
+/// The options of `SET`, see `Commands::set_options`.
+///
+/// Without `get` the reply is `OK`, or nil if the condition was not met,
+/// so it can be read as `bool`.  With `get` it is the previous value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SetOptions {
+    condition: Option<ExistenceCheck>,
+    expiration: Option<SetExpiry>,
+    get: bool,
+}
+
+impl SetOptions {
+    /// Creates options that set the key unconditionally and remove its
+    /// expiration, like a plain `SET`.
+    pub fn new() -> SetOptions {
+        SetOptions::default()
+    }
+
+    /// Only sets the key if it does not exist or if it exists.
+    pub fn conditional_set(mut self, condition: ExistenceCheck) -> SetOptions {
+        self.condition = Some(condition);
+        self
+    }
+
+    /// Sets the expiration of the key.
+    pub fn with_expiration(mut self, expiration: SetExpiry) -> SetOptions {
+        self.expiration = Some(expiration);
+        self
+    }
+
+    /// Returns the previous value of the key, nil if there was none
+    /// (`GET`, redis 6.2).
+    pub fn get(mut self, get: bool) -> SetOptions {
+        self.get = get;
+        self
+    }
+}
+
+impl ToRedisArgs for SetOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(ref condition) = self.condition {
+            condition.write_redis_args(out);
+        }
+        if let Some(ref expiration) = self.expiration {
+            expiration.write_redis_args(out);
+        }
+        if self.get {
+            out.write_arg(b"GET");
+        }
+    }
+}
+
+/// The options of `COPY`, see `Commands::copy_options`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CopyOptions {
+    db: Option<i64>,
+    replace: bool,
+}
+
+impl CopyOptions {
+    /// Creates options that copy within the selected database and do
+    /// not overwrite an existing key.
+    pub fn new() -> CopyOptions {
+        CopyOptions::default()
+    }
+
+    /// Copies to a key in the database (`DB`).
+    pub fn db(mut self, db: i64) -> CopyOptions {
+        self.db = Some(db);
+        self
+    }
+
+    /// Overwrites the destination key if it exists (`REPLACE`).
+    pub fn replace(mut self, replace: bool) -> CopyOptions {
+        self.replace = replace;
+        self
+    }
+}
+
+impl ToRedisArgs for CopyOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(db) = self.db {
+            out.write_arg(b"DB");
+            db.write_redis_args(out);
+        }
+        if self.replace {
+            out.write_arg(b"REPLACE");
+        }
+    }
+}
+
+/// The options of `LPOS`, see `Commands::lpos_options`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LposOptions {
+    rank: Option<isize>,
+    count: Option<usize>,
+    maxlen: Option<usize>,
+}
+
+impl LposOptions {
+    /// Creates options that return the first match.
+    pub fn new() -> LposOptions {
+        LposOptions::default()
+    }
+
+    /// Skips `rank - 1` matches, a negative rank searches from the tail of
+    /// the list (`RANK`).
+    pub fn rank(mut self, rank: isize) -> LposOptions {
+        self.rank = Some(rank);
+        self
+    }
+
+    /// Returns up to `count` matches, `0` returns all (`COUNT`).
+    pub fn count(mut self, count: usize) -> LposOptions {
+        self.count = Some(count);
+        self
+    }
+
+    /// Compares at most `maxlen` elements, `0` compares all (`MAXLEN`).
+    pub fn maxlen(mut self, maxlen: usize) -> LposOptions {
+        self.maxlen = Some(maxlen);
+        self
+    }
+}
+
+impl ToRedisArgs for LposOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(rank) = self.rank {
+            out.write_arg(b"RANK");
+            rank.write_redis_args(out);
+        }
+        if let Some(count) = self.count {
+            out.write_arg(b"COUNT");
+            count.write_redis_args(out);
+        }
+        if let Some(maxlen) = self.maxlen {
+            out.write_arg(b"MAXLEN");
+            maxlen.write_redis_args(out);
+        }
+    }
+}
+
+/// The end of a list that `LMPOP` pops from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListDirection {
+    /// The head of the list.
+    Left,
+    /// The tail of the list.
+    Right,
+}
+
+impl ToRedisArgs for ListDirection {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            ListDirection::Left => &b"LEFT"[..],
+            ListDirection::Right => &b"RIGHT"[..],
+        })
+    }
+}
+
+/// The end of a sorted set that `ZMPOP` pops from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScoreEnd {
+    /// The members with the lowest scores.
+    Min,
+    /// The members with the highest scores.
+    Max,
+}
+
+impl ToRedisArgs for ScoreEnd {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            ScoreEnd::Min => b"MIN",
+            ScoreEnd::Max => b"MAX",
+        })
+    }
+}
+
+/// The reply of `LMPOP` and `ZMPOP`: the key the elements were popped
+/// from and the elements.  Read it as `Option<MPopReply<T>>`, the reply
+/// is nil if all keys were empty.
+///
+/// The elements of `ZMPOP` are pairs of member and score, like
+/// `MPopReply<(String, f64)>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MPopReply<T> {
+    /// The key the elements were popped from.
+    pub key: String,
+    /// The popped elements.
+    pub elements: Vec<T>,
+}
+
+impl<T: FromRedisValue> FromRedisValue for MPopReply<T> {
+    fn from_redis_value(v: &Value) -> RedisResult<MPopReply<T>> {
+        match *v {
+            Value::Bulk(ref items) if items.len() == 2 => Ok(MPopReply {
+                key: from_redis_value(&items[0])?,
+                elements: from_redis_value(&items[1])?,
+            }),
+            _ => invalid_type_error!(v, "Response type not pop reply compatible"),
+        }
+    }
+}
+
+/// How the `start` and `stop` of `ZRANGE` are interpreted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZRangeBy {
+    /// As ranks, `0` is the first member and `-1` the last.
+    Index,
+    /// As scores, like `1`, `(1` for an exclusive bound or `-inf`
+    /// (`BYSCORE`).
+    Score,
+    /// As members, like `[a`, `(a`, `-` or `+` (`BYLEX`).
+    Lex,
+}
+
+impl Default for ZRangeBy {
+    fn default() -> ZRangeBy {
+        ZRangeBy::Index
+    }
+}
+
+/// The options of the unified `ZRANGE` and of `ZRANGESTORE` (redis 6.2),
+/// see `Commands::zrange_options` and `Commands::zrangestore_options`.
+///
+/// With `with_scores` the reply of `ZRANGE` is read as pairs of member
+/// and score, like `Vec<(String, f64)>`.  `ZRANGESTORE` rejects
+/// `WITHSCORES`.
+///
+/// ```rust,no_run
+/// # fn do_something() -> redis::RedisResult<()> {
+/// use redis::options::ZRangeOptions;
+/// use redis::Commands;
+///
+/// # let client = redis::Client::open("redis://127.0.0.1/")?;
+/// # let mut con = client.get_connection()?;
+/// let options = ZRangeOptions::new().by_score().rev().limit(0, 10).with_scores();
+/// let top: Vec<(String, f64)> = con.zrange_options("scores", "+inf", "-inf", &options)?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ZRangeOptions {
+    by: ZRangeBy,
+    rev: bool,
+    limit: Option<(isize, isize)>,
+    with_scores: bool,
+}
+
+impl ZRangeOptions {
+    /// Creates options for a range by index in ascending order.
+    pub fn new() -> ZRangeOptions {
+        ZRangeOptions::default()
+    }
+
+    /// Interprets `start` and `stop` as scores (`BYSCORE`).
+    pub fn by_score(mut self) -> ZRangeOptions {
+        self.by = ZRangeBy::Score;
+        self
+    }
+
+    /// Interprets `start` and `stop` as members (`BYLEX`).
+    pub fn by_lex(mut self) -> ZRangeOptions {
+        self.by = ZRangeBy::Lex;
+        self
+    }
+
+    /// Returns the members in descending order (`REV`).  `start` is then
+    /// the higher bound.
+    pub fn rev(mut self) -> ZRangeOptions {
+        self.rev = true;
+        self
+    }
+
+    /// Skips `offset` members and returns at most `count`, a negative
+    /// count returns all (`LIMIT`).  Only for ranges by score or member.
+    pub fn limit(mut self, offset: isize, count: isize) -> ZRangeOptions {
+        self.limit = Some((offset, count));
+        self
+    }
+
+    /// Returns the scores together with the members (`WITHSCORES`).
+    pub fn with_scores(mut self) -> ZRangeOptions {
+        self.with_scores = true;
+        self
+    }
+}
+
+impl ToRedisArgs for ZRangeOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        match self.by {
+            ZRangeBy::Index => {}
+            ZRangeBy::Score => out.write_arg(b"BYSCORE"),
+            ZRangeBy::Lex => out.write_arg(b"BYLEX"),
+        }
+        if self.rev {
+            out.write_arg(b"REV");
+        }
+        if let Some((offset, count)) = self.limit {
+            out.write_arg(b"LIMIT");
+            offset.write_redis_args(out);
+            count.write_redis_args(out);
+        }
+        if self.with_scores {
+            out.write_arg(b"WITHSCORES");
+        }
+    }
+}
+
+/// How `ZUNION` and `ZINTER` combine the scores of a member that is in
+/// several sorted sets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregate {
+    /// The sum of the scores, the default.
+    Sum,
+    /// The lowest score.
+    Min,
+    /// The highest score.
+    Max,
+}
+
+impl ToRedisArgs for Aggregate {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            Aggregate::Sum => b"SUM",
+            Aggregate::Min => b"MIN",
+            Aggregate::Max => b"MAX",
+        })
+    }
+}
+
+/// The options of `ZUNION` and `ZINTER` (redis 6.2), see
+/// `Commands::zunion_options` and `Commands::zinter_options`.
+///
+/// With `with_scores` the reply is read as pairs of member and score,
+/// like `Vec<(String, f64)>`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ZAggregateOptions {
+    weights: Vec<f64>,
+    aggregate: Option<Aggregate>,
+    with_scores: bool,
+}
+
+impl ZAggregateOptions {
+    /// Creates options that sum the scores with a weight of `1`.
+    pub fn new() -> ZAggregateOptions {
+        ZAggregateOptions::default()
+    }
+
+    /// Multiplies the scores of each sorted set by its weight before
+    /// they are combined, one weight per key (`WEIGHTS`).
+    pub fn weights(mut self, weights: &[f64]) -> ZAggregateOptions {
+        self.weights = weights.to_vec();
+        self
+    }
+
+    /// Sets how the scores are combined (`AGGREGATE`).
+    pub fn aggregate(mut self, aggregate: Aggregate) -> ZAggregateOptions {
+        self.aggregate = Some(aggregate);
+        self
+    }
+
+    /// Returns the scores together with the members (`WITHSCORES`).
+    pub fn with_scores(mut self) -> ZAggregateOptions {
+        self.with_scores = true;
+        self
+    }
+}
+
+impl ToRedisArgs for ZAggregateOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if !self.weights.is_empty() {
+            out.write_arg(b"WEIGHTS");
+            for weight in &self.weights {
+                weight.write_redis_args(out);
+            }
+        }
+        if let Some(ref aggregate) = self.aggregate {
+            out.write_arg(b"AGGREGATE");
+            aggregate.write_redis_args(out);
+        }
+        if self.with_scores {
+            out.write_arg(b"WITHSCORES");
+        }
+    }
+}
+
+/// The type of an integer in a `BITFIELD`, signed with up to 64 bits or
+/// unsigned with up to 63 bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitFieldType {
+    /// A signed integer of the number of bits, like `i8`.
+    Signed(u8),
+    /// An unsigned integer of the number of bits, like `u4`.
+    Unsigned(u8),
+}
+
+impl ToRedisArgs for BitFieldType {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        let ty = match *self {
+            BitFieldType::Signed(bits) => format!("i{}", bits),
+            BitFieldType::Unsigned(bits) => format!("u{}", bits),
+        };
+        out.write_arg(ty.as_bytes())
+    }
+}
+
+/// The offset of an integer in a `BITFIELD`.  A plain `usize` converts to
+/// an offset in bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitOffset {
+    /// The offset in bits.
+    Bit(usize),
+    /// The offset in integers of the type, `Field(2)` of `u8` is bit `16`
+    /// (`#2`).
+    Field(usize),
+}
+
+impl From<usize> for BitOffset {
+    fn from(bit: usize) -> BitOffset {
+        BitOffset::Bit(bit)
+    }
+}
+
+impl ToRedisArgs for BitOffset {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        match *self {
+            BitOffset::Bit(bit) => bit.write_redis_args(out),
+            BitOffset::Field(field) => out.write_arg(format!("#{}", field).as_bytes()),
+        }
+    }
+}
+
+/// What `BITFIELD` does when `SET` or `INCRBY` leaves the range of the
+/// type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wraps around, the default (`WRAP`).
+    Wrap,
+    /// Stops at the minimum or maximum (`SAT`).
+    Sat,
+    /// Leaves the integer unchanged and replies nil (`FAIL`).
+    Fail,
+}
+
+impl ToRedisArgs for Overflow {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            Overflow::Wrap => b"WRAP",
+            Overflow::Sat => b"SAT",
+            Overflow::Fail => b"FAIL",
+        })
+    }
+}
+
+/// The operations of `BITFIELD` and `BITFIELD_RO`, see
+/// `Commands::bitfield` and `Commands::bitfield_ro`.
+///
+/// The reply has an element per `get`, `set` and `incr_by`, in order, so
+/// it is read as `Vec<Option<i64>>`.  An element is nil if the operation
+/// failed with `Overflow::Fail`.
+///
+/// ```rust,no_run
+/// # fn do_something() -> redis::RedisResult<()> {
+/// use redis::options::{BitFieldOptions, BitFieldType, BitOffset, Overflow};
+/// use redis::Commands;
+///
+/// # let client = redis::Client::open("redis://127.0.0.1/")?;
+/// # let mut con = client.get_connection()?;
+/// let counter = BitFieldType::Unsigned(8);
+/// let options = BitFieldOptions::new()
+///     .overflow(Overflow::Sat)
+///     .incr_by(counter, BitOffset::Field(3), 1)
+///     .get(counter, BitOffset::Field(4));
+/// let values: Vec<Option<i64>> = con.bitfield("counters", &options)?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BitFieldOptions {
+    args: Vec<Vec<u8>>,
+}
+
+impl BitFieldOptions {
+    /// Creates options without operations.
+    pub fn new() -> BitFieldOptions {
+        BitFieldOptions::default()
+    }
+
+    fn push<T: ToRedisArgs>(mut self, arg: T) -> BitFieldOptions {
+        self.args.extend(arg.to_redis_args());
+        self
+    }
+
+    /// Reads an integer (`GET`).  The only operation of `BITFIELD_RO`.
+    pub fn get<O: Into<BitOffset>>(self, ty: BitFieldType, offset: O) -> BitFieldOptions {
+        self.push("GET").push(ty).push(offset.into())
+    }
+
+    /// Writes an integer and replies its previous value (`SET`).
+    pub fn set<O: Into<BitOffset>>(
+        self,
+        ty: BitFieldType,
+        offset: O,
+        value: i64,
+    ) -> BitFieldOptions {
+        self.push("SET").push(ty).push(offset.into()).push(value)
+    }
+
+    /// Increments an integer and replies its new value (`INCRBY`).
+    pub fn incr_by<O: Into<BitOffset>>(
+        self,
+        ty: BitFieldType,
+        offset: O,
+        increment: i64,
+    ) -> BitFieldOptions {
+        self.push("INCRBY")
+            .push(ty)
+            .push(offset.into())
+            .push(increment)
+    }
+
+    /// Sets the overflow behavior of the following `set` and `incr_by`
+    /// (`OVERFLOW`).
+    pub fn overflow(self, overflow: Overflow) -> BitFieldOptions {
+        self.push("OVERFLOW").push(overflow)
+    }
+}
+
+impl ToRedisArgs for BitFieldOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        for arg in &self.args {
+            out.write_arg(arg);
+        }
+    }
+}
+
+/// A range of a string for `BITCOUNT` and `BITPOS`.  Negative indices
+/// count from the end, `-1` is the last byte or bit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitRange {
+    /// From the start byte to the end byte, both inclusive.
+    Bytes(isize, isize),
+    /// From the start bit to the end bit, both inclusive (`BIT`,
+    /// redis 7).
+    Bits(isize, isize),
+}
+
+impl ToRedisArgs for BitRange {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        // bytes are the default unit, leaving out `BYTE` keeps the range
+        // working on servers before 7
+        match *self {
+            BitRange::Bytes(start, end) => {
+                start.write_redis_args(out);
+                end.write_redis_args(out);
+            }
+            BitRange::Bits(start, end) => {
+                start.write_redis_args(out);
+                end.write_redis_args(out);
+                out.write_arg(b"BIT");
+            }
+        }
+    }
+}
+
+/// The options of `LCS` with `IDX`, see `Commands::lcs_idx`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LcsOptions {
+    min_match_len: Option<usize>,
+    with_match_len: bool,
+}
+
+impl LcsOptions {
+    /// Creates options that return all matches without their lengths.
+    pub fn new() -> LcsOptions {
+        LcsOptions::default()
+    }
+
+    /// Leaves out matches shorter than `len` (`MINMATCHLEN`).
+    pub fn min_match_len(mut self, len: usize) -> LcsOptions {
+        self.min_match_len = Some(len);
+        self
+    }
+
+    /// Returns the length of every match (`WITHMATCHLEN`).
+    pub fn with_match_len(mut self) -> LcsOptions {
+        self.with_match_len = true;
+        self
+    }
+}
+
+impl ToRedisArgs for LcsOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(len) = self.min_match_len {
+            out.write_arg(b"MINMATCHLEN");
+            len.write_redis_args(out);
+        }
+        if self.with_match_len {
+            out.write_arg(b"WITHMATCHLEN");
+        }
+    }
+}
+
+/// A common substring of the two strings compared by `LCS`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LcsMatch {
+    /// The first and last index of the match in the first string.
+    pub first: (usize, usize),
+    /// The first and last index of the match in the second string.
+    pub second: (usize, usize),
+    /// The length of the match, if `with_match_len` was set.
+    pub len: Option<usize>,
+}
+
+impl FromRedisValue for LcsMatch {
+    fn from_redis_value(v: &Value) -> RedisResult<LcsMatch> {
+        match *v {
+            Value::Bulk(ref items) if items.len() == 2 || items.len() == 3 => Ok(LcsMatch {
+                first: from_redis_value(&items[0])?,
+                second: from_redis_value(&items[1])?,
+                len: match items.get(2) {
+                    Some(len) => Some(from_redis_value(len)?),
+                    None => None,
+                },
+            }),
+            _ => invalid_type_error!(v, "Response type not LCS match compatible"),
+        }
+    }
+}
+
+/// The reply of `LCS` with `IDX`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LcsReply {
+    /// The matches that make up the longest common subsequence, from the
+    /// end of the strings to their start.
+    pub matches: Vec<LcsMatch>,
+    /// The length of the longest common subsequence.
+    pub len: usize,
+}
+
+impl FromRedisValue for LcsReply {
+    fn from_redis_value(v: &Value) -> RedisResult<LcsReply> {
+        let mut fields: HashMap<String, Value> = from_redis_value(v)?;
+        match (fields.remove("matches"), fields.remove("len")) {
+            (Some(matches), Some(len)) => Ok(LcsReply {
+                matches: from_redis_value(&matches)?,
+                len: from_redis_value(&len)?,
+            }),
+            _ => invalid_type_error!(v, "Response type not LCS reply compatible"),
+        }
+    }
+}
+
+/// What the hash field expiration commands like `HEXPIRE` did with a
+/// field (redis 7.4).  The reply has one per field, so it is read as
+/// `Vec<FieldExpireResult>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldExpireResult {
+    /// The field does not exist (`-2`).
+    NoField,
+    /// The condition of the `ExpireOption` was not met (`0`).
+    ConditionNotMet,
+    /// The expiration was set (`1`).
+    Set,
+    /// The field was deleted because the expiration is in the past (`2`).
+    Deleted,
+}
+
+impl FromRedisValue for FieldExpireResult {
+    fn from_redis_value(v: &Value) -> RedisResult<FieldExpireResult> {
+        match from_redis_value::<i64>(v)? {
+            -2 => Ok(FieldExpireResult::NoField),
+            0 => Ok(FieldExpireResult::ConditionNotMet),
+            1 => Ok(FieldExpireResult::Set),
+            2 => Ok(FieldExpireResult::Deleted),
+            _ => invalid_type_error!(v, "Unknown field expire result"),
+        }
+    }
+}
+
+/// What `HPERSIST` did with a field (redis 7.4).  The reply has one per
+/// field, so it is read as `Vec<FieldPersistResult>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldPersistResult {
+    /// The field does not exist (`-2`).
+    NoField,
+    /// The field has no expiration (`-1`).
+    NoExpiry,
+    /// The expiration was removed (`1`).
+    Persisted,
+}
+
+impl FromRedisValue for FieldPersistResult {
+    fn from_redis_value(v: &Value) -> RedisResult<FieldPersistResult> {
+        match from_redis_value::<i64>(v)? {
+            -2 => Ok(FieldPersistResult::NoField),
+            -1 => Ok(FieldPersistResult::NoExpiry),
+            1 => Ok(FieldPersistResult::Persisted),
+            _ => invalid_type_error!(v, "Unknown field persist result"),
+        }
+    }
+}
+
+/// The options of the `SCAN` family, see `Commands::scan_options`.
+///
+/// ```rust,no_run
+/// # fn do_something() -> redis::RedisResult<()> {
+/// use redis::keys::KeyType;
+/// use redis::options::ScanOptions;
+/// use redis::Commands;
+///
+/// # let client = redis::Client::open("redis://127.0.0.1/")?;
+/// # let mut con = client.get_connection()?;
+/// let options = ScanOptions::new()
+///     .with_pattern("events:*")
+///     .with_count(1000)
+///     .with_type(KeyType::Stream);
+/// for key in con.scan_options::<String>(&options)? {
+///     println!("{}", key);
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScanOptions {
+    pattern: Option<Vec<Vec<u8>>>,
+    count: Option<usize>,
+    key_type: Option<KeyType>,
+}
+
+impl ScanOptions {
+    /// Creates options that iterate all keys or elements.
+    pub fn new() -> ScanOptions {
+        ScanOptions::default()
+    }
+
+    /// Only returns the keys or elements matching the glob-style pattern
+    /// (`MATCH`).
+    pub fn with_pattern<P: ToRedisArgs>(mut self, pattern: P) -> ScanOptions {
+        self.pattern = Some(pattern.to_redis_args());
+        self
+    }
+
+    /// Hints how many keys or elements the server looks at per call
+    /// (`COUNT`).  Larger counts need fewer round trips.
+    pub fn with_count(mut self, count: usize) -> ScanOptions {
+        self.count = Some(count);
+        self
+    }
+
+    /// Only returns the keys of the type (`TYPE`, redis 6).  Only for
+    /// `SCAN`.
+    pub fn with_type(mut self, key_type: KeyType) -> ScanOptions {
+        self.key_type = Some(key_type);
+        self
+    }
+}
+
+impl ToRedisArgs for ScanOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(ref pattern) = self.pattern {
+            out.write_arg(b"MATCH");
+            for arg in pattern {
+                out.write_arg(arg);
+            }
+        }
+        if let Some(count) = self.count {
+            out.write_arg(b"COUNT");
+            count.write_redis_args(out);
+        }
+        if let Some(ref key_type) = self.key_type {
+            out.write_arg(b"TYPE");
+            key_type.write_redis_args(out);
+        }
+    }
+}
+
+/// The options of `SORT` and `SORT_RO`, see `Commands::sort`.
+///
+/// Patterns like `weight_*` or `object_*->field` refer to other keys or
+/// hash fields, `*` is replaced by the element.  The reply has an element
+/// per `get` pattern for every sorted element, or the elements themselves
+/// without `get`.
+///
+/// ```rust,no_run
+/// # fn do_something() -> redis::RedisResult<()> {
+/// use redis::options::SortOptions;
+/// use redis::Commands;
+///
+/// # let client = redis::Client::open("redis://127.0.0.1/")?;
+/// # let mut con = client.get_connection()?;
+/// let options = SortOptions::new()
+///     .by("user:*->age")
+///     .get("#")
+///     .get("user:*->name")
+///     .desc()
+///     .limit(0, 10);
+/// let users: Vec<(String, Option<String>)> = con.sort("user_ids", &options)?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SortOptions {
+    by: Option<Vec<Vec<u8>>>,
+    limit: Option<(isize, isize)>,
+    get: Vec<Vec<Vec<u8>>>,
+    descending: bool,
+    alpha: bool,
+}
+
+impl SortOptions {
+    /// Creates options that sort the elements numerically in ascending
+    /// order.
+    pub fn new() -> SortOptions {
+        SortOptions::default()
+    }
+
+    /// Sorts by the values of the keys or hash fields the pattern refers
+    /// to (`BY`).  A pattern without `*`, like `nosort`, skips sorting.
+    pub fn by<P: ToRedisArgs>(mut self, pattern: P) -> SortOptions {
+        self.by = Some(pattern.to_redis_args());
+        self
+    }
+
+    /// Skips `offset` elements and returns at most `count` (`LIMIT`).
+    pub fn limit(mut self, offset: isize, count: isize) -> SortOptions {
+        self.limit = Some((offset, count));
+        self
+    }
+
+    /// Returns the values of the keys or hash fields the pattern refers to
+    /// instead of the elements, `#` returns the element itself (`GET`).
+    /// Can be given several times.
+    pub fn get<P: ToRedisArgs>(mut self, pattern: P) -> SortOptions {
+        self.get.push(pattern.to_redis_args());
+        self
+    }
+
+    /// Sorts in ascending order, the default (`ASC`).
+    pub fn asc(mut self) -> SortOptions {
+        self.descending = false;
+        self
+    }
+
+    /// Sorts in descending order (`DESC`).
+    pub fn desc(mut self) -> SortOptions {
+        self.descending = true;
+        self
+    }
+
+    /// Sorts lexicographically instead of numerically (`ALPHA`).
+    pub fn alpha(mut self) -> SortOptions {
+        self.alpha = true;
+        self
+    }
+}
+
+impl ToRedisArgs for SortOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(ref by) = self.by {
+            out.write_arg(b"BY");
+            for arg in by {
+                out.write_arg(arg);
+            }
+        }
+        if let Some((offset, count)) = self.limit {
+            out.write_arg(b"LIMIT");
+            offset.write_redis_args(out);
+            count.write_redis_args(out);
+        }
+        for pattern in &self.get {
+            out.write_arg(b"GET");
+            for arg in pattern {
+                out.write_arg(arg);
+            }
+        }
+        if self.descending {
+            out.write_arg(b"DESC");
+        }
+        if self.alpha {
+            out.write_arg(b"ALPHA");
+        }
+    }
+}
+
+/// The options of `RESTORE`, see `Commands::restore_options`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RestoreOptions {
+    replace: bool,
+    abs_ttl: bool,
+    idle_time: Option<u64>,
+    freq: Option<u8>,
+}
+
+impl RestoreOptions {
+    /// Creates options that fail if the key exists.
+    pub fn new() -> RestoreOptions {
+        RestoreOptions::default()
+    }
+
+    /// Overwrites the key if it exists (`REPLACE`).
+    pub fn replace(mut self) -> RestoreOptions {
+        self.replace = true;
+        self
+    }
+
+    /// Interprets the time to live as unix time in milliseconds
+    /// (`ABSTTL`, redis 5).
+    pub fn abs_ttl(mut self) -> RestoreOptions {
+        self.abs_ttl = true;
+        self
+    }
+
+    /// Sets the seconds since the key was last accessed, for eviction by
+    /// LRU (`IDLETIME`, redis 5).
+    pub fn idle_time(mut self, secs: u64) -> RestoreOptions {
+        self.idle_time = Some(secs);
+        self
+    }
+
+    /// Sets the access frequency counter, for eviction by LFU (`FREQ`,
+    /// redis 5).
+    pub fn freq(mut self, freq: u8) -> RestoreOptions {
+        self.freq = Some(freq);
+        self
+    }
+}
+
+impl ToRedisArgs for RestoreOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if self.replace {
+            out.write_arg(b"REPLACE");
+        }
+        if self.abs_ttl {
+            out.write_arg(b"ABSTTL");
+        }
+        if let Some(secs) = self.idle_time {
+            out.write_arg(b"IDLETIME");
+            secs.write_redis_args(out);
+        }
+        if let Some(freq) = self.freq {
+            out.write_arg(b"FREQ");
+            (freq as u32).write_redis_args(out);
+        }
+    }
+}
+
+/// The options of `MIGRATE`, see `Commands::migrate`.
+///
+/// The reply is `OK`, or `NOKEY` if none of the keys exist, so it is read
+/// as `String`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MigrateOptions {
+    copy: bool,
+    replace: bool,
+    auth: Option<(Option<String>, String)>,
+}
+
+impl MigrateOptions {
+    /// Creates options that move the keys and fail if they exist on the
+    /// target.
+    pub fn new() -> MigrateOptions {
+        MigrateOptions::default()
+    }
+
+    /// Keeps the keys on the source server (`COPY`).
+    pub fn copy(mut self) -> MigrateOptions {
+        self.copy = true;
+        self
+    }
+
+    /// Overwrites existing keys on the target (`REPLACE`).
+    pub fn replace(mut self) -> MigrateOptions {
+        self.replace = true;
+        self
+    }
+
+    /// Authenticates with the password at the target (`AUTH`).
+    pub fn auth(mut self, password: &str) -> MigrateOptions {
+        self.auth = Some((None, password.to_string()));
+        self
+    }
+
+    /// Authenticates as the user at the target (`AUTH2`, redis 6).
+    pub fn auth2(mut self, username: &str, password: &str) -> MigrateOptions {
+        self.auth = Some((Some(username.to_string()), password.to_string()));
+        self
+    }
+}
+
+impl ToRedisArgs for MigrateOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if self.copy {
+            out.write_arg(b"COPY");
+        }
+        if self.replace {
+            out.write_arg(b"REPLACE");
+        }
+        match self.auth {
+            Some((Some(ref username), ref password)) => {
+                out.write_arg(b"AUTH2");
+                out.write_arg(username.as_bytes());
+                out.write_arg(password.as_bytes());
+            }
+            Some((None, ref password)) => {
+                out.write_arg(b"AUTH");
+                out.write_arg(password.as_bytes());
+            }
+            None => {}
+        }
+    }
+}
+
+/// How `FLUSHDB` and `FLUSHALL` free the memory of the deleted keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlushMode {
+    /// Before replying (`SYNC`, redis 6.2).
+    Sync,
+    /// In the background after replying (`ASYNC`, redis 4).
+    Async,
+}
+
+impl ToRedisArgs for FlushMode {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            FlushMode::Sync => &b"SYNC"[..],
+            FlushMode::Async => &b"ASYNC"[..],
+        })
+    }
+}
+
+/// The options of `SHUTDOWN`, see `Commands::shutdown`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShutdownOptions {
+    save: Option<bool>,
+    now: bool,
+    force: bool,
+}
+
+impl ShutdownOptions {
+    /// Creates options that save the dataset if save points are
+    /// configured and wait for replicas to catch up.
+    pub fn new() -> ShutdownOptions {
+        ShutdownOptions::default()
+    }
+
+    /// Saves the dataset even without configured save points (`SAVE`).
+    pub fn save(mut self) -> ShutdownOptions {
+        self.save = Some(true);
+        self
+    }
+
+    /// Does not save the dataset even with configured save points
+    /// (`NOSAVE`).
+    pub fn nosave(mut self) -> ShutdownOptions {
+        self.save = Some(false);
+        self
+    }
+
+    /// Does not wait for lagging replicas (`NOW`, redis 7).
+    pub fn now(mut self) -> ShutdownOptions {
+        self.now = true;
+        self
+    }
+
+    /// Shuts down even if saving the dataset fails (`FORCE`, redis 7).
+    pub fn force(mut self) -> ShutdownOptions {
+        self.force = true;
+        self
+    }
+}
+
+impl ToRedisArgs for ShutdownOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        match self.save {
+            Some(true) => out.write_arg(b"SAVE"),
+            Some(false) => out.write_arg(b"NOSAVE"),
+            None => {}
+        }
+        if self.now {
+            out.write_arg(b"NOW");
+        }
+        if self.force {
+            out.write_arg(b"FORCE");
+        }
+    }
+}
@@ -1,11 +1,22 @@
+use std::error;
+use std::fmt;
 use std::io::{self, BufRead};
+use std::mem;
 use std::str;
 
-use types::{make_extension_error, ErrorKind, RedisError, RedisResult, Value};
+use connection::ReplyLimits;
+use types::{
+    make_extension_error, make_server_error, ErrorKind, PushKind, RedisError, RedisResult, Value,
+    ValueRef,
+};
 
-use bytes::BytesMut;
 use futures::{Async, Future, Poll};
+
+#[cfg(feature = "aio")]
+use bytes::{Bytes, BytesMut};
+#[cfg(feature = "aio")]
 use tokio_io::codec::{Decoder, Encoder};
+#[cfg(feature = "aio")]
 use tokio_io::AsyncRead;
 
 use combine;
@@ -53,11 +64,86 @@ where
     }
 }
 
+// Turns the line of an error reply, like `ERR unknown command`, into an error.
+pub fn error_from_line(line: &str) -> RedisError {
+    let mut pieces = line.splitn(2, ' ');
+    let code = pieces.next().unwrap();
+    match ErrorKind::from_code(code) {
+        Some(kind) => make_server_error(kind, pieces.next()),
+        None => make_extension_error(code, pieces.next()),
+    }
+}
+
+// Raised by the parser for a length prefix beyond the `ReplyLimits`, so
+// that it can be told apart from malformed data.
+#[derive(Debug)]
+struct LimitExceeded(&'static str);
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl error::Error for LimitExceeded {
+    fn description(&self) -> &str {
+        self.0
+    }
+}
+
+fn limit_error(detail: &str) -> RedisError {
+    RedisError::from((
+        ErrorKind::ReplyTooLarge,
+        "Reply exceeds the configured limits",
+        detail.to_string(),
+    ))
+}
+
+// Returns the limit error among the errors of a failed parse, if any.
+fn exceeded_limit<T, R, P>(err: &combine::easy::Errors<T, R, P>) -> Option<RedisError> {
+    err.errors
+        .iter()
+        .filter_map(|err| match *err {
+            combine::easy::Error::Other(ref err) => err.downcast_ref::<LimitExceeded>(),
+            _ => None,
+        })
+        .map(|err| limit_error(err.0))
+        .next()
+}
+
+// Pairs up the keys and values of a map.
+fn into_map(items: Vec<Value>) -> Value {
+    let mut pairs = Vec::with_capacity(items.len() / 2);
+    let mut iter = items.into_iter();
+    while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+        pairs.push((key, value));
+    }
+    Value::Map(pairs)
+}
+
+// Splits a push message into its kind and the remaining items.
+fn into_push(items: Vec<Value>) -> Value {
+    let mut iter = items.into_iter();
+    let kind = match iter.next() {
+        Some(Value::Data(ref name)) => PushKind::from_name(&String::from_utf8_lossy(name)),
+        Some(Value::Status(ref name)) => PushKind::from_name(name),
+        _ => PushKind::Other(String::new()),
+    };
+    Value::Push {
+        kind,
+        data: iter.collect(),
+    }
+}
+
+// Attributes are skipped.
 parser! {
     type PartialState = AnySendPartialState;
-    fn value['a, I]()(I) -> RedisResult<Value>
+    fn value['a, I](limits: ReplyLimits)(I) -> RedisResult<Value>
         where [I: FullRangeStream<Item = u8, Range = &'a [u8]> ]
     {
+        // `limits` is borrowed from the parser, the nested parsers get copies
+        let limits = *limits;
+
         let line = || recognize(take_until_bytes(&b"\r\n"[..]).with(take(2).map(|_| ())))
             .and_then(|line: &[u8]| {
                 str::from_utf8(&line[..line.len() - 2])
@@ -79,7 +165,20 @@ parser! {
             }
         });
 
-        let data = || int().then_partial(move |size| {
+        // The length of a string or aggregate, which must not exceed `max`
+        let limited_int = |max: usize, detail: &'static str| int().and_then(move |n| {
+            if n > 0 && n as u64 > max as u64 {
+                Err(StreamErrorFor::<I>::other(LimitExceeded(detail)))
+            } else {
+                Ok(n)
+            }
+        });
+
+        let bulk_size = || limited_int(limits.max_bulk_size, "String exceeds the maximum size");
+
+        let array_len = || limited_int(limits.max_array_len, "Aggregate exceeds the maximum length");
+
+        let data = || bulk_size().then_partial(move |size| {
             if *size < 0 {
                 combine::value(Value::Nil).left()
             } else {
@@ -91,12 +190,12 @@ parser! {
         });
 
         let bulk = || {
-            int().then_partial(|&mut length| {
+            array_len().then_partial(move |&mut length| {
                 if length < 0 {
                     combine::value(Value::Nil).map(Ok).left()
                 } else {
                     let length = length as usize;
-                    combine::count_min_max(length, length, value())
+                    combine::count_min_max(length, length, value(limits))
                         .map(|result: ResultExtend<_, _>| {
                             result.0.map(Value::Bulk)
                         })
@@ -105,42 +204,107 @@ parser! {
             })
         };
 
-        let error = || {
-            line()
-                .map(|line: &str| {
-                    let desc = "An error was signalled by the server";
-                    let mut pieces = line.splitn(2, ' ');
-                    let kind = match pieces.next().unwrap() {
-                        "ERR" => ErrorKind::ResponseError,
-                        "EXECABORT" => ErrorKind::ExecAbortError,
-                        "LOADING" => ErrorKind::BusyLoadingError,
-                        "NOSCRIPT" => ErrorKind::NoScriptError,
-                        code => {
-                            return make_extension_error(code, pieces.next())
-                        }
-                    };
-                    match pieces.next() {
-                        Some(detail) => RedisError::from((kind, desc, detail.to_string())),
-                        None => RedisError::from((kind, desc)),
-                    }
-                })
+        let error = || line().map(error_from_line);
+
+        let null = || line().map(|_| Value::Nil);
+
+        let boolean = || line().and_then(|line: &str| {
+            match line {
+                "t" => Ok(Value::Boolean(true)),
+                "f" => Ok(Value::Boolean(false)),
+                _ => Err(StreamErrorFor::<I>::message_static_message("Expected boolean, got garbage")),
+            }
+        });
+
+        let double = || line().and_then(|line: &str| {
+            match line.trim() {
+                "inf" => Ok(Value::Double(::std::f64::INFINITY)),
+                "-inf" => Ok(Value::Double(::std::f64::NEG_INFINITY)),
+                "nan" => Ok(Value::Double(::std::f64::NAN)),
+                line => match line.parse::<f64>() {
+                    Ok(value) => Ok(Value::Double(value)),
+                    Err(_) => Err(StreamErrorFor::<I>::message_static_message("Expected double, got garbage")),
+                },
+            }
+        });
+
+        let big_number = || line().map(|line: &str| Value::BigNumber(line.into()));
+
+        // The data is copied before the trailing CRLF is parsed so that the
+        // partial state does not borrow from the input.
+        let blob = || bulk_size().then_partial(|&mut size| {
+            take(size.max(0) as usize).map(|bs: &[u8]| bs.to_vec()).skip(crlf())
+        });
+
+        let blob_error = || blob().map(|bs: Vec<u8>| error_from_line(&String::from_utf8_lossy(&bs)));
+
+        let verbatim = || blob().and_then(|bs: Vec<u8>| {
+            // the text is prefixed with its format, like `txt:`
+            let (format, text) = if bs.len() >= 4 && bs[3] == b':' {
+                (&bs[..3], &bs[4..])
+            } else {
+                (&b"txt"[..], &bs[..])
+            };
+            match (str::from_utf8(format), str::from_utf8(text)) {
+                (Ok(format), Ok(text)) => Ok(Value::VerbatimString {
+                    format: format.into(),
+                    text: text.into(),
+                }),
+                _ => Err(StreamErrorFor::<I>::message_static_message("Expected text, got binary data")),
+            }
+        });
+
+        // An aggregate of `length * factor` values, like the keys and values of a map
+        let aggregate = |factor: usize| {
+            array_len().then_partial(move |&mut length| {
+                let length = length.max(0) as usize * factor;
+                combine::count_min_max(length, length, value(limits))
+                    .map(|result: ResultExtend<Vec<Value>, _>| result.0)
+            })
         };
 
+        let attribute = || aggregate(2).with(value(limits));
+
         any_send_partial_state(choice((
            byte(b'+').with(status().map(Ok)),
            byte(b':').with(int().map(Value::Int).map(Ok)),
            byte(b'$').with(data().map(Ok)),
            byte(b'*').with(bulk()),
-           byte(b'-').with(error().map(Err))
+           byte(b'-').with(error().map(Err)),
+           byte(b'_').with(null().map(Ok)),
+           byte(b'#').with(boolean().map(Ok)),
+           byte(b',').with(double().map(Ok)),
+           byte(b'(').with(big_number().map(Ok)),
+           byte(b'=').with(verbatim().map(Ok)),
+           byte(b'!').with(blob_error().map(Err)),
+           byte(b'%').with(aggregate(2).map(|result| result.map(into_map))),
+           byte(b'~').with(aggregate(1).map(|result| result.map(Value::Set))),
+           byte(b'>').with(aggregate(1).map(|result| result.map(into_push))),
+           byte(b'|').with(attribute())
         )))
     }
 }
 
+#[cfg(feature = "aio")]
 #[derive(Default)]
 pub struct ValueCodec {
     state: AnySendPartialState,
+    limits: ReplyLimits,
+    // the bytes of the current reply that were parsed already
+    consumed: usize,
+}
+
+#[cfg(feature = "aio")]
+impl ValueCodec {
+    pub fn new(limits: ReplyLimits) -> ValueCodec {
+        ValueCodec {
+            limits,
+            ..Default::default()
+        }
+    }
 }
 
+#[cfg(feature = "aio")]
 impl Encoder for ValueCodec {
     type Item = Vec<u8>;
     type Error = RedisError;
@@ -150,6 +314,7 @@ impl Encoder for ValueCodec {
     }
 }
 
+#[cfg(feature = "aio")]
 impl Decoder for ValueCodec {
     type Item = Value;
     type Error = RedisError;
@@ -157,25 +322,202 @@ impl Decoder for ValueCodec {
         let (opt, removed_len) = {
             let buffer = &bytes[..];
             let stream = combine::easy::Stream(combine::stream::PartialStream(buffer));
-            match combine::stream::decode(value(), stream, &mut self.state) {
+            match combine::stream::decode(value(self.limits), stream, &mut self.state) {
                 Ok(x) => x,
                 Err(err) => {
+                    if let Some(err) = exceeded_limit(&err) {
+                        return Err(err);
+                    }
                     let err = err
                         .map_position(|pos| pos.translate_position(buffer))
                         .map_range(|range| format!("{:?}", range))
                         .to_string();
-                    return Err(RedisError::from((
-                        ErrorKind::ResponseError,
-                        "parse error",
-                        err,
+                    // the rest of the stream cannot be matched to requests anymore, so
+                    // this is reported like a broken connection
+                    return Err(RedisError::from(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("parse error: {}", err),
                     )));
                 }
             }
         };
 
         bytes.split_to(removed_len);
+        self.consumed += removed_len;
         match opt {
-            Some(result) => Ok(Some(result?)),
+            Some(result) => {
+                let consumed = mem::replace(&mut self.consumed, 0);
+                if consumed > self.limits.max_reply_size {
+                    return Err(limit_error("Reply exceeds the maximum size"));
+                }
+                Ok(Some(result?))
+            }
+            None => {
+                if self.consumed + bytes.len() > self.limits.max_reply_size {
+                    return Err(limit_error("Reply exceeds the maximum size"));
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// A complete reply that is kept in the buffer it was read into.
+///
+/// Reading a `Reply` allocates one buffer for the whole reply instead of
+/// one for every string in it.  `value` parses it into a `ValueRef` that
+/// borrows the strings from the buffer, and `Bytes::slice_ref` on
+/// `as_bytes` keeps one of them around without copying it.
+///
+/// Replies are read by `aio::Connection::read_reply` and `ReplyCodec`;
+/// the other connections still decode every reply into a `Value`.
+#[cfg(feature = "aio")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reply(Bytes);
+
+#[cfg(feature = "aio")]
+impl Reply {
+    /// Parses the reply.  An error reply of the server is returned as an
+    /// error.
+    pub fn value(&self) -> RedisResult<ValueRef> {
+        parse_redis_value_ref(&self.0)
+    }
+
+    /// Returns the buffer of the reply, which holds its RESP encoding.
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.0
+    }
+}
+
+#[cfg(feature = "aio")]
+fn invalid_data(detail: &str) -> RedisError {
+    // the rest of the stream cannot be matched to requests anymore, so
+    // this is reported like a broken connection
+    RedisError::from(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("parse error: {}", detail),
+    ))
+}
+
+#[cfg(feature = "aio")]
+fn scan_int(line: &[u8]) -> RedisResult<i64> {
+    str::from_utf8(line)
+        .ok()
+        .and_then(|line| line.trim().parse().ok())
+        .ok_or_else(|| invalid_data("Expected integer, got garbage"))
+}
+
+// Finds the end of a reply without building its value.  The scan is
+// resumed where it stopped once more data arrived: `pos` is the offset of
+// the next value to read and `missing` the number of values still missing.
+#[cfg(feature = "aio")]
+#[derive(Default)]
+struct ReplyScanner {
+    pos: usize,
+    missing: usize,
+}
+
+#[cfg(feature = "aio")]
+impl ReplyScanner {
+    // Returns the length of the reply at the start of the buffer, or `None`
+    // if it is not complete yet.
+    fn scan(&mut self, buf: &[u8], limits: &ReplyLimits) -> RedisResult<Option<usize>> {
+        if self.missing == 0 {
+            // a new reply
+            self.pos = 0;
+            self.missing = 1;
+        }
+        while self.missing > 0 {
+            let rest = &buf[self.pos..];
+            let end = match rest.windows(2).position(|w| w == b"\r\n") {
+                Some(end) if end > 0 => end,
+                Some(_) => return Err(invalid_data("Invalid response when parsing value")),
+                None => {
+                    if buf.len() > limits.max_reply_size {
+                        return Err(limit_error("Reply exceeds the maximum size"));
+                    }
+                    return Ok(None);
+                }
+            };
+            let line = &rest[1..end];
+            let mut next = self.pos + end + 2;
+            let mut values = 0;
+            match rest[0] {
+                b'$' | b'=' | b'!' => {
+                    let size = scan_int(line)?;
+                    if size > 0 && size as u64 > limits.max_bulk_size as u64 {
+                        return Err(limit_error("String exceeds the maximum size"));
+                    }
+                    if size >= 0 {
+                        next += size as usize + 2;
+                    }
+                }
+                prefix @ b'*' | prefix @ b'~' | prefix @ b'>' | prefix @ b'%' | prefix @ b'|' => {
+                    let length = scan_int(line)?;
+                    if length > 0 && length as u64 > limits.max_array_len as u64 {
+                        return Err(limit_error("Aggregate exceeds the maximum length"));
+                    }
+                    let length = length.max(0) as usize;
+                    values = match prefix {
+                        b'%' => length * 2,
+                        // attributes are followed by the value they describe
+                        b'|' => length * 2 + 1,
+                        _ => length,
+                    };
+                }
+                b'+' | b'-' | b':' | b'_' | b'#' | b',' | b'(' => {}
+                _ => return Err(invalid_data("Invalid response when parsing value")),
+            }
+            if next > limits.max_reply_size {
+                return Err(limit_error("Reply exceeds the maximum size"));
+            }
+            if next > buf.len() {
+                return Ok(None);
+            }
+            self.pos = next;
+            self.missing += values;
+            self.missing -= 1;
+        }
+        Ok(Some(self.pos))
+    }
+}
+
+/// Decodes replies into `Reply`s, which share the read buffer instead of
+/// copying their strings out of it.
+#[cfg(feature = "aio")]
+#[derive(Default)]
+pub struct ReplyCodec {
+    scanner: ReplyScanner,
+    limits: ReplyLimits,
+}
+
+#[cfg(feature = "aio")]
+impl ReplyCodec {
+    pub fn new(limits: ReplyLimits) -> ReplyCodec {
+        ReplyCodec {
+            limits,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(feature = "aio")]
+impl Encoder for ReplyCodec {
+    type Item = Vec<u8>;
+    type Error = RedisError;
+    fn encode(&mut self, item: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.extend(item);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "aio")]
+impl Decoder for ReplyCodec {
+    type Item = Reply;
+    type Error = RedisError;
+    fn decode(&mut self, bytes: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.scanner.scan(bytes, &self.limits)? {
+            Some(len) => Ok(Some(Reply(bytes.split_to(len).freeze()))),
             None => Ok(None),
         }
     }
@@ -187,16 +529,18 @@ pub struct ValueFuture<R> {
     // Intermediate storage for data we know that we need to parse a value but we haven't been able
     // to parse completely yet
     remaining: Vec<u8>,
+    limits: ReplyLimits,
+    // the bytes of the reply that were consumed from `reader` already
+    consumed: usize,
 }
 
-impl<R> Future for ValueFuture<R>
+impl<R> ValueFuture<R>
 where
     R: BufRead,
 {
-    type Item = (R, Value);
-    type Error = RedisError;
-
-    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+    // Parses the next value.  An error reply of the server is returned
+    // together with the reader so that the connection can still be used.
+    fn poll_reply(&mut self) -> Poll<(R, RedisResult<Value>), RedisError> {
         loop {
             assert!(
                 self.reader.is_some(),
@@ -205,7 +549,13 @@ where
             let remaining_data = self.remaining.len();
 
             let (opt, mut removed) = {
-                let buffer = try_nb!(self.reader.as_mut().unwrap().fill_buf());
+                let buffer = match self.reader.as_mut().unwrap().fill_buf() {
+                    Ok(buffer) => buffer,
+                    Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        return Ok(Async::NotReady)
+                    }
+                    Err(err) => return Err(err.into()),
+                };
                 if buffer.len() == 0 {
                     fail!((ErrorKind::ResponseError, "Could not read enough bytes"))
                 }
@@ -216,9 +566,12 @@ where
                     buffer
                 };
                 let stream = combine::easy::Stream(combine::stream::PartialStream(buffer));
-                match combine::stream::decode(value(), stream, &mut self.state) {
+                match combine::stream::decode(value(self.limits), stream, &mut self.state) {
                     Ok(x) => x,
                     Err(err) => {
+                        if let Some(err) = exceeded_limit(&err) {
+                            return Err(err);
+                        }
                         let err = err
                             .map_position(|pos| pos.translate_position(buffer))
                             .map_range(|range| format!("{:?}", range))
@@ -245,9 +598,12 @@ where
 
             match opt {
                 Some(value) => {
+                    if self.consumed + removed > self.limits.max_reply_size {
+                        fail!(limit_error("Reply exceeds the maximum size"));
+                    }
                     self.reader.as_mut().unwrap().consume(removed);
                     let reader = self.reader.take().unwrap();
-                    return Ok(Async::Ready((reader, value?)));
+                    return Ok(Async::Ready((reader, value)));
                 }
                 None => {
                     // We have not enough data to produce a Value but we know that all the data of
@@ -261,13 +617,129 @@ where
                         buffer.len()
                     };
                     self.reader.as_mut().unwrap().consume(buffer_len);
+                    self.consumed += buffer_len;
+                    if self.consumed > self.limits.max_reply_size {
+                        fail!(limit_error("Reply exceeds the maximum size"));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<R> Future for ValueFuture<R>
+where
+    R: BufRead,
+{
+    type Item = (R, Value);
+    type Error = RedisError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let (reader, value) = try_ready!(self.poll_reply());
+        Ok(Async::Ready((reader, value?)))
+    }
+}
+
+// Reads the next reply into one buffer, see `Reply`.
+#[cfg(feature = "aio")]
+pub struct ReplyFuture<R> {
+    reader: Option<R>,
+    buffer: BytesMut,
+    scanner: ReplyScanner,
+    limits: ReplyLimits,
+}
+
+#[cfg(feature = "aio")]
+impl<R> Future for ReplyFuture<R>
+where
+    R: BufRead,
+{
+    type Item = (R, Reply);
+    type Error = RedisError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let (used, end) = {
+                let reader = self
+                    .reader
+                    .as_mut()
+                    .expect("ReplyFuture: poll called on completed future");
+                let data = match reader.fill_buf() {
+                    Ok(data) => data,
+                    Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        return Ok(Async::NotReady)
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                if data.is_empty() {
+                    fail!((ErrorKind::ResponseError, "Could not read enough bytes"))
                 }
+                let start = self.buffer.len();
+                self.buffer.extend_from_slice(data);
+                match self.scanner.scan(&self.buffer, &self.limits)? {
+                    // the rest of the data belongs to the next reply
+                    Some(end) => (end - start, Some(end)),
+                    None => (data.len(), None),
+                }
+            };
+            self.reader.as_mut().unwrap().consume(used);
+            if let Some(end) = end {
+                self.buffer.truncate(end);
+                let reply = Reply(self.buffer.take().freeze());
+                return Ok(Async::Ready((self.reader.take().unwrap(), reply)));
             }
         }
     }
 }
 
+#[cfg(feature = "aio")]
+pub fn read_async_reply<R>(reader: R, limits: ReplyLimits) -> ReplyFuture<R>
+where
+    R: AsyncRead + BufRead,
+{
+    ReplyFuture {
+        reader: Some(reader),
+        buffer: BytesMut::new(),
+        scanner: Default::default(),
+        limits,
+    }
+}
+
+/// Like `parse_async` but an error reply of the server is returned with the
+/// reader instead of failing the future.
+#[cfg(feature = "aio")]
+pub fn parse_async_reply<R>(
+    reader: R,
+    limits: ReplyLimits,
+) -> impl Future<Item = (R, RedisResult<Value>), Error = RedisError>
+where
+    R: AsyncRead + BufRead,
+{
+    let mut parser = ValueFuture {
+        reader: Some(reader),
+        state: Default::default(),
+        remaining: Vec::new(),
+        limits,
+        consumed: 0,
+    };
+    ::futures::future::poll_fn(move || parser.poll_reply())
+}
+
+#[cfg(feature = "aio")]
 pub fn parse_async<R>(reader: R) -> impl Future<Item = (R, Value), Error = RedisError>
+where
+    R: AsyncRead + BufRead,
+{
+    parse_async_with_limits(reader, ReplyLimits::default())
+}
+
+/// Like `parse_async` but fails with `ErrorKind::ReplyTooLarge` for a
+/// reply beyond the limits.
+#[cfg(feature = "aio")]
+pub fn parse_async_with_limits<R>(
+    reader: R,
+    limits: ReplyLimits,
+) -> impl Future<Item = (R, Value), Error = RedisError>
 where
     R: AsyncRead + BufRead,
 {
@@ -275,12 +747,16 @@ where
         reader: Some(reader),
         state: Default::default(),
         remaining: Vec::new(),
+        limits,
+        consumed: 0,
     }
 }
 
 /// The internal redis response parser.
 pub struct Parser<T> {
     reader: T,
+    buffer: Vec<u8>,
+    limits: ReplyLimits,
 }
 
 /// The parser can be used to parse redis responses into values.  Generally
@@ -293,21 +769,55 @@ impl<'a, T: BufRead> Parser<T> {
     /// be invoked multiple times.  In other words: the stream does not have
     /// to be terminated.
     pub fn new(reader: T) -> Parser<T> {
-        Parser { reader: reader }
+        Parser::with_limits(reader, ReplyLimits::default())
+    }
+
+    /// Creates a parser that fails with `ErrorKind::ReplyTooLarge` for
+    /// values beyond the limits.
+    pub fn with_limits(reader: T, limits: ReplyLimits) -> Parser<T> {
+        Parser {
+            reader,
+            buffer: Vec::new(),
+            limits,
+        }
+    }
+
+    /// Returns the reader.
+    pub fn into_inner(self) -> T {
+        self.reader
     }
 
     // public api
 
+    /// Parses the next value.  An error reply is returned as an `Err`
+    /// whose `code` is the one sent by the server.
     pub fn parse_value(&mut self) -> RedisResult<Value> {
-        let mut parser = ValueFuture {
-            reader: Some(&mut self.reader),
-            state: Default::default(),
-            remaining: Vec::new(),
-        };
-        match parser.poll()? {
-            Async::NotReady => Err(io::Error::from(io::ErrorKind::WouldBlock).into()),
-            Async::Ready((_, value)) => Ok(value),
-        }
+        parse_value_with_buffer(&mut self.reader, &mut self.buffer, self.limits)
+    }
+}
+
+/// Like `Parser::parse_value` but a reply that does not fit into the buffer
+/// of the reader is assembled in `buffer`, whose allocation is kept for the
+/// next reply.  A reply beyond the limits fails with
+/// `ErrorKind::ReplyTooLarge`.
+pub fn parse_value_with_buffer<R: BufRead>(
+    reader: R,
+    buffer: &mut Vec<u8>,
+    limits: ReplyLimits,
+) -> RedisResult<Value> {
+    buffer.clear();
+    let mut parser = ValueFuture {
+        reader: Some(reader),
+        state: Default::default(),
+        remaining: mem::replace(buffer, Vec::new()),
+        limits,
+        consumed: 0,
+    };
+    let result = parser.poll();
+    *buffer = mem::replace(&mut parser.remaining, Vec::new());
+    match result? {
+        Async::NotReady => Err(io::Error::from(io::ErrorKind::WouldBlock).into()),
+        Async::Ready((_, value)) => Ok(value),
     }
 }
 
@@ -319,3 +829,176 @@ pub fn parse_redis_value(bytes: &[u8]) -> RedisResult<Value> {
     let mut parser = Parser::new(bytes);
     parser.parse_value()
 }
+
+/// Parses bytes into a value that borrows its strings from them instead
+/// of copying each into a new allocation.
+///
+/// Like `parse_redis_value` this parses the first value of the bytes and
+/// fails if they do not hold a complete value.
+///
+/// ```rust
+/// use redis::{parse_redis_value_ref, ValueRef};
+///
+/// let value = parse_redis_value_ref(b"*2\r\n$3\r\nfoo\r\n:42\r\n").unwrap();
+/// assert_eq!(value, ValueRef::Bulk(vec![ValueRef::Data(b"foo"), ValueRef::Int(42)]));
+/// ```
+pub fn parse_redis_value_ref(bytes: &[u8]) -> RedisResult<ValueRef> {
+    parse_ref(bytes).map(|(value, _)| value)
+}
+
+fn parse_error(detail: &str) -> RedisError {
+    RedisError::from((ErrorKind::ResponseError, "parse error", detail.to_string()))
+}
+
+// Splits off the line up to the next `\r\n`.
+fn split_line(buf: &[u8]) -> RedisResult<(&[u8], &[u8])> {
+    match buf.windows(2).position(|w| w == b"\r\n") {
+        Some(end) => Ok((&buf[..end], &buf[end + 2..])),
+        None => Err(parse_error("Could not read enough bytes")),
+    }
+}
+
+fn split_str_line(buf: &[u8]) -> RedisResult<(&str, &[u8])> {
+    let (line, rest) = split_line(buf)?;
+    match str::from_utf8(line) {
+        Ok(line) => Ok((line, rest)),
+        Err(_) => Err(parse_error("Expected text, got binary data")),
+    }
+}
+
+fn split_int(buf: &[u8]) -> RedisResult<(i64, &[u8])> {
+    let (line, rest) = split_str_line(buf)?;
+    match line.trim().parse() {
+        Ok(value) => Ok((value, rest)),
+        Err(_) => Err(parse_error("Expected integer, got garbage")),
+    }
+}
+
+// Splits off a string whose length precedes it.
+fn split_blob(buf: &[u8]) -> RedisResult<(&[u8], &[u8])> {
+    let (size, rest) = split_int(buf)?;
+    let size = size.max(0) as usize;
+    if rest.len() < size + 2 {
+        return Err(parse_error("Could not read enough bytes"));
+    }
+    if &rest[size..size + 2] != b"\r\n" {
+        return Err(parse_error("Expected \\r\\n after data"));
+    }
+    Ok((&rest[..size], &rest[size + 2..]))
+}
+
+// Parses `count` values.
+fn split_values(mut buf: &[u8], count: i64) -> RedisResult<(Vec<ValueRef>, &[u8])> {
+    let count = count.max(0) as usize;
+    // do not trust the count for the allocation, every value takes 3 bytes or more
+    let mut values = Vec::with_capacity(count.min(buf.len() / 3));
+    for _ in 0..count {
+        let (value, rest) = parse_ref(buf)?;
+        values.push(value);
+        buf = rest;
+    }
+    Ok((values, buf))
+}
+
+// Parses the value at the start of the buffer and returns the remaining bytes.
+fn parse_ref(buf: &[u8]) -> RedisResult<(ValueRef, &[u8])> {
+    let (&prefix, buf) = match buf.split_first() {
+        Some(split) => split,
+        None => return Err(parse_error("Could not read enough bytes")),
+    };
+    match prefix {
+        b'+' => {
+            let (line, rest) = split_str_line(buf)?;
+            let value = if line == "OK" {
+                ValueRef::Okay
+            } else {
+                ValueRef::Status(line)
+            };
+            Ok((value, rest))
+        }
+        b':' => split_int(buf).map(|(value, rest)| (ValueRef::Int(value), rest)),
+        b'$' => {
+            if buf.starts_with(b"-") {
+                let (_, rest) = split_line(buf)?;
+                return Ok((ValueRef::Nil, rest));
+            }
+            split_blob(buf).map(|(data, rest)| (ValueRef::Data(data), rest))
+        }
+        b'*' => {
+            let (length, rest) = split_int(buf)?;
+            if length < 0 {
+                return Ok((ValueRef::Nil, rest));
+            }
+            split_values(rest, length).map(|(values, rest)| (ValueRef::Bulk(values), rest))
+        }
+        b'-' => Err(error_from_line(split_str_line(buf)?.0)),
+        b'_' => split_line(buf).map(|(_, rest)| (ValueRef::Nil, rest)),
+        b'#' => match split_line(buf)? {
+            (b"t", rest) => Ok((ValueRef::Boolean(true), rest)),
+            (b"f", rest) => Ok((ValueRef::Boolean(false), rest)),
+            _ => Err(parse_error("Expected boolean, got garbage")),
+        },
+        b',' => {
+            let (line, rest) = split_str_line(buf)?;
+            let value = match line.trim() {
+                "inf" => ::std::f64::INFINITY,
+                "-inf" => ::std::f64::NEG_INFINITY,
+                "nan" => ::std::f64::NAN,
+                line => line
+                    .parse()
+                    .map_err(|_| parse_error("Expected double, got garbage"))?,
+            };
+            Ok((ValueRef::Double(value), rest))
+        }
+        b'(' => split_str_line(buf).map(|(line, rest)| (ValueRef::BigNumber(line), rest)),
+        b'=' => {
+            let (blob, rest) = split_blob(buf)?;
+            // the text is prefixed with its format, like `txt:`
+            let (format, text) = if blob.len() >= 4 && blob[3] == b':' {
+                (&blob[..3], &blob[4..])
+            } else {
+                (&b"txt"[..], blob)
+            };
+            match (str::from_utf8(format), str::from_utf8(text)) {
+                (Ok(format), Ok(text)) => Ok((ValueRef::VerbatimString { format, text }, rest)),
+                _ => Err(parse_error("Expected text, got binary data")),
+            }
+        }
+        b'!' => Err(error_from_line(&String::from_utf8_lossy(
+            split_blob(buf)?.0,
+        ))),
+        b'%' => {
+            let (length, rest) = split_int(buf)?;
+            let (items, rest) = split_values(rest, length.max(0) * 2)?;
+            let mut pairs = Vec::with_capacity(items.len() / 2);
+            let mut iter = items.into_iter();
+            while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+                pairs.push((key, value));
+            }
+            Ok((ValueRef::Map(pairs), rest))
+        }
+        b'~' => {
+            let (length, rest) = split_int(buf)?;
+            split_values(rest, length).map(|(values, rest)| (ValueRef::Set(values), rest))
+        }
+        b'>' => {
+            let (length, rest) = split_int(buf)?;
+            let (values, rest) = split_values(rest, length)?;
+            let mut iter = values.into_iter();
+            let kind = match iter.next() {
+                Some(ValueRef::Data(name)) => PushKind::from_name(&String::from_utf8_lossy(name)),
+                Some(ValueRef::Status(name)) => PushKind::from_name(name),
+                _ => PushKind::Other(String::new()),
+            };
+            let data = iter.collect();
+            Ok((ValueRef::Push { kind, data }, rest))
+        }
+        // attributes are skipped
+        b'|' => {
+            let (length, rest) = split_int(buf)?;
+            let (_, rest) = split_values(rest, length.max(0) * 2)?;
+            parse_ref(rest)
+        }
+        _ => Err(parse_error("Invalid response when parsing value")),
+    }
+}
@@ -0,0 +1,742 @@
+//! A simple pool of connections.
+//!
+//! `Pool` hands out blocking connections and `AsyncPool` async ones; both
+//! share the same configuration and statistics.  This module is only
+//! available with the `with-pool` feature, `AsyncPool` additionally needs
+//! the `tokio-comp` or the `async-std-comp` feature.
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "aio")]
+use futures::future;
+#[cfg(feature = "aio")]
+use futures::task;
+use futures::task::Task;
+#[cfg(feature = "aio")]
+use futures::{Async, Future, Poll};
+#[cfg(feature = "aio")]
+use runtime::Delay;
+
+#[cfg(feature = "aio")]
+use aio;
+use client::Client;
+use cmd::{cmd, Cmd};
+use connection::{Connection, ConnectionLike};
+#[cfg(feature = "aio")]
+use types::RedisFuture;
+use types::{RedisError, RedisResult, Value};
+
+/// Decides when a pooled connection is validated before it is handed out.
+///
+/// A validated connection is sent a `PING` and if that fails the connection
+/// is discarded and another one is checked out instead.  This avoids that
+/// the first command after a long idle period fails because the server or a
+/// load balancer closed the socket in the meantime.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HealthCheck {
+    /// Connections are handed out without validation.
+    Never,
+    /// Every connection is validated on checkout.
+    Always,
+    /// Only connections that were idle for at least the given duration are
+    /// validated.
+    IdleLongerThan(Duration),
+}
+
+/// Configures the behavior of a `Pool`.
+#[derive(Clone, Debug)]
+pub struct PoolConfig {
+    /// The maximum number of connections the pool keeps open, both idle
+    /// and checked out ones.
+    pub max_size: usize,
+    /// The number of connections that are opened when the pool is created.
+    pub min_idle: usize,
+    /// How long `Pool::get` and `AsyncPool::get` wait for a connection to
+    /// become available before they give up.
+    pub connection_timeout: Duration,
+    /// When to validate a connection before handing it out.
+    pub health_check: HealthCheck,
+    /// Connections older than this are closed instead of being reused.
+    pub max_lifetime: Option<Duration>,
+    /// Connections that were idle for longer than this are closed instead
+    /// of being reused.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> PoolConfig {
+        PoolConfig {
+            max_size: 10,
+            min_idle: 0,
+            connection_timeout: Duration::from_secs(30),
+            health_check: HealthCheck::Never,
+            max_lifetime: None,
+            idle_timeout: None,
+        }
+    }
+}
+
+/// A snapshot of the state of a `Pool`, as returned by `Pool::status`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoolStatus {
+    /// The number of open connections, idle and checked out ones.
+    pub connections: usize,
+    /// The number of idle connections.
+    pub idle_connections: usize,
+    /// The number of connections that are checked out.
+    pub in_use: usize,
+    /// The number of connections opened since the pool was created.
+    pub connections_created: u64,
+    /// The number of connections closed since the pool was created, either
+    /// because they broke or because they were recycled.
+    pub connections_closed: u64,
+    /// The number of successful checkouts.
+    pub checkouts: u64,
+    /// The number of checkouts that timed out.
+    pub checkout_timeouts: u64,
+    /// The total time spent waiting for successful checkouts.
+    pub total_wait_time: Duration,
+    /// The longest time spent waiting for a single checkout.
+    pub max_wait_time: Duration,
+}
+
+impl PoolStatus {
+    /// Returns the average time a successful checkout had to wait.
+    pub fn average_wait_time(&self) -> Duration {
+        if self.checkouts == 0 {
+            Duration::from_secs(0)
+        } else {
+            self.total_wait_time / self.checkouts as u32
+        }
+    }
+}
+
+struct IdleConnection<C> {
+    con: C,
+    created: Instant,
+    idle_since: Instant,
+}
+
+struct PoolState<C> {
+    idle: Vec<IdleConnection<C>>,
+    // the async checkouts waiting for a connection
+    waiting: Vec<Task>,
+    // all connections that are open or being opened, idle or not
+    num_connections: usize,
+    connections_created: u64,
+    connections_closed: u64,
+    checkouts: u64,
+    checkout_timeouts: u64,
+    total_wait_time: Duration,
+    max_wait_time: Duration,
+}
+
+struct PoolInner<C> {
+    client: Client,
+    config: PoolConfig,
+    state: Mutex<PoolState<C>>,
+    available: Condvar,
+}
+
+/// A pool of connections to a single server.
+///
+/// Connections are checked out with `get` and automatically returned to
+/// the pool when the `PooledConnection` is dropped.  The pool can be cloned
+/// cheaply and shared between threads; all clones refer to the same set
+/// of connections.
+///
+/// The pool hands out blocking connections, `AsyncPool` is its
+/// counterpart for async connections.
+///
+/// ```rust,no_run
+/// # fn do_something() -> redis::RedisResult<()> {
+/// use redis::Commands;
+///
+/// let client = redis::Client::open("redis://127.0.0.1/")?;
+/// let pool = client.get_pool(16, 2)?;
+/// let mut con = pool.get()?;
+/// let _: () = con.set("my_key", 42)?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct Pool {
+    inner: Arc<PoolInner<Connection>>,
+}
+
+impl Pool {
+    /// Creates a new pool and opens `min_idle` connections right away.
+    pub fn new(client: Client, config: PoolConfig) -> RedisResult<Pool> {
+        let mut idle = Vec::with_capacity(config.min_idle);
+        for _ in 0..config.min_idle.min(config.max_size) {
+            idle.push(client.get_connection()?);
+        }
+        Ok(Pool {
+            inner: Arc::new(PoolInner::new(client, config, idle)),
+        })
+    }
+
+    /// Returns the configuration of the pool.
+    pub fn config(&self) -> &PoolConfig {
+        &self.inner.config
+    }
+
+    /// Returns the current number of connections and the counters collected
+    /// since the pool was created.
+    pub fn status(&self) -> PoolStatus {
+        self.inner.status()
+    }
+
+    /// Checks out a connection, waiting for up to the configured
+    /// `connection_timeout` if all connections are in use.
+    pub fn get(&self) -> RedisResult<PooledConnection> {
+        self.get_timeout(self.inner.config.connection_timeout)
+    }
+
+    /// Checks out a connection, waiting for up to `timeout` if all
+    /// connections are in use.  If no connection became available in time
+    /// an error is returned for which `is_timeout` is true.
+    pub fn get_timeout(&self, timeout: Duration) -> RedisResult<PooledConnection> {
+        let start = Instant::now();
+        let deadline = start + timeout;
+        let mut state = self.inner.state.lock().unwrap();
+        loop {
+            if let Some(idle) = state.idle.pop() {
+                drop(state);
+                let created = idle.created;
+                if let Some(con) = self.inner.check(idle) {
+                    self.inner.checked_out(start);
+                    return Ok(PooledConnection {
+                        pool: self.inner.clone(),
+                        con: Some(con),
+                        created: created,
+                    });
+                }
+                self.inner.discard();
+                state = self.inner.state.lock().unwrap();
+                continue;
+            }
+
+            if state.num_connections < self.inner.config.max_size {
+                state.num_connections += 1;
+                drop(state);
+                return match self.inner.client.get_connection() {
+                    Ok(con) => {
+                        self.inner.created();
+                        self.inner.checked_out(start);
+                        Ok(PooledConnection {
+                            pool: self.inner.clone(),
+                            con: Some(con),
+                            created: Instant::now(),
+                        })
+                    }
+                    Err(err) => {
+                        self.inner.open_failed();
+                        Err(err)
+                    }
+                };
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                state.checkout_timeouts += 1;
+                return Err(checkout_timeout_error());
+            }
+            state = self
+                .inner
+                .available
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+}
+
+fn checkout_timeout_error() -> RedisError {
+    RedisError::from(io::Error::new(
+        io::ErrorKind::TimedOut,
+        "timed out waiting for a pooled connection",
+    ))
+}
+
+impl<C> PoolInner<C> {
+    fn new(client: Client, config: PoolConfig, connections: Vec<C>) -> PoolInner<C> {
+        let now = Instant::now();
+        let idle: Vec<IdleConnection<C>> = connections
+            .into_iter()
+            .map(|con| IdleConnection {
+                con: con,
+                created: now,
+                idle_since: now,
+            })
+            .collect();
+        PoolInner {
+            client: client,
+            state: Mutex::new(PoolState {
+                num_connections: idle.len(),
+                connections_created: idle.len() as u64,
+                connections_closed: 0,
+                checkouts: 0,
+                checkout_timeouts: 0,
+                total_wait_time: Duration::from_secs(0),
+                max_wait_time: Duration::from_secs(0),
+                idle: idle,
+                waiting: vec![],
+            }),
+            config: config,
+            available: Condvar::new(),
+        }
+    }
+
+    fn status(&self) -> PoolStatus {
+        let state = self.state.lock().unwrap();
+        PoolStatus {
+            connections: state.num_connections,
+            idle_connections: state.idle.len(),
+            in_use: state.num_connections - state.idle.len(),
+            connections_created: state.connections_created,
+            connections_closed: state.connections_closed,
+            checkouts: state.checkouts,
+            checkout_timeouts: state.checkout_timeouts,
+            total_wait_time: state.total_wait_time,
+            max_wait_time: state.max_wait_time,
+        }
+    }
+
+    fn is_expired(&self, created: Instant) -> bool {
+        match self.config.max_lifetime {
+            Some(max_lifetime) => created.elapsed() >= max_lifetime,
+            None => false,
+        }
+    }
+
+    // Returns whether the idle connection is too old to be reused.
+    fn is_stale(&self, idle: &IdleConnection<C>) -> bool {
+        if self.is_expired(idle.created) {
+            return true;
+        }
+        match self.config.idle_timeout {
+            Some(idle_timeout) => idle.idle_since.elapsed() >= idle_timeout,
+            None => false,
+        }
+    }
+
+    fn needs_ping(&self, idle: &IdleConnection<C>) -> bool {
+        match self.config.health_check {
+            HealthCheck::Never => false,
+            HealthCheck::Always => true,
+            HealthCheck::IdleLongerThan(duration) => idle.idle_since.elapsed() >= duration,
+        }
+    }
+
+    // Wakes up a blocked checkout and all waiting async checkouts, since
+    // some of the latter may have been given up already.
+    fn notify(&self, state: &mut PoolState<C>) {
+        self.available.notify_one();
+        for task in state.waiting.drain(..) {
+            task.notify();
+        }
+    }
+
+    fn created(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.connections_created += 1;
+    }
+
+    fn checked_out(&self, start: Instant) {
+        let wait_time = start.elapsed();
+        let mut state = self.state.lock().unwrap();
+        state.checkouts += 1;
+        state.total_wait_time += wait_time;
+        if wait_time > state.max_wait_time {
+            state.max_wait_time = wait_time;
+        }
+        drop(state);
+        if let Some(observer) = self.client.metrics_observer() {
+            observer.pool_checkout(wait_time);
+        }
+    }
+
+    fn put_back(&self, con: C, created: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.idle.push(IdleConnection {
+            con: con,
+            created: created,
+            idle_since: Instant::now(),
+        });
+        self.notify(&mut state);
+    }
+
+    // Forgets about a connection that was closed.
+    fn discard(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.num_connections -= 1;
+        state.connections_closed += 1;
+        self.notify(&mut state);
+    }
+
+    // Gives up the slot reserved for a connection that could not be opened.
+    fn open_failed(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.num_connections -= 1;
+        self.notify(&mut state);
+    }
+}
+
+impl PoolInner<Connection> {
+    // Returns the connection if it is still usable.
+    fn check(&self, idle: IdleConnection<Connection>) -> Option<Connection> {
+        if !idle.con.is_open() || self.is_stale(&idle) {
+            return None;
+        }
+        let needs_ping = self.needs_ping(&idle);
+        let mut con = idle.con;
+        if needs_ping && cmd("PING").query::<()>(&mut con).is_err() {
+            return None;
+        }
+        Some(con)
+    }
+}
+
+/// A connection checked out from a `Pool`.
+///
+/// It dereferences to the underlying `Connection` and implements
+/// `ConnectionLike` so it can be used like any other connection.  Once
+/// dropped the connection goes back into the pool unless it was closed.
+pub struct PooledConnection {
+    pool: Arc<PoolInner<Connection>>,
+    con: Option<Connection>,
+    created: Instant,
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.con.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.con.as_mut().unwrap()
+    }
+}
+
+impl ConnectionLike for PooledConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        (**self).req_packed_command(cmd)
+    }
+
+    fn req_command(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        (**self).req_command(cmd)
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        (**self).req_packed_commands(cmd, offset, count)
+    }
+
+    fn req_commands(
+        &mut self,
+        cmds: &[&Cmd],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        (**self).req_commands(cmds, offset, count)
+    }
+
+    fn req_commands_each(&mut self, cmds: &[&Cmd]) -> RedisResult<Vec<RedisResult<Value>>> {
+        (**self).req_commands_each(cmds)
+    }
+
+    fn get_db(&self) -> i64 {
+        (**self).get_db()
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        let con = self.con.take().unwrap();
+        if con.is_open() && !self.pool.is_expired(self.created) {
+            self.pool.put_back(con, self.created);
+        } else {
+            self.pool.discard();
+        }
+    }
+}
+
+/// A pool of async connections to a single server.
+///
+/// This is the counterpart of `Pool` for async code and takes the same
+/// configuration.  Connections are checked out with `get`, which resolves
+/// once a connection is available, and go back into the pool when the
+/// `AsyncPooledConnection` is dropped.  With `tokio-comp` the pool has to
+/// be used from within a tokio executor.
+///
+/// ```rust,no_run
+/// # extern crate futures;
+/// # extern crate redis;
+/// use futures::Future;
+///
+/// # fn main() {
+/// let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+/// let value = client
+///     .get_async_pool(16, 2)
+///     .and_then(|pool| pool.get())
+///     .and_then(|con| redis::cmd("GET").arg("my_key").query_async(con))
+///     .map(|(_con, value): (_, Option<String>)| value);
+/// # }
+/// ```
+#[cfg(feature = "aio")]
+#[derive(Clone)]
+pub struct AsyncPool {
+    inner: Arc<PoolInner<aio::Connection>>,
+}
+
+#[cfg(feature = "aio")]
+impl AsyncPool {
+    /// Creates a new pool and opens `min_idle` connections right away.
+    pub fn new(
+        client: Client,
+        config: PoolConfig,
+    ) -> impl Future<Item = AsyncPool, Error = RedisError> {
+        let connections: Vec<_> = (0..config.min_idle.min(config.max_size))
+            .map(|_| client.get_async_connection())
+            .collect();
+        future::join_all(connections).map(move |idle| AsyncPool {
+            inner: Arc::new(PoolInner::new(client, config, idle)),
+        })
+    }
+
+    /// Returns the configuration of the pool.
+    pub fn config(&self) -> &PoolConfig {
+        &self.inner.config
+    }
+
+    /// Returns the current number of connections and the counters collected
+    /// since the pool was created.
+    pub fn status(&self) -> PoolStatus {
+        self.inner.status()
+    }
+
+    /// Checks out a connection, waiting for up to the configured
+    /// `connection_timeout` if all connections are in use.
+    pub fn get(&self) -> impl Future<Item = AsyncPooledConnection, Error = RedisError> {
+        self.get_timeout(self.inner.config.connection_timeout)
+    }
+
+    /// Checks out a connection, waiting for up to `timeout` if all
+    /// connections are in use.  If no connection became available in time
+    /// an error is returned for which `is_timeout` is true.
+    pub fn get_timeout(
+        &self,
+        timeout: Duration,
+    ) -> impl Future<Item = AsyncPooledConnection, Error = RedisError> {
+        let start = Instant::now();
+        Checkout {
+            pool: self.inner.clone(),
+            start: start,
+            deadline: Delay::new(timeout),
+            pending: None,
+        }
+    }
+}
+
+// A connection that a checkout waits for, either a new one or an idle one
+// that is being health checked.
+#[cfg(feature = "aio")]
+enum Pending {
+    Opening(RedisFuture<aio::Connection>),
+    Checking(RedisFuture<aio::Connection>, Instant),
+}
+
+#[cfg(feature = "aio")]
+struct Checkout {
+    pool: Arc<PoolInner<aio::Connection>>,
+    start: Instant,
+    deadline: Delay,
+    pending: Option<Pending>,
+}
+
+#[cfg(feature = "aio")]
+impl Checkout {
+    fn checked_out(&self, con: aio::Connection, created: Instant) -> AsyncPooledConnection {
+        self.pool.checked_out(self.start);
+        AsyncPooledConnection {
+            pool: self.pool.clone(),
+            con: Some(con),
+            created: created,
+        }
+    }
+
+    fn poll_pending(&mut self) -> Poll<Option<AsyncPooledConnection>, RedisError> {
+        match self.pending.take() {
+            Some(Pending::Opening(mut future)) => match future.poll() {
+                Ok(Async::Ready(con)) => {
+                    self.pool.created();
+                    Ok(Async::Ready(Some(self.checked_out(con, Instant::now()))))
+                }
+                Ok(Async::NotReady) => {
+                    self.pending = Some(Pending::Opening(future));
+                    Ok(Async::NotReady)
+                }
+                Err(err) => {
+                    self.pool.open_failed();
+                    Err(err)
+                }
+            },
+            Some(Pending::Checking(mut future, created)) => match future.poll() {
+                Ok(Async::Ready(con)) => Ok(Async::Ready(Some(self.checked_out(con, created)))),
+                Ok(Async::NotReady) => {
+                    self.pending = Some(Pending::Checking(future, created));
+                    Ok(Async::NotReady)
+                }
+                // the connection is replaced by another one
+                Err(_) => {
+                    self.pool.discard();
+                    Ok(Async::Ready(None))
+                }
+            },
+            None => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+#[cfg(feature = "aio")]
+impl Future for Checkout {
+    type Item = AsyncPooledConnection;
+    type Error = RedisError;
+
+    fn poll(&mut self) -> Poll<AsyncPooledConnection, RedisError> {
+        loop {
+            if let Some(con) = try_ready!(self.poll_pending()) {
+                return Ok(Async::Ready(con));
+            }
+
+            let mut state = self.pool.state.lock().unwrap();
+            if let Some(idle) = state.idle.pop() {
+                drop(state);
+                if self.pool.is_stale(&idle) {
+                    self.pool.discard();
+                } else if self.pool.needs_ping(&idle) {
+                    let ping = cmd("PING")
+                        .query_async::<_, ()>(idle.con)
+                        .map(|(con, ())| con);
+                    self.pending = Some(Pending::Checking(Box::new(ping), idle.created));
+                } else {
+                    return Ok(Async::Ready(self.checked_out(idle.con, idle.created)));
+                }
+                continue;
+            }
+
+            if state.num_connections < self.pool.config.max_size {
+                state.num_connections += 1;
+                drop(state);
+                let con = self.pool.client.get_async_connection();
+                self.pending = Some(Pending::Opening(Box::new(con)));
+                continue;
+            }
+
+            state.waiting.push(task::current());
+            return match self.deadline.poll() {
+                Ok(Async::NotReady) => Ok(Async::NotReady),
+                // the timeout passed or the timer failed
+                _ => {
+                    state.checkout_timeouts += 1;
+                    Err(checkout_timeout_error())
+                }
+            };
+        }
+    }
+}
+
+#[cfg(feature = "aio")]
+impl Drop for Checkout {
+    fn drop(&mut self) {
+        // give up the connection of a checkout that was cancelled
+        match self.pending.take() {
+            Some(Pending::Opening(_)) => self.pool.open_failed(),
+            Some(Pending::Checking(..)) => self.pool.discard(),
+            None => {}
+        }
+    }
+}
+
+/// An async connection checked out from an `AsyncPool`.
+///
+/// It dereferences to the underlying `aio::Connection` and implements
+/// `aio::ConnectionLike`, so commands are sent with `query_async` as
+/// usual.  Once dropped the connection goes back into the pool, unless a
+/// request on it failed, which closes the connection.
+#[cfg(feature = "aio")]
+pub struct AsyncPooledConnection {
+    pool: Arc<PoolInner<aio::Connection>>,
+    con: Option<aio::Connection>,
+    created: Instant,
+}
+
+#[cfg(feature = "aio")]
+impl Deref for AsyncPooledConnection {
+    type Target = aio::Connection;
+
+    fn deref(&self) -> &aio::Connection {
+        self.con.as_ref().unwrap()
+    }
+}
+
+#[cfg(feature = "aio")]
+impl DerefMut for AsyncPooledConnection {
+    fn deref_mut(&mut self) -> &mut aio::Connection {
+        self.con.as_mut().unwrap()
+    }
+}
+
+#[cfg(feature = "aio")]
+impl aio::ConnectionLike for AsyncPooledConnection {
+    fn req_packed_command(mut self, cmd: Vec<u8>) -> RedisFuture<(Self, Value)> {
+        let con = self.con.take().unwrap();
+        Box::new(con.req_packed_command(cmd).map(move |(con, value)| {
+            self.con = Some(con);
+            (self, value)
+        }))
+    }
+
+    fn req_packed_commands(
+        mut self,
+        cmd: Vec<u8>,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<(Self, Vec<Value>)> {
+        let con = self.con.take().unwrap();
+        Box::new(
+            con.req_packed_commands(cmd, offset, count)
+                .map(move |(con, values)| {
+                    self.con = Some(con);
+                    (self, values)
+                }),
+        )
+    }
+
+    fn get_db(&self) -> i64 {
+        aio::ConnectionLike::get_db(&**self)
+    }
+}
+
+#[cfg(feature = "aio")]
+impl Drop for AsyncPooledConnection {
+    fn drop(&mut self) {
+        // the connection is missing if a request on it failed
+        match self.con.take() {
+            Some(con) if !self.pool.is_expired(self.created) => {
+                self.pool.put_back(con, self.created)
+            }
+            _ => self.pool.discard(),
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Rate limiters that keep their state on the server.
+//!
+//! Each check runs a bundled lua script, so concurrent clients never race
+//! each other between reading and updating the counters, and the time is
+//! taken from the server so that the clocks of the clients do not matter.
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use std::time::Duration;
+//! use redis::ratelimit::RateLimiter;
+//!
+//! # let client = redis::Client::open("redis://127.0.0.1/")?;
+//! # let mut con = client.get_connection()?;
+//! let limiter = RateLimiter::sliding_window_log();
+//! let decision = limiter.check(&mut con, "ratelimit:user:42", 100, Duration::from_secs(60))?;
+//! if !decision.allowed {
+//!     println!("try again in {:?}", decision.retry_after);
+//! }
+//! # Ok(()) }
+//! ```
+use std::time::Duration;
+
+use connection::ConnectionLike;
+use script::Script;
+#[cfg(feature = "aio")]
+use types::RedisFuture;
+use types::{from_redis_value, ErrorKind, FromRedisValue, RedisResult, ToRedisArgs, Value};
+
+#[cfg(feature = "aio")]
+use futures::future;
+
+// All scripts take the limit and the window in milliseconds and return
+// whether the request is allowed, how many requests remain and after how
+// many milliseconds the next request is allowed.  The time of the server
+// is read before writing, which needs effects replication before redis 5.
+
+// A counter that expires with the window.  Denied requests are not
+// counted.
+const FIXED_WINDOW: &str = r"
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local count = tonumber(redis.call('GET', KEYS[1]) or '0')
+if count >= limit then
+    local ttl = redis.call('PTTL', KEYS[1])
+    if ttl < 0 then
+        redis.call('PEXPIRE', KEYS[1], window)
+        ttl = window
+    end
+    return {0, 0, ttl}
+end
+count = redis.call('INCR', KEYS[1])
+if count == 1 then
+    redis.call('PEXPIRE', KEYS[1], window)
+end
+return {1, limit - count, 0}
+";
+
+// A sorted set of the times of the allowed requests in the last window.
+// The members add the count so that requests within the same microsecond
+// stay distinct.
+const SLIDING_WINDOW_LOG: &str = r"
+if redis.replicate_commands then redis.replicate_commands() end
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now - window)
+local count = redis.call('ZCARD', KEYS[1])
+if count >= limit then
+    local oldest = redis.call('ZRANGE', KEYS[1], 0, 0, 'WITHSCORES')
+    if oldest[2] then
+        return {0, 0, tonumber(oldest[2]) + window - now}
+    end
+    return {0, 0, window}
+end
+redis.call('ZADD', KEYS[1], now, time[1] .. '.' .. time[2] .. ':' .. count)
+redis.call('PEXPIRE', KEYS[1], window)
+return {1, limit - count - 1, 0}
+";
+
+// A hash with the tokens left and the time they were counted.  The bucket
+// holds `limit` tokens and refills at `limit` tokens per window, so once
+// the key expired the bucket is full again.
+const TOKEN_BUCKET: &str = r"
+if redis.replicate_commands then redis.replicate_commands() end
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or limit
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(limit, tokens + math.max(0, now - ts) * limit / window)
+local allowed = 0
+local retry_after = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+else
+    retry_after = math.ceil((1 - tokens) * window / limit)
+end
+redis.call('HMSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], window)
+return {allowed, math.floor(tokens), retry_after}
+";
+
+/// The strategy of a `RateLimiter`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Counts the requests in consecutive windows.  Cheap, but allows up
+    /// to twice the limit around the end of a window.
+    FixedWindow,
+    /// Remembers the time of every allowed request in the last window.
+    /// Exact, but the memory grows with the limit.
+    SlidingWindowLog,
+    /// Allows bursts of up to `limit` requests and refills `limit`
+    /// requests per window evenly.
+    TokenBucket,
+}
+
+/// The outcome of `RateLimiter::check`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Decision {
+    /// Whether the request is within the limit.  Requests that are not
+    /// allowed do not count against it.
+    pub allowed: bool,
+    /// How many more requests would be allowed right now.
+    pub remaining: u64,
+    /// When a request is allowed again, none if this one was allowed.
+    pub retry_after: Option<Duration>,
+}
+
+impl FromRedisValue for Decision {
+    fn from_redis_value(v: &Value) -> RedisResult<Decision> {
+        let (allowed, remaining, retry_after): (i64, u64, u64) = from_redis_value(v)?;
+        Ok(Decision {
+            allowed: allowed == 1,
+            remaining,
+            retry_after: if allowed == 1 {
+                None
+            } else {
+                Some(Duration::from_millis(retry_after))
+            },
+        })
+    }
+}
+
+/// Limits how many requests are allowed per key in a window of time.
+///
+/// The limiter itself holds no state, so one can be shared by all
+/// connections and keys.  The key of a check holds the state on the
+/// server and expires when it is no longer needed.  Use a limiter of
+/// one algorithm per key, since the algorithms store different types.
+#[derive(Clone)]
+pub struct RateLimiter {
+    algorithm: Algorithm,
+    script: Script,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter with the given algorithm.
+    pub fn new(algorithm: Algorithm) -> RateLimiter {
+        let code = match algorithm {
+            Algorithm::FixedWindow => FIXED_WINDOW,
+            Algorithm::SlidingWindowLog => SLIDING_WINDOW_LOG,
+            Algorithm::TokenBucket => TOKEN_BUCKET,
+        };
+        RateLimiter {
+            algorithm,
+            script: Script::new(code),
+        }
+    }
+
+    /// Creates a fixed window rate limiter.
+    pub fn fixed_window() -> RateLimiter {
+        RateLimiter::new(Algorithm::FixedWindow)
+    }
+
+    /// Creates a sliding window log rate limiter.
+    pub fn sliding_window_log() -> RateLimiter {
+        RateLimiter::new(Algorithm::SlidingWindowLog)
+    }
+
+    /// Creates a token bucket rate limiter.
+    pub fn token_bucket() -> RateLimiter {
+        RateLimiter::new(Algorithm::TokenBucket)
+    }
+
+    /// Returns the algorithm of the limiter.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Counts a request against `key` and decides whether it is within
+    /// `limit` requests per `window`.  The window is rounded down to
+    /// milliseconds; a limit or window of zero is an error.
+    pub fn check<K: ToRedisArgs>(
+        &self,
+        con: &mut ConnectionLike,
+        key: K,
+        limit: u64,
+        window: Duration,
+    ) -> RedisResult<Decision> {
+        let window = window_millis(limit, window)?;
+        self.script.key(key).arg(limit).arg(window).invoke(con)
+    }
+
+    /// Like `check` for async connections.
+    #[cfg(feature = "aio")]
+    pub fn check_async<C, K>(
+        &self,
+        con: C,
+        key: K,
+        limit: u64,
+        window: Duration,
+    ) -> RedisFuture<(C, Decision)>
+    where
+        C: ::aio::ConnectionLike + Clone + Send + 'static,
+        K: ToRedisArgs,
+    {
+        let window = match window_millis(limit, window) {
+            Ok(window) => window,
+            Err(err) => return Box::new(future::err(err)),
+        };
+        self.script
+            .key(key)
+            .arg(limit)
+            .arg(window)
+            .invoke_async(con)
+    }
+}
+
+fn window_millis(limit: u64, window: Duration) -> RedisResult<u64> {
+    let millis = window.as_secs() * 1000 + u64::from(window.subsec_nanos() / 1_000_000);
+    if limit == 0 || millis == 0 {
+        fail!((
+            ErrorKind::InvalidClientConfig,
+            "Rate limits need a positive limit and window"
+        ));
+    }
+    Ok(millis)
+}
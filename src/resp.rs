@@ -0,0 +1,101 @@
+//! The redis protocol (RESP) without a connection.
+//!
+//! This module exposes the encoder and the parsers the connections use
+//! themselves, for tools like proxies, fuzzers or recorders that handle
+//! the wire format directly.  Commands are encoded with
+//! `Cmd::get_packed_command` and `Pipeline::get_packed_pipeline`, values
+//! with `encode_value`.  `Parser` reads values from any `BufRead` and,
+//! with the `tokio-comp` or `async-std-comp` feature, `ValueCodec` turns
+//! an `AsyncRead` into a stream of values.  `ReplyCodec` does the same but
+//! yields each reply as a `Reply` that shares the read buffer, so its
+//! strings are borrowed instead of copied.
+//!
+//! ```rust
+//! use redis::resp::{encode_value, Parser};
+//! use redis::Value;
+//!
+//! // a command is just an array of strings
+//! let packed = redis::cmd("SET").arg("key").arg(42).get_packed_command();
+//! let mut parser = Parser::new(&packed[..]);
+//! assert_eq!(
+//!     parser.parse_value().unwrap(),
+//!     Value::Bulk(vec![
+//!         Value::Data(b"SET".to_vec()),
+//!         Value::Data(b"key".to_vec()),
+//!         Value::Data(b"42".to_vec()),
+//!     ])
+//! );
+//!
+//! let mut reply = Vec::new();
+//! encode_value(&Value::Okay, &mut reply);
+//! assert_eq!(reply, b"+OK\r\n");
+//! ```
+use std::io::Write;
+
+use types::Value;
+
+pub use connection::ReplyLimits;
+#[cfg(feature = "aio")]
+pub use parser::{parse_async, parse_async_with_limits, Reply, ReplyCodec, ValueCodec};
+pub use parser::{parse_redis_value, parse_redis_value_ref, Parser};
+
+/// Appends the encoding of a value to `out`, the way a server sends it.
+/// RESP3 types are encoded as such, so a RESP2 client cannot read them.
+pub fn encode_value(value: &Value, out: &mut Vec<u8>) {
+    // writing to a vector cannot fail
+    match *value {
+        Value::Nil => out.extend_from_slice(b"$-1\r\n"),
+        Value::Int(val) => write!(out, ":{}\r\n", val).unwrap(),
+        Value::Data(ref val) => {
+            write!(out, "${}\r\n", val.len()).unwrap();
+            out.extend_from_slice(val);
+            out.extend_from_slice(b"\r\n");
+        }
+        Value::Bulk(ref values) => encode_values(b'*', values, out),
+        Value::Okay => out.extend_from_slice(b"+OK\r\n"),
+        Value::Status(ref s) => write!(out, "+{}\r\n", s).unwrap(),
+        Value::Map(ref pairs) => {
+            write!(out, "%{}\r\n", pairs.len()).unwrap();
+            for &(ref key, ref val) in pairs {
+                encode_value(key, out);
+                encode_value(val, out);
+            }
+        }
+        Value::Set(ref values) => encode_values(b'~', values, out),
+        Value::Double(val) => {
+            if val.is_nan() {
+                out.extend_from_slice(b",nan\r\n");
+            } else {
+                write!(out, ",{}\r\n", val).unwrap();
+            }
+        }
+        Value::Boolean(val) => out.extend_from_slice(if val { b"#t\r\n" } else { b"#f\r\n" }),
+        Value::BigNumber(ref val) => write!(out, "({}\r\n", val).unwrap(),
+        Value::VerbatimString {
+            ref format,
+            ref text,
+        } => write!(
+            out,
+            "={}\r\n{}:{}\r\n",
+            format.len() + text.len() + 1,
+            format,
+            text
+        )
+        .unwrap(),
+        Value::Push { ref kind, ref data } => {
+            write!(out, ">{}\r\n", data.len() + 1).unwrap();
+            encode_value(&Value::Data(kind.name().as_bytes().to_vec()), out);
+            for val in data {
+                encode_value(val, out);
+            }
+        }
+    }
+}
+
+fn encode_values(prefix: u8, values: &[Value], out: &mut Vec<u8>) {
+    out.push(prefix);
+    write!(out, "{}\r\n", values.len()).unwrap();
+    for val in values {
+        encode_value(val, out);
+    }
+}
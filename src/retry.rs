@@ -0,0 +1,140 @@
+//! Automatic retries of failed requests.
+//!
+//! A `RetryPolicy` attached to a `Client` with `Client::set_retry_policy`
+//! is used whenever the client itself is used as a connection and by
+//! `ConnectionManager`s created from the client.  Requests that failed with
+//! an error of one of the configured classes are retried with exponential
+//! backoff until they succeed or the maximum number of attempts is reached.
+//!
+//! Note that a request which failed because the connection broke or timed
+//! out might have been executed by the server regardless.  Only retry those
+//! classes of errors if the commands sent are idempotent.
+use std::time::Duration;
+
+use types::{random_u64, ErrorKind, RedisError};
+
+/// Classes of errors that a `RetryPolicy` can retry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RetryableError {
+    /// The server is still loading its dataset into memory (`LOADING`).
+    Loading,
+    /// The server is busy running a script (`BUSY`).
+    Busy,
+    /// The connection was refused, reset or closed.
+    ConnectionLost,
+    /// Reading or writing timed out.
+    Timeout,
+    /// The cluster is reconfiguring, for instance during a failover
+    /// (`TRYAGAIN`, `CLUSTERDOWN`).  `MOVED` and `ASK` redirects are never
+    /// retried as sending the request to the same node again cannot
+    /// succeed; the cluster client follows them instead.
+    ClusterReconfiguration,
+    /// A replica is not ready to serve requests (`MASTERDOWN`, `READONLY`).
+    ReplicaUnavailable,
+}
+
+impl RetryableError {
+    /// Returns the class of the given error or `None` if it can never be
+    /// retried.
+    pub fn classify(err: &RedisError) -> Option<RetryableError> {
+        if err.is_timeout() {
+            return Some(RetryableError::Timeout);
+        }
+        if err.is_connection_refusal() || err.is_connection_dropped() {
+            return Some(RetryableError::ConnectionLost);
+        }
+        match err.kind() {
+            ErrorKind::BusyLoadingError => Some(RetryableError::Loading),
+            ErrorKind::Busy => Some(RetryableError::Busy),
+            ErrorKind::IoError => Some(RetryableError::ConnectionLost),
+            ErrorKind::TryAgain | ErrorKind::ClusterDown => {
+                Some(RetryableError::ClusterReconfiguration)
+            }
+            ErrorKind::MasterDown | ErrorKind::ReadOnly => Some(RetryableError::ReplicaUnavailable),
+            _ => None,
+        }
+    }
+}
+
+/// Describes how often and how fast failed requests are retried.
+///
+/// The delay before the `n`th retry is `base_delay * 2^(n - 1)`, capped at
+/// `max_delay`.  With `jitter` enabled a random delay between zero and that
+/// value is used instead so that many clients do not retry in lockstep.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use redis::retry::{RetryPolicy, RetryableError};
+///
+/// let policy = RetryPolicy {
+///     max_attempts: 5,
+///     base_delay: Duration::from_millis(50),
+///     retry_on: vec![RetryableError::Loading, RetryableError::ConnectionLost],
+///     ..Default::default()
+/// };
+/// assert_eq!(policy.delay(1), Duration::from_millis(50));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts including the first one.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    pub base_delay: Duration,
+    /// The upper bound of the delay between two attempts.
+    pub max_delay: Duration,
+    /// Whether to randomize the delays.
+    pub jitter: bool,
+    /// The classes of errors that are retried.
+    pub retry_on: Vec<RetryableError>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: false,
+            retry_on: vec![
+                RetryableError::Loading,
+                RetryableError::Busy,
+                RetryableError::ClusterReconfiguration,
+                RetryableError::ReplicaUnavailable,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns true if a request whose `attempt`th attempt (counting from
+    /// one) failed with `err` should be attempted again.
+    pub fn should_retry(&self, err: &RedisError, attempt: u32) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+        match RetryableError::classify(err) {
+            Some(class) => self.retry_on.contains(&class),
+            None => false,
+        }
+    }
+
+    /// Returns how long to wait after the `attempt`th attempt (counting
+    /// from one) failed.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        if !self.jitter {
+            return delay;
+        }
+        let nanos = delay.as_secs() * 1_000_000_000 + u64::from(delay.subsec_nanos());
+        if nanos == 0 {
+            return delay;
+        }
+        let jittered = random_u64() % (nanos + 1);
+        Duration::new(jittered / 1_000_000_000, (jittered % 1_000_000_000) as u32)
+    }
+}
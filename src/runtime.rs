@@ -0,0 +1,227 @@
+//! The parts of the async API that need a runtime: sockets, timers and
+//! spawning the tasks that drive multiplexed connections.  Tokio is used
+//! with the `tokio-comp` feature, async-std with `async-std-comp` only.
+//! The latter also builds for wasm32, where there are no sockets.
+
+use std::net::SocketAddr;
+#[cfg(feature = "with-unix-sockets")]
+use std::path::Path;
+use std::time::Duration;
+
+use futures::{Future, Poll};
+
+use connection::TcpOptions;
+use types::{RedisError, RedisFuture};
+
+#[cfg(feature = "tokio-comp")]
+pub use self::with_tokio::*;
+
+#[cfg(not(feature = "tokio-comp"))]
+pub use self::with_async_std::*;
+
+/// Resolves once the duration passed.
+pub struct Delay(Inner);
+
+impl Delay {
+    pub fn new(duration: Duration) -> Delay {
+        Delay(delay(duration))
+    }
+}
+
+impl Future for Delay {
+    type Item = ();
+    type Error = RedisError;
+
+    fn poll(&mut self) -> Poll<(), RedisError> {
+        self.0.poll()
+    }
+}
+
+#[cfg(feature = "tokio-comp")]
+mod with_tokio {
+    use super::*;
+
+    use std::io;
+    use std::time::Instant;
+
+    use tokio_executor::{self, DefaultExecutor, Executor};
+    use tokio_timer;
+
+    pub use tokio_tcp::TcpStream;
+    #[cfg(feature = "with-unix-sockets")]
+    pub use tokio_uds::UnixStream;
+
+    pub type Inner = Box<Future<Item = (), Error = RedisError> + Send>;
+
+    pub fn delay(duration: Duration) -> Inner {
+        Box::new(
+            tokio_timer::Delay::new(Instant::now() + duration)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err).into()),
+        )
+    }
+
+    /// Spawns the future on the default executor.  This panics outside of
+    /// a tokio runtime.
+    pub fn spawn<F>(future: F)
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        tokio_executor::spawn(future);
+    }
+
+    /// Like `spawn` but drops the future outside of a tokio runtime.
+    pub fn try_spawn<F>(future: F)
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        let _ = DefaultExecutor::current().spawn(Box::new(future));
+    }
+
+    pub fn connect_tcp(socket_addr: &SocketAddr, options: TcpOptions) -> RedisFuture<TcpStream> {
+        Box::new(
+            TcpStream::connect(socket_addr)
+                .and_then(move |con| {
+                    if options.nodelay {
+                        con.set_nodelay(true)?;
+                    }
+                    if options.keepalive.is_some() {
+                        con.set_keepalive(options.keepalive)?;
+                    }
+                    if let Some(size) = options.send_buffer_size {
+                        con.set_send_buffer_size(size)?;
+                    }
+                    if let Some(size) = options.recv_buffer_size {
+                        con.set_recv_buffer_size(size)?;
+                    }
+                    Ok(con)
+                })
+                .from_err(),
+        )
+    }
+
+    #[cfg(feature = "with-unix-sockets")]
+    pub fn connect_unix(path: &Path) -> RedisFuture<UnixStream> {
+        Box::new(UnixStream::connect(path).from_err())
+    }
+}
+
+#[cfg(not(feature = "tokio-comp"))]
+mod with_async_std {
+    use super::*;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    use async_std::net;
+    #[cfg(feature = "with-unix-sockets")]
+    use async_std::os::unix;
+    use async_std::task;
+    use futures_util::compat::{Compat, Future01CompatExt};
+    use futures_util::future::{BoxFuture, FutureExt, TryFutureExt};
+
+    #[cfg(not(target_arch = "wasm32"))]
+    use connection::connect_socket;
+
+    #[cfg(target_arch = "wasm32")]
+    pub use super::without_sockets::*;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub type TcpStream = Compat<net::TcpStream>;
+    #[cfg(feature = "with-unix-sockets")]
+    pub type UnixStream = Compat<unix::net::UnixStream>;
+
+    pub type Inner = Compat<BoxFuture<'static, Result<(), RedisError>>>;
+
+    pub fn delay(duration: Duration) -> Inner {
+        task::sleep(duration).map(Ok).boxed().compat()
+    }
+
+    /// Spawns the future as a detached async-std task.
+    pub fn spawn<F>(future: F)
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        task::spawn(future.compat());
+    }
+
+    pub fn try_spawn<F>(future: F)
+    where
+        F: Future<Item = (), Error = ()> + Send + 'static,
+    {
+        spawn(future)
+    }
+
+    // async-std cannot set all of the TCP options on its sockets, so the
+    // socket is connected and set up on the blocking thread pool instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn connect_tcp(socket_addr: &SocketAddr, options: TcpOptions) -> RedisFuture<TcpStream> {
+        let socket_addr = *socket_addr;
+        Box::new(
+            task::spawn_blocking(move || connect_socket(&socket_addr, &options))
+                .map_ok(|con| Compat::new(net::TcpStream::from(con)))
+                .boxed()
+                .compat()
+                .from_err(),
+        )
+    }
+
+    #[cfg(feature = "with-unix-sockets")]
+    pub fn connect_unix(path: &Path) -> RedisFuture<UnixStream> {
+        let path = path.to_owned();
+        Box::new(
+            unix::net::UnixStream::connect(path)
+                .map_ok(Compat::new)
+                .boxed()
+                .compat()
+                .from_err(),
+        )
+    }
+}
+
+// There are no sockets on wasm32, connections run over a `Transport` like
+// `aio::WebSocketTransport` there.  `connect_tcp` always fails, so no
+// stream is ever created.
+#[cfg(all(not(feature = "tokio-comp"), target_arch = "wasm32"))]
+mod without_sockets {
+    use super::*;
+
+    use std::io::{self, Read, Write};
+
+    use futures::{future, Async};
+    use tokio_io::{AsyncRead, AsyncWrite};
+
+    use types::ErrorKind;
+
+    pub struct TcpStream {
+        _private: (),
+    }
+
+    impl Read for TcpStream {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::NotConnected.into())
+        }
+    }
+
+    impl Write for TcpStream {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::NotConnected.into())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl AsyncRead for TcpStream {}
+
+    impl AsyncWrite for TcpStream {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(Async::Ready(()))
+        }
+    }
+
+    pub fn connect_tcp(_: &SocketAddr, _: TcpOptions) -> RedisFuture<TcpStream> {
+        Box::new(future::err(RedisError::from((
+            ErrorKind::InvalidClientConfig,
+            "TCP connections are not available on wasm32, \
+             use aio::Connection::from_transport",
+        ))))
+    }
+}
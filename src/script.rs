@@ -1,10 +1,18 @@
 use sha1::Sha1;
 
-use cmd::cmd;
+use cmd::{cmd, Cmd};
+#[cfg(feature = "aio")]
+use cmd::{pipe, Pipeline};
 use connection::ConnectionLike;
-use types::{ErrorKind, FromRedisValue, RedisResult, ToRedisArgs};
+#[cfg(feature = "aio")]
+use types::RedisFuture;
+use types::{ArgBuffer, ErrorKind, FromRedisValue, RedisResult, ToRedisArgs};
+
+#[cfg(feature = "aio")]
+use futures::{future, Future};
 
 /// Represents a lua script.
+#[derive(Clone)]
 pub struct Script {
     code: String,
     hash: String,
@@ -44,21 +52,17 @@ impl Script {
     /// Creates a script invocation object with a key filled in.
     #[inline]
     pub fn key<T: ToRedisArgs>(&self, key: T) -> ScriptInvocation {
-        ScriptInvocation {
-            script: self,
-            args: vec![],
-            keys: key.to_redis_args(),
-        }
+        let mut invocation = self.prepare_invoke();
+        key.write_redis_args(&mut invocation.keys);
+        invocation
     }
 
     /// Creates a script invocation object with an argument filled in.
     #[inline]
     pub fn arg<T: ToRedisArgs>(&self, arg: T) -> ScriptInvocation {
-        ScriptInvocation {
-            script: self,
-            args: arg.to_redis_args(),
-            keys: vec![],
-        }
+        let mut invocation = self.prepare_invoke();
+        arg.write_redis_args(&mut invocation.args);
+        invocation
     }
 
     /// Returns an empty script invocation object.  This is primarily useful
@@ -68,28 +72,84 @@ impl Script {
     pub fn prepare_invoke(&self) -> ScriptInvocation {
         ScriptInvocation {
             script: self,
-            args: vec![],
-            keys: vec![],
+            args: ArgBuffer::new(),
+            keys: ArgBuffer::new(),
+            read_only: false,
         }
     }
 
     /// Invokes the script directly without arguments.
     #[inline]
     pub fn invoke<T: FromRedisValue>(&self, con: &mut ConnectionLike) -> RedisResult<T> {
-        ScriptInvocation {
-            script: self,
-            args: vec![],
-            keys: vec![],
-        }
-        .invoke(con)
+        self.prepare_invoke().invoke(con)
+    }
+
+    /// Invokes the script on an async connection without arguments.
+    #[cfg(feature = "aio")]
+    #[inline]
+    pub fn invoke_async<C, T>(&self, con: C) -> RedisFuture<(C, T)>
+    where
+        C: ::aio::ConnectionLike + Clone + Send + 'static,
+        T: FromRedisValue + Send + 'static,
+    {
+        self.prepare_invoke().invoke_async(con)
+    }
+
+    /// Loads the script into the script cache of the server with
+    /// `SCRIPT LOAD`, for instance to warm up a new connection.  On a
+    /// cluster the script is loaded on all masters.
+    pub fn load_on(&self, con: &mut ConnectionLike) -> RedisResult<()> {
+        let _: String = self.load_cmd().query(con)?;
+        Ok(())
+    }
+
+    /// Returns true if the script is in the script cache of the server.
+    pub fn exists_on(&self, con: &mut ConnectionLike) -> RedisResult<bool> {
+        let exists: Vec<bool> = script_exists_cmd(Some(self)).query(con)?;
+        Ok(exists.first().cloned().unwrap_or(false))
+    }
+
+    /// Like `load_on` for async connections.
+    #[cfg(feature = "aio")]
+    pub fn load_async<C>(&self, con: C) -> RedisFuture<C>
+    where
+        C: ::aio::ConnectionLike + Send + 'static,
+    {
+        Box::new(
+            self.load_cmd()
+                .query_async(con)
+                .map(|(con, _): (C, String)| con),
+        )
+    }
+
+    /// Like `exists_on` for async connections.
+    #[cfg(feature = "aio")]
+    pub fn exists_async<C>(&self, con: C) -> RedisFuture<(C, bool)>
+    where
+        C: ::aio::ConnectionLike + Send + 'static,
+    {
+        Box::new(
+            script_exists_cmd(Some(self))
+                .query_async(con)
+                .map(|(con, exists): (C, Vec<bool>)| {
+                    (con, exists.first().cloned().unwrap_or(false))
+                }),
+        )
+    }
+
+    fn load_cmd(&self) -> Cmd {
+        let mut cmd = cmd("SCRIPT");
+        cmd.arg("LOAD").arg(&self.code);
+        cmd
     }
 }
 
 /// Represents a prepared script call.
 pub struct ScriptInvocation<'a> {
     script: &'a Script,
-    args: Vec<Vec<u8>>,
-    keys: Vec<Vec<u8>>,
+    args: ArgBuffer,
+    keys: ArgBuffer,
+    read_only: bool,
 }
 
 /// This type collects keys and other arguments for the script so that it
@@ -119,31 +179,148 @@ impl<'a> ScriptInvocation<'a> {
         self
     }
 
+    /// Invokes the script with `EVALSHA_RO` and `EVAL_RO` (redis 7).  The
+    /// script must not write, and on a cluster that reads from replicas
+    /// it may be run by a replica.
+    #[inline]
+    pub fn read_only<'b>(&'b mut self) -> &'b mut ScriptInvocation<'a>
+    where
+        'a: 'b,
+    {
+        self.read_only = true;
+        self
+    }
+
+    fn eval_cmd(&self, name: &str, script: &[u8]) -> Cmd {
+        let mut cmd = cmd(name);
+        cmd.arg(script)
+            .arg(self.keys.len())
+            .arg(&self.keys)
+            .arg(&self.args);
+        cmd
+    }
+
+    fn eval_names(&self) -> (&'static str, &'static str) {
+        if self.read_only {
+            ("EVALSHA_RO", "EVAL_RO")
+        } else {
+            ("EVALSHA", "EVAL")
+        }
+    }
+
     /// Invokes the script and returns the result.
+    ///
+    /// The script is run by its hash with `EVALSHA`.  If the server does
+    /// not know it yet, it is sent with `EVAL`, which also caches it.  On
+    /// a cluster both commands go to the node serving the keys, so the
+    /// script is loaded where it is needed; keys in different slots fail
+    /// with `ErrorKind::CrossSlot`.
     #[inline]
     pub fn invoke<T: FromRedisValue>(&self, con: &mut ConnectionLike) -> RedisResult<T> {
-        loop {
-            match cmd("EVALSHA")
-                .arg(self.script.hash.as_bytes())
-                .arg(self.keys.len())
-                .arg(&*self.keys)
-                .arg(&*self.args)
-                .query(con)
-            {
-                Ok(val) => {
-                    return Ok(val);
-                }
-                Err(err) => {
-                    if err.kind() == ErrorKind::NoScriptError {
-                        let _: () = cmd("SCRIPT")
-                            .arg("LOAD")
-                            .arg(self.script.code.as_bytes())
-                            .query(con)?;
-                    } else {
-                        fail!(err);
-                    }
-                }
+        let (evalsha, eval) = self.eval_names();
+        let evalsha = self.eval_cmd(evalsha, self.script.hash.as_bytes());
+        match evalsha.query(con) {
+            Err(ref err) if err.kind() == ErrorKind::NoScriptError => {}
+            result => return result,
+        }
+        self.eval_cmd(eval, self.script.code.as_bytes()).query(con)
+    }
+
+    /// Invokes the script on an async connection, like a
+    /// `MultiplexedConnection` or a `ConnectionManager`, and returns the
+    /// result.
+    ///
+    /// The script is run by its hash with `EVALSHA`.  If the server does
+    /// not know it yet, it is loaded with `SCRIPT LOAD` and run again on a
+    /// clone of the connection, since a failed request does not return the
+    /// connection.
+    #[cfg(feature = "aio")]
+    pub fn invoke_async<C, T>(&self, con: C) -> RedisFuture<(C, T)>
+    where
+        C: ::aio::ConnectionLike + Clone + Send + 'static,
+        T: FromRedisValue + Send + 'static,
+    {
+        let (evalsha, _) = self.eval_names();
+        let evalsha = self.eval_cmd(evalsha, self.script.hash.as_bytes());
+        let load = self.script.load_cmd();
+        let retry_con = con.clone();
+        Box::new(evalsha.query_async(con).or_else(move |err| {
+            if err.kind() != ErrorKind::NoScriptError {
+                return future::Either::A(future::err(err));
             }
+            future::Either::B(
+                load.query_async(retry_con)
+                    .and_then(move |(con, _): (C, String)| evalsha.query_async(con)),
+            )
+        }))
+    }
+}
+
+// The command `Pipeline::invoke_script` adds for an invocation.  The
+// pipeline loads the script with `load_scripts` before it is sent.
+pub fn pipeline_command(invocation: &ScriptInvocation) -> (Cmd, Script) {
+    let (evalsha, _) = invocation.eval_names();
+    (
+        invocation.eval_cmd(evalsha, invocation.script.hash.as_bytes()),
+        invocation.script.clone(),
+    )
+}
+
+fn script_exists_cmd<'a, I: IntoIterator<Item = &'a Script>>(scripts: I) -> Cmd {
+    let mut cmd = cmd("SCRIPT");
+    cmd.arg("EXISTS");
+    for script in scripts {
+        cmd.arg(&script.hash);
+    }
+    cmd
+}
+
+#[cfg(feature = "aio")]
+fn script_load_pipeline(scripts: &[Script], exists: &[bool]) -> Pipeline {
+    let mut pipe = pipe();
+    for (script, &exists) in scripts.iter().zip(exists) {
+        if !exists {
+            pipe.add_command(script.load_cmd()).ignore();
         }
     }
+    pipe
+}
+
+// Loads the scripts the server does not know yet, so that their `EVALSHA`
+// commands cannot fail with `NOSCRIPT` in the middle of a pipeline.
+pub fn load_scripts(scripts: &[Script], con: &mut ConnectionLike) -> RedisResult<()> {
+    if scripts.is_empty() {
+        return Ok(());
+    }
+    let exists: Vec<bool> = script_exists_cmd(scripts).query(con)?;
+    // The scripts are not loaded with a pipeline since a cluster sends
+    // `SCRIPT` commands to all masters, which cannot be pipelined.
+    for (script, exists) in scripts.iter().zip(exists) {
+        if !exists {
+            script.load_on(con)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "aio")]
+pub fn load_scripts_async<C>(scripts: Vec<Script>, con: C) -> RedisFuture<C>
+where
+    C: ::aio::ConnectionLike + Send + 'static,
+{
+    if scripts.is_empty() {
+        return Box::new(future::ok(con));
+    }
+    Box::new(script_exists_cmd(&scripts[..]).query_async(con).and_then(
+        move |(con, exists): (C, Vec<bool>)| {
+            if exists.iter().all(|&exists| exists) {
+                return future::Either::A(future::ok(con));
+            }
+            future::Either::B(
+                script_load_pipeline(&scripts, &exists)
+                    .query_async(con)
+                    .map(|(con, ()): (C, ())| con),
+            )
+        },
+    ))
 }
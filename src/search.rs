@@ -0,0 +1,928 @@
+//! Full-text and secondary indexes of the RediSearch module
+//! (`with-search` feature).
+//!
+//! An index over hashes or JSON documents is created with `FT.CREATE`
+//! from a `Schema`, and queried with `FT.SEARCH` and `FT.AGGREGATE`:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::search::{CreateOptions, Schema, SearchOptions, SearchResult};
+//! use redis::Commands;
+//!
+//! # let client = redis::Client::open("redis://127.0.0.1/")?;
+//! # let mut con = client.get_connection()?;
+//! let schema = Schema::new()
+//!     .text("title").weight(2.0)
+//!     .tag("genre")
+//!     .numeric("year").sortable();
+//! let _: () = con.ft_create("movies", &CreateOptions::new().prefix("movie:"), &schema)?;
+//!
+//! let options = SearchOptions::new().sort_by("year", false).limit(0, 10);
+//! let result: SearchResult = con.ft_search("movies", "@genre:{drama} love", &options)?;
+//! println!("{} movies found", result.total);
+//! for movie in result.documents {
+//!     println!("{}: {:?}", movie.id, movie.fields.get("title"));
+//! }
+//! # Ok(()) }
+//! ```
+use std::collections::HashMap;
+
+use types::{
+    from_redis_value, ErrorKind, FromRedisValue, RedisError, RedisResult, RedisWrite, ToRedisArgs,
+    Value,
+};
+
+/// The kind of data an index is built over (`ON`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexOn {
+    /// Hashes, the default.
+    Hash,
+    /// JSON documents of the RedisJSON module.
+    Json,
+}
+
+/// The options of `FT.CREATE`, see `Commands::ft_create`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CreateOptions {
+    on: Option<IndexOn>,
+    prefixes: Vec<String>,
+    filter: Option<String>,
+    language: Option<String>,
+    stopwords: Option<Vec<String>>,
+}
+
+impl CreateOptions {
+    /// Creates options that index all hashes.
+    pub fn new() -> CreateOptions {
+        Default::default()
+    }
+
+    /// Sets the kind of data to index (`ON`).
+    pub fn on(mut self, on: IndexOn) -> CreateOptions {
+        self.on = Some(on);
+        self
+    }
+
+    /// Only indexes keys with the prefix (`PREFIX`).  Can be given more
+    /// than once.
+    pub fn prefix(mut self, prefix: &str) -> CreateOptions {
+        self.prefixes.push(prefix.to_string());
+        self
+    }
+
+    /// Only indexes documents for which the expression is true
+    /// (`FILTER`).
+    pub fn filter(mut self, expression: &str) -> CreateOptions {
+        self.filter = Some(expression.to_string());
+        self
+    }
+
+    /// Sets the language used for stemming (`LANGUAGE`).
+    pub fn language(mut self, language: &str) -> CreateOptions {
+        self.language = Some(language.to_string());
+        self
+    }
+
+    /// Replaces the default stop-words (`STOPWORDS`), an empty list
+    /// disables them.
+    pub fn stopwords(mut self, stopwords: &[&str]) -> CreateOptions {
+        self.stopwords = Some(stopwords.iter().map(|word| word.to_string()).collect());
+        self
+    }
+}
+
+impl ToRedisArgs for CreateOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(on) = self.on {
+            out.write_arg(b"ON");
+            out.write_arg(match on {
+                IndexOn::Hash => &b"HASH"[..],
+                IndexOn::Json => &b"JSON"[..],
+            });
+        }
+        if !self.prefixes.is_empty() {
+            out.write_arg(b"PREFIX");
+            self.prefixes.len().write_redis_args(out);
+            for prefix in &self.prefixes {
+                prefix.write_redis_args(out);
+            }
+        }
+        if let Some(ref filter) = self.filter {
+            out.write_arg(b"FILTER");
+            filter.write_redis_args(out);
+        }
+        if let Some(ref language) = self.language {
+            out.write_arg(b"LANGUAGE");
+            language.write_redis_args(out);
+        }
+        if let Some(ref stopwords) = self.stopwords {
+            out.write_arg(b"STOPWORDS");
+            stopwords.len().write_redis_args(out);
+            for word in stopwords {
+                word.write_redis_args(out);
+            }
+        }
+    }
+}
+
+/// The type of a field of a `Schema`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    /// Full-text searchable text (`TEXT`).
+    Text,
+    /// Exact-match tags (`TAG`).
+    Tag,
+    /// Numbers for range queries (`NUMERIC`).
+    Numeric,
+    /// Longitude and latitude pairs for radius queries (`GEO`).
+    Geo,
+}
+
+impl ToRedisArgs for FieldType {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            FieldType::Text => &b"TEXT"[..],
+            FieldType::Tag => &b"TAG"[..],
+            FieldType::Numeric => &b"NUMERIC"[..],
+            FieldType::Geo => &b"GEO"[..],
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct SchemaField {
+    name: String,
+    alias: Option<String>,
+    kind: FieldType,
+    weight: Option<f64>,
+    no_stem: bool,
+    separator: Option<char>,
+    case_sensitive: bool,
+    sortable: bool,
+    no_index: bool,
+}
+
+/// The fields of an index, see `Commands::ft_create`.
+///
+/// Fields are added with `text`, `tag`, `numeric` and `geo`, and the
+/// other methods change the field that was added last.  For JSON indexes
+/// the names are JSON paths, which usually get an alias.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schema {
+    fields: Vec<SchemaField>,
+}
+
+impl Schema {
+    /// Creates an empty schema.
+    pub fn new() -> Schema {
+        Default::default()
+    }
+
+    fn field(mut self, name: &str, kind: FieldType) -> Schema {
+        self.fields.push(SchemaField {
+            name: name.to_string(),
+            alias: None,
+            kind,
+            weight: None,
+            no_stem: false,
+            separator: None,
+            case_sensitive: false,
+            sortable: false,
+            no_index: false,
+        });
+        self
+    }
+
+    fn last<F: FnOnce(&mut SchemaField)>(mut self, func: F) -> Schema {
+        if let Some(field) = self.fields.last_mut() {
+            func(field);
+        }
+        self
+    }
+
+    /// Adds a `TEXT` field.
+    pub fn text(self, name: &str) -> Schema {
+        self.field(name, FieldType::Text)
+    }
+
+    /// Adds a `TAG` field.
+    pub fn tag(self, name: &str) -> Schema {
+        self.field(name, FieldType::Tag)
+    }
+
+    /// Adds a `NUMERIC` field.
+    pub fn numeric(self, name: &str) -> Schema {
+        self.field(name, FieldType::Numeric)
+    }
+
+    /// Adds a `GEO` field.
+    pub fn geo(self, name: &str) -> Schema {
+        self.field(name, FieldType::Geo)
+    }
+
+    /// Sets the name the field is queried by (`AS`).
+    pub fn alias(self, alias: &str) -> Schema {
+        self.last(|field| field.alias = Some(alias.to_string()))
+    }
+
+    /// Sets the importance of a text field for scoring (`WEIGHT`).
+    pub fn weight(self, weight: f64) -> Schema {
+        self.last(|field| field.weight = Some(weight))
+    }
+
+    /// Disables stemming of a text field (`NOSTEM`).
+    pub fn no_stem(self) -> Schema {
+        self.last(|field| field.no_stem = true)
+    }
+
+    /// Sets the character that separates the tags of a tag field
+    /// (`SEPARATOR`), a comma by default.
+    pub fn separator(self, separator: char) -> Schema {
+        self.last(|field| field.separator = Some(separator))
+    }
+
+    /// Keeps the case of the tags of a tag field (`CASESENSITIVE`).
+    pub fn case_sensitive(self) -> Schema {
+        self.last(|field| field.case_sensitive = true)
+    }
+
+    /// Allows sorting the results by the field (`SORTABLE`).
+    pub fn sortable(self) -> Schema {
+        self.last(|field| field.sortable = true)
+    }
+
+    /// Keeps the field for sorting but does not index it (`NOINDEX`).
+    pub fn no_index(self) -> Schema {
+        self.last(|field| field.no_index = true)
+    }
+}
+
+impl ToRedisArgs for Schema {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(b"SCHEMA");
+        for field in &self.fields {
+            field.name.write_redis_args(out);
+            if let Some(ref alias) = field.alias {
+                out.write_arg(b"AS");
+                alias.write_redis_args(out);
+            }
+            field.kind.write_redis_args(out);
+            if field.no_stem {
+                out.write_arg(b"NOSTEM");
+            }
+            if let Some(weight) = field.weight {
+                out.write_arg(b"WEIGHT");
+                weight.write_redis_args(out);
+            }
+            if let Some(separator) = field.separator {
+                out.write_arg(b"SEPARATOR");
+                separator.to_string().write_redis_args(out);
+            }
+            if field.case_sensitive {
+                out.write_arg(b"CASESENSITIVE");
+            }
+            if field.sortable {
+                out.write_arg(b"SORTABLE");
+            }
+            if field.no_index {
+                out.write_arg(b"NOINDEX");
+            }
+        }
+    }
+}
+
+/// The options of `FT.SEARCH`, see `Commands::ft_search`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchOptions {
+    no_content: bool,
+    verbatim: bool,
+    with_scores: bool,
+    return_fields: Option<Vec<String>>,
+    sort_by: Option<(String, bool)>,
+    limit: Option<(usize, usize)>,
+    params: Vec<(String, String)>,
+    dialect: Option<u32>,
+}
+
+impl SearchOptions {
+    /// Creates options that return the first ten documents with all
+    /// their fields.
+    pub fn new() -> SearchOptions {
+        Default::default()
+    }
+
+    /// Returns only the ids of the documents (`NOCONTENT`).
+    /// `SearchResult` cannot tell scores from ids then, so this should
+    /// not be combined with `with_scores`.
+    pub fn no_content(mut self) -> SearchOptions {
+        self.no_content = true;
+        self
+    }
+
+    /// Does not stem the terms of the query (`VERBATIM`).
+    pub fn verbatim(mut self) -> SearchOptions {
+        self.verbatim = true;
+        self
+    }
+
+    /// Returns the relevance score of each document (`WITHSCORES`).
+    pub fn with_scores(mut self) -> SearchOptions {
+        self.with_scores = true;
+        self
+    }
+
+    /// Returns only the fields given (`RETURN`).
+    pub fn return_fields(mut self, fields: &[&str]) -> SearchOptions {
+        self.return_fields = Some(fields.iter().map(|field| field.to_string()).collect());
+        self
+    }
+
+    /// Sorts the documents by a sortable field instead of relevance
+    /// (`SORTBY`).
+    pub fn sort_by(mut self, field: &str, ascending: bool) -> SearchOptions {
+        self.sort_by = Some((field.to_string(), ascending));
+        self
+    }
+
+    /// Returns `count` documents after skipping `offset` of them
+    /// (`LIMIT`).  `limit(0, 0)` only counts the documents.
+    pub fn limit(mut self, offset: usize, count: usize) -> SearchOptions {
+        self.limit = Some((offset, count));
+        self
+    }
+
+    /// Sets a parameter that is referenced as `$name` in the query
+    /// (`PARAMS`).  Parameters require dialect 2.
+    pub fn param(mut self, name: &str, value: &str) -> SearchOptions {
+        self.params.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the version of the query syntax (`DIALECT`).
+    pub fn dialect(mut self, dialect: u32) -> SearchOptions {
+        self.dialect = Some(dialect);
+        self
+    }
+}
+
+impl ToRedisArgs for SearchOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if self.no_content {
+            out.write_arg(b"NOCONTENT");
+        }
+        if self.verbatim {
+            out.write_arg(b"VERBATIM");
+        }
+        if self.with_scores {
+            out.write_arg(b"WITHSCORES");
+        }
+        if let Some(ref fields) = self.return_fields {
+            out.write_arg(b"RETURN");
+            fields.len().write_redis_args(out);
+            for field in fields {
+                field.write_redis_args(out);
+            }
+        }
+        if let Some((ref field, ascending)) = self.sort_by {
+            out.write_arg(b"SORTBY");
+            field.write_redis_args(out);
+            out.write_arg(if ascending { &b"ASC"[..] } else { &b"DESC"[..] });
+        }
+        if let Some((offset, count)) = self.limit {
+            out.write_arg(b"LIMIT");
+            offset.write_redis_args(out);
+            count.write_redis_args(out);
+        }
+        write_params(&self.params, out);
+        if let Some(dialect) = self.dialect {
+            out.write_arg(b"DIALECT");
+            dialect.write_redis_args(out);
+        }
+    }
+}
+
+fn write_params<W>(params: &[(String, String)], out: &mut W)
+where
+    W: ?Sized + RedisWrite,
+{
+    if !params.is_empty() {
+        out.write_arg(b"PARAMS");
+        (params.len() * 2).write_redis_args(out);
+        for &(ref name, ref value) in params {
+            name.write_redis_args(out);
+            value.write_redis_args(out);
+        }
+    }
+}
+
+/// A function that combines the rows of a group of `FT.AGGREGATE`
+/// (`REDUCE`), see `AggregateOptions::group_by`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reducer {
+    function: String,
+    args: Vec<String>,
+    alias: Option<String>,
+}
+
+impl Reducer {
+    /// Creates a reducer calling a function with arguments.
+    pub fn new(function: &str, args: &[&str]) -> Reducer {
+        Reducer {
+            function: function.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            alias: None,
+        }
+    }
+
+    /// Counts the rows of the group (`COUNT`).
+    pub fn count() -> Reducer {
+        Reducer::new("COUNT", &[])
+    }
+
+    /// Counts the distinct values of a property (`COUNT_DISTINCT`).
+    pub fn count_distinct(property: &str) -> Reducer {
+        Reducer::new("COUNT_DISTINCT", &[property])
+    }
+
+    /// Sums a numeric property (`SUM`).
+    pub fn sum(property: &str) -> Reducer {
+        Reducer::new("SUM", &[property])
+    }
+
+    /// Returns the smallest value of a numeric property (`MIN`).
+    pub fn min(property: &str) -> Reducer {
+        Reducer::new("MIN", &[property])
+    }
+
+    /// Returns the largest value of a numeric property (`MAX`).
+    pub fn max(property: &str) -> Reducer {
+        Reducer::new("MAX", &[property])
+    }
+
+    /// Returns the mean of a numeric property (`AVG`).
+    pub fn avg(property: &str) -> Reducer {
+        Reducer::new("AVG", &[property])
+    }
+
+    /// Returns the distinct values of a property as a list (`TOLIST`).
+    pub fn to_list(property: &str) -> Reducer {
+        Reducer::new("TOLIST", &[property])
+    }
+
+    /// Sets the name of the property the result is stored in (`AS`).
+    pub fn alias(mut self, alias: &str) -> Reducer {
+        self.alias = Some(alias.to_string());
+        self
+    }
+}
+
+impl ToRedisArgs for Reducer {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(b"REDUCE");
+        self.function.write_redis_args(out);
+        self.args.len().write_redis_args(out);
+        for arg in &self.args {
+            arg.write_redis_args(out);
+        }
+        if let Some(ref alias) = self.alias {
+            out.write_arg(b"AS");
+            alias.write_redis_args(out);
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    GroupBy(Vec<String>, Vec<Reducer>),
+    SortBy(Vec<(String, bool)>, Option<usize>),
+    Apply(String, String),
+    Filter(String),
+    Limit(usize, usize),
+}
+
+/// The options of `FT.AGGREGATE`, see `Commands::ft_aggregate`.
+///
+/// The steps of the pipeline are run in the order they were added.
+/// Properties are referenced with an `@`, like `@year`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AggregateOptions {
+    verbatim: bool,
+    load: Option<Vec<String>>,
+    steps: Vec<Step>,
+    params: Vec<(String, String)>,
+    dialect: Option<u32>,
+}
+
+impl AggregateOptions {
+    /// Creates options without steps.
+    pub fn new() -> AggregateOptions {
+        Default::default()
+    }
+
+    /// Does not stem the terms of the query (`VERBATIM`).
+    pub fn verbatim(mut self) -> AggregateOptions {
+        self.verbatim = true;
+        self
+    }
+
+    /// Loads fields of the documents that are not sortable (`LOAD`).
+    pub fn load(mut self, fields: &[&str]) -> AggregateOptions {
+        self.load = Some(fields.iter().map(|field| field.to_string()).collect());
+        self
+    }
+
+    /// Loads all fields of the documents (`LOAD *`).
+    pub fn load_all(self) -> AggregateOptions {
+        self.load(&["*"])
+    }
+
+    /// Groups the rows by properties and reduces each group to one row
+    /// (`GROUPBY`).
+    pub fn group_by(mut self, properties: &[&str], reducers: Vec<Reducer>) -> AggregateOptions {
+        let properties = properties.iter().map(|prop| prop.to_string()).collect();
+        self.steps.push(Step::GroupBy(properties, reducers));
+        self
+    }
+
+    /// Sorts the rows by properties, each ascending or descending
+    /// (`SORTBY`).
+    pub fn sort_by(mut self, properties: &[(&str, bool)]) -> AggregateOptions {
+        let properties = properties
+            .iter()
+            .map(|&(prop, ascending)| (prop.to_string(), ascending))
+            .collect();
+        self.steps.push(Step::SortBy(properties, None));
+        self
+    }
+
+    /// Sorts the rows like `sort_by` and keeps the first `max` of them
+    /// (`SORTBY ... MAX`).
+    pub fn sort_by_max(mut self, properties: &[(&str, bool)], max: usize) -> AggregateOptions {
+        self = self.sort_by(properties);
+        if let Some(&mut Step::SortBy(_, ref mut limit)) = self.steps.last_mut() {
+            *limit = Some(max);
+        }
+        self
+    }
+
+    /// Adds a property computed from an expression (`APPLY ... AS`).
+    pub fn apply(mut self, expression: &str, alias: &str) -> AggregateOptions {
+        self.steps
+            .push(Step::Apply(expression.to_string(), alias.to_string()));
+        self
+    }
+
+    /// Keeps the rows for which the expression is true (`FILTER`).
+    pub fn filter(mut self, expression: &str) -> AggregateOptions {
+        self.steps.push(Step::Filter(expression.to_string()));
+        self
+    }
+
+    /// Keeps `count` rows after skipping `offset` of them (`LIMIT`).
+    pub fn limit(mut self, offset: usize, count: usize) -> AggregateOptions {
+        self.steps.push(Step::Limit(offset, count));
+        self
+    }
+
+    /// Sets a parameter that is referenced as `$name` in the query
+    /// (`PARAMS`).  Parameters require dialect 2.
+    pub fn param(mut self, name: &str, value: &str) -> AggregateOptions {
+        self.params.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the version of the query syntax (`DIALECT`).
+    pub fn dialect(mut self, dialect: u32) -> AggregateOptions {
+        self.dialect = Some(dialect);
+        self
+    }
+}
+
+impl ToRedisArgs for AggregateOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if self.verbatim {
+            out.write_arg(b"VERBATIM");
+        }
+        if let Some(ref fields) = self.load {
+            out.write_arg(b"LOAD");
+            if fields.len() == 1 && fields[0] == "*" {
+                out.write_arg(b"*");
+            } else {
+                fields.len().write_redis_args(out);
+                for field in fields {
+                    field.write_redis_args(out);
+                }
+            }
+        }
+        for step in &self.steps {
+            match *step {
+                Step::GroupBy(ref properties, ref reducers) => {
+                    out.write_arg(b"GROUPBY");
+                    properties.len().write_redis_args(out);
+                    for prop in properties {
+                        prop.write_redis_args(out);
+                    }
+                    for reducer in reducers {
+                        reducer.write_redis_args(out);
+                    }
+                }
+                Step::SortBy(ref properties, max) => {
+                    out.write_arg(b"SORTBY");
+                    (properties.len() * 2).write_redis_args(out);
+                    for &(ref prop, ascending) in properties {
+                        prop.write_redis_args(out);
+                        out.write_arg(if ascending { &b"ASC"[..] } else { &b"DESC"[..] });
+                    }
+                    if let Some(max) = max {
+                        out.write_arg(b"MAX");
+                        max.write_redis_args(out);
+                    }
+                }
+                Step::Apply(ref expression, ref alias) => {
+                    out.write_arg(b"APPLY");
+                    expression.write_redis_args(out);
+                    out.write_arg(b"AS");
+                    alias.write_redis_args(out);
+                }
+                Step::Filter(ref expression) => {
+                    out.write_arg(b"FILTER");
+                    expression.write_redis_args(out);
+                }
+                Step::Limit(offset, count) => {
+                    out.write_arg(b"LIMIT");
+                    offset.write_redis_args(out);
+                    count.write_redis_args(out);
+                }
+            }
+        }
+        write_params(&self.params, out);
+        if let Some(dialect) = self.dialect {
+            out.write_arg(b"DIALECT");
+            dialect.write_redis_args(out);
+        }
+    }
+}
+
+/// A document found by `FT.SEARCH`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Document {
+    /// The key of the document.
+    pub id: String,
+    /// The relevance score, if it was asked for with `with_scores`.
+    pub score: Option<f64>,
+    /// The fields that were returned, none with `no_content`.  JSON
+    /// documents have their content in the `$` field.
+    pub fields: HashMap<String, String>,
+}
+
+/// The reply of `FT.SEARCH`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    /// The number of documents that matched, which may be more than were
+    /// returned.
+    pub total: usize,
+    /// The documents that were returned.
+    pub documents: Vec<Document>,
+}
+
+impl FromRedisValue for SearchResult {
+    fn from_redis_value(v: &Value) -> RedisResult<SearchResult> {
+        let items = match *v {
+            Value::Bulk(ref items) if !items.is_empty() => items,
+            Value::Map(_) => {
+                let map: HashMap<String, Value> = from_redis_value(v)?;
+                let results: Vec<HashMap<String, Value>> = field(&map, "results")?;
+                let mut documents = Vec::with_capacity(results.len());
+                for result in &results {
+                    documents.push(Document {
+                        id: field(result, "id")?,
+                        score: optional_field(result, "score")?,
+                        fields: optional_field(result, "extra_attributes")?.unwrap_or_default(),
+                    });
+                }
+                return Ok(SearchResult {
+                    total: field(&map, "total_results")?,
+                    documents,
+                });
+            }
+            _ => invalid_type_error!(v, "Response type not search result compatible"),
+        };
+        let mut documents = vec![];
+        let mut idx = 1;
+        while idx < items.len() {
+            let mut document = Document {
+                id: from_redis_value(&items[idx])?,
+                score: None,
+                fields: HashMap::new(),
+            };
+            idx += 1;
+            // the fields are the only arrays, a score comes before them
+            if idx + 1 < items.len() && !is_bulk(&items[idx]) && is_bulk(&items[idx + 1]) {
+                document.score = Some(from_redis_value(&items[idx])?);
+                idx += 1;
+            }
+            if idx < items.len() && is_bulk(&items[idx]) {
+                document.fields = from_redis_value(&items[idx])?;
+                idx += 1;
+            }
+            documents.push(document);
+        }
+        Ok(SearchResult {
+            total: from_redis_value(&items[0])?,
+            documents,
+        })
+    }
+}
+
+fn is_bulk(value: &Value) -> bool {
+    match *value {
+        Value::Bulk(_) => true,
+        _ => false,
+    }
+}
+
+/// The reply of `FT.AGGREGATE`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AggregateResult {
+    /// The number of rows before `LIMIT`, as reported by the server.
+    pub total: usize,
+    /// The properties of each row.  They are strings except for the
+    /// results of reducers like `TOLIST`.
+    pub rows: Vec<HashMap<String, Value>>,
+}
+
+impl FromRedisValue for AggregateResult {
+    fn from_redis_value(v: &Value) -> RedisResult<AggregateResult> {
+        match *v {
+            Value::Bulk(ref items) if !items.is_empty() => {
+                let mut rows = Vec::with_capacity(items.len() - 1);
+                for item in &items[1..] {
+                    rows.push(from_redis_value(item)?);
+                }
+                Ok(AggregateResult {
+                    total: from_redis_value(&items[0])?,
+                    rows,
+                })
+            }
+            Value::Map(_) => {
+                let map: HashMap<String, Value> = from_redis_value(v)?;
+                let results: Vec<HashMap<String, Value>> = field(&map, "results")?;
+                let mut rows = Vec::with_capacity(results.len());
+                for result in &results {
+                    rows.push(optional_field(result, "extra_attributes")?.unwrap_or_default());
+                }
+                Ok(AggregateResult {
+                    total: field(&map, "total_results")?,
+                    rows,
+                })
+            }
+            _ => invalid_type_error!(v, "Response type not aggregate result compatible"),
+        }
+    }
+}
+
+/// A field of an index, as listed by `FT.INFO`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttributeInfo {
+    /// The name or JSON path of the field.
+    pub identifier: String,
+    /// The name the field is queried by.
+    pub attribute: String,
+    /// The type of the field, like `TEXT`.
+    pub kind: String,
+    /// The other options of the field, like `SORTABLE` or `WEIGHT` and its
+    /// value.
+    pub options: Vec<String>,
+}
+
+impl FromRedisValue for AttributeInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<AttributeInfo> {
+        let items = match *v {
+            Value::Bulk(ref items) => items,
+            Value::Map(_) => {
+                let map: HashMap<String, Value> = from_redis_value(v)?;
+                return Ok(AttributeInfo {
+                    identifier: field(&map, "identifier")?,
+                    attribute: field(&map, "attribute")?,
+                    kind: field(&map, "type")?,
+                    options: optional_field(&map, "flags")?.unwrap_or_default(),
+                });
+            }
+            _ => invalid_type_error!(v, "Response type not index attribute compatible"),
+        };
+        let mut rv = AttributeInfo {
+            identifier: String::new(),
+            attribute: String::new(),
+            kind: String::new(),
+            options: vec![],
+        };
+        let mut iter = items.iter();
+        while let Some(item) = iter.next() {
+            let item = string(item)?;
+            if item != "identifier" && item != "attribute" && item != "type" {
+                rv.options.push(item);
+                continue;
+            }
+            let value = match iter.next() {
+                Some(value) => string(value)?,
+                None => break,
+            };
+            match &item[..] {
+                "identifier" => rv.identifier = value,
+                "attribute" => rv.attribute = value,
+                _ => rv.kind = value,
+            }
+        }
+        Ok(rv)
+    }
+}
+
+// Options of attributes like `WEIGHT` have numbers as values.
+fn string(value: &Value) -> RedisResult<String> {
+    match *value {
+        Value::Int(val) => Ok(val.to_string()),
+        _ => from_redis_value(value),
+    }
+}
+
+/// The reply of `FT.INFO`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexInfo {
+    /// The name of the index.
+    pub name: String,
+    /// The number of indexed documents.
+    pub num_docs: u64,
+    /// The number of distinct terms.
+    pub num_terms: u64,
+    /// The number of entries in the inverted indexes.
+    pub num_records: u64,
+    /// Whether the existing keys are still being indexed.
+    pub indexing: bool,
+    /// The share of existing keys indexed so far, from 0 to 1.
+    pub percent_indexed: f64,
+    /// The number of documents that could not be indexed.
+    pub hash_indexing_failures: u64,
+    /// The fields of the index.
+    pub attributes: Vec<AttributeInfo>,
+}
+
+impl FromRedisValue for IndexInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<IndexInfo> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        let indexing: i64 = field(&map, "indexing")?;
+        // versions before 2.2 called the attributes fields
+        let attributes = match optional_field(&map, "attributes")? {
+            Some(attributes) => attributes,
+            None => field(&map, "fields")?,
+        };
+        Ok(IndexInfo {
+            name: field(&map, "index_name")?,
+            num_docs: field(&map, "num_docs")?,
+            num_terms: field(&map, "num_terms")?,
+            num_records: field(&map, "num_records")?,
+            indexing: indexing != 0,
+            percent_indexed: field(&map, "percent_indexed")?,
+            hash_indexing_failures: field(&map, "hash_indexing_failures")?,
+            attributes,
+        })
+    }
+}
+
+fn field<T: FromRedisValue>(map: &HashMap<String, Value>, name: &str) -> RedisResult<T> {
+    match map.get(name) {
+        Some(value) => from_redis_value(value),
+        None => Err(RedisError::from((
+            ErrorKind::TypeError,
+            "Search reply without field",
+            name.to_string(),
+        ))),
+    }
+}
+
+fn optional_field<T: FromRedisValue>(
+    map: &HashMap<String, Value>,
+    name: &str,
+) -> RedisResult<Option<T>> {
+    match map.get(name) {
+        Some(&Value::Nil) | None => Ok(None),
+        Some(value) => from_redis_value(value).map(Some),
+    }
+}
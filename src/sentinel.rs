@@ -0,0 +1,412 @@
+//! Support for deployments managed by redis sentinel.
+//!
+//! A `ConnectionAddr::Sentinel` address names a service that is monitored by
+//! a group of sentinels instead of a single server.  Every time a connection
+//! to such an address is opened, the sentinels are asked for the current
+//! master (or the replicas) of the service and the connection is made to
+//! that node.  Sentinels disconnect the clients of a demoted master during
+//! a failover, so clients, pools and `ConnectionManager`s transparently
+//! follow the master when they reconnect.
+//!
+//! The address is usually created from a `redis+sentinel://` URL like
+//! `redis+sentinel://sentinel-1,sentinel-2/mymaster?role=master`.  The
+//! same configuration spelled out:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::sentinel::{SentinelConfig, SentinelRole};
+//! use redis::{ConnectionAddr, ConnectionInfo};
+//!
+//! let info = ConnectionInfo {
+//!     addr: Box::new(ConnectionAddr::Sentinel(SentinelConfig {
+//!         sentinels: vec![
+//!             ConnectionAddr::Tcp("sentinel-1".to_string(), 26379),
+//!             ConnectionAddr::Tcp("sentinel-2".to_string(), 26379),
+//!         ],
+//!         service_name: "mymaster".to_string(),
+//!         role: SentinelRole::Master,
+//!         passwd: None,
+//!     })),
+//!     ..redis::IntoConnectionInfo::into_connection_info("redis://localhost/")?
+//! };
+//! let client = redis::Client::open(info)?;
+//! let mut con = client.get_connection()?;
+//! # Ok(()) }
+//! ```
+use std::collections::HashMap;
+
+#[cfg(feature = "aio")]
+use futures::future::{self, Either};
+#[cfg(feature = "aio")]
+use futures::Future;
+
+use cmd::{cmd, Cmd};
+use connection::{connect_addr, tcp_addr, Connection, ConnectionAddr, ConnectionInfo};
+#[cfg(feature = "aio")]
+use types::RedisFuture;
+use types::{from_redis_value, random_u64, ErrorKind, RedisError, RedisResult, Value};
+
+/// The kind of node to connect to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SentinelRole {
+    /// The master of the service.
+    Master,
+    /// A randomly chosen healthy replica of the service.  The master is
+    /// used if no replica is available.
+    Replica,
+    /// The master or a healthy replica, chosen at random, which spreads
+    /// read-only connections over all nodes.
+    Any,
+}
+
+/// Describes a service monitored by sentinels.
+///
+/// The sentinels are contacted with the timeouts and the TCP and TLS
+/// options of the `ConnectionInfo` the address belongs to.  Nodes are
+/// connected to with TLS if the sentinel that reported them was.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SentinelConfig {
+    /// The addresses of the sentinels, which are asked in order.
+    pub sentinels: Vec<ConnectionAddr>,
+    /// The name of the monitored master.
+    pub service_name: String,
+    /// Whether to connect to the master or to a replica.  Connections to
+    /// replicas can only be used for reads.
+    pub role: SentinelRole,
+    /// The password of the sentinels, if they require one.  The password
+    /// of the nodes is the one of the `ConnectionInfo`.
+    pub passwd: Option<String>,
+}
+
+/// A master switch announced by a sentinel after a failover.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MasterSwitch {
+    /// The name of the service that failed over.
+    pub service_name: String,
+    /// The address of the previous master.
+    pub old_addr: ConnectionAddr,
+    /// The address of the new master.
+    pub new_addr: ConnectionAddr,
+}
+
+/// A subscription to the master switches of a service, see
+/// `SentinelConfig::master_switches`.
+pub struct MasterSwitches {
+    con: Connection,
+    service_name: String,
+    tls: bool,
+}
+
+type Parse<T> = fn(&SentinelConfig, Value, bool) -> RedisResult<T>;
+
+fn master_cmd(config: &SentinelConfig) -> Cmd {
+    let mut cmd = cmd("SENTINEL");
+    cmd.arg("get-master-addr-by-name").arg(&config.service_name);
+    cmd
+}
+
+fn replicas_cmd(config: &SentinelConfig) -> Cmd {
+    // `SLAVES` instead of `REPLICAS` works with sentinels older than 5.0.
+    let mut cmd = cmd("SENTINEL");
+    cmd.arg("SLAVES").arg(&config.service_name);
+    cmd
+}
+
+fn parse_master(config: &SentinelConfig, value: Value, tls: bool) -> RedisResult<ConnectionAddr> {
+    match from_redis_value(&value)? {
+        Some((host, port)) => Ok(tcp_addr(host, port, tls)),
+        None => Err(unknown_service_error(config)),
+    }
+}
+
+// Moves a random element to the front.
+fn shuffle(addrs: &mut Vec<ConnectionAddr>) {
+    let len = addrs.len();
+    if len > 1 {
+        addrs.rotate_left((random_u64() % len as u64) as usize);
+    }
+}
+
+fn parse_replicas(_: &SentinelConfig, value: Value, tls: bool) -> RedisResult<Vec<ConnectionAddr>> {
+    let replicas: Vec<HashMap<String, String>> = from_redis_value(&value)?;
+    let mut addrs: Vec<ConnectionAddr> = replicas
+        .into_iter()
+        .filter(|replica| {
+            let flags = replica.get("flags").map(|x| &x[..]).unwrap_or("");
+            !flags
+                .split(',')
+                .any(|flag| flag == "s_down" || flag == "o_down" || flag == "disconnected")
+                && replica.get("master-link-status").map(|x| &x[..]) == Some("ok")
+        })
+        .filter_map(|mut replica| {
+            let port = replica.get("port").and_then(|port| port.parse().ok());
+            match (replica.remove("ip"), port) {
+                (Some(host), Some(port)) => Some(tcp_addr(host, port, tls)),
+                _ => None,
+            }
+        })
+        .collect();
+    shuffle(&mut addrs);
+    Ok(addrs)
+}
+
+fn check_role(config: &SentinelConfig, value: &Value) -> RedisResult<()> {
+    let expected = match config.role {
+        SentinelRole::Master => "master",
+        SentinelRole::Replica | SentinelRole::Any => "slave",
+    };
+    let role: Option<String> = match *value {
+        Value::Bulk(ref items) if !items.is_empty() => from_redis_value(&items[0]).ok(),
+        _ => None,
+    };
+    if config.role != SentinelRole::Master && role.as_ref().map(|x| &x[..]) == Some("master") {
+        // the fallback when no replica is available
+        return Ok(());
+    }
+    if role.as_ref().map(|x| &x[..]) != Some(expected) {
+        fail!((
+            ErrorKind::ResponseError,
+            "Node reported by sentinel has an unexpected role",
+            format!("expected {}, got {:?}", expected, role)
+        ));
+    }
+    Ok(())
+}
+
+fn unknown_service_error(config: &SentinelConfig) -> RedisError {
+    RedisError::from((
+        ErrorKind::InvalidClientConfig,
+        "Service is not monitored by the sentinel",
+        config.service_name.clone(),
+    ))
+}
+
+fn no_sentinel_error() -> RedisError {
+    RedisError::from((ErrorKind::InvalidClientConfig, "No sentinel to connect to"))
+}
+
+impl SentinelConfig {
+    // The connection info used to talk to the sentinel at `addr`.
+    fn sentinel_info(&self, addr: &ConnectionAddr, info: &ConnectionInfo) -> ConnectionInfo {
+        ConnectionInfo {
+            addr: Box::new(addr.clone()),
+            db: 0,
+            passwd: self.passwd.clone(),
+            client_name: None,
+            fallback_addrs: vec![],
+            shuffle_addrs: false,
+            ..info.clone()
+        }
+    }
+
+    fn is_tls(addr: &ConnectionAddr) -> bool {
+        match *addr {
+            ConnectionAddr::TcpTls { .. } => true,
+            _ => false,
+        }
+    }
+
+    // Asks the sentinels one after another until one of them knows the
+    // answer.  Fails with the error of the last sentinel otherwise.
+    fn query<T>(&self, info: &ConnectionInfo, cmd: &Cmd, parse: Parse<T>) -> RedisResult<T> {
+        let mut result = Err(no_sentinel_error());
+        for addr in &self.sentinels {
+            if let ConnectionAddr::Sentinel(_) = *addr {
+                continue;
+            }
+            result = connect_addr(addr, &self.sentinel_info(addr, info))
+                .and_then(|mut con| cmd.query(&mut con))
+                .and_then(|value| parse(self, value, SentinelConfig::is_tls(addr)));
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+
+    /// Asks the sentinels for the address of the current master.
+    pub fn master_addr(&self, info: &ConnectionInfo) -> RedisResult<ConnectionAddr> {
+        self.query(info, &master_cmd(self), parse_master)
+    }
+
+    /// Asks the sentinels for the addresses of the healthy replicas in
+    /// random order.
+    pub fn replica_addrs(&self, info: &ConnectionInfo) -> RedisResult<Vec<ConnectionAddr>> {
+        self.query(info, &replicas_cmd(self), parse_replicas)
+    }
+
+    fn node_addrs(&self, info: &ConnectionInfo) -> RedisResult<Vec<ConnectionAddr>> {
+        match self.role {
+            SentinelRole::Master => Ok(vec![self.master_addr(info)?]),
+            SentinelRole::Replica => {
+                let addrs = self.replica_addrs(info)?;
+                if !addrs.is_empty() {
+                    return Ok(addrs);
+                }
+                Ok(vec![self.master_addr(info)?])
+            }
+            SentinelRole::Any => {
+                let mut addrs = self.replica_addrs(info)?;
+                addrs.push(self.master_addr(info)?);
+                shuffle(&mut addrs);
+                Ok(addrs)
+            }
+        }
+    }
+
+    /// Subscribes to the master switches of the service at the first
+    /// sentinel that can be reached.
+    pub fn master_switches(&self, info: &ConnectionInfo) -> RedisResult<MasterSwitches> {
+        let mut result = Err(no_sentinel_error());
+        for addr in &self.sentinels {
+            if let ConnectionAddr::Sentinel(_) = *addr {
+                continue;
+            }
+            let switches =
+                connect_addr(addr, &self.sentinel_info(addr, info)).and_then(|mut con| {
+                    let _: () = cmd("SUBSCRIBE").arg("+switch-master").query(&mut con)?;
+                    Ok(MasterSwitches {
+                        con: con,
+                        service_name: self.service_name.clone(),
+                        tls: SentinelConfig::is_tls(addr),
+                    })
+                });
+            match switches {
+                Ok(switches) => return Ok(switches),
+                Err(err) => result = Err(err),
+            }
+        }
+        result
+    }
+}
+
+impl MasterSwitches {
+    /// Blocks until the next master switch of the service is announced.
+    pub fn next_switch(&mut self) -> RedisResult<MasterSwitch> {
+        loop {
+            let message: Vec<Value> = from_redis_value(&self.con.recv_response()?)?;
+            if message.len() != 3 || from_redis_value::<String>(&message[0])? != "message" {
+                continue;
+            }
+            let payload: String = from_redis_value(&message[2])?;
+            let parts: Vec<&str> = payload.split(' ').collect();
+            if parts.len() != 5 || parts[0] != self.service_name {
+                continue;
+            }
+            let old_port = unwrap_or!(parts[2].parse().ok(), continue);
+            let new_port = unwrap_or!(parts[4].parse().ok(), continue);
+            return Ok(MasterSwitch {
+                service_name: parts[0].to_string(),
+                old_addr: tcp_addr(parts[1].to_string(), old_port, self.tls),
+                new_addr: tcp_addr(parts[3].to_string(), new_port, self.tls),
+            });
+        }
+    }
+
+    /// Returns the connection to the sentinel, for instance to set a read
+    /// timeout.
+    pub fn get_ref(&self) -> &Connection {
+        &self.con
+    }
+}
+
+/// Connects to the node of a sentinel managed service.
+pub fn connect(config: &SentinelConfig, info: &ConnectionInfo) -> RedisResult<Connection> {
+    let mut result = Err(unknown_service_error(config));
+    for addr in config.node_addrs(info)? {
+        result = connect_addr(&addr, info).and_then(|mut con| {
+            let role: Value = cmd("ROLE").query(&mut con)?;
+            check_role(config, &role)?;
+            Ok(con)
+        });
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+#[cfg(feature = "aio")]
+fn query_async<T: Send + 'static>(
+    config: &SentinelConfig,
+    info: &ConnectionInfo,
+    cmd: Cmd,
+    parse: Parse<T>,
+) -> RedisFuture<T> {
+    let mut result: RedisFuture<T> = Box::new(future::err(no_sentinel_error()));
+    for addr in &config.sentinels {
+        if let ConnectionAddr::Sentinel(_) = *addr {
+            continue;
+        }
+        let sentinel_info = config.sentinel_info(addr, info);
+        let tls = SentinelConfig::is_tls(addr);
+        let config = config.clone();
+        let cmd = cmd.clone();
+        result = Box::new(result.or_else(move |_| {
+            ::aio::connect(sentinel_info)
+                .and_then(move |con| cmd.query_async::<_, Value>(con))
+                .and_then(move |(_, value)| parse(&config, value, tls))
+        }));
+    }
+    result
+}
+
+/// Connects an async connection to the node of a sentinel managed service.
+#[cfg(feature = "aio")]
+pub fn connect_async(
+    config: &SentinelConfig,
+    info: &ConnectionInfo,
+) -> RedisFuture<::aio::Connection> {
+    let addrs: RedisFuture<Vec<ConnectionAddr>> = match config.role {
+        SentinelRole::Master => Box::new(
+            query_async(config, info, master_cmd(config), parse_master).map(|addr| vec![addr]),
+        ),
+        SentinelRole::Replica => {
+            let master = query_async(config, info, master_cmd(config), parse_master);
+            Box::new(
+                query_async(config, info, replicas_cmd(config), parse_replicas).and_then(
+                    move |addrs| {
+                        if addrs.is_empty() {
+                            Either::A(master.map(|addr| vec![addr]))
+                        } else {
+                            Either::B(future::ok(addrs))
+                        }
+                    },
+                ),
+            )
+        }
+        SentinelRole::Any => {
+            let master = query_async(config, info, master_cmd(config), parse_master);
+            Box::new(
+                query_async(config, info, replicas_cmd(config), parse_replicas)
+                    .join(master)
+                    .map(|(mut addrs, master)| {
+                        addrs.push(master);
+                        shuffle(&mut addrs);
+                        addrs
+                    }),
+            )
+        }
+    };
+
+    let config = config.clone();
+    let info = info.clone();
+    Box::new(addrs.and_then(move |addrs| {
+        let mut connection: RedisFuture<::aio::Connection> =
+            Box::new(future::err(unknown_service_error(&config)));
+        for addr in addrs {
+            let node_info = ConnectionInfo {
+                addr: Box::new(addr),
+                fallback_addrs: vec![],
+                ..info.clone()
+            };
+            let config = config.clone();
+            connection = Box::new(connection.or_else(move |_| {
+                ::aio::connect(node_info)
+                    .and_then(|con| cmd("ROLE").query_async::<_, Value>(con))
+                    .and_then(move |(con, role)| check_role(&config, &role).map(|()| con))
+            }));
+        }
+        connection
+    }))
+}
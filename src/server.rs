@@ -0,0 +1,315 @@
+//! Types for the commands that inspect and manage the server, like
+//! `SLOWLOG`, `MEMORY` and `COMMAND`.
+use std::collections::HashMap;
+use std::str::from_utf8;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use types::{from_redis_value, ErrorKind, FromRedisValue, RedisError, RedisResult, Value};
+
+/// A command that took longer than `slowlog-log-slower-than`, as listed
+/// by `SLOWLOG GET`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlowlogEntry {
+    /// The unique id of the entry.
+    pub id: i64,
+    /// When the command was processed.
+    pub time: SystemTime,
+    /// How long the command took, without I/O.
+    pub duration: Duration,
+    /// The command and its arguments.  Long arguments are truncated and
+    /// many arguments are cut short by the server.
+    pub args: Vec<Vec<u8>>,
+    /// The address of the client, `ip:port` (redis 4).
+    pub client_addr: Option<String>,
+    /// The name the client set with `CLIENT SETNAME`, if any (redis 4).
+    pub client_name: Option<String>,
+}
+
+impl SlowlogEntry {
+    /// Returns the name of the command as string.  If it is not valid
+    /// UTF-8 the return value is `"?"`.
+    pub fn command_name(&self) -> &str {
+        match self.args.first() {
+            Some(name) => from_utf8(name).unwrap_or("?"),
+            None => "?",
+        }
+    }
+}
+
+impl FromRedisValue for SlowlogEntry {
+    fn from_redis_value(v: &Value) -> RedisResult<SlowlogEntry> {
+        let items = match *v {
+            Value::Bulk(ref items) if items.len() >= 4 => items,
+            _ => invalid_type_error!(v, "Response type not slowlog entry compatible"),
+        };
+        let time: u64 = from_redis_value(&items[1])?;
+        let micros: u64 = from_redis_value(&items[2])?;
+        let name = |idx: usize| -> RedisResult<Option<String>> {
+            match items.get(idx) {
+                Some(value) => {
+                    let name: String = from_redis_value(value)?;
+                    Ok(if name.is_empty() { None } else { Some(name) })
+                }
+                None => Ok(None),
+            }
+        };
+        Ok(SlowlogEntry {
+            id: from_redis_value(&items[0])?,
+            time: UNIX_EPOCH + Duration::from_secs(time),
+            duration: Duration::new(micros / 1_000_000, (micros % 1_000_000) as u32 * 1000),
+            args: from_redis_value(&items[3])?,
+            client_addr: name(4)?,
+            client_name: name(5)?,
+        })
+    }
+}
+
+/// The memory statistics of `MEMORY STATS`.  Sizes are in bytes.
+///
+/// Fields that older servers do not report are `None`, all numeric
+/// fields are kept in `fields`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryStats {
+    /// The peak memory use (`peak.allocated`).
+    pub peak_allocated: u64,
+    /// The total memory use (`total.allocated`).
+    pub total_allocated: u64,
+    /// The memory used by the server at startup (`startup.allocated`).
+    pub startup_allocated: u64,
+    /// The memory used by the buffers of replicas
+    /// (`clients.slaves`).
+    pub clients_replicas: u64,
+    /// The memory used by the buffers of other clients
+    /// (`clients.normal`).
+    pub clients_normal: u64,
+    /// The memory used for the values of all databases
+    /// (`dataset.bytes`).
+    pub dataset_bytes: u64,
+    /// The number of keys (`keys.count`).
+    pub keys_count: u64,
+    /// The average memory per key (`keys.bytes-per-key`).
+    pub keys_bytes_per_key: u64,
+    /// The fragmentation of the allocator (`fragmentation`).
+    pub fragmentation: Option<f64>,
+    /// All numeric fields by name, like `replication.backlog`.  The
+    /// overhead of each database is listed as `db.0.overhead.hashtable.main`
+    /// and the like.
+    pub fields: HashMap<String, f64>,
+}
+
+impl FromRedisValue for MemoryStats {
+    fn from_redis_value(v: &Value) -> RedisResult<MemoryStats> {
+        let raw: HashMap<String, Value> = from_redis_value(v)?;
+        let mut fields = HashMap::new();
+        for (name, value) in &raw {
+            collect_numbers(name, value, &mut fields);
+        }
+        let field = |name: &str| -> RedisResult<u64> {
+            match fields.get(name) {
+                Some(&value) => Ok(value as u64),
+                None => Err(RedisError::from((
+                    ErrorKind::TypeError,
+                    "Memory stats without field",
+                    name.to_string(),
+                ))),
+            }
+        };
+        Ok(MemoryStats {
+            peak_allocated: field("peak.allocated")?,
+            total_allocated: field("total.allocated")?,
+            startup_allocated: field("startup.allocated")?,
+            clients_replicas: field("clients.slaves")?,
+            clients_normal: field("clients.normal")?,
+            dataset_bytes: field("dataset.bytes")?,
+            keys_count: field("keys.count")?,
+            keys_bytes_per_key: field("keys.bytes-per-key")?,
+            fragmentation: fields.get("fragmentation").cloned(),
+            fields: fields.clone(),
+        })
+    }
+}
+
+// Flattens nested maps like the ones of the databases into names joined by
+// dots.  Values that are not numbers, like `db.0` itself, are skipped.
+fn collect_numbers(name: &str, value: &Value, out: &mut HashMap<String, f64>) {
+    match *value {
+        Value::Bulk(_) | Value::Map(_) => {
+            let nested: HashMap<String, Value> = unwrap_or!(from_redis_value(value).ok(), return);
+            for (key, value) in &nested {
+                collect_numbers(&format!("{}.{}", name, key), value, out);
+            }
+        }
+        _ => {
+            if let Ok(number) = from_redis_value(value) {
+                out.insert(name.to_string(), number);
+            }
+        }
+    }
+}
+
+/// The details of a command, as returned by `COMMAND INFO`.
+///
+/// Fields that older servers do not report are empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandInfo {
+    /// The name of the command in lowercase, like `get` or `config|get`
+    /// for subcommands.
+    pub name: String,
+    /// The number of arguments including the name of the command.  A
+    /// negative arity `-n` means at least `n` arguments.
+    pub arity: i64,
+    /// Flags like `write`, `readonly` or `noscript`.
+    pub flags: Vec<String>,
+    /// The position of the first key, 0 for commands without keys.
+    pub first_key: i64,
+    /// The position of the last key, negative positions count from the
+    /// end of the arguments.
+    pub last_key: i64,
+    /// The step between the positions of the keys.
+    pub step: i64,
+    /// The ACL categories, like `@read` (redis 6).
+    pub acl_categories: Vec<String>,
+    /// Hints for clients, like `request_policy:all_shards` (redis 7).
+    pub tips: Vec<String>,
+    /// The subcommands, like the ones of `CONFIG` (redis 7).
+    pub subcommands: Vec<CommandInfo>,
+}
+
+impl CommandInfo {
+    /// Returns whether the command has a flag, ignoring case.
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f.eq_ignore_ascii_case(flag))
+    }
+}
+
+impl FromRedisValue for CommandInfo {
+    fn from_redis_value(v: &Value) -> RedisResult<CommandInfo> {
+        let items = match *v {
+            Value::Bulk(ref items) if items.len() >= 6 => items,
+            _ => invalid_type_error!(v, "Response type not command info compatible"),
+        };
+        Ok(CommandInfo {
+            name: from_redis_value(&items[0])?,
+            arity: from_redis_value(&items[1])?,
+            flags: from_redis_value(&items[2])?,
+            first_key: from_redis_value(&items[3])?,
+            last_key: from_redis_value(&items[4])?,
+            step: from_redis_value(&items[5])?,
+            acl_categories: optional_item(items, 6)?,
+            tips: optional_item(items, 7)?,
+            subcommands: optional_item(items, 9)?,
+        })
+    }
+}
+
+/// The documentation of a command, as returned by `COMMAND DOCS`
+/// (redis 7).
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandDocs {
+    /// A short description of the command.
+    pub summary: Option<String>,
+    /// The version of redis that added the command.
+    pub since: Option<String>,
+    /// The group of the command, like `string` or `server`.
+    pub group: Option<String>,
+    /// The time complexity of the command.
+    pub complexity: Option<String>,
+    /// Flags like `deprecated` or `syscmd`.
+    pub doc_flags: Vec<String>,
+    /// The version of redis that deprecated the command.
+    pub deprecated_since: Option<String>,
+    /// The command that replaces a deprecated one.
+    pub replaced_by: Option<String>,
+    /// The changes of the command as pairs of the version and a
+    /// description.
+    pub history: Vec<(String, String)>,
+    /// The arguments of the command.
+    pub arguments: Vec<CommandArgument>,
+    /// The documentation of the subcommands by name, like `config|get`.
+    pub subcommands: HashMap<String, CommandDocs>,
+}
+
+impl FromRedisValue for CommandDocs {
+    fn from_redis_value(v: &Value) -> RedisResult<CommandDocs> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(CommandDocs {
+            summary: optional_field(&map, "summary")?,
+            since: optional_field(&map, "since")?,
+            group: optional_field(&map, "group")?,
+            complexity: optional_field(&map, "complexity")?,
+            doc_flags: optional_field(&map, "doc_flags")?.unwrap_or_default(),
+            deprecated_since: optional_field(&map, "deprecated_since")?,
+            replaced_by: optional_field(&map, "replaced_by")?,
+            history: optional_field(&map, "history")?.unwrap_or_default(),
+            arguments: optional_field(&map, "arguments")?.unwrap_or_default(),
+            subcommands: optional_field(&map, "subcommands")?.unwrap_or_default(),
+        })
+    }
+}
+
+/// An argument of a command in its `CommandDocs`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandArgument {
+    /// The name of the argument.
+    pub name: String,
+    /// The type, like `key`, `string`, `integer`, `pure-token`, `oneof`
+    /// or `block`.
+    pub kind: String,
+    /// The keyword that comes before the argument, like `EX`.
+    pub token: Option<String>,
+    /// Flags like `optional` or `multiple`.
+    pub flags: Vec<String>,
+    /// The version of redis that added the argument.
+    pub since: Option<String>,
+    /// The nested arguments of `oneof` and `block` arguments.
+    pub arguments: Vec<CommandArgument>,
+}
+
+impl CommandArgument {
+    /// Returns whether the argument may be left out.
+    pub fn is_optional(&self) -> bool {
+        self.flags.iter().any(|flag| flag == "optional")
+    }
+}
+
+impl FromRedisValue for CommandArgument {
+    fn from_redis_value(v: &Value) -> RedisResult<CommandArgument> {
+        let map: HashMap<String, Value> = from_redis_value(v)?;
+        Ok(CommandArgument {
+            name: field(&map, "name")?,
+            kind: field(&map, "type")?,
+            token: optional_field(&map, "token")?,
+            flags: optional_field(&map, "flags")?.unwrap_or_default(),
+            since: optional_field(&map, "since")?,
+            arguments: optional_field(&map, "arguments")?.unwrap_or_default(),
+        })
+    }
+}
+
+fn optional_item<T: FromRedisValue + Default>(items: &[Value], idx: usize) -> RedisResult<T> {
+    match items.get(idx) {
+        Some(value) => from_redis_value(value),
+        None => Ok(T::default()),
+    }
+}
+
+fn field<T: FromRedisValue>(map: &HashMap<String, Value>, name: &str) -> RedisResult<T> {
+    match map.get(name) {
+        Some(value) => from_redis_value(value),
+        None => Err(RedisError::from((
+            ErrorKind::TypeError,
+            "Command docs without field",
+            name.to_string(),
+        ))),
+    }
+}
+
+fn optional_field<T: FromRedisValue>(
+    map: &HashMap<String, Value>,
+    name: &str,
+) -> RedisResult<Option<T>> {
+    match map.get(name) {
+        Some(&Value::Nil) | None => Ok(None),
+        Some(value) => from_redis_value(value).map(Some),
+    }
+}
@@ -0,0 +1,360 @@
+//! Support for the redis streams data type.
+//!
+//! The types in this module map the replies of the stream commands into
+//! something that is easier to work with than the raw nested bulk values
+//! and provide helpers for common stream consumption patterns.
+use std::collections::{HashMap, VecDeque};
+
+use cmd::cmd;
+use connection::ConnectionLike;
+use types::{
+    from_redis_value, ErrorKind, FromRedisValue, RedisResult, RedisWrite, ToRedisArgs, Value,
+};
+
+/// Splits a stream id of the form `<ms>-<seq>` into its two numeric
+/// parts.  An id without a sequence part is treated as having a sequence
+/// number of `0`.  Returns `None` if the id is not a valid stream id.
+pub fn parse_stream_id(id: &str) -> Option<(u64, u64)> {
+    let mut pieces = id.splitn(2, '-');
+    let ms = unwrap_or!(pieces.next().and_then(|x| x.parse().ok()), return None);
+    let seq = match pieces.next() {
+        Some(x) => unwrap_or!(x.parse().ok(), return None),
+        None => 0,
+    };
+    Some((ms, seq))
+}
+
+/// Turns a stream id into an exclusive range bound for `XRANGE` and
+/// `XREVRANGE` by prefixing it with `(`.  Exclusive bounds are supported
+/// by redis 6.2 and later.
+///
+/// ```rust
+/// use redis::ToRedisArgs;
+/// use redis::streams::ExclusiveId;
+///
+/// assert_eq!(ExclusiveId("1526919030474-55").to_redis_args(), vec![b"(1526919030474-55".to_vec()]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExclusiveId<T>(pub T);
+
+impl<T: ToRedisArgs> ToRedisArgs for ExclusiveId<T> {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        for id in self.0.to_redis_args() {
+            let mut arg = Vec::with_capacity(id.len() + 1);
+            arg.push(b'(');
+            arg.extend_from_slice(&id);
+            out.write_arg(&arg);
+        }
+    }
+
+    fn is_single_arg(&self) -> bool {
+        self.0.is_single_arg()
+    }
+}
+
+/// A single entry of a stream as returned by `XREAD` and `XRANGE`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamId {
+    /// The id of the entry in `<ms>-<seq>` format.
+    pub id: String,
+    /// The field/value pairs stored in the entry.
+    pub map: HashMap<String, Value>,
+}
+
+impl StreamId {
+    /// Fetches a field of the entry and converts it into the given type.
+    pub fn get<T: FromRedisValue>(&self, key: &str) -> Option<T> {
+        match self.map.get(key) {
+            Some(x) => from_redis_value(x).ok(),
+            None => None,
+        }
+    }
+
+    /// Returns true if the entry contains the given field.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
+
+    /// Returns the numeric parts of the entry id so that entries can be
+    /// ordered.  Invalid ids sort before all valid ones.
+    pub fn sort_key(&self) -> (u64, u64) {
+        parse_stream_id(&self.id).unwrap_or((0, 0))
+    }
+}
+
+impl FromRedisValue for StreamId {
+    fn from_redis_value(v: &Value) -> RedisResult<StreamId> {
+        match *v {
+            Value::Bulk(ref items) if items.len() == 2 => Ok(StreamId {
+                id: from_redis_value(&items[0])?,
+                map: from_redis_value(&items[1])?,
+            }),
+            _ => invalid_type_error!(v, "Response type not stream entry compatible"),
+        }
+    }
+}
+
+/// The entries read from a single stream key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamKey {
+    /// The name of the stream.
+    pub key: String,
+    /// The entries that were read, in id order.
+    pub ids: Vec<StreamId>,
+}
+
+impl FromRedisValue for StreamKey {
+    fn from_redis_value(v: &Value) -> RedisResult<StreamKey> {
+        match *v {
+            Value::Bulk(ref items) if items.len() == 2 => Ok(StreamKey {
+                key: from_redis_value(&items[0])?,
+                ids: from_redis_value(&items[1])?,
+            }),
+            _ => invalid_type_error!(v, "Response type not stream key compatible"),
+        }
+    }
+}
+
+/// The reply of an `XRANGE` or `XREVRANGE` command.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StreamRangeReply {
+    /// The entries in the order returned by the server.
+    pub ids: Vec<StreamId>,
+}
+
+impl StreamRangeReply {
+    /// Returns the id of the last entry of the reply.  When paginating
+    /// this is the id to continue after.
+    pub fn last_id(&self) -> Option<&str> {
+        self.ids.last().map(|entry| &entry.id[..])
+    }
+}
+
+impl FromRedisValue for StreamRangeReply {
+    fn from_redis_value(v: &Value) -> RedisResult<StreamRangeReply> {
+        Ok(StreamRangeReply {
+            ids: from_redis_value(v)?,
+        })
+    }
+}
+
+/// The reply of an `XREAD` command.  A timed out blocking read yields
+/// an empty reply.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StreamReadReply {
+    /// The streams that had new entries.
+    pub keys: Vec<StreamKey>,
+}
+
+impl FromRedisValue for StreamReadReply {
+    fn from_redis_value(v: &Value) -> RedisResult<StreamReadReply> {
+        match *v {
+            Value::Nil => Ok(StreamReadReply::default()),
+            Value::Bulk(_) => Ok(StreamReadReply {
+                keys: from_redis_value(v)?,
+            }),
+            // RESP3 maps the stream names to their entries
+            Value::Map(ref pairs) => Ok(StreamReadReply {
+                keys: pairs
+                    .iter()
+                    .map(|&(ref key, ref ids)| {
+                        Ok(StreamKey {
+                            key: from_redis_value(key)?,
+                            ids: from_redis_value(ids)?,
+                        })
+                    })
+                    .collect::<RedisResult<_>>()?,
+            }),
+            _ => invalid_type_error!(v, "Response type not stream read compatible"),
+        }
+    }
+}
+
+struct FanInStream {
+    key: String,
+    last_id: String,
+    pending: VecDeque<StreamId>,
+}
+
+/// Reads several streams at once and yields their entries merged in
+/// global id order.
+///
+/// All streams are polled with a single `XREAD` and a cursor is kept for
+/// every stream, so entries that are sharded over several keys can be
+/// processed roughly in the order in which they were added.  The streams
+/// are read again once the entries of the previous read were all handed
+/// out, and the entries of every read are merged in id order.  Entries
+/// that only show up in a later read are returned after those of the
+/// earlier reads even if their id is lower.
+///
+/// Every key may only be given once.
+///
+/// ```rust,no_run
+/// # fn do_something() -> redis::RedisResult<()> {
+/// # let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+/// # let mut con = client.get_connection().unwrap();
+/// use redis::streams::StreamFanIn;
+///
+/// let reader = StreamFanIn::new(&mut con, &["events:0", "events:1"], "0")?.count(100);
+/// for entry in reader {
+///     let (key, entry) = entry?;
+///     println!("{} {}", key, entry.id);
+/// }
+/// # Ok(()) }
+/// ```
+pub struct StreamFanIn<'a, C: ConnectionLike + 'a> {
+    con: &'a mut C,
+    streams: Vec<FanInStream>,
+    count: usize,
+    block: Option<usize>,
+}
+
+impl<'a, C: ConnectionLike> StreamFanIn<'a, C> {
+    /// Creates a reader for the given stream keys that yields all entries
+    /// with an id greater than `start_id` on every stream.  Fails if a key
+    /// is given more than once.
+    pub fn new<K: ToString>(
+        con: &'a mut C,
+        keys: &[K],
+        start_id: &str,
+    ) -> RedisResult<StreamFanIn<'a, C>> {
+        StreamFanIn::with_cursors(
+            con,
+            keys.iter()
+                .map(|key| (key.to_string(), start_id.to_string()))
+                .collect(),
+        )
+    }
+
+    /// Creates a reader that resumes every stream from its own id.  This
+    /// is usually fed with what `cursors` returned for an earlier reader.
+    /// Fails if a key is given more than once.
+    pub fn with_cursors(
+        con: &'a mut C,
+        cursors: Vec<(String, String)>,
+    ) -> RedisResult<StreamFanIn<'a, C>> {
+        for (idx, &(ref key, _)) in cursors.iter().enumerate() {
+            if cursors[..idx].iter().any(|&(ref other, _)| other == key) {
+                fail!((
+                    ErrorKind::InvalidClientConfig,
+                    "Stream key given more than once",
+                    key.clone()
+                ));
+            }
+        }
+        Ok(StreamFanIn {
+            con: con,
+            streams: cursors
+                .into_iter()
+                .map(|(key, last_id)| FanInStream {
+                    key: key,
+                    last_id: last_id,
+                    pending: VecDeque::new(),
+                })
+                .collect(),
+            count: 100,
+            block: None,
+        })
+    }
+
+    /// Sets the maximum number of entries fetched from a stream per read.
+    pub fn count(mut self, count: usize) -> StreamFanIn<'a, C> {
+        self.count = count;
+        self
+    }
+
+    /// Makes the reader block for up to `ms` milliseconds waiting for new
+    /// entries once all streams are drained instead of ending the iteration.
+    pub fn block(mut self, ms: usize) -> StreamFanIn<'a, C> {
+        self.block = Some(ms);
+        self
+    }
+
+    /// Returns the id of the last entry handed out for every stream.
+    pub fn cursors(&self) -> Vec<(&str, &str)> {
+        self.streams
+            .iter()
+            .map(|stream| (&stream.key[..], &stream.last_id[..]))
+            .collect()
+    }
+
+    // Reads all streams, which only happens once all their entries were
+    // handed out.
+    fn fill(&mut self, block: Option<usize>) -> RedisResult<()> {
+        if !self.is_drained() {
+            return Ok(());
+        }
+
+        let mut c = cmd("XREAD");
+        c.arg("COUNT").arg(self.count);
+        if let Some(ms) = block {
+            c.arg("BLOCK").arg(ms);
+        }
+        c.arg("STREAMS");
+        for stream in &self.streams {
+            c.arg(&stream.key);
+        }
+        for stream in &self.streams {
+            c.arg(&stream.last_id);
+        }
+
+        let reply: StreamReadReply = c.query(&mut *self.con)?;
+        for stream_key in reply.keys {
+            match self.streams.iter_mut().find(|s| s.key == stream_key.key) {
+                Some(stream) => stream.pending.extend(stream_key.ids),
+                None => fail!((
+                    ErrorKind::ResponseError,
+                    "XREAD returned entries for an unknown stream",
+                    stream_key.key
+                )),
+            }
+        }
+        Ok(())
+    }
+
+    fn is_drained(&self) -> bool {
+        self.streams.iter().all(|stream| stream.pending.is_empty())
+    }
+
+    /// Fetches the entry with the lowest id across all streams together
+    /// with the key of the stream it came from.  Returns `None` once all
+    /// streams are drained (after blocking, if enabled).
+    pub fn next_entry(&mut self) -> RedisResult<Option<(String, StreamId)>> {
+        self.fill(None)?;
+        if self.is_drained() {
+            match self.block {
+                Some(ms) => self.fill(Some(ms))?,
+                None => return Ok(None),
+            }
+            if self.is_drained() {
+                return Ok(None);
+            }
+        }
+
+        let idx = unwrap_or!(
+            (0..self.streams.len())
+                .filter(|&idx| !self.streams[idx].pending.is_empty())
+                .min_by_key(|&idx| self.streams[idx].pending[0].sort_key()),
+            return Ok(None)
+        );
+        let stream = &mut self.streams[idx];
+        let entry = stream.pending.pop_front().unwrap();
+        stream.last_id = entry.id.clone();
+        Ok(Some((stream.key.clone(), entry)))
+    }
+}
+
+impl<'a, C: ConnectionLike> Iterator for StreamFanIn<'a, C> {
+    type Item = RedisResult<(String, StreamId)>;
+
+    fn next(&mut self) -> Option<RedisResult<(String, StreamId)>> {
+        match self.next_entry() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
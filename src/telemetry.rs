@@ -0,0 +1,131 @@
+//! OpenTelemetry spans for commands (`opentelemetry` feature).
+//!
+//! `TracingInterceptor` is an interceptor of the `middleware` module that
+//! records a client span for every command, with the `db.*` and `net.*`
+//! attributes of the OpenTelemetry semantic conventions.  On a cluster the
+//! peer attributes name the node that served the command:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::middleware::InterceptedConnection;
+//! use redis::telemetry::TracingInterceptor;
+//! use redis::Commands;
+//!
+//! let client = redis::Client::open("redis://127.0.0.1/")?;
+//! let tracing = TracingInterceptor::new(client.get_connection_info());
+//! let mut con = InterceptedConnection::new(client.get_connection()?).with(tracing);
+//! let _: () = con.set("my_key", 42)?;
+//! # Ok(()) }
+//! ```
+//!
+//! Spans are reported to the global tracer provider unless a tracer is
+//! given with `with_tracer`.  The spans describe the commands as they were
+//! sent, after other interceptors rewrote them.
+use std::time::{Duration, SystemTime};
+
+use opentelemetry::api::{Key, KeyValue, Span, SpanKind, StatusCode, Tracer};
+use opentelemetry::global::{self, BoxedTracer};
+
+use connection::{ConnectionAddr, ConnectionInfo};
+use middleware::{Command, Interceptor};
+use types::{RedisResult, Value};
+
+/// Records a span for every command, see the module documentation.
+pub struct TracingInterceptor {
+    tracer: BoxedTracer,
+    db: i64,
+    peer: Option<(String, Option<u16>, &'static str)>,
+    statements: bool,
+}
+
+impl TracingInterceptor {
+    /// Creates an interceptor that reports to the global tracer provider
+    /// and takes the peer attributes from `info`.
+    pub fn new(info: &ConnectionInfo) -> TracingInterceptor {
+        TracingInterceptor {
+            tracer: global::tracer("redis"),
+            db: info.db,
+            peer: peer(&info.addr),
+            statements: false,
+        }
+    }
+
+    /// Reports to the given tracer instead of the global one.
+    pub fn with_tracer(mut self, tracer: BoxedTracer) -> TracingInterceptor {
+        self.tracer = tracer;
+        self
+    }
+
+    /// Records the arguments of the commands in `db.statement`.  They are
+    /// left out by default since they may contain values that should not
+    /// end up in traces.
+    pub fn with_statements(mut self, statements: bool) -> TracingInterceptor {
+        self.statements = statements;
+        self
+    }
+
+    fn attributes(&self, command: &Command, name: &str) -> Vec<KeyValue> {
+        let mut rv = vec![
+            Key::new("db.system").string("redis"),
+            Key::new("db.operation").string(name.to_string()),
+            Key::new("db.redis.database_index").i64(self.db),
+        ];
+        if self.statements {
+            let statement: Vec<String> = command
+                .args()
+                .iter()
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect();
+            rv.push(Key::new("db.statement").string(statement.join(" ")));
+        }
+        let peer = match command.node() {
+            Some(node) => node_peer(node),
+            None => self.peer.clone(),
+        };
+        if let Some((name, port, transport)) = peer {
+            rv.push(Key::new("net.peer.name").string(name));
+            if let Some(port) = port {
+                rv.push(Key::new("net.peer.port").i64(i64::from(port)));
+            }
+            rv.push(Key::new("net.transport").string(transport));
+        }
+        rv
+    }
+}
+
+impl Interceptor for TracingInterceptor {
+    fn after(&mut self, command: &Command, elapsed: Duration, result: &RedisResult<Value>) {
+        let end = SystemTime::now();
+        let name = command.name();
+        let builder = self
+            .tracer
+            .span_builder(&name)
+            .with_kind(SpanKind::Client)
+            .with_start_time(end - elapsed)
+            .with_attributes(self.attributes(command, &name));
+        let builder = match *result {
+            Ok(_) => builder.with_status_code(StatusCode::OK),
+            Err(ref err) => builder
+                .with_status_code(StatusCode::Unknown)
+                .with_status_message(err.to_string()),
+        };
+        self.tracer.build(builder).end_with_timestamp(end);
+    }
+}
+
+fn peer(addr: &ConnectionAddr) -> Option<(String, Option<u16>, &'static str)> {
+    match *addr {
+        ConnectionAddr::Tcp(ref host, port) | ConnectionAddr::TcpTls { ref host, port } => {
+            Some((host.clone(), Some(port), "IP.TCP"))
+        }
+        ConnectionAddr::Unix(ref path) => Some((path.to_string_lossy().into_owned(), None, "Unix")),
+        ConnectionAddr::Sentinel(_) => None,
+    }
+}
+
+// Splits the `host:port` of a cluster node.
+fn node_peer(node: &str) -> Option<(String, Option<u16>, &'static str)> {
+    let idx = node.rfind(':')?;
+    let port = node[idx + 1..].parse().ok();
+    Some((node[..idx].to_string(), port, "IP.TCP"))
+}
@@ -0,0 +1,315 @@
+//! Throwaway servers for integration tests (`test-support` feature).
+//!
+//! A `TestContext` starts a `redis-server` process on a free port or on a
+//! unix socket, waits until it accepts connections and kills it again
+//! when it is dropped, so every test gets a server of its own:
+//!
+//! ```rust,no_run
+//! use redis::test_support::TestContext;
+//! use redis::Commands;
+//!
+//! #[test]
+//! fn test_visits() {
+//!     let ctx = TestContext::new();
+//!     let mut con = ctx.connection();
+//!     let visits: u64 = con.incr("visits", 1).unwrap();
+//!     assert_eq!(visits, 1);
+//! }
+//! ```
+//!
+//! The `redis-server` binary has to be in the `PATH`.  The type of the
+//! server is taken from the `REDISRS_SERVER_TYPE` environment variable,
+//! either `tcp` (the default) or `unix`.
+//!
+//! The feature also enables the `debug_*` commands, which simulate slow
+//! servers and restarts through `DEBUG`.  Redis 7 only allows them with
+//! `enable-debug-command` set, see `RedisServer::with_args`.
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::process;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use net2;
+
+use client::Client;
+use cmd::cmd;
+use connection::{
+    Connection, ConnectionAddr, ConnectionInfo, ProtocolVersion, ReplyLimits, TcpOptions,
+    TlsOptions,
+};
+use types::{from_redis_value, random_u64, FromRedisValue, RedisResult, Value};
+
+#[cfg(feature = "aio")]
+use futures::Future;
+#[cfg(feature = "aio")]
+use types::RedisError;
+
+/// How a key is stored, as returned by `DEBUG OBJECT`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugObject {
+    /// The number of references to the value.
+    pub refcount: u64,
+    /// The internal encoding, like `embstr` or `listpack`.
+    pub encoding: String,
+    /// The size of the value when saved to disk.
+    pub serialized_length: u64,
+    /// The seconds since the key was accessed.
+    pub lru_seconds_idle: u64,
+    /// All fields of the reply, including ones that depend on the
+    /// encoding like `ql_nodes`.
+    pub fields: HashMap<String, String>,
+}
+
+impl FromRedisValue for DebugObject {
+    fn from_redis_value(v: &Value) -> RedisResult<DebugObject> {
+        let text: String = from_redis_value(v)?;
+        let fields: HashMap<String, String> = text
+            .split_whitespace()
+            .filter_map(|field| {
+                let mut parts = field.splitn(2, ':');
+                match (parts.next(), parts.next()) {
+                    (Some(name), Some(value)) => Some((name.to_string(), value.to_string())),
+                    _ => None,
+                }
+            })
+            .collect();
+        let (refcount, serialized_length, lru_seconds_idle) = match (
+            number_field(&fields, "refcount"),
+            number_field(&fields, "serializedlength"),
+            number_field(&fields, "lru_seconds_idle"),
+        ) {
+            (Some(refcount), Some(length), Some(idle)) => (refcount, length, idle),
+            _ => invalid_type_error!(v, "Response type not debug object compatible"),
+        };
+        let encoding = fields.get("encoding").cloned().unwrap_or_default();
+        Ok(DebugObject {
+            refcount,
+            encoding,
+            serialized_length,
+            lru_seconds_idle,
+            fields,
+        })
+    }
+}
+
+fn number_field(fields: &HashMap<String, String>, name: &str) -> Option<u64> {
+    fields.get(name).and_then(|value| value.parse().ok())
+}
+
+/// How a `RedisServer` accepts connections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServerType {
+    /// On a free TCP port of `127.0.0.1`.
+    Tcp,
+    /// On a unix socket in the temporary directory.
+    Unix,
+}
+
+impl ServerType {
+    /// Returns the type named by the `REDISRS_SERVER_TYPE` environment
+    /// variable, `Tcp` if it is not set.
+    ///
+    /// # Panics
+    ///
+    /// If the variable names another type.
+    pub fn from_env() -> ServerType {
+        match env::var("REDISRS_SERVER_TYPE")
+            .ok()
+            .as_ref()
+            .map(|x| &x[..])
+        {
+            Some("tcp") | None => ServerType::Tcp,
+            Some("unix") => ServerType::Unix,
+            val => {
+                panic!("Unknown server type {:?}", val);
+            }
+        }
+    }
+}
+
+/// A `redis-server` process that is killed when dropped.
+pub struct RedisServer {
+    /// The server process.
+    pub process: process::Child,
+    addr: ConnectionAddr,
+}
+
+impl RedisServer {
+    /// Starts a server of the type from the environment, see
+    /// `ServerType::from_env`.
+    ///
+    /// # Panics
+    ///
+    /// If `redis-server` cannot be started.
+    pub fn new() -> RedisServer {
+        RedisServer::with_args(ServerType::from_env(), &[])
+            .unwrap_or_else(|err| panic!("Could not start redis-server: {}", err))
+    }
+
+    /// Starts a server of the given type, passing additional arguments
+    /// like `--appendonly yes` to `redis-server`.
+    pub fn with_args(server_type: ServerType, args: &[&str]) -> io::Result<RedisServer> {
+        let mut cmd = process::Command::new("redis-server");
+        cmd.stdout(process::Stdio::null())
+            .stderr(process::Stdio::null());
+
+        let addr = match server_type {
+            ServerType::Tcp => {
+                // this is technically a race but we can't do better with
+                // the tools that redis gives us :(
+                let listener = net2::TcpBuilder::new_v4()?
+                    .reuse_address(true)?
+                    .bind("127.0.0.1:0")?
+                    .listen(1)?;
+                let server_port = listener.local_addr()?.port();
+                cmd.arg("--port")
+                    .arg(server_port.to_string())
+                    .arg("--bind")
+                    .arg("127.0.0.1");
+                ConnectionAddr::Tcp("127.0.0.1".to_string(), server_port)
+            }
+            ServerType::Unix => {
+                let path = env::temp_dir().join(format!(
+                    "redis-rs-test-{}-{}.sock",
+                    random_u64(),
+                    random_u64()
+                ));
+                cmd.arg("--port").arg("0").arg("--unixsocket").arg(&path);
+                ConnectionAddr::Unix(path)
+            }
+        };
+        cmd.args(args);
+
+        Ok(RedisServer {
+            process: cmd.spawn()?,
+            addr,
+        })
+    }
+
+    /// Waits for the server process to exit.
+    pub fn wait(&mut self) {
+        self.process.wait().unwrap();
+    }
+
+    /// Returns the address the server accepts connections on.
+    pub fn get_client_addr(&self) -> &ConnectionAddr {
+        &self.addr
+    }
+
+    /// Returns the connection info for the server, on database 0.
+    pub fn connection_info(&self) -> ConnectionInfo {
+        ConnectionInfo {
+            addr: Box::new(self.addr.clone()),
+            db: 0,
+            passwd: None,
+            client_name: None,
+            set_lib_info: true,
+            connect_timeout: None,
+            response_timeout: None,
+            tcp: TcpOptions::default(),
+            tls: TlsOptions::default(),
+            fallback_addrs: vec![],
+            shuffle_addrs: false,
+            shuffle_resolved_addrs: false,
+            protocol: ProtocolVersion::default(),
+            reply_limits: ReplyLimits::default(),
+        }
+    }
+
+    /// Kills the server and removes its unix socket.
+    pub fn stop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        if let ConnectionAddr::Unix(ref path) = self.addr {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+impl Default for RedisServer {
+    fn default() -> RedisServer {
+        RedisServer::new()
+    }
+}
+
+impl Drop for RedisServer {
+    fn drop(&mut self) {
+        self.stop()
+    }
+}
+
+/// A running server with a client for it, see the module documentation.
+pub struct TestContext {
+    /// The server, killed when the context is dropped.
+    pub server: RedisServer,
+    /// A client for the server.
+    pub client: Client,
+}
+
+impl TestContext {
+    /// Starts a server of the type from the environment and waits for up
+    /// to 10 seconds until it accepts connections.
+    ///
+    /// # Panics
+    ///
+    /// If the server cannot be started or does not come up in time.
+    pub fn new() -> TestContext {
+        TestContext::with_server(RedisServer::new())
+    }
+
+    /// Waits for up to 10 seconds until the server accepts connections and
+    /// empties its database 0.
+    ///
+    /// # Panics
+    ///
+    /// If the server does not come up in time.
+    pub fn with_server(server: RedisServer) -> TestContext {
+        let client = Client::open(server.connection_info()).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut con = loop {
+            match client.get_connection() {
+                Ok(con) => break con,
+                Err(ref err) if err.is_connection_refusal() && Instant::now() < deadline => {
+                    sleep(Duration::from_millis(1));
+                }
+                Err(err) => panic!("Could not connect: {}", err),
+            }
+        };
+        cmd("FLUSHDB").execute(&mut con);
+
+        TestContext { server, client }
+    }
+
+    /// Opens a new connection to the server.
+    pub fn connection(&self) -> Connection {
+        self.client.get_connection().unwrap()
+    }
+
+    /// Opens a new async connection to the server.
+    #[cfg(feature = "aio")]
+    pub fn async_connection(&self) -> impl Future<Item = ::aio::Connection, Error = RedisError> {
+        self.client.get_async_connection()
+    }
+
+    /// Opens a new multiplexed async connection to the server.
+    #[cfg(feature = "aio")]
+    pub fn multiplexed_async_connection(
+        &self,
+    ) -> impl Future<Item = ::aio::MultiplexedConnection, Error = RedisError> {
+        self.client.get_multiplexed_async_connection()
+    }
+
+    /// Kills the server, for tests of how clients deal with that.
+    pub fn stop_server(&mut self) {
+        self.server.stop();
+    }
+}
+
+impl Default for TestContext {
+    fn default() -> TestContext {
+        TestContext::new()
+    }
+}
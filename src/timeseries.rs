@@ -0,0 +1,497 @@
+//! Options and replies of the RedisTimeSeries module (`with-timeseries`
+//! feature).
+//!
+//! The commands are the `ts_*` methods of `Commands`:
+//!
+//! ```rust,no_run
+//! # fn do_something() -> redis::RedisResult<()> {
+//! use redis::timeseries::{Aggregation, RangeBound, RangeOptions, Sample, SeriesOptions, Timestamp};
+//! use redis::Commands;
+//!
+//! # let client = redis::Client::open("redis://127.0.0.1/")?;
+//! # let mut con = client.get_connection()?;
+//! let options = SeriesOptions::new()
+//!     .retention(24 * 3600 * 1000)
+//!     .label("sensor", "kitchen");
+//! let _: () = con.ts_create("temperature:kitchen", &options)?;
+//! let _: i64 = con.ts_add("temperature:kitchen", Timestamp::Now, 21.5)?;
+//!
+//! let hourly = RangeOptions::new().aggregation(Aggregation::Avg, 3600 * 1000);
+//! let samples: Vec<Sample> =
+//!     con.ts_range("temperature:kitchen", RangeBound::Earliest, RangeBound::Latest, &hourly)?;
+//! # Ok(()) }
+//! ```
+use std::collections::HashMap;
+
+use types::{from_redis_value, FromRedisValue, RedisResult, RedisWrite, ToRedisArgs, Value};
+
+/// The time of a new sample.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timestamp {
+    /// The time of the server (`*`).
+    Now,
+    /// Milliseconds since the epoch.
+    At(i64),
+}
+
+impl ToRedisArgs for Timestamp {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        match *self {
+            Timestamp::Now => out.write_arg(b"*"),
+            Timestamp::At(millis) => millis.write_redis_args(out),
+        }
+    }
+}
+
+/// A bound of the range of `ts_range` and `ts_mrange`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangeBound {
+    /// The first sample (`-`).
+    Earliest,
+    /// The last sample (`+`).
+    Latest,
+    /// Milliseconds since the epoch, inclusive.
+    At(i64),
+}
+
+impl ToRedisArgs for RangeBound {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        match *self {
+            RangeBound::Earliest => out.write_arg(b"-"),
+            RangeBound::Latest => out.write_arg(b"+"),
+            RangeBound::At(millis) => millis.write_redis_args(out),
+        }
+    }
+}
+
+/// What happens when a sample is added for a time that has one already.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Fails the command (`BLOCK`), the default.
+    Block,
+    /// Keeps the existing sample (`FIRST`).
+    First,
+    /// Replaces the existing sample (`LAST`).
+    Last,
+    /// Keeps the smaller value (`MIN`).
+    Min,
+    /// Keeps the larger value (`MAX`).
+    Max,
+    /// Adds the values (`SUM`).
+    Sum,
+}
+
+impl ToRedisArgs for DuplicatePolicy {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            DuplicatePolicy::Block => &b"BLOCK"[..],
+            DuplicatePolicy::First => &b"FIRST"[..],
+            DuplicatePolicy::Last => &b"LAST"[..],
+            DuplicatePolicy::Min => &b"MIN"[..],
+            DuplicatePolicy::Max => &b"MAX"[..],
+            DuplicatePolicy::Sum => &b"SUM"[..],
+        })
+    }
+}
+
+/// How the samples of a series are stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Compressed chunks (`COMPRESSED`), the default.
+    Compressed,
+    /// Raw samples (`UNCOMPRESSED`).
+    Uncompressed,
+}
+
+/// The options of a series, for `ts_create`, `ts_alter` and for the
+/// series `ts_add_options` creates.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SeriesOptions {
+    retention: Option<u64>,
+    encoding: Option<Encoding>,
+    chunk_size: Option<usize>,
+    duplicate_policy: Option<DuplicatePolicy>,
+    on_duplicate: Option<DuplicatePolicy>,
+    labels: Vec<(String, String)>,
+}
+
+impl SeriesOptions {
+    /// Creates options that keep samples forever and have no labels.
+    pub fn new() -> SeriesOptions {
+        Default::default()
+    }
+
+    /// Drops samples older than `millis` relative to the latest one
+    /// (`RETENTION`).
+    pub fn retention(mut self, millis: u64) -> SeriesOptions {
+        self.retention = Some(millis);
+        self
+    }
+
+    /// Sets how samples are stored (`ENCODING`).  Only when the series
+    /// is created.
+    pub fn encoding(mut self, encoding: Encoding) -> SeriesOptions {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// Sets the size of the memory chunks in bytes (`CHUNK_SIZE`).
+    pub fn chunk_size(mut self, bytes: usize) -> SeriesOptions {
+        self.chunk_size = Some(bytes);
+        self
+    }
+
+    /// Sets the policy of the series for samples with the same time
+    /// (`DUPLICATE_POLICY`).
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> SeriesOptions {
+        self.duplicate_policy = Some(policy);
+        self
+    }
+
+    /// Overrides the policy of the series for a single `ts_add_options`
+    /// (`ON_DUPLICATE`).
+    pub fn on_duplicate(mut self, policy: DuplicatePolicy) -> SeriesOptions {
+        self.on_duplicate = Some(policy);
+        self
+    }
+
+    /// Adds a label, by which `ts_mrange` selects series (`LABELS`).
+    /// With `ts_alter` the labels replace all existing ones.
+    pub fn label(mut self, name: &str, value: &str) -> SeriesOptions {
+        self.labels.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+impl ToRedisArgs for SeriesOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if let Some(retention) = self.retention {
+            out.write_arg(b"RETENTION");
+            retention.write_redis_args(out);
+        }
+        if let Some(encoding) = self.encoding {
+            out.write_arg(b"ENCODING");
+            out.write_arg(match encoding {
+                Encoding::Compressed => &b"COMPRESSED"[..],
+                Encoding::Uncompressed => &b"UNCOMPRESSED"[..],
+            });
+        }
+        if let Some(chunk_size) = self.chunk_size {
+            out.write_arg(b"CHUNK_SIZE");
+            chunk_size.write_redis_args(out);
+        }
+        if let Some(policy) = self.duplicate_policy {
+            out.write_arg(b"DUPLICATE_POLICY");
+            policy.write_redis_args(out);
+        }
+        if let Some(policy) = self.on_duplicate {
+            out.write_arg(b"ON_DUPLICATE");
+            policy.write_redis_args(out);
+        }
+        if !self.labels.is_empty() {
+            out.write_arg(b"LABELS");
+            for &(ref name, ref value) in &self.labels {
+                name.write_redis_args(out);
+                value.write_redis_args(out);
+            }
+        }
+    }
+}
+
+/// The function that combines the samples of a bucket or, for the
+/// `GROUPBY` of `ts_mrange`, of a group of series.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregation {
+    /// The arithmetic mean (`avg`).
+    Avg,
+    /// The sum (`sum`).
+    Sum,
+    /// The smallest value (`min`).
+    Min,
+    /// The largest value (`max`).
+    Max,
+    /// The difference between the largest and the smallest value
+    /// (`range`).
+    Range,
+    /// The number of samples (`count`).
+    Count,
+    /// The value of the earliest sample (`first`).
+    First,
+    /// The value of the latest sample (`last`).
+    Last,
+    /// The population standard deviation (`std.p`).
+    StdP,
+    /// The sample standard deviation (`std.s`).
+    StdS,
+    /// The population variance (`var.p`).
+    VarP,
+    /// The sample variance (`var.s`).
+    VarS,
+    /// The time-weighted average (`twa`).  Not for `GROUPBY`.
+    Twa,
+}
+
+impl ToRedisArgs for Aggregation {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(match *self {
+            Aggregation::Avg => &b"avg"[..],
+            Aggregation::Sum => &b"sum"[..],
+            Aggregation::Min => &b"min"[..],
+            Aggregation::Max => &b"max"[..],
+            Aggregation::Range => &b"range"[..],
+            Aggregation::Count => &b"count"[..],
+            Aggregation::First => &b"first"[..],
+            Aggregation::Last => &b"last"[..],
+            Aggregation::StdP => &b"std.p"[..],
+            Aggregation::StdS => &b"std.s"[..],
+            Aggregation::VarP => &b"var.p"[..],
+            Aggregation::VarS => &b"var.s"[..],
+            Aggregation::Twa => &b"twa"[..],
+        })
+    }
+}
+
+/// The options of `ts_range` and `ts_revrange`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RangeOptions {
+    filter_by_ts: Vec<i64>,
+    filter_by_value: Option<(f64, f64)>,
+    count: Option<usize>,
+    aggregation: Option<(Aggregation, u64)>,
+    empty: bool,
+}
+
+impl RangeOptions {
+    /// Creates options that return all samples of the range.
+    pub fn new() -> RangeOptions {
+        Default::default()
+    }
+
+    /// Only returns the samples at these times (`FILTER_BY_TS`).
+    pub fn filter_by_ts(mut self, timestamps: &[i64]) -> RangeOptions {
+        self.filter_by_ts = timestamps.to_vec();
+        self
+    }
+
+    /// Only returns samples with values between `min` and `max`,
+    /// inclusive (`FILTER_BY_VALUE`).
+    pub fn filter_by_value(mut self, min: f64, max: f64) -> RangeOptions {
+        self.filter_by_value = Some((min, max));
+        self
+    }
+
+    /// Returns at most `count` samples or buckets (`COUNT`).
+    pub fn count(mut self, count: usize) -> RangeOptions {
+        self.count = Some(count);
+        self
+    }
+
+    /// Combines the samples in buckets of `bucket_millis` milliseconds
+    /// (`AGGREGATION`).  A bucket is reported at its start.
+    pub fn aggregation(mut self, aggregation: Aggregation, bucket_millis: u64) -> RangeOptions {
+        self.aggregation = Some((aggregation, bucket_millis));
+        self
+    }
+
+    /// Also reports buckets without samples (`EMPTY`).  Only with an
+    /// aggregation.
+    pub fn empty(mut self) -> RangeOptions {
+        self.empty = true;
+        self
+    }
+}
+
+impl ToRedisArgs for RangeOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        if !self.filter_by_ts.is_empty() {
+            out.write_arg(b"FILTER_BY_TS");
+            for timestamp in &self.filter_by_ts {
+                timestamp.write_redis_args(out);
+            }
+        }
+        if let Some((min, max)) = self.filter_by_value {
+            out.write_arg(b"FILTER_BY_VALUE");
+            min.write_redis_args(out);
+            max.write_redis_args(out);
+        }
+        if let Some(count) = self.count {
+            out.write_arg(b"COUNT");
+            count.write_redis_args(out);
+        }
+        if let Some((aggregation, bucket)) = self.aggregation {
+            out.write_arg(b"AGGREGATION");
+            aggregation.write_redis_args(out);
+            bucket.write_redis_args(out);
+            if self.empty {
+                out.write_arg(b"EMPTY");
+            }
+        }
+    }
+}
+
+/// The options of `ts_mrange`, which selects series by their labels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MRangeOptions {
+    range: RangeOptions,
+    filters: Vec<String>,
+    with_labels: bool,
+    selected_labels: Vec<String>,
+    group_by: Option<(String, Aggregation)>,
+}
+
+impl MRangeOptions {
+    /// Creates options for the series that match all `filters`, like
+    /// `sensor=kitchen`, `sensor!=`, or `room=(kitchen,hall)`.  At least
+    /// one filter has to be of the `label=value` kind.
+    pub fn new(filters: &[&str]) -> MRangeOptions {
+        MRangeOptions {
+            range: RangeOptions::new(),
+            filters: filters.iter().map(|filter| filter.to_string()).collect(),
+            with_labels: false,
+            selected_labels: vec![],
+            group_by: None,
+        }
+    }
+
+    /// Applies the range options to each series.
+    pub fn range(mut self, range: RangeOptions) -> MRangeOptions {
+        self.range = range;
+        self
+    }
+
+    /// Reports all labels of each series (`WITHLABELS`).
+    pub fn with_labels(mut self) -> MRangeOptions {
+        self.with_labels = true;
+        self
+    }
+
+    /// Reports only these labels of each series (`SELECTED_LABELS`).
+    pub fn selected_labels(mut self, labels: &[&str]) -> MRangeOptions {
+        self.selected_labels = labels.iter().map(|label| label.to_string()).collect();
+        self
+    }
+
+    /// Combines the series with the same value of `label` into one,
+    /// sample by sample (`GROUPBY`).
+    pub fn group_by(mut self, label: &str, reducer: Aggregation) -> MRangeOptions {
+        self.group_by = Some((label.to_string(), reducer));
+        self
+    }
+}
+
+impl ToRedisArgs for MRangeOptions {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        self.range.write_redis_args(out);
+        if self.with_labels {
+            out.write_arg(b"WITHLABELS");
+        } else if !self.selected_labels.is_empty() {
+            out.write_arg(b"SELECTED_LABELS");
+            self.selected_labels.write_redis_args(out);
+        }
+        // the filters run to the end of the command or to `GROUPBY`
+        out.write_arg(b"FILTER");
+        self.filters.write_redis_args(out);
+        if let Some((ref label, reducer)) = self.group_by {
+            out.write_arg(b"GROUPBY");
+            label.write_redis_args(out);
+            out.write_arg(b"REDUCE");
+            reducer.write_redis_args(out);
+        }
+    }
+}
+
+/// A sample of a series, as returned by `ts_range`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    /// Milliseconds since the epoch.
+    pub timestamp: i64,
+    /// The value.
+    pub value: f64,
+}
+
+impl FromRedisValue for Sample {
+    fn from_redis_value(v: &Value) -> RedisResult<Sample> {
+        let (timestamp, value) = from_redis_value(v)?;
+        Ok(Sample { timestamp, value })
+    }
+}
+
+/// A series with its samples, as returned by `ts_mrange` into a
+/// `Vec<Series>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Series {
+    /// The key, or `label=value` of the group with `GROUPBY`.
+    pub key: String,
+    /// The labels, empty unless requested with `with_labels` or
+    /// `selected_labels`.
+    pub labels: HashMap<String, String>,
+    /// The samples in the range.
+    pub samples: Vec<Sample>,
+}
+
+impl FromRedisValue for Series {
+    fn from_redis_value(v: &Value) -> RedisResult<Series> {
+        match *v {
+            Value::Bulk(ref items) if items.len() >= 3 => {
+                series(&items[0], &items[1], &items[items.len() - 1])
+            }
+            _ => invalid_type_error!(v, "Response type not time series compatible"),
+        }
+    }
+
+    fn from_redis_values(items: &[Value]) -> RedisResult<Vec<Series>> {
+        // RESP3 replies with a map from the keys to the labels, the
+        // reducers or aggregators and the samples, which arrives here
+        // flattened into keys and values
+        let flattened = match items.first() {
+            Some(&Value::Bulk(_)) | None => false,
+            Some(_) => true,
+        };
+        if !flattened {
+            return items.iter().map(from_redis_value).collect();
+        }
+        items
+            .chunks(2)
+            .map(|pair| match (&pair[0], pair.get(1)) {
+                (key, Some(&Value::Bulk(ref rest))) if rest.len() >= 2 => {
+                    series(key, &rest[0], &rest[rest.len() - 1])
+                }
+                _ => invalid_type_error!(pair, "Response type not time series compatible"),
+            })
+            .collect()
+    }
+}
+
+fn series(key: &Value, labels: &Value, samples: &Value) -> RedisResult<Series> {
+    // labels missing from a series are nil with `SELECTED_LABELS`
+    let labels: Vec<(String, Option<String>)> = from_redis_value(labels)?;
+    Ok(Series {
+        key: from_redis_value(key)?,
+        labels: labels
+            .into_iter()
+            .filter_map(|(name, value)| value.map(|value| (name, value)))
+            .collect(),
+        samples: from_redis_value(samples)?,
+    })
+}
@@ -1,17 +1,26 @@
+use std::collections::hash_map::RandomState;
 use std::collections::{BTreeMap, BTreeSet};
 use std::collections::{HashMap, HashSet};
 use std::convert::From;
 use std::default::Default;
 use std::error;
 use std::fmt;
-use std::hash::{BuildHasher, Hash};
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::io;
-use std::str::{from_utf8, Utf8Error};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+#[cfg(feature = "with-nonzero")]
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize};
+use std::path::{Path, PathBuf};
+use std::str::{from_utf8, FromStr, Utf8Error};
 
 use futures::Future;
 
+#[cfg(feature = "json")]
+use serde_json;
 #[cfg(feature = "with-rustc-json")]
 use serialize::json;
+#[cfg(feature = "with-uuid")]
+use uuid::Uuid;
 
 /// Helper enum that is used in some situations to describe
 /// the behavior of arguments in a numeric context.
@@ -47,10 +56,95 @@ pub enum ErrorKind {
     /// An extension error.  This is an error created by the server
     /// that is not directly understood by the library.
     ExtensionError,
+    /// The operation was run against a key holding the wrong kind of value
+    /// (`WRONGTYPE`).
+    WrongType,
+    /// The consumer group or the stream does not exist (`NOGROUP`).
+    NoGroup,
+    /// The consumer group already exists (`BUSYGROUP`).
+    BusyGroup,
+    /// The server ran out of memory (`OOM`).
+    OutOfMemory,
+    /// The server is busy running a script or function (`BUSY`).
+    Busy,
+    /// A write was sent to a read only replica (`READONLY`).
+    ReadOnly,
+    /// The replica lost its link with the master (`MASTERDOWN`).
+    MasterDown,
+    /// The user lacks the permissions for the command (`NOPERM`).
+    NoPermission,
+    /// The key is served by another cluster node (`MOVED`).
+    Moved,
+    /// The key is being migrated to another cluster node (`ASK`).
+    Ask,
+    /// The command should be retried shortly, for instance because a slot
+    /// is being migrated (`TRYAGAIN`).
+    TryAgain,
+    /// The cluster is down (`CLUSTERDOWN`).
+    ClusterDown,
+    /// The keys of a command do not hash to the same slot (`CROSSSLOT`).
+    CrossSlot,
+    /// A reply exceeds the `ReplyLimits` of the connection.  The rest of
+    /// the reply is not read, so the connection is closed.
+    ReplyTooLarge,
+    /// Messages were dropped because the buffer of an async `PubSub` was
+    /// full, see `OverflowPolicy::Error`.
+    BufferFull,
+}
+
+impl ErrorKind {
+    /// Returns the kind of server error for the given error code, which is
+    /// the first word of an error reply.  Returns `None` for codes that are
+    /// not known to the library.
+    pub fn from_code(code: &str) -> Option<ErrorKind> {
+        Some(match code {
+            "ERR" => ErrorKind::ResponseError,
+            "EXECABORT" => ErrorKind::ExecAbortError,
+            "LOADING" => ErrorKind::BusyLoadingError,
+            "NOSCRIPT" => ErrorKind::NoScriptError,
+            "WRONGTYPE" => ErrorKind::WrongType,
+            "NOGROUP" => ErrorKind::NoGroup,
+            "BUSYGROUP" => ErrorKind::BusyGroup,
+            "OOM" => ErrorKind::OutOfMemory,
+            "BUSY" => ErrorKind::Busy,
+            "READONLY" => ErrorKind::ReadOnly,
+            "MASTERDOWN" => ErrorKind::MasterDown,
+            "NOPERM" => ErrorKind::NoPermission,
+            "MOVED" => ErrorKind::Moved,
+            "ASK" => ErrorKind::Ask,
+            "TRYAGAIN" => ErrorKind::TryAgain,
+            "CLUSTERDOWN" => ErrorKind::ClusterDown,
+            "CROSSSLOT" => ErrorKind::CrossSlot,
+            _ => return None,
+        })
+    }
+
+    // The inverse of `from_code` for kinds that only the server produces.
+    fn code(&self) -> Option<&'static str> {
+        Some(match *self {
+            ErrorKind::ExecAbortError => "EXECABORT",
+            ErrorKind::BusyLoadingError => "LOADING",
+            ErrorKind::NoScriptError => "NOSCRIPT",
+            ErrorKind::WrongType => "WRONGTYPE",
+            ErrorKind::NoGroup => "NOGROUP",
+            ErrorKind::BusyGroup => "BUSYGROUP",
+            ErrorKind::OutOfMemory => "OOM",
+            ErrorKind::Busy => "BUSY",
+            ErrorKind::ReadOnly => "READONLY",
+            ErrorKind::MasterDown => "MASTERDOWN",
+            ErrorKind::NoPermission => "NOPERM",
+            ErrorKind::Moved => "MOVED",
+            ErrorKind::Ask => "ASK",
+            ErrorKind::TryAgain => "TRYAGAIN",
+            ErrorKind::ClusterDown => "CLUSTERDOWN",
+            ErrorKind::CrossSlot => "CROSSSLOT",
+            _ => return None,
+        })
+    }
 }
 
 /// Internal low-level redis value enum.
-#[derive(PartialEq, Eq, Clone)]
+#[derive(PartialEq, Clone)]
 pub enum Value {
     /// A nil response from the server.
     Nil,
@@ -68,6 +162,188 @@ pub enum Value {
     Status(String),
     /// A status response which represents the string "OK".
     Okay,
+    /// A map of keys to values (RESP3).
+    Map(Vec<(Value, Value)>),
+    /// An unordered collection of distinct values (RESP3).
+    Set(Vec<Value>),
+    /// A floating point number (RESP3).
+    Double(f64),
+    /// A boolean (RESP3).
+    Boolean(bool),
+    /// An integer that does not fit into 64 bits, in decimal (RESP3).
+    BigNumber(String),
+    /// A string with a hint how it is formatted, for instance `txt` for
+    /// plain text or `mkd` for markdown (RESP3).
+    VerbatimString {
+        /// The three letter format of the text.
+        format: String,
+        /// The text itself.
+        text: String,
+    },
+    /// An out-of-band message of the server (RESP3), like an invalidation
+    /// of client side caching or a pub/sub message.  Connections hand
+    /// these to their push sender instead of returning them as replies.
+    Push {
+        /// The kind of message.
+        kind: PushKind,
+        /// The items that follow the kind.
+        data: Vec<Value>,
+    },
+}
+
+/// The kind of a RESP3 push message.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum PushKind {
+    /// Keys cached with `CLIENT TRACKING` were modified.
+    Invalidate,
+    /// A message of a subscribed channel.
+    Message,
+    /// A message of a subscribed pattern.
+    PMessage,
+    /// A message of a subscribed shard channel.
+    SMessage,
+    /// Confirms `SUBSCRIBE`.
+    Subscribe,
+    /// Confirms `PSUBSCRIBE`.
+    PSubscribe,
+    /// Confirms `SSUBSCRIBE`.
+    SSubscribe,
+    /// Confirms `UNSUBSCRIBE`.
+    Unsubscribe,
+    /// Confirms `PUNSUBSCRIBE`.
+    PUnsubscribe,
+    /// Confirms `SUNSUBSCRIBE`.
+    SUnsubscribe,
+    /// A kind unknown to the library.
+    Other(String),
+}
+
+impl PushKind {
+    /// Returns the kind with the given name, for instance `invalidate`.
+    pub fn from_name(name: &str) -> PushKind {
+        match name {
+            "invalidate" => PushKind::Invalidate,
+            "message" => PushKind::Message,
+            "pmessage" => PushKind::PMessage,
+            "smessage" => PushKind::SMessage,
+            "subscribe" => PushKind::Subscribe,
+            "psubscribe" => PushKind::PSubscribe,
+            "ssubscribe" => PushKind::SSubscribe,
+            "unsubscribe" => PushKind::Unsubscribe,
+            "punsubscribe" => PushKind::PUnsubscribe,
+            "sunsubscribe" => PushKind::SUnsubscribe,
+            other => PushKind::Other(other.to_string()),
+        }
+    }
+
+    /// Returns the name the server uses for the kind.
+    pub fn name(&self) -> &str {
+        match *self {
+            PushKind::Invalidate => "invalidate",
+            PushKind::Message => "message",
+            PushKind::PMessage => "pmessage",
+            PushKind::SMessage => "smessage",
+            PushKind::Subscribe => "subscribe",
+            PushKind::PSubscribe => "psubscribe",
+            PushKind::SSubscribe => "ssubscribe",
+            PushKind::Unsubscribe => "unsubscribe",
+            PushKind::PUnsubscribe => "punsubscribe",
+            PushKind::SUnsubscribe => "sunsubscribe",
+            PushKind::Other(ref name) => name,
+        }
+    }
+}
+
+/// A push message that a connection received, see `Value::Push`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct PushInfo {
+    /// The kind of message.
+    pub kind: PushKind,
+    /// The items that follow the kind.
+    pub data: Vec<Value>,
+}
+
+/// A value that borrows its strings from the buffer it was parsed from,
+/// see `parse_redis_value_ref` and `resp::Reply`.  The variants are those
+/// of `Value`.
+///
+/// Parsing into a `ValueRef` does not allocate for strings, which makes
+/// a difference for large replies like stream batches.  Use `to_value` to
+/// keep a value beyond the lifetime of the buffer.
+#[derive(PartialEq, Clone, Debug)]
+pub enum ValueRef<'a> {
+    /// A nil response from the server.
+    Nil,
+    /// An integer response.
+    Int(i64),
+    /// Arbitrary binary data.
+    Data(&'a [u8]),
+    /// A bulk response of more data.
+    Bulk(Vec<ValueRef<'a>>),
+    /// A status response.
+    Status(&'a str),
+    /// A status response which represents the string "OK".
+    Okay,
+    /// A map of keys to values (RESP3).
+    Map(Vec<(ValueRef<'a>, ValueRef<'a>)>),
+    /// An unordered collection of distinct values (RESP3).
+    Set(Vec<ValueRef<'a>>),
+    /// A floating point number (RESP3).
+    Double(f64),
+    /// A boolean (RESP3).
+    Boolean(bool),
+    /// An integer that does not fit into 64 bits, in decimal (RESP3).
+    BigNumber(&'a str),
+    /// A string with a hint how it is formatted (RESP3).
+    VerbatimString {
+        /// The three letter format of the text.
+        format: &'a str,
+        /// The text itself.
+        text: &'a str,
+    },
+    /// An out-of-band message of the server (RESP3).
+    Push {
+        /// The kind of message.
+        kind: PushKind,
+        /// The items that follow the kind.
+        data: Vec<ValueRef<'a>>,
+    },
+}
+
+impl<'a> ValueRef<'a> {
+    /// Copies the value into an owned `Value`.
+    pub fn to_value(&self) -> Value {
+        fn to_values(values: &[ValueRef]) -> Vec<Value> {
+            values.iter().map(ValueRef::to_value).collect()
+        }
+
+        match *self {
+            ValueRef::Nil => Value::Nil,
+            ValueRef::Int(val) => Value::Int(val),
+            ValueRef::Data(val) => Value::Data(val.to_vec()),
+            ValueRef::Bulk(ref values) => Value::Bulk(to_values(values)),
+            ValueRef::Status(val) => Value::Status(val.to_string()),
+            ValueRef::Okay => Value::Okay,
+            ValueRef::Map(ref pairs) => Value::Map(
+                pairs
+                    .iter()
+                    .map(|&(ref key, ref val)| (key.to_value(), val.to_value()))
+                    .collect(),
+            ),
+            ValueRef::Set(ref values) => Value::Set(to_values(values)),
+            ValueRef::Double(val) => Value::Double(val),
+            ValueRef::Boolean(val) => Value::Boolean(val),
+            ValueRef::BigNumber(val) => Value::BigNumber(val.to_string()),
+            ValueRef::VerbatimString { format, text } => Value::VerbatimString {
+                format: format.to_string(),
+                text: text.to_string(),
+            },
+            ValueRef::Push { ref kind, ref data } => Value::Push {
+                kind: kind.clone(),
+                data: to_values(data),
+            },
+        }
+    }
 }
 
 /// Values are generally not used directly unless you are using the
@@ -77,6 +353,11 @@ pub enum Value {
 /// While on the redis protocol there is an error type this is already
 /// separated at an early point so the value only holds the remaining
 /// types.
+///
+/// The RESP3 types convert to the same Rust types as the values a RESP2
+/// connection gets for the same command: a `Map` converts to a `HashMap`
+/// just like a bulk of keys and values, a `Double` to an `f64` just like
+/// its textual form and so on.
 impl Value {
     /// Checks if the return value looks like it fulfils the cursor
     /// protocol.  That means the result is a bulk item of length
@@ -132,6 +413,44 @@ impl fmt::Debug for Value {
             }
             Value::Okay => write!(fmt, "ok"),
             Value::Status(ref s) => write!(fmt, "status({:?})", s),
+            Value::Map(ref pairs) => {
+                write!(fmt, "map(")?;
+                let mut is_first = true;
+                for &(ref key, ref val) in pairs.iter() {
+                    if !is_first {
+                        write!(fmt, ", ")?;
+                    }
+                    write!(fmt, "{:?}: {:?}", key, val)?;
+                    is_first = false;
+                }
+                write!(fmt, ")")
+            }
+            Value::Set(ref values) => {
+                write!(fmt, "set(")?;
+                let mut is_first = true;
+                for val in values.iter() {
+                    if !is_first {
+                        write!(fmt, ", ")?;
+                    }
+                    write!(fmt, "{:?}", val)?;
+                    is_first = false;
+                }
+                write!(fmt, ")")
+            }
+            Value::Double(val) => write!(fmt, "double({:?})", val),
+            Value::Boolean(val) => write!(fmt, "boolean({:?})", val),
+            Value::BigNumber(ref val) => write!(fmt, "big-number({})", val),
+            Value::VerbatimString {
+                ref format,
+                ref text,
+            } => write!(fmt, "verbatim-string({}, {:?})", format, text),
+            Value::Push { ref kind, ref data } => {
+                write!(fmt, "push({}", kind.name())?;
+                for val in data.iter() {
+                    write!(fmt, ", {:?}", val)?;
+                }
+                write!(fmt, ")")
+            }
         }
     }
 }
@@ -177,6 +496,19 @@ impl From<io::Error> for RedisError {
     }
 }
 
+#[cfg(feature = "tls-native")]
+impl From<::native_tls::Error> for RedisError {
+    fn from(err: ::native_tls::Error) -> RedisError {
+        RedisError {
+            repr: ErrorRepr::WithDescriptionAndDetail(
+                ErrorKind::IoError,
+                "TLS error",
+                err.to_string(),
+            ),
+        }
+    }
+}
+
 impl From<Utf8Error> for RedisError {
     fn from(_: Utf8Error) -> RedisError {
         RedisError {
@@ -268,6 +600,80 @@ impl RedisError {
             ErrorKind::InvalidClientConfig => "invalid client config",
             ErrorKind::IoError => "I/O error",
             ErrorKind::ExtensionError => "extension error",
+            ErrorKind::WrongType => "wrong type",
+            ErrorKind::NoGroup => "no such group",
+            ErrorKind::BusyGroup => "group already exists",
+            ErrorKind::OutOfMemory => "out of memory",
+            ErrorKind::Busy => "busy",
+            ErrorKind::ReadOnly => "read only",
+            ErrorKind::MasterDown => "master down",
+            ErrorKind::NoPermission => "no permission",
+            ErrorKind::Moved => "key moved",
+            ErrorKind::Ask => "key being migrated",
+            ErrorKind::TryAgain => "try again",
+            ErrorKind::ClusterDown => "cluster down",
+            ErrorKind::CrossSlot => "cross slot",
+            ErrorKind::ReplyTooLarge => "reply too large",
+            ErrorKind::BufferFull => "buffer full",
+        }
+    }
+
+    /// Returns the error code sent by the server, like `WRONGTYPE`, for
+    /// errors other than generic `ERR` replies.  Returns `None` for errors
+    /// that did not come from the server.
+    pub fn code(&self) -> Option<&str> {
+        match self.repr {
+            ErrorRepr::ExtensionError(ref code, _) => Some(code),
+            _ => self.kind().code(),
+        }
+    }
+
+    /// Returns true if the command certainly was not executed because of a
+    /// transient condition of the server or cluster, so sending it again
+    /// later may succeed.  This is the case while the server is loading
+    /// its dataset or busy running a script, during cluster reconfiguration
+    /// and if the connection could not be established at all.  Errors that
+    /// happen after a command was sent, like timeouts, are not considered
+    /// retryable since the command may already have been executed.
+    ///
+    /// Cluster redirects are not retryable either, as sending the command
+    /// to the same node again gets the same reply.  Use
+    /// `is_cluster_redirect` to find out whether to follow them.
+    pub fn is_retryable(&self) -> bool {
+        match self.kind() {
+            ErrorKind::BusyLoadingError
+            | ErrorKind::Busy
+            | ErrorKind::ReadOnly
+            | ErrorKind::MasterDown
+            | ErrorKind::TryAgain
+            | ErrorKind::ClusterDown => true,
+            ErrorKind::IoError => self.is_connection_refusal(),
+            _ => false,
+        }
+    }
+
+    /// Returns true if the server redirected the command to another
+    /// cluster node with `MOVED` or `ASK`.
+    pub fn is_cluster_redirect(&self) -> bool {
+        match self.kind() {
+            ErrorKind::Moved | ErrorKind::Ask => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the node (`host:port`) and the slot a `MOVED` or `ASK`
+    /// error redirects to.
+    pub fn redirect_node(&self) -> Option<(&str, u16)> {
+        if !self.is_cluster_redirect() {
+            return None;
+        }
+        match self.repr {
+            ErrorRepr::WithDescriptionAndDetail(_, _, ref detail) => {
+                let mut pieces = detail.split(' ');
+                let slot = pieces.next()?.parse().ok()?;
+                Some((pieces.next()?, slot))
+            }
+            _ => None,
         }
     }
 
@@ -326,15 +732,40 @@ impl RedisError {
         }
     }
 
-    /// Returns the extension error code
+    /// Returns the extension error code.  This includes the codes of
+    /// server errors that have their own `ErrorKind`, like `MOVED` or
+    /// `WRONGTYPE`, as they used to be reported as extension errors.
     pub fn extension_error_code(&self) -> Option<&str> {
+        match self.kind() {
+            ErrorKind::ExecAbortError | ErrorKind::BusyLoadingError | ErrorKind::NoScriptError => {
+                None
+            }
+            _ => self.code(),
+        }
+    }
+
+    /// Returns true if the error is an error reply of the server, like
+    /// `WRONGTYPE`, as opposed to an I/O error or a reply that could not
+    /// be converted.  The connection is still usable after such an error.
+    pub fn is_server_error(&self) -> bool {
         match self.repr {
-            ErrorRepr::ExtensionError(ref code, _) => Some(&code),
-            _ => None,
+            ErrorRepr::WithDescription(_, desc)
+            | ErrorRepr::WithDescriptionAndDetail(_, desc, _) => desc == SERVER_ERROR_DESC,
+            ErrorRepr::ExtensionError(_, _) => true,
+            ErrorRepr::IoError(_) => false,
         }
     }
 }
 
+const SERVER_ERROR_DESC: &str = "An error was signalled by the server";
+
+pub fn make_server_error(kind: ErrorKind, detail: Option<&str>) -> RedisError {
+    match detail {
+        Some(detail) => RedisError::from((kind, SERVER_ERROR_DESC, detail.to_string())),
+        None => RedisError::from((kind, SERVER_ERROR_DESC)),
+    }
+}
+
 pub fn make_extension_error(code: &str, detail: Option<&str>) -> RedisError {
     RedisError {
         repr: ErrorRepr::ExtensionError(
@@ -347,6 +778,31 @@ pub fn make_extension_error(code: &str, detail: Option<&str>) -> RedisError {
     }
 }
 
+/// Creates a copy of an error.  As IO errors cannot be cloned only their
+/// kind and message are carried over for them.
+pub fn clone_error(err: &RedisError) -> RedisError {
+    RedisError {
+        repr: match err.repr {
+            ErrorRepr::WithDescription(kind, desc) => ErrorRepr::WithDescription(kind, desc),
+            ErrorRepr::WithDescriptionAndDetail(kind, desc, ref detail) => {
+                ErrorRepr::WithDescriptionAndDetail(kind, desc, detail.clone())
+            }
+            ErrorRepr::ExtensionError(ref code, ref detail) => {
+                ErrorRepr::ExtensionError(code.clone(), detail.clone())
+            }
+            ErrorRepr::IoError(ref err) => {
+                ErrorRepr::IoError(io::Error::new(err.kind(), err.to_string()))
+            }
+        },
+    }
+}
+
+/// Returns a random number.  `RandomState` is seeded randomly, which is good
+/// enough for jitter and load balancing but nothing else.
+pub fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
 /// Library generic result type.
 pub type RedisResult<T> = Result<T, RedisError>;
 
@@ -425,6 +881,88 @@ impl RedisWrite for Vec<Vec<u8>> {
     }
 }
 
+/// Collects arguments in a single buffer instead of allocating a vector
+/// for each of them like `to_redis_args` does.
+///
+/// ```rust
+/// use redis::{ArgBuffer, ToRedisArgs};
+///
+/// let mut args = ArgBuffer::new();
+/// ("key", 42).write_redis_args(&mut args);
+/// assert_eq!(args.len(), 2);
+/// assert_eq!(args.iter().collect::<Vec<_>>(), vec![&b"key"[..], &b"42"[..]]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArgBuffer {
+    data: Vec<u8>,
+    // the offset in `data` where each argument ends
+    ends: Vec<usize>,
+}
+
+impl ArgBuffer {
+    /// Creates an empty buffer.
+    pub fn new() -> ArgBuffer {
+        ArgBuffer::default()
+    }
+
+    /// Creates an empty buffer with room for `args` arguments with a
+    /// total size of `bytes`.
+    pub fn with_capacity(args: usize, bytes: usize) -> ArgBuffer {
+        ArgBuffer {
+            data: Vec::with_capacity(bytes),
+            ends: Vec::with_capacity(args),
+        }
+    }
+
+    /// Returns the number of arguments.
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Returns true if there are no arguments.
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Removes all arguments but keeps the allocations.
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.ends.clear();
+    }
+
+    /// Iterates over the arguments.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let mut start = 0;
+        self.ends.iter().map(move |&end| {
+            let arg = &self.data[start..end];
+            start = end;
+            arg
+        })
+    }
+}
+
+impl RedisWrite for ArgBuffer {
+    fn write_arg(&mut self, arg: &[u8]) {
+        self.data.extend_from_slice(arg);
+        self.ends.push(self.data.len());
+    }
+}
+
+impl<'a> ToRedisArgs for &'a ArgBuffer {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        for arg in self.iter() {
+            out.write_arg(arg);
+        }
+    }
+
+    fn is_single_arg(&self) -> bool {
+        self.len() == 1
+    }
+}
+
 /// Used to convert a value into one or multiple redis argument
 /// strings.  Most values will produce exactly one item but in
 /// some cases it might make sense to produce more than one.
@@ -496,16 +1034,6 @@ pub trait ToRedisArgs: Sized {
     }
 }
 
-macro_rules! invalid_type_error {
-    ($v:expr, $det:expr) => {{
-        fail!((
-            ErrorKind::TypeError,
-            "Response was of incompatible type",
-            format!("{:?} (response was {:?})", $det, $v)
-        ));
-    }};
-}
-
 macro_rules! itoa_based_to_redis_impl {
     ($t:ty, $numeric:expr) => {
         impl ToRedisArgs for $t {
@@ -532,9 +1060,9 @@ macro_rules! dtoa_based_to_redis_impl {
             where
                 W: ?Sized + RedisWrite,
             {
-                let mut buf = Vec::new();
-                ::dtoa::write(&mut buf, *self).unwrap();
-                out.write_arg(&buf)
+                let mut buf = [0; 32];
+                let n = ::dtoa::write(&mut buf[..], *self).unwrap();
+                out.write_arg(&buf[..n])
             }
 
             fn describe_numeric_behavior(&self) -> NumericBehavior {
@@ -597,7 +1125,7 @@ impl ToRedisArgs for String {
     }
 }
 
-impl<'a> ToRedisArgs for &'a String {
+impl<'a> ToRedisArgs for &'a str {
     fn write_redis_args<W>(&self, out: &mut W)
     where
         W: ?Sized + RedisWrite,
@@ -606,12 +1134,111 @@ impl<'a> ToRedisArgs for &'a String {
     }
 }
 
-impl<'a> ToRedisArgs for &'a str {
+/// References are written like the values they point to, so arguments
+/// and option structs can be passed without giving them away.
+impl<'a, T: ToRedisArgs> ToRedisArgs for &'a T {
     fn write_redis_args<W>(&self, out: &mut W)
     where
         W: ?Sized + RedisWrite,
     {
-        out.write_arg(self.as_bytes())
+        (*self).write_redis_args(out)
+    }
+
+    fn describe_numeric_behavior(&self) -> NumericBehavior {
+        (*self).describe_numeric_behavior()
+    }
+
+    fn is_single_arg(&self) -> bool {
+        (*self).is_single_arg()
+    }
+}
+
+macro_rules! display_based_to_redis_impl {
+    ($t:ty) => {
+        impl ToRedisArgs for $t {
+            fn write_redis_args<W>(&self, out: &mut W)
+            where
+                W: ?Sized + RedisWrite,
+            {
+                out.write_arg(self.to_string().as_bytes())
+            }
+        }
+    };
+}
+
+display_based_to_redis_impl!(IpAddr);
+display_based_to_redis_impl!(Ipv4Addr);
+display_based_to_redis_impl!(Ipv6Addr);
+display_based_to_redis_impl!(SocketAddr);
+display_based_to_redis_impl!(SocketAddrV4);
+display_based_to_redis_impl!(SocketAddrV6);
+
+/// Written in the hyphenated form, like
+/// `67e55044-10b1-426f-9247-bb680e5fe0c8`.
+#[cfg(feature = "with-uuid")]
+impl ToRedisArgs for Uuid {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        out.write_arg(self.to_hyphenated().to_string().as_bytes())
+    }
+}
+
+#[cfg(feature = "with-nonzero")]
+macro_rules! non_zero_to_redis_impl {
+    ($t:ty) => {
+        impl ToRedisArgs for $t {
+            fn write_redis_args<W>(&self, out: &mut W)
+            where
+                W: ?Sized + RedisWrite,
+            {
+                self.get().write_redis_args(out)
+            }
+
+            fn describe_numeric_behavior(&self) -> NumericBehavior {
+                NumericBehavior::NumberIsInteger
+            }
+        }
+    };
+}
+
+#[cfg(feature = "with-nonzero")]
+non_zero_to_redis_impl!(NonZeroU8);
+#[cfg(feature = "with-nonzero")]
+non_zero_to_redis_impl!(NonZeroU16);
+#[cfg(feature = "with-nonzero")]
+non_zero_to_redis_impl!(NonZeroU32);
+#[cfg(feature = "with-nonzero")]
+non_zero_to_redis_impl!(NonZeroU64);
+#[cfg(feature = "with-nonzero")]
+non_zero_to_redis_impl!(NonZeroUsize);
+
+/// Paths are written as they are on unix and as UTF-8, with invalid
+/// characters replaced, on other platforms.
+impl<'a> ToRedisArgs for &'a Path {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            out.write_arg(self.as_os_str().as_bytes())
+        }
+        #[cfg(not(unix))]
+        {
+            out.write_arg(self.to_string_lossy().as_bytes())
+        }
+    }
+}
+
+impl ToRedisArgs for PathBuf {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        self.as_path().write_redis_args(out)
     }
 }
 
@@ -733,6 +1360,17 @@ impl ToRedisArgs for json::Json {
     }
 }
 
+#[cfg(feature = "json")]
+impl ToRedisArgs for serde_json::Value {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        // a `serde_json::Value` always serializes
+        out.write_arg(&serde_json::to_vec(self).unwrap())
+    }
+}
+
 macro_rules! to_redis_args_for_tuple {
     () => ();
     ($($name:ident,)+) => (
@@ -841,6 +1479,17 @@ macro_rules! from_redis_value_for_num_internal {
                 Ok(rv) => Ok(rv),
                 Err(_) => invalid_type_error!(v, "Could not convert from string."),
             },
+            // like the textual doubles of RESP2, only integral values
+            // convert to integers
+            Value::Double(val) => match val.to_string().parse::<$t>() {
+                Ok(rv) => Ok(rv),
+                Err(_) => invalid_type_error!(v, "Could not convert from double."),
+            },
+            Value::Boolean(val) => Ok(val as u8 as $t),
+            Value::BigNumber(ref s) => match s.parse::<$t>() {
+                Ok(rv) => Ok(rv),
+                Err(_) => invalid_type_error!(v, "Could not convert from big number."),
+            },
             _ => invalid_type_error!(v, "Response type not convertible to numeric."),
         }
     }};
@@ -893,6 +1542,7 @@ impl FromRedisValue for bool {
                 }
             }
             Value::Okay => Ok(true),
+            Value::Boolean(val) => Ok(val),
             _ => invalid_type_error!(v, "Response type not bool compatible."),
         }
     }
@@ -904,11 +1554,94 @@ impl FromRedisValue for String {
             Value::Data(ref bytes) => Ok(from_utf8(bytes)?.to_string()),
             Value::Okay => Ok("OK".to_string()),
             Value::Status(ref val) => Ok(val.to_string()),
+            Value::VerbatimString { ref text, .. } => Ok(text.to_string()),
+            Value::BigNumber(ref val) => Ok(val.to_string()),
+            Value::Double(val) => Ok(val.to_string()),
             _ => invalid_type_error!(v, "Response type not string compatible."),
         }
     }
 }
 
+macro_rules! from_str_based_from_redis_impl {
+    ($t:ty) => {
+        impl FromRedisValue for $t {
+            fn from_redis_value(v: &Value) -> RedisResult<$t> {
+                let s: String = from_redis_value(v)?;
+                match <$t>::from_str(&s) {
+                    Ok(rv) => Ok(rv),
+                    Err(_) => invalid_type_error!(v, "Could not convert from string."),
+                }
+            }
+        }
+    };
+}
+
+from_str_based_from_redis_impl!(IpAddr);
+from_str_based_from_redis_impl!(Ipv4Addr);
+from_str_based_from_redis_impl!(Ipv6Addr);
+from_str_based_from_redis_impl!(SocketAddr);
+from_str_based_from_redis_impl!(SocketAddrV4);
+from_str_based_from_redis_impl!(SocketAddrV6);
+
+/// Reads any textual form of a UUID as well as its 16 raw bytes.
+#[cfg(feature = "with-uuid")]
+impl FromRedisValue for Uuid {
+    fn from_redis_value(v: &Value) -> RedisResult<Uuid> {
+        if let Value::Data(ref bytes) = *v {
+            if bytes.len() == 16 {
+                if let Ok(rv) = Uuid::from_slice(bytes) {
+                    return Ok(rv);
+                }
+            }
+        }
+        let s: String = from_redis_value(v)?;
+        match Uuid::parse_str(&s) {
+            Ok(rv) => Ok(rv),
+            Err(_) => invalid_type_error!(v, "Could not convert from string."),
+        }
+    }
+}
+
+#[cfg(feature = "with-nonzero")]
+macro_rules! non_zero_from_redis_impl {
+    ($t:ty, $int:ty) => {
+        impl FromRedisValue for $t {
+            fn from_redis_value(v: &Value) -> RedisResult<$t> {
+                match <$t>::new(from_redis_value::<$int>(v)?) {
+                    Some(rv) => Ok(rv),
+                    None => invalid_type_error!(v, "Expected a number other than zero."),
+                }
+            }
+        }
+    };
+}
+
+#[cfg(feature = "with-nonzero")]
+non_zero_from_redis_impl!(NonZeroU8, u8);
+#[cfg(feature = "with-nonzero")]
+non_zero_from_redis_impl!(NonZeroU16, u16);
+#[cfg(feature = "with-nonzero")]
+non_zero_from_redis_impl!(NonZeroU32, u32);
+#[cfg(feature = "with-nonzero")]
+non_zero_from_redis_impl!(NonZeroU64, u64);
+#[cfg(feature = "with-nonzero")]
+non_zero_from_redis_impl!(NonZeroUsize, usize);
+
+impl FromRedisValue for PathBuf {
+    fn from_redis_value(v: &Value) -> RedisResult<PathBuf> {
+        #[cfg(unix)]
+        {
+            use std::ffi::OsString;
+            use std::os::unix::ffi::OsStringExt;
+            if let Value::Data(ref bytes) = *v {
+                return Ok(PathBuf::from(OsString::from_vec(bytes.clone())));
+            }
+        }
+        let s: String = from_redis_value(v)?;
+        Ok(PathBuf::from(s))
+    }
+}
+
 impl<T: FromRedisValue> FromRedisValue for Vec<T> {
     fn from_redis_value(v: &Value) -> RedisResult<Vec<T>> {
         match *v {
@@ -918,7 +1651,28 @@ impl<T: FromRedisValue> FromRedisValue for Vec<T> {
                 Some(x) => Ok(x),
                 None => invalid_type_error!(v, "Response type not vector compatible."),
             },
-            Value::Bulk(ref items) => FromRedisValue::from_redis_values(items),
+            Value::VerbatimString { ref text, .. } => {
+                match FromRedisValue::from_byte_vec(text.as_bytes()) {
+                    Some(x) => Ok(x),
+                    None => invalid_type_error!(v, "Response type not vector compatible."),
+                }
+            }
+            Value::Bulk(ref items) | Value::Set(ref items) => {
+                FromRedisValue::from_redis_values(items)
+            }
+            // flattened into keys and values like the reply of RESP2, which
+            // sends the numbers of a map as bulk strings
+            Value::Map(ref pairs) => {
+                let items: Vec<Value> = pairs
+                    .iter()
+                    .flat_map(|&(ref key, ref val)| vec![key.clone(), val.clone()])
+                    .map(|item| match item {
+                        Value::Int(val) => Value::Data(val.to_string().into_bytes()),
+                        item => item,
+                    })
+                    .collect();
+                FromRedisValue::from_redis_values(&items)
+            }
             Value::Nil => Ok(vec![]),
             _ => invalid_type_error!(v, "Response type not vector compatible."),
         }
@@ -940,6 +1694,13 @@ impl<K: FromRedisValue + Eq + Hash, V: FromRedisValue, S: BuildHasher + Default>
                 }
                 Ok(rv)
             }
+            Value::Map(ref pairs) => {
+                let mut rv = HashMap::default();
+                for &(ref k, ref v) in pairs.iter() {
+                    rv.insert(from_redis_value(k)?, from_redis_value(v)?);
+                }
+                Ok(rv)
+            }
             _ => invalid_type_error!(v, "Response type not hashmap compatible"),
         }
     }
@@ -961,6 +1722,13 @@ where
                 }
                 Ok(rv)
             }
+            Value::Map(ref pairs) => {
+                let mut rv = BTreeMap::new();
+                for &(ref k, ref v) in pairs.iter() {
+                    rv.insert(from_redis_value(k)?, from_redis_value(v)?);
+                }
+                Ok(rv)
+            }
             _ => invalid_type_error!(v, "Response type not btreemap compatible"),
         }
     }
@@ -969,7 +1737,7 @@ where
 impl<T: FromRedisValue + Eq + Hash, S: BuildHasher + Default> FromRedisValue for HashSet<T, S> {
     fn from_redis_value(v: &Value) -> RedisResult<HashSet<T, S>> {
         match *v {
-            Value::Bulk(ref items) => {
+            Value::Bulk(ref items) | Value::Set(ref items) => {
                 let mut rv = HashSet::default();
                 for item in items.iter() {
                     rv.insert(from_redis_value(item)?);
@@ -987,7 +1755,7 @@ where
 {
     fn from_redis_value(v: &Value) -> RedisResult<BTreeSet<T>> {
         match *v {
-            Value::Bulk(ref items) => {
+            Value::Bulk(ref items) | Value::Set(ref items) => {
                 let mut rv = BTreeSet::new();
                 for item in items.iter() {
                     rv.insert(from_redis_value(item)?);
@@ -1011,6 +1779,29 @@ impl FromRedisValue for () {
     }
 }
 
+// Adds the position of the tuple element that could not be converted to a
+// type error, so that the failing command of a pipeline can be told apart.
+fn tuple_element_error(err: RedisError, idx: usize, len: usize) -> RedisError {
+    let repr = match err.repr {
+        ErrorRepr::WithDescription(ErrorKind::TypeError, desc) => {
+            ErrorRepr::WithDescriptionAndDetail(
+                ErrorKind::TypeError,
+                desc,
+                format!("element {} of {}", idx, len),
+            )
+        }
+        ErrorRepr::WithDescriptionAndDetail(ErrorKind::TypeError, desc, detail) => {
+            ErrorRepr::WithDescriptionAndDetail(
+                ErrorKind::TypeError,
+                desc,
+                format!("element {} of {}: {}", idx, len, detail),
+            )
+        }
+        repr => repr,
+    };
+    RedisError { repr }
+}
+
 macro_rules! from_redis_value_for_tuple {
     () => ();
     ($($name:ident,)+) => (
@@ -1026,14 +1817,15 @@ macro_rules! from_redis_value_for_tuple {
                         let mut n = 0;
                         $(let $name = (); n += 1;)*
                         if items.len() != n {
-                            invalid_type_error!(v, "Bulk response of wrong dimension")
+                            invalid_type_error!(v, format!(
+                                "Bulk response of wrong dimension, expected {} elements", n))
                         }
 
                         // this is pretty ugly too.  The { i += 1; i - 1} is rust's
                         // postfix increment :)
                         let mut i = 0;
-                        Ok(($({let $name = (); from_redis_value(
-                             &items[{ i += 1; i - 1 }])?},)*))
+                        Ok(($({let $name = (); i += 1; from_redis_value(&items[i - 1])
+                             .map_err(|err| tuple_element_error(err, i - 1, n))?},)*))
                     }
                     _ => invalid_type_error!(v, "Not a bulk response")
                 }
@@ -1044,6 +1836,17 @@ macro_rules! from_redis_value_for_tuple {
                 // hacky way to count the tuple size
                 let mut n = 0;
                 $(let $name = (); n += 1;)*
+
+                // RESP3 nests pairs, like the members and scores of
+                // `ZRANGE WITHSCORES`, instead of flattening them
+                let nested = n > 1 && !items.is_empty() && items.iter().all(|item| match *item {
+                    Value::Bulk(ref tuple) => tuple.len() == n,
+                    _ => false,
+                });
+                if nested {
+                    return items.iter().map(from_redis_value).collect();
+                }
+
                 if items.len() % n != 0 {
                     invalid_type_error!(items, "Bulk response of wrong dimension")
                 }
@@ -1088,6 +1891,26 @@ impl FromRedisValue for json::Json {
         let rv = match *v {
             Value::Data(ref b) => json::Json::from_str(from_utf8(b)?),
             Value::Status(ref s) => json::Json::from_str(s),
+            Value::VerbatimString { ref text, .. } => json::Json::from_str(text),
+            _ => invalid_type_error!(v, "Not JSON compatible"),
+        };
+        match rv {
+            Ok(value) => Ok(value),
+            Err(_) => invalid_type_error!(v, "Not valid JSON"),
+        }
+    }
+}
+
+/// Parses a string holding JSON, like a JSON blob stored with `SET` or the
+/// reply of RedisJSON's `JSON.GET`.  Use `From<&Value>` to convert the
+/// structure of a reply instead.
+#[cfg(feature = "json")]
+impl FromRedisValue for serde_json::Value {
+    fn from_redis_value(v: &Value) -> RedisResult<serde_json::Value> {
+        let rv = match *v {
+            Value::Data(ref b) => serde_json::from_slice(b),
+            Value::Status(ref s) => serde_json::from_str(s),
+            Value::VerbatimString { ref text, .. } => serde_json::from_str(text),
             _ => invalid_type_error!(v, "Not JSON compatible"),
         };
         match rv {
@@ -1097,6 +1920,81 @@ impl FromRedisValue for json::Json {
     }
 }
 
+/// Converts the structure of a reply: aggregates become arrays, maps
+/// become objects with their keys turned into strings, and strings that
+/// are not valid UTF-8 are converted lossily.  Doubles that are not finite
+/// become `null`.
+#[cfg(feature = "json")]
+impl<'a> From<&'a Value> for serde_json::Value {
+    fn from(v: &'a Value) -> serde_json::Value {
+        fn key_string(v: &Value) -> String {
+            match *v {
+                Value::Data(ref b) => String::from_utf8_lossy(b).into_owned(),
+                ref other => match serde_json::Value::from(other) {
+                    serde_json::Value::String(s) => s,
+                    other => other.to_string(),
+                },
+            }
+        }
+        fn array(items: &[Value]) -> serde_json::Value {
+            serde_json::Value::Array(items.iter().map(serde_json::Value::from).collect())
+        }
+
+        match *v {
+            Value::Nil => serde_json::Value::Null,
+            Value::Int(val) => val.into(),
+            Value::Data(ref b) => String::from_utf8_lossy(b).into_owned().into(),
+            Value::Bulk(ref items) | Value::Set(ref items) => array(items),
+            Value::Status(ref s) => s.clone().into(),
+            Value::Okay => "OK".into(),
+            Value::Map(ref pairs) => serde_json::Value::Object(
+                pairs
+                    .iter()
+                    .map(|&(ref key, ref val)| (key_string(key), val.into()))
+                    .collect(),
+            ),
+            Value::Double(val) => serde_json::Number::from_f64(val)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            Value::Boolean(val) => val.into(),
+            Value::BigNumber(ref s) => s.clone().into(),
+            Value::VerbatimString { ref text, .. } => text.clone().into(),
+            Value::Push { ref kind, ref data } => {
+                let mut items = vec![serde_json::Value::from(kind.name())];
+                items.extend(data.iter().map(serde_json::Value::from));
+                serde_json::Value::Array(items)
+            }
+        }
+    }
+}
+
+/// Builds a reply with the structure of the JSON, for instance for a mock
+/// server.  Strings become data and objects become RESP3 maps.
+#[cfg(feature = "json")]
+impl From<serde_json::Value> for Value {
+    fn from(v: serde_json::Value) -> Value {
+        match v {
+            serde_json::Value::Null => Value::Nil,
+            serde_json::Value::Bool(val) => Value::Boolean(val),
+            serde_json::Value::Number(val) => match val.as_i64() {
+                Some(val) => Value::Int(val),
+                // integers beyond i64 keep all their digits
+                None if val.is_u64() => Value::BigNumber(val.to_string()),
+                None => Value::Double(val.as_f64().unwrap_or(::std::f64::NAN)),
+            },
+            serde_json::Value::String(val) => Value::Data(val.into_bytes()),
+            serde_json::Value::Array(items) => {
+                Value::Bulk(items.into_iter().map(Value::from).collect())
+            }
+            serde_json::Value::Object(map) => Value::Map(
+                map.into_iter()
+                    .map(|(key, val)| (Value::Data(key.into_bytes()), Value::from(val)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
 impl<T: FromRedisValue> FromRedisValue for Option<T> {
     fn from_redis_value(v: &Value) -> RedisResult<Option<T>> {
         match *v {
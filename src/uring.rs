@@ -0,0 +1,562 @@
+//! Async connections over Linux io_uring.
+//!
+//! This module is only available with the `with-io-uring` feature on
+//! Linux.  `UringStream` is a TCP stream that submits its reads and writes
+//! to an io_uring instead of waiting for readiness through epoll and
+//! issuing a system call per operation.  It implements `aio::Transport`, so
+//! every async connection type can run over it:
+//!
+//! ```rust,no_run
+//! # extern crate futures;
+//! # extern crate redis;
+//! # use futures::Future;
+//! use redis::IntoConnectionInfo;
+//!
+//! # fn main() {
+//! let info = "redis://127.0.0.1/".into_connection_info().unwrap();
+//! let con = redis::uring::connect(info).and_then(redis::aio::MultiplexedConnection::new);
+//! # }
+//! ```
+//!
+//! Every stream owns a small ring.  Completions are signalled through an
+//! eventfd that is registered with the tokio reactor, so the stream has to
+//! be used from within a tokio runtime like any other async connection.
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+use futures::task::{self, Task};
+use futures::{future, Async, Future, Poll};
+use io_uring::{opcode, types, IoUring};
+use libc;
+use mio::unix::EventedFd;
+use mio::{self, Evented, PollOpt, Ready, Token};
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_reactor::PollEvented;
+
+use aio;
+use connection::{resolve, ConnectionAddr, ConnectionInfo};
+use types::{ErrorKind, RedisError};
+
+const RING_ENTRIES: u32 = 8;
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+// The `user_data` of the submitted operations.  There is at most one
+// operation of every kind in flight.
+const RECV: u64 = 1;
+const SEND: u64 = 2;
+const CONNECT: u64 = 3;
+const CANCEL: u64 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Idle,
+    InFlight,
+    // The result of the completion, a negative errno on failure.
+    Done(i32),
+}
+
+fn op_result(res: i32) -> io::Result<usize> {
+    if res < 0 {
+        Err(io::Error::from_raw_os_error(-res))
+    } else {
+        Ok(res as usize)
+    }
+}
+
+// The eventfd the ring signals completions on.
+struct EventFd(RawFd);
+
+impl EventFd {
+    fn new() -> io::Result<EventFd> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(EventFd(fd))
+    }
+
+    // Resets the counter so that the next completion is reported again.
+    fn reset(&self) {
+        let mut value = 0u64;
+        unsafe {
+            libc::read(
+                self.0,
+                &mut value as *mut u64 as *mut libc::c_void,
+                mem::size_of::<u64>(),
+            );
+        }
+    }
+}
+
+impl Evented for EventFd {
+    fn register(
+        &self,
+        poll: &mio::Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.0).register(poll, token, interest, opts)
+    }
+
+    fn reregister(
+        &self,
+        poll: &mio::Poll,
+        token: Token,
+        interest: Ready,
+        opts: PollOpt,
+    ) -> io::Result<()> {
+        EventedFd(&self.0).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &mio::Poll) -> io::Result<()> {
+        EventedFd(&self.0).deregister(poll)
+    }
+}
+
+impl Drop for EventFd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+        }
+    }
+}
+
+/// A TCP stream whose IO is done through an io_uring.
+///
+/// Writes are copied into an internal buffer and sent in the background,
+/// so a write only has to wait if the previous one is still being sent.
+/// Reads receive into an internal buffer as well.  Both halves can be used
+/// from different tasks.
+pub struct UringStream {
+    ring: IoUring,
+    event: PollEvented<EventFd>,
+    socket: TcpStream,
+    read_buf: Box<[u8]>,
+    read_pos: usize,
+    read_len: usize,
+    write_buf: Vec<u8>,
+    write_pos: usize,
+    recv: Op,
+    send: Op,
+    connect: Op,
+    read_task: Option<Task>,
+    write_task: Option<Task>,
+}
+
+impl UringStream {
+    /// Wraps an already connected TCP stream.
+    pub fn from_std(socket: TcpStream) -> io::Result<UringStream> {
+        let ring = IoUring::new(RING_ENTRIES)?;
+        let event = EventFd::new()?;
+        ring.submitter().register_eventfd(event.0)?;
+        Ok(UringStream {
+            ring,
+            event: PollEvented::new(event),
+            socket,
+            read_buf: vec![0; READ_BUFFER_SIZE].into_boxed_slice(),
+            read_pos: 0,
+            read_len: 0,
+            write_buf: Vec::with_capacity(WRITE_BUFFER_SIZE),
+            write_pos: 0,
+            recv: Op::Idle,
+            send: Op::Idle,
+            connect: Op::Idle,
+            read_task: None,
+            write_task: None,
+        })
+    }
+
+    /// Opens a TCP connection to `addr` through the ring.
+    pub fn connect(addr: &SocketAddr) -> ConnectFuture {
+        let addr = SockAddr::from(addr);
+        let stream = new_socket(addr.family()).and_then(|socket| {
+            let mut stream = UringStream::from_std(socket)?;
+            let entry = opcode::Connect::new(
+                types::Fd(stream.socket.as_raw_fd()),
+                addr.as_ptr(),
+                addr.len,
+            )
+            .build()
+            .user_data(CONNECT);
+            stream.push(&entry)?;
+            stream.connect = Op::InFlight;
+            Ok(stream)
+        });
+        ConnectFuture {
+            stream: Some(stream),
+            _addr: addr,
+        }
+    }
+
+    /// Returns the underlying TCP stream, for instance to set socket
+    /// options.
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.socket
+    }
+
+    fn push(&mut self, entry: &io_uring::squeue::Entry) -> io::Result<()> {
+        unsafe {
+            if self.ring.submission().push(entry).is_err() {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "io_uring submission queue is full",
+                ));
+            }
+        }
+        self.ring.submit()?;
+        Ok(())
+    }
+
+    fn submit_recv(&mut self) -> io::Result<()> {
+        let entry = opcode::Recv::new(
+            types::Fd(self.socket.as_raw_fd()),
+            self.read_buf.as_mut_ptr(),
+            self.read_buf.len() as u32,
+        )
+        .build()
+        .user_data(RECV);
+        self.push(&entry)?;
+        self.recv = Op::InFlight;
+        Ok(())
+    }
+
+    fn submit_send(&mut self) -> io::Result<()> {
+        let pending = &self.write_buf[self.write_pos..];
+        let entry = opcode::Send::new(
+            types::Fd(self.socket.as_raw_fd()),
+            pending.as_ptr(),
+            pending.len() as u32,
+        )
+        .build()
+        .user_data(SEND);
+        self.push(&entry)?;
+        self.send = Op::InFlight;
+        Ok(())
+    }
+
+    // Takes the available completions off the ring and wakes up the tasks
+    // waiting for them.  Returns true if there were any.
+    fn drain_completions(&mut self) -> bool {
+        let mut completed = false;
+        for entry in self.ring.completion() {
+            let res = entry.result();
+            match entry.user_data() {
+                RECV => {
+                    self.recv = Op::Done(res);
+                    if let Some(task) = self.read_task.take() {
+                        task.notify();
+                    }
+                }
+                SEND => {
+                    self.send = Op::Done(res);
+                    if let Some(task) = self.write_task.take() {
+                        task.notify();
+                    }
+                }
+                CONNECT => self.connect = Op::Done(res),
+                _ => continue,
+            }
+            completed = true;
+        }
+        completed
+    }
+
+    // Waits for completions.  Returns false and registers the current task
+    // for a wakeup if there are none yet.
+    fn poll_completions(&mut self) -> io::Result<bool> {
+        loop {
+            if self.drain_completions() {
+                return Ok(true);
+            }
+            match self.event.poll_read_ready(Ready::readable())? {
+                Async::NotReady => return Ok(false),
+                Async::Ready(_) => {
+                    self.event.get_ref().reset();
+                    self.event.clear_read_ready(Ready::readable())?;
+                }
+            }
+        }
+    }
+
+    fn would_block() -> io::Error {
+        io::Error::new(io::ErrorKind::WouldBlock, "io_uring operation in flight")
+    }
+
+    fn in_flight(&self) -> usize {
+        [self.recv, self.send, self.connect]
+            .iter()
+            .filter(|&&op| op == Op::InFlight)
+            .count()
+    }
+}
+
+impl Read for UringStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.read_pos < self.read_len {
+                let len = buf.len().min(self.read_len - self.read_pos);
+                buf[..len].copy_from_slice(&self.read_buf[self.read_pos..self.read_pos + len]);
+                self.read_pos += len;
+                return Ok(len);
+            }
+            match self.recv {
+                Op::Done(res) => {
+                    self.recv = Op::Idle;
+                    self.read_pos = 0;
+                    self.read_len = op_result(res)?;
+                    if self.read_len == 0 {
+                        return Ok(0);
+                    }
+                    continue;
+                }
+                Op::Idle => self.submit_recv()?,
+                Op::InFlight => {}
+            }
+            if !self.poll_completions()? {
+                self.read_task = Some(task::current());
+                return Err(UringStream::would_block());
+            }
+        }
+    }
+}
+
+impl Write for UringStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        loop {
+            match self.send {
+                Op::Idle => {
+                    let len = buf.len().min(WRITE_BUFFER_SIZE);
+                    self.write_buf.clear();
+                    self.write_buf.extend_from_slice(&buf[..len]);
+                    self.write_pos = 0;
+                    if len > 0 {
+                        self.submit_send()?;
+                    }
+                    return Ok(len);
+                }
+                Op::Done(res) => {
+                    self.send = Op::Idle;
+                    self.write_pos += op_result(res)?;
+                    if self.write_pos < self.write_buf.len() {
+                        self.submit_send()?;
+                    }
+                    continue;
+                }
+                Op::InFlight => {}
+            }
+            if !self.poll_completions()? {
+                self.write_task = Some(task::current());
+                return Err(UringStream::would_block());
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        loop {
+            match self.send {
+                Op::Idle => return Ok(()),
+                Op::Done(res) => {
+                    self.send = Op::Idle;
+                    self.write_pos += op_result(res)?;
+                    if self.write_pos < self.write_buf.len() {
+                        self.submit_send()?;
+                    }
+                    continue;
+                }
+                Op::InFlight => {}
+            }
+            if !self.poll_completions()? {
+                self.write_task = Some(task::current());
+                return Err(UringStream::would_block());
+            }
+        }
+    }
+}
+
+impl AsyncRead for UringStream {}
+
+impl AsyncWrite for UringStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        match self.flush() {
+            Ok(()) => Ok(Async::Ready(())),
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => Ok(Async::NotReady),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl Drop for UringStream {
+    fn drop(&mut self) {
+        // the kernel may still write into the buffers, so the operations
+        // in flight have to be cancelled and waited for
+        for &(op, user_data) in &[
+            (self.recv, RECV),
+            (self.send, SEND),
+            (self.connect, CONNECT),
+        ] {
+            if op == Op::InFlight {
+                let entry = opcode::AsyncCancel::new(user_data)
+                    .build()
+                    .user_data(CANCEL);
+                let _ = self.push(&entry);
+            }
+        }
+        // a receive or send that could not be cancelled completes once the
+        // socket is shut down
+        let _ = self.socket.shutdown(Shutdown::Both);
+        // the loop must not be left early, not even on errors: an interrupted
+        // wait is simply repeated, and the other errors (EBUSY while the
+        // completion queue is full, EAGAIN) go away once completions are
+        // drained
+        while self.in_flight() > 0 {
+            let _ = self.ring.submit_and_wait(1);
+            self.drain_completions();
+        }
+    }
+}
+
+// A socket address in the representation of the kernel.
+struct SockAddr {
+    storage: Box<libc::sockaddr_storage>,
+    len: libc::socklen_t,
+}
+
+impl SockAddr {
+    fn family(&self) -> libc::c_int {
+        libc::c_int::from(self.storage.ss_family)
+    }
+
+    fn as_ptr(&self) -> *const libc::sockaddr {
+        &*self.storage as *const libc::sockaddr_storage as *const libc::sockaddr
+    }
+}
+
+impl<'a> From<&'a SocketAddr> for SockAddr {
+    fn from(addr: &SocketAddr) -> SockAddr {
+        let mut storage: Box<libc::sockaddr_storage> = Box::new(unsafe { mem::zeroed() });
+        let len = match *addr {
+            SocketAddr::V4(ref addr) => {
+                let sin = unsafe {
+                    &mut *(&mut *storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in)
+                };
+                sin.sin_family = libc::AF_INET as libc::sa_family_t;
+                sin.sin_port = addr.port().to_be();
+                sin.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+                mem::size_of::<libc::sockaddr_in>()
+            }
+            SocketAddr::V6(ref addr) => {
+                let sin6 = unsafe {
+                    &mut *(&mut *storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in6)
+                };
+                sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                sin6.sin6_port = addr.port().to_be();
+                sin6.sin6_flowinfo = addr.flowinfo();
+                sin6.sin6_addr.s6_addr = addr.ip().octets();
+                sin6.sin6_scope_id = addr.scope_id();
+                mem::size_of::<libc::sockaddr_in6>()
+            }
+        };
+        SockAddr {
+            storage,
+            len: len as libc::socklen_t,
+        }
+    }
+}
+
+fn new_socket(family: libc::c_int) -> io::Result<TcpStream> {
+    let fd = unsafe { libc::socket(family, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { TcpStream::from_raw_fd(fd) })
+}
+
+/// A future that resolves to a connected `UringStream`, see
+/// `UringStream::connect`.
+pub struct ConnectFuture {
+    // declared before the address so that a pending connect is cancelled
+    // before the address it reads from is freed
+    stream: Option<io::Result<UringStream>>,
+    _addr: SockAddr,
+}
+
+impl Future for ConnectFuture {
+    type Item = UringStream;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<UringStream, io::Error> {
+        match self.stream {
+            Some(Ok(ref mut stream)) => loop {
+                if let Op::Done(res) = stream.connect {
+                    stream.connect = Op::Idle;
+                    op_result(res)?;
+                    break;
+                }
+                if !stream.poll_completions()? {
+                    return Ok(Async::NotReady);
+                }
+            },
+            Some(Err(_)) => {}
+            None => panic!("ConnectFuture polled after completion"),
+        }
+        self.stream.take().unwrap().map(Async::Ready)
+    }
+}
+
+/// Opens an async connection over io_uring.  Only TCP addresses are
+/// supported; the addresses of the connection info are tried in order
+/// like `aio::connect` does.
+pub fn connect(
+    connection_info: ConnectionInfo,
+) -> impl Future<Item = aio::Connection, Error = RedisError> {
+    let mut connection: Box<Future<Item = UringStream, Error = RedisError> + Send> =
+        Box::new(future::err(RedisError::from((
+            ErrorKind::InvalidClientConfig,
+            "No address to connect to",
+        ))));
+    for addr in connection_info.addrs() {
+        let (host, port) = match *addr {
+            ConnectionAddr::Tcp(ref host, port) => (host.clone(), port),
+            _ => {
+                connection = Box::new(connection.or_else(|_| {
+                    Err(RedisError::from((
+                        ErrorKind::InvalidClientConfig,
+                        "io_uring connections only support TCP",
+                    )))
+                }));
+                continue;
+            }
+        };
+        let shuffle = connection_info.shuffle_resolved_addrs;
+        let nodelay = connection_info.tcp.nodelay;
+        connection = Box::new(connection.or_else(move |_| {
+            future::result(resolve(&host, port, shuffle))
+                .from_err()
+                .and_then(move |socket_addrs| {
+                    // try every address the host name resolved to
+                    let mut stream: Box<Future<Item = UringStream, Error = RedisError> + Send> =
+                        Box::new(future::err(RedisError::from((
+                            ErrorKind::InvalidClientConfig,
+                            "No address found for host",
+                        ))));
+                    for socket_addr in socket_addrs {
+                        stream = Box::new(stream.or_else(move |_| {
+                            UringStream::connect(&socket_addr)
+                                .and_then(move |stream| {
+                                    stream.get_ref().set_nodelay(nodelay)?;
+                                    Ok(stream)
+                                })
+                                .from_err()
+                        }));
+                    }
+                    stream
+                })
+        }));
+    }
+
+    connection.and_then(move |stream| aio::Connection::from_transport(stream, connection_info))
+}
@@ -1,5 +1,6 @@
 extern crate redis;
 
+extern crate bytes;
 extern crate futures;
 extern crate partial_io;
 #[macro_use]
@@ -10,12 +11,16 @@ mod support;
 
 use std::io::BufReader;
 
+use bytes::BytesMut;
+
 use partial_io::{GenWouldBlock, PartialAsyncRead, PartialWithErrors};
 
+use tokio::codec::{Decoder, FramedRead};
 use tokio::executor::current_thread::block_on_all;
 
-use futures::Future;
+use futures::{Future, Stream};
 
+use redis::resp::{ReplyCodec, ReplyLimits};
 use redis::Value;
 
 use support::encode_value;
@@ -29,7 +34,13 @@ impl ::quickcheck::Arbitrary for ArbitraryValue {
     }
     fn shrink(&self) -> Box<Iterator<Item = Self>> {
         match self.0 {
-            Value::Nil | Value::Okay => Box::new(None.into_iter()),
+            Value::Nil
+            | Value::Okay
+            | Value::Double(_)
+            | Value::Boolean(_)
+            | Value::BigNumber(_)
+            | Value::VerbatimString { .. }
+            | Value::Push { .. } => Box::new(None.into_iter()),
             Value::Int(i) => Box::new(i.shrink().map(Value::Int).map(ArbitraryValue)),
             Value::Data(ref xs) => Box::new(xs.shrink().map(Value::Data).map(ArbitraryValue)),
             Value::Bulk(ref xs) => {
@@ -47,6 +58,32 @@ impl ::quickcheck::Arbitrary for ArbitraryValue {
             Value::Status(ref status) => {
                 Box::new(status.shrink().map(Value::Status).map(ArbitraryValue))
             }
+            Value::Map(ref pairs) => {
+                let ys = pairs
+                    .iter()
+                    .map(|&(ref key, ref val)| {
+                        (ArbitraryValue(key.clone()), ArbitraryValue(val.clone()))
+                    })
+                    .collect::<Vec<_>>();
+                Box::new(
+                    ys.shrink()
+                        .map(|pairs| pairs.into_iter().map(|(key, val)| (key.0, val.0)).collect())
+                        .map(Value::Map)
+                        .map(ArbitraryValue),
+                )
+            }
+            Value::Set(ref xs) => {
+                let ys = xs
+                    .iter()
+                    .map(|x| ArbitraryValue(x.clone()))
+                    .collect::<Vec<_>>();
+                Box::new(
+                    ys.shrink()
+                        .map(|xs| xs.into_iter().map(|x| x.0).collect())
+                        .map(Value::Set)
+                        .map(ArbitraryValue),
+                )
+            }
         }
     }
 }
@@ -56,7 +93,7 @@ fn arbitrary_value<G: ::quickcheck::Gen>(g: &mut G, recursive_size: usize) -> Va
     if recursive_size == 0 {
         Value::Nil
     } else {
-        match g.gen_range(0, 6) {
+        match g.gen_range(0, 12) {
             0 => Value::Nil,
             1 => Value::Int(Arbitrary::arbitrary(g)),
             2 => Value::Data(Arbitrary::arbitrary(g)),
@@ -84,6 +121,53 @@ fn arbitrary_value<G: ::quickcheck::Gen>(g: &mut G, recursive_size: usize) -> Va
                 }
             }
             5 => Value::Okay,
+            6 => {
+                let size = {
+                    let s = g.size();
+                    g.gen_range(0, s)
+                };
+                Value::Map(
+                    (0..size)
+                        .map(|_| {
+                            (
+                                arbitrary_value(g, recursive_size / size),
+                                arbitrary_value(g, recursive_size / size),
+                            )
+                        })
+                        .collect(),
+                )
+            }
+            7 => {
+                let size = {
+                    let s = g.size();
+                    g.gen_range(0, s)
+                };
+                Value::Set(
+                    (0..size)
+                        .map(|_| arbitrary_value(g, recursive_size / size))
+                        .collect(),
+                )
+            }
+            8 => {
+                // NaN is not equal to itself
+                let val: i32 = Arbitrary::arbitrary(g);
+                Value::Double(f64::from(val) / 8.0)
+            }
+            9 => Value::Boolean(Arbitrary::arbitrary(g)),
+            10 => {
+                let val: u64 = Arbitrary::arbitrary(g);
+                Value::BigNumber(format!("{}{:020}", val, val))
+            }
+            11 => {
+                let size = {
+                    let s = g.size();
+                    g.gen_range(0, s)
+                };
+                Value::VerbatimString {
+                    format: "txt".to_string(),
+                    text: g.gen_ascii_chars().take(size).collect(),
+                }
+            }
             _ => unreachable!(),
         }
     }
@@ -105,4 +189,191 @@ quickcheck! {
             input.0,
         );
     }
+
+    fn parse_ref(input: ArbitraryValue) -> () {
+        let mut encoded_input = Vec::new();
+        encode_value(&input.0, &mut encoded_input).unwrap();
+
+        let result = redis::parse_redis_value_ref(&encoded_input);
+        assert!(result.as_ref().is_ok(), "{}", result.unwrap_err());
+        assert_eq!(result.unwrap().to_value(), input.0);
+    }
+
+    fn partial_io_reply(input: ArbitraryValue, seq: PartialWithErrors<GenWouldBlock>) -> () {
+        let mut encoded_input = Vec::new();
+        encode_value(&input.0, &mut encoded_input).unwrap();
+
+        let mut reader = &encoded_input[..];
+        let partial_reader = PartialAsyncRead::new(&mut reader, seq);
+
+        let result = block_on_all(
+            FramedRead::new(partial_reader, ReplyCodec::default())
+                .into_future()
+                .map(|(reply, _)| reply.unwrap())
+                .map_err(|(err, _)| err),
+        );
+        assert!(result.as_ref().is_ok(), "{}", result.unwrap_err());
+        let reply = result.unwrap();
+        assert_eq!(&reply.as_bytes()[..], &encoded_input[..]);
+        assert_eq!(reply.value().unwrap().to_value(), input.0);
+    }
+}
+
+#[test]
+fn reply_codec() {
+    use redis::ValueRef;
+
+    let mut codec = ReplyCodec::default();
+    let mut buffer = BytesMut::from(&b"*2\r\n$5\r\nhello\r\n:4"[..]);
+    assert_eq!(codec.decode(&mut buffer), Ok(None));
+    buffer.extend_from_slice(b"2\r\n$-1\r\n%1\r\n+a\r\n");
+    let reply = codec.decode(&mut buffer).unwrap().unwrap();
+    assert_eq!(&buffer[..], b"$-1\r\n%1\r\n+a\r\n");
+    let value = reply.value().unwrap();
+    assert_eq!(
+        value,
+        ValueRef::Bulk(vec![ValueRef::Data(b"hello"), ValueRef::Int(42)])
+    );
+    match value {
+        ValueRef::Bulk(ref items) => match items[0] {
+            ValueRef::Data(data) => assert_eq!(data.as_ptr(), reply.as_bytes()[8..].as_ptr()),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+    assert_eq!(
+        codec.decode(&mut buffer).unwrap().unwrap().value(),
+        Ok(ValueRef::Nil)
+    );
+    assert_eq!(codec.decode(&mut buffer), Ok(None));
+    buffer.extend_from_slice(b":1\r\n");
+    assert_eq!(
+        codec.decode(&mut buffer).unwrap().unwrap().value(),
+        Ok(ValueRef::Map(vec![(
+            ValueRef::Status("a"),
+            ValueRef::Int(1)
+        )]))
+    );
+    assert!(buffer.is_empty());
+
+    let limits = ReplyLimits {
+        max_bulk_size: 10,
+        ..Default::default()
+    };
+    let mut buffer = BytesMut::from(&b"$11\r\n"[..]);
+    let err = ReplyCodec::new(limits).decode(&mut buffer).unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ReplyTooLarge);
+
+    let mut buffer = BytesMut::from(&b"?\r\n"[..]);
+    let err = codec.decode(&mut buffer).unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::IoError);
+}
+
+#[test]
+fn parse_ref_borrows() {
+    use redis::{parse_redis_value_ref, ValueRef};
+
+    let input = b"*3\r\n$5\r\nhello\r\n+OK\r\n$-1\r\n";
+    let value = parse_redis_value_ref(input).unwrap();
+    assert_eq!(
+        value,
+        ValueRef::Bulk(vec![
+            ValueRef::Data(b"hello"),
+            ValueRef::Okay,
+            ValueRef::Nil
+        ])
+    );
+    match value {
+        ValueRef::Bulk(ref items) => match items[0] {
+            ValueRef::Data(data) => assert_eq!(data.as_ptr(), input[8..].as_ptr()),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+
+    let err = parse_redis_value_ref(b"-ERR unknown command\r\n").unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ResponseError);
+    let err = parse_redis_value_ref(b"*2\r\n:1\r\n").unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ResponseError);
+    let err = parse_redis_value_ref(b"$5\r\nhel").unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ResponseError);
+}
+
+#[test]
+fn resp_module() {
+    use redis::resp::{encode_value, Parser, ReplyLimits};
+
+    let mut packed = redis::cmd("PING").get_packed_command();
+    encode_value(&Value::Double(::std::f64::NAN), &mut packed);
+    encode_value(&Value::Data(vec![b'x'; 11]), &mut packed);
+    encode_value(&Value::Int(1), &mut packed);
+
+    let limits = ReplyLimits {
+        max_bulk_size: 10,
+        ..Default::default()
+    };
+    let mut parser = Parser::with_limits(&packed[..], limits);
+    assert_eq!(
+        parser.parse_value(),
+        Ok(Value::Bulk(vec![Value::Data(b"PING".to_vec())]))
+    );
+    match parser.parse_value() {
+        Ok(Value::Double(val)) => assert!(val.is_nan()),
+        value => panic!("unexpected value {:?}", value),
+    }
+    let err = parser.parse_value().unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ReplyTooLarge);
+}
+
+#[test]
+fn parse_resp3() {
+    use redis::parse_redis_value;
+
+    assert_eq!(parse_redis_value(b"_\r\n"), Ok(Value::Nil));
+    assert_eq!(parse_redis_value(b"#t\r\n"), Ok(Value::Boolean(true)));
+    assert_eq!(parse_redis_value(b"#f\r\n"), Ok(Value::Boolean(false)));
+    assert_eq!(parse_redis_value(b",3.25\r\n"), Ok(Value::Double(3.25)));
+    assert_eq!(
+        parse_redis_value(b",-inf\r\n"),
+        Ok(Value::Double(::std::f64::NEG_INFINITY))
+    );
+    assert_eq!(
+        parse_redis_value(b"(3492890328409238509324850943850943825024385\r\n"),
+        Ok(Value::BigNumber(
+            "3492890328409238509324850943850943825024385".to_string()
+        ))
+    );
+    assert_eq!(
+        parse_redis_value(b"=15\r\ntxt:Some string\r\n"),
+        Ok(Value::VerbatimString {
+            format: "txt".to_string(),
+            text: "Some string".to_string(),
+        })
+    );
+    assert_eq!(
+        parse_redis_value(b"%2\r\n+first\r\n:1\r\n+second\r\n:2\r\n"),
+        Ok(Value::Map(vec![
+            (Value::Status("first".to_string()), Value::Int(1)),
+            (Value::Status("second".to_string()), Value::Int(2)),
+        ]))
+    );
+    assert_eq!(
+        parse_redis_value(b"~2\r\n$3\r\nfoo\r\n_\r\n"),
+        Ok(Value::Set(vec![Value::Data(b"foo".to_vec()), Value::Nil]))
+    );
+    assert_eq!(
+        parse_redis_value(b">2\r\n$10\r\ninvalidate\r\n*1\r\n$3\r\nfoo\r\n"),
+        Ok(Value::Push {
+            kind: redis::PushKind::Invalidate,
+            data: vec![Value::Bulk(vec![Value::Data(b"foo".to_vec())])],
+        })
+    );
+    assert_eq!(
+        parse_redis_value(b"|1\r\n+key-popularity\r\n*0\r\n:42\r\n"),
+        Ok(Value::Int(42))
+    );
+
+    let err = parse_redis_value(b"!21\r\nSYNTAX invalid syntax\r\n").unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ExtensionError);
+    assert_eq!(err.extension_error_code(), Some("SYNTAX"));
 }
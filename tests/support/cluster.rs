@@ -0,0 +1,227 @@
+use redis;
+use redis::cluster::{ClusterClient, ClusterConfig, ClusterConnection};
+
+use std::env;
+use std::fs;
+use std::process;
+use std::thread::sleep;
+use std::time::Duration;
+
+use super::net2;
+use super::rand;
+
+const NODES: usize = 3;
+
+struct ClusterNode {
+    process: process::Child,
+    port: u16,
+    config: String,
+}
+
+impl Drop for ClusterNode {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_file(&self.config);
+    }
+}
+
+pub struct RedisCluster {
+    // the masters come first
+    nodes: Vec<ClusterNode>,
+    replicas: usize,
+}
+
+impl RedisCluster {
+    pub fn new() -> RedisCluster {
+        RedisCluster::with_replicas(0)
+    }
+
+    /// Starts a cluster with the given number of replicas per master.
+    pub fn with_replicas(replicas: usize) -> RedisCluster {
+        let mut nodes = vec![];
+        for _ in 0..NODES * (1 + replicas) {
+            let port = net2::TcpBuilder::new_v4()
+                .unwrap()
+                .reuse_address(true)
+                .unwrap()
+                .bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            let config = env::temp_dir()
+                .join(format!("redis-rs-cluster-{}.conf", rand::random::<u64>()))
+                .to_string_lossy()
+                .into_owned();
+            let process = process::Command::new("redis-server")
+                .arg("--port")
+                .arg(port.to_string())
+                .arg("--bind")
+                .arg("127.0.0.1")
+                .arg("--cluster-enabled")
+                .arg("yes")
+                .arg("--cluster-config-file")
+                .arg(&config)
+                .arg("--save")
+                .arg("")
+                .stdout(process::Stdio::null())
+                .stderr(process::Stdio::null())
+                .spawn()
+                .unwrap();
+            nodes.push(ClusterNode {
+                process: process,
+                port: port,
+                config: config,
+            });
+        }
+        let cluster = RedisCluster {
+            nodes: nodes,
+            replicas: replicas,
+        };
+        cluster.assign_slots();
+        cluster.add_replicas();
+        cluster
+    }
+
+    pub fn ports(&self) -> Vec<u16> {
+        self.nodes.iter().map(|node| node.port).collect()
+    }
+
+    pub fn master_ports(&self) -> Vec<u16> {
+        self.ports()[..NODES].to_vec()
+    }
+
+    pub fn replica_ports(&self) -> Vec<u16> {
+        self.ports()[NODES..].to_vec()
+    }
+
+    pub fn node_connection(&self, port: u16) -> redis::Connection {
+        let url = format!("redis://127.0.0.1:{}/", port);
+        let client = redis::Client::open(&url[..]).unwrap();
+        let millisecond = Duration::from_millis(1);
+        loop {
+            match client.get_connection() {
+                Err(ref err) if err.is_connection_refusal() => sleep(millisecond),
+                result => return result.unwrap(),
+            }
+        }
+    }
+
+    // Splits the slots evenly between the nodes and waits until the
+    // cluster agrees on its state.
+    fn assign_slots(&self) {
+        let ports = self.master_ports();
+        let per_node = 16384 / ports.len();
+        for (idx, &port) in ports.iter().enumerate() {
+            let mut con = self.node_connection(port);
+            let end = if idx + 1 == ports.len() {
+                16384
+            } else {
+                (idx + 1) * per_node
+            };
+            let slots: Vec<usize> = (idx * per_node..end).collect();
+            redis::cmd("CLUSTER")
+                .arg("ADDSLOTS")
+                .arg(slots)
+                .execute(&mut con);
+            if idx > 0 {
+                redis::cmd("CLUSTER")
+                    .arg("MEET")
+                    .arg("127.0.0.1")
+                    .arg(ports[0])
+                    .execute(&mut con);
+            }
+        }
+        self.wait_for_nodes(NODES);
+    }
+
+    // Makes the remaining nodes replicas of the masters in turn and waits
+    // until they are in sync.
+    fn add_replicas(&self) {
+        if self.replicas == 0 {
+            return;
+        }
+        let masters = self.master_ports();
+        for (idx, &port) in self.replica_ports().iter().enumerate() {
+            let mut master = self.node_connection(masters[idx % NODES]);
+            let id: String = redis::cmd("CLUSTER")
+                .arg("MYID")
+                .query(&mut master)
+                .unwrap();
+            let mut con = self.node_connection(port);
+            redis::cmd("CLUSTER")
+                .arg("MEET")
+                .arg("127.0.0.1")
+                .arg(masters[0])
+                .execute(&mut con);
+            // the replica has to learn about the master first
+            while redis::cmd("CLUSTER")
+                .arg("REPLICATE")
+                .arg(&id)
+                .query::<()>(&mut con)
+                .is_err()
+            {
+                sleep(Duration::from_millis(50));
+            }
+        }
+        self.wait_for_nodes(self.nodes.len());
+        for port in self.replica_ports() {
+            let mut con = self.node_connection(port);
+            loop {
+                let info: String = redis::cmd("INFO")
+                    .arg("replication")
+                    .query(&mut con)
+                    .unwrap();
+                if info.contains("master_link_status:up") {
+                    break;
+                }
+                sleep(Duration::from_millis(50));
+            }
+        }
+    }
+
+    fn wait_for_nodes(&self, count: usize) {
+        for port in self.ports() {
+            let mut con = self.node_connection(port);
+            loop {
+                let info: String = redis::cmd("CLUSTER").arg("INFO").query(&mut con).unwrap();
+                let known_nodes = format!("cluster_known_nodes:{}", count);
+                if info.contains("cluster_state:ok") && info.contains(&known_nodes) {
+                    break;
+                }
+                sleep(Duration::from_millis(50));
+            }
+        }
+    }
+}
+
+pub struct TestClusterContext {
+    pub cluster: RedisCluster,
+    pub client: ClusterClient,
+}
+
+impl TestClusterContext {
+    pub fn new() -> TestClusterContext {
+        TestClusterContext::with_replicas(0, ClusterConfig::default())
+    }
+
+    pub fn with_replicas(replicas: usize, config: ClusterConfig) -> TestClusterContext {
+        let cluster = RedisCluster::with_replicas(replicas);
+        let nodes: Vec<String> = cluster
+            .master_ports()
+            .iter()
+            .map(|port| format!("redis://127.0.0.1:{}/", port))
+            .collect();
+        let client =
+            ClusterClient::with_config(nodes.iter().map(|x| &x[..]).collect(), config).unwrap();
+        TestClusterContext {
+            cluster: cluster,
+            client: client,
+        }
+    }
+
+    pub fn connection(&self) -> ClusterConnection {
+        self.client.get_connection().unwrap()
+    }
+}
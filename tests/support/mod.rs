@@ -15,9 +15,16 @@ use std::time::Duration;
 
 use std::path::PathBuf;
 
+#[cfg(feature = "aio")]
 use self::futures::Future;
 
-use redis::{RedisError, Value};
+#[cfg(feature = "aio")]
+use redis::RedisError;
+use redis::Value;
+
+pub use self::cluster::*;
+
+mod cluster;
 
 #[derive(PartialEq)]
 enum ServerType {
@@ -124,8 +131,7 @@ impl TestContext {
 
         let client = redis::Client::open(redis::ConnectionInfo {
             addr: Box::new(server.get_client_addr().clone()),
-            db: 0,
-            passwd: None,
+            ..Default::default()
         })
         .unwrap();
         let mut con;
@@ -158,6 +164,7 @@ impl TestContext {
         self.client.get_connection().unwrap()
     }
 
+    #[cfg(feature = "aio")]
     pub fn async_connection(
         &self,
     ) -> impl Future<Item = redis::aio::Connection, Error = RedisError> {
@@ -168,10 +175,11 @@ impl TestContext {
         self.server.stop();
     }
 
-    pub fn shared_async_connection(
+    #[cfg(feature = "aio")]
+    pub fn multiplexed_async_connection(
         &self,
-    ) -> impl Future<Item = redis::aio::SharedConnection, Error = RedisError> {
-        self.client.get_shared_async_connection()
+    ) -> impl Future<Item = redis::aio::MultiplexedConnection, Error = RedisError> {
+        self.client.get_multiplexed_async_connection()
     }
 }
 
@@ -179,22 +187,7 @@ pub fn encode_value<W>(value: &Value, writer: &mut W) -> io::Result<()>
 where
     W: io::Write,
 {
-    match *value {
-        Value::Nil => write!(writer, "$-1\r\n"),
-        Value::Int(val) => write!(writer, ":{}\r\n", val),
-        Value::Data(ref val) => {
-            write!(writer, "${}\r\n", val.len())?;
-            writer.write_all(val)?;
-            writer.write_all(b"\r\n")
-        }
-        Value::Bulk(ref values) => {
-            write!(writer, "*{}\r\n", values.len())?;
-            for val in values.iter() {
-                encode_value(val, writer)?;
-            }
-            Ok(())
-        }
-        Value::Okay => write!(writer, "+OK\r\n"),
-        Value::Status(ref s) => write!(writer, "+{}\r\n", s),
-    }
+    let mut buf = Vec::new();
+    redis::resp::encode_value(value, &mut buf);
+    writer.write_all(&buf)
 }
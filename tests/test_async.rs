@@ -3,15 +3,18 @@ extern crate redis;
 extern crate futures;
 extern crate tokio;
 
+use std::time::{Duration, Instant};
+
 use futures::{future, Future};
 
 use support::*;
 
-use redis::aio::SharedConnection;
+use redis::aio::MultiplexedConnection;
 use redis::RedisError;
 
 use tokio::executor::current_thread::block_on_all;
 use tokio::runtime::current_thread::Runtime;
+use tokio::timer::Delay;
 
 mod support;
 
@@ -41,9 +44,9 @@ fn test_args() {
 }
 
 #[test]
-fn dont_panic_on_closed_shared_connection() {
+fn dont_panic_on_closed_multiplexed_connection() {
     let ctx = TestContext::new();
-    let connect = ctx.shared_async_connection();
+    let connect = ctx.multiplexed_async_connection();
     drop(ctx);
 
     Runtime::new()
@@ -107,7 +110,51 @@ fn test_pipeline_transaction() {
     .unwrap();
 }
 
-fn test_cmd(con: &SharedConnection, i: i32) -> Box<Future<Item = (), Error = RedisError> + Send> {
+#[test]
+fn test_transaction_helper() {
+    use redis::{AsyncCommands, PipelineCommands};
+
+    let ctx = TestContext::new();
+    let mut other = ctx.connection();
+    let _: () = redis::cmd("SET")
+        .arg("key")
+        .arg(42)
+        .query(&mut other)
+        .unwrap();
+
+    let mut attempts = 0;
+    block_on_all(ctx.async_connection().and_then(move |con| {
+        redis::aio::transaction(con, &["key"], move |con, mut pipe| {
+            attempts += 1;
+            if attempts == 1 {
+                // abort the first attempt
+                let _: () = redis::cmd("INCR").arg("key").query(&mut other).unwrap();
+            }
+            con.get("key").and_then(move |(con, val): (_, isize)| {
+                pipe.set("key", val + 1).ignore().get("key");
+                pipe.query_async(con)
+            })
+        })
+        .map(|(_con, response): (_, (isize,))| assert_eq!(response, (44,)))
+    }))
+    .unwrap();
+
+    let mut other = ctx.connection();
+    let result = block_on_all(ctx.async_connection().and_then(move |con| {
+        redis::aio::transaction_with_retries(con, &["key"], 0, move |con, mut pipe| {
+            let _: () = redis::cmd("INCR").arg("key").query(&mut other).unwrap();
+            pipe.get("key");
+            pipe.query_async(con)
+        })
+        .map(|(_con, _response): (_, (isize,))| ())
+    }));
+    assert_eq!(result.unwrap_err().kind(), redis::ErrorKind::ExecAbortError);
+}
+
+fn test_cmd(
+    con: &MultiplexedConnection,
+    i: i32,
+) -> Box<Future<Item = (), Error = RedisError> + Send> {
     let key = format!("key{}", i);
     let key_2 = key.clone();
     let key2 = format!("key{}_2", i);
@@ -136,7 +183,7 @@ fn test_cmd(con: &SharedConnection, i: i32) -> Box<Future<Item = (), Error = Red
     )
 }
 
-fn test_error(con: &SharedConnection) -> Box<Future<Item = (), Error = RedisError> + Send> {
+fn test_error(con: &MultiplexedConnection) -> Box<Future<Item = (), Error = RedisError> + Send> {
     Box::new(
         redis::cmd("SET")
             .query_async(con.clone())
@@ -148,12 +195,12 @@ fn test_error(con: &SharedConnection) -> Box<Future<Item = (), Error = RedisErro
 }
 
 #[test]
-fn test_args_shared_connection() {
+fn test_args_multiplexed_connection() {
     let ctx = TestContext::new();
     Runtime::new()
         .unwrap()
         .block_on(future::lazy(|| {
-            ctx.shared_async_connection()
+            ctx.multiplexed_async_connection()
                 .and_then(|con| {
                     let cmds = (0..100).map(move |i| test_cmd(&con, i));
                     future::join_all(cmds).map(|results| {
@@ -166,12 +213,35 @@ fn test_args_shared_connection() {
 }
 
 #[test]
-fn test_args_with_errors_shared_connection() {
+fn test_auto_pipeline_multiplexed_connection() {
+    let mut ctx = TestContext::new();
+    ctx.client
+        .set_auto_pipeline(redis::aio::AutoPipelineConfig {
+            flush_interval: Duration::from_millis(5),
+            max_batch_size: 16,
+        });
+    Runtime::new()
+        .unwrap()
+        .block_on(future::lazy(|| {
+            ctx.multiplexed_async_connection()
+                .and_then(|con| {
+                    let cmds = (0..100).map(move |i| test_cmd(&con, i));
+                    future::join_all(cmds).map(|results| {
+                        assert_eq!(results.len(), 100);
+                    })
+                })
+                .map_err(|err| panic!("{}", err))
+        }))
+        .unwrap();
+}
+
+#[test]
+fn test_args_with_errors_multiplexed_connection() {
     let ctx = TestContext::new();
     Runtime::new()
         .unwrap()
         .block_on(future::lazy(|| {
-            ctx.shared_async_connection()
+            ctx.multiplexed_async_connection()
                 .and_then(|con| {
                     let cmds = (0..100).map(move |i| {
                         if i % 2 == 0 {
@@ -190,12 +260,12 @@ fn test_args_with_errors_shared_connection() {
 }
 
 #[test]
-fn test_transaction_shared_connection() {
+fn test_transaction_multiplexed_connection() {
     let ctx = TestContext::new();
     Runtime::new()
         .unwrap()
         .block_on(future::lazy(|| {
-            ctx.shared_async_connection()
+            ctx.multiplexed_async_connection()
                 .and_then(|con| {
                     let cmds = (0..100).map(move |i| {
                         let foo = i;
@@ -230,3 +300,804 @@ fn test_transaction_shared_connection() {
         }))
         .unwrap();
 }
+
+#[test]
+fn test_pipeline_error_multiplexed_connection() {
+    let ctx = TestContext::new();
+    block_on_all(future::lazy(|| {
+        ctx.multiplexed_async_connection()
+            .and_then(|con| {
+                let mut pipe = redis::pipe();
+                pipe.cmd("SET")
+                    .arg("key")
+                    .arg("value")
+                    .cmd("INCR")
+                    .arg("key")
+                    .cmd("GET")
+                    .arg("key");
+                pipe.query_async::<_, (String, i64, String)>(con.clone())
+                    .then(move |result| {
+                        assert!(result.is_err());
+                        // the responses of the failed pipeline must not leak
+                        redis::cmd("PING").query_async(con)
+                    })
+            })
+            .map(|(_, pong): (_, String)| assert_eq!(pong, "PONG"))
+            .map_err(|err| panic!("{}", err))
+    }))
+    .unwrap();
+}
+
+#[test]
+fn test_cancelled_request_multiplexed_connection() {
+    let ctx = TestContext::new();
+    Runtime::new()
+        .unwrap()
+        .block_on(future::lazy(|| {
+            ctx.multiplexed_async_connection()
+                .and_then(|con| {
+                    let blocked = redis::cmd("BLPOP")
+                        .arg("cancelled_list")
+                        .arg(1)
+                        .query_async::<_, redis::Value>(con.clone());
+                    let timeout = Delay::new(Instant::now() + Duration::from_millis(100));
+                    // drops the BLPOP request while the server still blocks on it
+                    blocked.select2(timeout).then(move |result| {
+                        assert!(result.is_ok());
+                        redis::cmd("SET")
+                            .arg("key")
+                            .arg(42)
+                            .query_async::<_, ()>(con)
+                            .and_then(|(con, ())| redis::cmd("GET").arg("key").query_async(con))
+                    })
+                })
+                .map(|(_, value): (_, i64)| assert_eq!(value, 42))
+                .map_err(|err| panic!("{}", err))
+        }))
+        .unwrap();
+}
+
+#[test]
+fn test_async_commands() {
+    use redis::AsyncCommands;
+
+    let ctx = TestContext::new();
+    block_on_all(ctx.async_connection().and_then(|con| {
+        con.set("key1", b"foo")
+            .and_then(|(con, ()): (_, ())| con.set_multiple(&[("key2", "bar"), ("key3", "baz")]))
+            .and_then(|(con, ()): (_, ())| con.get(&["key1", "key2", "key3"]))
+            .and_then(|(con, values): (_, (String, String, String))| {
+                assert_eq!(
+                    values,
+                    ("foo".to_string(), "bar".to_string(), "baz".to_string())
+                );
+                con.hset_multiple("hash", &[("f1", 1), ("f2", 2)])
+            })
+            .and_then(|(con, ()): (_, ())| con.hgetall("hash"))
+            .map(
+                |(_con, hash): (_, ::std::collections::HashMap<String, i32>)| {
+                    assert_eq!(hash.len(), 2);
+                    assert_eq!(hash.get("f2"), Some(&2));
+                },
+            )
+    }))
+    .unwrap();
+}
+
+#[test]
+fn test_async_ttl_typed() {
+    use redis::keys::Ttl;
+    use redis::AsyncCommands;
+
+    let ctx = TestContext::new();
+    block_on_all(ctx.async_connection().and_then(|con| {
+        con.ttl_typed("key")
+            .and_then(|(con, ttl)| {
+                assert_eq!(ttl, Ttl::NoKey);
+                con.set_ex("key", 1, 100)
+            })
+            .and_then(|(con, ()): (_, ())| con.pttl_typed("key"))
+            .map(|(_con, ttl)| {
+                let ttl = ttl.duration().unwrap();
+                assert!(ttl > Duration::from_secs(90) && ttl <= Duration::from_secs(100));
+            })
+    }))
+    .unwrap();
+}
+
+#[test]
+fn test_async_scan() {
+    use futures::Stream;
+    use redis::options::ScanOptions;
+    use redis::AsyncCommands;
+    use std::collections::HashSet;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    for x in 0..1000 {
+        redis::cmd("SADD").arg("set").arg(x).execute(&mut con);
+        redis::cmd("SET")
+            .arg(format!("key_{}", x))
+            .arg(x)
+            .execute(&mut con);
+    }
+
+    block_on_all(ctx.async_connection().and_then(|con| {
+        let iter = con.sscan::<_, usize>("set");
+        future::loop_fn((iter, HashSet::new()), |(iter, mut members)| {
+            iter.into_future()
+                .map_err(|(err, _)| err)
+                .map(|(member, iter)| match member {
+                    Some(member) => {
+                        members.insert(member);
+                        future::Loop::Continue((iter, members))
+                    }
+                    None => future::Loop::Break((iter, members)),
+                })
+        })
+        .and_then(|(iter, members)| {
+            assert_eq!(members.len(), 1000);
+
+            let con = iter.into_connection().unwrap();
+            let options = ScanOptions::new().with_pattern("key_1??").with_count(100);
+            con.scan_options::<String>(&options).collect()
+        })
+    }))
+    .map(|keys| assert_eq!(keys.len(), 100))
+    .unwrap();
+
+    // a dropped scan does not disturb the connection it came from
+    block_on_all(ctx.multiplexed_async_connection().and_then(|con| {
+        con.clone()
+            .scan::<String>()
+            .take(1)
+            .collect()
+            .and_then(move |keys| {
+                assert_eq!(keys.len(), 1);
+                con.get("key_1")
+            })
+            .map(|(_con, value): (_, usize)| assert_eq!(value, 1))
+    }))
+    .unwrap();
+}
+
+#[test]
+fn test_async_commands_multiplexed_connection() {
+    use redis::AsyncCommands;
+
+    let ctx = TestContext::new();
+    Runtime::new()
+        .unwrap()
+        .block_on(future::lazy(|| {
+            ctx.multiplexed_async_connection()
+                .and_then(|con| {
+                    let writes = (0..10).map(move |i| con.clone().set(format!("key{}", i), i));
+                    future::join_all(writes).map(|_: Vec<(MultiplexedConnection, ())>| ())
+                })
+                .map_err(|err| panic!("{}", err))
+        }))
+        .unwrap();
+}
+
+#[test]
+fn test_script_multiplexed_connection() {
+    let ctx = TestContext::new();
+    let script = redis::Script::new("return {KEYS[1], tonumber(ARGV[1]) + 1}");
+    Runtime::new()
+        .unwrap()
+        .block_on(future::lazy(|| {
+            ctx.multiplexed_async_connection()
+                .and_then(|con| {
+                    redis::cmd("SCRIPT")
+                        .arg("FLUSH")
+                        .query_async(con)
+                        .map(|(con, ()): (MultiplexedConnection, ())| con)
+                })
+                .and_then(|con| script.exists_async(con))
+                .and_then(|(con, exists)| {
+                    assert!(!exists);
+                    // NOSCRIPT is handled by loading the script
+                    script.key("key").arg(41).invoke_async(con)
+                })
+                .and_then(|(con, result): (_, (String, i32))| {
+                    assert_eq!(result, ("key".to_string(), 42));
+                    script.exists_async(con)
+                })
+                .map(|(_con, exists)| assert!(exists))
+                .map_err(|err| panic!("{}", err))
+        }))
+        .unwrap();
+}
+
+#[test]
+fn test_push_sender_multiplexed_connection() {
+    use futures::sync::mpsc;
+    use futures::Stream;
+    use redis::{IntoConnectionInfo, ProtocolVersion, PushKind};
+
+    let ctx = TestContext::new();
+    let info = redis::ConnectionInfo {
+        addr: Box::new(ctx.server.get_client_addr().clone()),
+        protocol: ProtocolVersion::RESP3,
+        .."redis://127.0.0.1/".into_connection_info().unwrap()
+    };
+    let client = redis::Client::open(info).unwrap();
+    let mut other = ctx.connection();
+    let (sender, receiver) = mpsc::unbounded();
+
+    Runtime::new()
+        .unwrap()
+        .block_on(future::lazy(move || {
+            client
+                .get_async_connection()
+                .and_then(move |mut con| {
+                    if con.protocol() != ProtocolVersion::RESP3 {
+                        return future::Either::A(future::ok(()));
+                    }
+                    con.set_push_sender(sender);
+                    future::Either::B(
+                        MultiplexedConnection::new(con)
+                            .and_then(|con| {
+                                redis::cmd("CLIENT")
+                                    .arg("TRACKING")
+                                    .arg("ON")
+                                    .query_async(con)
+                            })
+                            .and_then(|(con, ()): (_, ())| {
+                                redis::cmd("SET").arg("key").arg(1).query_async(con)
+                            })
+                            .and_then(|(con, ()): (_, ())| {
+                                redis::cmd("GET").arg("key").query_async(con)
+                            })
+                            .and_then(move |(con, value): (_, i32)| {
+                                assert_eq!(value, 1);
+                                // modifying the key elsewhere invalidates it
+                                redis::cmd("SET").arg("key").arg(2).execute(&mut other);
+                                redis::cmd("GET").arg("key").query_async(con)
+                            })
+                            .and_then(|(_con, value): (MultiplexedConnection, i32)| {
+                                // the push message does not end up as the reply of a command
+                                assert_eq!(value, 2);
+                                receiver.into_future().map_err(|_| unreachable!())
+                            })
+                            .map(|(push, _)| {
+                                let push = push.unwrap();
+                                assert_eq!(push.kind, PushKind::Invalidate);
+                                assert_eq!(
+                                    push.data,
+                                    vec![redis::Value::Bulk(vec![redis::Value::Data(
+                                        b"key".to_vec()
+                                    )])]
+                                );
+                            }),
+                    )
+                })
+                .map_err(|err| panic!("{}", err))
+        }))
+        .unwrap();
+}
+
+#[test]
+fn test_connection_manager_reconnect() {
+    let ctx = TestContext::new();
+    let mut killer = ctx.connection();
+
+    let mut runtime = Runtime::new().unwrap();
+    let manager = runtime
+        .block_on(future::lazy(|| ctx.client.get_connection_manager()))
+        .unwrap();
+
+    let ping = |manager: redis::aio::ConnectionManager| {
+        redis::cmd("PING")
+            .query_async(manager)
+            .map(|(_, pong): (_, String)| pong)
+    };
+
+    assert_eq!(runtime.block_on(ping(manager.clone())).unwrap(), "PONG");
+
+    // kill every connection but our own so the manager's socket breaks
+    redis::cmd("CLIENT")
+        .arg("KILL")
+        .arg("TYPE")
+        .arg("normal")
+        .arg("SKIPME")
+        .arg("yes")
+        .execute(&mut killer);
+
+    let result = runtime.block_on(ping(manager.clone()));
+    assert_eq!(result.unwrap_err().kind(), redis::ErrorKind::IoError);
+
+    assert_eq!(runtime.block_on(ping(manager.clone())).unwrap(), "PONG");
+}
+
+#[test]
+fn test_custom_transport() {
+    use redis::IntoConnectionInfo;
+
+    let ctx = TestContext::new();
+    let socket_addr = match ctx.server.get_client_addr() {
+        &redis::ConnectionAddr::Tcp(ref host, port) => {
+            std::net::ToSocketAddrs::to_socket_addrs(&(&host[..], port))
+                .unwrap()
+                .next()
+                .unwrap()
+        }
+        _ => return,
+    };
+    let info = "redis://unused/".into_connection_info().unwrap();
+
+    block_on_all(
+        tokio::net::TcpStream::connect(&socket_addr)
+            .from_err()
+            .and_then(move |stream| redis::aio::Connection::from_transport(stream, info))
+            .and_then(|con| redis::cmd("PING").query_async(con))
+            .map(|(_, pong): (_, String)| assert_eq!(pong, "PONG")),
+    )
+    .unwrap();
+}
+
+// Joins two channels into one end of an in-memory message socket.
+struct MessageChannel {
+    sender: futures::sync::mpsc::UnboundedSender<Vec<u8>>,
+    receiver: futures::sync::mpsc::UnboundedReceiver<Vec<u8>>,
+}
+
+impl futures::Stream for MessageChannel {
+    type Item = Vec<u8>;
+    type Error = std::io::Error;
+
+    fn poll(&mut self) -> futures::Poll<Option<Vec<u8>>, std::io::Error> {
+        Ok(self.receiver.poll().unwrap())
+    }
+}
+
+impl futures::Sink for MessageChannel {
+    type SinkItem = Vec<u8>;
+    type SinkError = std::io::Error;
+
+    fn start_send(&mut self, item: Vec<u8>) -> futures::StartSend<Vec<u8>, std::io::Error> {
+        self.sender
+            .start_send(item)
+            .map_err(|_| std::io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn poll_complete(&mut self) -> futures::Poll<(), std::io::Error> {
+        self.sender
+            .poll_complete()
+            .map_err(|_| std::io::ErrorKind::BrokenPipe.into())
+    }
+}
+
+#[test]
+fn test_websocket_transport() {
+    use futures::sync::mpsc::unbounded;
+    use futures::Stream;
+    use redis::aio::{Connection, WebSocketTransport};
+    use redis::{IntoConnectionInfo, Value};
+
+    let (client_sender, server_receiver) = unbounded();
+    let (server_sender, client_receiver) = unbounded();
+    let socket = MessageChannel {
+        sender: client_sender,
+        receiver: client_receiver,
+    };
+
+    let mut runtime = Runtime::new().unwrap();
+    // answers every command and splits the replies across messages, the
+    // connection setup sends a pipeline as one message
+    runtime.spawn(server_receiver.for_each(move |message: Vec<u8>| {
+        let mut commands = &message[..];
+        while !commands.is_empty() {
+            let reply: &[u8] = match redis::Parser::new(&mut commands).parse_value().unwrap() {
+                Value::Bulk(ref args) if args[0] == Value::Data(b"PING".to_vec()) => b"+PONG\r\n",
+                _ => b"+OK\r\n",
+            };
+            for chunk in reply.chunks(3) {
+                server_sender.unbounded_send(chunk.to_vec()).unwrap();
+            }
+        }
+        Ok(())
+    }));
+
+    let info = "redis://gateway/".into_connection_info().unwrap();
+    let result = runtime.block_on(
+        Connection::from_transport(WebSocketTransport::new(socket), info)
+            .and_then(|con| redis::cmd("PING").query_async(con))
+            .and_then(|(con, pong): (_, String)| {
+                redis::cmd("SET")
+                    .arg("key")
+                    .arg("value")
+                    .query_async(con)
+                    .map(move |(_, ok): (_, String)| (pong, ok))
+            }),
+    );
+    assert_eq!(result.unwrap(), ("PONG".to_string(), "OK".to_string()));
+}
+
+#[test]
+fn test_query_reply() {
+    use redis::ValueRef;
+
+    let ctx = TestContext::new();
+    let value = vec![b'x'; 100 * 1024];
+
+    let result = block_on_all(ctx.async_connection().and_then({
+        let value = value.clone();
+        move |con| {
+            redis::cmd("RPUSH")
+                .arg("list")
+                .arg(value)
+                .arg(42)
+                .query_async(con)
+                .and_then(|(con, ()): (_, ())| {
+                    con.query_reply(redis::cmd("LRANGE").arg("list").arg(0).arg(-1))
+                })
+        }
+    }))
+    .unwrap();
+    assert_eq!(
+        result.1.value(),
+        Ok(ValueRef::Bulk(vec![
+            ValueRef::Data(&value),
+            ValueRef::Data(b"42")
+        ]))
+    );
+}
+
+#[test]
+fn test_subscribe_multiplexed_connection() {
+    use futures::sync::mpsc;
+    use futures::Stream;
+    use redis::{IntoConnectionInfo, ProtocolVersion, PushKind};
+
+    let ctx = TestContext::new();
+    let info = redis::ConnectionInfo {
+        addr: Box::new(ctx.server.get_client_addr().clone()),
+        protocol: ProtocolVersion::RESP3,
+        .."redis://127.0.0.1/".into_connection_info().unwrap()
+    };
+    let client = redis::Client::open(info).unwrap();
+    let mut publisher = ctx.connection();
+    let (sender, receiver) = mpsc::unbounded();
+
+    Runtime::new()
+        .unwrap()
+        .block_on(future::lazy(move || {
+            client
+                .get_multiplexed_async_connection()
+                .and_then(move |con| {
+                    if con.protocol() != ProtocolVersion::RESP3 {
+                        let result = con.subscribe("foo").then(|result| {
+                            assert_eq!(
+                                result.err().unwrap().kind(),
+                                redis::ErrorKind::InvalidClientConfig
+                            );
+                            Ok(())
+                        });
+                        return future::Either::A(result);
+                    }
+                    con.set_push_sender(sender);
+                    future::Either::B(
+                        con.subscribe(&["foo", "bar"])
+                            .and_then(move |(con, ())| {
+                                redis::cmd("PUBLISH")
+                                    .arg("foo")
+                                    .arg("hello")
+                                    .execute(&mut publisher);
+                                // commands can still be sent
+                                redis::cmd("PING").query_async(con)
+                            })
+                            .and_then(|(con, pong): (_, String)| {
+                                assert_eq!(pong, "PONG");
+                                receiver
+                                    .into_future()
+                                    .map(|(push, _)| (con, push.unwrap()))
+                                    .map_err(|_| unreachable!())
+                            })
+                            .and_then(|(con, push)| {
+                                assert_eq!(push.kind, PushKind::Message);
+                                assert_eq!(
+                                    push.data,
+                                    vec![
+                                        redis::Value::Data(b"foo".to_vec()),
+                                        redis::Value::Data(b"hello".to_vec()),
+                                    ]
+                                );
+                                con.unsubscribe(&["foo", "bar"])
+                            })
+                            .and_then(|(con, ())| redis::cmd("PING").query_async(con))
+                            .map(|(_con, pong): (MultiplexedConnection, String)| {
+                                assert_eq!(pong, "PONG");
+                            }),
+                    )
+                })
+                .map_err(|err| panic!("{}", err))
+        }))
+        .unwrap();
+}
+
+#[test]
+fn test_async_pubsub() {
+    use futures::Stream;
+
+    let ctx = TestContext::new();
+    let mut publisher = ctx.connection();
+    let client = ctx.client.clone();
+
+    Runtime::new()
+        .unwrap()
+        .block_on(future::lazy(move || {
+            client
+                .get_async_pubsub()
+                .and_then(|pubsub| {
+                    let sink = pubsub.sink();
+                    pubsub
+                        .subscribe("foo")
+                        .and_then(move |()| {
+                            redis::cmd("PUBLISH")
+                                .arg("foo")
+                                .arg("hello")
+                                .execute(&mut publisher);
+                            pubsub.into_future().map_err(|(err, _)| err).and_then(
+                                move |(msg, pubsub)| {
+                                    let msg = msg.unwrap();
+                                    assert_eq!(msg.get_channel_name(), "foo");
+                                    assert_eq!(msg.get_payload(), Ok("hello".to_string()));
+                                    // subscriptions change while the stream is active
+                                    sink.unsubscribe("foo")
+                                        .and_then(move |()| sink.psubscribe("ba*"))
+                                        .map(move |()| (pubsub, publisher))
+                                },
+                            )
+                        })
+                        .and_then(|(pubsub, mut publisher)| {
+                            redis::cmd("PUBLISH")
+                                .arg("foo")
+                                .arg("ignored")
+                                .execute(&mut publisher);
+                            redis::cmd("PUBLISH")
+                                .arg("bar")
+                                .arg("world")
+                                .execute(&mut publisher);
+                            pubsub.into_future().map_err(|(err, _)| err)
+                        })
+                        .map(|(msg, _pubsub)| {
+                            let msg = msg.unwrap();
+                            assert_eq!(msg.get_channel_name(), "bar");
+                            assert_eq!(msg.get_pattern(), Ok("ba*".to_string()));
+                            assert_eq!(msg.get_payload(), Ok("world".to_string()));
+                        })
+                })
+                .map_err(|err| panic!("{}", err))
+        }))
+        .unwrap();
+}
+
+#[test]
+fn test_pubsub_manager_resubscribes() {
+    use futures::Stream;
+    use redis::aio::PubSubEvent;
+
+    let ctx = TestContext::new();
+    let mut publisher = ctx.connection();
+    let client = ctx.client.clone();
+
+    Runtime::new()
+        .unwrap()
+        .block_on(future::lazy(move || {
+            client
+                .get_pubsub_manager()
+                .and_then(|manager| {
+                    manager
+                        .subscribe("foo")
+                        .and_then(move |()| manager.psubscribe("ba*").map(|()| manager))
+                })
+                .and_then(move |manager| {
+                    let _: () = redis::cmd("CLIENT")
+                        .arg("KILL")
+                        .arg("TYPE")
+                        .arg("pubsub")
+                        .query(&mut publisher)
+                        .unwrap();
+                    manager
+                        .into_future()
+                        .map_err(|(err, _)| err)
+                        .map(move |(event, manager)| (event, manager, publisher))
+                })
+                .and_then(|(event, manager, mut publisher)| {
+                    match event {
+                        Some(PubSubEvent::Resubscribed) => (),
+                        _ => panic!("expected to resubscribe"),
+                    }
+                    redis::cmd("PUBLISH")
+                        .arg("foo")
+                        .arg("one")
+                        .execute(&mut publisher);
+                    redis::cmd("PUBLISH")
+                        .arg("bar")
+                        .arg("two")
+                        .execute(&mut publisher);
+                    manager.take(2).collect()
+                })
+                .map(|events| {
+                    let payloads: Vec<String> = events
+                        .into_iter()
+                        .map(|event| match event {
+                            PubSubEvent::Message(msg) => msg.get_payload().unwrap(),
+                            PubSubEvent::Resubscribed => panic!("resubscribed twice"),
+                        })
+                        .collect();
+                    assert_eq!(payloads, vec!["one", "two"]);
+                })
+                .map_err(|err| panic!("{}", err))
+        }))
+        .unwrap();
+}
+
+#[test]
+fn test_async_pubsub_bounded_buffer() {
+    use futures::Stream;
+    use redis::aio::{OverflowPolicy, PubSub, PubSubConfig};
+
+    fn publish_and_drain(
+        ctx: &TestContext,
+        overflow: OverflowPolicy,
+    ) -> (Vec<Result<i32, redis::ErrorKind>>, u64) {
+        // the overflow error comes before the buffered messages
+        let count = if overflow == OverflowPolicy::Error {
+            3
+        } else {
+            2
+        };
+        let mut publisher = ctx.connection();
+        let client = ctx.client.clone();
+        let config = PubSubConfig {
+            buffer_size: Some(2),
+            overflow,
+        };
+        Runtime::new()
+            .unwrap()
+            .block_on(future::lazy(move || {
+                client
+                    .get_async_connection()
+                    .and_then(move |con| PubSub::with_config(con, config))
+                    .and_then(|pubsub| pubsub.subscribe("foo").map(move |()| pubsub))
+                    .and_then(move |pubsub| {
+                        for i in 1..6 {
+                            redis::cmd("PUBLISH")
+                                .arg("foo")
+                                .arg(i)
+                                .execute(&mut publisher);
+                        }
+                        // wait until all messages arrived
+                        Delay::new(Instant::now() + Duration::from_millis(200))
+                            .map(move |_| pubsub)
+                            .map_err(|err| panic!("{}", err))
+                    })
+                    .and_then(move |pubsub| {
+                        let dropped = pubsub.dropped_messages();
+                        assert_eq!(pubsub.buffered_messages(), 2);
+                        // errors do not end the stream
+                        pubsub
+                            .then(|result| {
+                                Ok::<_, ()>(
+                                    result
+                                        .map(|msg| msg.get_payload().unwrap())
+                                        .map_err(|err| err.kind()),
+                                )
+                            })
+                            .take(count)
+                            .collect()
+                            .map(move |items| (items, dropped))
+                            .map_err(|()| unreachable!())
+                    })
+                    .map_err(|err: RedisError| panic!("{}", err))
+            }))
+            .unwrap()
+    }
+
+    let ctx = TestContext::new();
+    let (items, dropped) = publish_and_drain(&ctx, OverflowPolicy::DropOldest);
+    assert_eq!(dropped, 3);
+    assert_eq!(items, vec![Ok(4), Ok(5)]);
+
+    let (items, dropped) = publish_and_drain(&ctx, OverflowPolicy::DropNewest);
+    assert_eq!(dropped, 3);
+    assert_eq!(items, vec![Ok(1), Ok(2)]);
+
+    let (items, dropped) = publish_and_drain(&ctx, OverflowPolicy::Error);
+    assert_eq!(dropped, 3);
+    assert_eq!(items, vec![Err(redis::ErrorKind::BufferFull), Ok(1), Ok(2)]);
+}
+
+#[test]
+fn test_async_pubsub_subscription_handles() {
+    let ctx = TestContext::new();
+    let mut publisher = ctx.connection();
+    let client = ctx.client.clone();
+
+    Runtime::new()
+        .unwrap()
+        .block_on(future::lazy(move || {
+            client
+                .get_async_pubsub()
+                .and_then(|pubsub| {
+                    let sink = pubsub.sink();
+                    pubsub
+                        .subscribe_handle(&["foo", "bar"])
+                        .join(sink.subscribe_handle("foo"))
+                        .map(move |handles| (pubsub, handles))
+                })
+                .and_then(move |(pubsub, (both, foo))| {
+                    assert_eq!(both.channels(), &[b"foo".to_vec(), b"bar".to_vec()][..]);
+                    let numsub = |publisher: &mut redis::Connection| -> Vec<(String, u32)> {
+                        redis::cmd("PUBSUB")
+                            .arg("NUMSUB")
+                            .arg("foo")
+                            .arg("bar")
+                            .query(publisher)
+                            .unwrap()
+                    };
+                    assert_eq!(
+                        numsub(&mut publisher),
+                        vec![("foo".to_string(), 1), ("bar".to_string(), 1)]
+                    );
+                    // "foo" stays subscribed through the other handle
+                    both.unsubscribe().map(move |()| {
+                        assert_eq!(
+                            numsub(&mut publisher),
+                            vec![("foo".to_string(), 1), ("bar".to_string(), 0)]
+                        );
+                        drop(foo);
+                        (pubsub, publisher, numsub)
+                    })
+                })
+                .and_then(|(pubsub, publisher, numsub)| {
+                    // the dropped handle unsubscribes in the background
+                    Delay::new(Instant::now() + Duration::from_millis(100))
+                        .map_err(|err| panic!("{}", err))
+                        .map(move |_| (pubsub, publisher, numsub))
+                })
+                .map(|(_pubsub, mut publisher, numsub)| {
+                    assert_eq!(
+                        numsub(&mut publisher),
+                        vec![("foo".to_string(), 0), ("bar".to_string(), 0)]
+                    );
+                })
+                .map_err(|err| panic!("{}", err))
+        }))
+        .unwrap();
+}
+
+#[test]
+fn test_response_timeout() {
+    use redis::IntoConnectionInfo;
+
+    let ctx = TestContext::new();
+    let info = redis::ConnectionInfo {
+        addr: Box::new(ctx.server.get_client_addr().clone()),
+        response_timeout: Some(Duration::from_millis(100)),
+        .."redis://127.0.0.1/".into_connection_info().unwrap()
+    };
+    let client = redis::Client::open(info).unwrap();
+
+    let result = Runtime::new().unwrap().block_on(future::lazy(move || {
+        client
+            .get_multiplexed_async_connection()
+            .and_then(|con| {
+                redis::cmd("PING")
+                    .query_async(con)
+                    .map(|(con, pong): (_, String)| {
+                        assert_eq!(pong, "PONG");
+                        con
+                    })
+            })
+            .and_then(|con| {
+                redis::cmd("BLPOP")
+                    .arg("empty_list")
+                    .arg(2)
+                    .query_async::<_, Option<(String, String)>>(con)
+            })
+    }));
+    assert!(result.err().unwrap().is_timeout());
+}
@@ -0,0 +1,70 @@
+extern crate redis;
+
+extern crate async_std;
+extern crate futures;
+extern crate futures_util;
+
+use futures::Future;
+use futures_util::compat::Future01CompatExt;
+
+use support::*;
+
+use redis::IntoConnectionInfo;
+
+mod support;
+
+#[test]
+fn test_args() {
+    let ctx = TestContext::new();
+    let connect = ctx.async_connection();
+
+    let result = async_std::task::block_on(
+        connect
+            .and_then(|con| {
+                redis::cmd("SET")
+                    .arg("key1")
+                    .arg(b"foo")
+                    .query_async(con)
+                    .and_then(|(con, ())| redis::cmd("SET").arg(&["key2", "bar"]).query_async(con))
+                    .and_then(|(con, ())| {
+                        redis::cmd("MGET")
+                            .arg(&["key1", "key2"])
+                            .query_async(con)
+                            .map(|t| t.1)
+                    })
+            })
+            .compat(),
+    );
+    assert_eq!(result, Ok(("foo".to_string(), b"bar".to_vec())));
+}
+
+#[test]
+fn test_args_multiplexed_connection() {
+    let ctx = TestContext::new();
+    let connect = ctx.multiplexed_async_connection();
+
+    let result = async_std::task::block_on(
+        connect
+            .and_then(|con| {
+                redis::cmd("SET")
+                    .arg("key1")
+                    .arg(b"foo")
+                    .query_async(con)
+                    .and_then(|(con, ())| redis::cmd("GET").arg("key1").query_async(con))
+                    .map(|(_, value): (_, String)| value)
+            })
+            .compat(),
+    );
+    assert_eq!(result, Ok("foo".to_string()));
+}
+
+#[test]
+fn test_connection_refused() {
+    let info = "redis://127.0.0.1:1/".into_connection_info().unwrap();
+    let client = redis::Client::open(info).unwrap();
+
+    let err = async_std::task::block_on(client.get_async_connection().compat())
+        .err()
+        .expect("connect should fail");
+    assert!(err.is_connection_refusal());
+}
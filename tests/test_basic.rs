@@ -1,6 +1,9 @@
 extern crate net2;
 extern crate rand;
 extern crate redis;
+#[cfg(feature = "with-serde")]
+#[macro_use]
+extern crate serde_derive;
 
 use redis::{Commands, ControlFlow, PipelineCommands, PubSubCommands};
 
@@ -31,6 +34,27 @@ fn test_parse_redis_url() {
     }
 }
 
+#[test]
+fn test_timeouts_from_url() {
+    use redis::IntoConnectionInfo;
+
+    let info = "redis://127.0.0.1/?connect_timeout=0.5&response_timeout=2"
+        .into_connection_info()
+        .unwrap();
+    assert_eq!(info.connect_timeout, Some(Duration::from_millis(500)));
+    assert_eq!(info.response_timeout, Some(Duration::from_secs(2)));
+
+    let info = "redis://127.0.0.1/".into_connection_info().unwrap();
+    assert_eq!(info.connect_timeout, None);
+    assert_eq!(info.response_timeout, None);
+
+    let result = "redis://127.0.0.1/?connect_timeout=soon".into_connection_info();
+    assert_eq!(
+        result.unwrap_err().kind(),
+        redis::ErrorKind::InvalidClientConfig
+    );
+}
+
 #[test]
 fn test_args() {
     let ctx = TestContext::new();
@@ -60,6 +84,853 @@ fn test_getset() {
     );
 }
 
+#[test]
+fn test_set_options() {
+    use redis::options::{ExistenceCheck, SetExpiry, SetOptions};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let nx = SetOptions::new()
+        .conditional_set(ExistenceCheck::Nx)
+        .with_expiration(SetExpiry::Ex(100));
+    assert_eq!(con.set_options("key", 1, &nx), Ok(true));
+    assert_eq!(con.set_options("key", 2, &nx), Ok(false));
+    assert_eq!(con.get("key"), Ok(1));
+    let ttl: i64 = con.ttl("key").unwrap();
+    assert!(ttl > 90 && ttl <= 100);
+
+    let xx = SetOptions::new().conditional_set(ExistenceCheck::Xx);
+    assert_eq!(con.set_options("missing", 1, &xx), Ok(false));
+    assert_eq!(con.exists("missing"), Ok(false));
+
+    let px = SetOptions::new().with_expiration(SetExpiry::Px(100_000));
+    assert_eq!(con.set_options("key", 3, &px), Ok(true));
+    let ttl: i64 = redis::cmd("PTTL").arg("key").query(&mut con).unwrap();
+    assert!(ttl > 90_000 && ttl <= 100_000);
+
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![6, 2] {
+        return;
+    }
+    let get = SetOptions::new()
+        .with_expiration(SetExpiry::KeepTtl)
+        .get(true);
+    assert_eq!(con.set_options("key", 4, &get), Ok(Some(3)));
+    assert_eq!(con.set_options("other", 1, &get), Ok(None::<i32>));
+    let ttl: i64 = redis::cmd("PTTL").arg("key").query(&mut con).unwrap();
+    assert!(ttl > 0);
+
+    let at = SetOptions::new().with_expiration(SetExpiry::ExAt(4_000_000_000));
+    assert_eq!(con.set_options("key", 5, &at), Ok(true));
+    let ttl: i64 = con.ttl("key").unwrap();
+    assert!(ttl > 1_000_000_000);
+}
+
+#[test]
+fn test_copy() {
+    use redis::options::CopyOptions;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![6, 2] {
+        return;
+    }
+
+    let _: () = con.rpush("list", &[1, 2, 3]).unwrap();
+    assert_eq!(con.copy("list", "copy"), Ok(true));
+    assert_eq!(con.lrange("copy", 0, -1), Ok(vec![1, 2, 3]));
+    let _: () = con.set("other", 1).unwrap();
+    assert_eq!(con.copy("list", "other"), Ok(false));
+
+    let replace = CopyOptions::new().replace(true);
+    assert_eq!(con.copy_options("list", "other", &replace), Ok(true));
+    assert_eq!(con.lrange("other", 0, -1), Ok(vec![1, 2, 3]));
+
+    let other_db = CopyOptions::new().db(1);
+    assert_eq!(con.copy_options("list", "list", &other_db), Ok(true));
+    assert_eq!(con.copy_options("list", "list", &other_db), Ok(false));
+    let _: () = redis::cmd("SELECT").arg(1).query(&mut con).unwrap();
+    assert_eq!(con.lrange("list", 0, -1), Ok(vec![1, 2, 3]));
+}
+
+#[test]
+fn test_expire_options() {
+    use redis::options::ExpireOption;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let major: u32 = version.split('.').next().unwrap().parse().unwrap();
+    if major < 7 {
+        return;
+    }
+
+    let _: () = con.set("key", 1).unwrap();
+    assert_eq!(con.expire_time("key"), Ok(-1));
+    assert_eq!(con.expire_time("missing"), Ok(-2));
+    assert_eq!(con.expire_options("key", 100, ExpireOption::Xx), Ok(false));
+    assert_eq!(con.expire_options("key", 100, ExpireOption::Nx), Ok(true));
+    assert_eq!(con.expire_options("key", 200, ExpireOption::Nx), Ok(false));
+    assert_eq!(con.expire_options("key", 50, ExpireOption::Gt), Ok(false));
+    assert_eq!(con.expire_options("key", 200, ExpireOption::Gt), Ok(true));
+    assert_eq!(
+        con.pexpire_options("key", 300_000, ExpireOption::Lt),
+        Ok(false)
+    );
+    assert_eq!(
+        con.pexpire_options("key", 150_000, ExpireOption::Lt),
+        Ok(true)
+    );
+
+    let expire_time: i64 = con.expire_time("key").unwrap();
+    let pexpire_time: i64 = con.pexpire_time("key").unwrap();
+    assert!((expire_time - pexpire_time / 1000).abs() <= 1);
+    let now: i64 = redis::cmd("TIME").query::<(i64, i64)>(&mut con).unwrap().0;
+    assert!(expire_time > now + 140 && expire_time <= now + 151);
+}
+
+#[test]
+fn test_ttl_typed() {
+    use redis::keys::Ttl;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    assert_eq!(con.ttl_typed("key"), Ok(Ttl::NoKey));
+    let _: () = con.set("key", 1).unwrap();
+    assert_eq!(con.ttl_typed("key"), Ok(Ttl::NoExpiry));
+    assert_eq!(con.pttl_typed("key"), Ok(Ttl::NoExpiry));
+    let _: () = con.expire("key", 100).unwrap();
+    let ttl = con.ttl_typed("key").unwrap().duration().unwrap();
+    assert!(ttl > Duration::from_secs(90) && ttl <= Duration::from_secs(100));
+    let ttl = con.pttl_typed("key").unwrap().duration().unwrap();
+    assert!(ttl > Duration::from_secs(90) && ttl <= Duration::from_secs(100));
+    assert_eq!(
+        Ttl::from_millis(1500),
+        Ttl::Expires(Duration::from_millis(1500))
+    );
+}
+
+#[test]
+fn test_lpos() {
+    use redis::options::LposOptions;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![6, 0, 6] {
+        return;
+    }
+
+    let _: () = con.rpush("list", &["a", "b", "c", "b", "d", "b"]).unwrap();
+    assert_eq!(con.lpos("list", "b"), Ok(Some(1usize)));
+    assert_eq!(con.lpos("list", "x"), Ok(None::<usize>));
+
+    let options = LposOptions::new().rank(-1);
+    assert_eq!(con.lpos_options("list", "b", &options), Ok(Some(5usize)));
+    let options = LposOptions::new().rank(2).count(0);
+    assert_eq!(con.lpos_options("list", "b", &options), Ok(vec![3usize, 5]));
+    let options = LposOptions::new().count(0).maxlen(4);
+    assert_eq!(con.lpos_options("list", "b", &options), Ok(vec![1usize, 3]));
+    let options = LposOptions::new().count(2);
+    assert_eq!(
+        con.lpos_options("list", "x", &options),
+        Ok(Vec::<usize>::new())
+    );
+}
+
+#[test]
+fn test_zrange_options() {
+    use redis::options::ZRangeOptions;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![6, 2] {
+        return;
+    }
+
+    let _: () = con
+        .zadd_multiple("zset", &[(1, "a"), (2, "b"), (3, "c"), (4, "d")])
+        .unwrap();
+
+    let options = ZRangeOptions::new()
+        .by_score()
+        .rev()
+        .limit(1, 2)
+        .with_scores();
+    let members: Vec<(String, f64)> = con.zrange_options("zset", "+inf", "(1", &options).unwrap();
+    assert_eq!(
+        members,
+        vec![("c".to_string(), 3.0), ("b".to_string(), 2.0)]
+    );
+
+    let options = ZRangeOptions::new().by_lex();
+    let members: Vec<String> = con.zrange_options("zset", "[b", "[c", &options).unwrap();
+    assert_eq!(members, vec!["b", "c"]);
+
+    assert_eq!(con.zrangestore("dst", "zset", 0, 1), Ok(2));
+    assert_eq!(
+        con.zrange("dst", 0, -1),
+        Ok(vec!["a".to_string(), "b".to_string()])
+    );
+
+    let options = ZRangeOptions::new().by_score();
+    assert_eq!(
+        con.zrangestore_options("dst", "zset", 3, "+inf", &options),
+        Ok(2)
+    );
+    let members: Vec<(String, f64)> = con.zrange_withscores("dst", 0, -1).unwrap();
+    assert_eq!(
+        members,
+        vec![("c".to_string(), 3.0), ("d".to_string(), 4.0)]
+    );
+}
+
+#[test]
+fn test_zset_operations() {
+    use redis::options::{Aggregate, ZAggregateOptions};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![6, 2] {
+        return;
+    }
+
+    let _: () = con
+        .zadd_multiple("zset1", &[(1, "a"), (2, "b"), (3, "c")])
+        .unwrap();
+    let _: () = con.zadd_multiple("zset2", &[(10, "b"), (20, "d")]).unwrap();
+
+    assert_eq!(
+        con.zdiff(&["zset1", "zset2"]),
+        Ok(vec!["a".to_string(), "c".to_string()])
+    );
+    let members: Vec<(String, f64)> = con.zdiff_withscores(&["zset1", "zset2"]).unwrap();
+    assert_eq!(
+        members,
+        vec![("a".to_string(), 1.0), ("c".to_string(), 3.0)]
+    );
+
+    assert_eq!(con.zinter(&["zset1", "zset2"]), Ok(vec!["b".to_string()]));
+    let members: Vec<(String, f64)> = con.zinter_withscores(&["zset1", "zset2"]).unwrap();
+    assert_eq!(members, vec![("b".to_string(), 12.0)]);
+
+    let options = ZAggregateOptions::new()
+        .weights(&[2.0, 1.0])
+        .aggregate(Aggregate::Max)
+        .with_scores();
+    let members: Vec<(String, f64)> = con.zunion_options(&["zset1", "zset2"], &options).unwrap();
+    assert_eq!(
+        members,
+        vec![
+            ("a".to_string(), 2.0),
+            ("c".to_string(), 6.0),
+            ("b".to_string(), 10.0),
+            ("d".to_string(), 20.0),
+        ]
+    );
+    let members: Vec<String> = con.zunion(&["zset1", "zset2"]).unwrap();
+    assert_eq!(members.len(), 4);
+
+    let options = ZAggregateOptions::new()
+        .aggregate(Aggregate::Min)
+        .with_scores();
+    let members: Vec<(String, f64)> = con.zinter_options(&["zset1", "zset2"], &options).unwrap();
+    assert_eq!(members, vec![("b".to_string(), 2.0)]);
+}
+
+#[test]
+fn test_geosearch() {
+    use redis::geo::{GeoSearchOptions, GeoSearchResult, Unit};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![6, 2] {
+        return;
+    }
+
+    let _: () = redis::cmd("GEOADD")
+        .arg("sicily")
+        .arg(&["13.361389", "38.115556", "Palermo"])
+        .arg(&["15.087269", "37.502669", "Catania"])
+        .query(&mut con)
+        .unwrap();
+
+    let options = GeoSearchOptions::from_member("Palermo")
+        .by_radius(200.0, Unit::Kilometers)
+        .desc()
+        .with_dist()
+        .with_coord()
+        .with_hash();
+    let results: Vec<GeoSearchResult> = con.geosearch("sicily", &options).unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].name, "Catania");
+    let dist = results[0].dist.unwrap();
+    assert!(dist > 166.0 && dist < 167.0);
+    let (longitude, latitude) = results[0].coord.unwrap();
+    assert!((longitude - 15.087269).abs() < 0.001);
+    assert!((latitude - 37.502669).abs() < 0.001);
+    assert!(results[0].hash.is_some());
+    assert_eq!(results[1].name, "Palermo");
+
+    let options = GeoSearchOptions::from_lonlat(15.0, 37.0)
+        .by_box(400.0, 400.0, Unit::Kilometers)
+        .asc()
+        .count(1);
+    let results: Vec<GeoSearchResult> = con.geosearch("sicily", &options).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].name, "Catania");
+    assert_eq!(results[0].dist, None);
+
+    let options = GeoSearchOptions::from_member("Palermo").by_radius(100.0, Unit::Kilometers);
+    assert_eq!(con.geosearch_store("near", "sicily", &options), Ok(1));
+    let options = GeoSearchOptions::from_member("Palermo").by_radius(200.0, Unit::Kilometers);
+    assert_eq!(con.geosearch_store_dist("dists", "sicily", &options), Ok(2));
+    let dists: Vec<(String, f64)> = con.zrange_withscores("dists", 0, -1).unwrap();
+    assert_eq!(dists[0], ("Palermo".to_string(), 0.0));
+}
+
+#[test]
+fn test_bitfield() {
+    use redis::options::{BitFieldOptions, BitFieldType, BitOffset, Overflow};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let counter = BitFieldType::Unsigned(8);
+    let options = BitFieldOptions::new()
+        .set(counter, BitOffset::Field(1), 250)
+        .incr_by(counter, BitOffset::Field(1), 10)
+        .overflow(Overflow::Sat)
+        .incr_by(counter, 8, 250)
+        .overflow(Overflow::Fail)
+        .incr_by(counter, 8, 1)
+        .get(BitFieldType::Signed(4), 0);
+    let values: Vec<Option<i64>> = con.bitfield("bits", &options).unwrap();
+    assert_eq!(values, vec![Some(0), Some(4), Some(255), None, Some(0)]);
+
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![6, 0] {
+        return;
+    }
+    let options = BitFieldOptions::new().get(counter, BitOffset::Field(1));
+    let values: Vec<Option<i64>> = con.bitfield_ro("bits", &options).unwrap();
+    assert_eq!(values, vec![Some(255)]);
+}
+
+#[test]
+fn test_bit_ranges() {
+    use redis::options::BitRange;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    // 0xff 0xf0 0x00
+    let _: () = con.set("bits", &[0xffu8, 0xf0, 0x00][..]).unwrap();
+    assert_eq!(con.bitcount_in_range("bits", BitRange::Bytes(1, -1)), Ok(4));
+    assert_eq!(con.bitpos("bits", false), Ok(12));
+    assert_eq!(
+        con.bitpos_in_range("bits", true, BitRange::Bytes(1, 2)),
+        Ok(8)
+    );
+    assert_eq!(
+        con.bitpos_in_range("bits", true, BitRange::Bytes(2, 2)),
+        Ok(-1)
+    );
+
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![7, 0] {
+        return;
+    }
+    assert_eq!(con.bitcount_in_range("bits", BitRange::Bits(4, 13)), Ok(8));
+    assert_eq!(
+        con.bitpos_in_range("bits", true, BitRange::Bits(14, -1)),
+        Ok(-1)
+    );
+    assert_eq!(
+        con.bitpos_in_range("bits", false, BitRange::Bits(3, 15)),
+        Ok(12)
+    );
+}
+
+#[test]
+fn test_lcs() {
+    use redis::options::{LcsMatch, LcsOptions, LcsReply};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![7, 0] {
+        return;
+    }
+
+    let _: () = con.set("key1", "ohmytext").unwrap();
+    let _: () = con.set("key2", "mynewtext").unwrap();
+    assert_eq!(con.lcs("key1", "key2"), Ok("mytext".to_string()));
+    assert_eq!(con.lcs_len("key1", "key2"), Ok(6));
+
+    let reply: LcsReply = con.lcs_idx("key1", "key2", &LcsOptions::new()).unwrap();
+    assert_eq!(reply.len, 6);
+    assert_eq!(
+        reply.matches,
+        vec![
+            LcsMatch {
+                first: (4, 7),
+                second: (5, 8),
+                len: None,
+            },
+            LcsMatch {
+                first: (2, 3),
+                second: (0, 1),
+                len: None,
+            },
+        ]
+    );
+
+    let options = LcsOptions::new().min_match_len(4).with_match_len();
+    let reply: LcsReply = con.lcs_idx("key1", "key2", &options).unwrap();
+    assert_eq!(
+        reply.matches,
+        vec![LcsMatch {
+            first: (4, 7),
+            second: (5, 8),
+            len: Some(4),
+        }]
+    );
+}
+
+#[test]
+fn test_hash_field_expiration() {
+    use redis::keys::Ttl;
+    use redis::options::{ExpireOption, FieldExpireResult, FieldPersistResult};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![7, 4] {
+        return;
+    }
+
+    let _: () = con
+        .hset_multiple("hash", &[("a", 1), ("b", 2), ("c", 3)])
+        .unwrap();
+
+    let results: Vec<FieldExpireResult> = con.hexpire("hash", 100, &["a", "missing"]).unwrap();
+    assert_eq!(
+        results,
+        vec![FieldExpireResult::Set, FieldExpireResult::NoField]
+    );
+    let results: Vec<FieldExpireResult> = con
+        .hexpire_options("hash", 200, ExpireOption::Lt, &["a"])
+        .unwrap();
+    assert_eq!(results, vec![FieldExpireResult::ConditionNotMet]);
+    let results: Vec<FieldExpireResult> = con.hpexpire("hash", 100_000, &["b"]).unwrap();
+    assert_eq!(results, vec![FieldExpireResult::Set]);
+    let results: Vec<FieldExpireResult> = con.hexpire_at("hash", 1, &["c"]).unwrap();
+    assert_eq!(results, vec![FieldExpireResult::Deleted]);
+    assert_eq!(con.hexists("hash", "c"), Ok(false));
+
+    let ttls: Vec<i64> = con.httl("hash", &["a", "missing"]).unwrap();
+    let ttls: Vec<Ttl> = ttls.into_iter().map(Ttl::from_secs).collect();
+    assert!(ttls[0].duration().unwrap().as_secs() > 90);
+    assert_eq!(ttls[1], Ttl::NoKey);
+    let ttls: Vec<i64> = con.hpttl("hash", &["b"]).unwrap();
+    assert!(ttls[0] > 90_000);
+    let times: Vec<i64> = con.hexpire_time("hash", &["a"]).unwrap();
+    assert!(times[0] > 1_700_000_000);
+    let times: Vec<i64> = con.hpexpire_time("hash", &["a"]).unwrap();
+    assert!(times[0] > 1_700_000_000_000);
+
+    let results: Vec<FieldPersistResult> = con.hpersist("hash", &["a", "b", "missing"]).unwrap();
+    assert_eq!(
+        results,
+        vec![
+            FieldPersistResult::Persisted,
+            FieldPersistResult::Persisted,
+            FieldPersistResult::NoField,
+        ]
+    );
+    let results: Vec<FieldPersistResult> = con.hpersist("hash", &["a"]).unwrap();
+    assert_eq!(results, vec![FieldPersistResult::NoExpiry]);
+}
+
+#[test]
+fn test_sort() {
+    use redis::options::SortOptions;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = con.rpush("ids", &[3, 1, 2]).unwrap();
+    let _: () = con
+        .set_multiple(&[("weight_1", 30), ("weight_2", 10), ("weight_3", 20)])
+        .unwrap();
+    let _: () = con.hset("user_1", "name", "ann").unwrap();
+    let _: () = con.hset("user_3", "name", "cid").unwrap();
+
+    assert_eq!(con.sort("ids", &SortOptions::new()), Ok(vec![1, 2, 3]));
+    assert_eq!(
+        con.sort("ids", &SortOptions::new().desc().limit(0, 2)),
+        Ok(vec![3, 2])
+    );
+    assert_eq!(
+        con.sort("ids", &SortOptions::new().by("weight_*")),
+        Ok(vec![2, 3, 1])
+    );
+
+    let options = SortOptions::new().get("#").get("user_*->name").alpha();
+    let users: Vec<(i32, Option<String>)> = con.sort("ids", &options).unwrap();
+    assert_eq!(
+        users,
+        vec![
+            (1, Some("ann".to_string())),
+            (2, None),
+            (3, Some("cid".to_string())),
+        ]
+    );
+
+    let options = SortOptions::new().by("nosort").get("weight_*");
+    assert_eq!(con.sort_store("ids", "weights", &options), Ok(3));
+    assert_eq!(con.lrange("weights", 0, -1), Ok(vec![20, 30, 10]));
+
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![7, 0] {
+        return;
+    }
+    assert_eq!(
+        con.sort_ro("ids", &SortOptions::new().desc()),
+        Ok(vec![3, 2, 1])
+    );
+}
+
+#[test]
+fn test_info_typed() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = con.set("key", 1).unwrap();
+    let info = con.info_typed().unwrap();
+    let server = info.server.unwrap();
+    assert!(!server.redis_version.is_empty());
+    assert!(info.clients.unwrap().connected_clients >= 1);
+    assert!(info.memory.unwrap().used_memory > 0);
+    assert!(info.persistence.is_some());
+    assert_eq!(info.replication.unwrap().role, "master");
+    assert_eq!(info.keyspace[&0].keys, 1);
+}
+
+#[test]
+fn test_slowlog() {
+    use redis::server::SlowlogEntry;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("slowlog-log-slower-than")
+        .arg(0)
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.slowlog_reset().unwrap();
+    let _: () = con.set("key", "value").unwrap();
+
+    let entries: Vec<SlowlogEntry> = con.slowlog_get(10).unwrap();
+    let entry = entries
+        .iter()
+        .find(|entry| entry.command_name().eq_ignore_ascii_case("set"))
+        .unwrap();
+    assert_eq!(
+        entry.args[1..].to_vec(),
+        vec![b"key".to_vec(), b"value".to_vec()]
+    );
+    assert!(entry.time > std::time::UNIX_EPOCH);
+    let len: usize = con.slowlog_len().unwrap();
+    assert!(len >= 2);
+
+    let _: () = con.slowlog_reset().unwrap();
+    let entries: Vec<SlowlogEntry> = con.slowlog_get(10).unwrap();
+    assert!(entries.len() <= 1);
+}
+
+#[test]
+fn test_config() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = con.config_set("slowlog-max-len", 64).unwrap();
+    let config: HashMap<String, usize> = con.config_get("slowlog-max-len").unwrap();
+    assert_eq!(config["slowlog-max-len"], 64);
+    let config: HashMap<String, String> = con.config_get("slowlog-*").unwrap();
+    assert!(config.contains_key("slowlog-log-slower-than"));
+    let _: () = con.config_resetstat().unwrap();
+
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![7, 0] {
+        return;
+    }
+    let _: () = con
+        .config_set_multiple(&[("slowlog-max-len", 32), ("maxmemory-samples", 7)])
+        .unwrap();
+    let config: HashMap<String, usize> = con
+        .config_get(&["slowlog-max-len", "maxmemory-samples"])
+        .unwrap();
+    assert_eq!(config.len(), 2);
+    assert_eq!(config["slowlog-max-len"], 32);
+    assert_eq!(config["maxmemory-samples"], 7);
+    // either all parameters are set or none
+    assert!(con
+        .config_set_multiple::<_, _, ()>(&[("slowlog-max-len", "16"), ("maxmemory-samples", "x")])
+        .is_err());
+    let config: HashMap<String, usize> = con.config_get("slowlog-max-len").unwrap();
+    assert_eq!(config["slowlog-max-len"], 32);
+}
+
+#[test]
+fn test_memory() {
+    use redis::server::MemoryStats;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = con.set("key", "x".repeat(1000)).unwrap();
+    let _: () = con.rpush("list", &[1, 2, 3]).unwrap();
+    let usage: Option<usize> = con.memory_usage("key", 5).unwrap();
+    assert!(usage.unwrap() > 1000);
+    let usage: Option<usize> = con.memory_usage("list", 0).unwrap();
+    assert!(usage.unwrap() > 0);
+    let usage: Option<usize> = con.memory_usage("missing", 5).unwrap();
+    assert_eq!(usage, None);
+
+    let stats: MemoryStats = con.memory_stats().unwrap();
+    assert_eq!(stats.keys_count, 2);
+    assert!(stats.total_allocated >= stats.dataset_bytes);
+    assert!(stats.fields.keys().any(|name| name.starts_with("db.0.")));
+
+    let advice: String = con.memory_doctor().unwrap();
+    assert!(!advice.is_empty());
+}
+
+#[test]
+fn test_dump_restore() {
+    use redis::options::RestoreOptions;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = con.rpush("list", &[1, 2, 3]).unwrap();
+    let dumped: Option<Vec<u8>> = con.dump("list").unwrap();
+    let dumped = dumped.unwrap();
+    assert_eq!(con.dump("missing"), Ok(None::<Vec<u8>>));
+
+    let _: () = con.restore("copy", 0, &dumped[..]).unwrap();
+    assert_eq!(con.lrange("copy", 0, -1), Ok(vec![1, 2, 3]));
+    assert!(con.restore::<_, _, ()>("copy", 0, &dumped[..]).is_err());
+
+    let _: () = con
+        .restore_options(
+            "copy",
+            100_000,
+            &dumped[..],
+            &RestoreOptions::new().replace(),
+        )
+        .unwrap();
+    let ttl: i64 = redis::cmd("PTTL").arg("copy").query(&mut con).unwrap();
+    assert!(ttl > 90_000);
+
+    let options = RestoreOptions::new().replace().idle_time(1000);
+    let _: () = con
+        .restore_options("copy", 0, &dumped[..], &options)
+        .unwrap();
+    let idle: usize = con.object_idletime("copy").unwrap();
+    assert!(idle >= 1000);
+}
+
+#[test]
+fn test_migrate() {
+    use redis::options::MigrateOptions;
+
+    let ctx = TestContext::new();
+    let target = TestContext::new();
+    let (host, port) = match *target.server.get_client_addr() {
+        redis::ConnectionAddr::Tcp(ref host, port) => (host.clone(), port),
+        _ => return,
+    };
+    let mut con = ctx.connection();
+    let mut target_con = target.connection();
+
+    let _: () = con.set("key1", 1).unwrap();
+    let _: () = con.set("key2", 2).unwrap();
+    let _: () = con.set("key3", 3).unwrap();
+
+    let reply: String = con
+        .migrate(&host[..], port, "key1", 0, 1000, &MigrateOptions::new())
+        .unwrap();
+    assert_eq!(reply, "OK");
+    assert_eq!(con.exists("key1"), Ok(false));
+    assert_eq!(target_con.get("key1"), Ok(1));
+
+    let reply: String = con
+        .migrate_keys(
+            &host[..],
+            port,
+            &["key2", "key3", "missing"],
+            0,
+            1000,
+            &MigrateOptions::new().copy(),
+        )
+        .unwrap();
+    assert_eq!(reply, "OK");
+    assert_eq!(con.exists("key2"), Ok(true));
+    assert_eq!(target_con.get(&["key2", "key3"]), Ok((2, 3)));
+
+    let reply: String = con
+        .migrate(&host[..], port, "missing", 0, 1000, &MigrateOptions::new())
+        .unwrap();
+    assert_eq!(reply, "NOKEY");
+    assert!(con
+        .migrate::<_, _, ()>(&host[..], port, "key2", 0, 1000, &MigrateOptions::new())
+        .is_err());
+    let _: () = con
+        .migrate(
+            &host[..],
+            port,
+            "key2",
+            0,
+            1000,
+            &MigrateOptions::new().replace(),
+        )
+        .unwrap();
+}
+
+#[test]
+fn test_mpop() {
+    use redis::options::{ListDirection, MPopReply, ScoreEnd};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let major: u32 = version.split('.').next().unwrap().parse().unwrap();
+    if major < 7 {
+        return;
+    }
+
+    let _: () = con.rpush("list2", &[1, 2, 3]).unwrap();
+    let reply: Option<MPopReply<i32>> = con
+        .lmpop(&["list1", "list2"], ListDirection::Right, 2)
+        .unwrap();
+    assert_eq!(
+        reply,
+        Some(MPopReply {
+            key: "list2".to_string(),
+            elements: vec![3, 2],
+        })
+    );
+    let reply: Option<MPopReply<i32>> = con
+        .blmpop(1, &["list1", "list2"], ListDirection::Left, 5)
+        .unwrap();
+    assert_eq!(reply.unwrap().elements, vec![1]);
+    let reply: Option<MPopReply<i32>> = con
+        .lmpop(&["list1", "list2"], ListDirection::Left, 1)
+        .unwrap();
+    assert_eq!(reply, None);
+
+    let _: () = con
+        .zadd_multiple("zset", &[(1, "one"), (2, "two"), (3, "three")])
+        .unwrap();
+    let reply: Option<MPopReply<(String, f64)>> = con.zmpop(&["zset"], ScoreEnd::Max, 2).unwrap();
+    assert_eq!(
+        reply.unwrap().elements,
+        vec![("three".to_string(), 3.0), ("two".to_string(), 2.0)]
+    );
+    let reply: Option<MPopReply<(String, f64)>> =
+        con.bzmpop(1, &["zset"], ScoreEnd::Min, 2).unwrap();
+    assert_eq!(reply.unwrap().elements, vec![("one".to_string(), 1.0)]);
+}
+
+#[test]
+fn test_object() {
+    use redis::keys::ObjectEncoding;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = con.set("int", 42).unwrap();
+    let _: () = con.set("raw", "x".repeat(100)).unwrap();
+    let _: () = con.sadd("set", &[1, 2, 3]).unwrap();
+    assert_eq!(con.object_encoding("int"), Ok(ObjectEncoding::Int));
+    assert_eq!(con.object_encoding("raw"), Ok(ObjectEncoding::Raw));
+    assert_eq!(con.object_encoding("set"), Ok(ObjectEncoding::IntSet));
+    assert_eq!(con.object_encoding("missing"), Ok(None::<ObjectEncoding>));
+    assert!(ObjectEncoding::IntSet.is_compact());
+    assert_eq!(
+        ObjectEncoding::from_name("listpackex"),
+        ObjectEncoding::Other("listpackex".to_string())
+    );
+
+    let idle: u64 = con.object_idletime("raw").unwrap();
+    assert!(idle < 10);
+    let refcount: i64 = con.object_refcount("raw").unwrap();
+    assert!(refcount >= 1);
+    // FREQ needs an LFU policy
+    assert!(con.object_freq::<_, i64>("raw").is_err());
+    let _: () = redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("maxmemory-policy")
+        .arg("allkeys-lfu")
+        .query(&mut con)
+        .unwrap();
+    let freq: i64 = con.object_freq("raw").unwrap();
+    assert!(freq >= 0);
+}
+
+#[test]
+fn test_getset_large_values() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    // values larger than the buffers of the connection, mixed with small ones
+    for &size in &[10, 200_000, 10, 1_000_000, 10] {
+        let value = vec![b'x'; size];
+        redis::cmd("SET")
+            .arg("foo")
+            .arg(&value[..])
+            .execute(&mut con);
+        assert_eq!(redis::cmd("GET").arg("foo").query(&mut con), Ok(value));
+    }
+}
+
 #[test]
 fn test_incr() {
     let ctx = TestContext::new();
@@ -181,85 +1052,500 @@ fn test_optionals() {
     assert_eq!(a, 0i32);
 }
 
-#[cfg(feature = "with-rustc-json")]
+#[cfg(feature = "with-rustc-json")]
+#[test]
+fn test_json() {
+    use redis::Json;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    redis::cmd("SET")
+        .arg("foo")
+        .arg("[1, 2, 3]")
+        .execute(&mut con);
+
+    let json: Json = redis::cmd("GET").arg("foo").query(&mut con).unwrap();
+    assert_eq!(
+        json,
+        Json::Array(vec![Json::U64(1), Json::U64(2), Json::U64(3)])
+    );
+}
+
+#[cfg(feature = "with-serde")]
+#[test]
+fn test_hash_struct() {
+    use redis::hash::{to_hash_fields, HashCommands};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    enum Plan {
+        Free,
+        Pro,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Address {
+        city: String,
+        zip: u32,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name: String,
+        age: u8,
+        active: bool,
+        email: Option<String>,
+        plan: Plan,
+        address: Address,
+    }
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let mut user = User {
+        name: "Alice".to_string(),
+        age: 42,
+        active: true,
+        email: Some("alice@example.com".to_string()),
+        plan: Plan::Pro,
+        address: Address {
+            city: "Vienna".to_string(),
+            zip: 1010,
+        },
+    };
+    con.hset_struct("user", &user).unwrap();
+    assert_eq!(con.hget("user", "address.zip"), Ok(1010));
+    assert_eq!(con.hget("user", "plan"), Ok("Pro".to_string()));
+    assert_eq!(con.hgetall_as("user"), Ok(Some(user.clone())));
+
+    // fields that became `None` are removed
+    user.email = None;
+    user.plan = Plan::Free;
+    con.hset_struct("user", &user).unwrap();
+    assert_eq!(con.hexists("user", "email"), Ok(false));
+    assert_eq!(con.hgetall_as("user"), Ok(Some(user.clone())));
+
+    assert_eq!(con.hgetall_as::<_, User>("missing"), Ok(None));
+    let _: () = con.hset("user", "age", "old").unwrap();
+    let err = con.hgetall_as::<_, User>("user").unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::TypeError);
+    assert!(to_hash_fields(&vec![1, 2]).is_err());
+}
+
+#[test]
+fn test_scanning() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let mut unseen = HashSet::new();
+
+    for x in 0..1000 {
+        redis::cmd("SADD").arg("foo").arg(x).execute(&mut con);
+        unseen.insert(x);
+    }
+
+    let iter = redis::cmd("SSCAN")
+        .arg("foo")
+        .cursor_arg(0)
+        .clone()
+        .iter(&mut con)
+        .unwrap();
+
+    for x in iter {
+        // type inference limitations
+        let x: usize = x;
+        unseen.remove(&x);
+    }
+
+    assert_eq!(unseen.len(), 0);
+}
+
+#[test]
+fn test_filtered_scanning() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let mut unseen = HashSet::new();
+
+    for x in 0..3000 {
+        let _: () = con
+            .hset("foo", format!("key_{}_{}", x % 100, x), x)
+            .unwrap();
+        if x % 100 == 0 {
+            unseen.insert(x);
+        }
+    }
+
+    let iter = con.hscan_match("foo", "key_0_*").unwrap();
+
+    for x in iter {
+        // type inference limitations
+        let x: usize = x;
+        unseen.remove(&x);
+    }
+
+    assert_eq!(unseen.len(), 0);
+}
+
+#[test]
+fn test_scan_options() {
+    use redis::keys::KeyType;
+    use redis::options::ScanOptions;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    for x in 0..100 {
+        let _: () = con.set(format!("str_{}", x), x).unwrap();
+    }
+    let _: () = con.rpush("list_1", 1).unwrap();
+    let _: () = con.sadd("set_1", &[1, 10, 2]).unwrap();
+    assert_eq!(con.key_type("list_1"), Ok(KeyType::List));
+    assert_eq!(con.key_type("missing"), Ok(KeyType::NoKey));
+
+    let options = ScanOptions::new().with_pattern("str_1*").with_count(50);
+    let keys: HashSet<String> = con.scan_options(&options).unwrap().collect();
+    assert_eq!(keys.len(), 11);
+
+    let options = ScanOptions::new().with_pattern("1*");
+    let members: HashSet<usize> = con.sscan_options("set_1", &options).unwrap().collect();
+    assert_eq!(members, vec![1, 10].into_iter().collect());
+
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![6, 0] {
+        return;
+    }
+    let options = ScanOptions::new().with_type(KeyType::List);
+    let keys: Vec<String> = con.scan_options(&options).unwrap().collect();
+    assert_eq!(keys, vec!["list_1"]);
+    let options = ScanOptions::new()
+        .with_pattern("*_1")
+        .with_type(KeyType::Set);
+    let keys: Vec<String> = con.scan_options(&options).unwrap().collect();
+    assert_eq!(keys, vec!["set_1"]);
+}
+
+#[test]
+fn test_binary_scanning() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let key = &b"key\xff"[..];
+    let _: () = con.set(key, 1).unwrap();
+    let _: () = con.set("text", 1).unwrap();
+    let _: () = con
+        .hset("hash", &b"field\xfe"[..], &b"value\xfd"[..])
+        .unwrap();
+    let _: () = con.sadd("set", &b"member\xfc"[..]).unwrap();
+    let _: () = con.zadd("zset", &b"member\xfb"[..], 2).unwrap();
+
+    let keys: HashSet<Vec<u8>> = con.scan().unwrap().collect();
+    assert!(keys.contains(key));
+    assert!(keys.contains(&b"text"[..]));
+    // keys that are not valid UTF-8 are skipped as strings
+    let keys: HashSet<String> = con.scan().unwrap().collect();
+    assert!(keys.contains("text"));
+    assert_eq!(keys.len(), 4);
+
+    let keys: Vec<Vec<u8>> = con.scan_match(&b"key\xff*"[..]).unwrap().collect();
+    assert_eq!(keys, vec![key.to_vec()]);
+
+    let pairs: Vec<(Vec<u8>, Vec<u8>)> = con.hscan("hash").unwrap().collect();
+    assert_eq!(pairs, vec![(b"field\xfe".to_vec(), b"value\xfd".to_vec())]);
+    let members: Vec<Vec<u8>> = con.sscan("set").unwrap().collect();
+    assert_eq!(members, vec![b"member\xfc".to_vec()]);
+    let members: Vec<(Vec<u8>, f64)> = con.zscan("zset").unwrap().collect();
+    assert_eq!(members, vec![(b"member\xfb".to_vec(), 2.0)]);
+}
+
+#[test]
+fn test_pipeline() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let ((k1, k2),): ((i32, i32),) = redis::pipe()
+        .cmd("SET")
+        .arg("key_1")
+        .arg(42)
+        .ignore()
+        .cmd("SET")
+        .arg("key_2")
+        .arg(43)
+        .ignore()
+        .cmd("MGET")
+        .arg(&["key_1", "key_2"])
+        .query(&mut con)
+        .unwrap();
+
+    assert_eq!(k1, 42);
+    assert_eq!(k2, 43);
+}
+
+#[test]
+fn test_pipeline_typed_results() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = con.set("a", "foo").unwrap();
+    let (a, len, missing): (String, i64, Option<String>) = redis::pipe()
+        .get("a")
+        .lpush("b", 1)
+        .get("missing")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(a, "foo");
+    assert_eq!(len, 1);
+    assert_eq!(missing, None);
+
+    let result: redis::RedisResult<(i64, i64)> =
+        redis::pipe().lpush("b", 2).get("a").query(&mut con);
+    let err = result.unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::TypeError);
+    assert!(err.to_string().contains("element 1 of 2"), "{}", err);
+}
+
+#[test]
+fn test_pipeline_query_each() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let results: Vec<redis::RedisResult<redis::Value>> = redis::pipe()
+        .set("text", "foo")
+        .ignore()
+        .incr("text", 1)
+        .incr("counter", 1)
+        .query_each(&mut con)
+        .unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0], Ok(redis::Value::Okay));
+    let err = results[1].as_ref().unwrap_err();
+    assert!(err.is_server_error());
+    assert_eq!(err.kind(), redis::ErrorKind::ResponseError);
+    assert_eq!(results[2], Ok(redis::Value::Int(1)));
+
+    // values that cannot be converted fail in place as well
+    let results: Vec<redis::RedisResult<i64>> = redis::pipe()
+        .get("text")
+        .get("counter")
+        .query_each(&mut con)
+        .unwrap();
+    let err = results[0].as_ref().unwrap_err();
+    assert!(!err.is_server_error());
+    assert_eq!(err.kind(), redis::ErrorKind::TypeError);
+    assert_eq!(results[1], Ok(1));
+
+    // the connection is still in sync
+    assert_eq!(con.get("counter"), Ok(1));
+
+    let result = redis::pipe()
+        .atomic()
+        .get("text")
+        .query_each::<redis::Value>(&mut con);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        redis::ErrorKind::InvalidClientConfig
+    );
+}
+
+#[test]
+fn test_pipeline_chunked() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let mut pipe = redis::pipe();
+    for i in 0..1000 {
+        pipe.set(format!("key:{}", i), i).ignore();
+    }
+    pipe.execute_chunked(&mut con, 64).unwrap();
+    let size: usize = redis::cmd("DBSIZE").query(&mut con).unwrap();
+    assert_eq!(size, 1000);
+
+    let mut pipe = redis::pipe();
+    for i in 0..1000 {
+        pipe.incr(format!("key:{}", i), 1)
+            .ignore()
+            .get(format!("key:{}", i));
+    }
+    let values: Vec<usize> = pipe.query_chunked(&mut con, 100).unwrap();
+    assert_eq!(values, (1..1001).collect::<Vec<usize>>());
+
+    let result: redis::RedisResult<()> = redis::pipe()
+        .atomic()
+        .get("key:0")
+        .query_chunked(&mut con, 10);
+    assert_eq!(
+        result.unwrap_err().kind(),
+        redis::ErrorKind::InvalidClientConfig
+    );
+}
+
 #[test]
-fn test_json() {
-    use redis::Json;
+fn test_interceptors() {
+    use redis::middleware::{Command, InterceptedConnection, Interceptor};
+    use redis::{RedisResult, Value};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    struct Recorder(Arc<Mutex<Vec<(String, bool)>>>);
+
+    impl Interceptor for Recorder {
+        fn after(&mut self, command: &Command, _: Duration, result: &RedisResult<Value>) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((command.name(), result.is_ok()));
+        }
+    }
 
-    let ctx = TestContext::new();
-    let mut con = ctx.connection();
+    struct Prefix;
 
-    redis::cmd("SET")
-        .arg("foo")
-        .arg("[1, 2, 3]")
-        .execute(&mut con);
+    impl Interceptor for Prefix {
+        fn before(&mut self, command: &mut Command) -> Option<RedisResult<Value>> {
+            if command.name() == "PING" {
+                return Some(Ok(Value::Status("intercepted".to_string())));
+            }
+            if command.args().len() > 1 {
+                let key = &mut command.args_mut()[1];
+                *key = [&b"app:"[..], &key[..]].concat();
+            }
+            None
+        }
+    }
 
-    let json: Json = redis::cmd("GET").arg("foo").query(&mut con).unwrap();
+    let ctx = TestContext::new();
+    let log = Arc::new(Mutex::new(vec![]));
+    let mut con = InterceptedConnection::new(ctx.connection())
+        .with(Recorder(log.clone()))
+        .with(Prefix);
+
+    let _: () = con.set("key", 1).unwrap();
+    assert_eq!(con.get("key"), Ok(1));
+    assert_eq!(con.get_mut().get("app:key"), Ok(1));
+    let pong: String = redis::cmd("PING").query(&mut con).unwrap();
+    assert_eq!(pong, "intercepted");
+
+    let (a, b): (i32, i32) = redis::pipe()
+        .incr("a", 1)
+        .incr("b", 2)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!((a, b), (1, 2));
+    assert_eq!(con.get_mut().get("app:b"), Ok(2));
+    assert!(con.hgetall::<_, ()>("key").is_err());
+
+    let log = log.lock().unwrap();
+    let expected = vec![
+        ("SET", true),
+        ("GET", true),
+        ("PING", true),
+        ("INCR", true),
+        ("INCR", true),
+        ("HGETALL", false),
+    ];
     assert_eq!(
-        json,
-        Json::Array(vec![Json::U64(1), Json::U64(2), Json::U64(3)])
+        log.iter()
+            .map(|&(ref name, ok)| (&name[..], ok))
+            .collect::<Vec<_>>(),
+        expected
     );
 }
 
 #[test]
-fn test_scanning() {
-    let ctx = TestContext::new();
-    let mut con = ctx.connection();
-    let mut unseen = HashSet::new();
+fn test_metrics_observer() {
+    use redis::metrics::{CommandEvent, MetricsObserver};
+    use redis::ConnectionAddr;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Recorder {
+        connects: Mutex<usize>,
+        issued: Mutex<Vec<String>>,
+        completed: Mutex<Vec<(String, usize, bool)>>,
+        bytes: Mutex<(usize, usize)>,
+    }
 
-    for x in 0..1000 {
-        redis::cmd("SADD").arg("foo").arg(x).execute(&mut con);
-        unseen.insert(x);
+    impl MetricsObserver for Recorder {
+        fn command_issued(&self, name: &str) {
+            self.issued.lock().unwrap().push(name.to_string());
+        }
+
+        fn command_completed(&self, event: &CommandEvent) {
+            self.completed.lock().unwrap().push((
+                event.name.to_string(),
+                event.commands,
+                event.error.is_none(),
+            ));
+            let mut bytes = self.bytes.lock().unwrap();
+            bytes.0 += event.bytes_sent;
+            bytes.1 += event.bytes_received;
+        }
+
+        fn connected(&self, _: &ConnectionAddr) {
+            *self.connects.lock().unwrap() += 1;
+        }
     }
 
-    let iter = redis::cmd("SSCAN")
-        .arg("foo")
-        .cursor_arg(0)
-        .clone()
-        .iter(&mut con)
+    let ctx = TestContext::new();
+    let recorder = Arc::new(Recorder::default());
+    let mut client = ctx.client.clone();
+    client.set_metrics_observer(Some(recorder.clone()));
+    let mut con = client.get_connection().unwrap();
+    assert_eq!(*recorder.connects.lock().unwrap(), 1);
+
+    let _: () = con.set("key", 1).unwrap();
+    let _: (i32, i32) = redis::pipe()
+        .incr("a", 1)
+        .incr("b", 2)
+        .query(&mut con)
         .unwrap();
+    assert!(con.hgetall::<_, ()>("key").is_err());
 
-    for x in iter {
-        // type inference limitations
-        let x: usize = x;
-        unseen.remove(&x);
-    }
+    assert_eq!(
+        *recorder.issued.lock().unwrap(),
+        vec!["SET", "PIPELINE", "HGETALL"]
+    );
+    let completed = recorder.completed.lock().unwrap();
+    let expected = vec![
+        ("SET", 1, true),
+        ("PIPELINE", 2, true),
+        ("HGETALL", 1, false),
+    ];
+    assert_eq!(
+        completed
+            .iter()
+            .map(|&(ref name, commands, ok)| (&name[..], commands, ok))
+            .collect::<Vec<_>>(),
+        expected
+    );
+    drop(completed);
 
-    assert_eq!(unseen.len(), 0);
+    // `+OK\r\n`, `:1\r\n:2\r\n` and the WRONGTYPE error
+    let bytes = *recorder.bytes.lock().unwrap();
+    assert!(bytes.0 > 0);
+    assert!(bytes.1 > 5 + 8);
+
+    // connections of clients without an observer are not observed
+    let _: () = ctx.connection().set("key", 2).unwrap();
+    assert_eq!(recorder.completed.lock().unwrap().len(), 3);
 }
 
 #[test]
-fn test_filtered_scanning() {
+fn test_empty_pipeline() {
     let ctx = TestContext::new();
     let mut con = ctx.connection();
-    let mut unseen = HashSet::new();
-
-    for x in 0..3000 {
-        let _: () = con
-            .hset("foo", format!("key_{}_{}", x % 100, x), x)
-            .unwrap();
-        if x % 100 == 0 {
-            unseen.insert(x);
-        }
-    }
 
-    let iter = con.hscan_match("foo", "key_0_*").unwrap();
-
-    for x in iter {
-        // type inference limitations
-        let x: usize = x;
-        unseen.remove(&x);
-    }
+    let _: () = redis::pipe().cmd("PING").ignore().query(&mut con).unwrap();
 
-    assert_eq!(unseen.len(), 0);
+    let _: () = redis::pipe().query(&mut con).unwrap();
 }
 
 #[test]
-fn test_pipeline() {
+fn test_pipeline_transaction() {
     let ctx = TestContext::new();
     let mut con = ctx.connection();
 
     let ((k1, k2),): ((i32, i32),) = redis::pipe()
+        .atomic()
         .cmd("SET")
         .arg("key_1")
         .arg(42)
@@ -278,37 +1564,158 @@ fn test_pipeline() {
 }
 
 #[test]
-fn test_empty_pipeline() {
+fn test_send_without_replies() {
     let ctx = TestContext::new();
     let mut con = ctx.connection();
 
-    let _: () = redis::pipe().cmd("PING").ignore().query(&mut con).unwrap();
+    redis::cmd("SET")
+        .arg("key")
+        .arg(1)
+        .send_without_reply(&mut con)
+        .unwrap();
+    redis::cmd("INCR")
+        .arg("key")
+        .send_without_reply(&mut con)
+        .unwrap();
+    assert_eq!(con.get("key"), Ok(2));
+
+    // errors are not reported and do not stop the other commands
+    redis::pipe()
+        .cmd("INCR")
+        .arg("counter")
+        .cmd("LPUSH")
+        .arg("key")
+        .arg(1)
+        .cmd("INCR")
+        .arg("counter")
+        .send_without_replies(&mut con)
+        .unwrap();
+    assert_eq!(con.get("counter"), Ok(2));
 
-    let _: () = redis::pipe().query(&mut con).unwrap();
+    redis::pipe()
+        .atomic()
+        .cmd("INCR")
+        .arg("counter")
+        .cmd("INCR")
+        .arg("counter")
+        .send_without_replies(&mut con)
+        .unwrap();
+    assert_eq!(con.get("counter"), Ok(4));
 }
 
 #[test]
-fn test_pipeline_transaction() {
+fn test_pipeline_large_arguments() {
     let ctx = TestContext::new();
     let mut con = ctx.connection();
 
-    let ((k1, k2),): ((i32, i32),) = redis::pipe()
+    // small and large arguments, the latter are not copied into one buffer
+    let mut pipe = redis::pipe();
+    for i in 0..2000 {
+        let payload = vec![b'a' + (i % 26) as u8; if i % 2 == 0 { 10 } else { 5000 }];
+        pipe.cmd("XADD")
+            .arg("stream")
+            .arg("*")
+            .arg("payload")
+            .arg(payload)
+            .ignore();
+    }
+    pipe.cmd("XLEN").arg("stream");
+    assert_eq!(pipe.query(&mut con), Ok((2000,)));
+
+    let entries: Vec<(String, Vec<(String, Vec<u8>)>)> = redis::cmd("XRANGE")
+        .arg("stream")
+        .arg("-")
+        .arg("+")
+        .query(&mut con)
+        .unwrap();
+    for (i, &(_, ref fields)) in entries.iter().enumerate() {
+        let expected = vec![b'a' + (i % 26) as u8; if i % 2 == 0 { 10 } else { 5000 }];
+        assert_eq!(fields, &vec![("payload".to_string(), expected)]);
+    }
+
+    let (len,): (usize,) = redis::pipe()
         .atomic()
         .cmd("SET")
-        .arg("key_1")
-        .arg(42)
-        .ignore()
-        .cmd("SET")
-        .arg("key_2")
-        .arg(43)
+        .arg("key")
+        .arg(vec![b'x'; 100_000])
         .ignore()
-        .cmd("MGET")
-        .arg(&["key_1", "key_2"])
+        .cmd("STRLEN")
+        .arg("key")
         .query(&mut con)
         .unwrap();
+    assert_eq!(len, 100_000);
+}
 
-    assert_eq!(k1, 42);
-    assert_eq!(k2, 43);
+#[test]
+fn test_query_iter() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let items: Vec<i32> = (0..10_000).collect();
+    redis::cmd("RPUSH")
+        .arg("list")
+        .arg(&items[..])
+        .execute(&mut con);
+
+    let mut lrange = redis::cmd("LRANGE");
+    lrange.arg("list").arg(0).arg(-1);
+    let iter = lrange.query_iter::<i32>(&mut con).unwrap();
+    assert_eq!(iter.size_hint(), (10_000, Some(10_000)));
+    let read: Vec<i32> = iter.map(Result::unwrap).collect();
+    assert_eq!(read, items);
+
+    // the rest of the reply is skipped when the iterator is dropped early
+    let first: Vec<i32> = lrange
+        .query_iter(&mut con)
+        .unwrap()
+        .take(3)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(first, vec![0, 1, 2]);
+    assert_eq!(redis::cmd("LLEN").arg("list").query(&mut con), Ok(10_000));
+
+    // elements that do not convert fail one by one
+    redis::cmd("RPUSH")
+        .arg("mixed")
+        .arg(1)
+        .arg("x")
+        .arg(3)
+        .execute(&mut con);
+    let mixed: Vec<bool> = redis::cmd("LRANGE")
+        .arg("mixed")
+        .arg(0)
+        .arg(-1)
+        .query_iter::<i32>(&mut con)
+        .unwrap()
+        .map(|item| item.is_ok())
+        .collect();
+    assert_eq!(mixed, vec![true, false, true]);
+
+    // a missing key yields nothing, other types fail
+    let mut empty = redis::cmd("LRANGE")
+        .arg("missing")
+        .arg(0)
+        .arg(-1)
+        .query_iter::<i32>(&mut con)
+        .unwrap();
+    assert!(empty.next().is_none());
+    drop(empty);
+    redis::cmd("SET").arg("key").arg(1).execute(&mut con);
+    let err = redis::cmd("GET")
+        .arg("key")
+        .query_iter::<i32>(&mut con)
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), redis::ErrorKind::TypeError);
+    let err = redis::cmd("LRANGE")
+        .arg("key")
+        .arg(0)
+        .arg(-1)
+        .query_iter::<i32>(&mut con)
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), redis::ErrorKind::WrongType);
+    assert_eq!(redis::cmd("GET").arg("key").query(&mut con), Ok(1));
 }
 
 #[test]
@@ -444,6 +1851,44 @@ fn test_real_transaction_highlevel() {
     assert_eq!(response, (43,));
 }
 
+#[test]
+fn test_transaction_with_retries() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let mut other = ctx.connection();
+
+    let key = "the_key";
+    let _: () = con.set(key, 42).unwrap();
+
+    // another client modifies the key during every attempt
+    let mut attempts = 0;
+    let response: redis::RedisResult<(isize,)> =
+        redis::transaction_with_retries(&mut con, &[key], 2, |con, pipe| {
+            attempts += 1;
+            let val: isize = con.get(key)?;
+            let _: () = other.incr(key, 100)?;
+            pipe.set(key, val + 1).ignore().get(key).query(con)
+        });
+    let err = response.unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ExecAbortError);
+    assert_eq!(attempts, 3);
+    assert_eq!(con.get(key), Ok(342));
+
+    // the transaction goes through once the key is left alone
+    let mut attempts = 0;
+    let response: (isize,) = redis::transaction_with_retries(&mut con, &[key], 2, |con, pipe| {
+        attempts += 1;
+        let val: isize = con.get(key)?;
+        if attempts == 1 {
+            let _: () = other.incr(key, 100)?;
+        }
+        pipe.set(key, val + 1).ignore().get(key).query(con)
+    })
+    .unwrap();
+    assert_eq!(response, (443,));
+    assert_eq!(attempts, 2);
+}
+
 #[test]
 fn test_pubsub() {
     use std::sync::{Arc, Barrier};
@@ -481,6 +1926,175 @@ fn test_pubsub() {
     thread.join().ok().expect("Something went wrong");
 }
 
+#[test]
+fn test_pubsub_binary_payloads() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let mut pubsub_con = ctx.connection();
+    let mut pubsub = pubsub_con.as_pubsub();
+    pubsub.psubscribe(&b"bin\xff*"[..]).unwrap();
+
+    let payload = vec![0u8, 159, 146, 150, 255];
+    assert_eq!(con.publish(&b"bin\xff1"[..], &payload[..]), Ok(1));
+    assert_eq!(con.publish(&b"bin\xff2"[..], 42), Ok(1));
+
+    let msg = pubsub.get_message().unwrap();
+    assert_eq!(msg.payload_bytes(), &payload[..]);
+    assert_eq!(msg.get_payload(), Ok(payload.clone()));
+    assert!(msg.get_payload::<String>().is_err());
+    assert_eq!(msg.channel_bytes(), b"bin\xff1");
+    assert_eq!(msg.get_channel(), Ok(b"bin\xff1".to_vec()));
+    assert_eq!(msg.get_channel_name(), "?");
+    assert_eq!(msg.pattern_bytes(), Some(&b"bin\xff*"[..]));
+
+    let msg = pubsub.get_message().unwrap();
+    assert_eq!(msg.get_payload(), Ok(42));
+    assert_eq!(msg.get_payload(), Ok("42".to_string()));
+}
+
+#[test]
+fn test_sharded_pubsub() {
+    use std::sync::{Arc, Barrier};
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let major: u32 = version.split('.').next().unwrap().parse().unwrap();
+    if major < 7 {
+        return;
+    }
+
+    let mut pubsub_con = ctx.connection();
+    let barrier = Arc::new(Barrier::new(2));
+    let pubsub_barrier = barrier.clone();
+
+    let thread = spawn(move || {
+        let mut pubsub = pubsub_con.as_pubsub();
+        pubsub.ssubscribe("shard").unwrap();
+        pubsub.subscribe("plain").unwrap();
+
+        let _ = pubsub_barrier.wait();
+
+        let msg = pubsub.get_message().unwrap();
+        assert_eq!(msg.get_channel(), Ok("shard".to_string()));
+        assert_eq!(msg.get_payload(), Ok(1));
+        assert!(msg.from_shard_channel());
+        assert_eq!(*msg.kind(), redis::PushKind::SMessage);
+
+        let msg = pubsub.get_message().unwrap();
+        assert_eq!(msg.get_channel(), Ok("plain".to_string()));
+        assert!(!msg.from_shard_channel());
+        assert_eq!(*msg.kind(), redis::PushKind::Message);
+
+        pubsub.sunsubscribe("shard").unwrap();
+    });
+
+    let _ = barrier.wait();
+    assert_eq!(
+        con.pubsub_shardchannels::<&str, Vec<String>>(None),
+        Ok(vec!["shard".to_string()])
+    );
+    assert_eq!(
+        con.pubsub_shardnumsub(&["shard", "other"]),
+        Ok(vec![("shard".to_string(), 1), ("other".to_string(), 0)])
+    );
+    assert_eq!(con.spublish("shard", 1), Ok(1));
+    assert_eq!(con.publish("plain", 2), Ok(1));
+
+    thread.join().ok().expect("Something went wrong");
+}
+
+#[test]
+fn test_keyspace_notifications() {
+    use redis::notifications::{
+        KeyEventKind, KeyspaceEvent, KeyspaceNotifications, NotificationOptions,
+    };
+    use redis::{Msg, Value};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let mut notifications_con = ctx.connection();
+    let options = NotificationOptions {
+        db: Some(0),
+        key_pattern: "watched:*".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(options.pattern(), "__keyspace@0__:watched:*");
+    let mut notifications = KeyspaceNotifications::new(&mut notifications_con, options).unwrap();
+    notifications
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let _: () = con.set("other", 1).unwrap();
+    let _: () = con.set("watched:a", 1).unwrap();
+    let _: () = con.del("watched:a").unwrap();
+    let _: () = con.rpush("watched:list", 1).unwrap();
+
+    let events: Vec<KeyspaceEvent> = (0..3)
+        .map(|_| notifications.next_event().unwrap())
+        .collect();
+    let kinds: Vec<(&str, KeyEventKind)> = events
+        .iter()
+        .map(|event| (event.key_name(), event.kind.clone()))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ("watched:a", KeyEventKind::Set),
+            ("watched:a", KeyEventKind::Del),
+            ("watched:list", KeyEventKind::RPush),
+        ]
+    );
+    assert!(events.iter().all(|event| event.db == 0));
+
+    let msg = Msg::from_value(&Value::Bulk(vec![
+        Value::Data(b"pmessage".to_vec()),
+        Value::Data(b"__keyevent@*__:*".to_vec()),
+        Value::Data(b"__keyevent@3__:hexpired".to_vec()),
+        Value::Data(b"user:1".to_vec()),
+    ]))
+    .unwrap();
+    assert_eq!(
+        KeyspaceEvent::from_msg(&msg),
+        Some(KeyspaceEvent {
+            db: 3,
+            key: b"user:1".to_vec(),
+            kind: KeyEventKind::Other("hexpired".to_string()),
+        })
+    );
+}
+
+#[test]
+fn test_monitor() {
+    use redis::monitor::MonitorEntry;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let mut monitor = ctx.client.get_monitor().unwrap();
+    monitor
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let _: () = con.set("monitored", "a \"b\"\n\x01").unwrap();
+
+    let entry = monitor
+        .by_ref()
+        .map(|entry| entry.unwrap())
+        .find(|entry| entry.command_name().eq_ignore_ascii_case("set"))
+        .unwrap();
+    assert_eq!(entry.db, 0);
+    assert_eq!(
+        entry.args,
+        vec![b"monitored".to_vec(), b"a \"b\"\n\x01".to_vec()]
+    );
+
+    let entry = MonitorEntry::parse(r#"1339518083.107412 [0 lua] "keys" "*""#).unwrap();
+    assert_eq!(entry.client, "lua");
+    assert_eq!(entry.command, b"keys".to_vec());
+    assert_eq!(entry.args, vec![b"*".to_vec()]);
+    assert!(MonitorEntry::parse("OK").is_none());
+}
+
 #[test]
 fn test_pubsub_unsubscribe() {
     let ctx = TestContext::new();
@@ -609,14 +2223,176 @@ fn test_script() {
     ",
     );
 
-    let _: () = redis::cmd("SET")
-        .arg("my_key")
-        .arg("foo")
-        .query(&mut con)
+    let _: () = redis::cmd("SET")
+        .arg("my_key")
+        .arg("foo")
+        .query(&mut con)
+        .unwrap();
+    let response = script.key("my_key").arg(42).invoke(&mut con);
+
+    assert_eq!(response, Ok(("foo".to_string(), 42)));
+}
+
+#[test]
+fn test_script_load() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = redis::cmd("SCRIPT").arg("FLUSH").query(&mut con).unwrap();
+    let script = redis::Script::new("return 1");
+    assert_eq!(script.exists_on(&mut con), Ok(false));
+    script.load_on(&mut con).unwrap();
+    assert_eq!(script.exists_on(&mut con), Ok(true));
+    assert_eq!(script.invoke(&mut con), Ok(1));
+}
+
+#[test]
+fn test_script_in_pipeline() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = redis::cmd("SCRIPT").arg("FLUSH").query(&mut con).unwrap();
+    let script = redis::Script::new("return redis.call('INCRBY', KEYS[1], ARGV[1])");
+
+    let mut pipe = redis::pipe();
+    pipe.atomic()
+        .cmd("SET")
+        .arg("counter")
+        .arg(1)
+        .ignore()
+        .invoke_script(script.key("counter").arg(2))
+        .invoke_script(script.key("counter").arg(3))
+        .cmd("GET")
+        .arg("counter");
+    let result: (i32, i32, i32) = pipe.query(&mut con).unwrap();
+    assert_eq!(result, (3, 6, 6));
+
+    // The script is loaded now, a second run does not load it again.
+    let result: (i32, i32, i32) = pipe.query(&mut con).unwrap();
+    assert_eq!(result, (3, 6, 6));
+    let exists: Vec<bool> = redis::cmd("SCRIPT")
+        .arg("EXISTS")
+        .arg(script.get_hash())
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(exists, vec![true]);
+}
+
+#[test]
+fn test_script_read_only() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![7, 0] {
+        return;
+    }
+
+    let _: () = con.set("my_key", "foo").unwrap();
+    let _: () = redis::cmd("SCRIPT").arg("FLUSH").query(&mut con).unwrap();
+
+    let read = redis::Script::new("return redis.call('GET', KEYS[1])");
+    let response: redis::RedisResult<String> = read.key("my_key").read_only().invoke(&mut con);
+    assert_eq!(response, Ok("foo".to_string()));
+    let response: redis::RedisResult<String> = read.key("my_key").read_only().invoke(&mut con);
+    assert_eq!(response, Ok("foo".to_string()));
+
+    let write = redis::Script::new("return redis.call('SET', KEYS[1], ARGV[1])");
+    let response: redis::RedisResult<()> =
+        write.key("my_key").arg("bar").read_only().invoke(&mut con);
+    assert!(response.is_err());
+    assert_eq!(con.get("my_key"), Ok("foo".to_string()));
+}
+
+#[test]
+fn test_functions() {
+    use redis::functions::{FunctionStats, Library, LibraryInfo, RestorePolicy};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![7, 0] {
+        return;
+    }
+
+    let _: () = con.function_flush().unwrap();
+    let library = Library::new(
+        r"#!lua name=testlib
+        redis.register_function('bump', function(keys, args)
+            return redis.call('INCRBY', keys[1], args[1])
+        end)
+        redis.register_function{
+            function_name = 'peek',
+            callback = function(keys, args) return redis.call('GET', keys[1]) end,
+            flags = {'no-writes'},
+        }
+    ",
+    );
+    assert_eq!(library.name(), "testlib");
+
+    // The library is loaded on the first call.
+    assert_eq!(
+        library
+            .function("bump")
+            .key("counter")
+            .arg(5)
+            .invoke(&mut con),
+        Ok(5)
+    );
+    assert_eq!(
+        library
+            .function("peek")
+            .key("counter")
+            .read_only()
+            .invoke(&mut con),
+        Ok(5)
+    );
+    assert_eq!(con.fcall("bump", &["counter"], 2), Ok(7));
+    assert_eq!(
+        con.fcall_ro("peek", &["counter"], Vec::<String>::new()),
+        Ok(7)
+    );
+
+    let libraries: Vec<LibraryInfo> = con.function_list(true).unwrap();
+    assert_eq!(libraries.len(), 1);
+    assert_eq!(libraries[0].name, "testlib");
+    assert_eq!(libraries[0].engine, "LUA");
+    assert_eq!(
+        libraries[0].code.as_ref().map(|code| &code[..]),
+        Some(library.code())
+    );
+    let mut names: Vec<&str> = libraries[0]
+        .functions
+        .iter()
+        .map(|function| &function.name[..])
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["bump", "peek"]);
+    let libraries: Vec<LibraryInfo> = con.function_list_matching("other*", false).unwrap();
+    assert!(libraries.is_empty());
+
+    let stats: FunctionStats = con.function_stats().unwrap();
+    assert_eq!(stats.running_script, None);
+    assert_eq!(stats.engines["LUA"].functions_count, 2);
+
+    let dump: Vec<u8> = con.function_dump().unwrap();
+    let _: () = con.function_delete("testlib").unwrap();
+    assert!(con.fcall::<_, _, _, i64>("bump", &["counter"], 1).is_err());
+    let _: () = con
+        .function_restore(&dump[..], RestorePolicy::Append)
+        .unwrap();
+    assert_eq!(con.fcall("bump", &["counter"], 1), Ok(8));
+    assert!(con
+        .function_restore::<_, ()>(&dump[..], RestorePolicy::Append)
+        .is_err());
+    let _: () = con
+        .function_restore(&dump[..], RestorePolicy::Replace)
         .unwrap();
-    let response = script.key("my_key").arg(42).invoke(&mut con);
-
-    assert_eq!(response, Ok(("foo".to_string(), 42)));
 }
 
 #[test]
@@ -826,3 +2602,976 @@ fn test_redis_server_down() {
     assert_eq!(ping.is_err(), true);
     assert_eq!(con.is_open(), false);
 }
+
+#[test]
+fn test_query_timeout() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let pong: String = redis::cmd("PING")
+        .query_timeout(&mut con, Duration::from_secs(1))
+        .unwrap();
+    assert_eq!(pong, "PONG");
+    assert_eq!(con.is_open(), true);
+
+    let result: redis::RedisResult<Option<(String, String)>> = redis::cmd("BLPOP")
+        .arg("empty_list")
+        .arg(2)
+        .query_timeout(&mut con, Duration::from_millis(100));
+    assert!(result.unwrap_err().is_timeout());
+
+    // the late reply of BLPOP must not be mistaken for another reply
+    assert_eq!(con.is_open(), false);
+}
+
+#[test]
+fn test_retry_policy() {
+    use redis::retry::{RetryPolicy, RetryableError};
+
+    let policy = RetryPolicy {
+        max_attempts: 4,
+        base_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(30),
+        ..Default::default()
+    };
+    assert_eq!(policy.delay(1), Duration::from_millis(10));
+    assert_eq!(policy.delay(2), Duration::from_millis(20));
+    assert_eq!(policy.delay(3), Duration::from_millis(30));
+
+    let jittered = RetryPolicy {
+        jitter: true,
+        ..policy.clone()
+    };
+    assert!(jittered.delay(3) <= Duration::from_millis(30));
+
+    let loading = redis::RedisError::from((
+        redis::ErrorKind::BusyLoadingError,
+        "Redis is loading the dataset in memory",
+    ));
+    assert_eq!(
+        RetryableError::classify(&loading),
+        Some(RetryableError::Loading)
+    );
+    assert!(policy.should_retry(&loading, 3));
+    assert!(!policy.should_retry(&loading, 4));
+
+    let wrong_type = redis::RedisError::from((redis::ErrorKind::TypeError, "wrong type"));
+    assert_eq!(RetryableError::classify(&wrong_type), None);
+    assert!(!policy.should_retry(&wrong_type, 1));
+    let try_again = redis::parse_redis_value(b"-TRYAGAIN Multiple keys request\r\n").unwrap_err();
+    assert_eq!(
+        RetryableError::classify(&try_again),
+        Some(RetryableError::ClusterReconfiguration)
+    );
+    assert!(policy.should_retry(&try_again, 1));
+
+    // redirects are followed by the cluster client instead
+    let moved = redis::parse_redis_value(b"-MOVED 3999 127.0.0.1:6381\r\n").unwrap_err();
+    assert_eq!(RetryableError::classify(&moved), None);
+    assert!(!policy.should_retry(&moved, 1));
+}
+
+#[test]
+fn test_server_error_kinds() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = con.set("key", "value").unwrap();
+    let err = con.lpush::<_, _, ()>("key", 1).unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::WrongType);
+    assert_eq!(err.code(), Some("WRONGTYPE"));
+    assert_eq!(err.extension_error_code(), Some("WRONGTYPE"));
+    assert!(!err.is_retryable());
+
+    redis::cmd("XGROUP")
+        .arg("CREATE")
+        .arg("stream")
+        .arg("group")
+        .arg("$")
+        .arg("MKSTREAM")
+        .execute(&mut con);
+    let err = redis::cmd("XGROUP")
+        .arg("CREATE")
+        .arg("stream")
+        .arg("group")
+        .arg("$")
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::BusyGroup);
+
+    let err = redis::cmd("ERRORTHATDOESNOTEXIST")
+        .query::<()>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ResponseError);
+    assert_eq!(err.code(), None);
+
+    let moved = redis::RedisError::from((redis::ErrorKind::Moved, "moved"));
+    assert!(moved.is_cluster_redirect());
+    assert_eq!(moved.extension_error_code(), Some("MOVED"));
+    assert!(!moved.is_retryable());
+}
+
+#[test]
+fn test_tcp_options() {
+    use redis::IntoConnectionInfo;
+
+    let ctx = TestContext::new();
+    let addr = match ctx.server.get_client_addr() {
+        &redis::ConnectionAddr::Tcp(ref host, port) => format!("redis://{}:{}/", host, port),
+        _ => return,
+    };
+    let mut info = (&addr[..]).into_connection_info().unwrap();
+    info.tcp.nodelay = true;
+    info.tcp.keepalive = Some(Duration::from_secs(60));
+    info.tcp.recv_buffer_size = Some(64 * 1024);
+
+    let client = redis::Client::open(info).unwrap();
+    let mut con = client.get_connection().unwrap();
+    assert_eq!(redis::cmd("PING").query(&mut con), Ok("PONG".to_string()));
+}
+
+#[test]
+fn test_fallback_addrs() {
+    use redis::{ConnectionAddr, IntoConnectionInfo};
+
+    let info = "redis://10.0.0.1/?fallback=10.0.0.2:6380&fallback=[::1]"
+        .into_connection_info()
+        .unwrap();
+    assert_eq!(
+        info.addrs(),
+        vec![
+            &ConnectionAddr::Tcp("10.0.0.1".to_string(), 6379),
+            &ConnectionAddr::Tcp("10.0.0.2".to_string(), 6380),
+            &ConnectionAddr::Tcp("::1".to_string(), 6379),
+        ]
+    );
+
+    let ctx = TestContext::new();
+    let mut info = "redis://127.0.0.1:1/".into_connection_info().unwrap();
+    info.fallback_addrs
+        .push(ctx.server.get_client_addr().clone());
+    let client = redis::Client::open(info).unwrap();
+    let mut con = client.get_connection().unwrap();
+    assert_eq!(redis::cmd("PING").query(&mut con), Ok("PONG".to_string()));
+}
+
+#[test]
+fn test_resolve_all_addresses() {
+    use redis::IntoConnectionInfo;
+
+    let ctx = TestContext::new();
+    let port = match ctx.server.get_client_addr() {
+        &redis::ConnectionAddr::Tcp(_, port) => port,
+        _ => return,
+    };
+    // localhost may resolve to ::1 first while the server only listens on
+    // 127.0.0.1, so every resolved address has to be tried
+    let url = format!("redis://localhost:{}/?shuffle_resolved_addrs=true", port);
+    let info = (&url[..]).into_connection_info().unwrap();
+    assert!(info.shuffle_resolved_addrs);
+    let client = redis::Client::open(info).unwrap();
+    let mut con = client.get_connection().unwrap();
+    assert_eq!(redis::cmd("PING").query(&mut con), Ok("PONG".to_string()));
+}
+
+#[test]
+fn test_custom_transport() {
+    use redis::IntoConnectionInfo;
+
+    let ctx = TestContext::new();
+    let (host, port) = match ctx.server.get_client_addr() {
+        &redis::ConnectionAddr::Tcp(ref host, port) => (host.clone(), port),
+        _ => return,
+    };
+    let stream = std::net::TcpStream::connect((&host[..], port)).unwrap();
+    let info = "redis://unused/3".into_connection_info().unwrap();
+
+    let mut con = redis::Connection::from_transport(stream, &info).unwrap();
+    assert_eq!(con.set("key", 42), Ok(()));
+    assert_eq!(con.get("key"), Ok(42));
+    assert_eq!(redis::ConnectionLike::get_db(&con), 3);
+}
+
+#[test]
+fn test_rediss_url() {
+    use redis::{ConnectionAddr, IntoConnectionInfo};
+
+    let info = "rediss://example.com:6380/?fallback=other.example.com"
+        .into_connection_info()
+        .unwrap();
+    assert_eq!(
+        info.addrs(),
+        vec![
+            &ConnectionAddr::TcpTls {
+                host: "example.com".to_string(),
+                port: 6380,
+            },
+            &ConnectionAddr::TcpTls {
+                host: "other.example.com".to_string(),
+                port: 6379,
+            },
+        ]
+    );
+    assert_eq!(info.addr.is_supported(), cfg!(feature = "tls-native"));
+}
+
+#[test]
+fn test_tls_client_cert() {
+    use redis::{IntoConnectionInfo, TlsOptions};
+    use std::env;
+    use std::fs;
+
+    let dir = env::temp_dir();
+    let cert = dir.join(format!("redis-rs-test-{}.crt", rand::random::<u64>()));
+    let key = dir.join(format!("redis-rs-test-{}.key", rand::random::<u64>()));
+    fs::write(&cert, "CERT").unwrap();
+    fs::write(&key, "KEY").unwrap();
+
+    let mut info = "rediss://127.0.0.1/".into_connection_info().unwrap();
+    assert_eq!(info.tls, TlsOptions::default());
+    info.tls = info.tls.with_client_cert_files(&cert, &key).unwrap();
+    assert_eq!(info.tls.client_cert, Some(b"CERT".to_vec()));
+    assert_eq!(info.tls.client_key, Some(b"KEY".to_vec()));
+    assert!(!format!("{:?}", info).contains("KEY"));
+
+    fs::remove_file(&cert).unwrap();
+    fs::remove_file(&key).unwrap();
+    assert!(TlsOptions::default()
+        .with_client_cert_files(&cert, &key)
+        .is_err());
+}
+
+#[test]
+fn test_tls_insecure_url() {
+    use redis::IntoConnectionInfo;
+
+    let info = "rediss://127.0.0.1/".into_connection_info().unwrap();
+    assert!(!info.tls.danger_accept_invalid_certs);
+    let info = "rediss://127.0.0.1/?insecure=true"
+        .into_connection_info()
+        .unwrap();
+    assert!(info.tls.danger_accept_invalid_certs);
+    assert!(!info.tls.danger_accept_invalid_hostnames);
+    assert!("rediss://127.0.0.1/?insecure=maybe"
+        .into_connection_info()
+        .is_err());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_unix_url() {
+    use redis::{ConnectionAddr, IntoConnectionInfo};
+    use std::path::PathBuf;
+
+    for url in &[
+        "redis+unix:///var/run/redis.sock?db=2&password=secret",
+        "unix:///var/run/redis.sock?db=2&password=secret",
+    ] {
+        let info = url.to_string().into_connection_info().unwrap();
+        assert_eq!(
+            *info.addr,
+            ConnectionAddr::Unix(PathBuf::from("/var/run/redis.sock"))
+        );
+        assert_eq!(info.db, 2);
+        assert_eq!(info.passwd, Some("secret".to_string()));
+    }
+    assert!("redis+unix:///var/run/redis.sock?db=x"
+        .into_connection_info()
+        .is_err());
+}
+
+#[test]
+fn test_url_query_params() {
+    use redis::IntoConnectionInfo;
+    use std::time::Duration;
+
+    let info = "redis://127.0.0.1/?db=3&protocol=resp2&connect_timeout=2s&response_timeout=250ms&client_name=myapp"
+        .into_connection_info()
+        .unwrap();
+    assert_eq!(info.db, 3);
+    assert_eq!(info.connect_timeout, Some(Duration::from_secs(2)));
+    assert_eq!(info.response_timeout, Some(Duration::from_millis(250)));
+    assert_eq!(info.client_name, Some("myapp".to_string()));
+    assert!(info.set_lib_info);
+    assert!(
+        !"redis://127.0.0.1/?set_lib_info=false"
+            .into_connection_info()
+            .unwrap()
+            .set_lib_info
+    );
+
+    assert!("redis://127.0.0.1/?db=x".into_connection_info().is_err());
+    assert!("redis://127.0.0.1/?protocol=resp4"
+        .into_connection_info()
+        .is_err());
+    assert_eq!(info.protocol, redis::ProtocolVersion::RESP2);
+    let info = "redis://127.0.0.1/?protocol=resp3"
+        .into_connection_info()
+        .unwrap();
+    assert_eq!(info.protocol, redis::ProtocolVersion::RESP3);
+}
+
+#[test]
+fn test_resp3() {
+    use redis::{IntoConnectionInfo, ProtocolVersion};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let major: u32 = version.split('.').next().unwrap().parse().unwrap();
+
+    let info = redis::ConnectionInfo {
+        addr: Box::new(ctx.server.get_client_addr().clone()),
+        protocol: ProtocolVersion::RESP3,
+        .."redis://127.0.0.1/".into_connection_info().unwrap()
+    };
+    let mut con = redis::Client::open(info).unwrap().get_connection().unwrap();
+    if major >= 6 {
+        assert_eq!(con.protocol(), ProtocolVersion::RESP3);
+    } else {
+        assert_eq!(con.protocol(), ProtocolVersion::RESP2);
+    }
+
+    // maps, sets and doubles convert like their RESP2 counterparts
+    let _: () = con.hset_multiple("hash", &[("a", 1), ("b", 2)]).unwrap();
+    let hash: HashMap<String, i64> = con.hgetall("hash").unwrap();
+    assert_eq!(hash.len(), 2);
+    assert_eq!(hash["b"], 2);
+    let _: () = con.sadd("set", &[1, 2, 3]).unwrap();
+    let set: HashSet<i64> = con.smembers("set").unwrap();
+    assert_eq!(set.len(), 3);
+    let _: () = con.zadd("zset", "one", 1.5).unwrap();
+    assert_eq!(con.zscore("zset", "one"), Ok(1.5));
+    assert_eq!(con.exists("zset"), Ok(true));
+}
+
+#[test]
+fn test_reply_limits() {
+    use redis::IntoConnectionInfo;
+
+    let ctx = TestContext::new();
+    let mut info = redis::ConnectionInfo {
+        addr: Box::new(ctx.server.get_client_addr().clone()),
+        .."redis://127.0.0.1/".into_connection_info().unwrap()
+    };
+    info.reply_limits.max_bulk_size = 100;
+    info.reply_limits.max_array_len = 10;
+    info.reply_limits.max_reply_size = 200;
+    let client = redis::Client::open(info).unwrap();
+
+    let mut con = ctx.connection();
+    let _: () = con.set("small", "x".repeat(100)).unwrap();
+    let _: () = con.set("large", "x".repeat(101)).unwrap();
+    let _: () = con.rpush("list", &[1; 11][..]).unwrap();
+    let _: () = con.rpush("wide", &vec!["x".repeat(50); 4][..]).unwrap();
+
+    let mut limited = client.get_connection().unwrap();
+    assert_eq!(limited.get("small"), Ok("x".repeat(100)));
+    let err = limited.get::<_, String>("large").unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ReplyTooLarge);
+    // the rest of the reply is not read, so the connection is unusable
+    assert!(!limited.is_open());
+
+    let mut limited = client.get_connection().unwrap();
+    let err = limited.lrange::<_, Vec<i32>>("list", 0, -1).unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ReplyTooLarge);
+
+    let mut limited = client.get_connection().unwrap();
+    let err = limited.lrange::<_, Vec<String>>("wide", 0, -1).unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::ReplyTooLarge);
+}
+
+#[test]
+fn test_push_sender() {
+    use redis::{IntoConnectionInfo, ProtocolVersion, PushKind};
+    use std::sync::mpsc;
+
+    let ctx = TestContext::new();
+    let info = redis::ConnectionInfo {
+        addr: Box::new(ctx.server.get_client_addr().clone()),
+        protocol: ProtocolVersion::RESP3,
+        .."redis://127.0.0.1/".into_connection_info().unwrap()
+    };
+    let mut con = redis::Client::open(info).unwrap().get_connection().unwrap();
+    if con.protocol() != ProtocolVersion::RESP3 {
+        return;
+    }
+    let (sender, receiver) = mpsc::channel();
+    con.set_push_sender(sender);
+
+    let _: () = redis::cmd("CLIENT")
+        .arg("TRACKING")
+        .arg("ON")
+        .query(&mut con)
+        .unwrap();
+    let _: () = con.set("key", 1).unwrap();
+    assert_eq!(con.get("key"), Ok(1));
+
+    // modifying the key elsewhere invalidates it
+    let mut other = ctx.connection();
+    let _: () = other.set("key", 2).unwrap();
+
+    // the push message does not end up as the reply of a command
+    assert_eq!(con.get("key"), Ok(2));
+    let push = receiver.try_recv().unwrap();
+    assert_eq!(push.kind, PushKind::Invalidate);
+    assert_eq!(
+        push.data,
+        vec![redis::Value::Bulk(vec![redis::Value::Data(
+            b"key".to_vec()
+        )])]
+    );
+}
+
+#[test]
+fn test_client_name() {
+    let ctx = TestContext::new();
+    let url = match ctx.server.get_client_addr() {
+        &redis::ConnectionAddr::Tcp(ref host, port) => {
+            format!("redis://{}:{}/?client_name=redis-rs-test", host, port)
+        }
+        _ => return,
+    };
+    let client = redis::Client::open(&url[..]).unwrap();
+    let mut con = client.get_connection().unwrap();
+
+    let name: String = redis::cmd("CLIENT").arg("GETNAME").query(&mut con).unwrap();
+    assert_eq!(name, "redis-rs-test");
+
+    // `CLIENT SETINFO` needs redis 7.2
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![7, 2] {
+        return;
+    }
+    let info: redis::clients::ClientInfo = con.client_info().unwrap();
+    assert_eq!(info.get("lib-name"), Some("redis-rs"));
+    assert_eq!(info.get("lib-ver"), Some(env!("CARGO_PKG_VERSION")));
+
+    let client = redis::Client::open(&format!("{}&set_lib_info=false", url)[..]).unwrap();
+    let mut con = client.get_connection().unwrap();
+    let info: redis::clients::ClientInfo = con.client_info().unwrap();
+    assert_eq!(info.get("lib-name"), Some(""));
+}
+
+#[test]
+fn test_client_list() {
+    use redis::clients::{ClientInfo, ClientType};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let mut pubsub_con = ctx.connection();
+    let _: () = redis::cmd("CLIENT")
+        .arg("SETNAME")
+        .arg("lister")
+        .query(&mut con)
+        .unwrap();
+    let _: () = redis::cmd("SELECT").arg(2).query(&mut con).unwrap();
+    let pubsub_id: i64 = pubsub_con.client_id().unwrap();
+    let mut pubsub = pubsub_con.as_pubsub();
+    pubsub.subscribe("news").unwrap();
+
+    let id: i64 = con.client_id().unwrap();
+    let clients: Vec<ClientInfo> = con.client_list().unwrap();
+    assert_eq!(clients.len(), 2);
+    let me = clients.iter().find(|client| client.id == id).unwrap();
+    assert_eq!(me.name, Some("lister".to_string()));
+    assert_eq!(me.db, 2);
+    assert_eq!(me.client_type(), ClientType::Normal);
+    // `client|list` since redis 7
+    assert!(me.cmd.starts_with("client"));
+
+    let clients: Vec<ClientInfo> = con.client_list_type(ClientType::PubSub).unwrap();
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0].id, pubsub_id);
+    assert_eq!(clients[0].sub, 1);
+    assert_eq!(clients[0].client_type(), ClientType::PubSub);
+
+    let info = ClientInfo::parse(
+        "id=3 addr=127.0.0.1:52555 fd=8 name= age=855 idle=0 flags=N db=0 sub=0 psub=0 \
+         multi=-1 qbuf=0 qbuf-free=32768 obl=0 oll=0 omem=0 events=r cmd=client",
+    )
+    .unwrap();
+    assert_eq!(info.name, None);
+    assert_eq!(info.age, Duration::from_secs(855));
+    assert_eq!(info.ssub, None);
+    assert_eq!(info.get("qbuf-free"), Some("32768"));
+    assert!(ClientInfo::parse("id=x").is_err());
+}
+
+#[test]
+fn test_client_pause() {
+    use redis::clients::{ClientInfo, PauseMode};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![6, 2] {
+        return;
+    }
+
+    let _: () = con.set("key", 1).unwrap();
+    let _: () = con.client_pause(60_000, PauseMode::Write).unwrap();
+
+    let mut writer = ctx.connection();
+    let handle = spawn(move || {
+        let _: () = writer.set("key", 2).unwrap();
+    });
+    sleep(Duration::from_millis(100));
+    // reads go on while writes wait
+    let mut reader = ctx.connection();
+    assert_eq!(reader.get("key"), Ok(1));
+
+    let _: () = con.client_unpause().unwrap();
+    handle.join().unwrap();
+    assert_eq!(reader.get("key"), Ok(2));
+
+    if version < vec![7, 2] {
+        return;
+    }
+    let _: () = con.client_no_evict(true).unwrap();
+    let _: () = con.client_no_touch(true).unwrap();
+    let info: ClientInfo = con.client_info().unwrap();
+    assert!(info.has_flag('e'));
+    assert!(info.has_flag('T'));
+    let _: () = con.client_no_evict(false).unwrap();
+    let _: () = con.client_no_touch(false).unwrap();
+    let info: ClientInfo = con.client_info().unwrap();
+    assert!(!info.has_flag('e'));
+    assert!(!info.has_flag('T'));
+}
+
+#[test]
+fn test_recover() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![6, 2] {
+        assert_eq!(
+            con.recover().unwrap_err().kind(),
+            redis::ErrorKind::ResponseError
+        );
+        return;
+    }
+
+    // stuck in a transaction with a reply outstanding
+    let _: () = redis::cmd("MULTI").query(&mut con).unwrap();
+    let _: () = con.set("key", 1).unwrap();
+    con.send_packed_command(&redis::cmd("SET").arg("key").arg(2).get_packed_command())
+        .unwrap();
+    con.recover().unwrap();
+    assert_eq!(con.get("key"), Ok(None::<i32>));
+
+    // stuck in subscribed state
+    con.send_packed_command(&redis::cmd("SUBSCRIBE").arg("news").get_packed_command())
+        .unwrap();
+    con.recover().unwrap();
+    let _: () = con.set("key", 3).unwrap();
+    assert_eq!(con.get("key"), Ok(3));
+
+    // the database is selected again
+    let _: () = redis::cmd("SELECT").arg(1).query(&mut con).unwrap();
+    con.recover().unwrap();
+    assert_eq!(con.get("key"), Ok(3));
+}
+
+#[test]
+fn test_client_kill() {
+    use redis::clients::{ClientKillOptions, ClientType};
+    use redis::ToRedisArgs;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let mut victim = ctx.connection();
+    let id: i64 = redis::cmd("CLIENT").arg("ID").query(&mut victim).unwrap();
+
+    let options = ClientKillOptions::new().id(id);
+    assert_eq!(
+        options.to_redis_args(),
+        vec![b"ID".to_vec(), id.to_string().into_bytes()]
+    );
+    assert_eq!(con.client_kill(&options), Ok(1));
+    assert!(redis::cmd("PING").query::<String>(&mut victim).is_err());
+    assert_eq!(con.client_kill(&options), Ok(0));
+
+    // the connection sending the command is skipped by default
+    let options = ClientKillOptions::new().client_type(ClientType::Normal);
+    let killed: usize = con.client_kill(&options).unwrap();
+    assert_eq!(killed, 0);
+    assert_eq!(
+        redis::cmd("PING").query::<String>(&mut con),
+        Ok("PONG".to_string())
+    );
+    assert!(con.client_kill::<usize>(&options.skipme(false)).is_ok());
+    assert!(redis::cmd("PING").query::<String>(&mut con).is_err());
+}
+
+#[test]
+fn test_replication_commands() {
+    let master = TestContext::new();
+    let (host, port) = match master.server.get_client_addr() {
+        &redis::ConnectionAddr::Tcp(ref host, port) => (host.clone(), port),
+        _ => return,
+    };
+    let replica = TestContext::new();
+    let mut con = replica.connection();
+
+    let _: () = con.slaveof(&host[..], port).unwrap();
+    let info: redis::InfoDict = redis::cmd("INFO")
+        .arg("replication")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(info.get("role"), Some("slave".to_string()));
+    assert_eq!(info.get("master_port"), Some(port));
+
+    let _: () = con.slaveof_no_one().unwrap();
+    let info: redis::InfoDict = redis::cmd("INFO")
+        .arg("replication")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(info.get("role"), Some("master".to_string()));
+}
+
+#[test]
+fn test_rate_limiters() {
+    use redis::ratelimit::{Algorithm, RateLimiter};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let window = Duration::from_secs(60);
+
+    for &algorithm in &[
+        Algorithm::FixedWindow,
+        Algorithm::SlidingWindowLog,
+        Algorithm::TokenBucket,
+    ] {
+        let limiter = RateLimiter::new(algorithm);
+        let key = format!("limit:{:?}", algorithm);
+        for remaining in (0..3).rev() {
+            let decision = limiter.check(&mut con, &key[..], 3, window).unwrap();
+            assert!(decision.allowed, "{:?}", algorithm);
+            assert_eq!(decision.remaining, remaining, "{:?}", algorithm);
+            assert_eq!(decision.retry_after, None);
+        }
+
+        let decision = limiter.check(&mut con, &key[..], 3, window).unwrap();
+        assert!(!decision.allowed, "{:?}", algorithm);
+        assert_eq!(decision.remaining, 0);
+        let retry_after = decision.retry_after.unwrap();
+        assert!(retry_after > Duration::from_secs(0) && retry_after <= window);
+        match con.pttl_typed(&key[..]).unwrap() {
+            redis::keys::Ttl::Expires(_) => {}
+            ttl => panic!("{:?} left {:?}", algorithm, ttl),
+        }
+    }
+
+    // the window starts over once the key expired
+    let limiter = RateLimiter::fixed_window();
+    let window = Duration::from_millis(100);
+    assert!(limiter.check(&mut con, "short", 1, window).unwrap().allowed);
+    assert!(!limiter.check(&mut con, "short", 1, window).unwrap().allowed);
+    sleep(Duration::from_millis(150));
+    assert!(limiter.check(&mut con, "short", 1, window).unwrap().allowed);
+
+    assert!(limiter.check(&mut con, "zero", 0, window).is_err());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_cache_get_or_set() {
+    use redis::cache::Cache;
+    use redis::codec::JsonCodec;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let cache: Cache<JsonCodec> = Cache::new();
+    let ttl = Duration::from_secs(60);
+
+    let value: redis::RedisResult<Vec<u32>> =
+        cache.get_or_set(&mut con, "numbers", ttl, || Ok(vec![1, 2, 3]));
+    assert_eq!(value, Ok(vec![1, 2, 3]));
+    let value: redis::RedisResult<Vec<u32>> =
+        cache.get_or_set(&mut con, "numbers", ttl, || panic!("computed twice"));
+    assert_eq!(value, Ok(vec![1, 2, 3]));
+    assert!(con.pttl_typed("numbers").unwrap() != redis::keys::Ttl::NoExpiry);
+
+    // errors are not cached and release the lock
+    let failed: redis::RedisResult<u32> = cache.get_or_set(&mut con, "failing", ttl, || {
+        Err((redis::ErrorKind::ResponseError, "unavailable").into())
+    });
+    assert!(failed.is_err());
+    assert_eq!(con.exists("failing"), Ok(false));
+    assert_eq!(con.exists("failing:lock"), Ok(false));
+
+    // concurrent misses compute the value once
+    let computed = Arc::new(AtomicUsize::new(0));
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let client = ctx.client.clone();
+            let cache = cache.clone();
+            let computed = computed.clone();
+            spawn(move || {
+                let mut con = client.get_connection().unwrap();
+                let value: redis::RedisResult<String> =
+                    cache.get_or_set(&mut con, "slow", ttl, || {
+                        computed.fetch_add(1, Ordering::SeqCst);
+                        sleep(Duration::from_millis(200));
+                        Ok("done".to_string())
+                    });
+                value.unwrap()
+            })
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), "done");
+    }
+    assert_eq!(computed.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_leaderboard() {
+    use redis::leaderboard::{Entry, Leaderboard, Order, ScorePolicy};
+    use std::time::UNIX_EPOCH;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let board = Leaderboard::new("scores").policy(ScorePolicy::Increment);
+    for &(member, score) in &[
+        ("a", 10.0),
+        ("b", 30.0),
+        ("c", 20.0),
+        ("d", 5.0),
+        ("a", 15.0),
+    ] {
+        board.submit_score(&mut con, member, score).unwrap();
+    }
+    assert_eq!(board.score_of(&mut con, "a"), Ok(Some(25.0)));
+    assert_eq!(board.rank_of(&mut con, "b"), Ok(Some(0)));
+    assert_eq!(board.rank_of(&mut con, "x"), Ok(None));
+    assert_eq!(board.len(&mut con), Ok(4));
+
+    let top: Vec<Entry<String>> = board.top(&mut con, 2).unwrap();
+    let expected = vec![
+        Entry {
+            member: "b".to_string(),
+            score: 30.0,
+            rank: 0,
+        },
+        Entry {
+            member: "a".to_string(),
+            score: 25.0,
+            rank: 1,
+        },
+    ];
+    assert_eq!(top, expected);
+
+    let around: Vec<Entry<String>> = board.around(&mut con, "c", 1).unwrap();
+    let around: Vec<(&str, u64)> = around.iter().map(|e| (&e.member[..], e.rank)).collect();
+    assert_eq!(around, vec![("a", 1), ("c", 2), ("d", 3)]);
+    let around: Vec<Entry<String>> = board.around(&mut con, "b", 1).unwrap();
+    assert_eq!(around.len(), 2);
+    assert!(board
+        .around::<_, String>(&mut con, "x", 1)
+        .unwrap()
+        .is_empty());
+
+    let fastest = Leaderboard::new("scores").order(Order::LowestFirst);
+    assert_eq!(fastest.rank_of(&mut con, "d"), Ok(Some(0)));
+
+    assert_eq!(board.rotate(&mut con, "scores:archive", None), Ok(true));
+    assert_eq!(board.len(&mut con), Ok(0));
+    assert_eq!(board.rotate(&mut con, "scores:archive", None), Ok(false));
+    let archive = Leaderboard::new("scores:archive");
+    assert_eq!(archive.len(&mut con), Ok(4));
+    assert_eq!(archive.reset(&mut con), Ok(true));
+
+    let period = Duration::from_secs(3600);
+    let at = UNIX_EPOCH + Duration::from_secs(7200);
+    assert_eq!(
+        Leaderboard::for_period("daily", period, at).key(),
+        "daily:2"
+    );
+
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![6, 2] {
+        return;
+    }
+    let best = Leaderboard::new("laps")
+        .order(Order::LowestFirst)
+        .policy(ScorePolicy::KeepBest)
+        .expire_after(Duration::from_secs(60));
+    assert_eq!(best.submit_score(&mut con, "a", 62.5), Ok(62.5));
+    assert_eq!(best.submit_score(&mut con, "a", 64.0), Ok(62.5));
+    assert_eq!(best.submit_score(&mut con, "a", 61.0), Ok(61.0));
+    match con.pttl_typed("laps").unwrap() {
+        redis::keys::Ttl::Expires(_) => {}
+        ttl => panic!("leaderboard left {:?}", ttl),
+    }
+}
+
+#[test]
+fn test_namespaced_connection() {
+    use redis::namespace::NamespacedConnection;
+
+    let ctx = TestContext::new();
+    let mut raw = ctx.connection();
+    let _: () = raw.set("outside", 1).unwrap();
+    let mut con = NamespacedConnection::new(ctx.connection(), "app:");
+
+    let _: () = con.set("a", 1).unwrap();
+    let _: () = con.set_multiple(&[("b", 2), ("c*", 3)]).unwrap();
+    assert_eq!(raw.get("app:a"), Ok(1));
+    assert_eq!(con.get("a"), Ok(1));
+    assert_eq!(con.get(&["a", "b"]), Ok((1, 2)));
+    assert_eq!(con.exists("outside"), Ok(false));
+
+    let mut keys: Vec<String> = con.scan().unwrap().collect();
+    keys.sort();
+    assert_eq!(keys, vec!["a", "b", "c*"]);
+    let keys: Vec<String> = con.scan_match("c*").unwrap().collect();
+    assert_eq!(keys, vec!["c*"]);
+    let mut keys: Vec<String> = con.keys("*").unwrap();
+    keys.sort();
+    assert_eq!(keys, vec!["a", "b", "c*"]);
+
+    let _: () = con.rpush("queue", "job").unwrap();
+    assert_eq!(
+        con.blpop("queue", 1),
+        Ok(("queue".to_string(), "job".to_string()))
+    );
+
+    let (renamed, keys): (bool, Vec<String>) = redis::pipe()
+        .atomic()
+        .cmd("RENAMENX")
+        .arg("a")
+        .arg("d")
+        .cmd("KEYS")
+        .arg("d")
+        .query(&mut con)
+        .unwrap();
+    assert!(renamed);
+    assert_eq!(keys, vec!["d"]);
+
+    let script = redis::Script::new("return redis.call('GET', KEYS[1])");
+    assert_eq!(script.key("b").invoke(&mut con), Ok(2));
+    assert_eq!(raw.get("outside"), Ok(1));
+}
+
+#[test]
+fn test_keyspace_copy() {
+    use redis::keyspace::{CopyProgress, KeyspaceCopy};
+
+    let source_ctx = TestContext::new();
+    let target_ctx = TestContext::new();
+    let mut source = source_ctx.connection();
+    let mut target = target_ctx.connection();
+
+    for i in 0..25 {
+        let _: () = source.set(format!("copy:{}", i), i).unwrap();
+    }
+    let _: () = source.set_ex("copy:expiring", "soon", 100).unwrap();
+    let _: () = source.rpush("copy:list", &[1, 2, 3]).unwrap();
+    let _: () = source.set("other", 1).unwrap();
+    let _: () = target.set("copy:0", "kept").unwrap();
+
+    let mut reports = 0;
+    let progress = KeyspaceCopy::new()
+        .with_pattern("copy:*")
+        .batch_size(10)
+        .copy(&mut source, &mut target, |_| reports += 1)
+        .unwrap();
+    assert_eq!(
+        progress,
+        CopyProgress {
+            scanned: 27,
+            copied: 26,
+            skipped: 1,
+        }
+    );
+    assert!(reports >= 3);
+    assert_eq!(target.get("copy:0"), Ok("kept".to_string()));
+    assert_eq!(target.get("copy:24"), Ok(24));
+    assert_eq!(target.lrange("copy:list", 0, -1), Ok(vec![1, 2, 3]));
+    assert_eq!(target.exists("other"), Ok(false));
+    let ttl: i64 = target.ttl("copy:expiring").unwrap();
+    assert!(ttl > 90 && ttl <= 100);
+
+    // copies over several connections and replaces existing keys
+    let targets = vec![target_ctx.connection(), target_ctx.connection()];
+    let progress = KeyspaceCopy::new()
+        .replace()
+        .batch_size(5)
+        .copy_parallel(&mut source, targets, |_| {})
+        .unwrap();
+    assert_eq!(progress.copied, 28);
+    assert_eq!(target.get("copy:0"), Ok(0));
+    assert_eq!(target.get("other"), Ok(1));
+}
+
+#[test]
+fn test_flush_and_shutdown() {
+    use redis::options::{FlushMode, ShutdownOptions};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = con.set("key", 1).unwrap();
+    let _: () = con.flushdb_options(FlushMode::Async).unwrap();
+    assert_eq!(con.exists("key"), Ok(false));
+
+    let _: () = redis::cmd("SELECT").arg(1).query(&mut con).unwrap();
+    let _: () = con.set("key", 1).unwrap();
+    let _: () = con.flushall().unwrap();
+    assert_eq!(con.exists("key"), Ok(false));
+
+    let err = con
+        .shutdown::<()>(&ShutdownOptions::new().nosave())
+        .unwrap_err();
+    assert!(err.is_connection_dropped() || err.is_io_error());
+}
+
+#[test]
+fn test_command_info_and_docs() {
+    use redis::server::{CommandDocs, CommandInfo};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let count: usize = con.command_count().unwrap();
+    assert!(count > 100);
+
+    let infos: Vec<Option<CommandInfo>> = con.command_info(&["get", "nosuchcommand"]).unwrap();
+    assert_eq!(infos.len(), 2);
+    let get = infos[0].as_ref().unwrap();
+    assert_eq!(get.name, "get");
+    assert_eq!(get.arity, 2);
+    assert!(get.has_flag("READONLY"));
+    assert_eq!((get.first_key, get.last_key, get.step), (1, 1, 1));
+    assert_eq!(infos[1], None);
+
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    if version < vec![7] {
+        return;
+    }
+
+    let docs: HashMap<String, CommandDocs> = con.command_docs("set").unwrap();
+    let set = &docs["set"];
+    assert_eq!(set.group, Some("string".to_string()));
+    assert_eq!(set.arguments[0].kind, "key");
+    assert!(set.arguments.iter().any(|arg| arg.is_optional()));
+    let docs: HashMap<String, CommandDocs> = con.command_docs("nosuchcommand").unwrap();
+    assert!(docs.is_empty());
+}
@@ -0,0 +1,128 @@
+extern crate redis;
+
+use redis::bloom::{BloomInfo, CmsInfo, CuckooInfo, TDigestInfo, TopKInfo};
+use redis::{Commands, Connection};
+
+use support::*;
+
+mod support;
+
+// The tests are skipped when the server does not have the module.
+fn has_bloom(con: &mut Connection) -> bool {
+    con.bf_exists::<_, _, bool>("probe", "item").is_ok()
+}
+
+#[test]
+fn test_bloom_filter() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    if !has_bloom(&mut con) {
+        return;
+    }
+
+    let _: () = con.bf_reserve("bf", 0.01, 1000).unwrap();
+    assert_eq!(con.bf_add("bf", "a"), Ok(true));
+    assert_eq!(con.bf_add("bf", "a"), Ok(false));
+    assert_eq!(
+        con.bf_madd("bf", &["b", "c", "a"]),
+        Ok(vec![true, true, false])
+    );
+    assert_eq!(con.bf_exists("bf", "b"), Ok(true));
+    assert_eq!(con.bf_mexists("bf", &["c", "x"]), Ok(vec![true, false]));
+    assert_eq!(con.bf_card("bf"), Ok(3));
+
+    let info: BloomInfo = con.bf_info("bf").unwrap();
+    assert_eq!(info.capacity, 1000);
+    assert_eq!(info.items, 3);
+    assert_eq!(info.filters, 1);
+    assert!(info.size > 0);
+}
+
+#[test]
+fn test_cuckoo_filter() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    if !has_bloom(&mut con) {
+        return;
+    }
+
+    let _: () = con.cf_reserve("cf", 1000).unwrap();
+    assert_eq!(con.cf_add("cf", "a"), Ok(true));
+    assert_eq!(con.cf_add("cf", "a"), Ok(true));
+    assert_eq!(con.cf_addnx("cf", "b"), Ok(true));
+    assert_eq!(con.cf_addnx("cf", "b"), Ok(false));
+    assert_eq!(con.cf_count("cf", "a"), Ok(2));
+    assert_eq!(con.cf_mexists("cf", &["a", "x"]), Ok(vec![true, false]));
+    assert_eq!(con.cf_del("cf", "a"), Ok(true));
+    assert_eq!(con.cf_del("cf", "x"), Ok(false));
+
+    let info: CuckooInfo = con.cf_info("cf").unwrap();
+    assert_eq!(info.items_inserted, 2);
+    assert_eq!(info.items_deleted, 1);
+    assert!(info.buckets > 0);
+}
+
+#[test]
+fn test_count_min_sketch_and_top_k() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    if !has_bloom(&mut con) {
+        return;
+    }
+
+    let _: () = con.cms_initbydim("cms", 2000, 5).unwrap();
+    assert_eq!(
+        con.cms_incrby("cms", &[("a", 3), ("b", 1), ("a", 2)]),
+        Ok(vec![3, 1, 5])
+    );
+    assert_eq!(con.cms_query("cms", &["a", "b", "c"]), Ok(vec![5, 1, 0]));
+    let info: CmsInfo = con.cms_info("cms").unwrap();
+    assert_eq!((info.width, info.depth, info.count), (2000, 5, 6));
+
+    let _: () = con.topk_reserve("topk", 2).unwrap();
+    let _: Vec<Option<String>> = con.topk_incrby("topk", &[("a", 5), ("b", 3)]).unwrap();
+    let expelled: Vec<Option<String>> = con.topk_add("topk", &["c"]).unwrap();
+    assert_eq!(expelled, vec![None]);
+    assert_eq!(con.topk_query("topk", &["a", "c"]), Ok(vec![true, false]));
+    assert_eq!(
+        con.topk_list("topk"),
+        Ok(vec!["a".to_string(), "b".to_string()])
+    );
+    assert_eq!(
+        con.topk_list_with_count("topk"),
+        Ok(vec![("a".to_string(), 5), ("b".to_string(), 3)])
+    );
+    let info: TopKInfo = con.topk_info("topk").unwrap();
+    assert_eq!(info.k, 2);
+    assert!(info.decay > 0.0);
+}
+
+#[test]
+fn test_tdigest() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    if !has_bloom(&mut con) {
+        return;
+    }
+    // t-digests were added in RedisBloom 2.4
+    if con.tdigest_create::<_, ()>("td", Some(200)).is_err() {
+        return;
+    }
+
+    let values: Vec<f64> = (1..101).map(f64::from).collect();
+    let _: () = con.tdigest_add("td", &values).unwrap();
+    assert_eq!(con.tdigest_min("td"), Ok(1.0));
+    assert_eq!(con.tdigest_max("td"), Ok(100.0));
+    let median: Vec<f64> = con.tdigest_quantile("td", &[0.5]).unwrap();
+    assert!((median[0] - 50.0).abs() <= 1.0);
+    let cdf: Vec<f64> = con.tdigest_cdf("td", &[25.0]).unwrap();
+    assert!((cdf[0] - 0.25).abs() <= 0.02);
+
+    let info: TDigestInfo = con.tdigest_info("td").unwrap();
+    assert_eq!(info.compression, 200);
+    assert_eq!(info.observations, 100);
+
+    let _: () = con.tdigest_reset("td").unwrap();
+    let info: TDigestInfo = con.tdigest_info("td").unwrap();
+    assert_eq!(info.observations, 0);
+}
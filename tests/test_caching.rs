@@ -0,0 +1,150 @@
+extern crate redis;
+
+use std::time::Duration;
+
+use redis::caching::{CacheConfig, CachingConnection};
+use redis::{Commands, IntoConnectionInfo, ProtocolVersion};
+
+use support::*;
+
+mod support;
+
+// Returns `None` if the server does not support RESP3.
+fn caching_connection(ctx: &TestContext, config: CacheConfig) -> Option<CachingConnection> {
+    let info = redis::ConnectionInfo {
+        addr: Box::new(ctx.server.get_client_addr().clone()),
+        protocol: ProtocolVersion::RESP3,
+        .."redis://127.0.0.1/".into_connection_info().unwrap()
+    };
+    let con = redis::Client::open(info).unwrap().get_connection().unwrap();
+    if con.protocol() != ProtocolVersion::RESP3 {
+        return None;
+    }
+    Some(CachingConnection::new(con, config).unwrap())
+}
+
+#[test]
+fn test_requires_resp3() {
+    let ctx = TestContext::new();
+    let err = CachingConnection::new(ctx.connection(), CacheConfig::default())
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), redis::ErrorKind::InvalidClientConfig);
+}
+
+#[test]
+fn test_invalidation() {
+    let ctx = TestContext::new();
+    let mut con = match caching_connection(&ctx, CacheConfig::default()) {
+        Some(con) => con,
+        None => return,
+    };
+    let mut other = ctx.connection();
+
+    let _: () = other.set("key", 1).unwrap();
+    assert_eq!(con.get("key"), Ok(1));
+    assert_eq!(con.get("key"), Ok(1));
+    assert_eq!(con.cached_entries(), 1);
+
+    // a change by another connection removes the cached reply
+    let _: () = other.set("key", 2).unwrap();
+    assert_eq!(con.get("key"), Ok(2));
+
+    // so does a change by the connection itself
+    let _: () = con.set("key", 3).unwrap();
+    assert_eq!(con.get("key"), Ok(3));
+
+    // writes are never cached
+    let _: () = con.hset("hash", "field", 1).unwrap();
+    assert_eq!(con.hget("hash", "field"), Ok(1));
+    assert_eq!(con.cached_entries(), 2);
+
+    let _: () = redis::cmd("FLUSHDB").query(&mut other).unwrap();
+    assert_eq!(con.get("key"), Ok(None::<i32>));
+    assert_eq!(con.hget("hash", "field"), Ok(None::<i32>));
+}
+
+#[test]
+fn test_limits() {
+    let ctx = TestContext::new();
+    let config = CacheConfig {
+        max_entries: 2,
+        ttl: Some(Duration::from_millis(50)),
+    };
+    let mut con = match caching_connection(&ctx, config) {
+        Some(con) => con,
+        None => return,
+    };
+
+    for key in &["a", "b", "c"] {
+        let _: () = con.set(*key, *key).unwrap();
+        assert_eq!(con.get(*key), Ok(key.to_string()));
+    }
+    assert_eq!(con.cached_entries(), 2);
+
+    ::std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(con.get("c"), Ok("c".to_string()));
+    assert_eq!(con.cached_entries(), 2);
+    con.clear_cache();
+    assert_eq!(con.cached_entries(), 0);
+}
+
+#[test]
+fn test_tracking_commands() {
+    use redis::caching::{TrackingInfo, TrackingMode, TrackingOptions};
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    let version: Vec<u32> = version.split('.').map(|n| n.parse().unwrap()).collect();
+    // CLIENT TRACKINGINFO needs redis 6.2
+    if version < vec![6, 2] {
+        return;
+    }
+
+    let mut receiver = ctx.connection();
+    let receiver_id: i64 = redis::cmd("CLIENT").arg("ID").query(&mut receiver).unwrap();
+
+    let info: TrackingInfo = con.client_trackinginfo().unwrap();
+    assert!(!info.is_enabled());
+    assert_eq!(info.redirect, -1);
+
+    let options = TrackingOptions {
+        redirect: Some(receiver_id),
+        bcast: true,
+        prefixes: vec![b"user:".to_vec(), b"session:".to_vec()],
+        noloop: true,
+        ..Default::default()
+    };
+    let _: () = con.client_tracking(&options).unwrap();
+    let info: TrackingInfo = con.client_trackinginfo().unwrap();
+    assert!(info.is_enabled());
+    assert!(info.has_flag("bcast"));
+    assert!(info.has_flag("noloop"));
+    assert_eq!(info.redirect, receiver_id);
+    let mut prefixes = info.prefixes.clone();
+    prefixes.sort();
+    assert_eq!(prefixes, vec![b"session:".to_vec(), b"user:".to_vec()]);
+
+    let _: () = con.client_tracking_off().unwrap();
+    let options = TrackingOptions {
+        mode: TrackingMode::OptIn,
+        ..Default::default()
+    };
+    let _: () = con.client_tracking(&options).unwrap();
+    let _: () = con.client_caching(true).unwrap();
+    let info: TrackingInfo = con.client_trackinginfo().unwrap();
+    assert!(info.has_flag("optin"));
+    assert!(info.has_flag("caching-yes"));
+    assert_eq!(info.redirect, 0);
+
+    // OPTIN and OPTOUT are exclusive
+    let _: () = con.client_tracking_off().unwrap();
+    let options = TrackingOptions {
+        mode: TrackingMode::OptOut,
+        ..Default::default()
+    };
+    let _: () = con.client_tracking(&options).unwrap();
+    assert!(con.client_caching::<()>(true).is_err());
+}
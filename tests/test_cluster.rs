@@ -0,0 +1,485 @@
+extern crate redis;
+
+use redis::cluster::{
+    hash_tag, key_slot, tagged_key, ClusterConfig, ClusterInfo, ClusterNodesReply,
+    ClusterShardsReply, ClusterSlotsReply, ReadFrom, ScanOptions,
+};
+use redis::Commands;
+
+use std::collections::HashSet;
+use std::thread::sleep;
+use std::time::Duration;
+
+use support::*;
+
+mod support;
+
+#[test]
+fn test_cluster_basics() {
+    let ctx = TestClusterContext::new();
+    let mut con = ctx.connection();
+
+    // keys in different slots end up on different nodes
+    for i in 0..20 {
+        let _: () = con.set(format!("key{}", i), i).unwrap();
+    }
+    for i in 0..20 {
+        assert_eq!(con.get(format!("key{}", i)), Ok(i));
+    }
+    let mut total = 0;
+    for port in ctx.cluster.ports() {
+        let mut node = ctx.cluster.node_connection(port);
+        let size: usize = redis::cmd("DBSIZE").query(&mut node).unwrap();
+        assert!(size < 20);
+        total += size;
+    }
+    assert_eq!(total, 20);
+
+    redis::cmd("FLUSHALL").execute(&mut con);
+    assert_eq!(con.get("key1"), Ok(None::<i32>));
+}
+
+#[test]
+fn test_cluster_hash_tags_and_streams() {
+    let ctx = TestClusterContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = con.set("{user1}.name", "Ada").unwrap();
+    let _: () = con.set("{user1}.lang", "Rust").unwrap();
+    assert_eq!(
+        con.get(&["{user1}.name", "{user1}.lang"][..]),
+        Ok(("Ada".to_string(), "Rust".to_string()))
+    );
+
+    let _: String = redis::cmd("XADD")
+        .arg("stream")
+        .arg("*")
+        .arg("field")
+        .arg("value")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(redis::cmd("XLEN").arg("stream").query(&mut con), Ok(1));
+    let _: () = redis::cmd("XGROUP")
+        .arg("CREATE")
+        .arg("stream")
+        .arg("group")
+        .arg("0")
+        .query(&mut con)
+        .unwrap();
+    let reply: redis::Value = redis::cmd("XREAD")
+        .arg("COUNT")
+        .arg(1)
+        .arg("STREAMS")
+        .arg("stream")
+        .arg("0")
+        .query(&mut con)
+        .unwrap();
+    assert!(reply != redis::Value::Nil);
+}
+
+#[test]
+fn test_cluster_pipeline() {
+    let ctx = TestClusterContext::new();
+    let mut con = ctx.connection();
+
+    let mut pipe = redis::pipe();
+    for i in 0..10 {
+        pipe.cmd("SET").arg(format!("key{}", i)).arg(i).ignore();
+    }
+    for i in 0..10 {
+        pipe.cmd("GET").arg(format!("key{}", i));
+    }
+    pipe.cmd("PING");
+    let result: Vec<redis::Value> = pipe.query(&mut con).unwrap();
+    let values: Vec<i32> = result[..10]
+        .iter()
+        .map(|v| redis::from_redis_value(v).unwrap())
+        .collect();
+    let pong: String = redis::from_redis_value(&result[10]).unwrap();
+    assert_eq!(values, (0..10).collect::<Vec<_>>());
+    assert_eq!(pong, "PONG");
+
+    let (a, b): (i32, i32) = redis::pipe()
+        .atomic()
+        .cmd("INCR")
+        .arg("{tx}.a")
+        .cmd("INCRBY")
+        .arg("{tx}.b")
+        .arg(2)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!((a, b), (1, 2));
+}
+
+#[test]
+fn test_cluster_cross_slot() {
+    let ctx = TestClusterContext::new();
+    let mut con = ctx.connection();
+
+    let result: redis::RedisResult<()> = redis::cmd("MSET")
+        .arg("key1")
+        .arg(1)
+        .arg("key2")
+        .arg(2)
+        .query(&mut con);
+    assert_eq!(result.unwrap_err().kind(), redis::ErrorKind::CrossSlot);
+
+    let result: redis::RedisResult<(i32, i32)> = redis::pipe()
+        .atomic()
+        .cmd("INCR")
+        .arg("key1")
+        .cmd("INCR")
+        .arg("key2")
+        .query(&mut con);
+    assert_eq!(result.unwrap_err().kind(), redis::ErrorKind::CrossSlot);
+}
+
+#[test]
+fn test_cluster_follows_moved() {
+    let ctx = TestClusterContext::new();
+    let mut con = ctx.connection();
+    let ports = ctx.cluster.ports();
+
+    // "foo" hashes to slot 12182, which the last node serves; hand it to
+    // the first node behind the back of the connection
+    let mut first = ctx.cluster.node_connection(ports[0]);
+    let id: String = redis::cmd("CLUSTER").arg("MYID").query(&mut first).unwrap();
+    for &port in &ports {
+        let mut node = ctx.cluster.node_connection(port);
+        redis::cmd("CLUSTER")
+            .arg("SETSLOT")
+            .arg(12182)
+            .arg("NODE")
+            .arg(&id)
+            .execute(&mut node);
+    }
+
+    let _: () = con.set("foo", 42).unwrap();
+    assert_eq!(redis::cmd("GET").arg("foo").query(&mut first), Ok(42));
+    assert_eq!(con.get("foo"), Ok(42));
+}
+
+#[test]
+fn test_cluster_read_from_replicas() {
+    let ctx = TestClusterContext::with_replicas(
+        1,
+        ClusterConfig {
+            read_from: ReadFrom::ReplicaPreferred,
+            ..Default::default()
+        },
+    );
+    let mut con = ctx.connection();
+
+    let _: () = con.set("foo", 42).unwrap();
+    // replication is asynchronous
+    let mut value: Option<i32> = con.get("foo").unwrap();
+    for _ in 0..100 {
+        if value.is_some() {
+            break;
+        }
+        sleep(Duration::from_millis(10));
+        value = con.get("foo").unwrap();
+    }
+    assert_eq!(value, Some(42));
+
+    // the reads went to a replica, the write to a master
+    let gets = |port| {
+        let mut node = ctx.cluster.node_connection(port);
+        let stats: String = redis::cmd("INFO")
+            .arg("commandstats")
+            .query(&mut node)
+            .unwrap();
+        stats.contains("cmdstat_get:")
+    };
+    assert!(ctx.cluster.replica_ports().into_iter().any(&gets));
+    assert!(!ctx.cluster.master_ports().into_iter().any(&gets));
+
+    // writes are not affected
+    let _: () = con.set("foo", 43).unwrap();
+    assert_eq!(con.incr("foo", 1), Ok(44));
+}
+
+#[test]
+fn test_cluster_key_slot() {
+    let ctx = TestClusterContext::new();
+    let mut con = ctx.connection();
+
+    for key in &[
+        "foo",
+        "{user1}.name",
+        "{}foo",
+        "foo{}{bar}",
+        "{{bar}}",
+        "\u{e9}t\u{e9}",
+    ] {
+        assert_eq!(con.cluster_keyslot(*key), Ok(key_slot(key)));
+    }
+    assert_eq!(key_slot("123456789"), 12739);
+
+    assert_eq!(hash_tag(b"{user1}.name"), Some(&b"user1"[..]));
+    assert_eq!(hash_tag(b"foo{}{bar}"), None);
+    assert_eq!(hash_tag(b"{{bar}}"), Some(&b"{bar"[..]));
+    assert_eq!(hash_tag(b"foo"), None);
+
+    let key = tagged_key("user1", ".email");
+    assert_eq!(key, "{user1}.email");
+    assert_eq!(key_slot(&key), key_slot("{user1}.name"));
+}
+
+#[test]
+fn test_cluster_scan() {
+    let ctx = TestClusterContext::new();
+    let mut con = ctx.connection();
+
+    for i in 0..100 {
+        let _: () = con.set(format!("key{}", i), i).unwrap();
+    }
+    let _: () = con.set("other", 1).unwrap();
+
+    let keys: HashSet<String> = con
+        .scan_keys(ScanOptions::default())
+        .collect::<redis::RedisResult<_>>()
+        .unwrap();
+    assert_eq!(keys.len(), 101);
+
+    let options = ScanOptions {
+        pattern: Some("key*".into()),
+        count: Some(5),
+        ..Default::default()
+    };
+    let keys: Vec<String> = con
+        .scan_keys(options)
+        .collect::<redis::RedisResult<_>>()
+        .unwrap();
+    let unique: HashSet<&String> = keys.iter().collect();
+    assert_eq!(keys.len(), 100);
+    assert_eq!(unique.len(), 100);
+    assert!(!unique.contains(&"other".to_string()));
+
+    // keys are binary safe
+    let _: () = con.set(&b"bin\xff"[..], 1).unwrap();
+    let options = ScanOptions {
+        pattern: Some(b"bin\xff*".to_vec()),
+        ..Default::default()
+    };
+    let keys: Vec<Vec<u8>> = con
+        .scan_keys(options)
+        .collect::<redis::RedisResult<_>>()
+        .unwrap();
+    assert_eq!(keys, vec![b"bin\xff".to_vec()]);
+}
+
+#[test]
+fn test_cluster_typed_replies() {
+    let ctx = TestClusterContext::new();
+    let ports = ctx.cluster.ports();
+    let mut con = ctx.cluster.node_connection(ports[0]);
+
+    let info: ClusterInfo = con.cluster_info().unwrap();
+    assert!(info.state_ok);
+    assert_eq!(info.slots_assigned, 16384);
+    assert_eq!(info.known_nodes, 3);
+    assert_eq!(info.size, 3);
+    assert_eq!(info.fields.get("cluster_state"), Some(&"ok".to_string()));
+
+    let slots: ClusterSlotsReply = con.cluster_slots().unwrap();
+    assert_eq!(slots.ranges.len(), 3);
+    let total: usize = slots
+        .ranges
+        .iter()
+        .map(|range| usize::from(range.end - range.start) + 1)
+        .sum();
+    assert_eq!(total, 16384);
+    for range in &slots.ranges {
+        assert!(ports.contains(&range.master.port));
+        assert!(range.replicas.is_empty());
+    }
+
+    let nodes: ClusterNodesReply = con.cluster_nodes().unwrap();
+    assert_eq!(nodes.nodes.len(), 3);
+    let myself = nodes.myself().unwrap();
+    assert_eq!(myself.port, ports[0]);
+    assert!(myself.is_master());
+    assert_eq!(myself.master_id, None);
+    assert_eq!(myself.slots, vec![(0, 5460)]);
+
+    // "foo" is served by the last node
+    let mut last = ctx.cluster.node_connection(ports[2]);
+    let _: () = last.set("foo", 1).unwrap();
+    assert_eq!(last.cluster_countkeysinslot(12182), Ok(1));
+
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    if version.split('.').next().unwrap().parse::<u32>().unwrap() >= 7 {
+        let shards: ClusterShardsReply = con.cluster_shards().unwrap();
+        assert_eq!(shards.shards.len(), 3);
+        for shard in &shards.shards {
+            assert_eq!(shard.nodes.len(), 1);
+            assert_eq!(shard.nodes[0].role, "master");
+        }
+    }
+}
+
+// Publishes until the subscriber receives a matching message, since
+// subscriptions are made in the background.
+fn publish_until_received<F: Fn(&redis::Msg) -> bool>(
+    con: &mut redis::cluster::ClusterConnection,
+    pubsub: &mut redis::cluster::ClusterPubSub,
+    command: &str,
+    channel: &str,
+    accept: F,
+) -> redis::Msg {
+    pubsub.set_read_timeout(Some(Duration::from_millis(100)));
+    for _ in 0..50 {
+        redis::cmd(command).arg(channel).arg("hello").execute(con);
+        loop {
+            match pubsub.get_message() {
+                Ok(ref msg) if !accept(msg) => {}
+                Ok(msg) => return msg,
+                Err(ref err) if err.is_timeout() => break,
+                Err(err) => panic!("{}", err),
+            }
+        }
+    }
+    panic!("no message received");
+}
+
+#[test]
+fn test_cluster_pubsub() {
+    let ctx = TestClusterContext::new();
+    let mut con = ctx.connection();
+    let mut pubsub = ctx.client.get_pubsub().unwrap();
+
+    pubsub.subscribe("news").unwrap();
+    let msg = publish_until_received(&mut con, &mut pubsub, "PUBLISH", "news", |_| true);
+    assert_eq!(msg.get_channel_name(), "news");
+    assert_eq!(msg.get_payload(), Ok("hello".to_string()));
+
+    pubsub.unsubscribe("news").unwrap();
+    pubsub.psubscribe("new*").unwrap();
+    let msg = publish_until_received(&mut con, &mut pubsub, "PUBLISH", "news", |msg| {
+        msg.from_pattern()
+    });
+    assert_eq!(msg.get_pattern(), Ok("new*".to_string()));
+
+    let server: redis::InfoDict = redis::cmd("INFO").arg("server").query(&mut con).unwrap();
+    let version: String = server.get("redis_version").unwrap();
+    if version.split('.').next().unwrap().parse::<u32>().unwrap() < 7 {
+        return;
+    }
+    pubsub.punsubscribe("new*").unwrap();
+    // shard channels in slots of different nodes
+    for channel in &["{user1}:events", "{user2}:events"] {
+        pubsub.ssubscribe(*channel).unwrap();
+        let msg = publish_until_received(&mut con, &mut pubsub, "SPUBLISH", channel, |msg| {
+            msg.get_channel_name() == *channel
+        });
+        assert!(!msg.from_pattern());
+        assert!(msg.from_shard_channel());
+    }
+
+    let mut channels: Vec<String> = con.shard_channels(None).unwrap();
+    channels.sort();
+    assert_eq!(channels, vec!["{user1}:events", "{user2}:events"]);
+    assert_eq!(
+        con.pubsub_shardnumsub("{user2}:events"),
+        Ok(vec![("{user2}:events".to_string(), 1)])
+    );
+    assert_eq!(con.spublish("{user1}:events", "direct"), Ok(1));
+}
+
+#[test]
+fn test_cluster_script() {
+    let ctx = TestClusterContext::new();
+    let mut con = ctx.connection();
+
+    let script = redis::Script::new(
+        r"
+        redis.call('SET', KEYS[1], ARGV[1])
+        return redis.call('INCRBY', KEYS[2], ARGV[1])
+    ",
+    );
+    assert_eq!(
+        script
+            .key("{user1}.a")
+            .key("{user1}.b")
+            .arg(2)
+            .invoke(&mut con),
+        Ok(2)
+    );
+    assert_eq!(con.get("{user1}.a"), Ok(2));
+
+    // the script is loaded again on the node that needs it
+    redis::cmd("SCRIPT").arg("FLUSH").execute(&mut con);
+    assert_eq!(
+        script
+            .key("{user1}.a")
+            .key("{user1}.b")
+            .arg(3)
+            .invoke(&mut con),
+        Ok(5)
+    );
+
+    let err = script
+        .key("a")
+        .key("b")
+        .arg(1)
+        .invoke::<i32>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.kind(), redis::ErrorKind::CrossSlot);
+
+    let keyless = redis::Script::new("return ARGV[1]");
+    assert_eq!(keyless.arg(42).invoke(&mut con), Ok(42));
+}
+
+#[test]
+fn test_cluster_last_node() {
+    use redis::middleware::{Command, InterceptedConnection, Interceptor};
+    use redis::{ConnectionLike, RedisResult, Value};
+    use std::sync::{Arc, Mutex};
+
+    struct Nodes(Arc<Mutex<Vec<Option<String>>>>);
+
+    impl Interceptor for Nodes {
+        fn after(&mut self, command: &Command, _: Duration, _: &RedisResult<Value>) {
+            let node = command.node().map(|node| node.to_string());
+            self.0.lock().unwrap().push(node);
+        }
+    }
+
+    let ctx = TestClusterContext::new();
+    let nodes = Arc::new(Mutex::new(vec![]));
+    let mut con = InterceptedConnection::new(ctx.connection()).with(Nodes(nodes.clone()));
+
+    for i in 0..10 {
+        let _: () = con.set(format!("key{}", i), i).unwrap();
+        let node = nodes.lock().unwrap().pop().unwrap().unwrap();
+        assert_eq!(con.last_node(), Some(&node[..]));
+
+        // the key is stored on the node that served the command
+        let port: u16 = node[node.rfind(':').unwrap() + 1..].parse().unwrap();
+        let mut node = ctx.cluster.node_connection(port);
+        let exists: bool = redis::cmd("EXISTS")
+            .arg(format!("key{}", i))
+            .query(&mut node)
+            .unwrap();
+        assert!(exists);
+    }
+}
+
+#[test]
+fn test_cluster_failover() {
+    let ctx = TestClusterContext::with_replicas(1, ClusterConfig::default());
+    let replica_port = ctx.cluster.replica_ports()[0];
+    let mut replica = ctx.cluster.node_connection(replica_port);
+
+    let _: () = replica.cluster_failover().unwrap();
+    for _ in 0..100 {
+        let nodes: ClusterNodesReply = replica.cluster_nodes().unwrap();
+        if nodes.myself().unwrap().is_master() {
+            return;
+        }
+        sleep(Duration::from_millis(50));
+    }
+    panic!("the replica did not take over");
+}
@@ -0,0 +1,220 @@
+#[cfg(feature = "tokio-comp")]
+extern crate futures;
+extern crate redis;
+
+use std::collections::HashMap;
+
+use redis::mock::{server_error, MockConnection};
+use redis::streams::{StreamRangeReply, StreamReadReply};
+use redis::{cmd, Commands, ErrorKind, PipelineCommands, Value};
+
+#[test]
+fn test_scripted_replies() {
+    let mut con = MockConnection::new()
+        .expect(cmd("SET").arg("greeting").arg("hello"), Ok(Value::Okay))
+        .expect(
+            cmd("GET").arg("greeting"),
+            Ok(Value::Data(b"hello".to_vec())),
+        )
+        .expect(
+            cmd("LPUSH").arg("greeting").arg(1),
+            Err(server_error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value",
+            )),
+        );
+
+    let _: () = con.set("greeting", "hello").unwrap();
+    assert!(!con.is_done());
+    assert_eq!(con.get("greeting"), Ok("hello".to_string()));
+    let err = con.lpush::<_, _, i64>("greeting", 1).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::WrongType);
+    assert!(con.is_done());
+    assert_eq!(
+        con.received(),
+        vec![
+            vec![b"SET".to_vec(), b"greeting".to_vec(), b"hello".to_vec()],
+            vec![b"GET".to_vec(), b"greeting".to_vec()],
+            vec![b"LPUSH".to_vec(), b"greeting".to_vec(), b"1".to_vec()],
+        ]
+    );
+}
+
+#[test]
+fn test_scripted_pipeline() {
+    let mut con = MockConnection::new()
+        .expect(cmd("INCRBY").arg("visits").arg(1), Ok(Value::Int(1)))
+        .expect(cmd("EXPIRE").arg("visits").arg(60), Ok(Value::Int(1)))
+        .expect(cmd("GET").arg("visits"), Ok(Value::Data(b"1".to_vec())));
+
+    let (visits,): (u64,) = redis::pipe()
+        .atomic()
+        .incr("visits", 1)
+        .ignore()
+        .expire("visits", 60)
+        .ignore()
+        .get("visits")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(visits, 1);
+    assert!(con.is_done());
+    assert_eq!(con.received().len(), 5);
+}
+
+#[test]
+#[should_panic(expected = "MockConnection expected `GET foo` but received `GET bar`")]
+fn test_scripted_unexpected_command() {
+    let mut con = MockConnection::new().expect(cmd("GET").arg("foo"), Ok(Value::Nil));
+    let _: Option<String> = con.get("bar").unwrap();
+}
+
+#[test]
+fn test_in_memory_strings() {
+    let mut con = MockConnection::in_memory();
+
+    let _: () = con.set("key", "value").unwrap();
+    assert_eq!(con.get("key"), Ok("value".to_string()));
+    assert_eq!(con.get("missing"), Ok(None::<String>));
+    assert_eq!(con.incr("counter", 5), Ok(5));
+    assert_eq!(con.incr("counter", -2), Ok(3));
+    assert_eq!(
+        con.incr::<_, _, i64>("key", 1).unwrap_err().kind(),
+        ErrorKind::ResponseError
+    );
+    assert_eq!(con.exists("key"), Ok(true));
+    assert_eq!(con.del("key"), Ok(1));
+    assert_eq!(con.exists("key"), Ok(false));
+
+    let _: () = con.set_ex("expiring", 1, 100).unwrap();
+    let ttl: i64 = con.ttl("expiring").unwrap();
+    assert!(ttl > 90 && ttl <= 100);
+    assert_eq!(con.ttl("counter"), Ok(-1));
+    assert_eq!(con.ttl("missing"), Ok(-2));
+    let _: () = cmd("PSETEX")
+        .arg("expired")
+        .arg(1)
+        .arg(1)
+        .query(&mut con)
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    assert_eq!(con.exists("expired"), Ok(false));
+
+    let mut keys: Vec<String> = con.scan_match("ex*").unwrap().collect();
+    keys.sort();
+    assert_eq!(keys, vec!["expiring".to_string()]);
+}
+
+#[test]
+fn test_in_memory_collections() {
+    let mut con = MockConnection::in_memory();
+
+    assert_eq!(con.hset_multiple("hash", &[("a", 1), ("b", 2)]), Ok(()));
+    assert_eq!(con.hget("hash", "b"), Ok(2));
+    let hash: HashMap<String, i64> = con.hgetall("hash").unwrap();
+    assert_eq!(hash.len(), 2);
+    assert_eq!(con.hdel("hash", &["a", "b"]), Ok(2));
+    assert_eq!(con.exists("hash"), Ok(false));
+
+    assert_eq!(con.rpush("list", &[1, 2, 3]), Ok(3));
+    assert_eq!(con.lpush("list", 0), Ok(4));
+    assert_eq!(con.lrange("list", 1, -1), Ok(vec![1, 2, 3]));
+    assert_eq!(con.lpop("list"), Ok(0));
+
+    assert_eq!(con.sadd("set", &["a", "b", "a"]), Ok(2));
+    assert_eq!(con.sismember("set", "b"), Ok(true));
+    assert_eq!(
+        con.smembers("set"),
+        Ok(vec!["a".to_string(), "b".to_string()])
+    );
+
+    let err = con.sadd::<_, _, i64>("list", "a").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::WrongType);
+}
+
+#[test]
+fn test_in_memory_streams() {
+    let mut con = MockConnection::in_memory();
+
+    for i in 1..4 {
+        let _: String = cmd("XADD")
+            .arg("events")
+            .arg(format!("{}-0", i))
+            .arg("n")
+            .arg(i)
+            .query(&mut con)
+            .unwrap();
+    }
+    let id: String = cmd("XADD")
+        .arg("events")
+        .arg("*")
+        .arg("n")
+        .arg(4)
+        .query(&mut con)
+        .unwrap();
+    assert!(id.ends_with("-0"));
+    let err = cmd("XADD")
+        .arg("events")
+        .arg("2-0")
+        .arg("n")
+        .arg(5)
+        .query::<String>(&mut con)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ResponseError);
+
+    let page: StreamRangeReply = con.xrange_after("events", "1-0", 2).unwrap();
+    let ids: Vec<&str> = page.ids.iter().map(|entry| &entry.id[..]).collect();
+    assert_eq!(ids, vec!["2-0", "3-0"]);
+    assert_eq!(page.ids[0].get("n"), Some(2));
+
+    let read: StreamReadReply = cmd("XREAD")
+        .arg("COUNT")
+        .arg(10)
+        .arg("STREAMS")
+        .arg("events")
+        .arg("3-0")
+        .query(&mut con)
+        .unwrap();
+    assert_eq!(read.keys.len(), 1);
+    assert_eq!(read.keys[0].ids.len(), 1);
+    assert_eq!(read.keys[0].ids[0].id, id);
+}
+
+#[test]
+fn test_in_memory_transaction_and_injected_error() {
+    let mut con = MockConnection::in_memory().expect(
+        cmd("GET").arg("flaky"),
+        Err(server_error(
+            "LOADING Redis is loading the dataset in memory",
+        )),
+    );
+
+    let (a, b): (i64, i64) = redis::pipe()
+        .atomic()
+        .incr("a", 1)
+        .incr("b", 2)
+        .query(&mut con)
+        .unwrap();
+    assert_eq!((a, b), (1, 2));
+
+    let err = con.get::<_, Option<String>>("flaky").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BusyLoadingError);
+    assert_eq!(con.get("flaky"), Ok(None::<String>));
+
+    let err = cmd("NOSUCHCOMMAND").query::<()>(&mut con).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ResponseError);
+}
+
+#[test]
+#[cfg(feature = "tokio-comp")]
+fn test_mock_async() {
+    use futures::Future;
+
+    let con = MockConnection::in_memory();
+    let (con, ()) = cmd("SET")
+        .arg("key")
+        .arg(42)
+        .query_async(con)
+        .wait()
+        .unwrap();
+    let (_, value): (MockConnection, i64) = cmd("GET").arg("key").query_async(con).wait().unwrap();
+    assert_eq!(value, 42);
+}
@@ -0,0 +1,219 @@
+extern crate redis;
+
+extern crate futures;
+extern crate tokio;
+
+use redis::Commands;
+
+use std::thread;
+use std::time::Duration;
+
+use support::*;
+
+mod support;
+
+#[test]
+fn test_pool_reuses_connections() {
+    let ctx = TestContext::new();
+    let pool = ctx.client.get_pool(2, 1).unwrap();
+
+    {
+        let mut con = pool.get().unwrap();
+        assert_eq!(con.set("key", 42), Ok(()));
+    }
+    {
+        let mut con = pool.get().unwrap();
+        assert_eq!(con.get("key"), Ok(42));
+    }
+}
+
+#[test]
+fn test_pool_checkout_timeout() {
+    let ctx = TestContext::new();
+    let pool = ctx.client.get_pool(1, 0).unwrap();
+
+    let con = pool.get().unwrap();
+    let err = pool
+        .get_timeout(Duration::from_millis(10))
+        .err()
+        .expect("checkout should time out");
+    assert!(err.is_timeout());
+
+    drop(con);
+    assert!(pool.get_timeout(Duration::from_millis(10)).is_ok());
+}
+
+#[test]
+fn test_pool_shared_between_threads() {
+    let ctx = TestContext::new();
+    let pool = ctx.client.get_pool(4, 0).unwrap();
+
+    let handles: Vec<_> = (0..8)
+        .map(|i| {
+            let pool = pool.clone();
+            thread::spawn(move || {
+                let mut con = pool.get().unwrap();
+                let _: () = con.set(format!("key{}", i), i).unwrap();
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let mut con = pool.get().unwrap();
+    for i in 0..8 {
+        assert_eq!(con.get(format!("key{}", i)), Ok(i));
+    }
+}
+
+#[test]
+fn test_pool_health_check_replaces_dead_connections() {
+    use redis::pool::{HealthCheck, Pool, PoolConfig};
+
+    let ctx = TestContext::new();
+    let pool = Pool::new(
+        ctx.client.clone(),
+        PoolConfig {
+            max_size: 1,
+            min_idle: 1,
+            health_check: HealthCheck::Always,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    // kill the idle pooled connection behind the pool's back
+    let mut killer = ctx.connection();
+    redis::cmd("CLIENT")
+        .arg("KILL")
+        .arg("TYPE")
+        .arg("normal")
+        .arg("SKIPME")
+        .arg("yes")
+        .execute(&mut killer);
+
+    let mut con = pool.get().unwrap();
+    assert_eq!(con.set("key", 1), Ok(()));
+}
+
+#[test]
+fn test_pool_status_and_max_lifetime() {
+    use redis::pool::{Pool, PoolConfig};
+
+    let ctx = TestContext::new();
+    let pool = Pool::new(
+        ctx.client.clone(),
+        PoolConfig {
+            max_size: 2,
+            min_idle: 1,
+            max_lifetime: Some(Duration::from_millis(100)),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    {
+        let con = pool.get().unwrap();
+        let status = pool.status();
+        assert_eq!(status.connections, 1);
+        assert_eq!(status.in_use, 1);
+        assert_eq!(status.idle_connections, 0);
+        drop(con);
+    }
+    assert_eq!(pool.status().idle_connections, 1);
+
+    // the idle connection is too old and gets replaced on checkout
+    thread::sleep(Duration::from_millis(150));
+    let mut con = pool.get().unwrap();
+    assert_eq!(con.set("key", 1), Ok(()));
+
+    let status = pool.status();
+    assert_eq!(status.connections, 1);
+    assert_eq!(status.connections_created, 2);
+    assert_eq!(status.connections_closed, 1);
+    assert_eq!(status.checkouts, 2);
+}
+
+#[test]
+fn test_pool_status_failed_open() {
+    use redis::pool::{Pool, PoolConfig};
+
+    // nothing listens on port 1, so opening a connection fails
+    let client = redis::Client::open("redis://127.0.0.1:1/").unwrap();
+    let pool = Pool::new(
+        client,
+        PoolConfig {
+            max_size: 1,
+            min_idle: 0,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert!(pool.get().is_err());
+    let status = pool.status();
+    assert_eq!(status.connections, 0);
+    assert_eq!(status.connections_created, 0);
+    assert_eq!(status.connections_closed, 0);
+}
+
+#[cfg(feature = "tokio-comp")]
+#[test]
+fn test_async_pool() {
+    use futures::Future;
+    use tokio::runtime::current_thread::Runtime;
+
+    let ctx = TestContext::new();
+    let mut rt = Runtime::new().unwrap();
+    let pool = rt.block_on(ctx.client.get_async_pool(1, 1)).unwrap();
+
+    let con = rt.block_on(pool.get()).unwrap();
+    let (con, ()) = rt
+        .block_on(redis::cmd("SET").arg("key").arg(42).query_async(con))
+        .unwrap();
+    let err = rt
+        .block_on(pool.get_timeout(Duration::from_millis(10)))
+        .err()
+        .expect("checkout should time out");
+    assert!(err.is_timeout());
+
+    drop(con);
+    let value = rt.block_on(
+        pool.get()
+            .and_then(|con| redis::cmd("GET").arg("key").query_async(con))
+            .map(|(_, value): (_, i32)| value),
+    );
+    assert_eq!(value, Ok(42));
+
+    let status = pool.status();
+    assert_eq!(status.connections_created, 1);
+    assert_eq!(status.checkouts, 3);
+    assert_eq!(status.checkout_timeouts, 1);
+}
+
+#[cfg(feature = "tokio-comp")]
+#[test]
+fn test_async_pool_status_failed_open() {
+    use redis::pool::{AsyncPool, PoolConfig};
+    use tokio::runtime::current_thread::Runtime;
+
+    // nothing listens on port 1, so opening a connection fails
+    let client = redis::Client::open("redis://127.0.0.1:1/").unwrap();
+    let mut rt = Runtime::new().unwrap();
+    let pool = rt
+        .block_on(AsyncPool::new(
+            client,
+            PoolConfig {
+                max_size: 1,
+                min_idle: 0,
+                ..Default::default()
+            },
+        ))
+        .unwrap();
+
+    assert!(rt.block_on(pool.get()).is_err());
+    let status = pool.status();
+    assert_eq!(status.connections, 0);
+    assert_eq!(status.connections_created, 0);
+}
@@ -0,0 +1,130 @@
+extern crate redis;
+
+use redis::search::{
+    AggregateOptions, AggregateResult, CreateOptions, IndexInfo, Reducer, Schema, SearchOptions,
+    SearchResult,
+};
+use redis::{Commands, Connection};
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use support::*;
+
+mod support;
+
+// The tests are skipped when the server does not have the module.
+fn has_search(con: &mut Connection) -> bool {
+    redis::cmd("FT._LIST").query::<redis::Value>(con).is_ok()
+}
+
+fn create_movies(con: &mut Connection) {
+    let schema = Schema::new()
+        .text("title")
+        .weight(2.0)
+        .tag("genre")
+        .numeric("year")
+        .sortable();
+    let options = CreateOptions::new().prefix("movie:");
+    let _: () = con.ft_create("movies", &options, &schema).unwrap();
+
+    let movies = [
+        ("movie:1", "The Love Boat", "drama", "1977"),
+        ("movie:2", "Love Actually", "comedy", "2003"),
+        ("movie:3", "Casablanca", "drama", "1942"),
+    ];
+    for &(key, title, genre, year) in &movies {
+        let _: () = con
+            .hset_multiple(key, &[("title", title), ("genre", genre), ("year", year)])
+            .unwrap();
+    }
+
+    // indexing is asynchronous
+    for _ in 0..50 {
+        let info: IndexInfo = con.ft_info("movies").unwrap();
+        if !info.indexing && info.num_docs == 3 {
+            return;
+        }
+        sleep(Duration::from_millis(10));
+    }
+    panic!("movies were not indexed");
+}
+
+#[test]
+fn test_search() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    if !has_search(&mut con) {
+        return;
+    }
+    create_movies(&mut con);
+
+    let options = SearchOptions::new().sort_by("year", true);
+    let result: SearchResult = con.ft_search("movies", "love", &options).unwrap();
+    assert_eq!(result.total, 2);
+    let ids: Vec<&str> = result.documents.iter().map(|doc| &doc.id[..]).collect();
+    assert_eq!(ids, vec!["movie:1", "movie:2"]);
+    assert_eq!(result.documents[0].fields["title"], "The Love Boat");
+    assert_eq!(result.documents[0].score, None);
+
+    let options = SearchOptions::new().with_scores().return_fields(&["year"]);
+    let result: SearchResult = con.ft_search("movies", "@genre:{drama}", &options).unwrap();
+    assert_eq!(result.total, 2);
+    for doc in &result.documents {
+        assert!(doc.score.is_some());
+        assert_eq!(doc.fields.len(), 1);
+    }
+
+    let options = SearchOptions::new().no_content().limit(0, 1);
+    let result: SearchResult = con.ft_search("movies", "*", &options).unwrap();
+    assert_eq!(result.total, 3);
+    assert_eq!(result.documents.len(), 1);
+    assert!(result.documents[0].fields.is_empty());
+
+    let options = SearchOptions::new().param("since", "2000").dialect(2);
+    let result: SearchResult = con
+        .ft_search("movies", "@year:[$since +inf]", &options)
+        .unwrap();
+    assert_eq!(result.total, 1);
+    assert_eq!(result.documents[0].id, "movie:2");
+}
+
+#[test]
+fn test_search_aggregate_and_info() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    if !has_search(&mut con) {
+        return;
+    }
+    create_movies(&mut con);
+
+    let options = AggregateOptions::new()
+        .group_by(&["@genre"], vec![Reducer::count().alias("movies")])
+        .sort_by(&[("@movies", false)]);
+    let result: AggregateResult = con.ft_aggregate("movies", "*", &options).unwrap();
+    assert_eq!(result.rows.len(), 2);
+    assert_eq!(
+        result.rows[0]["genre"],
+        redis::Value::Data(b"drama".to_vec())
+    );
+    assert_eq!(result.rows[0]["movies"], redis::Value::Data(b"2".to_vec()));
+
+    let info: IndexInfo = con.ft_info("movies").unwrap();
+    assert_eq!(info.name, "movies");
+    assert_eq!(info.num_docs, 3);
+    assert_eq!(info.hash_indexing_failures, 0);
+    let attributes: Vec<(&str, &str)> = info
+        .attributes
+        .iter()
+        .map(|attr| (&attr.identifier[..], &attr.kind[..]))
+        .collect();
+    assert_eq!(
+        attributes,
+        vec![("title", "TEXT"), ("genre", "TAG"), ("year", "NUMERIC")]
+    );
+    assert!(info.attributes[2].options.contains(&"SORTABLE".to_string()));
+
+    let _: () = con.ft_dropindex("movies", true).unwrap();
+    assert!(con.ft_info::<_, IndexInfo>("movies").is_err());
+    assert_eq!(con.exists("movie:1"), Ok(false));
+}
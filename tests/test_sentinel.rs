@@ -0,0 +1,210 @@
+extern crate net2;
+extern crate rand;
+extern crate redis;
+
+use redis::sentinel::{SentinelConfig, SentinelRole};
+use redis::{ConnectionAddr, ConnectionInfo, IntoConnectionInfo};
+
+use std::env;
+use std::fs;
+use std::process;
+use std::thread::sleep;
+use std::time::Duration;
+
+use support::*;
+
+mod support;
+
+struct Sentinel {
+    process: process::Child,
+    config: String,
+    port: u16,
+}
+
+impl Sentinel {
+    fn new(master: &ConnectionAddr) -> Sentinel {
+        let (host, master_port) = match *master {
+            ConnectionAddr::Tcp(ref host, port) => (host.clone(), port),
+            _ => panic!("sentinels monitor TCP servers only"),
+        };
+        let port = net2::TcpBuilder::new_v4()
+            .unwrap()
+            .bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = env::temp_dir()
+            .join(format!("redis-rs-sentinel-{}.conf", rand::random::<u64>()))
+            .to_string_lossy()
+            .into_owned();
+        fs::write(
+            &config,
+            format!(
+                "port {}\nbind 127.0.0.1\nsentinel monitor mymaster {} {} 1\n",
+                port, host, master_port
+            ),
+        )
+        .unwrap();
+        let process = process::Command::new("redis-server")
+            .arg(&config)
+            .arg("--sentinel")
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::null())
+            .spawn()
+            .unwrap();
+        Sentinel {
+            process: process,
+            config: config,
+            port: port,
+        }
+    }
+
+    fn addr(&self) -> ConnectionAddr {
+        ConnectionAddr::Tcp("127.0.0.1".to_string(), self.port)
+    }
+}
+
+impl Drop for Sentinel {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_file(&self.config);
+    }
+}
+
+fn sentinel_info(sentinel: &Sentinel, role: SentinelRole) -> ConnectionInfo {
+    ConnectionInfo {
+        addr: Box::new(ConnectionAddr::Sentinel(SentinelConfig {
+            sentinels: vec![
+                ConnectionAddr::Tcp("127.0.0.1".to_string(), 1),
+                sentinel.addr(),
+            ],
+            service_name: "mymaster".to_string(),
+            role: role,
+            passwd: None,
+        })),
+        ..("redis://127.0.0.1/").into_connection_info().unwrap()
+    }
+}
+
+#[test]
+fn test_sentinel_master() {
+    let ctx = TestContext::new();
+    let master = ctx.server.get_client_addr().clone();
+    if let ConnectionAddr::Unix(_) = master {
+        return;
+    }
+    let sentinel = Sentinel::new(&master);
+    let info = sentinel_info(&sentinel, SentinelRole::Master);
+
+    let config = match *info.addr {
+        ConnectionAddr::Sentinel(ref config) => config.clone(),
+        _ => unreachable!(),
+    };
+    let mut addr = config.master_addr(&info);
+    for _ in 0..100 {
+        if addr.is_ok() {
+            break;
+        }
+        sleep(Duration::from_millis(10));
+        addr = config.master_addr(&info);
+    }
+    assert_eq!(addr.unwrap(), master);
+    // no replicas, so the master is used
+    assert_eq!(config.replica_addrs(&info).unwrap(), vec![]);
+
+    let client = redis::Client::open(info).unwrap();
+    let mut con = client.get_connection().unwrap();
+    redis::cmd("SET").arg("foo").arg(42).execute(&mut con);
+    let mut con = ctx.connection();
+    assert_eq!(redis::cmd("GET").arg("foo").query(&mut con), Ok(42));
+
+    for &role in &[SentinelRole::Replica, SentinelRole::Any] {
+        let client = redis::Client::open(sentinel_info(&sentinel, role)).unwrap();
+        let mut con = client.get_connection().unwrap();
+        assert_eq!(redis::cmd("GET").arg("foo").query(&mut con), Ok(42));
+    }
+}
+
+#[test]
+fn test_sentinel_unknown_service() {
+    let ctx = TestContext::new();
+    let master = ctx.server.get_client_addr().clone();
+    if let ConnectionAddr::Unix(_) = master {
+        return;
+    }
+    let sentinel = Sentinel::new(&master);
+    let mut info = sentinel_info(&sentinel, SentinelRole::Master);
+    if let ConnectionAddr::Sentinel(ref mut config) = *info.addr {
+        config.service_name = "unknown".to_string();
+    }
+
+    let mut result = redis::Client::open(info.clone()).unwrap().get_connection();
+    for _ in 0..100 {
+        match result {
+            Err(ref err) if err.is_connection_refusal() => {}
+            _ => break,
+        }
+        sleep(Duration::from_millis(10));
+        result = redis::Client::open(info.clone()).unwrap().get_connection();
+    }
+    assert_eq!(
+        result.err().unwrap().kind(),
+        redis::ErrorKind::InvalidClientConfig
+    );
+}
+
+#[test]
+fn test_sentinel_url() {
+    let info = "redis+sentinel://:secret@sentinel-1,sentinel-2:5000/mymaster/2?role=replica&sentinel_password=hidden&client_name=app"
+        .into_connection_info()
+        .unwrap();
+    assert_eq!(
+        *info.addr,
+        ConnectionAddr::Sentinel(SentinelConfig {
+            sentinels: vec![
+                ConnectionAddr::Tcp("sentinel-1".to_string(), 26379),
+                ConnectionAddr::Tcp("sentinel-2".to_string(), 5000),
+            ],
+            service_name: "mymaster".to_string(),
+            role: SentinelRole::Replica,
+            passwd: Some("hidden".to_string()),
+        })
+    );
+    assert_eq!(info.db, 2);
+    assert_eq!(info.passwd, Some("secret".to_string()));
+    assert_eq!(info.client_name, Some("app".to_string()));
+
+    let info = "rediss+sentinel://sentinel-1/mymaster"
+        .into_connection_info()
+        .unwrap();
+    match *info.addr {
+        ConnectionAddr::Sentinel(ref config) => {
+            assert_eq!(config.role, SentinelRole::Master);
+            assert_eq!(
+                config.sentinels,
+                vec![ConnectionAddr::TcpTls {
+                    host: "sentinel-1".to_string(),
+                    port: 26379,
+                }]
+            );
+        }
+        _ => panic!("not a sentinel address"),
+    }
+
+    let info = "redis+sentinel://sentinel-1/mymaster?role=any"
+        .into_connection_info()
+        .unwrap();
+    match *info.addr {
+        ConnectionAddr::Sentinel(ref config) => assert_eq!(config.role, SentinelRole::Any),
+        _ => panic!("not a sentinel address"),
+    }
+
+    assert!("redis+sentinel://sentinel-1/"
+        .into_connection_info()
+        .is_err());
+    assert!("redis+sentinel://sentinel-1/mymaster?role=leader"
+        .into_connection_info()
+        .is_err());
+}
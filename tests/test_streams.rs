@@ -0,0 +1,119 @@
+extern crate redis;
+
+use redis::streams::{parse_stream_id, StreamFanIn};
+
+use support::*;
+
+mod support;
+
+fn xadd(con: &mut redis::Connection, key: &str, id: &str, value: &str) {
+    redis::cmd("XADD")
+        .arg(key)
+        .arg(id)
+        .arg("value")
+        .arg(value)
+        .execute(con);
+}
+
+#[test]
+fn test_parse_stream_id() {
+    assert_eq!(
+        parse_stream_id("1526919030474-55"),
+        Some((1526919030474, 55))
+    );
+    assert_eq!(parse_stream_id("1526919030474"), Some((1526919030474, 0)));
+    assert_eq!(parse_stream_id("foo-1"), None);
+}
+
+#[test]
+fn test_stream_fan_in() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    xadd(&mut con, "shard:0", "1-0", "a");
+    xadd(&mut con, "shard:1", "2-0", "b");
+    xadd(&mut con, "shard:0", "3-0", "c");
+    xadd(&mut con, "shard:1", "3-1", "d");
+    xadd(&mut con, "shard:1", "5-0", "e");
+
+    let mut fan_in = StreamFanIn::new(&mut con, &["shard:0", "shard:1"], "0")
+        .unwrap()
+        .count(1);
+    let mut seen = vec![];
+    while let Some((key, entry)) = fan_in.next_entry().unwrap() {
+        seen.push((key, entry.get::<String>("value").unwrap()));
+    }
+
+    assert_eq!(
+        seen,
+        vec![
+            ("shard:0".to_string(), "a".to_string()),
+            ("shard:1".to_string(), "b".to_string()),
+            ("shard:0".to_string(), "c".to_string()),
+            ("shard:1".to_string(), "d".to_string()),
+            ("shard:1".to_string(), "e".to_string()),
+        ]
+    );
+    assert_eq!(
+        fan_in.cursors(),
+        vec![("shard:0", "3-0"), ("shard:1", "5-0")]
+    );
+}
+
+#[test]
+fn test_stream_fan_in_resume() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    xadd(&mut con, "shard:0", "1-0", "a");
+    xadd(&mut con, "shard:1", "2-0", "b");
+
+    let cursors = vec![
+        ("shard:0".to_string(), "1-0".to_string()),
+        ("shard:1".to_string(), "0".to_string()),
+    ];
+    let ids: Vec<String> = StreamFanIn::with_cursors(&mut con, cursors)
+        .unwrap()
+        .map(|entry| entry.unwrap().1.id)
+        .collect();
+    assert_eq!(ids, vec!["2-0".to_string()]);
+}
+
+#[test]
+fn test_stream_fan_in_duplicate_keys() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let err = StreamFanIn::new(&mut con, &["shard:0", "shard:1", "shard:0"], "0")
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), redis::ErrorKind::InvalidClientConfig);
+}
+
+#[test]
+fn test_xrange_after_pagination() {
+    use redis::streams::StreamRangeReply;
+    use redis::Commands;
+
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    for (id, value) in &[("1-0", "a"), ("2-0", "b"), ("3-0", "c")] {
+        xadd(&mut con, "stream", id, value);
+    }
+
+    let page: StreamRangeReply = con.xrange_count("stream", "-", "+", 2).unwrap();
+    assert_eq!(page.ids.len(), 2);
+    assert_eq!(page.last_id(), Some("2-0"));
+
+    let page: StreamRangeReply = con.xrange_after("stream", "2-0", 2).unwrap();
+    assert_eq!(page.ids.len(), 1);
+    assert_eq!(page.ids[0].id, "3-0");
+
+    let page: StreamRangeReply = con.xrange_after("stream", "3-0", 2).unwrap();
+    assert_eq!(page.ids.len(), 0);
+
+    let page: StreamRangeReply = con.xrevrange_before("stream", "3-0", 5).unwrap();
+    let ids: Vec<&str> = page.ids.iter().map(|entry| &entry.id[..]).collect();
+    assert_eq!(ids, vec!["2-0", "1-0"]);
+}
@@ -0,0 +1,57 @@
+extern crate redis;
+
+use redis::test_support::{RedisServer, ServerType, TestContext};
+use redis::Commands;
+
+#[test]
+fn test_context() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    assert_eq!(con.incr("visits", 1), Ok(1));
+
+    // every context has a server of its own
+    let other = TestContext::new();
+    assert_eq!(other.connection().exists("visits"), Ok(false));
+}
+
+#[test]
+fn test_server_with_args() {
+    let server_type = ServerType::from_env();
+    let server =
+        RedisServer::with_args(server_type, &["--maxmemory-policy", "allkeys-lru"]).unwrap();
+    let mut ctx = TestContext::with_server(server);
+
+    let policy: (String, String) = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("maxmemory-policy")
+        .query(&mut ctx.connection())
+        .unwrap();
+    assert_eq!(policy.1, "allkeys-lru");
+
+    ctx.stop_server();
+    assert!(ctx.client.get_connection().is_err());
+}
+
+#[test]
+fn test_debug_commands() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+
+    let _: () = con.set("key", "value").unwrap();
+    let object: redis::test_support::DebugObject = match con.debug_object("key") {
+        Ok(object) => object,
+        // redis 7 without enable-debug-command
+        Err(_) => return,
+    };
+    assert_eq!(object.refcount, 1);
+    assert_eq!(object.encoding, "embstr");
+
+    let start = std::time::Instant::now();
+    let _: () = con.debug_sleep(0.1).unwrap();
+    assert!(start.elapsed() >= std::time::Duration::from_millis(100));
+
+    let _: () = con.debug_set_active_expire(false).unwrap();
+    let _: () = con.debug_reload().unwrap();
+    assert_eq!(con.get("key"), Ok("value".to_string()));
+    let _: () = con.debug_set_active_expire(true).unwrap();
+}
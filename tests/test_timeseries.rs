@@ -0,0 +1,109 @@
+extern crate redis;
+
+use redis::timeseries::{
+    Aggregation, DuplicatePolicy, MRangeOptions, RangeBound, RangeOptions, Sample, Series,
+    SeriesOptions, Timestamp,
+};
+use redis::{Commands, Connection};
+
+use support::*;
+
+mod support;
+
+// The tests are skipped when the server does not have the module.
+fn has_timeseries(con: &mut Connection) -> bool {
+    con.ts_create::<_, ()>("probe", &SeriesOptions::new())
+        .is_ok()
+}
+
+fn sample(timestamp: i64, value: f64) -> Sample {
+    Sample { timestamp, value }
+}
+
+#[test]
+fn test_timeseries_add_and_range() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    if !has_timeseries(&mut con) {
+        return;
+    }
+
+    let options = SeriesOptions::new()
+        .retention(60_000)
+        .duplicate_policy(DuplicatePolicy::Block)
+        .label("room", "kitchen");
+    let _: () = con.ts_create("temp", &options).unwrap();
+    assert_eq!(con.ts_add("temp", Timestamp::At(1000), 20.0), Ok(1000));
+    assert!(con
+        .ts_add::<_, i64>("temp", Timestamp::At(1000), 21.0)
+        .is_err());
+    let last = SeriesOptions::new().on_duplicate(DuplicatePolicy::Last);
+    assert_eq!(
+        con.ts_add_options("temp", Timestamp::At(1000), 21.0, &last),
+        Ok(1000)
+    );
+    assert_eq!(
+        con.ts_madd(&[
+            ("temp", Timestamp::At(2000), 22.0),
+            ("temp", Timestamp::At(3000), 26.0),
+        ]),
+        Ok(vec![2000, 3000])
+    );
+
+    let all = RangeOptions::new();
+    let samples: Vec<Sample> = con
+        .ts_range("temp", RangeBound::Earliest, RangeBound::Latest, &all)
+        .unwrap();
+    assert_eq!(
+        samples,
+        vec![sample(1000, 21.0), sample(2000, 22.0), sample(3000, 26.0)]
+    );
+
+    let options = RangeOptions::new().filter_by_value(21.5, 30.0).count(1);
+    let samples: Vec<Sample> = con
+        .ts_revrange("temp", RangeBound::At(0), RangeBound::Latest, &options)
+        .unwrap();
+    assert_eq!(samples, vec![sample(3000, 26.0)]);
+
+    let options = RangeOptions::new().aggregation(Aggregation::Avg, 2000);
+    let samples: Vec<Sample> = con
+        .ts_range("temp", RangeBound::Earliest, RangeBound::Latest, &options)
+        .unwrap();
+    assert_eq!(samples, vec![sample(0, 21.0), sample(2000, 24.0)]);
+}
+
+#[test]
+fn test_timeseries_mrange() {
+    let ctx = TestContext::new();
+    let mut con = ctx.connection();
+    if !has_timeseries(&mut con) {
+        return;
+    }
+
+    for &(key, room, value) in &[("temp:1", "kitchen", 20.0), ("temp:2", "hall", 18.0)] {
+        let options = SeriesOptions::new()
+            .label("type", "temp")
+            .label("room", room);
+        let _: () = con.ts_create(key, &options).unwrap();
+        let _: i64 = con.ts_add(key, Timestamp::At(1000), value).unwrap();
+    }
+
+    let options = MRangeOptions::new(&["type=temp", "room=kitchen"]).with_labels();
+    let series: Vec<Series> = con
+        .ts_mrange(RangeBound::Earliest, RangeBound::Latest, &options)
+        .unwrap();
+    assert_eq!(series.len(), 1);
+    assert_eq!(series[0].key, "temp:1");
+    assert_eq!(series[0].labels["room"], "kitchen");
+    assert_eq!(series[0].samples, vec![sample(1000, 20.0)]);
+
+    let options = MRangeOptions::new(&["type=temp"])
+        .range(RangeOptions::new().aggregation(Aggregation::Max, 1000))
+        .group_by("type", Aggregation::Sum);
+    let series: Vec<Series> = con
+        .ts_mrange(RangeBound::Earliest, RangeBound::Latest, &options)
+        .unwrap();
+    assert_eq!(series.len(), 1);
+    assert_eq!(series[0].key, "type=temp");
+    assert_eq!(series[0].samples, vec![sample(1000, 38.0)]);
+}
@@ -1,5 +1,14 @@
+#[cfg(feature = "with-chrono")]
+extern crate chrono;
 extern crate fnv;
 extern crate redis;
+#[cfg(feature = "with-serde")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "with-time")]
+extern crate time;
+#[cfg(feature = "with-uuid")]
+extern crate uuid;
 
 #[test]
 fn test_is_single_arg() {
@@ -35,6 +44,59 @@ fn test_info_dict() {
     assert_eq!(d.get::<String>("key3"), None);
 }
 
+#[test]
+fn test_info_typed() {
+    use redis::info::{Info, KeyspaceInfo, ReplicaInfo};
+    use redis::{FromRedisValue, Value};
+    use std::time::Duration;
+
+    let text = "# Server\r\nredis_version:7.2.4\r\nredis_mode:standalone\r\nos:Linux\r\n\
+                process_id:42\r\nrun_id:abc\r\ntcp_port:6379\r\nuptime_in_seconds:120\r\n\r\n\
+                # Replication\r\nrole:master\r\nconnected_slaves:1\r\n\
+                slave0:ip=127.0.0.1,port=6380,state=online,offset=1234,lag=0\r\n\
+                master_repl_offset:1234\r\n\r\n\
+                # Stats\r\ntotal_commands_processed:99\r\n\r\n\
+                # Keyspace\r\ndb0:keys=3,expires=1,avg_ttl=5000\r\ndb2:keys=1,expires=0,avg_ttl=0\r\n";
+    let info: Info = FromRedisValue::from_redis_value(&Value::Data(text.into())).unwrap();
+
+    let server = info.server.as_ref().unwrap();
+    assert_eq!(server.redis_version, "7.2.4");
+    assert_eq!(server.redis_mode, Some("standalone".to_string()));
+    assert_eq!(server.tcp_port, 6379);
+    assert_eq!(server.uptime, Duration::from_secs(120));
+    assert_eq!(info.memory, None);
+
+    let replication = info.replication.as_ref().unwrap();
+    assert_eq!(replication.role, "master");
+    assert_eq!(replication.master_host, None);
+    assert_eq!(
+        replication.replicas,
+        vec![ReplicaInfo {
+            ip: "127.0.0.1".to_string(),
+            port: 6380,
+            state: "online".to_string(),
+            offset: 1234,
+            lag: Some(0),
+        }]
+    );
+
+    assert_eq!(
+        info.keyspace.get(&0),
+        Some(&KeyspaceInfo {
+            keys: 3,
+            expires: 1,
+            avg_ttl: Duration::from_millis(5000),
+        })
+    );
+    assert_eq!(info.keyspace.keys().collect::<Vec<_>>(), vec![&0, &2]);
+    assert_eq!(info.get("total_commands_processed"), Some("99"));
+    assert_eq!(info.sections["stats"].len(), 1);
+
+    let info: Result<Info, _> =
+        FromRedisValue::from_redis_value(&Value::Data("# Server\r\nredis_version:7\r\n".into()));
+    assert!(info.is_err());
+}
+
 #[test]
 fn test_i32() {
     use redis::{ErrorKind, FromRedisValue, Value};
@@ -76,6 +138,27 @@ fn test_vec() {
     assert_eq!(v, Ok(vec![1i32, 2, 3]));
 }
 
+#[test]
+fn test_tuple() {
+    use redis::{ErrorKind, FromRedisValue, Value};
+
+    let value = Value::Bulk(vec![Value::Data("foo".into()), Value::Nil, Value::Int(3)]);
+    let v: (String, Option<String>, i64) = FromRedisValue::from_redis_value(&value).unwrap();
+    assert_eq!(v, ("foo".to_string(), None, 3));
+
+    // the error names the element that failed to convert
+    let err = <(String, String, i64)>::from_redis_value(&value).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TypeError);
+    assert!(err.to_string().contains("element 1 of 3"), "{}", err);
+
+    let err = <(String, i64)>::from_redis_value(&value).unwrap_err();
+    assert!(err.to_string().contains("expected 2 elements"), "{}", err);
+
+    // an aborted transaction
+    let v: Option<(String, i64)> = FromRedisValue::from_redis_value(&Value::Nil).unwrap();
+    assert_eq!(v, None);
+}
+
 #[test]
 fn test_hashmap() {
     use fnv::FnvHasher;
@@ -142,6 +225,80 @@ fn test_bool() {
 
     let v = FromRedisValue::from_redis_value(&Value::Int(42));
     assert_eq!(v, Ok(true));
+
+    let v = FromRedisValue::from_redis_value(&Value::Boolean(true));
+    assert_eq!(v, Ok(true));
+}
+
+#[test]
+fn test_resp3_values() {
+    use redis::{ErrorKind, FromRedisValue, Value};
+    use std::collections::{BTreeMap, HashMap, HashSet};
+
+    let map = Value::Map(vec![
+        (Value::Data(b"a".to_vec()), Value::Int(1)),
+        (Value::Data(b"b".to_vec()), Value::Double(2.0)),
+    ]);
+    let v: HashMap<String, i64> = FromRedisValue::from_redis_value(&map).unwrap();
+    assert_eq!(v["a"], 1);
+    assert_eq!(v["b"], 2);
+    let v: BTreeMap<String, f64> = FromRedisValue::from_redis_value(&map).unwrap();
+    assert_eq!(v["b"], 2.0);
+    // flattened like the RESP2 reply
+    let v: Vec<String> = FromRedisValue::from_redis_value(&map).unwrap();
+    assert_eq!(v, vec!["a", "1", "b", "2"]);
+    let v: Vec<(String, i64)> = FromRedisValue::from_redis_value(&map).unwrap();
+    assert_eq!(v, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+
+    let set = Value::Set(vec![Value::Int(1), Value::Int(2)]);
+    let v: HashSet<i64> = FromRedisValue::from_redis_value(&set).unwrap();
+    assert_eq!(v.len(), 2);
+    let v: Vec<i64> = FromRedisValue::from_redis_value(&set).unwrap();
+    assert_eq!(v, vec![1, 2]);
+
+    assert_eq!(
+        FromRedisValue::from_redis_value(&Value::Double(1.5)),
+        Ok(1.5f64)
+    );
+    assert_eq!(
+        FromRedisValue::from_redis_value(&Value::Double(3.0)),
+        Ok(3i64)
+    );
+    let v: Result<i64, _> = FromRedisValue::from_redis_value(&Value::Double(1.5));
+    assert_eq!(v.unwrap_err().kind(), ErrorKind::TypeError);
+    assert_eq!(
+        FromRedisValue::from_redis_value(&Value::Double(1.5)),
+        Ok("1.5".to_string())
+    );
+
+    let big = Value::BigNumber("3492890328409238509324850943850943825024385".into());
+    let v: Result<i64, _> = FromRedisValue::from_redis_value(&big);
+    assert_eq!(v.unwrap_err().kind(), ErrorKind::TypeError);
+    assert_eq!(
+        FromRedisValue::from_redis_value(&big),
+        Ok("3492890328409238509324850943850943825024385".to_string())
+    );
+
+    let verbatim = Value::VerbatimString {
+        format: "txt".into(),
+        text: "some text".into(),
+    };
+    assert_eq!(
+        FromRedisValue::from_redis_value(&verbatim),
+        Ok("some text".to_string())
+    );
+    assert_eq!(
+        FromRedisValue::from_redis_value(&verbatim),
+        Ok(b"some text".to_vec())
+    );
+
+    // RESP3 nests the pairs of `ZRANGE WITHSCORES`
+    let pairs = Value::Bulk(vec![
+        Value::Bulk(vec![Value::Data(b"one".to_vec()), Value::Double(1.0)]),
+        Value::Bulk(vec![Value::Data(b"two".to_vec()), Value::Double(2.5)]),
+    ]);
+    let v: Vec<(String, f64)> = FromRedisValue::from_redis_value(&pairs).unwrap();
+    assert_eq!(v, vec![("one".to_string(), 1.0), ("two".to_string(), 2.5)]);
 }
 
 #[test]
@@ -187,3 +344,318 @@ fn test_types_to_redis_args() {
             > 0
     );
 }
+
+#[test]
+fn test_arg_buffer() {
+    use redis::{ArgBuffer, ToRedisArgs};
+
+    let mut args = ArgBuffer::with_capacity(4, 32);
+    ("key", 42, 1.5f64, &["a", "b"][..]).write_redis_args(&mut args);
+    assert_eq!(args.len(), 5);
+    assert_eq!(
+        args.iter().collect::<Vec<_>>(),
+        vec![&b"key"[..], &b"42"[..], &b"1.5"[..], &b"a"[..], &b"b"[..]]
+    );
+    assert!(!(&args).is_single_arg());
+
+    // the arguments can be passed on as they are
+    assert_eq!(
+        redis::cmd("MGET").arg(&args).get_packed_command(),
+        redis::cmd("MGET")
+            .arg("key")
+            .arg(42)
+            .arg(1.5f64)
+            .arg(&["a", "b"])
+            .get_packed_command()
+    );
+
+    args.clear();
+    assert!(args.is_empty());
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_serde_json() {
+    use redis::serde_json::{self, json};
+    use redis::{FromRedisValue, ToRedisArgs, Value};
+
+    let doc = json!({"name": "redis", "tags": ["fast", "kv"], "stars": 5});
+    let args = doc.to_redis_args();
+    assert_eq!(args.len(), 1);
+    let stored = Value::Data(args[0].clone());
+    assert_eq!(
+        serde_json::Value::from_redis_value(&stored),
+        Ok(doc.clone())
+    );
+    assert_eq!(
+        serde_json::Value::from_redis_value(&Value::Data(b"{oops".to_vec()))
+            .unwrap_err()
+            .kind(),
+        redis::ErrorKind::TypeError
+    );
+
+    let reply = Value::Bulk(vec![
+        Value::Int(1),
+        Value::Data(b"two".to_vec()),
+        Value::Nil,
+        Value::Map(vec![(Value::Data(b"ok".to_vec()), Value::Boolean(true))]),
+        Value::Double(::std::f64::NAN),
+    ]);
+    assert_eq!(
+        serde_json::Value::from(&reply),
+        json!([1, "two", null, {"ok": true}, null])
+    );
+
+    assert_eq!(
+        Value::from(json!({"list": [1, 2.5, "x", null]})),
+        Value::Map(vec![(
+            Value::Data(b"list".to_vec()),
+            Value::Bulk(vec![
+                Value::Int(1),
+                Value::Double(2.5),
+                Value::Data(b"x".to_vec()),
+                Value::Nil,
+            ]),
+        )])
+    );
+}
+
+#[cfg(feature = "with-chrono")]
+#[test]
+fn test_chrono() {
+    use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+    use redis::datetime::{from_expire_time, from_pttl, Rfc3339, UnixMillis};
+    use redis::{FromRedisValue, ToRedisArgs, Value};
+
+    let ts = Utc.ymd(2019, 5, 4).and_hms_milli(12, 30, 0, 250);
+    assert_eq!(
+        ts.to_redis_args(),
+        vec![b"2019-05-04T12:30:00.250Z".to_vec()]
+    );
+    assert_eq!(
+        UnixMillis(ts).to_redis_args(),
+        vec![b"1556973000250".to_vec()]
+    );
+
+    // plain timestamps are read from either format
+    let rfc3339 = Value::Data(b"2019-05-04T14:30:00.250+02:00".to_vec());
+    let millis = Value::Int(1_556_973_000_250);
+    assert_eq!(DateTime::<Utc>::from_redis_value(&rfc3339), Ok(ts));
+    assert_eq!(DateTime::<Utc>::from_redis_value(&millis), Ok(ts));
+    assert_eq!(NaiveDateTime::from_redis_value(&millis), Ok(ts.naive_utc()));
+    assert_eq!(
+        UnixMillis::<DateTime<Utc>>::from_redis_value(&Value::Double(1_556_973_000_250.0)),
+        Ok(UnixMillis(ts))
+    );
+    assert!(UnixMillis::<DateTime<Utc>>::from_redis_value(&rfc3339).is_err());
+    assert!(Rfc3339::<DateTime<Utc>>::from_redis_value(&millis).is_err());
+
+    assert_eq!(
+        from_expire_time(1_556_973_000),
+        Some(Utc.ymd(2019, 5, 4).and_hms(12, 30, 0))
+    );
+    assert_eq!(from_expire_time::<DateTime<Utc>>(-1), None);
+    assert_eq!(from_pttl::<DateTime<Utc>>(-2), None);
+    let expires: DateTime<Utc> = from_pttl(60_000).unwrap();
+    assert!(expires > Utc::now());
+}
+
+#[cfg(feature = "with-time")]
+#[test]
+fn test_time() {
+    use redis::datetime::UnixMillis;
+    use redis::{FromRedisValue, ToRedisArgs, Value};
+    use time::OffsetDateTime;
+
+    let ts = OffsetDateTime::from_unix_timestamp(1_556_973_000).unwrap();
+    assert_eq!(ts.to_redis_args(), vec![b"2019-05-04T12:30:00Z".to_vec()]);
+    assert_eq!(
+        UnixMillis(ts).to_redis_args(),
+        vec![b"1556973000000".to_vec()]
+    );
+    let value = Value::Data(b"2019-05-04T12:30:00Z".to_vec());
+    assert_eq!(OffsetDateTime::from_redis_value(&value), Ok(ts));
+    assert_eq!(
+        OffsetDateTime::from_redis_value(&Value::Int(1_556_973_000_000)),
+        Ok(ts)
+    );
+}
+
+#[test]
+fn test_std_types() {
+    use redis::{FromRedisValue, ToRedisArgs, Value};
+    use std::net::{IpAddr, SocketAddr};
+    use std::path::PathBuf;
+
+    let ip: IpAddr = "::1".parse().unwrap();
+    assert_eq!(ip.to_redis_args(), vec![b"::1".to_vec()]);
+    assert_eq!(
+        IpAddr::from_redis_value(&Value::Data(b"::1".to_vec())),
+        Ok(ip)
+    );
+    let addr: SocketAddr = "127.0.0.1:6379".parse().unwrap();
+    assert_eq!(
+        SocketAddr::from_redis_value(&Value::Data(addr.to_redis_args()[0].clone())),
+        Ok(addr)
+    );
+    assert!(IpAddr::from_redis_value(&Value::Data(b"localhost".to_vec())).is_err());
+
+    let path = PathBuf::from("/tmp/dump.rdb");
+    assert_eq!(path.to_redis_args(), vec![b"/tmp/dump.rdb".to_vec()]);
+    assert_eq!(
+        PathBuf::from_redis_value(&Value::Data(b"/tmp/dump.rdb".to_vec())),
+        Ok(path)
+    );
+}
+
+#[cfg(feature = "with-nonzero")]
+#[test]
+fn test_non_zero() {
+    use redis::{FromRedisValue, ToRedisArgs, Value};
+    use std::num::NonZeroU64;
+
+    let n = NonZeroU64::new(42).unwrap();
+    assert_eq!(n.to_redis_args(), vec![b"42".to_vec()]);
+    assert_eq!(NonZeroU64::from_redis_value(&Value::Int(42)), Ok(n));
+    assert!(NonZeroU64::from_redis_value(&Value::Int(0)).is_err());
+}
+
+#[cfg(feature = "with-uuid")]
+#[test]
+fn test_uuid() {
+    use redis::{FromRedisValue, ToRedisArgs, Value};
+    use uuid::Uuid;
+
+    let text = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+    let id = Uuid::parse_str(text).unwrap();
+    assert_eq!(id.to_redis_args(), vec![text.as_bytes().to_vec()]);
+    assert_eq!(
+        Uuid::from_redis_value(&Value::Data(text.as_bytes().to_vec())),
+        Ok(id)
+    );
+    assert_eq!(
+        Uuid::from_redis_value(&Value::Data(id.as_bytes().to_vec())),
+        Ok(id)
+    );
+    assert!(Uuid::from_redis_value(&Value::Int(1)).is_err());
+}
+
+#[cfg(feature = "with-serde")]
+#[test]
+fn test_codecs() {
+    use redis::{FromRedisValue, ToRedisArgs, Value};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Job {
+        id: u64,
+        tags: Vec<String>,
+        retry: Option<u8>,
+    }
+
+    fn roundtrip<T: ToRedisArgs + FromRedisValue>(value: T) -> T {
+        let args = value.to_redis_args();
+        assert_eq!(args.len(), 1);
+        T::from_redis_value(&Value::Data(args[0].clone())).unwrap()
+    }
+
+    let job = Job {
+        id: 7,
+        tags: vec!["a".to_string(), "b".to_string()],
+        retry: None,
+    };
+
+    #[cfg(feature = "json")]
+    {
+        use redis::codec::{Codec, Encoded, Json, JsonCodec};
+        use std::collections::HashMap;
+
+        assert_eq!(roundtrip(Json(job.clone())), Json(job.clone()));
+        assert_eq!(
+            Json(&job).to_redis_args(),
+            vec![br#"{"id":7,"tags":["a","b"],"retry":null}"#.to_vec()]
+        );
+        let encoded: Encoded<JsonCodec, Job> = Encoded::new(job.clone());
+        assert_eq!(roundtrip(encoded).into_inner(), job);
+        assert_eq!(
+            Json::<Job>::from_redis_value(&Value::Data(b"{}".to_vec()))
+                .unwrap_err()
+                .kind(),
+            redis::ErrorKind::TypeError
+        );
+        assert!(Json::<Job>::from_redis_value(&Value::Int(1)).is_err());
+        let mut bad = HashMap::new();
+        bad.insert(vec![1], 1);
+        assert!(JsonCodec::encode(&bad).is_err());
+        assert_eq!(
+            Json(&bad).encode().unwrap_err().kind(),
+            redis::ErrorKind::TypeError
+        );
+        assert_eq!(
+            Json(&job).encode(),
+            Ok(br#"{"id":7,"tags":["a","b"],"retry":null}"#.to_vec())
+        );
+    }
+
+    #[cfg(feature = "with-msgpack")]
+    {
+        use redis::codec::MsgPack;
+
+        assert_eq!(roundtrip(MsgPack(job.clone())), MsgPack(job.clone()));
+        let items = vec![MsgPack(1u32), MsgPack(2u32)];
+        assert_eq!(items.to_redis_args().len(), 2);
+    }
+
+    #[cfg(feature = "with-bincode")]
+    {
+        use redis::codec::{Bincode, BincodeCodec, Codec};
+
+        assert_eq!(roundtrip(Bincode(job.clone())), Bincode(job.clone()));
+        assert_eq!(BincodeCodec::encode(&7u32), Ok(vec![7, 0, 0, 0]));
+        assert!(BincodeCodec::decode::<u64>(&[1, 2]).is_err());
+    }
+}
+
+#[cfg(any(feature = "with-lz4", feature = "with-zstd"))]
+#[test]
+fn test_compression() {
+    use redis::compression::{decompress, is_compressed, Compressed, Compression};
+    use redis::{FromRedisValue, ToRedisArgs, Value};
+
+    let small = "tiny".to_string();
+    assert_eq!(Compressed(&small).to_redis_args(), vec![b"tiny".to_vec()]);
+
+    let large = "abcdefgh".repeat(1000);
+    let args = Compressed(&large).to_redis_args();
+    assert_eq!(args.len(), 1);
+    assert!(is_compressed(&args[0]));
+    assert!(args[0].len() < large.len());
+    assert_eq!(decompress(&args[0]), Ok(large.clone().into_bytes()));
+
+    let reply = Value::Bulk(vec![
+        Value::Data(args[0].clone()),
+        Value::Data(b"plain".to_vec()),
+        Value::Nil,
+    ]);
+    assert_eq!(
+        Compressed::<Vec<Option<String>>>::from_redis_value(&reply),
+        Ok(Compressed(vec![
+            Some(large.clone()),
+            Some("plain".to_string()),
+            None
+        ]))
+    );
+
+    // values that do not get smaller are stored as they are
+    let compression = Compression {
+        threshold: 0,
+        ..Compression::default()
+    };
+    assert_eq!(compression.compress(b"xyz"), b"xyz".to_vec());
+
+    let mut corrupt = args[0].clone();
+    corrupt.truncate(12);
+    assert_eq!(
+        decompress(&corrupt).unwrap_err().kind(),
+        redis::ErrorKind::TypeError
+    );
+}
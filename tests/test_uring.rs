@@ -0,0 +1,74 @@
+#![cfg(target_os = "linux")]
+extern crate redis;
+
+extern crate futures;
+extern crate tokio;
+
+use futures::Future;
+
+use support::*;
+
+use redis::{ConnectionInfo, IntoConnectionInfo};
+
+use tokio::runtime::current_thread::Runtime;
+
+mod support;
+
+fn connection_info(ctx: &TestContext) -> ConnectionInfo {
+    ConnectionInfo {
+        addr: Box::new(ctx.server.get_client_addr().clone()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_uring_args() {
+    let ctx = TestContext::new();
+    let mut rt = Runtime::new().unwrap();
+
+    let result = rt.block_on(
+        redis::uring::connect(connection_info(&ctx)).and_then(|con| {
+            redis::cmd("SET")
+                .arg("key1")
+                .arg(b"foo")
+                .query_async(con)
+                .and_then(|(con, ())| redis::cmd("GET").arg("key1").query_async(con))
+                .map(|(_, value): (_, String)| value)
+        }),
+    );
+    assert_eq!(result, Ok("foo".to_string()));
+}
+
+#[test]
+fn test_uring_large_value() {
+    let ctx = TestContext::new();
+    let mut rt = Runtime::new().unwrap();
+
+    // larger than the buffers of the stream so that it takes several
+    // sends and receives
+    let value = vec![b'x'; 300 * 1024];
+    let result = rt.block_on(redis::uring::connect(connection_info(&ctx)).and_then({
+        let value = value.clone();
+        move |con| {
+            redis::cmd("SET")
+                .arg("key")
+                .arg(value)
+                .query_async(con)
+                .and_then(|(con, ())| redis::cmd("GET").arg("key").query_async(con))
+                .map(|(_, value): (_, Vec<u8>)| value)
+        }
+    }));
+    assert_eq!(result, Ok(value));
+}
+
+#[test]
+fn test_uring_connection_refused() {
+    let info = "redis://127.0.0.1:1/".into_connection_info().unwrap();
+    let mut rt = Runtime::new().unwrap();
+
+    let err = rt
+        .block_on(redis::uring::connect(info))
+        .err()
+        .expect("connect should fail");
+    assert!(err.is_connection_refusal());
+}